@@ -12,19 +12,36 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{error::Error, result::Result};
+use std::{error::Error, path::PathBuf, result::Result};
+
+/// The releases whose protos are kept under `golden/`, the current protos must be wire compatible
+/// with them, see `tests/proto_compat_test.rs`.
+const GOLDEN_RELEASES: &[&str] = &["v0.4.0"];
 
 fn main() -> Result<(), Box<dyn Error>> {
     std::env::set_var("PROTOC", protoc_build::PROTOC);
     std::env::set_var("PROTOC_INCLUDE", protoc_build::PROTOC_INCLUDE);
 
-    tonic_build::configure().compile(
-        &[
-            "engula/v1/engula.proto",
-            "engula/server/v1/node.proto",
-            "engula/server/v1/root.proto",
-        ],
-        &["."],
-    )?;
+    let protos = [
+        "engula/v1/engula.proto",
+        "engula/server/v1/node.proto",
+        "engula/server/v1/root.proto",
+    ];
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("engula_descriptor.bin"))
+        .compile(&protos, &["."])?;
+
+    // Only the descriptor sets of the golden protos are used.
+    for release in GOLDEN_RELEASES {
+        let golden_out_dir = out_dir.join("golden").join(release);
+        std::fs::create_dir_all(&golden_out_dir)?;
+        tonic_build::configure()
+            .build_client(false)
+            .build_server(false)
+            .out_dir(&golden_out_dir)
+            .file_descriptor_set_path(out_dir.join(format!("golden_{release}_descriptor.bin")))
+            .compile(&protos, &[format!("golden/{release}")])?;
+    }
     Ok(())
 }
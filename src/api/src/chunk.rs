@@ -0,0 +1,84 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The layout of large values, which are split into chunks by clients. Each chunk is saved in a
+//! chunk key, and the user key saves a manifest record which references the chunks by the write
+//! id, which is the unix timestamp in nanoseconds of the write. Servers only need the layout to
+//! route the chunks and to reclaim the orphan chunks.
+
+/// The magic prefix of manifest records.
+pub const MANIFEST_MAGIC: &[u8] = b"\x00ENGULA\xCC";
+
+/// The length of manifest records: the magic, the write id, the number of chunks, the total
+/// length and the checksum of the value.
+pub const MANIFEST_LEN: usize = MANIFEST_MAGIC.len() + 8 + 4 + 8 + 4;
+
+/// The separator between the user key and the suffix of chunk keys.
+const CHUNK_KEY_SEPARATOR: &[u8] = b"\x00__chunk__";
+
+/// The length of the suffix of chunk keys: the separator, the write id and the chunk index.
+const CHUNK_KEY_SUFFIX_LEN: usize = CHUNK_KEY_SEPARATOR.len() + 8 + 4;
+
+/// Whether the value looks like a manifest record.
+#[inline]
+pub fn is_manifest(value: &[u8]) -> bool {
+    value.starts_with(MANIFEST_MAGIC)
+}
+
+/// Return the write id of the manifest record, `None` is returned if the value isn't a manifest.
+pub fn manifest_write_id(value: &[u8]) -> Option<u64> {
+    if value.len() != MANIFEST_LEN || !is_manifest(value) {
+        return None;
+    }
+    let buf = &value[MANIFEST_MAGIC.len()..];
+    Some(u64::from_be_bytes(buf[..8].try_into().unwrap()))
+}
+
+pub fn chunk_key(key: &[u8], write_id: u64, idx: u32) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(key.len() + CHUNK_KEY_SUFFIX_LEN);
+    buf.extend_from_slice(key);
+    buf.extend_from_slice(CHUNK_KEY_SEPARATOR);
+    buf.extend_from_slice(&write_id.to_be_bytes());
+    buf.extend_from_slice(&idx.to_be_bytes());
+    buf
+}
+
+/// Parse the user key and the write id of a chunk key, `None` is returned if the key isn't a
+/// chunk key.
+pub fn parse_chunk_key(key: &[u8]) -> Option<(&[u8], u64)> {
+    if key.len() < CHUNK_KEY_SUFFIX_LEN {
+        return None;
+    }
+    let (user_key, suffix) = key.split_at(key.len() - CHUNK_KEY_SUFFIX_LEN);
+    let write_id = suffix.strip_prefix(CHUNK_KEY_SEPARATOR)?;
+    Some((
+        user_key,
+        u64::from_be_bytes(write_id[..8].try_into().unwrap()),
+    ))
+}
+
+/// Whether the key is a chunk key of a large value, which should be hidden from users.
+#[inline]
+pub fn is_chunk_key(key: &[u8]) -> bool {
+    parse_chunk_key(key).is_some()
+}
+
+/// Return the key used to compute the hash slot. The chunk keys are hashed by the user key, so
+/// that the chunks of hash partitioned collections are saved in the same shard as the manifest.
+#[inline]
+pub fn routing_key(key: &[u8]) -> &[u8] {
+    parse_chunk_key(key)
+        .map(|(user_key, _)| user_key)
+        .unwrap_or(key)
+}
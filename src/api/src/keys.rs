@@ -0,0 +1,237 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Order-preserving encodings for composite keys.
+//!
+//! The bytewise order of the encoded keys is the same as the order of the values, so that
+//! structured keys still work with range partitions and prefix scans. Each `put_*` appends a
+//! component to the buffer, and the corresponding `get_*` consumes it from the front of the
+//! input, `None` is returned if the input is malformed.
+//!
+//! The `*_desc` variants encode the component in reverse order.
+
+const ESCAPE: u8 = 0x00;
+const ESCAPED_00: u8 = 0xFF;
+const TERMINATOR: u8 = 0x01;
+
+const SIGN_MASK: u64 = 1 << 63;
+
+/// Append a `u64` in big endian.
+#[inline]
+pub fn put_u64(buf: &mut Vec<u8>, v: u64) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+/// Append a `u64` in reverse order.
+#[inline]
+pub fn put_u64_desc(buf: &mut Vec<u8>, v: u64) {
+    put_u64(buf, !v);
+}
+
+/// Append an `i64`, the sign bit is flipped so negative values sort before positive ones.
+#[inline]
+pub fn put_i64(buf: &mut Vec<u8>, v: i64) {
+    put_u64(buf, v as u64 ^ SIGN_MASK);
+}
+
+/// Append an `i64` in reverse order.
+#[inline]
+pub fn put_i64_desc(buf: &mut Vec<u8>, v: i64) {
+    put_u64_desc(buf, v as u64 ^ SIGN_MASK);
+}
+
+/// Append a byte string, `0x00` is escaped as `0x00 0xFF` and the string is terminated by
+/// `0x00 0x01`, so a string always sorts before the ones it is a prefix of.
+pub fn put_bytes(buf: &mut Vec<u8>, v: &[u8]) {
+    for &b in v {
+        buf.push(b);
+        if b == ESCAPE {
+            buf.push(ESCAPED_00);
+        }
+    }
+    buf.push(ESCAPE);
+    buf.push(TERMINATOR);
+}
+
+/// Append a byte string in reverse order.
+pub fn put_bytes_desc(buf: &mut Vec<u8>, v: &[u8]) {
+    let start = buf.len();
+    put_bytes(buf, v);
+    buf[start..].iter_mut().for_each(|b| *b = !*b);
+}
+
+/// Append a string, see [`put_bytes`].
+#[inline]
+pub fn put_str(buf: &mut Vec<u8>, v: &str) {
+    put_bytes(buf, v.as_bytes());
+}
+
+/// Append a string in reverse order.
+#[inline]
+pub fn put_str_desc(buf: &mut Vec<u8>, v: &str) {
+    put_bytes_desc(buf, v.as_bytes());
+}
+
+pub fn get_u64(input: &mut &[u8]) -> Option<u64> {
+    if input.len() < 8 {
+        return None;
+    }
+    let (head, tail) = input.split_at(8);
+    *input = tail;
+    Some(u64::from_be_bytes(head.try_into().unwrap()))
+}
+
+#[inline]
+pub fn get_u64_desc(input: &mut &[u8]) -> Option<u64> {
+    get_u64(input).map(|v| !v)
+}
+
+#[inline]
+pub fn get_i64(input: &mut &[u8]) -> Option<i64> {
+    get_u64(input).map(|v| (v ^ SIGN_MASK) as i64)
+}
+
+#[inline]
+pub fn get_i64_desc(input: &mut &[u8]) -> Option<i64> {
+    get_u64_desc(input).map(|v| (v ^ SIGN_MASK) as i64)
+}
+
+#[inline]
+pub fn get_bytes(input: &mut &[u8]) -> Option<Vec<u8>> {
+    get_escaped_bytes(input, 0)
+}
+
+#[inline]
+pub fn get_bytes_desc(input: &mut &[u8]) -> Option<Vec<u8>> {
+    get_escaped_bytes(input, 0xFF)
+}
+
+pub fn get_str(input: &mut &[u8]) -> Option<String> {
+    get_bytes(input).and_then(|v| String::from_utf8(v).ok())
+}
+
+pub fn get_str_desc(input: &mut &[u8]) -> Option<String> {
+    get_bytes_desc(input).and_then(|v| String::from_utf8(v).ok())
+}
+
+/// Return the smallest key that is larger than all keys prefixed by `prefix`, an empty key
+/// (the end of key space) is returned if there is no such key.
+pub fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_owned();
+    while let Some(b) = end.pop() {
+        if b != 0xFF {
+            end.push(b + 1);
+            return end;
+        }
+    }
+    end
+}
+
+fn get_escaped_bytes(input: &mut &[u8], mask: u8) -> Option<Vec<u8>> {
+    let mut value = Vec::new();
+    let mut i = 0;
+    loop {
+        let b = *input.get(i)? ^ mask;
+        if b != ESCAPE {
+            value.push(b);
+            i += 1;
+            continue;
+        }
+        match *input.get(i + 1)? ^ mask {
+            ESCAPED_00 => value.push(ESCAPE),
+            TERMINATOR => {
+                *input = &input[i + 2..];
+                return Some(value);
+            }
+            _ => return None,
+        }
+        i += 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode<T, F: Fn(&mut Vec<u8>, T)>(f: F, v: T) -> Vec<u8> {
+        let mut buf = Vec::new();
+        f(&mut buf, v);
+        buf
+    }
+
+    #[test]
+    fn order_preserving() {
+        let values = [i64::MIN, -256, -1, 0, 1, 255, 256, i64::MAX];
+        for w in values.windows(2) {
+            assert!(encode(put_i64, w[0]) < encode(put_i64, w[1]));
+            assert!(encode(put_i64_desc, w[0]) > encode(put_i64_desc, w[1]));
+            assert!(
+                encode(put_u64, w[0] as u64 ^ SIGN_MASK) < encode(put_u64, w[1] as u64 ^ SIGN_MASK)
+            );
+        }
+
+        let values: [&[u8]; 7] = [
+            b"",
+            b"\x00",
+            b"\x00\x00",
+            b"\x00\x01",
+            b"a",
+            b"a\x00",
+            b"ab",
+        ];
+        for w in values.windows(2) {
+            assert!(encode(put_bytes, w[0]) < encode(put_bytes, w[1]), "{w:?}");
+            assert!(
+                encode(put_bytes_desc, w[0]) > encode(put_bytes_desc, w[1]),
+                "{w:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn composite_key_round_trip() {
+        let mut buf = Vec::new();
+        put_str(&mut buf, "user\x00a");
+        put_i64(&mut buf, -42);
+        put_u64_desc(&mut buf, 7);
+        put_bytes_desc(&mut buf, b"\x00\xFF");
+        put_i64_desc(&mut buf, 9);
+
+        let mut input = buf.as_slice();
+        assert_eq!(get_str(&mut input).unwrap(), "user\x00a");
+        assert_eq!(get_i64(&mut input), Some(-42));
+        assert_eq!(get_u64_desc(&mut input), Some(7));
+        assert_eq!(get_bytes_desc(&mut input).unwrap(), b"\x00\xFF");
+        assert_eq!(get_i64_desc(&mut input), Some(9));
+        assert!(input.is_empty());
+
+        assert!(get_u64(&mut [1u8, 2].as_slice()).is_none());
+        assert!(get_bytes(&mut b"abc".as_slice()).is_none());
+        assert!(get_bytes(&mut b"a\x00\x02".as_slice()).is_none());
+    }
+
+    #[test]
+    fn composite_key_prefix_end() {
+        assert_eq!(prefix_end(b"ab"), b"ac");
+        assert_eq!(prefix_end(b"a\xFF"), b"b");
+        assert!(prefix_end(b"\xFF\xFF").is_empty());
+
+        let mut prefix = Vec::new();
+        put_str(&mut prefix, "user");
+        let mut key = prefix.clone();
+        put_u64(&mut key, u64::MAX);
+        assert!(prefix.as_slice() < key.as_slice());
+        assert!(key < prefix_end(&prefix));
+    }
+}
@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod chunk;
 mod error;
+pub mod keys;
 mod migration;
 pub mod shard;
 
@@ -21,6 +23,9 @@ pub mod v1 {
     tonic::include_proto!("engula.v1");
 }
 
+/// The encoded `FileDescriptorSet` of the engula protos, it is used by the gRPC reflection service.
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("engula_descriptor");
+
 pub mod server {
     pub mod v1 {
         #![allow(clippy::all)]
@@ -0,0 +1,212 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use prost::Message;
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, FileDescriptorSet, ServiceDescriptorProto,
+};
+
+/// The definitions of a descriptor set, indexed by the full names.
+#[derive(Default)]
+struct Definitions {
+    messages: HashMap<String, DescriptorProto>,
+    enums: HashMap<String, EnumDescriptorProto>,
+    services: HashMap<String, ServiceDescriptorProto>,
+}
+
+impl Definitions {
+    fn new(encoded: &[u8]) -> Self {
+        let set = FileDescriptorSet::decode(encoded).unwrap();
+        let mut defs = Definitions::default();
+        for file in set.file {
+            let package = format!(".{}", file.package());
+            for msg in file.message_type {
+                defs.add_message(&package, msg);
+            }
+            for e in file.enum_type {
+                defs.enums.insert(format!("{package}.{}", e.name()), e);
+            }
+            for service in file.service {
+                defs.services
+                    .insert(format!("{package}.{}", service.name()), service);
+            }
+        }
+        defs
+    }
+
+    fn add_message(&mut self, scope: &str, msg: DescriptorProto) {
+        let name = format!("{scope}.{}", msg.name());
+        for nested in &msg.nested_type {
+            self.add_message(&name, nested.clone());
+        }
+        for e in &msg.enum_type {
+            self.enums.insert(format!("{name}.{}", e.name()), e.clone());
+        }
+        self.messages.insert(name, msg);
+    }
+}
+
+fn is_reserved(msg: &DescriptorProto, number: i32) -> bool {
+    // The end of the reserved range of message is exclusive.
+    msg.reserved_range
+        .iter()
+        .any(|r| r.start() <= number && number < r.end())
+}
+
+fn is_reserved_enum_value(e: &EnumDescriptorProto, number: i32) -> bool {
+    // The end of the reserved range of enum is inclusive.
+    e.reserved_range
+        .iter()
+        .any(|r| r.start() <= number && number <= r.end())
+}
+
+/// Returns the wire incompatible changes from `golden` to `current`.
+fn incompatible_changes(golden: &Definitions, current: &Definitions) -> Vec<String> {
+    let mut changes = vec![];
+    for (name, golden_msg) in &golden.messages {
+        let msg = match current.messages.get(name) {
+            Some(msg) => msg,
+            None => {
+                changes.push(format!("message {name} is removed"));
+                continue;
+            }
+        };
+        for golden_field in &golden_msg.field {
+            let number = golden_field.number();
+            match msg.field.iter().find(|f| f.number() == number) {
+                Some(field) => {
+                    if field.r#type() != golden_field.r#type()
+                        || field.type_name() != golden_field.type_name()
+                        || field.label() != golden_field.label()
+                    {
+                        changes.push(format!(
+                            "the type of field {name}.{} ({number}) is changed",
+                            golden_field.name()
+                        ));
+                    }
+                    if field.oneof_index.is_some() != golden_field.oneof_index.is_some() {
+                        changes.push(format!(
+                            "field {name}.{} ({number}) is moved into or out of oneof",
+                            golden_field.name()
+                        ));
+                    }
+                }
+                None if is_reserved(msg, number) => {}
+                None => changes.push(format!(
+                    "field {name}.{} ({number}) is removed without reserving",
+                    golden_field.name()
+                )),
+            }
+        }
+    }
+
+    for (name, golden_enum) in &golden.enums {
+        let e = match current.enums.get(name) {
+            Some(e) => e,
+            None => {
+                changes.push(format!("enum {name} is removed"));
+                continue;
+            }
+        };
+        for value in &golden_enum.value {
+            let number = value.number();
+            if !e.value.iter().any(|v| v.number() == number) && !is_reserved_enum_value(e, number) {
+                changes.push(format!(
+                    "value {name}.{} ({number}) is removed without reserving",
+                    value.name()
+                ));
+            }
+        }
+    }
+
+    for (name, golden_service) in &golden.services {
+        let service = match current.services.get(name) {
+            Some(service) => service,
+            None => {
+                changes.push(format!("service {name} is removed"));
+                continue;
+            }
+        };
+        for golden_method in &golden_service.method {
+            match service
+                .method
+                .iter()
+                .find(|m| m.name() == golden_method.name())
+            {
+                Some(method) => {
+                    if method.input_type() != golden_method.input_type()
+                        || method.output_type() != golden_method.output_type()
+                        || method.client_streaming() != golden_method.client_streaming()
+                        || method.server_streaming() != golden_method.server_streaming()
+                    {
+                        changes.push(format!(
+                            "the signature of method {name}.{} is changed",
+                            golden_method.name()
+                        ));
+                    }
+                }
+                None => changes.push(format!("method {name}.{} is removed", golden_method.name())),
+            }
+        }
+    }
+    changes.sort();
+    changes
+}
+
+#[test]
+fn wire_compatible_with_previous_releases() {
+    let current = Definitions::new(engula_api::FILE_DESCRIPTOR_SET);
+    let goldens: &[(&str, &[u8])] = &[(
+        "v0.4.0",
+        include_bytes!(concat!(env!("OUT_DIR"), "/golden_v0.4.0_descriptor.bin")),
+    )];
+    for (release, encoded) in goldens {
+        let golden = Definitions::new(encoded);
+        assert!(!golden.messages.is_empty());
+        let changes = incompatible_changes(&golden, &current);
+        assert!(
+            changes.is_empty(),
+            "the protos are not wire compatible with release {release}:\n{}",
+            changes.join("\n")
+        );
+    }
+}
+
+#[test]
+fn detect_incompatible_changes() {
+    use prost_types::{field_descriptor_proto::Type, FieldDescriptorProto};
+
+    let golden = Definitions::new(engula_api::FILE_DESCRIPTOR_SET);
+    let mut current = Definitions::new(engula_api::FILE_DESCRIPTOR_SET);
+    assert!(incompatible_changes(&golden, &current).is_empty());
+
+    let desc = current
+        .messages
+        .get_mut(".engula.server.v1.NodeDesc")
+        .unwrap();
+    let field = desc.field.iter_mut().find(|f| f.name() == "addr").unwrap();
+    field.set_type(Type::Bytes);
+    desc.field.push(FieldDescriptorProto {
+        name: Some("extra".to_owned()),
+        number: Some(1000),
+        ..Default::default()
+    });
+    desc.field.retain(|f| f.name() != "zone");
+    let changes = incompatible_changes(&golden, &current);
+    assert_eq!(changes.len(), 2, "{changes:?}");
+    assert!(changes[0].contains("zone"), "{changes:?}");
+    assert!(changes[1].contains("addr"), "{changes:?}");
+}
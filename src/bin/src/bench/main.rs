@@ -136,6 +136,7 @@ async fn open_collection(cfg: &AppConfig) -> Result<Collection> {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(200)),
         timeout: Some(Duration::from_millis(500)),
+        ..Default::default()
     };
     let client = EngulaClient::new(opts, cfg.addrs.clone()).await?;
     let database = match client.open_database(cfg.database.clone()).await {
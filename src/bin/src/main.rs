@@ -18,6 +18,10 @@ use engula_server::{Error, Result};
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
 #[derive(Parser)]
 #[clap(name = "engula", version, author, about)]
 struct Command {
@@ -35,6 +39,10 @@ impl Command {
 enum SubCommand {
     Start(StartCommand),
     Bench(bench::BenchCommand),
+    DumpGroup(DumpGroupCommand),
+    DumpRaftLog(DumpRaftLogCommand),
+    Backup(BackupCommand),
+    VerifyBackup(VerifyBackupCommand),
 }
 
 impl SubCommand {
@@ -45,6 +53,10 @@ impl SubCommand {
                 cmd.run();
                 Ok(())
             }
+            SubCommand::DumpGroup(cmd) => cmd.run(),
+            SubCommand::DumpRaftLog(cmd) => cmd.run(),
+            SubCommand::Backup(cmd) => cmd.run(),
+            SubCommand::VerifyBackup(cmd) => cmd.run(),
         }
     }
 }
@@ -57,6 +69,11 @@ struct StartCommand {
         help = "Try to bootstrap a cluster if it not initialized, otherwise join a cluster"
     )]
     init: bool,
+    #[clap(
+        long,
+        help = "Serve a single group without raft and root coordination, for local development"
+    )]
+    standalone: bool,
     #[clap(long)]
     join: Option<Vec<String>>,
     #[clap(long)]
@@ -67,6 +84,11 @@ struct StartCommand {
     db: Option<String>,
     #[clap(long)]
     cpu_nums: Option<u32>,
+    #[clap(
+        long,
+        help = "The seconds to wait for transferring leaderships out before exiting"
+    )]
+    graceful_shutdown_seconds: Option<u64>,
 
     #[clap(long, help = "dump config as toml file and exit")]
     dump_config: Option<String>,
@@ -100,12 +122,117 @@ impl StartCommand {
         let owner = ExecutorOwner::with_config(config.cpu_nums as usize, config.executor.clone());
         let executor = owner.executor();
         executor.spawn(None, TaskPriority::Low, async move {
-            notifier.ctrl_c().await;
+            notifier.terminate_signal().await;
         });
         engula_server::run(config, executor, shutdown)
     }
 }
 
+#[derive(Parser)]
+#[clap(about = "Dump the engine contents of a group from the data directory of a stopped node")]
+struct DumpGroupCommand {
+    #[clap(long, help = "The root dir of the node")]
+    db: String,
+    #[clap(long)]
+    group: u64,
+    #[clap(long, help = "Dump to the file instead of stdout")]
+    output: Option<String>,
+}
+
+impl DumpGroupCommand {
+    fn run(self) -> Result<()> {
+        let num_entries = match &self.output {
+            Some(filename) => {
+                let file = std::fs::File::create(filename)?;
+                let mut w = std::io::BufWriter::new(file);
+                engula_server::dump_group(&self.db, self.group, &mut w)?
+            }
+            None => {
+                let mut w = std::io::stdout().lock();
+                engula_server::dump_group(&self.db, self.group, &mut w)?
+            }
+        };
+        // The dumped contents might be written to stdout, so the summary goes to stderr.
+        eprintln!("group {} dump {num_entries} entries", self.group);
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+#[clap(about = "Dump the raft log of a group from the data directory of a stopped node")]
+struct DumpRaftLogCommand {
+    #[clap(long, help = "The root dir of the node")]
+    db: String,
+    #[clap(long)]
+    group: u64,
+    #[clap(long, help = "Print each entry as a JSON object")]
+    json: bool,
+    #[clap(long, help = "Dump to the file instead of stdout")]
+    output: Option<String>,
+}
+
+impl DumpRaftLogCommand {
+    fn run(self) -> Result<()> {
+        let num_entries = match &self.output {
+            Some(filename) => {
+                let file = std::fs::File::create(filename)?;
+                let mut w = std::io::BufWriter::new(file);
+                engula_server::dump_raft_log(&self.db, self.group, self.json, &mut w)?
+            }
+            None => {
+                let mut w = std::io::stdout().lock();
+                engula_server::dump_raft_log(&self.db, self.group, self.json, &mut w)?
+            }
+        };
+        eprintln!("group {} dump {num_entries} raft log entries", self.group);
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+#[clap(about = "Backup the engine from the data directory of a stopped node, with a manifest")]
+struct BackupCommand {
+    #[clap(long, help = "The root dir of the node")]
+    db: String,
+    #[clap(long, help = "The backup dir, which must not exist")]
+    output: String,
+}
+
+impl BackupCommand {
+    fn run(self) -> Result<()> {
+        let manifest = engula_server::backup(&self.db, &self.output)?;
+        println!(
+            "backup {} files {} bytes to {}, root hash {:08x}",
+            manifest.files.len(),
+            manifest.total_size(),
+            self.output,
+            manifest.root_hash
+        );
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+#[clap(about = "Verify a backup against its manifest before it is relied upon for restore")]
+struct VerifyBackupCommand {
+    #[clap(long, help = "The backup dir")]
+    dir: String,
+}
+
+impl VerifyBackupCommand {
+    fn run(self) -> Result<()> {
+        let manifest = engula_server::verify_backup(&self.dir)?;
+        println!(
+            "backup {} is valid, {} files {} bytes, root hash {:08x}",
+            self.dir,
+            manifest.files.len(),
+            manifest.total_size(),
+            manifest.root_hash
+        );
+        Ok(())
+    }
+}
+
 fn main() -> Result<()> {
     let default_panic = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
@@ -134,8 +261,11 @@ fn load_config(
     let mut builder = Config::builder()
         .set_default("addr", "127.0.0.1:21805")?
         .set_default("init", false)?
+        .set_default("standalone", false)?
         .set_default("enable_proxy_service", false)?
+        .set_default("enable_pprof", false)?
         .set_default("cpu_nums", 0u32)?
+        .set_default("graceful_shutdown_seconds", 0u64)?
         .set_default("join_list", Vec::<String>::default())?;
 
     if let Some(conf) = cmd.conf.as_ref() {
@@ -148,7 +278,9 @@ fn load_config(
         .set_override_option("root_dir", cmd.db.clone())?
         .set_override_option("join_list", cmd.join.clone())?
         .set_override_option("cpu_nums", cmd.cpu_nums)?
+        .set_override_option("graceful_shutdown_seconds", cmd.graceful_shutdown_seconds)?
         .set_override_option("init", if cmd.init { Some(true) } else { None })?
+        .set_override_option("standalone", if cmd.standalone { Some(true) } else { None })?
         .build()?;
 
     c.try_deserialize()
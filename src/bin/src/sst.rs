@@ -0,0 +1,229 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `engula-sst` inspects a table file of the group engine offline.
+
+use clap::{Parser, Subcommand};
+use engula_server::{
+    sst::{escape, format_property, SstReader},
+    Error, Result,
+};
+
+#[derive(Parser)]
+#[clap(
+    name = "engula-sst",
+    version,
+    author,
+    about = "Inspect and verify engine table files"
+)]
+struct Command {
+    #[clap(subcommand)]
+    subcmd: SubCommand,
+}
+
+#[derive(Subcommand)]
+enum SubCommand {
+    Footer(FooterCommand),
+    Properties(PropertiesCommand),
+    Index(IndexCommand),
+    Scan(ScanCommand),
+    Verify(VerifyCommand),
+}
+
+impl SubCommand {
+    fn run(self) -> Result<()> {
+        match self {
+            SubCommand::Footer(cmd) => cmd.run(),
+            SubCommand::Properties(cmd) => cmd.run(),
+            SubCommand::Index(cmd) => cmd.run(),
+            SubCommand::Scan(cmd) => cmd.run(),
+            SubCommand::Verify(cmd) => cmd.run(),
+        }
+    }
+}
+
+#[derive(Parser)]
+#[clap(about = "Print the footer and the meta blocks")]
+struct FooterCommand {
+    file: String,
+}
+
+impl FooterCommand {
+    fn run(self) -> Result<()> {
+        let reader = SstReader::open(&self.file)?;
+        let footer = reader.footer();
+        println!("format version: {}", footer.format_version);
+        println!("checksum type: {:?}", footer.checksum_type);
+        println!(
+            "metaindex: offset {} size {}",
+            footer.metaindex.offset, footer.metaindex.size
+        );
+        println!(
+            "index: offset {} size {}",
+            footer.index.offset, footer.index.size
+        );
+        for (name, handle) in reader.meta_blocks() {
+            println!(
+                "meta block {name}: offset {} size {}",
+                handle.offset, handle.size
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+#[clap(about = "Print the table properties")]
+struct PropertiesCommand {
+    file: String,
+}
+
+impl PropertiesCommand {
+    fn run(self) -> Result<()> {
+        let reader = SstReader::open(&self.file)?;
+        for (name, value) in reader.properties() {
+            println!("{name}: {}", format_property(name, value));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+#[clap(about = "Print the index, one entry per data block")]
+struct IndexCommand {
+    file: String,
+}
+
+impl IndexCommand {
+    fn run(self) -> Result<()> {
+        let reader = SstReader::open(&self.file)?;
+        for entry in reader.index()? {
+            println!(
+                "{} => offset {} size {}",
+                escape(&entry.user_key),
+                entry.handle.offset,
+                entry.handle.size
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+#[clap(about = "Print the entries in the key range, and the range deletions")]
+struct ScanCommand {
+    file: String,
+    #[clap(
+        long,
+        help = "The start key, inclusive, hex encoded if prefixed with 0x"
+    )]
+    from: Option<String>,
+    #[clap(long, help = "The end key, exclusive, hex encoded if prefixed with 0x")]
+    to: Option<String>,
+    #[clap(long, help = "Print at most this many entries")]
+    limit: Option<usize>,
+}
+
+impl ScanCommand {
+    fn run(self) -> Result<()> {
+        let reader = SstReader::open(&self.file)?;
+        let start = self.from.as_deref().map(parse_key).transpose()?;
+        let end = self.to.as_deref().map(parse_key).transpose()?;
+        let start = start.unwrap_or_default();
+        let end = end.unwrap_or_default();
+
+        let mut num_entries = 0;
+        reader.scan(&start, &end, |entry| {
+            if self
+                .limit
+                .map(|limit| num_entries >= limit)
+                .unwrap_or_default()
+            {
+                return false;
+            }
+            println!(
+                "{} @ {} : {} => {}",
+                escape(&entry.user_key),
+                entry.sequence,
+                entry.value_type,
+                escape(&entry.value)
+            );
+            num_entries += 1;
+            true
+        })?;
+        for entry in reader.range_deletions()? {
+            if !end.is_empty() && entry.user_key >= end {
+                continue;
+            }
+            if !entry.value.is_empty() && entry.value <= start {
+                continue;
+            }
+            println!(
+                "range deletion [{}, {}) @ {}",
+                escape(&entry.user_key),
+                escape(&entry.value),
+                entry.sequence
+            );
+        }
+        eprintln!("scan {num_entries} entries");
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+#[clap(about = "Verify the checksums of all blocks and the order of entries")]
+struct VerifyCommand {
+    file: String,
+}
+
+impl VerifyCommand {
+    fn run(self) -> Result<()> {
+        let reader = SstReader::open(&self.file)?;
+        let report = reader.verify()?;
+        println!(
+            "{} is valid, {} blocks {} entries{}",
+            self.file,
+            report.blocks,
+            report.entries,
+            if report.checksum_verified {
+                ""
+            } else {
+                ", written without checksums"
+            }
+        );
+        Ok(())
+    }
+}
+
+fn parse_key(key: &str) -> Result<Vec<u8>> {
+    let hex = match key.strip_prefix("0x") {
+        Some(hex) => hex,
+        None => return Ok(key.as_bytes().to_vec()),
+    };
+    if hex.len() % 2 != 0 {
+        return Err(invalid_key(key));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid_key(key)))
+        .collect()
+}
+
+fn invalid_key(key: &str) -> Error {
+    Error::InvalidArgument(format!("invalid hex key {key}"))
+}
+
+fn main() -> Result<()> {
+    Command::parse().subcmd.run()
+}
@@ -12,19 +12,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, ops::Range, sync::Arc, time::Duration};
 
 use engula_api::{
     server::v1::{group_request_union::Request, group_response_union::Response, *},
     v1::{create_collection_request::*, *},
 };
+use tracing::debug;
 
 use crate::{
-    conn_manager::ConnManager, discovery::StaticServiceDiscovery, group_client::GroupClient,
-    metrics::*, record_latency, AdminRequestBuilder, AdminResponseExtractor, AppError, AppResult,
-    RetryState, RootClient, Router,
+    batcher::{Batcher, Write},
+    cache::{Cache, InvalidateGuard},
+    chunk::{self, Manifest, ManifestCache},
+    conn_manager::ConnManager,
+    discovery::{SeedServiceDiscovery, ServiceDiscovery, StaticServiceDiscovery},
+    group_client::GroupClient,
+    metrics::*,
+    record_latency, AdminRequestBuilder, AdminResponseExtractor, AppError, AppResult, KeyWatcher,
+    RetryPolicy, RetryState, RootClient, Router, RouterGroupState,
 };
 
+/// The default limit of key size, see [`ClientOptions::max_key_size`].
+pub const DEFAULT_MAX_KEY_SIZE: usize = 64 << 10;
+
+/// The default limit of value size, see [`ClientOptions::max_value_size`].
+pub const DEFAULT_MAX_VALUE_SIZE: usize = 64 << 20;
+
+/// The default limit of batch size, see [`ClientOptions::max_batch_size`].
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 128 << 20;
+
+/// The default time to live of cached values, see [`ClientOptions::cache_ttl`].
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone, Default)]
 pub struct ClientOptions {
     /// The duration of connection timeout, an error is issued if establish connection is not
@@ -33,6 +52,91 @@ pub struct ClientOptions {
 
     /// The duration of RPC over this client.
     pub timeout: Option<Duration>,
+
+    /// Values larger than this size are split into chunks of this size transparently, the chunks
+    /// are saved in separate keys and the user key saves a manifest record.
+    ///
+    /// Default: disabled
+    pub chunk_size: Option<usize>,
+
+    /// Requests with larger keys are rejected with [`AppError::KeyTooLarge`] before sending. It is
+    /// overridden by the `max_key_size` of collection.
+    ///
+    /// Default: 64KB
+    pub max_key_size: Option<usize>,
+
+    /// Puts with larger values are rejected with [`AppError::ValueTooLarge`] before sending, the
+    /// chunked values are limited by the total size. It is overridden by the `max_value_size` of
+    /// collection.
+    ///
+    /// Default: 64MB
+    pub max_value_size: Option<usize>,
+
+    /// Batch writes whose total size of keys and values exceeds it are rejected with
+    /// [`AppError::BatchTooLarge`] before sending.
+    ///
+    /// Default: 128MB
+    pub max_batch_size: Option<usize>,
+
+    /// The puts and deletes issued within this window are coalesced into per-group batch writes
+    /// transparently. It trades the latency of single write for throughput.
+    ///
+    /// Default: disabled
+    pub batch_window: Option<Duration>,
+
+    /// The requests whose encoded size exceed this threshold are compressed with gzip, to reduce
+    /// the bandwidth between client and nodes. The small requests are sent as is, since the
+    /// compression costs more than it saves.
+    ///
+    /// Default: disabled
+    pub compress_threshold: Option<usize>,
+
+    /// The max number of entries of the client-side read cache. The values are cached for the
+    /// TTL, so the reads might observe the values overwritten by other clients within the TTL. It
+    /// is designed for the read-mostly workloads which could tolerate the bounded staleness.
+    ///
+    /// Default: disabled
+    pub cache_capacity: Option<usize>,
+
+    /// The time to live of the cached values, it is overridden by [`Collection::with_cache_ttl`].
+    ///
+    /// Default: 1s
+    pub cache_ttl: Option<Duration>,
+
+    /// The zone where this client is deployed. The eventually consistent reads are served by the
+    /// replicas in the same zone, see [`ReadConsistency::Eventual`].
+    ///
+    /// Default: none
+    pub zone: Option<String>,
+
+    /// The policy of retrying the stale routes and unavailable groups, the retries are bounded by
+    /// [`ClientOptions::timeout`] too.
+    ///
+    /// Default: unlimited attempts, backoff from 8ms to 250ms
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+/// The options of a collection, which are saved in the collection descriptor.
+#[derive(Debug, Clone, Default)]
+pub struct CollectionOptions {
+    /// The limit of key size, overrides [`ClientOptions::max_key_size`] if it isn't 0.
+    pub max_key_size: u64,
+
+    /// The limit of value size, overrides [`ClientOptions::max_value_size`] if it isn't 0.
+    pub max_value_size: u64,
+
+    /// The zone where the leaders of the groups serving this collection are preferred, so that
+    /// clients deployed in the same zone access leaders locally. Empty means no preference.
+    pub leader_zone: String,
+
+    /// The HTTP endpoint which the leaders POST the keys written to this collection to, with
+    /// at-least-once delivery, eg to invalidate caches. Empty means disabled.
+    pub webhook_url: String,
+
+    /// The zones where a learner replica of each group serving this collection is placed, so
+    /// that the clients in these zones serve eventually consistent reads locally, see
+    /// [`ReadConsistency::Eventual`].
+    pub read_replica_zones: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -46,27 +150,62 @@ struct ClientInner {
     root_client: RootClient,
     router: Router,
     conn_manager: ConnManager,
+    batcher: Option<Batcher>,
+    cache: Option<Arc<Cache>>,
 }
 
 impl Client {
     pub async fn new(opts: ClientOptions, addrs: Vec<String>) -> AppResult<Self> {
+        let conn_manager = Self::conn_manager(&opts);
+        Self::connect(opts, addrs, conn_manager).await
+    }
+
+    /// Build a client which injects the faults into the requests sent to nodes, to test the retry
+    /// and idempotency logic of applications.
+    #[cfg(feature = "fault-injection")]
+    pub async fn new_with_fault_injector(
+        opts: ClientOptions,
+        addrs: Vec<String>,
+        faults: crate::fault::FaultInjector,
+    ) -> AppResult<Self> {
+        let conn_manager = Self::conn_manager(&opts).with_fault_injector(faults);
+        Self::connect(opts, addrs, conn_manager).await
+    }
+
+    fn conn_manager(opts: &ClientOptions) -> ConnManager {
         let conn_manager = if let Some(connect_timeout) = opts.connect_timeout {
             ConnManager::with_connect_timeout(connect_timeout)
         } else {
             ConnManager::new()
         };
+        let conn_manager = match opts.compress_threshold {
+            Some(threshold) => conn_manager.with_compression(threshold),
+            None => conn_manager,
+        };
+        match opts.retry_policy.clone() {
+            Some(policy) => conn_manager.with_retry_policy(policy),
+            None => conn_manager,
+        }
+    }
 
-        let discovery = Arc::new(StaticServiceDiscovery::new(addrs.clone()));
+    async fn connect(
+        opts: ClientOptions,
+        addrs: Vec<String>,
+        conn_manager: ConnManager,
+    ) -> AppResult<Self> {
+        let discovery: Arc<dyn ServiceDiscovery> =
+            if addrs.iter().any(|addr| SeedServiceDiscovery::is_seed(addr)) {
+                Arc::new(SeedServiceDiscovery::new(addrs))
+            } else {
+                Arc::new(StaticServiceDiscovery::new(addrs))
+            };
         let root_client = RootClient::new(discovery, conn_manager.clone());
-        let router = Router::new(root_client.clone()).await;
-        Ok(Self {
-            inner: Arc::new(ClientInner {
-                opts,
-                root_client,
-                router,
-                conn_manager,
-            }),
-        })
+        let capabilities = root_client.get_capabilities().await?;
+        let router = match (capabilities.node, capabilities.group) {
+            (Some(node), Some(group)) if capabilities.standalone => Router::standalone(node, group),
+            _ => Router::new(root_client.clone()).await,
+        };
+        Ok(Self::build(opts, router, root_client, conn_manager))
     }
 
     pub fn build(
@@ -75,16 +214,33 @@ impl Client {
         root_client: RootClient,
         conn_manager: ConnManager,
     ) -> Self {
+        let batcher = opts
+            .batch_window
+            .filter(|window| !window.is_zero())
+            .map(|window| Batcher::new(window, opts.timeout, router.clone(), conn_manager.clone()));
+        let cache = opts
+            .cache_capacity
+            .filter(|&capacity| capacity > 0)
+            .map(|capacity| Arc::new(Cache::new(capacity)));
         Client {
             inner: Arc::new(ClientInner {
                 opts,
                 root_client,
                 router,
                 conn_manager,
+                batcher,
+                cache,
             }),
         }
     }
 
+    /// Drop all values of the client-side cache.
+    pub fn invalidate_cache(&self) {
+        if let Some(cache) = &self.inner.cache {
+            cache.invalidate_all();
+        }
+    }
+
     pub async fn create_database(&self, name: String) -> AppResult<Database> {
         let root_client = self.inner.root_client.clone();
         let resp = root_client
@@ -176,6 +332,180 @@ impl From<create_collection_request::Partition> for Partition {
     }
 }
 
+/// A unit of a parallel full-collection scan, see [`Collection::scan_partitions`].
+///
+/// Each partition is exactly one shard, so the tasks of a scan don't overlap and could be read
+/// concurrently by [`Collection::scan_partition`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanPartition {
+    pub shard_id: u64,
+    /// The group owns the shard when the plan was made.
+    pub group_id: u64,
+    /// The key range `[start, end)` of a range partitioned shard, an empty `end` means unbounded.
+    pub range: Option<Range<Vec<u8>>>,
+    /// The slot of a hash partitioned shard.
+    pub slot: Option<u32>,
+    /// The address of the group leader when the plan was made, it is only a hint for scheduling
+    /// the task close to the data.
+    pub leader_addr: Option<String>,
+}
+
+/// A write of [`Collection::batch_write`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchWriteOp {
+    Put(Vec<u8>, Vec<u8>),
+    Delete(Vec<u8>),
+}
+
+impl BatchWriteOp {
+    pub fn key(&self) -> &[u8] {
+        match self {
+            BatchWriteOp::Put(key, _) | BatchWriteOp::Delete(key) => key,
+        }
+    }
+}
+
+/// The options of [`Collection::batch_get`] and [`Collection::batch_write`].
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// Fail the whole batch with the first error if any key isn't finished, instead of returning
+    /// the outcome of each key.
+    ///
+    /// The writes spanning multiple groups are not atomic: the keys of the groups which have
+    /// finished are not rolled back, but the batch could be retried as a whole since puts and
+    /// deletes are idempotent.
+    pub all_or_nothing: bool,
+}
+
+/// A batch of mixed gets, puts and deletes of a collection, built by [`Collection::batch`].
+///
+/// The gets and the writes are issued concurrently, a get of a key also written by the batch
+/// might observe the value either before or after the write.
+#[must_use = "a batch does nothing unless executed"]
+pub struct Batch<'a> {
+    collection: &'a Collection,
+    ops: Vec<BatchOp>,
+    opts: BatchOptions,
+}
+
+enum BatchOp {
+    Get(Vec<u8>),
+    Write(BatchWriteOp),
+}
+
+impl<'a> Batch<'a> {
+    pub fn get(mut self, key: Vec<u8>) -> Self {
+        self.ops.push(BatchOp::Get(key));
+        self
+    }
+
+    pub fn put(mut self, key: Vec<u8>, value: Vec<u8>) -> Self {
+        self.ops.push(BatchOp::Write(BatchWriteOp::Put(key, value)));
+        self
+    }
+
+    pub fn delete(mut self, key: Vec<u8>) -> Self {
+        self.ops.push(BatchOp::Write(BatchWriteOp::Delete(key)));
+        self
+    }
+
+    pub fn with_options(mut self, opts: BatchOptions) -> Self {
+        self.opts = opts;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Issue the gets with [`Collection::batch_get`] and the writes with
+    /// [`Collection::batch_write`] concurrently, and returns the outcome of each operation in
+    /// the order it was added.
+    pub async fn execute(self) -> AppResult<Vec<KeyOutcome>> {
+        let mut keys = Vec::new();
+        let mut writes = Vec::new();
+        let mut positions = Vec::with_capacity(self.ops.len());
+        for op in self.ops {
+            match op {
+                BatchOp::Get(key) => {
+                    positions.push((true, keys.len()));
+                    keys.push(key);
+                }
+                BatchOp::Write(write) => {
+                    positions.push((false, writes.len()));
+                    writes.push(write);
+                }
+            }
+        }
+
+        // The outcomes are checked after reassembling, so that the first error is the first one
+        // in the input order.
+        let opts = BatchOptions::default();
+        let (gets, writes) = futures::future::try_join(
+            async {
+                match keys.is_empty() {
+                    true => Ok(vec![]),
+                    false => self.collection.batch_get(keys, opts.clone()).await,
+                }
+            },
+            async {
+                match writes.is_empty() {
+                    true => Ok(vec![]),
+                    false => self.collection.batch_write(writes, opts.clone()).await,
+                }
+            },
+        )
+        .await?;
+        let outcomes = reassemble_batch_outcomes(&positions, gets, writes);
+        check_batch_outcomes(outcomes, &self.opts)
+    }
+}
+
+/// The consistency of reads, see [`Collection::get_with_consistency`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadConsistency {
+    /// Read the latest committed value from the leader.
+    #[default]
+    Strong,
+    /// Read from the replica in the zone of client if there is one, see
+    /// [`ClientOptions::zone`]. The value might be stale, but the cross-zone round trip is saved.
+    Eventual,
+}
+
+/// The outcome of a key of a batch operation.
+#[derive(Debug)]
+pub enum KeyOutcome {
+    /// The value of the key read by [`Collection::batch_get`].
+    Value(Vec<u8>),
+    /// The key read by [`Collection::batch_get`] doesn't exist.
+    NotFound,
+    /// The write of the key is committed.
+    Written,
+    /// The deadline is exceeded before the group serving the key responded, the write of the key
+    /// might have been applied.
+    TimedOut,
+    /// The operation of the key failed.
+    Failed(AppError),
+}
+
+impl KeyOutcome {
+    /// Returns whether the operation of the key is finished.
+    pub fn is_ok(&self) -> bool {
+        !matches!(self, KeyOutcome::TimedOut | KeyOutcome::Failed(_))
+    }
+
+    fn from_err(err: AppError) -> Self {
+        match err {
+            AppError::DeadlineExceeded(_) => KeyOutcome::TimedOut,
+            err => KeyOutcome::Failed(err),
+        }
+    }
+}
+
 impl Database {
     pub fn new(client: Client, desc: DatabaseDesc, rpc_timeout: Option<Duration>) -> Self {
         Database {
@@ -189,24 +519,50 @@ impl Database {
         &self,
         name: String,
         partition: Option<Partition>,
+    ) -> AppResult<Collection> {
+        self.create_collection_with_limits(name, partition, 0, 0)
+            .await
+    }
+
+    /// Create a collection whose key and value size limits override the ones of
+    /// [`ClientOptions`], 0 means no override.
+    pub async fn create_collection_with_limits(
+        &self,
+        name: String,
+        partition: Option<Partition>,
+        max_key_size: u64,
+        max_value_size: u64,
+    ) -> AppResult<Collection> {
+        let opts = CollectionOptions {
+            max_key_size,
+            max_value_size,
+            ..Default::default()
+        };
+        self.create_collection_with_options(name, partition, opts)
+            .await
+    }
+
+    /// Create a collection with the specified [`CollectionOptions`].
+    pub async fn create_collection_with_options(
+        &self,
+        name: String,
+        partition: Option<Partition>,
+        opts: CollectionOptions,
     ) -> AppResult<Collection> {
         let client = self.client.clone();
         let db_desc = self.desc.clone();
         let root_client = client.inner.root_client.clone();
         let resp = root_client
-            .admin(AdminRequestBuilder::create_collection(
+            .admin(AdminRequestBuilder::create_collection_with_options(
                 db_desc,
                 name.clone(),
                 partition.map(Into::into),
+                opts,
             ))
             .await?;
         match AdminResponseExtractor::create_collection(resp) {
             None => Err(AppError::NotFound(format!("collection {name}"))),
-            Some(co_desc) => Ok(Collection {
-                rpc_timeout: self.rpc_timeout,
-                co_desc,
-                client: client.clone(),
-            }),
+            Some(co_desc) => Ok(Collection::new(client.clone(), co_desc, self.rpc_timeout)),
         }
     }
 
@@ -234,11 +590,7 @@ impl Database {
             .await?;
         Ok(AdminResponseExtractor::list_collection(resp)
             .into_iter()
-            .map(|co_desc| Collection {
-                rpc_timeout: self.rpc_timeout,
-                co_desc,
-                client: client.clone(),
-            })
+            .map(|co_desc| Collection::new(client.clone(), co_desc, self.rpc_timeout))
             .collect::<Vec<_>>())
     }
 
@@ -251,11 +603,7 @@ impl Database {
             .await?;
         match AdminResponseExtractor::get_collection(resp) {
             None => Err(AppError::NotFound(format!("collection {}", name))),
-            Some(co_desc) => Ok(Collection {
-                rpc_timeout: self.rpc_timeout,
-                co_desc,
-                client: client.clone(),
-            }),
+            Some(co_desc) => Ok(Collection::new(client.clone(), co_desc, self.rpc_timeout)),
         }
     }
 
@@ -275,6 +623,12 @@ pub struct Collection {
     client: Client,
     co_desc: CollectionDesc,
     rpc_timeout: Option<Duration>,
+    chunk_size: Option<usize>,
+    manifest_cache: Arc<ManifestCache>,
+    max_key_size: usize,
+    max_value_size: usize,
+    max_batch_size: usize,
+    cache_ttl: Duration,
 }
 
 impl Collection {
@@ -283,21 +637,107 @@ impl Collection {
         co_desc: CollectionDesc,
         rpc_timeout: Option<Duration>,
     ) -> Collection {
+        let opts = &client.inner.opts;
+        let chunk_size = opts.chunk_size.filter(|&size| size > 0);
+        let max_key_size = match co_desc.max_key_size {
+            0 => opts.max_key_size.unwrap_or(DEFAULT_MAX_KEY_SIZE),
+            size => size as usize,
+        };
+        let max_value_size = match co_desc.max_value_size {
+            0 => opts.max_value_size.unwrap_or(DEFAULT_MAX_VALUE_SIZE),
+            size => size as usize,
+        };
+        let max_batch_size = opts.max_batch_size.unwrap_or(DEFAULT_MAX_BATCH_SIZE);
+        let cache_ttl = opts.cache_ttl.unwrap_or(DEFAULT_CACHE_TTL);
         Collection {
             client,
             co_desc,
             rpc_timeout,
+            chunk_size,
+            manifest_cache: Arc::default(),
+            max_key_size,
+            max_value_size,
+            max_batch_size,
+            cache_ttl,
+        }
+    }
+
+    fn retry_state(&self) -> RetryState {
+        let policy = self.client.inner.conn_manager.retry_policy();
+        RetryState::with_policy(policy, self.rpc_timeout)
+    }
+
+    /// Override the time to live of the cached values of this collection, zero disables caching
+    /// of the collection. It takes effect only if the cache is enabled by
+    /// [`ClientOptions::cache_capacity`].
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Drop the cached value of the key, so the next read fetches it from the cluster.
+    pub fn invalidate(&self, key: &[u8]) {
+        if let Some(cache) = self.cache() {
+            cache.invalidate(self.co_desc.id, key);
         }
     }
 
+    /// Drop all cached values of this collection.
+    pub fn invalidate_all(&self) {
+        if let Some(cache) = self.cache() {
+            cache.invalidate_collection(self.co_desc.id);
+        }
+    }
+
+    fn cache(&self) -> Option<&Cache> {
+        self.client.inner.cache.as_deref()
+    }
+
     pub async fn delete(&self, key: Vec<u8>) -> AppResult<()> {
         CLIENT_DATABASE_BYTES_TOTAL.rx.inc_by(key.len() as u64);
         CLIENT_DATABASE_REQUEST_TOTAL.delete.inc();
         record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.get);
-        let mut retry_state = RetryState::new(self.rpc_timeout);
+        self.check_size(&key, &[])?;
+        let _guard = InvalidateGuard::key(self.cache(), self.co_desc.id, &key);
+        let mut retry_state = self.retry_state();
+
+        if self.chunk_size.is_none() {
+            return self.delete_with_retry(&key, &mut retry_state).await;
+        }
+
+        // The manifest is removed before the chunks, so readers never observe a partial value.
+        self.manifest_cache.remove(&key);
+        let value = self.get_with_retry(&key, &mut retry_state).await?;
+        self.delete_with_retry(&key, &mut retry_state).await?;
+        if let Some(manifest) = value.as_deref().and_then(Manifest::decode) {
+            self.delete_chunks(&key, &manifest, &mut retry_state)
+                .await?;
+        }
+        Ok(())
+    }
 
+    /// Delete all keys in the range, an empty `end` means unbounded. The request is sent to all
+    /// overlapping shards, and each shard deletes its keys with a range tombstone, so the deletion
+    /// is not atomic across shards.
+    ///
+    /// The chunks of a large value are removed only if their keys are also in the range.
+    pub async fn delete_range(&self, range: Range<Vec<u8>>) -> AppResult<()> {
+        CLIENT_DATABASE_REQUEST_TOTAL.delete_range.inc();
+        record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.delete_range);
+        let Range { start, end } = range;
+        self.check_size(&start, &[])?;
+        self.check_size(&end, &[])?;
+        if !end.is_empty() && start >= end {
+            return Ok(());
+        }
+
+        let _guard = InvalidateGuard::range(self.cache(), self.co_desc.id, &start, &end);
+        let mut retry_state = self.retry_state();
         loop {
-            match self.delete_inner(&key, retry_state.timeout()).await {
+            match self
+                .delete_range_inner(&start, &end, retry_state.timeout())
+                .await
+            {
                 Ok(()) => return Ok(()),
                 Err(err) => {
                     retry_state.retry(err).await?;
@@ -306,17 +746,410 @@ impl Collection {
         }
     }
 
+    /// Rename the key `from` to `to`, the value of `to` is overwritten unless `only_if_absent` is
+    /// set. Returns false if `from` is not exists, or `to` is exists and `only_if_absent` is set.
+    ///
+    /// The rename is atomic, but both keys must belong to the same shard, the cross-shard renames
+    /// are rejected since there is no transaction yet. Chunked values are not supported either.
+    pub async fn rename(
+        &self,
+        from: Vec<u8>,
+        to: Vec<u8>,
+        only_if_absent: bool,
+    ) -> AppResult<bool> {
+        CLIENT_DATABASE_BYTES_TOTAL
+            .rx
+            .inc_by((from.len() + to.len()) as u64);
+        CLIENT_DATABASE_REQUEST_TOTAL.rename.inc();
+        record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.rename);
+        self.check_size(&from, &[])?;
+        self.check_size(&to, &[])?;
+        if self.chunk_size.is_some() {
+            return Err(AppError::InvalidArgument(
+                "rename does not support chunked values".into(),
+            ));
+        }
+
+        let _from_guard = InvalidateGuard::key(self.cache(), self.co_desc.id, &from);
+        let _to_guard = InvalidateGuard::key(self.cache(), self.co_desc.id, &to);
+        let mut retry_state = self.retry_state();
+        loop {
+            match self
+                .rename_inner(&from, &to, only_if_absent, retry_state.timeout())
+                .await
+            {
+                Ok(renamed) => return Ok(renamed),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Split a full-collection scan into partitions, one for each shard, so that the scan could
+    /// be executed by parallel tasks, each reading its shard directly.
+    ///
+    /// The plan is a snapshot of the routing table, if a shard is moved away during the scan, the
+    /// reading of the partition fails and the plan should be made again.
+    pub async fn scan_partitions(&self) -> AppResult<Vec<ScanPartition>> {
+        let mut retry_state = self.retry_state();
+        loop {
+            match self.scan_partitions_inner() {
+                Ok(partitions) => return Ok(partitions),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Read all key value pairs of the partition from the group owning it. The pairs of a shard
+    /// are streamed in chunks, ordered by key.
+    ///
+    /// The chunked values are not supported, since the chunks are stored as individual keys.
+    pub async fn scan_partition(
+        &self,
+        partition: &ScanPartition,
+    ) -> AppResult<impl futures::Stream<Item = AppResult<Vec<(Vec<u8>, Vec<u8>)>>>> {
+        use futures::StreamExt;
+
+        CLIENT_DATABASE_REQUEST_TOTAL.scan.inc();
+        if self.chunk_size.is_some() {
+            return Err(AppError::InvalidArgument(
+                "scan does not support chunked values".into(),
+            ));
+        }
+
+        let router = self.client.inner.router.clone();
+        let group = router.find_group(partition.group_id)?;
+        let client = GroupClient::new(group, router, self.client.inner.conn_manager.clone());
+        let streaming = client.retryable_pull(partition.shard_id, vec![]).await?;
+        Ok(streaming.map(|chunk| {
+            let chunk = chunk?;
+            let kvs: Vec<_> = chunk.data.into_iter().map(|d| (d.key, d.value)).collect();
+            CLIENT_DATABASE_BYTES_TOTAL.tx.inc_by(
+                kvs.iter()
+                    .map(|(key, value)| key.len() + value.len())
+                    .sum::<usize>() as u64,
+            );
+            Ok(kvs)
+        }))
+    }
+
+    /// Returns an approximately uniform random sample of at most `n` key-value pairs of the
+    /// collection, so that the distribution of data could be estimated without a full scan.
+    ///
+    /// Each shard draws the samples on the server side, then they are merged in proportion to
+    /// the number of keys of the shards. The chunked values are returned as manifests.
+    pub async fn sample(&self, n: usize) -> AppResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        CLIENT_DATABASE_REQUEST_TOTAL.sample.inc();
+        record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.sample);
+        if n == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut retry_state = self.retry_state();
+        loop {
+            match self.sample_inner(n, retry_state.timeout()).await {
+                Ok(samples) => {
+                    CLIENT_DATABASE_BYTES_TOTAL.tx.inc_by(
+                        samples
+                            .iter()
+                            .map(|(key, value)| key.len() + value.len())
+                            .sum::<usize>() as u64,
+                    );
+                    return Ok(samples);
+                }
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Returns a builder of a batch of mixed gets, puts and deletes, which are grouped by the
+    /// groups serving the keys and issued concurrently, see [`Batch::execute`].
+    pub fn batch(&self) -> Batch<'_> {
+        Batch {
+            collection: self,
+            ops: Vec::new(),
+            opts: BatchOptions::default(),
+        }
+    }
+
+    /// Read the keys concurrently, and returns the outcome of each key in the input order. A slow
+    /// or unavailable group only fails its own keys, unless [`BatchOptions::all_or_nothing`] is
+    /// set.
+    pub async fn batch_get(
+        &self,
+        keys: Vec<Vec<u8>>,
+        opts: BatchOptions,
+    ) -> AppResult<Vec<KeyOutcome>> {
+        CLIENT_DATABASE_REQUEST_TOTAL.batch_get.inc();
+        record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.batch_get);
+        let reads = keys.into_iter().map(|key| async move {
+            match self.get(key).await {
+                Ok(Some(value)) => KeyOutcome::Value(value),
+                Ok(None) => KeyOutcome::NotFound,
+                Err(err) => KeyOutcome::from_err(err),
+            }
+        });
+        let outcomes = futures::future::join_all(reads).await;
+        check_batch_outcomes(outcomes, &opts)
+    }
+
+    /// Write the keys with one `BatchWrite` request per group, issued concurrently, and returns
+    /// the outcome of each key in the input order. The writes of a group are committed
+    /// atomically, a slow or unavailable group only fails its own keys, unless
+    /// [`BatchOptions::all_or_nothing`] is set.
+    ///
+    /// Only the last write of a key in the batch is applied, the earlier ones share its outcome.
+    /// The chunked values and the writes of a group rejecting the batch, eg. one of its shards is
+    /// migrating, are written one by one.
+    pub async fn batch_write(
+        &self,
+        writes: Vec<BatchWriteOp>,
+        opts: BatchOptions,
+    ) -> AppResult<Vec<KeyOutcome>> {
+        CLIENT_DATABASE_REQUEST_TOTAL.batch_write.inc();
+        record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.batch_write);
+        let batch_size = writes
+            .iter()
+            .map(|w| match w {
+                BatchWriteOp::Put(key, value) => key.len() + value.len(),
+                BatchWriteOp::Delete(key) => key.len(),
+            })
+            .sum::<usize>();
+        if batch_size > self.max_batch_size {
+            return Err(AppError::BatchTooLarge(batch_size, self.max_batch_size));
+        }
+        let _guards = writes
+            .iter()
+            .map(|w| InvalidateGuard::key(self.cache(), self.co_desc.id, w.key()))
+            .collect::<Vec<_>>();
+
+        let last_writes = writes
+            .iter()
+            .enumerate()
+            .map(|(idx, w)| (w.key(), idx))
+            .collect::<HashMap<_, _>>();
+        let mut outcomes = writes.iter().map(|_| None).collect::<Vec<_>>();
+        let mut superseded = Vec::new();
+        let mut singles = Vec::new();
+        let mut groups = HashMap::<u64, (RouterGroupState, BatchWriteRequest, Vec<usize>)>::new();
+        let router = &self.client.inner.router;
+        for (idx, write) in writes.iter().enumerate() {
+            let last_idx = last_writes[write.key()];
+            if last_idx != idx {
+                superseded.push((idx, last_idx));
+                continue;
+            }
+            let value = match write {
+                BatchWriteOp::Put(_, value) => value.as_slice(),
+                BatchWriteOp::Delete(_) => &[],
+            };
+            if let Err(err) = self.check_size(write.key(), value) {
+                outcomes[idx] = Some(KeyOutcome::Failed(err));
+                continue;
+            }
+            let chunked = matches!(self.chunk_size, Some(chunk_size) if value.len() > chunk_size)
+                || chunk::is_manifest(value);
+            let (group, shard) = match router.find_shard(self.co_desc.clone(), write.key()) {
+                Ok(route) if !chunked => route,
+                _ => {
+                    singles.push(idx);
+                    continue;
+                }
+            };
+            let (_, req, indexes) = groups
+                .entry(group.id)
+                .or_insert_with(|| (group, BatchWriteRequest::default(), Vec::new()));
+            match write {
+                BatchWriteOp::Put(key, value) => req.puts.push(ShardPutRequest {
+                    shard_id: shard.id,
+                    put: Some(PutRequest {
+                        key: key.clone(),
+                        value: value.clone(),
+                        ..Default::default()
+                    }),
+                }),
+                BatchWriteOp::Delete(key) => req.deletes.push(ShardDeleteRequest {
+                    shard_id: shard.id,
+                    delete: Some(DeleteRequest { key: key.clone() }),
+                }),
+            }
+            indexes.push(idx);
+        }
+
+        let retry_state = self.retry_state();
+        let batches = groups.into_values().map(|(group, req, indexes)| {
+            let mut client = GroupClient::new(
+                group,
+                self.client.inner.router.clone(),
+                self.client.inner.conn_manager.clone(),
+            );
+            if let Some(duration) = retry_state.timeout() {
+                client.set_timeout(duration);
+            }
+            async move { (client.request(&Request::BatchWrite(req)).await, indexes) }
+        });
+        for (result, indexes) in futures::future::join_all(batches).await {
+            match result {
+                Ok(_) => {
+                    for idx in indexes {
+                        outcomes[idx] = Some(KeyOutcome::Written);
+                    }
+                }
+                Err(crate::Error::DeadlineExceeded(_)) => {
+                    for idx in indexes {
+                        outcomes[idx] = Some(KeyOutcome::TimedOut);
+                    }
+                }
+                Err(err) => {
+                    debug!("batch write {} keys: {err:?}", indexes.len());
+                    singles.extend(indexes);
+                }
+            }
+        }
+
+        let single_writes = singles.into_iter().map(|idx| {
+            let write = writes[idx].clone();
+            async move {
+                let result = match write {
+                    BatchWriteOp::Put(key, value) => self.put(key, value).await,
+                    BatchWriteOp::Delete(key) => self.delete(key).await,
+                };
+                (idx, result)
+            }
+        });
+        for (idx, result) in futures::future::join_all(single_writes).await {
+            outcomes[idx] = Some(match result {
+                Ok(()) => KeyOutcome::Written,
+                Err(err) => KeyOutcome::from_err(err),
+            });
+        }
+
+        for (idx, last_idx) in superseded {
+            outcomes[idx] = Some(match outcomes[last_idx].as_ref() {
+                Some(KeyOutcome::TimedOut) => KeyOutcome::TimedOut,
+                Some(KeyOutcome::Failed(err)) => KeyOutcome::Failed(AppError::Internal(
+                    format!("the last write of the key failed: {err}").into(),
+                )),
+                _ => KeyOutcome::Written,
+            });
+        }
+
+        let outcomes = outcomes
+            .into_iter()
+            .map(|outcome| outcome.expect("the outcome of each write is set"))
+            .collect();
+        check_batch_outcomes(outcomes, &opts)
+    }
+
     pub async fn put(&self, key: Vec<u8>, value: Vec<u8>) -> AppResult<()> {
         CLIENT_DATABASE_BYTES_TOTAL
             .rx
             .inc_by((key.len() + value.len()) as u64);
         CLIENT_DATABASE_REQUEST_TOTAL.put.inc();
         record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.put);
-        let mut retry_state = RetryState::new(self.rpc_timeout);
+        self.check_size(&key, &value)?;
+        let _guard = InvalidateGuard::key(self.cache(), self.co_desc.id, &key);
+        let mut retry_state = self.retry_state();
+
+        let chunk_size = match self.chunk_size {
+            Some(chunk_size) => chunk_size,
+            None => return self.put_with_retry(&key, &value, &mut retry_state).await,
+        };
+        let chunked = value.len() > chunk_size || chunk::is_manifest(&value);
+        if !chunked && !self.manifest_cache.remove(&key) {
+            // The replaced value isn't known to be chunked, skip reading it. If it is, the chunks
+            // become orphans and are reclaimed by servers.
+            return self.put_with_retry(&key, &value, &mut retry_state).await;
+        }
 
+        // All chunks are written before the manifest, and the chunks of the replaced value are
+        // removed after the key is overwritten.
+        let prev_value = self.get_with_retry(&key, &mut retry_state).await?;
+        if chunked {
+            let (manifest, chunks) = Manifest::split(&value, chunk_size);
+            for (chunk_key, chunk) in manifest.chunk_keys(&key).iter().zip(chunks) {
+                CLIENT_DATABASE_CHUNK_TOTAL.put.inc();
+                self.put_with_retry(chunk_key, chunk, &mut retry_state)
+                    .await?;
+            }
+            self.put_with_retry(&key, &manifest.encode(), &mut retry_state)
+                .await?;
+            self.manifest_cache.insert(&key);
+        } else {
+            self.put_with_retry(&key, &value, &mut retry_state).await?;
+        }
+        if let Some(prev_manifest) = prev_value.as_deref().and_then(Manifest::decode) {
+            self.delete_chunks(&key, &prev_manifest, &mut retry_state)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Put the value only if the condition is satisfied when the write is applied, otherwise
+    /// [`AppError::ConditionNotMet`] is returned. The response carries the epoch of the group and
+    /// the fencing token of the write, which could be used to detect stale writers.
+    ///
+    /// The value is not split into chunks, and the auto batcher is bypassed.
+    pub async fn put_with_condition(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        condition: WriteCondition,
+    ) -> AppResult<PutResponse> {
+        let put = PutRequest {
+            key,
+            value,
+            condition: Some(condition),
+            ..Default::default()
+        };
+        self.put_unbatched(put, "conditional put").await
+    }
+
+    /// Put the value, and the put is acknowledged once it reaches the durability of the write
+    /// concern, see [`WriteConcern`]. The response is the same as
+    /// [`Collection::put_with_condition`].
+    ///
+    /// The value is not split into chunks, and the auto batcher is bypassed.
+    pub async fn put_with_concern(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        concern: WriteConcern,
+    ) -> AppResult<PutResponse> {
+        let put = PutRequest {
+            key,
+            value,
+            write_concern: concern as i32,
+            ..Default::default()
+        };
+        self.put_unbatched(put, "put with write concern").await
+    }
+
+    /// Send the put alone, so that the options of the request are kept.
+    async fn put_unbatched(&self, put: PutRequest, name: &str) -> AppResult<PutResponse> {
+        let (key, value) = (&put.key, &put.value);
+        CLIENT_DATABASE_BYTES_TOTAL
+            .rx
+            .inc_by((key.len() + value.len()) as u64);
+        CLIENT_DATABASE_REQUEST_TOTAL.put.inc();
+        record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.put);
+        self.check_size(key, value)?;
+        if matches!(self.chunk_size, Some(chunk_size) if value.len() > chunk_size) {
+            return Err(AppError::InvalidArgument(format!(
+                "{name} does not support chunked values"
+            )));
+        }
+
+        let _guard = InvalidateGuard::key(self.cache(), self.co_desc.id, key);
+        let mut retry_state = self.retry_state();
         loop {
-            match self.put_inner(&key, &value, retry_state.timeout()).await {
-                Ok(()) => return Ok(()),
+            match self.put_unbatched_inner(&put, retry_state.timeout()).await {
+                Ok(resp) => return Ok(resp),
                 Err(err) => {
                     retry_state.retry(err).await?;
                 }
@@ -328,16 +1161,250 @@ impl Collection {
         CLIENT_DATABASE_BYTES_TOTAL.rx.inc_by(key.len() as u64);
         CLIENT_DATABASE_REQUEST_TOTAL.get.inc();
         record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.get);
-        let mut retry_state = RetryState::new(self.rpc_timeout);
+        self.check_size(&key, &[])?;
+        let cache = self.cache().filter(|_| !self.cache_ttl.is_zero());
+        let mut generation = 0;
+        if let Some(cache) = cache {
+            if let Some(value) = cache.get(self.co_desc.id, &key) {
+                return Ok(value);
+            }
+            generation = cache.generation();
+        }
+        let mut retry_state = self.retry_state();
+
+        let mut value = self.get_with_retry(&key, &mut retry_state).await?;
+        if let Some(manifest) = value.as_deref().and_then(Manifest::decode) {
+            self.manifest_cache.insert(&key);
+            value = Some(self.get_chunks(&key, &manifest, &mut retry_state).await?);
+        }
+        if let Some(cache) = cache {
+            cache.fill(
+                generation,
+                self.co_desc.id,
+                &key,
+                value.clone(),
+                self.cache_ttl,
+            );
+        }
+        CLIENT_DATABASE_BYTES_TOTAL
+            .tx
+            .inc_by(value.as_ref().map(Vec::len).unwrap_or_default() as u64);
+        Ok(value)
+    }
+
+    /// Returns at most `limit` key-value pairs in `[start, end)` ordered by key, an empty `end`
+    /// means the end of the collection. The shards overlapping the range are read concurrently
+    /// and their results are merged in key order.
+    ///
+    /// If the shards are split or moved during the scan, the routes are resolved again and the
+    /// scan resumes from the last returned key.
+    pub async fn scan(
+        &self,
+        start: Vec<u8>,
+        end: Vec<u8>,
+        limit: usize,
+    ) -> AppResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let data = self.scan_data(start, end, limit).await?;
+        Ok(data.into_iter().map(|d| (d.key, d.value)).collect())
+    }
+
+    /// Like [`Collection::scan`], but returns the versions of the values too, as
+    /// `(key, value, version)`.
+    pub async fn scan_with_version(
+        &self,
+        start: Vec<u8>,
+        end: Vec<u8>,
+        limit: usize,
+    ) -> AppResult<Vec<(Vec<u8>, Vec<u8>, u64)>> {
+        let data = self.scan_data(start, end, limit).await?;
+        Ok(data
+            .into_iter()
+            .map(|d| (d.key, d.value, d.version))
+            .collect())
+    }
+
+    async fn scan_data(
+        &self,
+        start: Vec<u8>,
+        end: Vec<u8>,
+        limit: usize,
+    ) -> AppResult<Vec<ShardData>> {
+        CLIENT_DATABASE_REQUEST_TOTAL.scan.inc();
+        record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.scan);
+        let mut kvs = Vec::new();
+        let mut cursor = start;
+        let mut retry_state = self.retry_state();
+        while kvs.len() < limit {
+            let rs = self
+                .scan_inner(&cursor, &end, limit - kvs.len(), retry_state.timeout())
+                .await;
+            let (data, resume_key) = match rs {
+                Ok(rs) => rs,
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                    continue;
+                }
+            };
+            for mut data in data {
+                if let Some(manifest) = Manifest::decode(&data.value) {
+                    data.value = self
+                        .get_chunks(&data.key, &manifest, &mut retry_state)
+                        .await?;
+                }
+                kvs.push(data);
+            }
+            match resume_key {
+                None => break,
+                Some(key) if key > cursor => cursor = key,
+                Some(_) => {
+                    // No progress is made, the routes are stale.
+                    let err = crate::Error::NotFound(format!("shard of key {cursor:?}"));
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+        CLIENT_DATABASE_BYTES_TOTAL.tx.inc_by(
+            kvs.iter()
+                .map(|data| data.key.len() + data.value.len())
+                .sum::<usize>() as u64,
+        );
+        Ok(kvs)
+    }
+
+    /// Read the value of key with the specified consistency. The eventually consistent reads fall
+    /// back to the leader if there is no replica in the zone of client or it is unavailable.
+    pub async fn get_with_consistency(
+        &self,
+        key: Vec<u8>,
+        consistency: ReadConsistency,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let zone = match (&consistency, &self.client.inner.opts.zone) {
+            (ReadConsistency::Eventual, Some(zone)) => zone,
+            _ => return self.get(key).await,
+        };
+
+        CLIENT_DATABASE_BYTES_TOTAL.rx.inc_by(key.len() as u64);
+        CLIENT_DATABASE_REQUEST_TOTAL.get.inc();
+        record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.get);
+        self.check_size(&key, &[])?;
+        let mut retry_state = self.retry_state();
+        let mut value = match self.stale_get_inner(&key, zone).await {
+            Some(value) => value,
+            None => self.get_with_retry(&key, &mut retry_state).await?,
+        };
+        if let Some(manifest) = value.as_deref().and_then(Manifest::decode) {
+            value = Some(self.get_chunks(&key, &manifest, &mut retry_state).await?);
+        }
+        CLIENT_DATABASE_BYTES_TOTAL
+            .tx
+            .inc_by(value.as_ref().map(Vec::len).unwrap_or_default() as u64);
+        Ok(value)
+    }
+
+    /// Watch the changes of the key, the current value is emitted first if the key exists.
+    ///
+    /// Values which are split into chunks are emitted as their raw manifests.
+    pub fn watch(&self, key: Vec<u8>) -> AppResult<KeyWatcher> {
+        self.check_size(&key, &[])?;
+        Ok(self.key_watcher(key, false))
+    }
+
+    /// Watch the changes of the keys with the prefix, the current values are emitted first.
+    ///
+    /// Only the keys in the shard which owns the prefix are watched, so it is intended for range
+    /// partitioned collections.
+    pub fn watch_prefix(&self, prefix: Vec<u8>) -> AppResult<KeyWatcher> {
+        self.check_size(&prefix, &[])?;
+        Ok(self.key_watcher(prefix, true))
+    }
+
+    fn key_watcher(&self, key: Vec<u8>, prefix: bool) -> KeyWatcher {
+        KeyWatcher::new(
+            self.client.inner.router.clone(),
+            self.client.inner.conn_manager.clone(),
+            self.co_desc.clone(),
+            key,
+            prefix,
+        )
+    }
+
+    fn check_size(&self, key: &[u8], value: &[u8]) -> AppResult<()> {
+        if key.len() > self.max_key_size {
+            return Err(AppError::KeyTooLarge(key.len(), self.max_key_size));
+        }
+        if value.len() > self.max_value_size {
+            return Err(AppError::ValueTooLarge(value.len(), self.max_value_size));
+        }
+        Ok(())
+    }
+
+    async fn get_chunks(
+        &self,
+        key: &[u8],
+        manifest: &Manifest,
+        retry_state: &mut RetryState,
+    ) -> AppResult<Vec<u8>> {
+        let mut chunks = Vec::with_capacity(manifest.num_chunks as usize);
+        for chunk_key in manifest.chunk_keys(key) {
+            CLIENT_DATABASE_CHUNK_TOTAL.get.inc();
+            match self.get_with_retry(&chunk_key, retry_state).await? {
+                Some(chunk) => chunks.push(chunk),
+                None => break,
+            }
+        }
+        manifest.assemble(chunks).ok_or_else(|| {
+            AppError::Internal(wrap("the chunks of large value are lost or corrupted"))
+        })
+    }
 
+    async fn delete_chunks(
+        &self,
+        key: &[u8],
+        manifest: &Manifest,
+        retry_state: &mut RetryState,
+    ) -> AppResult<()> {
+        for chunk_key in manifest.chunk_keys(key) {
+            CLIENT_DATABASE_CHUNK_TOTAL.delete.inc();
+            self.delete_with_retry(&chunk_key, retry_state).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_with_retry(&self, key: &[u8], retry_state: &mut RetryState) -> AppResult<()> {
         loop {
-            match self.get_inner(&key, retry_state.timeout()).await {
-                Ok(value) => {
-                    CLIENT_DATABASE_BYTES_TOTAL
-                        .tx
-                        .inc_by(value.as_ref().map(Vec::len).unwrap_or_default() as u64);
-                    return Ok(value);
+            match self.delete_inner(key, retry_state.timeout()).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    retry_state.retry(err).await?;
                 }
+            }
+        }
+    }
+
+    async fn put_with_retry(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        retry_state: &mut RetryState,
+    ) -> AppResult<()> {
+        loop {
+            match self.put_inner(key, value, retry_state.timeout()).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    async fn get_with_retry(
+        &self,
+        key: &[u8],
+        retry_state: &mut RetryState,
+    ) -> AppResult<Option<Vec<u8>>> {
+        loop {
+            match self.get_inner(key, retry_state.timeout()).await {
+                Ok(value) => return Ok(value),
                 Err(err) => {
                     retry_state.retry(err).await?;
                 }
@@ -348,17 +1415,27 @@ impl Collection {
     async fn delete_inner(&self, key: &[u8], timeout: Option<Duration>) -> crate::Result<()> {
         let router = self.client.inner.router.clone();
         let (group, shard) = router.find_shard(self.co_desc.clone(), key)?;
+        let delete = ShardDeleteRequest {
+            shard_id: shard.id,
+            delete: Some(DeleteRequest {
+                key: key.to_owned(),
+            }),
+        };
+        if let Some(batcher) = &self.client.inner.batcher {
+            if batcher
+                .submit(group.id, Write::Delete(delete.clone()))
+                .await
+            {
+                return Ok(());
+            }
+        }
+
         let mut client = GroupClient::new(
             group,
             self.client.inner.router.clone(),
             self.client.inner.conn_manager.clone(),
         );
-        let req = Request::Delete(ShardDeleteRequest {
-            shard_id: shard.id,
-            delete: Some(DeleteRequest {
-                key: key.to_owned(),
-            }),
-        });
+        let req = Request::Delete(delete);
         if let Some(duration) = timeout {
             client.set_timeout(duration);
         }
@@ -366,6 +1443,71 @@ impl Collection {
         Ok(())
     }
 
+    fn scan_partitions_inner(&self) -> crate::Result<Vec<ScanPartition>> {
+        let router = &self.client.inner.router;
+        let shards = router.find_shards_in_range(self.co_desc.clone(), &[], &[])?;
+        let mut partitions = shards
+            .into_iter()
+            .map(|(group, shard)| {
+                let (range, slot) = match shard.partition {
+                    Some(shard_desc::Partition::Range(shard_desc::RangePartition {
+                        start,
+                        end,
+                    })) => (Some(start..end), None),
+                    Some(shard_desc::Partition::Hash(shard_desc::HashPartition {
+                        slot_id,
+                        ..
+                    })) => (None, Some(slot_id)),
+                    None => (None, None),
+                };
+                let leader_addr = group
+                    .leader_state
+                    .and_then(|(leader_id, _)| group.replicas.get(&leader_id))
+                    .and_then(|replica| router.find_node_addr(replica.node_id).ok());
+                ScanPartition {
+                    shard_id: shard.id,
+                    group_id: group.id,
+                    range,
+                    slot,
+                    leader_addr,
+                }
+            })
+            .collect::<Vec<_>>();
+        partitions.sort_unstable_by(|a, b| {
+            let start = |p: &ScanPartition| p.range.as_ref().map(|r| r.start.clone());
+            (a.slot, start(a)).cmp(&(b.slot, start(b)))
+        });
+        Ok(partitions)
+    }
+
+    async fn delete_range_inner(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        timeout: Option<Duration>,
+    ) -> crate::Result<()> {
+        let router = self.client.inner.router.clone();
+        let shards = router.find_shards_in_range(self.co_desc.clone(), start, end)?;
+        let requests = shards.into_iter().map(|(group, shard)| {
+            let mut client = GroupClient::new(
+                group,
+                self.client.inner.router.clone(),
+                self.client.inner.conn_manager.clone(),
+            );
+            if let Some(duration) = timeout {
+                client.set_timeout(duration);
+            }
+            let req = Request::DeleteRange(ShardDeleteRangeRequest {
+                shard_id: shard.id,
+                start: start.to_owned(),
+                end: end.to_owned(),
+            });
+            async move { client.request(&req).await }
+        });
+        futures::future::try_join_all(requests).await?;
+        Ok(())
+    }
+
     async fn put_inner(
         &self,
         key: &[u8],
@@ -374,6 +1516,120 @@ impl Collection {
     ) -> crate::Result<()> {
         let router = self.client.inner.router.clone();
         let (group, shard) = router.find_shard(self.co_desc.clone(), key)?;
+        let put = ShardPutRequest {
+            shard_id: shard.id,
+            put: Some(PutRequest {
+                key: key.to_owned(),
+                value: value.to_owned(),
+                ..Default::default()
+            }),
+        };
+        if let Some(batcher) = &self.client.inner.batcher {
+            if batcher.submit(group.id, Write::Put(put.clone())).await {
+                return Ok(());
+            }
+        }
+
+        let mut client = GroupClient::new(
+            group,
+            self.client.inner.router.clone(),
+            self.client.inner.conn_manager.clone(),
+        );
+        let req = Request::Put(put);
+        if let Some(duration) = timeout {
+            client.set_timeout(duration);
+        }
+        client.request(&req).await?;
+        Ok(())
+    }
+
+    async fn rename_inner(
+        &self,
+        from: &[u8],
+        to: &[u8],
+        only_if_absent: bool,
+        timeout: Option<Duration>,
+    ) -> crate::Result<bool> {
+        let router = self.client.inner.router.clone();
+        let (group, shard) = router.find_shard(self.co_desc.clone(), from)?;
+        let (_, to_shard) = router.find_shard(self.co_desc.clone(), to)?;
+        if shard.id != to_shard.id {
+            return Err(crate::Error::InvalidArgument(format!(
+                "cross-shard rename from shard {} to {} is not supported",
+                shard.id, to_shard.id
+            )));
+        }
+
+        let mut client = GroupClient::new(
+            group,
+            self.client.inner.router.clone(),
+            self.client.inner.conn_manager.clone(),
+        );
+        let req = Request::Rename(ShardRenameRequest {
+            shard_id: shard.id,
+            from: from.to_owned(),
+            to: to.to_owned(),
+            only_if_absent,
+        });
+        if let Some(duration) = timeout {
+            client.set_timeout(duration);
+        }
+        match client.request(&req).await? {
+            Response::Rename(resp) => Ok(resp.renamed),
+            _ => Err(crate::Error::Internal(wrap(
+                "invalid response type, Rename is required",
+            ))),
+        }
+    }
+
+    async fn sample_inner(
+        &self,
+        n: usize,
+        timeout: Option<Duration>,
+    ) -> crate::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let router = self.client.inner.router.clone();
+        let shards = router.find_shards_in_range(self.co_desc.clone(), &[], &[])?;
+        let requests = shards.into_iter().map(|(group, shard)| {
+            let mut client = GroupClient::new(
+                group,
+                self.client.inner.router.clone(),
+                self.client.inner.conn_manager.clone(),
+            );
+            if let Some(duration) = timeout {
+                client.set_timeout(duration);
+            }
+            let req = Request::Sample(ShardSampleRequest {
+                shard_id: shard.id,
+                count: n.try_into().unwrap_or(u32::MAX),
+            });
+            async move {
+                match client.request(&req).await? {
+                    Response::Sample(resp) => Ok(resp),
+                    _ => Err(crate::Error::Internal(wrap(
+                        "invalid response type, Sample is required",
+                    ))),
+                }
+            }
+        });
+        let responses = futures::future::try_join_all(requests).await?;
+        let totals = responses.iter().map(|r| r.total).collect::<Vec<_>>();
+        let quotas = allocate_samples(n, &totals);
+        let samples = responses
+            .into_iter()
+            .zip(quotas)
+            .flat_map(|(resp, quota)| resp.samples.into_iter().take(quota))
+            .map(|data| (data.key, data.value))
+            .collect();
+        Ok(samples)
+    }
+
+    async fn put_unbatched_inner(
+        &self,
+        put: &PutRequest,
+        timeout: Option<Duration>,
+    ) -> crate::Result<PutResponse> {
+        let router = self.client.inner.router.clone();
+        let (group, shard) = router.find_shard(self.co_desc.clone(), &put.key)?;
         let mut client = GroupClient::new(
             group,
             self.client.inner.router.clone(),
@@ -381,16 +1637,17 @@ impl Collection {
         );
         let req = Request::Put(ShardPutRequest {
             shard_id: shard.id,
-            put: Some(PutRequest {
-                key: key.to_owned(),
-                value: value.to_owned(),
-            }),
+            put: Some(put.clone()),
         });
         if let Some(duration) = timeout {
             client.set_timeout(duration);
         }
-        client.request(&req).await?;
-        Ok(())
+        match client.request(&req).await? {
+            Response::Put(resp) => Ok(resp),
+            _ => Err(crate::Error::Internal(wrap(
+                "invalid response type, Put is required",
+            ))),
+        }
     }
 
     async fn get_inner(
@@ -422,6 +1679,92 @@ impl Collection {
         }
     }
 
+    /// Scan the shards overlapping `[start, end)`, returns the merged data and the key to resume
+    /// from, `None` if the range is exhausted.
+    async fn scan_inner(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+        timeout: Option<Duration>,
+    ) -> crate::Result<(Vec<ShardData>, Option<Vec<u8>>)> {
+        let router = self.client.inner.router.clone();
+        let shards = router.find_shards_in_range(self.co_desc.clone(), start, end)?;
+        let requests = shards.into_iter().map(|(group, shard)| {
+            let mut client = GroupClient::new(
+                group,
+                self.client.inner.router.clone(),
+                self.client.inner.conn_manager.clone(),
+            );
+            if let Some(duration) = timeout {
+                client.set_timeout(duration);
+            }
+            let (start, end) = match shard.partition {
+                Some(shard_desc::Partition::Range(range)) => {
+                    let start = std::cmp::max(start, range.start.as_slice()).to_owned();
+                    let end = if !range.end.is_empty()
+                        && (end.is_empty() || range.end.as_slice() < end)
+                    {
+                        range.end
+                    } else {
+                        end.to_owned()
+                    };
+                    (start, end)
+                }
+                _ => (start.to_owned(), end.to_owned()),
+            };
+            let req = Request::Scan(ShardScanRequest {
+                shard_id: shard.id,
+                start,
+                end,
+                limit: limit.try_into().unwrap_or(u32::MAX),
+            });
+            async move {
+                match client.request(&req).await? {
+                    Response::Scan(resp) => Ok(resp),
+                    _ => Err(crate::Error::Internal(wrap(
+                        "invalid response type, Scan is required",
+                    ))),
+                }
+            }
+        });
+        let responses = futures::future::try_join_all(requests).await?;
+        Ok(merge_scan_responses(responses, limit))
+    }
+
+    /// Read the key from the replica in the zone, `None` is returned if there is no such replica
+    /// or it fails to serve the read.
+    async fn stale_get_inner(&self, key: &[u8], zone: &str) -> Option<Option<Vec<u8>>> {
+        let router = self.client.inner.router.clone();
+        let (group, shard) = router.find_shard(self.co_desc.clone(), key).ok()?;
+        let replica = router.find_zone_replica(group.id, zone)?;
+        let mut client = GroupClient::new(
+            group,
+            self.client.inner.router.clone(),
+            self.client.inner.conn_manager.clone(),
+        );
+        let req = Request::Get(ShardGetRequest {
+            shard_id: shard.id,
+            get: Some(GetRequest {
+                key: key.to_owned(),
+            }),
+        });
+        if let Some(duration) = self.rpc_timeout {
+            client.set_timeout(duration);
+        }
+        match client.stale_read(replica.node_id, &req).await {
+            Ok(Response::Get(GetResponse { value })) => Some(value),
+            Ok(_) => None,
+            Err(err) => {
+                debug!(
+                    "stale read key from replica {} on node {}: {err}",
+                    replica.id, replica.node_id
+                );
+                None
+            }
+        }
+    }
+
     #[allow(dead_code)]
     fn name(&self) -> String {
         self.co_desc.name.to_owned()
@@ -438,3 +1781,194 @@ fn wrap(msg: &str) -> Box<dyn std::error::Error + Sync + Send + 'static> {
     let msg = String::from(msg);
     msg.into()
 }
+
+/// Merge the responses of shards in key order. The data after the smallest resume key are
+/// dropped, since the keys before them in other shards are not read yet.
+fn merge_scan_responses(
+    responses: Vec<ShardScanResponse>,
+    limit: usize,
+) -> (Vec<ShardData>, Option<Vec<u8>>) {
+    let mut resume_key = responses
+        .iter()
+        .filter(|r| !r.resume_key.is_empty())
+        .map(|r| r.resume_key.clone())
+        .min();
+    let mut data = responses
+        .into_iter()
+        .flat_map(|r| r.data)
+        .filter(|d| resume_key.as_ref().map(|r| &d.key < r).unwrap_or(true))
+        .collect::<Vec<_>>();
+    data.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+    if data.len() > limit {
+        resume_key = Some(data[limit].key.clone());
+        data.truncate(limit);
+    }
+    (data, resume_key)
+}
+
+/// Allocate `n` samples to shards in proportion to the number of their keys, with the largest
+/// remainder method.
+fn allocate_samples(n: usize, totals: &[u64]) -> Vec<usize> {
+    let sum: u64 = totals.iter().sum();
+    if sum == 0 {
+        return vec![0; totals.len()];
+    }
+    let n = std::cmp::min(n as u64, sum) as u128;
+    let mut quotas = Vec::with_capacity(totals.len());
+    let mut remainders = Vec::with_capacity(totals.len());
+    for (idx, &total) in totals.iter().enumerate() {
+        let share = n * total as u128;
+        quotas.push((share / sum as u128) as usize);
+        remainders.push((share % sum as u128, idx));
+    }
+    remainders.sort_unstable_by(|a, b| b.cmp(a));
+    let allocated = quotas.iter().sum::<usize>();
+    for (_, idx) in remainders.into_iter().take(n as usize - allocated) {
+        quotas[idx] += 1;
+    }
+    quotas
+}
+
+/// Merge the outcomes of the gets and the writes of a [`Batch`] back to the input order, each
+/// position tells whether the operation is a get and its index among the gets or the writes.
+fn reassemble_batch_outcomes(
+    positions: &[(bool, usize)],
+    gets: Vec<KeyOutcome>,
+    writes: Vec<KeyOutcome>,
+) -> Vec<KeyOutcome> {
+    let mut gets = gets.into_iter().map(Some).collect::<Vec<_>>();
+    let mut writes = writes.into_iter().map(Some).collect::<Vec<_>>();
+    positions
+        .iter()
+        .map(|&(is_get, idx)| {
+            let outcomes = if is_get { &mut gets } else { &mut writes };
+            outcomes[idx].take().expect("each outcome is taken once")
+        })
+        .collect()
+}
+
+/// Returns the first error of the outcomes if the batch is all-or-nothing.
+fn check_batch_outcomes(
+    outcomes: Vec<KeyOutcome>,
+    opts: &BatchOptions,
+) -> AppResult<Vec<KeyOutcome>> {
+    if !opts.all_or_nothing || outcomes.iter().all(KeyOutcome::is_ok) {
+        return Ok(outcomes);
+    }
+    for (idx, outcome) in outcomes.into_iter().enumerate() {
+        match outcome {
+            KeyOutcome::TimedOut => {
+                return Err(AppError::DeadlineExceeded(format!(
+                    "the key at {idx} of batch"
+                )));
+            }
+            KeyOutcome::Failed(err) => return Err(err),
+            _ => {}
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_or_nothing_batch_outcomes() {
+        let outcomes = || {
+            vec![
+                KeyOutcome::Written,
+                KeyOutcome::TimedOut,
+                KeyOutcome::Failed(AppError::KeyTooLarge(2, 1)),
+            ]
+        };
+        let partial = check_batch_outcomes(outcomes(), &BatchOptions::default()).unwrap();
+        assert_eq!(
+            partial.iter().map(KeyOutcome::is_ok).collect::<Vec<_>>(),
+            vec![true, false, false]
+        );
+
+        let opts = BatchOptions {
+            all_or_nothing: true,
+        };
+        assert!(matches!(
+            check_batch_outcomes(outcomes(), &opts),
+            Err(AppError::DeadlineExceeded(_))
+        ));
+        let finished = vec![KeyOutcome::NotFound, KeyOutcome::Value(vec![1])];
+        assert_eq!(check_batch_outcomes(finished, &opts).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn reassemble_batch_outcomes_in_input_order() {
+        let positions = [(false, 0), (true, 0), (true, 1), (false, 1)];
+        let gets = vec![KeyOutcome::Value(vec![1]), KeyOutcome::NotFound];
+        let writes = vec![KeyOutcome::Written, KeyOutcome::TimedOut];
+        let outcomes = reassemble_batch_outcomes(&positions, gets, writes);
+        assert!(matches!(
+            outcomes.as_slice(),
+            [
+                KeyOutcome::Written,
+                KeyOutcome::Value(v),
+                KeyOutcome::NotFound,
+                KeyOutcome::TimedOut
+            ] if v == &[1]
+        ));
+    }
+
+    #[test]
+    fn allocate_samples_by_shard_size() {
+        assert_eq!(allocate_samples(10, &[]), Vec::<usize>::new());
+        assert_eq!(allocate_samples(10, &[0, 0]), vec![0, 0]);
+        assert_eq!(allocate_samples(10, &[1, 2]), vec![1, 2]);
+        assert_eq!(allocate_samples(10, &[50, 50]), vec![5, 5]);
+        assert_eq!(allocate_samples(10, &[1, 1, 1]).iter().sum::<usize>(), 3);
+        assert_eq!(
+            allocate_samples(10, &[10, 10, 10]).iter().sum::<usize>(),
+            10
+        );
+        assert_eq!(allocate_samples(4, &[70, 20, 10]), vec![3, 1, 0]);
+    }
+
+    #[test]
+    fn merge_scan_responses_in_key_order() {
+        let response = |keys: &[&[u8]], resume_key: &[u8]| ShardScanResponse {
+            data: keys
+                .iter()
+                .map(|key| ShardData {
+                    key: key.to_vec(),
+                    value: key.to_vec(),
+                    version: 0,
+                })
+                .collect(),
+            resume_key: resume_key.to_vec(),
+        };
+        let keys = |data: &[ShardData]| data.iter().map(|d| d.key.clone()).collect::<Vec<_>>();
+
+        let (data, resume_key) = merge_scan_responses(
+            vec![response(&[b"b", b"d"], b""), response(&[b"a", b"c"], b"")],
+            10,
+        );
+        assert_eq!(
+            keys(&data),
+            vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]
+        );
+        assert!(resume_key.is_none());
+
+        // The keys after the smallest resume key are dropped.
+        let (data, resume_key) = merge_scan_responses(
+            vec![response(&[b"b", b"d"], b""), response(&[b"a"], b"c")],
+            10,
+        );
+        assert_eq!(keys(&data), vec![b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(resume_key, Some(b"c".to_vec()));
+
+        // The limit is applied after merging.
+        let (data, resume_key) = merge_scan_responses(
+            vec![response(&[b"b", b"d"], b""), response(&[b"a", b"c"], b"")],
+            3,
+        );
+        assert_eq!(data.len(), 3);
+        assert_eq!(resume_key, Some(b"d".to_vec()));
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, time::Duration};
+
+use engula_api::server::v1::{group_request_union::Request, *};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+use crate::{ConnManager, GroupClient, Router};
+
+/// The max number of writes coalesced into a batch.
+const MAX_BATCH_WRITES: usize = 256;
+
+/// A single put or delete waiting to be batched.
+#[derive(Debug)]
+pub enum Write {
+    Put(ShardPutRequest),
+    Delete(ShardDeleteRequest),
+}
+
+#[derive(Debug)]
+struct Task {
+    group_id: u64,
+    write: Write,
+    sender: oneshot::Sender<bool>,
+}
+
+/// The auto batcher coalesces the writes submitted within a small time window into per-group
+/// `BatchWrite` requests. Each write is completed with the result of the batch it belongs to.
+#[derive(Debug, Clone)]
+pub struct Batcher {
+    sender: mpsc::UnboundedSender<Task>,
+}
+
+impl Batcher {
+    pub fn new(
+        window: Duration,
+        timeout: Option<Duration>,
+        router: Router,
+        conn_manager: ConnManager,
+    ) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            collect_and_flush(receiver, window, timeout, router, conn_manager).await;
+        });
+        Batcher { sender }
+    }
+
+    /// Submit a write of the group and wait the batch it belongs to. Returns whether the write is
+    /// committed, the caller should resend the write alone if it is not, since a batch fails as a
+    /// whole, eg one of the shards is migrating.
+    pub async fn submit(&self, group_id: u64, write: Write) -> bool {
+        let (sender, receiver) = oneshot::channel();
+        let task = Task {
+            group_id,
+            write,
+            sender,
+        };
+        if self.sender.send(task).is_err() {
+            return false;
+        }
+        receiver.await.unwrap_or_default()
+    }
+}
+
+async fn collect_and_flush(
+    mut receiver: mpsc::UnboundedReceiver<Task>,
+    window: Duration,
+    timeout: Option<Duration>,
+    router: Router,
+    conn_manager: ConnManager,
+) {
+    while let Some(task) = receiver.recv().await {
+        let mut tasks = vec![task];
+        let deadline = tokio::time::sleep(window);
+        tokio::pin!(deadline);
+        while tasks.len() < MAX_BATCH_WRITES {
+            tokio::select! {
+                _ = &mut deadline => break,
+                task = receiver.recv() => match task {
+                    Some(task) => tasks.push(task),
+                    None => break,
+                },
+            }
+        }
+
+        for (group_id, tasks) in group_tasks(tasks) {
+            let mut client = GroupClient::lazy(group_id, router.clone(), conn_manager.clone());
+            if let Some(duration) = timeout {
+                client.set_timeout(duration);
+            }
+            tokio::spawn(async move {
+                flush(group_id, client, tasks).await;
+            });
+        }
+    }
+}
+
+fn group_tasks(tasks: Vec<Task>) -> HashMap<u64, Vec<Task>> {
+    let mut groups: HashMap<u64, Vec<Task>> = HashMap::default();
+    for task in tasks {
+        groups.entry(task.group_id).or_default().push(task);
+    }
+    groups
+}
+
+async fn flush(group_id: u64, mut client: GroupClient, tasks: Vec<Task>) {
+    let (req, senders) = build_batch_write(tasks);
+    let (num_puts, num_deletes) = (req.puts.len(), req.deletes.len());
+    let committed = match client.request(&Request::BatchWrite(req)).await {
+        Ok(_) => true,
+        Err(err) => {
+            debug!(
+                "group {group_id} batch with {num_puts} puts and {num_deletes} deletes: {err:?}"
+            );
+            false
+        }
+    };
+    for sender in senders {
+        let _ = sender.send(committed);
+    }
+}
+
+fn build_batch_write(tasks: Vec<Task>) -> (BatchWriteRequest, Vec<oneshot::Sender<bool>>) {
+    let mut req = BatchWriteRequest::default();
+    let mut senders = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        match task.write {
+            Write::Put(put) => req.puts.push(put),
+            Write::Delete(delete) => req.deletes.push(delete),
+        }
+        senders.push(task.sender);
+    }
+    (req, senders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_task(group_id: u64, key: &[u8]) -> (Task, oneshot::Receiver<bool>) {
+        let (sender, receiver) = oneshot::channel();
+        let write = Write::Put(ShardPutRequest {
+            shard_id: group_id,
+            put: Some(engula_api::v1::PutRequest {
+                key: key.to_owned(),
+                value: vec![],
+                ..Default::default()
+            }),
+        });
+        let task = Task {
+            group_id,
+            write,
+            sender,
+        };
+        (task, receiver)
+    }
+
+    #[test]
+    fn build_batch_per_group() {
+        let (t1, _r1) = put_task(1, b"a");
+        let (t2, _r2) = put_task(2, b"b");
+        let (t3, _r3) = put_task(1, b"c");
+        let (sender, _r4) = oneshot::channel();
+        let t4 = Task {
+            group_id: 1,
+            write: Write::Delete(ShardDeleteRequest {
+                shard_id: 1,
+                delete: Some(engula_api::v1::DeleteRequest { key: b"d".to_vec() }),
+            }),
+            sender,
+        };
+
+        let mut groups = group_tasks(vec![t1, t2, t3, t4]);
+        assert_eq!(groups.len(), 2);
+
+        let (req, senders) = build_batch_write(groups.remove(&1).unwrap());
+        assert_eq!(senders.len(), 3);
+        let keys = req
+            .puts
+            .iter()
+            .map(|p| p.put.as_ref().unwrap().key.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec![b"a".to_vec(), b"c".to_vec()]);
+        assert_eq!(req.deletes.len(), 1);
+
+        let (req, senders) = build_batch_write(groups.remove(&2).unwrap());
+        assert_eq!(senders.len(), 1);
+        assert_eq!(req.puts.len(), 1);
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::metrics::CLIENT_CACHE_TOTAL;
+
+/// A bounded LRU cache of the values read by client. The entries expire after the TTL of their
+/// collections, and they are invalidated by the writes issued by the same client.
+///
+/// There is no change stream of the values yet, so the writes of other clients are observed only
+/// after the cached entries expire, the readers must tolerate the staleness bounded by the TTL.
+#[derive(Debug)]
+pub struct Cache {
+    capacity: usize,
+    core: Mutex<Core>,
+}
+
+#[derive(Debug, Default)]
+struct Core {
+    tick: u64,
+    /// Increased by every invalidation, the values read before the invalidation are not filled,
+    /// since they might be overwritten.
+    generation: u64,
+    collections: HashMap<u64, HashMap<Vec<u8>, Entry>>,
+    /// The cached keys ordered by the tick of last access.
+    lru: BTreeMap<u64, (u64, Vec<u8>)>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// `None` means the key doesn't exist.
+    value: Option<Vec<u8>>,
+    expire_at: Instant,
+    tick: u64,
+}
+
+/// Invalidate the key or range of the cache when it is dropped, so the cached values are
+/// invalidated after the write finished, no matter it succeeds or not.
+pub struct InvalidateGuard<'a> {
+    cache: Option<&'a Cache>,
+    collection_id: u64,
+    start: &'a [u8],
+    /// `None` means a single key, an empty end means unbounded.
+    end: Option<&'a [u8]>,
+}
+
+impl Cache {
+    pub fn new(capacity: usize) -> Self {
+        Cache {
+            capacity,
+            core: Mutex::default(),
+        }
+    }
+
+    /// Returns the cached value, `Some(None)` means the key is cached as absent.
+    pub fn get(&self, collection_id: u64, key: &[u8]) -> Option<Option<Vec<u8>>> {
+        let mut core = self.core.lock().unwrap();
+        let core = &mut *core;
+        let entry = core
+            .collections
+            .get_mut(&collection_id)
+            .and_then(|entries| entries.get_mut(key));
+        let entry = match entry {
+            Some(entry) if entry.expire_at > Instant::now() => entry,
+            _ => {
+                CLIENT_CACHE_TOTAL.miss.inc();
+                return None;
+            }
+        };
+        CLIENT_CACHE_TOTAL.hit.inc();
+        core.tick += 1;
+        let lru_key = core.lru.remove(&entry.tick).expect("entry exists in lru");
+        entry.tick = core.tick;
+        core.lru.insert(entry.tick, lru_key);
+        Some(entry.value.clone())
+    }
+
+    /// Returns the current generation, which should be acquired before reading the value to fill.
+    pub fn generation(&self) -> u64 {
+        self.core.lock().unwrap().generation
+    }
+
+    /// Fill the value read at `generation`, it is ignored if any invalidation happened since then.
+    pub fn fill(
+        &self,
+        generation: u64,
+        collection_id: u64,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+        ttl: Duration,
+    ) {
+        let mut core = self.core.lock().unwrap();
+        if core.generation != generation || self.capacity == 0 {
+            return;
+        }
+
+        core.remove(collection_id, key);
+        while core.lru.len() >= self.capacity {
+            let (&tick, _) = core.lru.iter().next().expect("lru is not empty");
+            let (evicted_collection_id, evicted_key) = core.lru.remove(&tick).unwrap();
+            core.remove(evicted_collection_id, &evicted_key);
+            CLIENT_CACHE_TOTAL.evict.inc();
+        }
+
+        core.tick += 1;
+        let tick = core.tick;
+        core.lru.insert(tick, (collection_id, key.to_owned()));
+        core.collections.entry(collection_id).or_default().insert(
+            key.to_owned(),
+            Entry {
+                value,
+                expire_at: Instant::now() + ttl,
+                tick,
+            },
+        );
+    }
+
+    pub fn invalidate(&self, collection_id: u64, key: &[u8]) {
+        let mut core = self.core.lock().unwrap();
+        core.generation += 1;
+        core.remove(collection_id, key);
+    }
+
+    /// Invalidate the keys in range `[start, end)` of the collection, an empty `end` means
+    /// unbounded.
+    pub fn invalidate_range(&self, collection_id: u64, start: &[u8], end: &[u8]) {
+        let mut core = self.core.lock().unwrap();
+        core.generation += 1;
+        let keys = match core.collections.get(&collection_id) {
+            Some(entries) => entries
+                .keys()
+                .filter(|key| start <= key.as_slice() && (end.is_empty() || key.as_slice() < end))
+                .cloned()
+                .collect::<Vec<_>>(),
+            None => return,
+        };
+        for key in keys {
+            core.remove(collection_id, &key);
+        }
+    }
+
+    pub fn invalidate_collection(&self, collection_id: u64) {
+        let mut core = self.core.lock().unwrap();
+        core.generation += 1;
+        if let Some(entries) = core.collections.remove(&collection_id) {
+            for entry in entries.values() {
+                core.lru.remove(&entry.tick);
+            }
+        }
+    }
+
+    pub fn invalidate_all(&self) {
+        let mut core = self.core.lock().unwrap();
+        core.generation += 1;
+        core.collections.clear();
+        core.lru.clear();
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.core.lock().unwrap().lru.len()
+    }
+}
+
+impl Core {
+    fn remove(&mut self, collection_id: u64, key: &[u8]) {
+        if let Some(entries) = self.collections.get_mut(&collection_id) {
+            if let Some(entry) = entries.remove(key) {
+                self.lru.remove(&entry.tick);
+            }
+            if entries.is_empty() {
+                self.collections.remove(&collection_id);
+            }
+        }
+    }
+}
+
+impl<'a> InvalidateGuard<'a> {
+    pub fn key(cache: Option<&'a Cache>, collection_id: u64, key: &'a [u8]) -> Self {
+        InvalidateGuard {
+            cache,
+            collection_id,
+            start: key,
+            end: None,
+        }
+    }
+
+    pub fn range(
+        cache: Option<&'a Cache>,
+        collection_id: u64,
+        start: &'a [u8],
+        end: &'a [u8],
+    ) -> Self {
+        InvalidateGuard {
+            cache,
+            collection_id,
+            start,
+            end: Some(end),
+        }
+    }
+}
+
+impl<'a> Drop for InvalidateGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(cache) = self.cache {
+            match self.end {
+                None => cache.invalidate(self.collection_id, self.start),
+                Some(end) => cache.invalidate_range(self.collection_id, self.start, end),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn evict_least_recently_used() {
+        let cache = Cache::new(2);
+        cache.fill(0, 1, b"a", Some(b"1".to_vec()), TTL);
+        cache.fill(0, 1, b"b", None, TTL);
+        assert_eq!(cache.get(1, b"a"), Some(Some(b"1".to_vec())));
+        assert_eq!(cache.get(1, b"b"), Some(None));
+        assert_eq!(cache.get(2, b"a"), None);
+
+        // `a` is accessed before `b`.
+        cache.get(1, b"a");
+        cache.fill(0, 1, b"c", Some(b"3".to_vec()), TTL);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(1, b"b"), None);
+        assert!(cache.get(1, b"a").is_some());
+        assert!(cache.get(1, b"c").is_some());
+    }
+
+    #[test]
+    fn expire_after_ttl() {
+        let cache = Cache::new(2);
+        cache.fill(0, 1, b"a", Some(b"1".to_vec()), Duration::ZERO);
+        assert_eq!(cache.get(1, b"a"), None);
+    }
+
+    #[test]
+    fn skip_filling_stale_values() {
+        let cache = Cache::new(16);
+        let generation = cache.generation();
+        cache.invalidate(1, b"a");
+        cache.fill(generation, 1, b"a", Some(b"1".to_vec()), TTL);
+        assert_eq!(cache.get(1, b"a"), None);
+
+        let generation = cache.generation();
+        cache.fill(generation, 1, b"a", Some(b"1".to_vec()), TTL);
+        assert!(cache.get(1, b"a").is_some());
+    }
+
+    #[test]
+    fn invalidate_keys() {
+        let cache = Cache::new(16);
+        for key in [b"a", b"b", b"c", b"d"] {
+            cache.fill(0, 1, key, None, TTL);
+            cache.fill(0, 2, key, None, TTL);
+        }
+
+        {
+            let _guard = InvalidateGuard::key(Some(&cache), 1, b"a");
+            assert!(cache.get(1, b"a").is_some());
+        }
+        assert!(cache.get(1, b"a").is_none());
+
+        cache.invalidate_range(1, b"b", b"d");
+        assert!(cache.get(1, b"b").is_none());
+        assert!(cache.get(1, b"c").is_none());
+        assert!(cache.get(1, b"d").is_some());
+        cache.invalidate_range(1, b"", b"");
+        assert!(cache.get(1, b"d").is_none());
+
+        cache.invalidate_collection(2);
+        assert_eq!(cache.len(), 0);
+    }
+}
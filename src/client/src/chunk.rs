@@ -0,0 +1,173 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashSet,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub use engula_api::chunk::is_manifest;
+use engula_api::chunk::{chunk_key, MANIFEST_LEN, MANIFEST_MAGIC};
+
+/// A manifest record describes a large value which is split into chunks. Each chunk is saved in
+/// a separate chunk key, and the manifest is saved in the user key. The manifest is written after
+/// all chunks and removed before them, so readers never observe a partial value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// The unique id of this write, which isolates the chunks of different versions.
+    pub write_id: u64,
+    pub num_chunks: u32,
+    pub total_len: u64,
+    /// The crc32 checksum of the whole value.
+    pub checksum: u32,
+}
+
+impl Manifest {
+    /// Split the value into chunks and build the corresponding manifest.
+    pub fn split(value: &[u8], chunk_size: usize) -> (Manifest, Vec<&[u8]>) {
+        debug_assert!(chunk_size > 0);
+        let chunks = value.chunks(chunk_size).collect::<Vec<_>>();
+        let manifest = Manifest {
+            write_id: next_write_id(),
+            num_chunks: chunks.len() as u32,
+            total_len: value.len() as u64,
+            checksum: crc32fast::hash(value),
+        };
+        (manifest, chunks)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(MANIFEST_LEN);
+        buf.extend_from_slice(MANIFEST_MAGIC);
+        buf.extend_from_slice(&self.write_id.to_be_bytes());
+        buf.extend_from_slice(&self.num_chunks.to_be_bytes());
+        buf.extend_from_slice(&self.total_len.to_be_bytes());
+        buf.extend_from_slice(&self.checksum.to_be_bytes());
+        buf
+    }
+
+    /// Decode the manifest, `None` is returned if the value isn't a manifest record.
+    pub fn decode(value: &[u8]) -> Option<Manifest> {
+        if value.len() != MANIFEST_LEN || !is_manifest(value) {
+            return None;
+        }
+
+        let buf = &value[MANIFEST_MAGIC.len()..];
+        Some(Manifest {
+            write_id: u64::from_be_bytes(buf[..8].try_into().unwrap()),
+            num_chunks: u32::from_be_bytes(buf[8..12].try_into().unwrap()),
+            total_len: u64::from_be_bytes(buf[12..20].try_into().unwrap()),
+            checksum: u32::from_be_bytes(buf[20..24].try_into().unwrap()),
+        })
+    }
+
+    /// Returns the keys of all chunks.
+    pub fn chunk_keys(&self, key: &[u8]) -> Vec<Vec<u8>> {
+        (0..self.num_chunks)
+            .map(|idx| chunk_key(key, self.write_id, idx))
+            .collect()
+    }
+
+    /// Reassemble the chunks into the value, `None` is returned if chunks are corrupted.
+    pub fn assemble(&self, chunks: Vec<Vec<u8>>) -> Option<Vec<u8>> {
+        if chunks.len() != self.num_chunks as usize {
+            return None;
+        }
+        let mut value = Vec::with_capacity(self.total_len as usize);
+        for chunk in chunks {
+            value.extend_from_slice(&chunk);
+        }
+        if value.len() as u64 != self.total_len || crc32fast::hash(&value) != self.checksum {
+            return None;
+        }
+        Some(value)
+    }
+}
+
+/// The max number of keys in [`ManifestCache`].
+const MANIFEST_CACHE_CAPACITY: usize = 4096;
+
+/// The keys known to save manifests, because the manifests are written or read by this client.
+/// Puts of small values only read the replaced value to remove its chunks if the key is cached,
+/// the chunks missed are orphans and reclaimed by servers.
+#[derive(Debug, Default)]
+pub struct ManifestCache {
+    keys: Mutex<HashSet<Vec<u8>>>,
+}
+
+impl ManifestCache {
+    pub fn insert(&self, key: &[u8]) {
+        let mut keys = self.keys.lock().unwrap();
+        if keys.len() >= MANIFEST_CACHE_CAPACITY && !keys.contains(key) {
+            // Evict an arbitrary key, it only costs an orphan at most.
+            let evicted = keys.iter().next().cloned().unwrap();
+            keys.remove(&evicted);
+        }
+        keys.insert(key.to_owned());
+    }
+
+    /// Remove the key, returns whether the key is cached.
+    pub fn remove(&self, key: &[u8]) -> bool {
+        self.keys.lock().unwrap().remove(key)
+    }
+}
+
+/// The write id is the unix timestamp in nanoseconds, the servers rely on it to skip the chunks
+/// of inflight writes when reclaiming orphan chunks.
+fn next_write_id() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use engula_api::chunk::is_chunk_key;
+
+    use super::*;
+
+    #[test]
+    fn split_and_assemble() {
+        let value = (0..1000u32).map(|v| v as u8).collect::<Vec<_>>();
+        let (manifest, chunks) = Manifest::split(&value, 300);
+        assert_eq!(manifest.num_chunks, 4);
+        assert_eq!(chunks.last().unwrap().len(), 100);
+
+        let encoded = manifest.encode();
+        assert!(is_manifest(&encoded));
+        assert_eq!(Manifest::decode(&encoded), Some(manifest.clone()));
+        assert_eq!(manifest.chunk_keys(b"key").len(), 4);
+        assert!(manifest.chunk_keys(b"key").iter().all(|k| is_chunk_key(k)));
+        assert!(!is_chunk_key(b"key"));
+
+        let chunks = chunks
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        assert_eq!(manifest.assemble(chunks.clone()), Some(value));
+        assert!(manifest.assemble(chunks[..3].to_vec()).is_none());
+
+        let mut corrupted = chunks;
+        corrupted[0][0] ^= 0xFF;
+        assert!(manifest.assemble(corrupted).is_none());
+    }
+
+    #[test]
+    fn plain_value_is_not_manifest() {
+        assert!(Manifest::decode(b"value").is_none());
+        assert!(Manifest::decode(MANIFEST_MAGIC).is_none());
+    }
+}
@@ -20,11 +20,15 @@ use std::{
 use engula_api::server::v1::root_client::RootClient;
 use tonic::transport::{Channel, Endpoint};
 
-use crate::{Error, NodeClient, Result};
+use crate::{Error, NodeClient, Result, RetryPolicy};
 
 #[derive(Clone, Debug)]
 pub struct ConnManager {
     connect_timeout: Option<Duration>,
+    compress_threshold: Option<usize>,
+    retry_policy: RetryPolicy,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<Arc<crate::fault::FaultInjector>>,
     core: Arc<Mutex<Core>>,
 }
 
@@ -50,6 +54,31 @@ impl ConnManager {
         mgr
     }
 
+    /// Compress the node requests whose encoded size exceed the threshold with gzip, the
+    /// responses of these requests are compressed too if the node supports.
+    pub fn with_compression(mut self, threshold: usize) -> Self {
+        self.compress_threshold = Some(threshold);
+        self
+    }
+
+    /// The retry policy of the group clients built upon this manager.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    #[inline]
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Inject the faults into the group requests sent by the node clients.
+    #[cfg(feature = "fault-injection")]
+    pub fn with_fault_injector(mut self, faults: crate::fault::FaultInjector) -> Self {
+        self.faults = Some(Arc::new(faults));
+        self
+    }
+
     // TODO(walter) add tags
     pub fn get(&self, addr: String) -> Result<Channel> {
         let mut core = self.core.lock().unwrap();
@@ -79,7 +108,15 @@ impl ConnManager {
     #[inline]
     pub fn get_node_client(&self, addr: String) -> Result<NodeClient> {
         let channel = self.get(addr)?;
-        Ok(NodeClient::new(channel))
+        let client = match self.compress_threshold {
+            Some(threshold) => NodeClient::with_compression(channel, threshold),
+            None => NodeClient::new(channel),
+        };
+        #[cfg(feature = "fault-injection")]
+        if let Some(faults) = self.faults.clone() {
+            return Ok(client.with_fault_injector(faults));
+        }
+        Ok(client)
     }
 
     #[inline]
@@ -105,6 +142,10 @@ impl Default for ConnManager {
         ConnManager {
             core,
             connect_timeout: None,
+            compress_threshold: None,
+            retry_policy: RetryPolicy::default(),
+            #[cfg(feature = "fault-injection")]
+            faults: None,
         }
     }
 }
@@ -12,6 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::net::UdpSocket;
+use tracing::warn;
+
+/// The interval of resolving the seeds again.
+const SEED_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The timeout of resolving a seed.
+const SEED_RESOLVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+const DNS_PORT: u16 = 53;
+
+const DNS_TYPE_SRV: u16 = 33;
+
+const DNS_CLASS_IN: u16 = 1;
+
 #[crate::async_trait]
 pub trait ServiceDiscovery: Send + Sync {
     async fn list_nodes(&self) -> Vec<String>;
@@ -33,3 +56,329 @@ impl ServiceDiscovery for StaticServiceDiscovery {
         self.nodes.clone()
     }
 }
+
+/// Discovers the nodes from seeds, the supported seeds are:
+/// - `host:port`, the address is used as it is.
+/// - `dns://host:port`, the A/AAAA records of the host.
+/// - `dns+srv://_service._proto.name`, the targets and ports of the SRV records.
+/// - `http://host/path`, a seed URL which returns addresses separated by whitespaces or commas.
+///
+/// The resolved addresses are cached, and resolved again once they are older than
+/// [`SEED_REFRESH_INTERVAL`]. The last resolved addresses of a seed are used if it fails to
+/// resolve.
+pub struct SeedServiceDiscovery {
+    seeds: Vec<String>,
+    cache: Mutex<SeedCache>,
+}
+
+#[derive(Default)]
+struct SeedCache {
+    resolved_at: Option<Instant>,
+    nodes: Vec<Vec<String>>,
+}
+
+impl SeedServiceDiscovery {
+    pub fn new(seeds: Vec<String>) -> Self {
+        let nodes = vec![vec![]; seeds.len()];
+        SeedServiceDiscovery {
+            seeds,
+            cache: Mutex::new(SeedCache {
+                resolved_at: None,
+                nodes,
+            }),
+        }
+    }
+
+    /// Returns whether the address needs to be resolved.
+    pub fn is_seed(addr: &str) -> bool {
+        addr.contains("://")
+    }
+}
+
+#[crate::async_trait]
+impl ServiceDiscovery for SeedServiceDiscovery {
+    async fn list_nodes(&self) -> Vec<String> {
+        {
+            let cache = self.cache.lock().unwrap();
+            if let Some(resolved_at) = cache.resolved_at {
+                if resolved_at.elapsed() < SEED_REFRESH_INTERVAL {
+                    return cache.nodes.concat();
+                }
+            }
+        }
+
+        let mut resolved = Vec::with_capacity(self.seeds.len());
+        for seed in &self.seeds {
+            let result = tokio::time::timeout(SEED_RESOLVE_TIMEOUT, resolve_seed(seed)).await;
+            match result {
+                Ok(Ok(nodes)) => resolved.push(Some(nodes)),
+                Ok(Err(err)) => {
+                    warn!("resolve seed {seed}: {err}");
+                    resolved.push(None);
+                }
+                Err(_) => {
+                    warn!("resolve seed {seed}: timeout");
+                    resolved.push(None);
+                }
+            }
+        }
+
+        let mut cache = self.cache.lock().unwrap();
+        for (cached, nodes) in cache.nodes.iter_mut().zip(resolved) {
+            if let Some(nodes) = nodes {
+                *cached = nodes;
+            }
+        }
+        cache.resolved_at = Some(Instant::now());
+        cache.nodes.concat()
+    }
+}
+
+async fn resolve_seed(seed: &str) -> std::io::Result<Vec<String>> {
+    if let Some(host) = seed.strip_prefix("dns://") {
+        let addrs = tokio::net::lookup_host(host).await?;
+        Ok(addrs.map(|addr| addr.to_string()).collect())
+    } else if let Some(name) = seed.strip_prefix("dns+srv://") {
+        lookup_srv(name).await
+    } else if seed.starts_with("http://") {
+        fetch_seed_url(seed).await
+    } else if SeedServiceDiscovery::is_seed(seed) {
+        Err(invalid_data(format!("unknown scheme of seed {seed}")))
+    } else {
+        Ok(vec![seed.to_owned()])
+    }
+}
+
+async fn fetch_seed_url(url: &str) -> std::io::Result<Vec<String>> {
+    let uri = url
+        .parse::<hyper::Uri>()
+        .map_err(|err| invalid_data(err.to_string()))?;
+    let resp = hyper::Client::new()
+        .get(uri)
+        .await
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+    if !resp.status().is_success() {
+        return Err(invalid_data(format!("response status {}", resp.status())));
+    }
+    let body = hyper::body::to_bytes(resp.into_body())
+        .await
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+    let body = String::from_utf8_lossy(&body);
+    Ok(body
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|addr| !addr.is_empty())
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+/// Query the SRV records of the name from the first nameserver of `/etc/resolv.conf`, the
+/// targets are ordered by priority and weight.
+async fn lookup_srv(name: &str) -> std::io::Result<Vec<String>> {
+    let nameserver = std::fs::read_to_string("/etc/resolv.conf")
+        .ok()
+        .and_then(|conf| {
+            conf.lines()
+                .filter_map(|line| line.trim().strip_prefix("nameserver"))
+                .find_map(|ip| ip.trim().parse::<IpAddr>().ok())
+        })
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    let server = SocketAddr::new(nameserver, DNS_PORT);
+    let local: SocketAddr = if server.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+
+    let id = RandomState::new().build_hasher().finish() as u16;
+    let query = build_srv_query(id, name)?;
+    let socket = UdpSocket::bind(local).await?;
+    socket.send_to(&query, server).await?;
+    let mut buf = vec![0u8; 4096];
+    loop {
+        let (size, from) = socket.recv_from(&mut buf).await?;
+        if from == server && size >= 2 && u16::from_be_bytes([buf[0], buf[1]]) == id {
+            let mut records = parse_srv_response(&buf[..size])?;
+            records.sort_by_key(|r| (r.priority, std::cmp::Reverse(r.weight)));
+            return Ok(records
+                .into_iter()
+                .map(|r| format!("{}:{}", r.target, r.port))
+                .collect());
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct SrvRecord {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: String,
+}
+
+fn build_srv_query(id: u16, name: &str) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(12 + name.len() + 6);
+    buf.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired.
+    buf.extend_from_slice(&0x0100u16.to_be_bytes());
+    // One question, no answer, authority and additional records.
+    buf.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(invalid_data(format!("invalid domain name {name}")));
+        }
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+    buf.extend_from_slice(&DNS_TYPE_SRV.to_be_bytes());
+    buf.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+    Ok(buf)
+}
+
+fn parse_srv_response(packet: &[u8]) -> std::io::Result<Vec<SrvRecord>> {
+    let truncated = || invalid_data("truncated dns response".to_owned());
+    let read_u16 = |pos: usize| -> std::io::Result<u16> {
+        packet
+            .get(pos..pos + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(truncated)
+    };
+
+    let rcode = read_u16(2)? & 0x000F;
+    if rcode != 0 {
+        return Err(invalid_data(format!("dns response code {rcode}")));
+    }
+    let num_questions = read_u16(4)?;
+    let num_answers = read_u16(6)?;
+    let mut pos = 12;
+    for _ in 0..num_questions {
+        let (_, next) = read_name(packet, pos)?;
+        pos = next + 4;
+    }
+
+    let mut records = vec![];
+    for _ in 0..num_answers {
+        let (_, next) = read_name(packet, pos)?;
+        let rtype = read_u16(next)?;
+        let rdlen = read_u16(next + 8)? as usize;
+        let rdata = next + 10;
+        if packet.len() < rdata + rdlen {
+            return Err(truncated());
+        }
+        if rtype == DNS_TYPE_SRV {
+            let (target, _) = read_name(packet, rdata + 6)?;
+            records.push(SrvRecord {
+                priority: read_u16(rdata)?,
+                weight: read_u16(rdata + 2)?,
+                port: read_u16(rdata + 4)?,
+                target,
+            });
+        }
+        pos = rdata + rdlen;
+    }
+    Ok(records)
+}
+
+/// Read a domain name which might be compressed, returns the name and the position after it.
+fn read_name(packet: &[u8], mut pos: usize) -> std::io::Result<(String, usize)> {
+    const MAX_JUMPS: usize = 16;
+
+    let mut labels = vec![];
+    let mut end = None;
+    let mut jumps = 0;
+    loop {
+        let len = *packet
+            .get(pos)
+            .ok_or_else(|| invalid_data("truncated dns name".to_owned()))?
+            as usize;
+        if len & 0xC0 == 0xC0 {
+            let low = *packet
+                .get(pos + 1)
+                .ok_or_else(|| invalid_data("truncated dns name".to_owned()))?
+                as usize;
+            end.get_or_insert(pos + 2);
+            jumps += 1;
+            if jumps > MAX_JUMPS {
+                return Err(invalid_data("too many dns name pointers".to_owned()));
+            }
+            pos = ((len & 0x3F) << 8) | low;
+        } else if len == 0 {
+            let end = end.unwrap_or(pos + 1);
+            return Ok((labels.join("."), end));
+        } else {
+            let label = packet
+                .get(pos + 1..pos + 1 + len)
+                .ok_or_else(|| invalid_data("truncated dns name".to_owned()))?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            pos += 1 + len;
+        }
+    }
+}
+
+fn invalid_data(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_srv_records() {
+        let mut packet = build_srv_query(7, "_engula._tcp.example.com").unwrap();
+        // Set answer count to 2 and mark it as response.
+        packet[2] = 0x81;
+        packet[7] = 2;
+        let push_answer = |packet: &mut Vec<u8>, priority: u16, port: u16, target: &[u8]| {
+            // Pointer to the name of question.
+            packet.extend_from_slice(&[0xC0, 12]);
+            packet.extend_from_slice(&DNS_TYPE_SRV.to_be_bytes());
+            packet.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+            packet.extend_from_slice(&60u32.to_be_bytes());
+            packet.extend_from_slice(&((6 + target.len()) as u16).to_be_bytes());
+            packet.extend_from_slice(&priority.to_be_bytes());
+            packet.extend_from_slice(&10u16.to_be_bytes());
+            packet.extend_from_slice(&port.to_be_bytes());
+            packet.extend_from_slice(target);
+        };
+        push_answer(&mut packet, 2, 21805, b"\x05root1\xC0\x19");
+        push_answer(&mut packet, 1, 21806, b"\x05root2\x00");
+
+        let records = parse_srv_response(&packet).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                SrvRecord {
+                    priority: 2,
+                    weight: 10,
+                    port: 21805,
+                    target: "root1.example.com".to_owned(),
+                },
+                SrvRecord {
+                    priority: 1,
+                    weight: 10,
+                    port: 21806,
+                    target: "root2".to_owned(),
+                },
+            ]
+        );
+
+        // The truncated packet is rejected.
+        assert!(parse_srv_response(&packet[..packet.len() - 3]).is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_seeds() {
+        let discovery = SeedServiceDiscovery::new(vec![
+            "127.0.0.1:21805".to_owned(),
+            "dns://localhost:21806".to_owned(),
+            "unknown://localhost".to_owned(),
+        ]);
+        let nodes = discovery.list_nodes().await;
+        assert_eq!(nodes[0], "127.0.0.1:21805");
+        assert!(
+            nodes[1..].iter().all(|n| n.ends_with(":21806")),
+            "{nodes:?}"
+        );
+    }
+}
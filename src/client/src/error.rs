@@ -33,6 +33,19 @@ pub enum AppError {
     #[error("deadline exceeded {0}")]
     DeadlineExceeded(String),
 
+    #[error("key size {0} exceeds the limit {1}")]
+    KeyTooLarge(usize, usize),
+
+    #[error("value size {0} exceeds the limit {1}")]
+    ValueTooLarge(usize, usize),
+
+    #[error("batch size {0} exceeds the limit {1}")]
+    BatchTooLarge(usize, usize),
+
+    /// The condition of the write isn't satisfied, see [`engula_api::v1::WriteCondition`].
+    #[error("condition not met: {0}")]
+    ConditionNotMet(String),
+
     #[error("network: {0}")]
     Network(tonic::Status),
 
@@ -57,6 +70,9 @@ pub enum Error {
     #[error("{0} is exhausted")]
     ResourceExhausted(String),
 
+    #[error("condition not met: {0}")]
+    ConditionNotMet(String),
+
     #[error("group epoch not match")]
     EpochNotMatch(GroupDesc),
 
@@ -91,6 +107,53 @@ pub enum Error {
     Internal(Box<dyn StdError + Send + Sync + 'static>),
 }
 
+impl Error {
+    /// Returns whether the request could succeed by retrying, maybe after refreshing the route
+    /// (see [`Error::is_redirect`]), so callers don't need to inspect the error variants or the
+    /// messages of the status.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::EpochNotMatch(_)
+            | Error::GroupNotFound(_)
+            | Error::GroupNotAccessable(_)
+            | Error::NotRootLeader(..)
+            | Error::NotLeader(..)
+            | Error::Connect(_) => true,
+            Error::Rpc(status) => retryable_rpc_err(status),
+            _ => false,
+        }
+    }
+
+    /// Returns whether the request should be sent to another replica, the error carries the
+    /// descriptors to locate it.
+    pub fn is_redirect(&self) -> bool {
+        matches!(
+            self,
+            Error::EpochNotMatch(_) | Error::NotRootLeader(..) | Error::NotLeader(..)
+        )
+    }
+
+    /// Returns the id of the group which the error is about.
+    pub fn group_id(&self) -> Option<u64> {
+        match self {
+            Error::EpochNotMatch(desc) => Some(desc.id),
+            Error::GroupNotFound(group_id)
+            | Error::GroupNotAccessable(group_id)
+            | Error::NotLeader(group_id, ..) => Some(*group_id),
+            _ => None,
+        }
+    }
+}
+
+impl AppError {
+    /// Returns whether the operation could succeed by retrying later. The operation might have
+    /// been applied if the network is broken or the deadline is exceeded, but puts and deletes
+    /// are idempotent.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, AppError::Network(_) | AppError::DeadlineExceeded(_))
+    }
+}
+
 impl From<tonic::Status> for Error {
     fn from(status: tonic::Status) -> Self {
         use engula_api::server::v1;
@@ -105,6 +168,7 @@ impl From<tonic::Status> for Error {
             }
             Code::AlreadyExists => Error::AlreadyExists(status.message().into()),
             Code::ResourceExhausted => Error::ResourceExhausted(status.message().into()),
+            Code::FailedPrecondition => Error::ConditionNotMet(status.message().into()),
             Code::NotFound => Error::NotFound(status.message().into()),
             Code::Internal => Error::Internal(status.message().into()),
             Code::Unknown if !status.details().is_empty() => v1::Error::decode(status.details())
@@ -151,6 +215,7 @@ impl From<Error> for AppError {
             Error::DeadlineExceeded(v) => AppError::DeadlineExceeded(v),
             Error::NotFound(v) => AppError::NotFound(v),
             Error::AlreadyExists(v) => AppError::AlreadyExists(v),
+            Error::ConditionNotMet(v) => AppError::ConditionNotMet(v),
             Error::Internal(v) => AppError::Internal(v),
 
             Error::Transport(status) => AppError::Network(status),
@@ -176,6 +241,10 @@ impl From<AppError> for tonic::Status {
             AppError::AlreadyExists(msg) => Status::already_exists(msg),
             AppError::InvalidArgument(msg) => Status::invalid_argument(msg),
             AppError::DeadlineExceeded(msg) => Status::deadline_exceeded(msg),
+            AppError::ConditionNotMet(msg) => Status::failed_precondition(msg),
+            err @ (AppError::KeyTooLarge(..)
+            | AppError::ValueTooLarge(..)
+            | AppError::BatchTooLarge(..)) => Status::invalid_argument(err.to_string()),
             AppError::Network(status) => status, // as proxy
             AppError::Internal(err) => Status::internal(err.to_string()),
         }
@@ -263,3 +332,47 @@ pub fn transport_err(status: &tonic::Status) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use engula_api::server::v1::GroupDesc;
+    use prost::Message;
+
+    use super::*;
+
+    #[test]
+    fn error_taxonomy() {
+        let err = Error::NotLeader(1, 2, None);
+        assert!(err.is_retryable() && err.is_redirect());
+        assert_eq!(err.group_id(), Some(1));
+
+        let err = Error::EpochNotMatch(GroupDesc {
+            id: 3,
+            ..Default::default()
+        });
+        assert!(err.is_retryable() && err.is_redirect());
+        assert_eq!(err.group_id(), Some(3));
+
+        let err = Error::GroupNotAccessable(4);
+        assert!(err.is_retryable() && !err.is_redirect());
+        assert_eq!(err.group_id(), Some(4));
+
+        let err = Error::InvalidArgument("".into());
+        assert!(!err.is_retryable() && !err.is_redirect());
+        assert_eq!(err.group_id(), None);
+
+        // The details of group errors are decoded from status.
+        let detail = engula_api::server::v1::Error::not_leader(5, 1, None);
+        let status = tonic::Status::with_details(
+            tonic::Code::Unknown,
+            "not leader",
+            detail.encode_to_vec().into(),
+        );
+        let err = Error::from(status);
+        assert!(err.is_redirect());
+        assert_eq!(err.group_id(), Some(5));
+
+        assert!(AppError::Network(tonic::Status::unavailable("")).is_retryable());
+        assert!(!AppError::NotFound("".into()).is_retryable());
+    }
+}
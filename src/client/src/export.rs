@@ -0,0 +1,138 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export the scan results as Arrow record batches, and write them to Parquet files, so that
+//! the analytical pipelines could consume the data without custom converters.
+
+use std::{io::Write, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, BinaryBuilder},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use futures::{Stream, StreamExt};
+use parquet::arrow::ArrowWriter;
+
+use crate::{AppError, AppResult, Collection, ScanPartition};
+
+/// The column name of user keys.
+pub const KEY_COLUMN: &str = "key";
+
+/// The column name of values.
+pub const VALUE_COLUMN: &str = "value";
+
+/// Returns the schema of exported record batches.
+pub fn scan_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new(KEY_COLUMN, DataType::Binary, false),
+        Field::new(VALUE_COLUMN, DataType::Binary, false),
+    ]))
+}
+
+/// Convert the key value pairs into a record batch of [`scan_schema`].
+pub fn to_record_batch(kvs: &[(Vec<u8>, Vec<u8>)]) -> AppResult<RecordBatch> {
+    let key_bytes = kvs.iter().map(|(key, _)| key.len()).sum();
+    let value_bytes = kvs.iter().map(|(_, value)| value.len()).sum();
+    let mut keys = BinaryBuilder::with_capacity(kvs.len(), key_bytes);
+    let mut values = BinaryBuilder::with_capacity(kvs.len(), value_bytes);
+    for (key, value) in kvs {
+        keys.append_value(key);
+        values.append_value(value);
+    }
+    let columns: Vec<ArrayRef> = vec![Arc::new(keys.finish()), Arc::new(values.finish())];
+    RecordBatch::try_new(scan_schema(), columns).map_err(|e| AppError::Internal(Box::new(e)))
+}
+
+impl Collection {
+    /// Read the partition like [`Collection::scan_partition`], but each chunk of the scan is
+    /// returned as a record batch of [`scan_schema`].
+    pub async fn export_partition(
+        &self,
+        partition: &ScanPartition,
+    ) -> AppResult<impl Stream<Item = AppResult<RecordBatch>>> {
+        let chunks = self.scan_partition(partition).await?;
+        Ok(chunks.map(|kvs| to_record_batch(&kvs?)))
+    }
+}
+
+/// Write the record batches into a Parquet file, returns the number of rows written.
+pub async fn write_parquet<S, W>(batches: S, writer: W) -> AppResult<usize>
+where
+    S: Stream<Item = AppResult<RecordBatch>>,
+    W: Write,
+{
+    futures::pin_mut!(batches);
+    let mut writer = ArrowWriter::try_new(writer, scan_schema(), None)
+        .map_err(|e| AppError::Internal(Box::new(e)))?;
+    let mut rows = 0;
+    while let Some(batch) = batches.next().await {
+        let batch = batch?;
+        rows += batch.num_rows();
+        writer
+            .write(&batch)
+            .map_err(|e| AppError::Internal(Box::new(e)))?;
+    }
+    writer
+        .close()
+        .map_err(|e| AppError::Internal(Box::new(e)))?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::BinaryArray;
+
+    use super::*;
+
+    fn kvs() -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..16u8)
+            .map(|i| (vec![b'k', i], vec![b'v'; i as usize]))
+            .collect()
+    }
+
+    #[test]
+    fn convert_to_record_batch() {
+        let kvs = kvs();
+        let batch = to_record_batch(&kvs).unwrap();
+        assert_eq!(batch.schema(), scan_schema());
+        assert_eq!(batch.num_rows(), kvs.len());
+        let keys = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .unwrap();
+        let values = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .unwrap();
+        for (i, (key, value)) in kvs.iter().enumerate() {
+            assert_eq!(keys.value(i), key.as_slice());
+            assert_eq!(values.value(i), value.as_slice());
+        }
+    }
+
+    #[tokio::test]
+    async fn write_batches_to_parquet() {
+        let batches = vec![to_record_batch(&kvs()), to_record_batch(&[])];
+        let mut buf = Vec::new();
+        let rows = write_parquet(futures::stream::iter(batches), &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(rows, 16);
+        assert!(buf.starts_with(b"PAR1"));
+        assert!(buf.ends_with(b"PAR1"));
+    }
+}
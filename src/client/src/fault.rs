@@ -0,0 +1,276 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Inject errors and latency into the requests sent to nodes, so that applications could test
+//! their retry and idempotency logic against realistic failure modes. Only available with the
+//! `fault-injection` feature.
+//!
+//! ```no_run
+//! # async fn run() -> engula_client::AppResult<()> {
+//! use engula_client::{
+//!     fault::{FaultInjector, FaultRule, RpcKind},
+//!     ClientOptions, EngulaClient,
+//! };
+//!
+//! let faults = FaultInjector::new().with_rule(
+//!     RpcKind::Put,
+//!     FaultRule {
+//!         drop_response: 0.1,
+//!         duplicate: 0.05,
+//!         ..Default::default()
+//!     },
+//! );
+//! let addrs = vec!["127.0.0.1:21805".to_owned()];
+//! let client =
+//!     EngulaClient::new_with_fault_injector(ClientOptions::default(), addrs, faults).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::HashMap, future::Future, ops::Range, time::Duration};
+
+use engula_api::server::v1::{group_request_union::Request, BatchRequest, GroupResponse};
+use rand::Rng;
+use tonic::{Code, Status};
+
+/// The message of the injected errors.
+pub const INJECTED_FAULT: &str = "injected fault";
+
+/// The kind of the group requests, the faults are configured per kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RpcKind {
+    Get,
+    Put,
+    Delete,
+    DeleteRange,
+    Rename,
+    PrefixList,
+    Sample,
+    Scan,
+    BatchWrite,
+    /// The requests changing the metadata of groups, eg creating shards.
+    Admin,
+}
+
+/// The faults injected into a kind of requests, the probabilities are in `[0, 1]`.
+#[derive(Clone, Debug, Default)]
+pub struct FaultRule {
+    /// The probability that the request fails with `error_code` without being sent.
+    pub error_rate: f64,
+
+    /// The code of the injected errors.
+    ///
+    /// Default: `Unavailable`
+    pub error_code: Option<Code>,
+
+    /// The probability that the request is dropped before it is sent, so it fails with
+    /// `Unavailable` and has no effect.
+    pub drop_request: f64,
+
+    /// The probability that the response is dropped after the request is executed, so it fails
+    /// with `Unavailable` although it took effect.
+    pub drop_response: f64,
+
+    /// The probability that the request is sent twice, the response of the latter is returned.
+    pub duplicate: f64,
+
+    /// The latency added before sending the request, uniformly distributed in the range.
+    pub delay: Option<Range<Duration>>,
+
+    /// The probability that the response is held for up to `reorder_window`, so that the
+    /// responses of the requests sent later might be returned first.
+    pub reorder: f64,
+
+    pub reorder_window: Duration,
+}
+
+/// The faults injected into the requests sent to nodes.
+#[derive(Clone, Debug, Default)]
+pub struct FaultInjector {
+    rules: HashMap<RpcKind, FaultRule>,
+    default_rule: Option<FaultRule>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        FaultInjector::default()
+    }
+
+    /// Inject the faults into the requests of `kind`.
+    pub fn with_rule(mut self, kind: RpcKind, rule: FaultRule) -> Self {
+        self.rules.insert(kind, rule);
+        self
+    }
+
+    /// Inject the faults into the requests whose kind has no rule.
+    pub fn with_default_rule(mut self, rule: FaultRule) -> Self {
+        self.default_rule = Some(rule);
+        self
+    }
+
+    fn rule(&self, req: &BatchRequest) -> Option<&FaultRule> {
+        let kind = req
+            .requests
+            .first()
+            .and_then(|r| r.request.as_ref())
+            .and_then(|r| r.request.as_ref())
+            .map(rpc_kind)?;
+        self.rules.get(&kind).or(self.default_rule.as_ref())
+    }
+
+    pub(crate) async fn inject<F, Fut>(
+        &self,
+        req: tonic::Request<BatchRequest>,
+        send: F,
+    ) -> Result<Vec<GroupResponse>, Status>
+    where
+        F: Fn(tonic::Request<BatchRequest>) -> Fut,
+        Fut: Future<Output = Result<Vec<GroupResponse>, Status>>,
+    {
+        let rule = match self.rule(req.get_ref()) {
+            Some(rule) => rule,
+            None => return send(req).await,
+        };
+
+        if let Some(delay) = rule.delay.as_ref().filter(|r| !r.is_empty()) {
+            let delay = rand::thread_rng().gen_range(delay.clone());
+            tokio::time::sleep(delay).await;
+        }
+        if chance(rule.error_rate) {
+            let code = rule.error_code.unwrap_or(Code::Unavailable);
+            return Err(Status::new(code, INJECTED_FAULT));
+        }
+        if chance(rule.drop_request) {
+            return Err(Status::unavailable(INJECTED_FAULT));
+        }
+        if chance(rule.duplicate) {
+            let mut duplicated = tonic::Request::new(req.get_ref().clone());
+            *duplicated.metadata_mut() = req.metadata().clone();
+            let _ = send(duplicated).await;
+        }
+        let resp = send(req).await;
+        if chance(rule.drop_response) {
+            return Err(Status::unavailable(INJECTED_FAULT));
+        }
+        if !rule.reorder_window.is_zero() && chance(rule.reorder) {
+            let hold = rand::thread_rng().gen_range(Duration::ZERO..=rule.reorder_window);
+            tokio::time::sleep(hold).await;
+        }
+        resp
+    }
+}
+
+fn chance(probability: f64) -> bool {
+    probability > 0.0 && rand::thread_rng().gen_bool(probability.min(1.0))
+}
+
+fn rpc_kind(req: &Request) -> RpcKind {
+    match req {
+        Request::Get(_) => RpcKind::Get,
+        Request::Put(_) => RpcKind::Put,
+        Request::Delete(_) => RpcKind::Delete,
+        Request::DeleteRange(_) => RpcKind::DeleteRange,
+        Request::Rename(_) => RpcKind::Rename,
+        Request::PrefixList(_) => RpcKind::PrefixList,
+        Request::Sample(_) => RpcKind::Sample,
+        Request::Scan(_) => RpcKind::Scan,
+        Request::BatchWrite(_) => RpcKind::BatchWrite,
+        _ => RpcKind::Admin,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use engula_api::{server::v1::*, v1::PutRequest};
+
+    use super::*;
+
+    fn put_request() -> tonic::Request<BatchRequest> {
+        let put = ShardPutRequest {
+            shard_id: 1,
+            put: Some(PutRequest {
+                key: b"key".to_vec(),
+                value: b"value".to_vec(),
+                ..Default::default()
+            }),
+        };
+        tonic::Request::new(BatchRequest {
+            node_id: 1,
+            requests: vec![GroupRequest {
+                group_id: 1,
+                epoch: 1,
+                stale_read: false,
+                request: Some(GroupRequestUnion {
+                    request: Some(Request::Put(put)),
+                }),
+            }],
+        })
+    }
+
+    async fn send_with(faults: &FaultInjector) -> (Result<Vec<GroupResponse>, Status>, usize) {
+        let sent = AtomicUsize::new(0);
+        let result = faults
+            .inject(put_request(), |_| {
+                sent.fetch_add(1, Ordering::SeqCst);
+                async { Ok(vec![]) }
+            })
+            .await;
+        (result, sent.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn inject_faults_per_rpc_kind() {
+        let rule = |f: fn(&mut FaultRule)| {
+            let mut rule = FaultRule::default();
+            f(&mut rule);
+            FaultInjector::new().with_rule(RpcKind::Put, rule)
+        };
+
+        let (result, sent) = send_with(&FaultInjector::new()).await;
+        assert!(result.is_ok());
+        assert_eq!(sent, 1);
+
+        // The rules of other kinds don't apply.
+        let faults = FaultInjector::new().with_rule(
+            RpcKind::Get,
+            FaultRule {
+                drop_request: 1.0,
+                ..Default::default()
+            },
+        );
+        assert!(send_with(&faults).await.0.is_ok());
+
+        let (result, sent) = send_with(&rule(|r| {
+            r.error_rate = 1.0;
+            r.error_code = Some(Code::ResourceExhausted);
+        }))
+        .await;
+        assert_eq!(result.unwrap_err().code(), Code::ResourceExhausted);
+        assert_eq!(sent, 0);
+
+        let (result, sent) = send_with(&rule(|r| r.drop_request = 1.0)).await;
+        assert_eq!(result.unwrap_err().code(), Code::Unavailable);
+        assert_eq!(sent, 0);
+
+        let (result, sent) = send_with(&rule(|r| r.drop_response = 1.0)).await;
+        assert_eq!(result.unwrap_err().message(), INJECTED_FAULT);
+        assert_eq!(sent, 1);
+
+        let (result, sent) = send_with(&rule(|r| r.duplicate = 1.0)).await;
+        assert!(result.is_ok());
+        assert_eq!(sent, 2);
+    }
+}
@@ -137,10 +137,12 @@ impl GroupClient {
         }
         self.next_access_index = 0;
 
-        let deadline = self
-            .timeout
-            .take()
-            .map(|duration| Instant::now() + duration);
+        let policy = self.conn_manager.retry_policy().clone();
+        let deadline = policy.deadline(
+            self.timeout
+                .take()
+                .map(|duration| Instant::now() + duration),
+        );
         let mut index = 0;
         let group_id = self.group_id;
         while let Some((node_id, client)) = self.recommend_client() {
@@ -162,6 +164,9 @@ impl GroupClient {
             {
                 return Err(Error::DeadlineExceeded("issue rpc".to_owned()));
             }
+            if !policy.allow_attempt(index as u32) {
+                break;
+            }
             GROUP_CLIENT_RETRY_TOTAL.inc();
         }
 
@@ -372,6 +377,7 @@ impl GroupClient {
                 requests: vec![GroupRequest {
                     group_id: ctx.group_id,
                     epoch: ctx.epoch,
+                    stale_read: false,
                     request: Some(GroupRequestUnion {
                         request: Some(request.clone()),
                     }),
@@ -395,6 +401,39 @@ impl GroupClient {
         self.invoke_with_opt(op, opt).await
     }
 
+    /// Issue the read-only request to the replica on the node directly, which might be a follower
+    /// or learner. The replica serves it with the local state without checking the leadership,
+    /// so the result might be stale. No retry is taken, the callers are expected to fall back to
+    /// [`GroupClient::request`].
+    pub async fn stale_read(&mut self, node_id: u64, request: &Request) -> Result<Response> {
+        debug_assert!(is_read_only_request(request));
+        if self.epoch == 0 {
+            self.initial_group_state()?;
+        }
+        let client = self
+            .fetch_client(node_id)
+            .ok_or(Error::GroupNotAccessable(self.group_id))?;
+        let latency = take_group_request_metrics(request);
+        record_latency_opt!(latency);
+        let req = BatchRequest {
+            node_id,
+            requests: vec![GroupRequest {
+                group_id: self.group_id,
+                epoch: self.epoch,
+                stale_read: true,
+                request: Some(GroupRequestUnion {
+                    request: Some(request.clone()),
+                }),
+            }],
+        };
+        let resp = client
+            .batch_group_requests(RpcTimeout::new(self.timeout, req))
+            .await
+            .and_then(Self::batch_response)
+            .and_then(Self::group_response)?;
+        Ok(resp)
+    }
+
     fn batch_response<T>(mut resps: Vec<T>) -> std::result::Result<T, Status> {
         if resps.is_empty() {
             Err(Status::internal(
@@ -680,6 +719,31 @@ impl GroupClient {
         };
         self.invoke_with_opt(op, opt).await
     }
+
+    /// Subscribe the changes of the key, or the keys with the prefix, from the leader of the
+    /// group.
+    pub async fn watch_key(
+        &mut self,
+        shard_id: u64,
+        key: &[u8],
+        prefix: bool,
+    ) -> Result<tonic::Streaming<WatchKeyResponse>> {
+        let group_id = self.group_id;
+        let op = |_: InvokeContext, client: NodeClient| {
+            let request = WatchKeyRequest {
+                group_id,
+                shard_id,
+                key: key.to_owned(),
+                prefix,
+            };
+            async move { client.watch_key(request).await }
+        };
+        let opt = InvokeOpt {
+            ignore_transport_error: true,
+            ..Default::default()
+        };
+        self.invoke_with_opt(op, opt).await
+    }
 }
 
 impl RetryableShardChunkStreaming {
@@ -726,7 +790,10 @@ impl futures::Stream for RetryableShardChunkStreaming {
 
 #[inline]
 fn is_read_only_request(request: &Request) -> bool {
-    matches!(request, Request::Get(_) | Request::PrefixList(_))
+    matches!(
+        request,
+        Request::Get(_) | Request::PrefixList(_) | Request::Scan(_) | Request::Sample(_)
+    )
 }
 
 fn is_executable(descriptor: &GroupDesc, request: &Request) -> bool {
@@ -741,6 +808,8 @@ fn is_executable(descriptor: &GroupDesc, request: &Request) -> bool {
             is_target_shard_exists(descriptor, req.shard_id, &req.delete.as_ref().unwrap().key)
         }
         Request::PrefixList(req) => is_target_shard_exists(descriptor, req.shard_id, &req.prefix),
+        Request::Scan(req) => descriptor.shards.iter().any(|s| s.id == req.shard_id),
+        Request::Sample(req) => descriptor.shards.iter().any(|s| s.id == req.shard_id),
         _ => false,
     }
 }
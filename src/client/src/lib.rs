@@ -15,9 +15,16 @@
 #![feature(map_try_insert)]
 
 mod app_client;
+mod batcher;
+mod cache;
+mod chunk;
 mod conn_manager;
 mod discovery;
 pub mod error;
+#[cfg(feature = "export")]
+pub mod export;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
 mod group_client;
 mod metrics;
 mod migrate_client;
@@ -26,16 +33,22 @@ mod retry;
 mod root_client;
 mod router;
 mod shard_client;
+mod watch;
 
-pub use app_client::{Client as EngulaClient, ClientOptions, Collection, Database, Partition};
+pub use app_client::{
+    Batch, BatchOptions, BatchWriteOp, Client as EngulaClient, ClientOptions, Collection,
+    CollectionOptions, Database, KeyOutcome, Partition, ReadConsistency, ScanPartition,
+};
 pub use conn_manager::ConnManager;
-pub use discovery::{ServiceDiscovery, StaticServiceDiscovery};
+pub use discovery::{SeedServiceDiscovery, ServiceDiscovery, StaticServiceDiscovery};
+pub use engula_api::keys;
 pub use error::{AppError, AppResult, Error, Result};
 pub use group_client::{GroupClient, RetryableShardChunkStreaming};
 pub use migrate_client::MigrateClient;
 pub use node_client::{Client as NodeClient, RequestBatchBuilder, RpcTimeout};
-pub use retry::RetryState;
+pub use retry::{Backoff, RetryPolicy, RetryState};
 pub use root_client::{AdminRequestBuilder, AdminResponseExtractor, Client as RootClient};
-pub use router::{Router, RouterGroupState};
+pub use router::{LeaderChange, Router, RouterGroupState};
 pub use shard_client::ShardClient;
 use tonic::async_trait;
+pub use watch::{KeyWatcher, WatchEvent};
@@ -23,7 +23,11 @@ make_static_metric! {
             get,
             put,
             delete,
+            delete_range,
+            rename,
             list,
+            scan,
+            sample,
             transfer,
             batch_write,
             accept_shard,
@@ -37,7 +41,11 @@ make_static_metric! {
             get,
             put,
             delete,
+            delete_range,
+            rename,
             list,
+            scan,
+            sample,
             transfer,
             batch_write,
             accept_shard,
@@ -93,10 +101,26 @@ pub fn take_group_request_metrics(
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.delete.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.delete)
         }
+        Request::DeleteRange(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.delete_range.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.delete_range)
+        }
+        Request::Rename(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.rename.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.rename)
+        }
         Request::PrefixList(_) => {
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.list.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.list)
         }
+        Request::Scan(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.scan.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.scan)
+        }
+        Request::Sample(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.sample.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.sample)
+        }
         Request::BatchWrite(_) => {
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.batch_write.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.batch_write)
@@ -130,6 +154,12 @@ make_static_metric! {
             get,
             put,
             delete,
+            scan,
+            delete_range,
+            rename,
+            sample,
+            batch_get,
+            batch_write,
         }
     }
     pub struct DatabaseRequestDuration: Histogram {
@@ -137,6 +167,12 @@ make_static_metric! {
             get,
             put,
             delete,
+            scan,
+            delete_range,
+            rename,
+            sample,
+            batch_get,
+            batch_write,
         }
     }
     pub struct DatabaseBytesTotal: IntCounter {
@@ -145,6 +181,13 @@ make_static_metric! {
             tx,
         }
     }
+    pub struct CacheTotal: IntCounter {
+        "type" => {
+            hit,
+            miss,
+            evict,
+        }
+    }
 }
 
 lazy_static! {
@@ -174,6 +217,21 @@ lazy_static! {
     .unwrap();
     pub static ref CLIENT_DATABASE_BYTES_TOTAL: DatabaseBytesTotal =
         DatabaseBytesTotal::from(&CLIENT_DATABASE_BYTES_TOTAL_VEC);
+    pub static ref CLIENT_DATABASE_CHUNK_TOTAL_VEC: IntCounterVec = register_int_counter_vec!(
+        "client_database_chunk_total",
+        "The total chunk requests of large values of client",
+        &["type"]
+    )
+    .unwrap();
+    pub static ref CLIENT_DATABASE_CHUNK_TOTAL: DatabaseRequestTotal =
+        DatabaseRequestTotal::from(&CLIENT_DATABASE_CHUNK_TOTAL_VEC);
+    pub static ref CLIENT_CACHE_TOTAL_VEC: IntCounterVec = register_int_counter_vec!(
+        "client_cache_total",
+        "The total lookups and evictions of the client-side cache",
+        &["type"]
+    )
+    .unwrap();
+    pub static ref CLIENT_CACHE_TOTAL: CacheTotal = CacheTotal::from(&CLIENT_CACHE_TOTAL_VEC);
 }
 
 #[macro_export]
@@ -34,7 +34,7 @@ impl MigrateClient {
         }
     }
     pub async fn setup_migration(&mut self, desc: &MigrationDesc) -> Result<MigrateResponse> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = RetryState::with_policy(self.conn_manager.retry_policy(), None);
 
         loop {
             let mut client = self.group_client();
@@ -49,7 +49,7 @@ impl MigrateClient {
     }
 
     pub async fn commit_migration(&mut self, desc: &MigrationDesc) -> Result<MigrateResponse> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = RetryState::with_policy(self.conn_manager.retry_policy(), None);
 
         loop {
             let mut client = self.group_client();
@@ -67,7 +67,7 @@ impl MigrateClient {
         shard_id: u64,
         last_key: Vec<u8>,
     ) -> Result<RetryableShardChunkStreaming> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = RetryState::with_policy(self.conn_manager.retry_policy(), None);
 
         loop {
             let client = self.group_client();
@@ -81,7 +81,7 @@ impl MigrateClient {
     }
 
     pub async fn forward(&mut self, req: &ForwardRequest) -> Result<ForwardResponse> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = RetryState::with_policy(self.conn_manager.retry_policy(), None);
 
         loop {
             let mut client = self.group_client();
@@ -12,28 +12,73 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "fault-injection")]
+use std::sync::Arc;
 use std::time::Duration;
 
 use engula_api::{server::v1::*, v1::*};
 use prost::Message;
-use tonic::{transport::Channel, IntoRequest};
+use tonic::{codec::CompressionEncoding, transport::Channel, IntoRequest};
+
+#[cfg(feature = "fault-injection")]
+use crate::fault::FaultInjector;
 
 #[derive(Debug, Clone)]
 pub struct Client {
     client: node_client::NodeClient<Channel>,
+    /// The client used to send requests larger than the threshold, with gzip compression.
+    compressed: Option<(usize, node_client::NodeClient<Channel>)>,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<Arc<FaultInjector>>,
 }
 
 impl Client {
     pub fn new(channel: Channel) -> Self {
         Client {
             client: node_client::NodeClient::new(channel),
+            compressed: None,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
+        }
+    }
+
+    /// Build a client which compresses the requests whose encoded size exceed the threshold.
+    pub fn with_compression(channel: Channel, threshold: usize) -> Self {
+        let compressed = node_client::NodeClient::new(channel.clone())
+            .send_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Gzip);
+        Client {
+            client: node_client::NodeClient::new(channel),
+            compressed: Some((threshold, compressed)),
+            #[cfg(feature = "fault-injection")]
+            faults: None,
         }
     }
 
     pub async fn connect(addr: String) -> Result<Self, tonic::transport::Error> {
         let addr = format!("http://{}", addr);
         let client = node_client::NodeClient::connect(addr).await?;
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            compressed: None,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
+        })
+    }
+
+    /// Inject the faults into the group requests sent by this client.
+    #[cfg(feature = "fault-injection")]
+    pub fn with_fault_injector(mut self, faults: Arc<FaultInjector>) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Select the compressed client if the request is large enough.
+    fn select_client(&self, encoded_len: usize) -> node_client::NodeClient<Channel> {
+        match &self.compressed {
+            Some((threshold, client)) if encoded_len >= *threshold => client.clone(),
+            _ => self.client.clone(),
+        }
     }
 
     pub async fn get_root(&self) -> Result<RootDesc, tonic::Status> {
@@ -77,7 +122,19 @@ impl Client {
         &self,
         req: impl IntoRequest<BatchRequest>,
     ) -> Result<Vec<GroupResponse>, tonic::Status> {
-        let mut client = self.client.clone();
+        let req = req.into_request();
+        #[cfg(feature = "fault-injection")]
+        if let Some(faults) = self.faults.as_ref() {
+            return faults.inject(req, |req| self.send_batch(req)).await;
+        }
+        self.send_batch(req).await
+    }
+
+    async fn send_batch(
+        &self,
+        req: tonic::Request<BatchRequest>,
+    ) -> Result<Vec<GroupResponse>, tonic::Status> {
+        let mut client = self.select_client(req.get_ref().encoded_len());
         let res = client.batch(req).await?;
         Ok(res.into_inner().responses)
     }
@@ -95,13 +152,14 @@ impl Client {
         &self,
         req: PullRequest,
     ) -> Result<tonic::Streaming<ShardChunk>, tonic::Status> {
-        let mut client = self.client.clone();
+        // The shard chunks are always large, so the compressed client is selected if exists.
+        let mut client = self.select_client(usize::MAX);
         let res = client.pull(req).await?;
         Ok(res.into_inner())
     }
 
     pub async fn forward(&self, req: ForwardRequest) -> Result<ForwardResponse, tonic::Status> {
-        let mut client = self.client.clone();
+        let mut client = self.select_client(req.encoded_len());
         let res = client.forward(req).await?;
         Ok(res.into_inner())
     }
@@ -111,6 +169,24 @@ impl Client {
         let res = client.migrate(req).await?;
         Ok(res.into_inner())
     }
+
+    pub async fn collect_checksum(
+        &self,
+        req: CollectChecksumRequest,
+    ) -> Result<CollectChecksumResponse, tonic::Status> {
+        let mut client = self.client.clone();
+        let res = client.collect_checksum(req).await?;
+        Ok(res.into_inner())
+    }
+
+    pub async fn watch_key(
+        &self,
+        req: WatchKeyRequest,
+    ) -> Result<tonic::Streaming<WatchKeyResponse>, tonic::Status> {
+        let mut client = self.client.clone();
+        let res = client.watch_key(req).await?;
+        Ok(res.into_inner())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -131,6 +207,7 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::Get(ShardGetRequest {
                     shard_id,
@@ -152,10 +229,15 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::Put(ShardPutRequest {
                     shard_id,
-                    put: Some(PutRequest { key, value }),
+                    put: Some(PutRequest {
+                        key,
+                        value,
+                        ..Default::default()
+                    }),
                 })),
             }),
         });
@@ -166,6 +248,7 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::Delete(ShardDeleteRequest {
                     shard_id,
@@ -180,6 +263,7 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::CreateShard(
                     CreateShardRequest {
@@ -205,6 +289,7 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::ChangeReplicas(
                     change_replicas,
@@ -228,6 +313,7 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::ChangeReplicas(
                     change_replicas,
@@ -251,6 +337,7 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::ChangeReplicas(
                     change_replicas,
@@ -271,6 +358,7 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::AcceptShard(
                     AcceptShardRequest {
@@ -288,6 +376,7 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::Transfer(TransferRequest {
                     transferee,
@@ -301,6 +390,7 @@ impl RequestBatchBuilder {
         self.requests.push(GroupRequest {
             group_id,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::PrefixList(
                     ShardPrefixListRequest {
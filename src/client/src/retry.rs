@@ -14,13 +14,130 @@
 
 use std::time::{Duration, Instant};
 
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
 use crate::{Error, Result};
 
-pub struct RetryState {
-    interval_ms: u64,
+/// The policy of retrying the retryable errors, eg. the stale routes of clients, or the busy
+/// replicas of servers.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// The max attempts of an operation, including the first one, 0 means unlimited.
+    ///
+    /// Default: 0
+    pub max_attempts: u32,
+
+    /// The interval before the first retry, in microseconds. The intervals grow exponentially,
+    /// and are jittered in `[interval / 2, interval]`.
+    ///
+    /// Default: 8ms
+    pub initial_backoff_us: u64,
+
+    /// The upper bound of retry intervals, in microseconds.
+    ///
+    /// Default: 250ms
+    pub max_backoff_us: u64,
+
+    /// Retrying stops once an operation has taken this long, in milliseconds, 0 means no limit
+    /// besides the timeout of the operation.
+    ///
+    /// Default: 0
+    pub deadline_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 0,
+            initial_backoff_us: 8_000,
+            max_backoff_us: 250_000,
+            deadline_ms: 0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the deadline of an operation started now, the earlier one of the given deadline
+    /// and the one of this policy.
+    pub fn deadline(&self, deadline: Option<Instant>) -> Option<Instant> {
+        let policy_deadline = match self.deadline_ms {
+            0 => None,
+            ms => Instant::now().checked_add(Duration::from_millis(ms)),
+        };
+        match (deadline, policy_deadline) {
+            (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Whether the attempts of an operation is not exhausted.
+    pub fn allow_attempt(&self, attempts: u32) -> bool {
+        self.max_attempts == 0 || attempts < self.max_attempts
+    }
+}
+
+/// An exponential backoff with jitter, the intervals are capped by the deadline, so that the
+/// retries of overloaded replicas are scattered instead of in lockstep.
+#[derive(Debug)]
+pub struct Backoff {
+    policy: RetryPolicy,
+    interval: Duration,
+    attempts: u32,
     deadline: Option<Instant>,
 }
 
+impl Backoff {
+    pub fn new(policy: &RetryPolicy, deadline: Option<Instant>) -> Self {
+        Backoff {
+            policy: policy.clone(),
+            interval: Duration::from_micros(policy.initial_backoff_us),
+            attempts: 1,
+            deadline: policy.deadline(deadline),
+        }
+    }
+
+    #[inline]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Whether the deadline is exceeded.
+    pub fn is_expired(&self) -> bool {
+        self.deadline
+            .map(|deadline| deadline <= Instant::now())
+            .unwrap_or_default()
+    }
+
+    /// Returns the duration to sleep before next retry, `None` is returned if the attempts are
+    /// exhausted or the deadline is exceeded.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if !self.policy.allow_attempt(self.attempts) {
+            return None;
+        }
+        self.attempts += 1;
+
+        let half = self.interval.as_micros() as u64 / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half);
+        let mut delay = Duration::from_micros(half + jitter);
+        let max_backoff = Duration::from_micros(self.policy.max_backoff_us);
+        self.interval = std::cmp::min(self.interval * 2, max_backoff);
+        if let Some(deadline) = self.deadline {
+            let now = Instant::now();
+            if deadline <= now {
+                return None;
+            }
+            delay = std::cmp::min(delay, deadline - now);
+        }
+        Some(delay)
+    }
+}
+
+pub struct RetryState {
+    backoff: Backoff,
+}
+
 impl Default for RetryState {
     fn default() -> Self {
         RetryState::new(None)
@@ -29,32 +146,37 @@ impl Default for RetryState {
 
 impl RetryState {
     pub fn new(timeout: Option<Duration>) -> Self {
+        RetryState::with_policy(&RetryPolicy::default(), timeout)
+    }
+
+    pub fn with_policy(policy: &RetryPolicy, timeout: Option<Duration>) -> Self {
+        let deadline = timeout.and_then(|d| Instant::now().checked_add(d));
         RetryState {
-            interval_ms: 8,
-            deadline: timeout.and_then(|d| Instant::now().checked_add(d)),
+            backoff: Backoff::new(policy, deadline),
         }
     }
 
     #[inline]
     pub fn timeout(&self) -> Option<Duration> {
-        self.deadline
+        self.backoff
+            .deadline()
             .map(|d| d.saturating_duration_since(Instant::now()))
     }
 
     pub async fn retry(&mut self, err: Error) -> Result<()> {
         match err {
             Error::NotFound(_) | Error::EpochNotMatch(_) | Error::GroupNotAccessable(_) => {
-                let mut interval = Duration::from_millis(self.interval_ms);
-                if let Some(deadline) = self.deadline {
-                    if let Some(duration) = deadline.checked_duration_since(Instant::now()) {
-                        interval = std::cmp::min(interval, duration);
-                    } else {
-                        return Err(Error::DeadlineExceeded("timeout".into()));
+                match self.backoff.next_delay() {
+                    Some(interval) => {
+                        tokio::time::sleep(interval).await;
+                        Ok(())
+                    }
+                    None if self.backoff.is_expired() => {
+                        Err(Error::DeadlineExceeded("timeout".into()))
                     }
+                    // The attempts are exhausted.
+                    None => Err(err),
                 }
-                tokio::time::sleep(interval).await;
-                self.interval_ms = std::cmp::min(self.interval_ms * 2, 250);
-                Ok(())
             }
             Error::NotLeader(..)
             | Error::GroupNotFound(_)
@@ -66,9 +188,67 @@ impl RetryState {
             | Error::DeadlineExceeded(_)
             | Error::ResourceExhausted(_)
             | Error::AlreadyExists(_)
+            | Error::ConditionNotMet(_)
             | Error::Rpc(_)
             | Error::Transport(_)
             | Error::Internal(_) => Err(err),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_with_jitter() {
+        let policy = RetryPolicy::default();
+        let max_backoff = Duration::from_micros(policy.max_backoff_us);
+        let mut backoff = Backoff::new(&policy, None);
+        let mut upper = Duration::from_micros(policy.initial_backoff_us);
+        for _ in 0..16 {
+            let delay = backoff.next_delay().unwrap();
+            assert!(delay >= upper / 2, "delay {delay:?} upper {upper:?}");
+            assert!(delay <= upper, "delay {delay:?} upper {upper:?}");
+            upper = std::cmp::min(upper * 2, max_backoff);
+        }
+    }
+
+    #[test]
+    fn backoff_capped_by_deadline() {
+        let policy = RetryPolicy::default();
+        let mut backoff = Backoff::new(&policy, Some(Instant::now() + Duration::from_micros(50)));
+        for _ in 0..4 {
+            if let Some(delay) = backoff.next_delay() {
+                assert!(delay <= Duration::from_micros(50));
+            }
+        }
+
+        let mut backoff = Backoff::new(&policy, Some(Instant::now()));
+        std::thread::sleep(Duration::from_micros(10));
+        assert!(backoff.next_delay().is_none());
+        assert!(backoff.is_expired());
+
+        let policy = RetryPolicy {
+            deadline_ms: 1,
+            ..Default::default()
+        };
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert!(policy.deadline(Some(deadline)).unwrap() < deadline);
+        assert!(policy.deadline(None).is_some());
+        assert!(RetryPolicy::default().deadline(None).is_none());
+    }
+
+    #[test]
+    fn backoff_limited_by_max_attempts() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            ..Default::default()
+        };
+        let mut backoff = Backoff::new(&policy, None);
+        assert!(backoff.next_delay().is_some());
+        assert!(backoff.next_delay().is_some());
+        assert!(backoff.next_delay().is_none());
+        assert!(!backoff.is_expired());
+    }
+}
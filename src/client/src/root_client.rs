@@ -25,8 +25,8 @@ use tonic::{transport::Channel, Code, Status, Streaming};
 use tracing::trace;
 
 use crate::{
-    conn_manager::ConnManager, discovery::ServiceDiscovery, error::retryable_rpc_err, NodeClient,
-    Result,
+    conn_manager::ConnManager, discovery::ServiceDiscovery, error::retryable_rpc_err,
+    CollectionOptions, NodeClient, Result,
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -58,6 +58,11 @@ struct ClientShared {
     // Only one task is allowed to refresh root descriptor at a time.
     // The value is the latest epoch refreshed from nodes.
     refresh_descriptor_lock: Mutex<u64>,
+
+    /// The addresses of nodes learned from watch events, they are also used to refresh root
+    /// descriptor, so that the client survives the replacement of all nodes returned by
+    /// discovery.
+    learned_nodes: std::sync::Mutex<HashMap<u64, String>>,
 }
 
 #[derive(Debug, Clone)]
@@ -80,10 +85,23 @@ impl Client {
                     root: Arc::default(),
                 }),
                 refresh_descriptor_lock: Mutex::new(0),
+                learned_nodes: std::sync::Mutex::default(),
             }),
         }
     }
 
+    /// Remember the address of a node, which is used to refresh root descriptor once all known
+    /// roots are unreachable.
+    pub fn learn_node(&self, node_id: u64, addr: String) {
+        let mut learned_nodes = self.shared.learned_nodes.lock().unwrap();
+        learned_nodes.insert(node_id, addr);
+    }
+
+    pub fn forget_node(&self, node_id: u64) {
+        let mut learned_nodes = self.shared.learned_nodes.lock().unwrap();
+        learned_nodes.remove(&node_id);
+    }
+
     pub async fn report(&self, req: &ReportRequest) -> Result<ReportResponse> {
         let res = self
             .invoke(|mut client| {
@@ -138,6 +156,23 @@ impl Client {
         Ok(resp.into_inner())
     }
 
+    /// Get the capabilities of the server. The servers not supporting it are treated as the
+    /// default capabilities.
+    pub async fn get_capabilities(&self) -> Result<GetCapabilitiesResponse> {
+        let res =
+            self.invoke(|mut client| async move {
+                client.get_capabilities(GetCapabilitiesRequest {}).await
+            })
+            .await;
+        match res {
+            Ok(res) => Ok(res.into_inner()),
+            Err(crate::Error::Rpc(status)) if status.code() == Code::Unimplemented => {
+                Ok(GetCapabilitiesResponse::default())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     async fn invoke<F, O, V>(&self, op: F) -> Result<V>
     where
         F: Fn(root_client::RootClient<Channel>) -> O,
@@ -243,7 +278,15 @@ impl Client {
     }
 
     async fn refresh_root_descriptor(&self, local_epoch: u64) -> Result<Option<RootDesc>> {
-        let nodes = self.shared.discovery.list_nodes().await;
+        let mut nodes = self.shared.discovery.list_nodes().await;
+        {
+            let learned_nodes = self.shared.learned_nodes.lock().unwrap();
+            for addr in learned_nodes.values() {
+                if !nodes.contains(addr) {
+                    nodes.push(addr.clone());
+                }
+            }
+        }
         for node in nodes {
             let node_client = self.get_node_client(node)?;
             if let Ok(root) = node_client.get_root().await {
@@ -343,6 +386,30 @@ impl AdminRequestBuilder {
         database: DatabaseDesc,
         co_name: String,
         partition: Option<Partition>,
+    ) -> AdminRequest {
+        Self::create_collection_with_limits(database, co_name, partition, 0, 0)
+    }
+
+    pub fn create_collection_with_limits(
+        database: DatabaseDesc,
+        co_name: String,
+        partition: Option<Partition>,
+        max_key_size: u64,
+        max_value_size: u64,
+    ) -> AdminRequest {
+        let opts = CollectionOptions {
+            max_key_size,
+            max_value_size,
+            ..Default::default()
+        };
+        Self::create_collection_with_options(database, co_name, partition, opts)
+    }
+
+    pub fn create_collection_with_options(
+        database: DatabaseDesc,
+        co_name: String,
+        partition: Option<Partition>,
+        opts: CollectionOptions,
     ) -> AdminRequest {
         AdminRequest {
             request: Some(AdminRequestUnion {
@@ -351,6 +418,11 @@ impl AdminRequestBuilder {
                         name: co_name,
                         database: Some(database),
                         partition,
+                        max_key_size: opts.max_key_size,
+                        max_value_size: opts.max_value_size,
+                        leader_zone: opts.leader_zone,
+                        webhook_url: opts.webhook_url,
+                        read_replica_zones: opts.read_replica_zones,
                     },
                 )),
             }),
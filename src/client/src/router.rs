@@ -25,7 +25,9 @@ use engula_api::{
     },
     v1::*,
 };
-use tokio_stream::StreamExt;
+use futures::Stream;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::UnboundedReceiverStream, StreamExt};
 use tonic::Streaming;
 use tracing::{info, trace, warn};
 
@@ -34,11 +36,15 @@ use crate::RootClient;
 #[derive(Debug, Clone)]
 pub struct Router {
     state: Arc<Mutex<State>>,
+    /// The group serving all collections of a standalone server, see
+    /// [`Router::standalone`].
+    standalone_group: Option<u64>,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct State {
     node_id_lookup: HashMap<u64, String /* ip:port */>,
+    node_zone_lookup: HashMap<u64, String>,
     db_id_lookup: HashMap<u64, DatabaseDesc>,
     db_name_lookup: HashMap<String, u64>,
     co_id_lookup: HashMap<u64, CollectionDesc>,
@@ -48,6 +54,8 @@ pub struct State {
     group_id_lookup: HashMap<u64 /* group */, RouterGroupState>,
 
     cached_group_states: HashMap<u64, GroupState>,
+
+    leader_subscribers: Vec<mpsc::UnboundedSender<LeaderChange>>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -58,6 +66,16 @@ pub struct RouterGroupState {
     pub replicas: HashMap<u64, ReplicaDesc>,
 }
 
+/// The leadership of a group is changed, see [`Router::subscribe_leader_changes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaderChange {
+    pub group_id: u64,
+    pub leader_id: u64,
+    /// The node of the leader, `None` if the leader isn't a member of the known descriptor.
+    pub leader_node: Option<u64>,
+    pub term: u64,
+}
+
 impl Router {
     pub async fn new(root_client: RootClient) -> Self {
         let state = Arc::new(Mutex::new(State::default()));
@@ -65,7 +83,29 @@ impl Router {
         tokio::spawn(async move {
             state_main(state_clone, root_client).await;
         });
-        Self { state }
+        Self {
+            state,
+            standalone_group: None,
+        }
+    }
+
+    /// Create a router for a standalone server, which serves all collections with a single group
+    /// and a shard per collection. The descriptors are never watched, the shards are derived from
+    /// the collection directly.
+    pub fn standalone(node: NodeDesc, group: GroupDesc) -> Self {
+        let mut state = State::default();
+        let group_id = group.id;
+        state.node_zone_lookup.insert(node.id, node.zone);
+        state.node_id_lookup.insert(node.id, node.addr);
+        state.apply_group_descriptor(group.clone());
+        if let Some(leader) = group.replicas.first() {
+            let group_state = state.group_id_lookup.get_mut(&group_id).unwrap();
+            group_state.leader_state = Some((leader.id, 0));
+        }
+        Router {
+            state: Arc::new(Mutex::new(state)),
+            standalone_group: Some(group_id),
+        }
     }
 
     pub fn find_shard(
@@ -73,12 +113,14 @@ impl Router {
         desc: CollectionDesc,
         key: &[u8],
     ) -> Result<(RouterGroupState, ShardDesc), crate::Error> {
+        if let Some(group_id) = self.standalone_group {
+            return Ok((self.find_group(group_id)?, standalone_shard(&desc)));
+        }
+
         if let Some(collection_desc::Partition::Hash(collection_desc::HashPartition { slots })) =
             desc.partition
         {
-            // TODO: it's temp hash impl..
-            let crc = crc32fast::hash(key);
-            let slot = crc % (slots as u32);
+            let slot = engula_api::shard::key_slot(key, slots);
 
             let state = self.state.lock().unwrap();
 
@@ -135,7 +177,58 @@ impl Router {
         Err(crate::Error::NotFound(format!("shard (key={:?})", key)))
     }
 
+    /// Returns the shards of the collection which overlap with the key range `[start, end)`, an
+    /// empty `end` means unbounded. All shards are returned for hash partitioned collections,
+    /// since the keys of a range are scattered.
+    pub fn find_shards_in_range(
+        &self,
+        desc: CollectionDesc,
+        start: &[u8],
+        end: &[u8],
+    ) -> Result<Vec<(RouterGroupState, ShardDesc)>, crate::Error> {
+        if let Some(group_id) = self.standalone_group {
+            return Ok(vec![(self.find_group(group_id)?, standalone_shard(&desc))]);
+        }
+
+        let state = self.state.lock().unwrap();
+        let shards = state
+            .co_shards_lookup
+            .get(&desc.id)
+            .ok_or_else(|| crate::Error::NotFound(format!("shards (collection={})", desc.id)))?;
+        if let Some(collection_desc::Partition::Hash(collection_desc::HashPartition { slots })) =
+            desc.partition
+        {
+            if slots != shards.len() as u32 {
+                return Err(crate::Error::NotFound("expired shard info".into()));
+            }
+        }
+
+        let mut overlapped = Vec::new();
+        for shard in shards {
+            if let Some(shard_desc::Partition::Range(shard_desc::RangePartition {
+                start: shard_start,
+                end: shard_end,
+            })) = shard.partition.as_ref()
+            {
+                let before_end = end.is_empty() || shard_start.as_slice() < end;
+                let after_start = shard_end.is_empty() || start < shard_end.as_slice();
+                if !before_end || !after_start {
+                    continue;
+                }
+            }
+            let group_state = state
+                .find_group_by_shard(shard.id)
+                .ok_or_else(|| crate::Error::NotFound(format!("shard (id={}) group", shard.id)))?;
+            overlapped.push((group_state, shard.clone()));
+        }
+        Ok(overlapped)
+    }
+
     pub fn find_group_by_shard(&self, shard: u64) -> Result<RouterGroupState, crate::Error> {
+        if let Some(group_id) = self.standalone_group {
+            return self.find_group(group_id);
+        }
+
         let state = self.state.lock().unwrap();
         state
             .find_group_by_shard(shard)
@@ -148,15 +241,75 @@ impl Router {
         group.ok_or_else(|| crate::Error::NotFound(format!("group (id={:?})", id)))
     }
 
+    pub fn find_collection(&self, id: u64) -> Result<CollectionDesc, crate::Error> {
+        let state = self.state.lock().unwrap();
+        let desc = state.co_id_lookup.get(&id).cloned();
+        desc.ok_or_else(|| crate::Error::NotFound(format!("collection (id={:?})", id)))
+    }
+
     pub fn find_node_addr(&self, id: u64) -> Result<String, crate::Error> {
         let state = self.state.lock().unwrap();
         let addr = state.node_id_lookup.get(&id).cloned();
         addr.ok_or_else(|| crate::Error::NotFound(format!("node_addr (node_id={:?})", id)))
     }
 
+    /// Returns the zone of node, empty if the node doesn't specify one.
+    pub fn find_node_zone(&self, id: u64) -> Result<String, crate::Error> {
+        let state = self.state.lock().unwrap();
+        let zone = state.node_zone_lookup.get(&id).cloned();
+        zone.ok_or_else(|| crate::Error::NotFound(format!("node_zone (node_id={:?})", id)))
+    }
+
+    /// Find a replica of the group placed in the zone, to serve the eventually consistent reads
+    /// locally. The learners are preferred since they are placed for reading, see
+    /// `CollectionDesc::read_replica_zones`.
+    pub fn find_zone_replica(&self, group_id: u64, zone: &str) -> Option<ReplicaDesc> {
+        let state = self.state.lock().unwrap();
+        let group = state.group_id_lookup.get(&group_id)?;
+        group
+            .replicas
+            .values()
+            .filter(|r| {
+                state
+                    .node_zone_lookup
+                    .get(&r.node_id)
+                    .map(|z| z == zone)
+                    .unwrap_or_default()
+            })
+            .min_by_key(|r| (r.role != ReplicaRole::Learner as i32, r.id))
+            .cloned()
+    }
+
     pub fn total_nodes(&self) -> usize {
         self.state.lock().unwrap().node_id_lookup.len()
     }
+
+    /// Subscribe the leadership changes of groups. The current leaders of all known groups are
+    /// yielded first, so that subscribers could pre-warm the connections to them. The stream is
+    /// unbounded, the subscribers should consume it timely.
+    pub fn subscribe_leader_changes(&self) -> impl Stream<Item = LeaderChange> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut state = self.state.lock().unwrap();
+        for group in state.group_id_lookup.values() {
+            if let Some(change) = group.leader_change() {
+                let _ = sender.send(change);
+            }
+        }
+        state.leader_subscribers.push(sender);
+        UnboundedReceiverStream::new(receiver)
+    }
+}
+
+impl RouterGroupState {
+    fn leader_change(&self) -> Option<LeaderChange> {
+        let (leader_id, term) = self.leader_state?;
+        Some(LeaderChange {
+            group_id: self.id,
+            leader_id,
+            leader_node: self.replicas.get(&leader_id).map(|r| r.node_id),
+            term,
+        })
+    }
 }
 
 impl State {
@@ -174,6 +327,7 @@ impl State {
     fn apply_update_event(&mut self, event: UpdateEvent) {
         match event {
             UpdateEvent::Node(node_desc) => {
+                self.node_zone_lookup.insert(node_desc.id, node_desc.zone);
                 self.node_id_lookup.insert(node_desc.id, node_desc.addr);
             }
             UpdateEvent::Group(group_desc) => {
@@ -183,7 +337,12 @@ impl State {
                 trace!("update event; group state {group_state:?}");
                 let id = group_state.group_id;
                 if let Some(group) = self.group_id_lookup.get_mut(&id) {
-                    group.leader_state = leader_state(&group_state);
+                    let new_leader_state = leader_state(&group_state);
+                    if group.leader_state != new_leader_state {
+                        group.leader_state = new_leader_state;
+                        let change = group.leader_change();
+                        self.notify_leader_change(change);
+                    }
                 } else {
                     self.cached_group_states.insert(id, group_state);
                 }
@@ -230,6 +389,7 @@ impl State {
             group_state.leader_state = old_state.leader_state;
         } else if let Some(cached_state) = self.cached_group_states.remove(&id) {
             group_state.leader_state = leader_state(&cached_state);
+            self.notify_leader_change(group_state.leader_change());
         }
         self.group_id_lookup.insert(id, group_state);
 
@@ -259,9 +419,17 @@ impl State {
         }
     }
 
+    fn notify_leader_change(&mut self, change: Option<LeaderChange>) {
+        if let Some(change) = change {
+            self.leader_subscribers
+                .retain(|sender| sender.send(change.clone()).is_ok());
+        }
+    }
+
     fn apply_delete_event(&mut self, event: DeleteEvent) {
         match event {
             DeleteEvent::Node(node) => {
+                self.node_zone_lookup.remove(&node);
                 self.node_id_lookup.remove(&node);
             }
             DeleteEvent::Group(_) => todo!(),
@@ -304,11 +472,15 @@ async fn state_main(state: Arc<Mutex<State>>, root_client: RootClient) {
         };
 
         interval = 1;
-        watch_events(state.as_ref(), events).await;
+        watch_events(state.as_ref(), &root_client, events).await;
     }
 }
 
-async fn watch_events(state: &Mutex<State>, mut events: Streaming<WatchResponse>) {
+async fn watch_events(
+    state: &Mutex<State>,
+    root_client: &RootClient,
+    mut events: Streaming<WatchResponse>,
+) {
     while let Some(event) = events.next().await {
         let (updates, deletes) = match event {
             Ok(resp) => (resp.updates, resp.deletes),
@@ -319,12 +491,18 @@ async fn watch_events(state: &Mutex<State>, mut events: Streaming<WatchResponse>
         };
         for update in updates {
             if let Some(event) = update.event {
+                if let UpdateEvent::Node(node_desc) = &event {
+                    root_client.learn_node(node_desc.id, node_desc.addr.clone());
+                }
                 let mut state = state.lock().unwrap();
                 state.apply_update_event(event);
             }
         }
         for delete in deletes {
             if let Some(event) = delete.event {
+                if let DeleteEvent::Node(node_id) = &event {
+                    root_client.forget_node(*node_id);
+                }
                 let mut state = state.lock().unwrap();
                 state.apply_delete_event(event);
             }
@@ -354,6 +532,19 @@ fn leader_state(group_state: &GroupState) -> Option<(u64, u64)> {
     }
 }
 
+/// The shard serving the collection in standalone mode, it shares the id of the collection and
+/// covers the whole key space.
+fn standalone_shard(desc: &CollectionDesc) -> ShardDesc {
+    ShardDesc {
+        id: desc.id,
+        collection_id: desc.id,
+        partition: Some(shard_desc::Partition::Range(shard_desc::RangePartition {
+            start: vec![],
+            end: vec![],
+        })),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use engula_api::server::v1::shard_desc::{HashPartition, Partition};
@@ -380,6 +571,144 @@ mod tests {
         }
     }
 
+    fn group_state(group_id: u64, leader_id: u64, term: u64) -> GroupState {
+        GroupState {
+            group_id,
+            leader_id: Some(leader_id),
+            replicas: vec![ReplicaState {
+                replica_id: leader_id,
+                group_id,
+                term,
+                role: RaftRole::Leader as i32,
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn notify_leader_changes() {
+        let mut state = State::default();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        state.leader_subscribers.push(sender);
+
+        // The group state is cached until the descriptor is known.
+        state.apply_update_event(UpdateEvent::GroupState(group_state(1, 1, 1)));
+        assert!(receiver.try_recv().is_err());
+        let mut desc = descriptor(1, 1);
+        desc.replicas.push(ReplicaDesc {
+            id: 1,
+            node_id: 3,
+            ..Default::default()
+        });
+        state.apply_group_descriptor(desc);
+        let change = receiver.try_recv().unwrap();
+        assert_eq!(
+            change,
+            LeaderChange {
+                group_id: 1,
+                leader_id: 1,
+                leader_node: Some(3),
+                term: 1,
+            }
+        );
+
+        // The same leader is not notified again.
+        state.apply_update_event(UpdateEvent::GroupState(group_state(1, 1, 1)));
+        assert!(receiver.try_recv().is_err());
+
+        state.apply_update_event(UpdateEvent::GroupState(group_state(1, 2, 2)));
+        let change = receiver.try_recv().unwrap();
+        assert_eq!(
+            (change.leader_id, change.leader_node, change.term),
+            (2, None, 2)
+        );
+
+        // The closed subscribers are removed.
+        drop(receiver);
+        state.apply_update_event(UpdateEvent::GroupState(group_state(1, 1, 3)));
+        assert!(state.leader_subscribers.is_empty());
+    }
+
+    #[test]
+    fn route_in_standalone_mode() {
+        let node = NodeDesc {
+            id: 0,
+            addr: "127.0.0.1:21805".to_owned(),
+            ..Default::default()
+        };
+        let mut group = descriptor(1, 1);
+        group.replicas.push(ReplicaDesc {
+            id: 1,
+            node_id: 0,
+            ..Default::default()
+        });
+        let router = Router::standalone(node, group);
+
+        let co = CollectionDesc {
+            id: 1024,
+            partition: Some(collection_desc::Partition::Hash(
+                collection_desc::HashPartition { slots: 8 },
+            )),
+            ..Default::default()
+        };
+        let (group, shard) = router.find_shard(co.clone(), b"key").unwrap();
+        assert_eq!(group.id, 1);
+        assert_eq!(group.leader_state, Some((1, 0)));
+        assert_eq!(shard.id, co.id);
+        assert_eq!(
+            router.find_shards_in_range(co, b"a", b"b").unwrap().len(),
+            1
+        );
+        assert_eq!(router.find_group_by_shard(1024).unwrap().id, 1);
+        assert_eq!(router.find_node_addr(0).unwrap(), "127.0.0.1:21805");
+    }
+
+    #[test]
+    fn find_replica_in_zone() {
+        let router = Router {
+            state: Arc::default(),
+            standalone_group: None,
+        };
+        {
+            let mut state = router.state.lock().unwrap();
+            for (id, zone) in [(1, "a"), (2, "b"), (3, "b")] {
+                state.apply_update_event(UpdateEvent::Node(NodeDesc {
+                    id,
+                    zone: zone.to_owned(),
+                    ..Default::default()
+                }));
+            }
+            let mut desc = descriptor(1, 1);
+            for (id, role) in [(1, ReplicaRole::Voter), (2, ReplicaRole::Voter)] {
+                desc.replicas.push(ReplicaDesc {
+                    id,
+                    node_id: id,
+                    role: role as i32,
+                });
+            }
+            state.apply_group_descriptor(desc);
+        }
+        assert_eq!(router.find_node_zone(3).unwrap(), "b");
+        assert_eq!(router.find_zone_replica(1, "b").unwrap().id, 2);
+        assert!(router.find_zone_replica(1, "c").is_none());
+
+        // The learners are preferred.
+        let mut desc = descriptor(1, 2);
+        for (id, role) in [
+            (1, ReplicaRole::Voter),
+            (2, ReplicaRole::Voter),
+            (3, ReplicaRole::Learner),
+        ] {
+            desc.replicas.push(ReplicaDesc {
+                id,
+                node_id: id,
+                role: role as i32,
+            });
+        }
+        router.state.lock().unwrap().apply_group_descriptor(desc);
+        assert_eq!(router.find_zone_replica(1, "b").unwrap().id, 3);
+    }
+
     #[test]
     fn update_shard_by_group_descriptor() {
         // Shard 1 migrated from group 1 to group 2.
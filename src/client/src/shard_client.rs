@@ -41,7 +41,7 @@ impl ShardClient {
     }
 
     pub async fn prefix_list(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = RetryState::with_policy(self.conn_manager.retry_policy(), None);
 
         loop {
             match self.prefix_list_inner(prefix).await {
@@ -54,7 +54,7 @@ impl ShardClient {
     }
 
     pub async fn delete(&self, key: &[u8]) -> Result<()> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = RetryState::with_policy(self.conn_manager.retry_policy(), None);
 
         loop {
             match self.delete_inner(key).await {
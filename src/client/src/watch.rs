@@ -0,0 +1,214 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use engula_api::{server::v1::*, v1::CollectionDesc};
+use futures::{stream::BoxStream, StreamExt};
+
+use crate::{AppResult, ConnManager, Error, GroupClient, Result, Router};
+
+const MIN_RESUBSCRIBE_INTERVAL: Duration = Duration::from_millis(8);
+const MAX_RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A change of the watched keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchEvent {
+    pub key: Vec<u8>,
+    /// The new value of the key, `None` if the key is deleted.
+    pub value: Option<Vec<u8>>,
+    /// The applied index of the group serving the key when the change is observed. It only
+    /// increases as long as the key isn't moved to another group.
+    pub version: u64,
+}
+
+/// `KeyWatcher` streams the changes of a key, or of the keys with a prefix, of a collection.
+///
+/// It subscribes the leader of the group which owns the key, and subscribes again once the
+/// leader is changed or the shard is migrated to another group. The values received after
+/// subscribing again are compared with the known values, so only the actual changes are emitted.
+pub struct KeyWatcher {
+    stream: BoxStream<'static, AppResult<WatchEvent>>,
+}
+
+struct WatchState {
+    router: Router,
+    conn_manager: ConnManager,
+    co_desc: CollectionDesc,
+    key: Vec<u8>,
+    prefix: bool,
+
+    streaming: Option<tonic::Streaming<WatchKeyResponse>>,
+    /// The first response of a subscription carries all values, not only the changes.
+    synced: bool,
+    values: HashMap<Vec<u8>, Vec<u8>>,
+    pending: VecDeque<WatchEvent>,
+    interval: Duration,
+}
+
+impl KeyWatcher {
+    pub(crate) fn new(
+        router: Router,
+        conn_manager: ConnManager,
+        co_desc: CollectionDesc,
+        key: Vec<u8>,
+        prefix: bool,
+    ) -> Self {
+        let state = WatchState {
+            router,
+            conn_manager,
+            co_desc,
+            key,
+            prefix,
+            streaming: None,
+            synced: false,
+            values: HashMap::default(),
+            pending: VecDeque::default(),
+            interval: MIN_RESUBSCRIBE_INTERVAL,
+        };
+        // The in-flight future is kept by the stream across polls, so the wakers registered by
+        // the subscription and the backoff timer are not dropped.
+        let stream = futures::stream::unfold(state, |mut state| async move {
+            let event = state.next().await?;
+            Some((event, state))
+        });
+        KeyWatcher {
+            stream: stream.boxed(),
+        }
+    }
+}
+
+impl WatchState {
+    async fn next(&mut self) -> Option<AppResult<WatchEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+
+            if self.streaming.is_none() {
+                match self.subscribe().await {
+                    Ok(streaming) => {
+                        self.streaming = Some(streaming);
+                        self.synced = false;
+                    }
+                    Err(err) => {
+                        if let Err(err) = self.backoff(err).await {
+                            return Some(Err(err.into()));
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            let streaming = self.streaming.as_mut().expect("already subscribed");
+            match streaming.next().await {
+                Some(Ok(resp)) => {
+                    self.interval = MIN_RESUBSCRIBE_INTERVAL;
+                    self.apply(resp);
+                }
+                Some(Err(status)) => {
+                    self.streaming = None;
+                    if let Err(err) = self.backoff(status.into()).await {
+                        return Some(Err(err.into()));
+                    }
+                }
+                None => self.streaming = None,
+            }
+        }
+    }
+
+    async fn subscribe(&mut self) -> Result<tonic::Streaming<WatchKeyResponse>> {
+        let (group, shard) = self.router.find_shard(self.co_desc.clone(), &self.key)?;
+        let mut client = GroupClient::new(group, self.router.clone(), self.conn_manager.clone());
+        client.watch_key(shard.id, &self.key, self.prefix).await
+    }
+
+    /// Wait before subscribing again, if the error is caused by leadership or routing changes.
+    async fn backoff(&mut self, err: Error) -> Result<()> {
+        match err {
+            Error::NotFound(_)
+            | Error::EpochNotMatch(_)
+            | Error::GroupNotAccessable(_)
+            | Error::GroupNotFound(_)
+            | Error::NotLeader(..)
+            | Error::Connect(_)
+            | Error::Transport(_) => {
+                tokio::time::sleep(self.interval).await;
+                self.interval = std::cmp::min(self.interval * 2, MAX_RESUBSCRIBE_INTERVAL);
+                Ok(())
+            }
+            _ => Err(err),
+        }
+    }
+
+    fn apply(&mut self, resp: WatchKeyResponse) {
+        let version = resp.version;
+        if !self.synced {
+            // The known keys which are absent from the first response have been deleted.
+            self.synced = true;
+            let current = resp
+                .changes
+                .iter()
+                .filter(|c| !c.deleted)
+                .map(|c| c.key.as_slice())
+                .collect::<HashSet<_>>();
+            let deleted = self
+                .values
+                .keys()
+                .filter(|key| !current.contains(key.as_slice()))
+                .cloned()
+                .collect::<Vec<_>>();
+            for key in deleted {
+                self.values.remove(&key);
+                self.pending.push_back(WatchEvent {
+                    key,
+                    value: None,
+                    version,
+                });
+            }
+        }
+
+        for change in resp.changes {
+            if change.deleted {
+                if self.values.remove(&change.key).is_some() {
+                    self.pending.push_back(WatchEvent {
+                        key: change.key,
+                        value: None,
+                        version,
+                    });
+                }
+            } else if self.values.get(&change.key) != Some(&change.value) {
+                self.values.insert(change.key.clone(), change.value.clone());
+                self.pending.push_back(WatchEvent {
+                    key: change.key,
+                    value: Some(change.value),
+                    version,
+                });
+            }
+        }
+    }
+}
+
+impl futures::Stream for KeyWatcher {
+    type Item = AppResult<WatchEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.stream.poll_next_unpin(cx)
+    }
+}
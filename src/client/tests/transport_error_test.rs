@@ -60,12 +60,24 @@ impl futures::Stream for ShardChunkStream {
     }
 }
 
+struct KeyWatchStream {}
+
+#[allow(unused)]
+impl futures::Stream for KeyWatchStream {
+    type Item = std::result::Result<WatchKeyResponse, tonic::Status>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        todo!()
+    }
+}
+
 struct MockedServer {}
 
 #[allow(unused)]
 #[tonic::async_trait]
 impl node_server::Node for MockedServer {
     type PullStream = ShardChunkStream;
+    type WatchKeyStream = KeyWatchStream;
 
     async fn batch(
         &self,
@@ -125,6 +137,21 @@ impl node_server::Node for MockedServer {
     ) -> Result<tonic::Response<engula_api::server::v1::ForwardResponse>, tonic::Status> {
         todo!()
     }
+
+    async fn collect_checksum(
+        &self,
+        request: tonic::Request<engula_api::server::v1::CollectChecksumRequest>,
+    ) -> Result<tonic::Response<engula_api::server::v1::CollectChecksumResponse>, tonic::Status>
+    {
+        todo!()
+    }
+
+    async fn watch_key(
+        &self,
+        request: tonic::Request<engula_api::server::v1::WatchKeyRequest>,
+    ) -> Result<tonic::Response<Self::WatchKeyStream>, tonic::Status> {
+        todo!()
+    }
 }
 
 #[tokio::test]
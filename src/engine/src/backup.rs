@@ -0,0 +1,190 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
+
+use rocksdb::{checkpoint::Checkpoint, DB};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// The name of the manifest file written into the backup directory.
+pub const BACKUP_MANIFEST: &str = "BACKUP_MANIFEST";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// The file name relative to the backup directory.
+    pub name: String,
+    pub size: u64,
+    pub crc32: u32,
+}
+
+/// The files of a backup sorted by name, and a root hash over them, so that a backup is verified
+/// before it is relied upon for restore. See [`verify_backup`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub files: Vec<BackupFile>,
+    pub root_hash: u32,
+}
+
+impl BackupManifest {
+    /// Build the manifest of the files in the directory, except the manifest itself.
+    pub fn build<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut files = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name == BACKUP_MANIFEST {
+                continue;
+            }
+            files.push(read_file_meta(&entry.path(), name)?);
+        }
+        files.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        let root_hash = root_hash(&files);
+        Ok(BackupManifest { files, root_hash })
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+}
+
+/// Create a checkpoint of the db in `dir`, which must not exist, and write the manifest of it.
+pub fn backup_raw_db<P: AsRef<Path>>(db: &DB, dir: P) -> Result<BackupManifest> {
+    let dir = dir.as_ref();
+    Checkpoint::new(db)?.create_checkpoint(dir)?;
+    let manifest = BackupManifest::build(dir)?;
+    let content = serde_json::to_vec_pretty(&manifest).expect("BackupManifest is serializable");
+    let mut file = File::create(dir.join(BACKUP_MANIFEST))?;
+    file.write_all(&content)?;
+    file.sync_all()?;
+    Ok(manifest)
+}
+
+/// Validate the files in `dir` against the manifest of the backup: each listed file must exist
+/// with the recorded size and checksum, no unlisted file is allowed, and the root hash must match.
+pub fn verify_backup<P: AsRef<Path>>(dir: P) -> Result<BackupManifest> {
+    let dir = dir.as_ref();
+    let content = std::fs::read(dir.join(BACKUP_MANIFEST))?;
+    let expect: BackupManifest = serde_json::from_slice(&content)
+        .map_err(|e| Error::InvalidBackup(format!("manifest: {e}")))?;
+    if root_hash(&expect.files) != expect.root_hash {
+        return Err(Error::InvalidBackup(format!(
+            "manifest root hash {:08x} mismatch",
+            expect.root_hash
+        )));
+    }
+
+    let actual = BackupManifest::build(dir)?;
+    for file in &expect.files {
+        match actual.files.iter().find(|f| f.name == file.name) {
+            None => {
+                return Err(Error::InvalidBackup(format!(
+                    "file {} is missing",
+                    file.name
+                )));
+            }
+            Some(f) if f.size != file.size => {
+                return Err(Error::InvalidBackup(format!(
+                    "file {} size {} mismatch, expect {}",
+                    file.name, f.size, file.size
+                )));
+            }
+            Some(f) if f.crc32 != file.crc32 => {
+                return Err(Error::InvalidBackup(format!(
+                    "file {} crc32 {:08x} mismatch, expect {:08x}",
+                    file.name, f.crc32, file.crc32
+                )));
+            }
+            Some(_) => {}
+        }
+    }
+    if let Some(f) = actual.files.iter().find(|f| !expect.files.contains(f)) {
+        return Err(Error::InvalidBackup(format!(
+            "file {} is not in manifest",
+            f.name
+        )));
+    }
+    debug_assert_eq!(actual.root_hash, expect.root_hash);
+    Ok(expect)
+}
+
+fn read_file_meta(path: &Path, name: String) -> Result<BackupFile> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; 4096];
+    let mut hasher = crc32fast::Hasher::new();
+    let mut size = 0;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok(BackupFile {
+        name,
+        size,
+        crc32: hasher.finalize(),
+    })
+}
+
+fn root_hash(files: &[BackupFile]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for file in files {
+        hasher.update(file.name.as_bytes());
+        hasher.update(&file.size.to_le_bytes());
+        hasher.update(&file.crc32.to_le_bytes());
+    }
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::Engine;
+
+    #[test]
+    fn backup_and_verify() {
+        let dir = TempDir::new("engine-backup-and-verify").unwrap();
+        let engine = Engine::open(dir.path().join("db")).unwrap();
+        engine.put(b"a", b"1").unwrap();
+        engine.flush().unwrap();
+
+        let backup_dir = dir.path().join("backup");
+        let manifest = engine.backup(&backup_dir).unwrap();
+        assert!(!manifest.files.is_empty());
+        assert_eq!(verify_backup(&backup_dir).unwrap(), manifest);
+
+        // A corrupted file is rejected.
+        let target = backup_dir.join(&manifest.files[0].name);
+        let mut content = std::fs::read(&target).unwrap();
+        content.push(0);
+        std::fs::write(&target, content).unwrap();
+        assert!(matches!(
+            verify_backup(&backup_dir),
+            Err(Error::InvalidBackup(_))
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use rocksdb::{Direction, IteratorMode, ReadOptions, WriteOptions, DB};
+
+use crate::{backup_raw_db, db::open_raw_db, BackupManifest, DbConfig, Result};
+
+/// An embedded engine, the keys are ordered bytewise.
+///
+/// The writes are persisted with WAL but aren't synced by default, see
+/// [`Engine::set_sync_writes`].
+pub struct Engine {
+    db: DB,
+    sync_writes: bool,
+}
+
+/// A batch of writes, which are applied atomically by [`Engine::write`].
+#[derive(Default)]
+pub struct WriteBatch {
+    inner: rocksdb::WriteBatch,
+}
+
+/// A consistent view of the engine, the writes after it is taken are invisible.
+pub struct Snapshot<'a> {
+    inner: rocksdb::Snapshot<'a>,
+}
+
+/// The key value pairs in a range, in ascending order of keys.
+pub struct Scan<'a> {
+    iter: rocksdb::DBIterator<'a>,
+    end: Vec<u8>,
+}
+
+impl Engine {
+    /// Open the engine at the path with the default config, it is created if not exists.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_config(&DbConfig::default(), path)
+    }
+
+    pub fn open_with_config<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<Self> {
+        Ok(Engine {
+            db: open_raw_db(cfg, path)?,
+            sync_writes: false,
+        })
+    }
+
+    /// Sync the WAL before acknowledging each write, so the acknowledged writes survive the
+    /// crashes of the machine besides the process.
+    pub fn set_sync_writes(&mut self, sync_writes: bool) {
+        self.sync_writes = sync_writes;
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.db.get(key)?)
+    }
+
+    pub fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        batch.put(key, value);
+        self.write(batch)
+    }
+
+    pub fn delete(&self, key: &[u8]) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        batch.delete(key);
+        self.write(batch)
+    }
+
+    pub fn write(&self, batch: WriteBatch) -> Result<()> {
+        let mut opts = WriteOptions::default();
+        opts.set_sync(self.sync_writes);
+        Ok(self.db.write_opt(batch.inner, &opts)?)
+    }
+
+    /// Scan the keys in `[start, end)`, an empty `end` means unbounded.
+    pub fn scan(&self, start: &[u8], end: &[u8]) -> Scan<'_> {
+        let mode = IteratorMode::From(start, Direction::Forward);
+        Scan {
+            iter: self.db.iterator_opt(mode, ReadOptions::default()),
+            end: end.to_owned(),
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot<'_> {
+        Snapshot {
+            inner: self.db.snapshot(),
+        }
+    }
+
+    /// Persist the memtables into files.
+    pub fn flush(&self) -> Result<()> {
+        Ok(self.db.flush()?)
+    }
+
+    /// Backup the engine into `dir`, which must not exist, with a manifest of the checksums of the
+    /// files. The backup could be verified by [`crate::verify_backup`] and opened as an engine.
+    pub fn backup<P: AsRef<Path>>(&self, dir: P) -> Result<BackupManifest> {
+        backup_raw_db(&self.db, dir)
+    }
+
+    /// Compact the keys in `[start, end)` to reclaim the space of the overwritten and deleted
+    /// keys, an empty `end` means unbounded.
+    pub fn compact(&self, start: &[u8], end: &[u8]) {
+        let end = if end.is_empty() { None } else { Some(end) };
+        self.db.compact_range(Some(start), end);
+    }
+}
+
+impl WriteBatch {
+    pub fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.inner.put(key, value);
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.inner.delete(key);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+impl<'a> Snapshot<'a> {
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.inner.get(key)?)
+    }
+
+    /// Scan the keys in `[start, end)` of the snapshot, an empty `end` means unbounded.
+    pub fn scan(&self, start: &[u8], end: &[u8]) -> Scan<'_> {
+        let mode = IteratorMode::From(start, Direction::Forward);
+        Scan {
+            iter: self.inner.iterator_opt(mode, ReadOptions::default()),
+            end: end.to_owned(),
+        }
+    }
+}
+
+impl<'a> Iterator for Scan<'a> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.iter.next()? {
+            Ok((key, _)) if !self.end.is_empty() && key.as_ref() >= self.end.as_slice() => None,
+            Ok((key, value)) => Some(Ok((key.into_vec(), value.into_vec()))),
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn collect(scan: Scan) -> Vec<(Vec<u8>, Vec<u8>)> {
+        scan.collect::<Result<Vec<_>>>().unwrap()
+    }
+
+    fn kv(key: &str, value: &str) -> (Vec<u8>, Vec<u8>) {
+        (key.as_bytes().to_vec(), value.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn basic_operations() {
+        let dir = TempDir::new("engine-basic-operations").unwrap();
+        let engine = Engine::open(dir.path()).unwrap();
+
+        engine.put(b"a", b"1").unwrap();
+        engine.put(b"b", b"2").unwrap();
+        let mut batch = WriteBatch::default();
+        batch.put(b"c", b"3");
+        batch.delete(b"a");
+        assert_eq!(batch.len(), 2);
+        engine.write(batch).unwrap();
+
+        assert_eq!(engine.get(b"a").unwrap(), None);
+        assert_eq!(engine.get(b"c").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(
+            collect(engine.scan(b"", b"")),
+            vec![kv("b", "2"), kv("c", "3")]
+        );
+        assert_eq!(collect(engine.scan(b"a", b"c")), vec![kv("b", "2")]);
+
+        let snapshot = engine.snapshot();
+        engine.delete(b"b").unwrap();
+        assert_eq!(snapshot.get(b"b").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(
+            collect(snapshot.scan(b"", b"")),
+            vec![kv("b", "2"), kv("c", "3")]
+        );
+        drop(snapshot);
+
+        engine.flush().unwrap();
+        engine.compact(b"", b"");
+        drop(engine);
+
+        // The writes are persisted across reopening.
+        let engine = Engine::open(dir.path()).unwrap();
+        assert_eq!(collect(engine.scan(b"", b"")), vec![kv("c", "3")]);
+    }
+}
@@ -12,11 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
-    }
-}
+//! The storage engine of Engula, which could be embedded into applications and used in-process.
+//!
+//! ```no_run
+//! use engula_engine::Engine;
+//!
+//! let engine = Engine::open("/tmp/engula").unwrap();
+//! engine.put(b"key", b"value").unwrap();
+//! assert_eq!(engine.get(b"key").unwrap(), Some(b"value".to_vec()));
+//! ```
+
+mod backup;
+mod config;
+mod db;
+mod engine;
+mod error;
+
+pub use self::{
+    backup::{backup_raw_db, verify_backup, BackupFile, BackupManifest, BACKUP_MANIFEST},
+    config::DbConfig,
+    db::{open_raw_db, open_raw_db_with_cf_options},
+    engine::{Engine, Scan, Snapshot, WriteBatch},
+    error::{Error, Result},
+};
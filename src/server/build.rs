@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{error::Error, result::Result};
+use std::{error::Error, path::PathBuf, result::Result};
 
 fn main() -> Result<(), Box<dyn Error>> {
     std::env::set_var("PROTOC", protoc_build::PROTOC);
@@ -22,14 +22,17 @@ fn main() -> Result<(), Box<dyn Error>> {
     config.extern_path(".engula.server.v1", "::engula_api::server::v1");
     config.extern_path(".engula.v1", "::engula_api::v1");
     config.extern_path(".eraftpb", "::raft::eraftpb");
-    tonic_build::configure().compile_with_config(
-        config,
-        &[
-            "proto/v1/metadata.proto",
-            "proto/v1/raft.proto",
-            "proto/v1/schedule.proto",
-        ],
-        &["proto", "proto/include", "../api/"],
-    )?;
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("serverpb_descriptor.bin"))
+        .compile_with_config(
+            config,
+            &[
+                "proto/v1/metadata.proto",
+                "proto/v1/raft.proto",
+                "proto/v1/schedule.proto",
+            ],
+            &["proto", "proto/include", "../api/"],
+        )?;
     Ok(())
 }
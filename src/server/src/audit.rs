@@ -0,0 +1,469 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::channel::oneshot;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::Result;
+
+const AUDIT_FILE_NAME: &str = "audit.log";
+
+/// The number of recent entries kept in memory for the admin API.
+const MAX_RECENT_ENTRIES: usize = 1024;
+
+/// The result of the entries written ahead of the actions.
+const PENDING_RESULT: &str = "pending";
+
+const DEFAULT_MAX_FILE_SIZE: u64 = 64 << 20;
+const DEFAULT_MAX_FILES: usize = 8;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AuditConfig {
+    /// Record administrative actions, eg. creating databases and draining nodes, to the
+    /// append-only `audit.log` in `{root_dir}/audit`.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub enable: bool,
+
+    /// Record data mutations served by the proxy service too.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub enable_data_mutations: bool,
+
+    /// Rotate the audit log once its size exceeds the threshold.
+    ///
+    /// Default: 64MB
+    pub max_file_size: Option<u64>,
+
+    /// The number of rotated audit logs to keep, the oldest ones are removed.
+    ///
+    /// Default: 8
+    pub max_files: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuditEntry {
+    pub timestamp_ms: u64,
+    /// The entry written ahead of an action and the entry of its outcome share the request id.
+    pub request_id: String,
+    /// The peer address of the request, there is no authentication yet.
+    pub principal: String,
+    pub action: String,
+    pub target: String,
+    /// `pending` if the entry is written ahead of the action, otherwise `ok` or the error
+    /// message.
+    pub result: String,
+}
+
+/// An append-only log of administrative actions and (optionally) data mutations, each entry is
+/// saved as a line of json.
+///
+/// An action is recorded twice: [`AuditLog::begin`] persists a `pending` entry before the
+/// action is taken, so that a crash never loses the record of a completed action, and
+/// [`AuditRecord::finish`] appends its outcome. The entries are written and synced by a
+/// dedicated thread, the callers only wait for the `pending` entries being synced.
+pub struct AuditLog {
+    cfg: AuditConfig,
+    sender: Option<mpsc::UnboundedSender<WriteTask>>,
+    writer: Option<JoinHandle<()>>,
+    recent: Arc<Mutex<VecDeque<AuditEntry>>>,
+}
+
+/// An action which has been recorded ahead, its outcome should be recorded by
+/// [`AuditRecord::finish`].
+#[must_use]
+pub struct AuditRecord {
+    sender: Option<mpsc::UnboundedSender<WriteTask>>,
+    recent: Arc<Mutex<VecDeque<AuditEntry>>>,
+    entry: Option<AuditEntry>,
+}
+
+struct WriteTask {
+    entry: AuditEntry,
+    synced: Option<oneshot::Sender<()>>,
+}
+
+struct AuditWriter {
+    cfg: AuditConfig,
+    dir: PathBuf,
+    file: Option<File>,
+    size: u64,
+}
+
+impl AuditLog {
+    pub fn open<P: AsRef<Path>>(cfg: &AuditConfig, dir: P) -> Result<Self> {
+        let recent = Arc::default();
+        if !cfg.enable {
+            return Ok(AuditLog {
+                cfg: cfg.clone(),
+                sender: None,
+                writer: None,
+                recent,
+            });
+        }
+
+        let dir = dir.as_ref().to_owned();
+        std::fs::create_dir_all(&dir)?;
+        let file = open_append(&dir.join(AUDIT_FILE_NAME))?;
+        let writer = AuditWriter {
+            cfg: cfg.clone(),
+            dir,
+            size: file.metadata()?.len(),
+            file: Some(file),
+        };
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let writer = std::thread::Builder::new()
+            .name("audit-writer".to_owned())
+            .spawn(move || writer.run(receiver))?;
+        Ok(AuditLog {
+            cfg: cfg.clone(),
+            sender: Some(sender),
+            writer: Some(writer),
+            recent,
+        })
+    }
+
+    /// Record an administrative action ahead of taking it, nothing is recorded if audit is
+    /// disabled. An error is returned if the entry could not be persisted, and the action
+    /// should be rejected.
+    pub async fn begin(
+        &self,
+        principal: &str,
+        action: &str,
+        target: String,
+    ) -> Result<AuditRecord> {
+        self.begin_if(self.cfg.enable, principal, action, target)
+            .await
+    }
+
+    /// Record a data mutation ahead of applying it, nothing is recorded unless
+    /// `enable_data_mutations` is set.
+    pub async fn begin_data_mutation(
+        &self,
+        principal: &str,
+        action: &str,
+        target: String,
+    ) -> Result<AuditRecord> {
+        let enable = self.cfg.enable && self.cfg.enable_data_mutations;
+        self.begin_if(enable, principal, action, target).await
+    }
+
+    /// Returns at most `limit` recent entries, the latest one comes first.
+    pub fn recent(&self, limit: usize) -> Vec<AuditEntry> {
+        let recent = self.recent.lock().unwrap();
+        recent.iter().rev().take(limit).cloned().collect()
+    }
+
+    async fn begin_if(
+        &self,
+        enable: bool,
+        principal: &str,
+        action: &str,
+        target: String,
+    ) -> Result<AuditRecord> {
+        let sender = match &self.sender {
+            Some(sender) if enable => sender.clone(),
+            _ => {
+                return Ok(AuditRecord {
+                    sender: None,
+                    recent: self.recent.clone(),
+                    entry: None,
+                })
+            }
+        };
+
+        let entry = AuditEntry {
+            timestamp_ms: now_ms(),
+            request_id: uuid::Uuid::new_v4().to_string(),
+            principal: principal.to_owned(),
+            action: action.to_owned(),
+            target,
+            result: PENDING_RESULT.to_owned(),
+        };
+        let (tx, rx) = oneshot::channel();
+        let task = WriteTask {
+            entry: entry.clone(),
+            synced: Some(tx),
+        };
+        if sender.send(task).is_err() {
+            return Err(crate::Error::Canceled);
+        }
+        // The sender is dropped without notifying if the entry could not be written.
+        rx.await?;
+        Ok(AuditRecord {
+            sender: Some(sender),
+            recent: self.recent.clone(),
+            entry: Some(entry),
+        })
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        // Stop the writer once the pending entries are written.
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl AuditRecord {
+    /// Record the outcome of the action, without waiting for it being persisted.
+    pub fn finish<T, E: std::fmt::Display>(self, result: &std::result::Result<T, E>) {
+        let Some(mut entry) = self.entry else {
+            return;
+        };
+        entry.timestamp_ms = now_ms();
+        entry.result = match result {
+            Ok(_) => "ok".to_owned(),
+            Err(err) => err.to_string(),
+        };
+        {
+            let mut recent = self.recent.lock().unwrap();
+            if recent.len() >= MAX_RECENT_ENTRIES {
+                recent.pop_front();
+            }
+            recent.push_back(entry.clone());
+        }
+        if let Some(sender) = self.sender {
+            let _ = sender.send(WriteTask {
+                entry,
+                synced: None,
+            });
+        }
+    }
+}
+
+impl AuditWriter {
+    fn run(mut self, mut receiver: mpsc::UnboundedReceiver<WriteTask>) {
+        while let Some(task) = receiver.blocking_recv() {
+            // Sync the queued entries together.
+            let mut tasks = vec![task];
+            while let Ok(task) = receiver.try_recv() {
+                tasks.push(task);
+            }
+            let mut written = Vec::with_capacity(tasks.len());
+            for task in tasks {
+                match self.write_entry(&task.entry) {
+                    Ok(()) => written.extend(task.synced),
+                    Err(err) => warn!("write audit entry {:?}: {err:?}", task.entry),
+                }
+            }
+            if let Err(err) = self.sync() {
+                warn!("sync audit log: {err:?}");
+                continue;
+            }
+            for synced in written {
+                let _ = synced.send(());
+            }
+        }
+    }
+
+    fn write_entry(&mut self, entry: &AuditEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry).expect("serialize audit entry");
+        line.push(b'\n');
+        let max_file_size = self.cfg.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE);
+        if self.size > 0 && self.size + line.len() as u64 > max_file_size {
+            self.sync()?;
+            // The entries are appended to the current file if the rotation fails, it will be
+            // retried by the next entry.
+            match self.rotate() {
+                Ok(()) => self.file = None,
+                Err(err) => warn!("rotate audit log: {err:?}"),
+            }
+        }
+        if self.file.is_none() {
+            let file = open_append(&self.dir.join(AUDIT_FILE_NAME))?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        let file = self.file.as_mut().expect("audit log is opened");
+        if let Err(err) = file.write_all(&line) {
+            // Reopen the file for the next entry.
+            self.file = None;
+            return Err(err.into());
+        }
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Rename `audit.log.{n}` to `audit.log.{n+1}`, and the current `audit.log` to `audit.log.1`.
+    fn rotate(&self) -> Result<()> {
+        let max_files = self.cfg.max_files.unwrap_or(DEFAULT_MAX_FILES).max(1);
+        let rotated = |idx: usize| self.dir.join(format!("{AUDIT_FILE_NAME}.{idx}"));
+        let oldest = rotated(max_files);
+        if oldest.exists() {
+            std::fs::remove_file(oldest)?;
+        }
+        for idx in (1..max_files).rev() {
+            let from = rotated(idx);
+            if from.exists() {
+                std::fs::rename(from, rotated(idx + 1))?;
+            }
+        }
+        std::fs::rename(self.dir.join(AUDIT_FILE_NAME), rotated(1))?;
+        Ok(())
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn record(log: &AuditLog, action: &str, target: &str, result: &std::result::Result<(), &str>) {
+        let record = block_on(log.begin("peer", action, target.to_owned())).unwrap();
+        record.finish(result);
+    }
+
+    fn read_entries(path: &Path) -> Vec<AuditEntry> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn audit_disabled() {
+        let dir = TempDir::new("audit_disabled").unwrap();
+        let log = AuditLog::open(&AuditConfig::default(), dir.path().join("audit")).unwrap();
+        record(&log, "create_database", "db", &Ok(()));
+        assert!(log.recent(10).is_empty());
+        assert!(!dir.path().join("audit").exists());
+    }
+
+    #[test]
+    fn audit_write_ahead() {
+        let dir = TempDir::new("audit_write_ahead").unwrap();
+        let cfg = AuditConfig {
+            enable: true,
+            ..Default::default()
+        };
+        let log = AuditLog::open(&cfg, dir.path()).unwrap();
+        let record = block_on(log.begin("peer", "drain_node", "1".to_owned())).unwrap();
+
+        // The entry is persisted before the action is taken.
+        let entries = read_entries(&dir.path().join(AUDIT_FILE_NAME));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, "drain_node");
+        assert_eq!(entries[0].result, PENDING_RESULT);
+        assert!(log.recent(10).is_empty());
+
+        record.finish(&Ok::<(), String>(()));
+        drop(log);
+        let entries = read_entries(&dir.path().join(AUDIT_FILE_NAME));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].request_id, entries[0].request_id);
+        assert_eq!(entries[1].result, "ok");
+    }
+
+    #[test]
+    fn audit_record_and_rotate() {
+        let dir = TempDir::new("audit_record_and_rotate").unwrap();
+        let cfg = AuditConfig {
+            enable: true,
+            max_file_size: Some(256),
+            max_files: Some(2),
+            ..Default::default()
+        };
+        let log = AuditLog::open(&cfg, dir.path()).unwrap();
+        for i in 0..16 {
+            record(&log, "create_database", &format!("db{i}"), &Ok(()));
+        }
+        record(&log, "delete_database", "db0", &Err("not found"));
+        block_on(log.begin_data_mutation("peer", "put", "db0/co".to_owned()))
+            .unwrap()
+            .finish(&Ok::<(), String>(()));
+
+        let recent = log.recent(2);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].action, "delete_database");
+        assert_eq!(recent[0].result, "not found");
+        assert_eq!(recent[1].target, "db15");
+
+        // Wait for the outcomes being written.
+        drop(log);
+        assert!(dir.path().join("audit.log.1").exists());
+        assert!(dir.path().join("audit.log.2").exists());
+        assert!(!dir.path().join("audit.log.3").exists());
+        let entries = read_entries(&dir.path().join(AUDIT_FILE_NAME));
+        assert_eq!(entries.last(), Some(&recent[0]));
+    }
+
+    #[test]
+    fn audit_rotate_failed() {
+        let dir = TempDir::new("audit_rotate_failed").unwrap();
+        let cfg = AuditConfig {
+            enable: true,
+            max_file_size: Some(256),
+            max_files: Some(1),
+            ..Default::default()
+        };
+        // The oldest rotated file can't be removed.
+        std::fs::create_dir_all(dir.path().join("audit.log.1").join("busy")).unwrap();
+
+        let log = AuditLog::open(&cfg, dir.path()).unwrap();
+        for i in 0..8 {
+            record(&log, "create_database", &format!("db{i}"), &Ok(()));
+        }
+        drop(log);
+        let entries = read_entries(&dir.path().join(AUDIT_FILE_NAME));
+        assert_eq!(entries.len(), 16);
+
+        // The rotation is retried once it could proceed.
+        std::fs::remove_dir_all(dir.path().join("audit.log.1")).unwrap();
+        let log = AuditLog::open(&cfg, dir.path()).unwrap();
+        record(&log, "create_database", "db8", &Ok(()));
+        drop(log);
+        assert!(dir.path().join("audit.log.1").is_file());
+        let entries = read_entries(&dir.path().join(AUDIT_FILE_NAME));
+        assert_eq!(entries.last().map(|e| e.target.as_str()), Some("db8"));
+    }
+}
@@ -0,0 +1,38 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use engula_engine::BackupManifest;
+
+use crate::{bootstrap::open_engine_with_default_config, Result};
+
+/// Backup the engine from the data directory of a stopped node into `dir`, which must not exist.
+/// The backup contains a manifest of the checksums of the files, see
+/// [`engula_engine::verify_backup`].
+pub fn backup<P, Q>(root_dir: P, dir: Q) -> Result<BackupManifest>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let db_path = root_dir.as_ref().join("db");
+    if !db_path.exists() {
+        return Err(crate::Error::InvalidArgument(format!(
+            "db {} is not exists",
+            db_path.display()
+        )));
+    }
+    let raw_db = open_engine_with_default_config(&db_path)?;
+    Ok(engula_engine::backup_raw_db(&raw_db, dir)?)
+}
@@ -19,8 +19,17 @@ use engula_client::{ConnManager, RootClient, Router};
 use tracing::{debug, info, warn};
 
 use crate::{
+    audit::AuditLog,
     discovery::RootDiscovery,
-    node::{engine::StateEngine, resolver::AddressResolver, Node},
+    feature::{supported_features, FeatureGates},
+    node::{
+        engine::{GroupEngine, StateEngine},
+        health::StorageHealth,
+        lifecycle::LifecycleState,
+        memory::MemoryTracker,
+        resolver::AddressResolver,
+        Node,
+    },
     root::{Root, Schema},
     runtime::{Executor, Shutdown},
     serverpb::v1::{raft_server::RaftServer, NodeIdent},
@@ -45,6 +54,10 @@ lazy_static::lazy_static! {
 
 /// The main entrance of engula server.
 pub fn run(config: Config, executor: Executor, shutdown: Shutdown) -> Result<()> {
+    if config.standalone {
+        return crate::standalone::run(config, executor, shutdown);
+    }
+
     executor.block_on(async {
         let provider = build_provider(&config, executor.clone()).await?;
         let node = Node::new(config.clone(), provider.clone())?;
@@ -58,11 +71,13 @@ pub fn run(config: Config, executor: Executor, shutdown: Shutdown) -> Result<()>
             .set_initial_nodes(initial_node_descs);
 
         info!("node {} starts serving requests", ident.node_id);
+        node.lifecycle().advance(LifecycleState::Serving);
 
         let server = Server {
             node: Arc::new(node),
             root,
             address_resolver: provider.address_resolver.clone(),
+            audit: provider.audit.clone(),
         };
 
         let proxy_server = if config.enable_proxy_service {
@@ -70,13 +85,32 @@ pub fn run(config: Config, executor: Executor, shutdown: Shutdown) -> Result<()>
         } else {
             None
         };
-        bootstrap_services(&config.addr, server, proxy_server, shutdown).await
+        bootstrap_services(&config, server, proxy_server, shutdown).await?;
+
+        // Stop serving requests before touching the engines, then persist the memtables, so that
+        // less raft logs are replayed when restarting.
+        info!(
+            "node {} stops serving requests, flush engines",
+            ident.node_id
+        );
+        flush_engines(&provider)
     })
 }
 
+/// Flush the memtables of all column families, the group engines write without WAL.
+fn flush_engines(provider: &Provider) -> Result<()> {
+    let cf_names = rocksdb::DB::list_cf(&rocksdb::Options::default(), &provider.db_path)?;
+    for name in cf_names {
+        if let Some(cf_handle) = provider.raw_db.cf_handle(&name) {
+            provider.raw_db.flush_cf(&cf_handle)?;
+        }
+    }
+    Ok(())
+}
+
 /// Listen and serve incoming rpc requests.
 async fn bootstrap_services(
-    addr: &str,
+    config: &Config,
     server: Server,
     proxy_server: Option<ProxyServer>,
     shutdown: Shutdown,
@@ -84,99 +118,83 @@ async fn bootstrap_services(
     use engula_api::v1::engula_server::EngulaServer;
     use tokio::net::TcpListener;
     use tokio_stream::wrappers::TcpListenerStream;
-    use tonic::transport::Server;
+    use tonic::{codec::CompressionEncoding, transport::Server};
 
-    use crate::service::admin::make_admin_service;
+    use crate::service::{
+        admin::make_admin_service, http_proxy::HttpProxyService, pgwire::PgWireServer,
+    };
 
-    let listener = TcpListener::bind(addr).await?;
+    let listener = TcpListener::bind(&config.addr).await?;
     let listener = TcpListenerStream::new(listener);
 
+    if !config.pgwire_addr.is_empty() {
+        match proxy_server.clone() {
+            Some(proxy_server) => {
+                let listener = TcpListener::bind(&config.pgwire_addr).await?;
+                PgWireServer::new(proxy_server, config.proxy_auth_tokens.clone()).spawn(listener);
+            }
+            None => warn!("pgwire_addr is ignored since the proxy service is disabled"),
+        }
+    }
+
+    // Compressed requests are always accepted, the responses are compressed only if it is enabled
+    // and the peer accepts.
+    let mut node_server =
+        NodeServer::new(server.clone()).accept_compressed(CompressionEncoding::Gzip);
+    if config.node.compress_threshold > 0 {
+        node_server = node_server.send_compressed(CompressionEncoding::Gzip);
+    }
+
+    // Serve the descriptors of protos, so that debugging with tools like grpcurl works.
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(engula_api::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(crate::serverpb::v1::FILE_DESCRIPTOR_SET)
+        .build()
+        .expect("the embedded file descriptor sets are valid");
+
+    let node = server.node.clone();
     let server = Server::builder()
         .accept_http1(true) // Support http1 for admin service.
-        .add_service(NodeServer::new(server.clone()))
+        .add_service(node_server)
         .add_service(RaftServer::new(server.clone()))
         .add_service(RootServer::new(server.clone()))
-        .add_service(make_admin_service(server.clone()))
+        .add_service(make_admin_service(server.clone(), config.enable_pprof))
+        .add_service(reflection)
+        .add_optional_service(
+            proxy_server
+                .clone()
+                .map(|s| HttpProxyService::new(s, config.proxy_auth_tokens.clone())),
+        )
         .add_optional_service(proxy_server.map(EngulaServer::new))
         .serve_with_incoming(listener);
 
+    tokio::pin!(server);
     crate::runtime::select! {
-        res = server => { res? }
+        res = &mut server => { return Ok(res?) }
         _ = shutdown => {}
     };
 
+    // Keep serving requests until the leaderships are transferred out, so that the clients could
+    // be redirected to the new leaders without waiting for the election timeout.
+    if config.graceful_shutdown_seconds > 0 {
+        let timeout = Duration::from_secs(config.graceful_shutdown_seconds);
+        crate::runtime::select! {
+            res = &mut server => { res? }
+            _ = node.transfer_leaders_out(timeout) => {}
+        };
+    }
+
     Ok(())
 }
 
 pub(crate) fn open_engine<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<rocksdb::DB> {
-    use rocksdb::{BlockBasedIndexType, BlockBasedOptions, Cache, Options, DB};
-
-    std::fs::create_dir_all(&path)?;
-
-    let mut opts = Options::default();
-    opts.create_if_missing(true);
-    opts.create_missing_column_families(true);
-
-    opts.set_max_background_jobs(cfg.max_background_jobs);
-    opts.set_max_subcompactions(cfg.max_sub_compactions);
-    opts.set_max_manifest_file_size(cfg.max_manifest_file_size);
-    opts.set_bytes_per_sync(cfg.bytes_per_sync);
-    opts.set_compaction_readahead_size(cfg.compaction_readahead_size);
-    opts.set_use_direct_reads(cfg.use_direct_read);
-    opts.set_use_direct_io_for_flush_and_compaction(cfg.use_direct_io_for_flush_and_compaction);
-    opts.set_avoid_unnecessary_blocking_io(cfg.avoid_unnecessary_blocking_io);
-
-    opts.set_write_buffer_size(cfg.write_buffer_size);
-    opts.set_max_write_buffer_number(cfg.max_write_buffer_number);
-    opts.set_min_write_buffer_number_to_merge(cfg.min_write_buffer_number_to_merge);
-
-    opts.set_num_levels(cfg.num_levels);
-    opts.set_compression_per_level(&cfg.compression_per_level);
-
-    opts.set_level_zero_file_num_compaction_trigger(cfg.level0_file_num_compaction_trigger);
-    opts.set_target_file_size_base(cfg.target_file_size_base);
-    opts.set_max_bytes_for_level_base(cfg.max_bytes_for_level_base);
-    opts.set_max_bytes_for_level_multiplier(cfg.max_bytes_for_level_multiplier);
-    opts.set_max_compaction_bytes(cfg.max_compaction_bytes);
-    opts.set_level_compaction_dynamic_level_bytes(true);
-
-    opts.set_level_zero_slowdown_writes_trigger(cfg.level0_slowdown_writes_trigger);
-    opts.set_level_zero_stop_writes_trigger(cfg.level0_slowdown_writes_trigger);
-    opts.set_soft_pending_compaction_bytes_limit(cfg.soft_pending_compaction_bytes_limit);
-    opts.set_hard_pending_compaction_bytes_limit(cfg.hard_pending_compaction_bytes_limit);
-
-    opts.set_auto_tuned_ratelimiter(
-        cfg.rate_limiter_bytes_per_sec,
-        cfg.rate_limiter_refill_period,
-        10,
-        cfg.rate_limiter_auto_tuned,
-    );
-
-    let cache = Cache::new_lru_cache(cfg.block_cache_size)?;
-
-    let mut blk_opts = BlockBasedOptions::default();
-    blk_opts.set_index_type(BlockBasedIndexType::TwoLevelIndexSearch);
-    blk_opts.set_block_size(cfg.block_size);
-    blk_opts.set_block_cache(&cache);
-    blk_opts.set_cache_index_and_filter_blocks(true);
-    blk_opts.set_bloom_filter(10.0, false);
-    opts.set_block_based_table_factory(&blk_opts);
-
-    // List column families and open database with column families.
-    match DB::list_cf(&Options::default(), &path) {
-        Ok(cfs) => {
-            debug!("open local db with {} column families", cfs.len());
-            Ok(DB::open_cf(&opts, path, cfs)?)
-        }
-        Err(e) => {
-            if e.as_ref().ends_with("CURRENT: No such file or directory") {
-                info!("create new local db");
-                Ok(DB::open(&opts, &path)?)
-            } else {
-                Err(e.into())
-            }
-        }
-    }
+    // The column families of shards are opened with the compaction filters of range tombstones.
+    let db_path = path.as_ref().to_owned();
+    Ok(engula_engine::open_raw_db_with_cf_options(
+        cfg,
+        path,
+        |name, opts| GroupEngine::cf_options(&db_path, name, opts),
+    )?)
 }
 
 async fn bootstrap_or_join_cluster(
@@ -200,6 +218,7 @@ async fn bootstrap_or_join_cluster(
         try_join_cluster(
             node,
             &config.addr,
+            &config.node.zone,
             config.join_list.clone(),
             config.cpu_nums,
             root_client,
@@ -211,6 +230,7 @@ async fn bootstrap_or_join_cluster(
 async fn try_join_cluster(
     node: &Node,
     local_addr: &str,
+    zone: &str,
     join_list: Vec<String>,
     cpu_nums: u32,
     root_client: &RootClient,
@@ -236,6 +256,8 @@ async fn try_join_cluster(
     let req = JoinNodeRequest {
         addr: local_addr.to_owned(),
         capacity: Some(capacity),
+        zone: zone.to_owned(),
+        features: supported_features(),
     };
 
     let mut backoff: u64 = 1;
@@ -246,6 +268,8 @@ async fn try_join_cluster(
                 let node_ident =
                     save_node_ident(node.state_engine(), res.cluster_id, res.node_id).await;
                 node.update_root(res.root.unwrap_or_default()).await?;
+                node.update_features(res.features.unwrap_or_default())
+                    .await?;
                 return node_ident;
             }
             Err(e) => {
@@ -329,6 +353,10 @@ async fn write_initial_cluster_data(node: &Node, addr: &str) -> Result<()> {
         root_nodes: vec![root_node],
     };
     node.update_root(root_desc).await?;
+    node.update_features(ClusterFeatures {
+        active: supported_features(),
+    })
+    .await?;
 
     Ok(())
 }
@@ -336,6 +364,7 @@ async fn write_initial_cluster_data(node: &Node, addr: &str) -> Result<()> {
 pub(crate) async fn build_provider(config: &Config, executor: Executor) -> Result<Arc<Provider>> {
     let db_path = config.root_dir.join("db");
     let log_path = config.root_dir.join("log");
+    crate::layout::upgrade_layout(&config.root_dir)?;
     let raw_db = Arc::new(open_engine(&config.db, &db_path)?);
 
     let root_list = if config.init {
@@ -345,10 +374,17 @@ pub(crate) async fn build_provider(config: &Config, executor: Executor) -> Resul
     };
     let state_engine = StateEngine::new(raw_db.clone())?;
     let discovery = Arc::new(RootDiscovery::new(root_list, state_engine.clone()));
-    let conn_manager = ConnManager::new();
+    let mut conn_manager = ConnManager::new();
+    if config.node.compress_threshold > 0 {
+        conn_manager = conn_manager.with_compression(config.node.compress_threshold);
+    }
     let root_client = RootClient::new(discovery, conn_manager.clone());
     let router = Router::new(root_client.clone()).await;
     let address_resolver = Arc::new(AddressResolver::new(router.clone()));
+    let audit = Arc::new(AuditLog::open(
+        &config.audit,
+        config.root_dir.join("audit"),
+    )?);
     let provider = Arc::new(Provider {
         log_path,
         db_path,
@@ -359,11 +395,24 @@ pub(crate) async fn build_provider(config: &Config, executor: Executor) -> Resul
         raw_db,
         state_engine,
         executor,
+        audit,
+        memory_tracker: Arc::new(MemoryTracker::new(config.node.memory_budget)),
+        storage_health: Arc::new(StorageHealth::default()),
+        feature_gates: Arc::new(FeatureGates::default()),
     });
     Ok(provider)
 }
 
-#[cfg(test)]
+/// Open the local db in read only mode, it is used by offline tools so the data directory is never
+/// modified.
+pub(crate) fn open_engine_for_read_only<P: AsRef<Path>>(path: P) -> Result<rocksdb::DB> {
+    use rocksdb::{Options, DB};
+
+    let opts = Options::default();
+    let cfs = DB::list_cf(&opts, &path)?;
+    Ok(DB::open_cf_for_read_only(&opts, path, cfs, false)?)
+}
+
 pub(crate) fn open_engine_with_default_config<P: AsRef<Path>>(path: P) -> Result<rocksdb::DB> {
     open_engine(&DbConfig::default(), path)
 }
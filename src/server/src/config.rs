@@ -14,10 +14,10 @@
 
 use std::path::PathBuf;
 
-use rocksdb::DBCompressionType;
+pub use engula_engine::DbConfig;
 use serde::{Deserialize, Serialize};
 
-use crate::{ExecutorConfig, NodeConfig, RaftConfig, RootConfig};
+use crate::{AuditConfig, ExecutorConfig, NodeConfig, RaftConfig, RootConfig};
 
 #[derive(Default, Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
@@ -30,10 +30,44 @@ pub struct Config {
 
     pub init: bool,
 
+    /// Serve a single group backed directly by the engine, without raft and root coordination.
+    /// It is intended for local development and CI, the data isn't replicated.
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub standalone: bool,
+
     pub enable_proxy_service: bool,
 
+    /// The bearer tokens accepted by the HTTP proxy service, requests without one of them are
+    /// rejected. Empty means no authentication.
+    ///
+    /// Default: [].
+    #[serde(default)]
+    pub proxy_auth_tokens: Vec<String>,
+
+    /// The address of the experimental, read-only Postgres wire protocol listener, it requires
+    /// `enable_proxy_service`, and the `proxy_auth_tokens` are accepted as passwords. Empty
+    /// means disabled.
+    ///
+    /// Default: "".
+    #[serde(default)]
+    pub pgwire_addr: String,
+
+    /// Whether to serve the sampling profiler endpoints under `/debug/pprof`.
+    pub enable_pprof: bool,
+
+    /// The existing members of the cluster, the seeds supported by `SeedServiceDiscovery` are
+    /// also accepted.
     pub join_list: Vec<String>,
 
+    /// The seconds to wait for transferring leaderships out when shutting down, 0 means exit
+    /// immediately.
+    ///
+    /// Default: 0.
+    #[serde(default)]
+    pub graceful_shutdown_seconds: u64,
+
     #[serde(default)]
     pub node: NodeConfig,
 
@@ -48,106 +82,7 @@ pub struct Config {
 
     #[serde(default)]
     pub db: DbConfig,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct DbConfig {
-    // io related configs
-    pub max_background_jobs: i32,
-    pub max_sub_compactions: u32,
-    pub max_manifest_file_size: usize,
-    pub bytes_per_sync: u64,
-    pub compaction_readahead_size: usize,
-    pub use_direct_read: bool,
-    pub use_direct_io_for_flush_and_compaction: bool,
-    pub avoid_unnecessary_blocking_io: bool,
-
-    // block & block cache cache related configs
-    pub block_size: usize,
-    pub block_cache_size: usize,
-
-    // write buffer related configs
-    pub write_buffer_size: usize,
-    pub max_write_buffer_number: i32,
-    pub min_write_buffer_number_to_merge: i32,
-
-    pub num_levels: i32,
-    pub compression_per_level: [DBCompressionType; 7],
-
-    // compaction related configs
-    pub level0_file_num_compaction_trigger: i32,
-    pub target_file_size_base: u64,
-    pub max_bytes_for_level_base: u64,
-    pub max_bytes_for_level_multiplier: f64,
-    pub max_compaction_bytes: u64,
-    pub level_compaction_dynamic_level_bytes: bool,
-
-    // write slowdown related configs
-    pub level0_stop_write_trigger: i32,
-    pub level0_slowdown_writes_trigger: i32,
-    pub soft_pending_compaction_bytes_limit: usize,
-    pub hard_pending_compaction_bytes_limit: usize,
-
-    // rate limiter related configs
-    pub rate_limiter_bytes_per_sec: i64,
-    pub rate_limiter_refill_period: i64,
-    pub rate_limiter_auto_tuned: bool,
-}
 
-impl Default for DbConfig {
-    fn default() -> Self {
-        DbConfig {
-            max_background_jobs: 2,
-            max_sub_compactions: 1,
-            max_manifest_file_size: 1 << 30,
-            bytes_per_sync: 1 << 20,
-            compaction_readahead_size: 0,
-            use_direct_read: false,
-            use_direct_io_for_flush_and_compaction: false,
-            avoid_unnecessary_blocking_io: true,
-
-            block_size: 4 << 10,
-            block_cache_size: adaptive_block_cache_size(),
-            write_buffer_size: 64 << 20,
-            max_write_buffer_number: 3,
-            min_write_buffer_number_to_merge: 1,
-
-            num_levels: 7,
-            compression_per_level: [
-                DBCompressionType::None,
-                DBCompressionType::None,
-                DBCompressionType::Lz4,
-                DBCompressionType::Lz4,
-                DBCompressionType::Lz4,
-                DBCompressionType::Zstd,
-                DBCompressionType::Zstd,
-            ],
-
-            level0_file_num_compaction_trigger: 4,
-            target_file_size_base: 64 << 20,
-            max_bytes_for_level_base: 256 << 20,
-            max_bytes_for_level_multiplier: 10.0,
-            max_compaction_bytes: 0,
-            level_compaction_dynamic_level_bytes: true,
-
-            level0_stop_write_trigger: 36,
-            level0_slowdown_writes_trigger: 20,
-            soft_pending_compaction_bytes_limit: 64 << 30,
-            hard_pending_compaction_bytes_limit: 256 << 30,
-
-            rate_limiter_bytes_per_sec: 10 << 30,
-            rate_limiter_refill_period: 100_000,
-            rate_limiter_auto_tuned: true,
-        }
-    }
-}
-
-fn adaptive_block_cache_size() -> usize {
-    if cfg!(test) {
-        return 32 << 20;
-    }
-
-    use sysinfo::{RefreshKind, System, SystemExt};
-    let info = System::new_with_specifics(RefreshKind::new().with_memory());
-    (info.total_memory() / 2) as usize
+    #[serde(default)]
+    pub audit: AuditConfig,
 }
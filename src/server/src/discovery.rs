@@ -12,19 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use engula_client::ServiceDiscovery;
+use engula_client::{SeedServiceDiscovery, ServiceDiscovery};
 
 use crate::node::StateEngine;
 
+/// Discovers the root nodes from the persisted root descriptor, or from the initial nodes if the
+/// node has not joined the cluster yet. The initial nodes could be seeds, see
+/// [`SeedServiceDiscovery`].
 pub struct RootDiscovery {
-    initial_nodes: Vec<String>,
+    initial_nodes: SeedServiceDiscovery,
     state_engine: StateEngine,
 }
 
 impl RootDiscovery {
     pub fn new(initial_nodes: Vec<String>, state_engine: StateEngine) -> Self {
         RootDiscovery {
-            initial_nodes,
+            initial_nodes: SeedServiceDiscovery::new(initial_nodes),
             state_engine,
         }
     }
@@ -36,6 +39,6 @@ impl ServiceDiscovery for RootDiscovery {
         if let Ok(Some(root)) = self.state_engine.load_root_desc().await {
             return root.root_nodes.into_iter().map(|n| n.addr).collect();
         }
-        self.initial_nodes.clone()
+        self.initial_nodes.list_nodes().await
     }
 }
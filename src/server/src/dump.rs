@@ -0,0 +1,246 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{io::Write, path::Path};
+
+use raft::prelude::{ConfChangeV2, Entry, EntryType};
+use serde::Serialize;
+
+use crate::{
+    bootstrap::open_engine_for_read_only,
+    node::engine::{GroupEngine, WriteBatch},
+    raftgroup::{entry_data, fetch_all_entries},
+    serverpb::v1::{EvalResult, MigrationEvent, SyncOp},
+    Error, Result,
+};
+
+/// The summary of a raft log entry printed by [`dump_raft_log`].
+#[derive(Debug, Serialize)]
+struct RaftLogEntry {
+    replica_id: u64,
+    index: u64,
+    term: u64,
+    entry_type: &'static str,
+    data_size: usize,
+    summary: String,
+}
+
+/// Dump the engine contents of the group from the data directory of a stopped node, one line per
+/// mvcc entry: shard id, escaped user key, version, value size and tombstone flag, separated by
+/// tabs. Each replica of the group found in the local db is dumped after a `#` header line.
+///
+/// Returns the number of dumped entries.
+pub fn dump_group<P, W>(root_dir: P, group_id: u64, w: &mut W) -> Result<usize>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    let db_path = root_dir.as_ref().join("db");
+    let raw_db = open_engine_for_read_only(&db_path)?;
+    let mut num_entries = 0;
+    for (cf_name, replica_id) in group_replicas(&db_path, group_id)? {
+        writeln!(w, "# group {group_id} replica {replica_id}")?;
+        let desc = GroupEngine::dump(&raw_db, &cf_name, |shard_id, entry| {
+            let key = entry
+                .user_key()
+                .iter()
+                .flat_map(|b| std::ascii::escape_default(*b))
+                .map(char::from)
+                .collect::<String>();
+            let value_size = entry.value().map(|v| v.len()).unwrap_or_default();
+            writeln!(
+                w,
+                "{shard_id}\t{key}\t{}\t{value_size}\t{}",
+                entry.version(),
+                entry.is_tombstone()
+            )?;
+            num_entries += 1;
+            Ok(())
+        })?;
+        writeln!(
+            w,
+            "# group {group_id} replica {replica_id} descriptor {desc:?}"
+        )?;
+    }
+    w.flush()?;
+    Ok(num_entries)
+}
+
+/// Dump the raft log of the group from the data directory of a stopped node, one line per entry:
+/// index, term, entry type, data size and a summary of the decoded command, separated by tabs. Each
+/// entry is printed as a JSON object instead if `json` is specified.
+///
+/// Returns the number of dumped entries.
+pub fn dump_raft_log<P, W>(root_dir: P, group_id: u64, json: bool, w: &mut W) -> Result<usize>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    let replicas = group_replicas(&root_dir.as_ref().join("db"), group_id)?;
+    let engine_dir = root_dir.as_ref().join("log").join("engine");
+    let engine_cfg = raft_engine::Config {
+        dir: engine_dir.to_str().unwrap().to_owned(),
+        ..Default::default()
+    };
+    let engine = raft_engine::Engine::open(engine_cfg)?;
+
+    let mut num_entries = 0;
+    for (_, replica_id) in replicas {
+        if !json {
+            writeln!(w, "# group {group_id} replica {replica_id}")?;
+        }
+        for entry in fetch_all_entries(&engine, replica_id)? {
+            let entry = RaftLogEntry::new(replica_id, &entry);
+            if json {
+                serde_json::to_writer(&mut *w, &entry)
+                    .map_err(|e| Error::InvalidData(e.to_string()))?;
+                writeln!(w)?;
+            } else {
+                writeln!(
+                    w,
+                    "{}\t{}\t{}\t{}\t{}",
+                    entry.index, entry.term, entry.entry_type, entry.data_size, entry.summary
+                )?;
+            }
+            num_entries += 1;
+        }
+    }
+    w.flush()?;
+    Ok(num_entries)
+}
+
+/// Returns the column family names and replica ids of the group found in the local db.
+fn group_replicas(db_path: &Path, group_id: u64) -> Result<Vec<(String, u64)>> {
+    let prefix = format!("{group_id}-");
+    let replicas = rocksdb::DB::list_cf(&rocksdb::Options::default(), db_path)?
+        .into_iter()
+        .filter_map(|name| {
+            // The column families of shards, `{group}-{replica}-{shard}`, are skipped.
+            let replica_id = name.strip_prefix(&prefix)?.parse().ok()?;
+            Some((name, replica_id))
+        })
+        .collect::<Vec<_>>();
+    if replicas.is_empty() {
+        return Err(Error::GroupNotFound(group_id));
+    }
+    Ok(replicas)
+}
+
+impl RaftLogEntry {
+    fn new(replica_id: u64, entry: &Entry) -> Self {
+        use prost::Message;
+
+        let (entry_type, summary) = match entry.get_entry_type() {
+            EntryType::EntryNormal if entry.data.is_empty() => ("normal", "empty".to_owned()),
+            EntryType::EntryNormal => {
+                let summary = match entry_data(entry) {
+                    Ok(data) => match EvalResult::decode(&*data) {
+                        Ok(eval_result) => describe_eval_result(&eval_result),
+                        Err(e) => format!("invalid eval result: {e}"),
+                    },
+                    Err(e) => format!("invalid compressed data: {e}"),
+                };
+                ("normal", summary)
+            }
+            EntryType::EntryConfChange => ("conf_change", "unsupported".to_owned()),
+            EntryType::EntryConfChangeV2 if entry.data.is_empty() => {
+                ("conf_change_v2", "auto leave".to_owned())
+            }
+            EntryType::EntryConfChangeV2 => {
+                let summary = match ConfChangeV2::decode(&*entry.data) {
+                    Ok(cc) => format!("{:?}", cc.changes),
+                    Err(e) => format!("invalid conf change: {e}"),
+                };
+                ("conf_change_v2", summary)
+            }
+        };
+        RaftLogEntry {
+            replica_id,
+            index: entry.index,
+            term: entry.term,
+            entry_type,
+            data_size: entry.data.len(),
+            summary,
+        }
+    }
+}
+
+fn describe_eval_result(eval_result: &EvalResult) -> String {
+    let mut parts = vec![];
+    if let Some(batch) = eval_result.batch.as_ref() {
+        let wb = WriteBatch::new(&batch.data);
+        parts.push(format!("write batch {} ops", wb.len()));
+    }
+    if let Some(op) = eval_result.op.as_ref() {
+        parts.push(describe_sync_op(op));
+    }
+    parts.join(", ")
+}
+
+fn describe_sync_op(op: &SyncOp) -> String {
+    if let Some(shard) = op.add_shard.as_ref().and_then(|v| v.shard.as_ref()) {
+        format!("add shard {}", shard.id)
+    } else if let Some(purge) = op.purge_replica.as_ref() {
+        format!("purge replica {}", purge.replica_id)
+    } else if let Some(m) = op.migration.as_ref() {
+        let event = match MigrationEvent::from_i32(m.event) {
+            Some(event) => format!("{event:?}"),
+            None => format!("unknown event {}", m.event),
+        };
+        let shard_id = m
+            .migration_desc
+            .as_ref()
+            .and_then(|d| d.shard_desc.as_ref())
+            .map(|s| s.id)
+            .unwrap_or_default();
+        format!("migration {event} shard {shard_id}")
+    } else if let Some(c) = op.compute_checksum.as_ref() {
+        format!("compute checksum {} of shard {}", c.checksum_id, c.shard_id)
+    } else {
+        "unknown sync op".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engula_api::server::v1::ShardDesc;
+    use prost::Message;
+
+    use super::*;
+
+    #[test]
+    fn summarize_raft_log_entry() {
+        let mut entry = Entry {
+            index: 5,
+            term: 2,
+            ..Default::default()
+        };
+        let summary = RaftLogEntry::new(1, &entry);
+        assert_eq!(summary.entry_type, "normal");
+        assert_eq!(summary.summary, "empty");
+
+        let eval_result = EvalResult {
+            op: Some(SyncOp::add_shard(ShardDesc {
+                id: 3,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        entry.data = eval_result.encode_to_vec();
+        let summary = RaftLogEntry::new(1, &entry);
+        assert_eq!((summary.index, summary.term), (5, 2));
+        assert_eq!(summary.data_size, entry.data.len());
+        assert_eq!(summary.summary, "add shard 3");
+    }
+}
@@ -34,6 +34,18 @@ pub enum Error {
     #[error("{0} is exhausted")]
     ResourceExhausted(String),
 
+    #[error("key size {0} exceeds the limit {1}")]
+    KeyTooLarge(usize, usize),
+
+    #[error("value size {0} exceeds the limit {1}")]
+    ValueTooLarge(usize, usize),
+
+    #[error("batch size {0} exceeds the limit {1}")]
+    BatchTooLarge(usize, usize),
+
+    #[error("condition not met: {0}")]
+    ConditionNotMet(String),
+
     // internal errors
     #[error("shard {0} not found")]
     ShardNotFound(u64),
@@ -41,6 +53,9 @@ pub enum Error {
     #[error("invalid {0} data")]
     InvalidData(String),
 
+    #[error("apply task {0}")]
+    ApplyTask(String),
+
     #[error("request canceled")]
     Canceled,
 
@@ -97,6 +112,41 @@ pub enum Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl From<engula_engine::Error> for Error {
+    fn from(err: engula_engine::Error) -> Self {
+        match err {
+            engula_engine::Error::Io(err) => Error::Io(err),
+            engula_engine::Error::RocksDb(err) => Error::RocksDb(err),
+            err @ engula_engine::Error::InvalidBackup(_) => Error::InvalidData(err.to_string()),
+        }
+    }
+}
+
+impl Error {
+    /// Returns whether the request could succeed by retrying, maybe on another replica (see
+    /// [`Error::is_redirect`]). It is consistent with [`engula_client::Error::is_retryable`] once
+    /// the error is sent to clients.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Error::ServiceIsBusy(_)
+                | Error::GroupNotReady(_)
+                | Error::GroupNotFound(_)
+                | Error::NotLeader(..)
+                | Error::NotRootLeader(..)
+                | Error::EpochNotMatch(_)
+        )
+    }
+
+    /// Returns whether the request should be sent to another replica or group.
+    pub fn is_redirect(&self) -> bool {
+        matches!(
+            self,
+            Error::NotLeader(..) | Error::NotRootLeader(..) | Error::EpochNotMatch(_)
+        )
+    }
+}
+
 impl From<Error> for tonic::Status {
     fn from(e: Error) -> Self {
         use engula_api::server::v1;
@@ -109,6 +159,11 @@ impl From<Error> for tonic::Status {
             err @ Error::DatabaseNotFound(_) => Status::not_found(err.to_string()),
             err @ Error::AlreadyExists(_) => Status::already_exists(err.to_string()),
             Error::ResourceExhausted(msg) => Status::resource_exhausted(msg),
+            err
+            @ (Error::KeyTooLarge(..) | Error::ValueTooLarge(..) | Error::BatchTooLarge(..)) => {
+                Status::invalid_argument(err.to_string())
+            }
+            Error::ConditionNotMet(msg) => Status::failed_precondition(msg),
 
             Error::GroupNotFound(group_id) => Status::with_details(
                 Code::Unknown,
@@ -143,6 +198,7 @@ impl From<Error> for tonic::Status {
             | Error::AbortScheduleTask(_)
             | Error::ClusterNotMatch
             | Error::InvalidData(_)
+            | Error::ApplyTask(_)
             | Error::Transport(_)
             | Error::Io(_)
             | Error::RocksDb(_)
@@ -190,6 +246,11 @@ impl From<Error> for engula_api::server::v1::Error {
 
             Error::InvalidArgument(msg) => v1::Error::status(Code::InvalidArgument.into(), msg),
             Error::DeadlineExceeded(msg) => v1::Error::status(Code::DeadlineExceeded.into(), msg),
+            err
+            @ (Error::KeyTooLarge(..) | Error::ValueTooLarge(..) | Error::BatchTooLarge(..)) => {
+                v1::Error::status(Code::InvalidArgument.into(), err.to_string())
+            }
+            Error::ConditionNotMet(msg) => v1::Error::status(Code::FailedPrecondition.into(), msg),
 
             Error::Forward(_) => panic!("Forward only used inside node"),
             Error::ServiceIsBusy(_) => panic!("ServiceIsBusy only used inside node"),
@@ -204,6 +265,7 @@ impl From<Error> for engula_api::server::v1::Error {
             | Error::RocksDb(_)
             | Error::Io(_)
             | Error::InvalidData(_)
+            | Error::ApplyTask(_)
             | Error::DatabaseNotFound(_)
             | Error::ShardNotFound(_)
             | Error::ClusterNotMatch
@@ -227,6 +289,7 @@ impl From<engula_client::Error> for Error {
             engula_client::Error::DeadlineExceeded(v) => Error::DeadlineExceeded(v),
             engula_client::Error::AlreadyExists(v) => Error::AlreadyExists(v),
             engula_client::Error::ResourceExhausted(v) => Error::ResourceExhausted(v),
+            engula_client::Error::ConditionNotMet(v) => Error::ConditionNotMet(v),
             engula_client::Error::Rpc(err) => Error::Rpc(err),
             engula_client::Error::Connect(err) => Error::Rpc(err),
             engula_client::Error::Transport(err) => Error::Rpc(err),
@@ -0,0 +1,158 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashSet, sync::Mutex};
+
+use engula_api::server::v1::group_request_union::Request;
+
+use crate::{Error, Result};
+
+/// The features that change the wire or storage formats. They are activated only if all nodes of
+/// the cluster support them, so that the nodes running the former binary are never asked to apply
+/// the unknown formats during rolling upgrades.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// The `DeleteRange` group request and sync op.
+    DeleteRange,
+    /// The `Rename` group request and sync op.
+    Rename,
+    /// The `Sample` group request.
+    Sample,
+    /// The `Scan` group request.
+    Scan,
+    /// The raft entries compressed by zstd.
+    EntryCompression,
+}
+
+impl Feature {
+    /// All features supported by this binary.
+    pub const ALL: &'static [Feature] = &[
+        Feature::DeleteRange,
+        Feature::Rename,
+        Feature::Sample,
+        Feature::Scan,
+        Feature::EntryCompression,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::DeleteRange => "delete_range",
+            Feature::Rename => "rename",
+            Feature::Sample => "sample",
+            Feature::Scan => "scan",
+            Feature::EntryCompression => "entry_compression",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Feature> {
+        Feature::ALL.iter().find(|f| f.name() == name).cloned()
+    }
+
+    /// Returns the feature which must be activated before serving the request.
+    pub fn required_by(request: &Request) -> Option<Feature> {
+        match request {
+            Request::DeleteRange(_) => Some(Feature::DeleteRange),
+            Request::Rename(_) => Some(Feature::Rename),
+            Request::Sample(_) => Some(Feature::Sample),
+            Request::Scan(_) => Some(Feature::Scan),
+            _ => None,
+        }
+    }
+}
+
+/// The names of all features supported by this binary.
+pub fn supported_features() -> Vec<String> {
+    Feature::ALL.iter().map(|f| f.name().to_owned()).collect()
+}
+
+/// The features activated in the cluster, which are synced from root by heartbeats.
+#[derive(Default)]
+pub struct FeatureGates {
+    active: Mutex<HashSet<Feature>>,
+}
+
+impl FeatureGates {
+    pub fn is_active(&self, feature: Feature) -> bool {
+        self.active.lock().unwrap().contains(&feature)
+    }
+
+    /// Returns `Error::InvalidArgument` if the feature is not activated.
+    pub fn check(&self, feature: Feature) -> Result<()> {
+        if self.is_active(feature) {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument(format!(
+                "feature {} is not activated, some nodes of the cluster don't support it",
+                feature.name()
+            )))
+        }
+    }
+
+    /// Activate the features, the unknown features are ignored. A feature is never deactivated.
+    pub fn activate(&self, names: &[String]) {
+        let mut active = self.active.lock().unwrap();
+        active.extend(names.iter().filter_map(|name| Feature::from_name(name)));
+    }
+}
+
+/// Returns the features that should be activated besides the `active` ones: those supported by
+/// this binary and all of the `nodes`.
+pub fn activatable_features<'a, I>(active: &[String], nodes: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a [String]>,
+{
+    let mut candidates = Feature::ALL
+        .iter()
+        .map(|f| f.name())
+        .filter(|name| !active.iter().any(|a| a == name))
+        .collect::<HashSet<_>>();
+    for features in nodes {
+        candidates.retain(|name| features.iter().any(|f| f == name));
+    }
+    let mut features = candidates
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    features.sort_unstable();
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activate_features_supported_by_all_nodes() {
+        let old = vec!["delete_range".to_owned()];
+        let new = supported_features();
+        let nodes = [old.as_slice(), new.as_slice()];
+        assert_eq!(activatable_features(&[], nodes), vec!["delete_range"]);
+        assert!(activatable_features(&old, nodes).is_empty());
+
+        let nodes = [new.as_slice(), new.as_slice()];
+        assert_eq!(
+            activatable_features(&old, nodes),
+            vec!["entry_compression", "rename", "sample", "scan"]
+        );
+    }
+
+    #[test]
+    fn feature_gates_ignore_unknown_features() {
+        let gates = FeatureGates::default();
+        assert!(gates.check(Feature::Rename).is_err());
+        gates.activate(&["rename".to_owned(), "unknown".to_owned()]);
+        assert!(gates.check(Feature::Rename).is_ok());
+        assert!(!gates.is_active(Feature::DeleteRange));
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use crate::Result;
+
+/// Create or replace the file with `content` atomically: the content is written to a temp file in
+/// the same dir, synced and renamed to `path`, then the dir is synced. A crash leaves either the
+/// former file or the new one, never a half-written file.
+pub(crate) fn create_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+    tmp_name.push(".tmp");
+    let tmp = dir.join(tmp_name);
+
+    // A temp file might be left by the former crash, truncate it.
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::rename(&tmp, path)?;
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn create_atomic_replace_file() {
+        let dir = TempDir::new("create_atomic_replace_file").unwrap();
+        let path = dir.path().join("META");
+
+        // The stale temp file is longer than the new content.
+        std::fs::write(dir.path().join("META.tmp"), b"stale content").unwrap();
+        create_atomic(&path, b"abc").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"abc");
+
+        create_atomic(&path, b"de").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"de");
+        assert!(!dir.path().join("META.tmp").exists());
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The layout of the node data directory is versioned by the `LAYOUT` file under the root dir, the
+//! data directory written by former binaries is migrated to the current layout when starting.
+
+use std::{path::Path, time::Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{fs::create_atomic, Error, Result};
+
+/// The version of the data directory layout used by this binary.
+pub(crate) const LAYOUT_VERSION: u32 = 1;
+
+const LAYOUT_FILE: &str = "LAYOUT";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LayoutDesc {
+    version: u32,
+
+    /// The target version of the ongoing upgrade. It is persisted before migrating, so the older
+    /// binaries refuse to open a partially migrated data directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    upgrading: Option<u32>,
+}
+
+struct Migration {
+    /// Migrate the layout from `version` to `version + 1`.
+    version: u32,
+    desc: &'static str,
+    /// The migrations must be idempotent, they are executed again if the upgrade is interrupted.
+    migrate: fn(&Path) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 0,
+    desc: "adopt the unversioned data directory",
+    migrate: |_| Ok(()),
+}];
+
+/// Upgrade the layout of the data directory to [`LAYOUT_VERSION`], an error is returned if the
+/// data directory is written by a newer binary.
+pub(crate) fn upgrade_layout(root_dir: &Path) -> Result<()> {
+    upgrade_layout_to(root_dir, LAYOUT_VERSION, MIGRATIONS)
+}
+
+fn upgrade_layout_to(root_dir: &Path, target: u32, migrations: &[Migration]) -> Result<()> {
+    let path = root_dir.join(LAYOUT_FILE);
+    let mut desc = match read_layout(&path)? {
+        Some(desc) => desc,
+        None if !root_dir.join("db").exists() => {
+            // A fresh data directory.
+            std::fs::create_dir_all(root_dir)?;
+            return write_layout(
+                &path,
+                &LayoutDesc {
+                    version: target,
+                    upgrading: None,
+                },
+            );
+        }
+        None => LayoutDesc::default(),
+    };
+
+    let newest = desc.upgrading.unwrap_or_default().max(desc.version);
+    if newest > target {
+        return Err(Error::InvalidData(format!(
+            "data directory layout version {newest}, but at most {target} is supported, \
+             downgrade is not allowed"
+        )));
+    }
+    if let Some(version) = desc.upgrading {
+        warn!(
+            "resume the interrupted data directory layout upgrade from {} to {version}",
+            desc.version
+        );
+    }
+    if desc.version == target && desc.upgrading.is_none() {
+        return Ok(());
+    }
+
+    info!(
+        "upgrade data directory layout from {} to {target}",
+        desc.version
+    );
+    desc.upgrading = Some(target);
+    write_layout(&path, &desc)?;
+    while desc.version < target {
+        let migration = migrations
+            .iter()
+            .find(|m| m.version == desc.version)
+            .ok_or_else(|| {
+                Error::InvalidData(format!("no migration for layout version {}", desc.version))
+            })?;
+        let start = Instant::now();
+        info!(
+            "migrate data directory layout from {} to {}: {}",
+            desc.version,
+            desc.version + 1,
+            migration.desc
+        );
+        (migration.migrate)(root_dir)?;
+        desc.version += 1;
+        write_layout(&path, &desc)?;
+        info!(
+            "data directory layout is migrated to {}, takes {:?}",
+            desc.version,
+            start.elapsed()
+        );
+    }
+    desc.upgrading = None;
+    write_layout(&path, &desc)
+}
+
+fn read_layout(path: &Path) -> Result<Option<LayoutDesc>> {
+    match std::fs::read(path) {
+        Ok(content) => serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|e| Error::InvalidData(format!("layout file {}: {e}", path.display()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_layout(path: &Path, desc: &LayoutDesc) -> Result<()> {
+    let content = serde_json::to_vec(desc).expect("LayoutDesc is serializable");
+    create_atomic(path, &content)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 0,
+            desc: "adopt",
+            migrate: |_| Ok(()),
+        },
+        Migration {
+            version: 1,
+            desc: "move db",
+            migrate: |root_dir| {
+                if root_dir.join("db").exists() {
+                    std::fs::rename(root_dir.join("db"), root_dir.join("engine"))?;
+                }
+                Ok(())
+            },
+        },
+    ];
+
+    fn layout(root_dir: &Path) -> LayoutDesc {
+        read_layout(&root_dir.join(LAYOUT_FILE)).unwrap().unwrap()
+    }
+
+    #[test]
+    fn fresh_data_directory() {
+        let dir = TempDir::new("fresh_data_directory").unwrap();
+        let root_dir = dir.path().join("node");
+        upgrade_layout_to(&root_dir, 2, TEST_MIGRATIONS).unwrap();
+        assert_eq!(layout(&root_dir).version, 2);
+        assert!(!root_dir.join("engine").exists());
+    }
+
+    #[test]
+    fn migrate_unversioned_data_directory() {
+        let dir = TempDir::new("migrate_unversioned_data_directory").unwrap();
+        let root_dir = dir.path();
+        std::fs::create_dir(root_dir.join("db")).unwrap();
+        upgrade_layout_to(root_dir, 2, TEST_MIGRATIONS).unwrap();
+        assert_eq!(
+            layout(root_dir),
+            LayoutDesc {
+                version: 2,
+                upgrading: None
+            }
+        );
+        assert!(root_dir.join("engine").exists());
+
+        // Nothing changes if it is already upgraded.
+        upgrade_layout_to(root_dir, 2, TEST_MIGRATIONS).unwrap();
+        assert_eq!(layout(root_dir).version, 2);
+    }
+
+    #[test]
+    fn resume_interrupted_upgrade() {
+        let dir = TempDir::new("resume_interrupted_upgrade").unwrap();
+        let root_dir = dir.path();
+        std::fs::create_dir(root_dir.join("engine")).unwrap();
+        let desc = LayoutDesc {
+            version: 1,
+            upgrading: Some(2),
+        };
+        write_layout(&root_dir.join(LAYOUT_FILE), &desc).unwrap();
+
+        // The older binary refuses to open it.
+        assert!(upgrade_layout_to(root_dir, 1, TEST_MIGRATIONS).is_err());
+
+        upgrade_layout_to(root_dir, 2, TEST_MIGRATIONS).unwrap();
+        assert_eq!(
+            layout(root_dir),
+            LayoutDesc {
+                version: 2,
+                upgrading: None
+            }
+        );
+    }
+
+    #[test]
+    fn refuse_to_downgrade() {
+        let dir = TempDir::new("refuse_to_downgrade").unwrap();
+        let root_dir = dir.path();
+        upgrade_layout_to(root_dir, 2, TEST_MIGRATIONS).unwrap();
+        assert!(matches!(
+            upgrade_layout_to(root_dir, 1, TEST_MIGRATIONS),
+            Err(Error::InvalidData(_))
+        ));
+    }
+}
@@ -20,27 +20,39 @@
 #![feature(type_name_of_val)]
 #![feature(const_type_name)]
 
+mod audit;
+mod backup;
 mod bootstrap;
 mod config;
 mod discovery;
+mod dump;
 mod error;
+mod feature;
+mod fs;
+mod layout;
 mod root;
 mod schedule;
 mod service;
+mod standalone;
 
 pub mod node;
 pub mod raftgroup;
 pub mod runtime;
 pub mod serverpb;
+pub mod sst;
 
 use std::{path::PathBuf, sync::Arc};
 
 use engula_client::{ConnManager, RootClient, Router};
+pub use engula_engine::{verify_backup, BackupManifest};
 use tonic::async_trait;
 
 pub use crate::{
+    audit::AuditConfig,
+    backup::backup,
     bootstrap::run,
     config::*,
+    dump::{dump_group, dump_raft_log},
     error::{Error, Result},
     node::NodeConfig,
     raftgroup::RaftConfig,
@@ -49,14 +61,14 @@ pub use crate::{
     service::Server,
 };
 use crate::{
-    node::{resolver::AddressResolver, StateEngine},
+    audit::AuditLog,
+    feature::FeatureGates,
+    node::{health::StorageHealth, memory::MemoryTracker, resolver::AddressResolver, StateEngine},
     runtime::Executor,
 };
 
 pub(crate) struct Provider {
     pub log_path: PathBuf,
-
-    #[allow(unused)]
     pub db_path: PathBuf,
 
     pub address_resolver: Arc<AddressResolver>,
@@ -66,6 +78,10 @@ pub(crate) struct Provider {
     pub router: Router,
     pub raw_db: Arc<rocksdb::DB>,
     pub state_engine: StateEngine,
+    pub audit: Arc<AuditLog>,
+    pub memory_tracker: Arc<MemoryTracker>,
+    pub storage_health: Arc<StorageHealth>,
+    pub feature_gates: Arc<FeatureGates>,
 }
 
 #[cfg(test)]
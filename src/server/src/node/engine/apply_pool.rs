@@ -0,0 +1,190 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+};
+
+use tracing::{error, info};
+
+use crate::{Error, Result};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A small pool of threads shared by the group engines of a node, to apply the committed writes of
+/// a group in parallel, see [`super::GroupEngine::parallel_group_commit`].
+pub struct ApplyPool {
+    num_workers: usize,
+    sender: Mutex<mpsc::Sender<Job>>,
+}
+
+impl ApplyPool {
+    pub fn new(num_workers: usize) -> Self {
+        assert!(num_workers > 0);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for idx in 0..num_workers {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("apply-worker-{idx}"))
+                .spawn(move || loop {
+                    let job = match receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    job();
+                })
+                .expect("spawn apply worker");
+        }
+        info!("apply pool is started with {num_workers} workers");
+        ApplyPool {
+            num_workers,
+            sender: Mutex::new(sender),
+        }
+    }
+
+    #[inline]
+    pub fn num_workers(&self) -> usize {
+        self.num_workers
+    }
+
+    /// Run the tasks in the pool and wait until all of them are finished, the results are returned
+    /// in the order of tasks. A panicked task is caught, so that it won't kill the worker, and the
+    /// panic is returned as an error once all tasks are finished.
+    pub fn run_all<T, F>(&self, tasks: Vec<F>) -> Result<Vec<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let num_tasks = tasks.len();
+        let (result_sender, result_receiver) = mpsc::channel();
+        {
+            let sender = self.sender.lock().unwrap();
+            for (idx, task) in tasks.into_iter().enumerate() {
+                let result_sender = result_sender.clone();
+                let job: Job = Box::new(move || {
+                    let result = catch_unwind(AssertUnwindSafe(task));
+                    result_sender.send((idx, result)).unwrap_or_default();
+                });
+                sender.send(job).expect("apply workers are exited");
+            }
+        }
+        drop(result_sender);
+
+        let mut results = result_receiver.iter().collect::<Vec<_>>();
+        if results.len() != num_tasks {
+            return Err(Error::ApplyTask(format!(
+                "results are lost, {} of {num_tasks}",
+                num_tasks - results.len()
+            )));
+        }
+        results.sort_unstable_by_key(|(idx, _)| *idx);
+        results
+            .into_iter()
+            .map(|(idx, result)| {
+                result.map_err(|payload| {
+                    let msg = panic_message(payload.as_ref());
+                    error!("apply task {idx} is panicked: {msg}");
+                    Error::ApplyTask(format!("{idx} is panicked: {msg}"))
+                })
+            })
+            .collect()
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Returns the partition of the raw key, all mvcc versions of a user key belong to the same
+/// partition, so that the writes of a key are always applied by the same worker in order.
+pub(super) fn key_partition(raw_key: &[u8], num_partitions: usize) -> usize {
+    const L: usize = core::mem::size_of::<u64>();
+
+    // Strips the version of mvcc keys, see `keys::mvcc_key`.
+    let key = if raw_key.len() > 2 * L {
+        &raw_key[..raw_key.len() - L]
+    } else {
+        raw_key
+    };
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % num_partitions as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_tasks_in_order() {
+        let pool = ApplyPool::new(3);
+        let tasks = (0..32usize).map(|i| move || i * 2).collect::<Vec<_>>();
+        let results = pool.run_all(tasks).unwrap();
+        assert_eq!(results, (0..32usize).map(|i| i * 2).collect::<Vec<_>>());
+        assert!(pool.run_all(Vec::<fn()>::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn panicked_task_returns_error() {
+        let pool = ApplyPool::new(2);
+        let tasks = (0..8usize)
+            .map(|i| {
+                move || {
+                    if i == 3 {
+                        panic!("task {i} panics");
+                    }
+                    i
+                }
+            })
+            .collect::<Vec<_>>();
+        let err = pool.run_all(tasks).unwrap_err();
+        assert!(matches!(err, Error::ApplyTask(_)), "{err}");
+        assert!(err.to_string().contains("task 3 panics"), "{err}");
+
+        // The workers survive the panic.
+        let tasks = (0..8usize).map(|i| move || i).collect::<Vec<_>>();
+        assert_eq!(
+            pool.run_all(tasks).unwrap(),
+            (0..8usize).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn versions_of_key_in_same_partition() {
+        let key = |version: u64| {
+            let mut buf = 1u64.to_le_bytes().to_vec();
+            buf.extend_from_slice(b"user-key");
+            buf.push(b'8');
+            buf.extend_from_slice((!version).to_be_bytes().as_slice());
+            buf
+        };
+        for num_partitions in 1..8 {
+            let partition = key_partition(&key(0), num_partitions);
+            for version in [1, 2, u64::MAX - 1] {
+                assert_eq!(key_partition(&key(version), num_partitions), partition);
+            }
+        }
+    }
+}
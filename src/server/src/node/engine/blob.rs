@@ -0,0 +1,298 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A reader of the blob files written by RocksDB, see `db/blob/blob_log_format.h`. It is used to
+//! find the values saved in a blob file and to punch holes over the dead ones, so that their space
+//! returns to the filesystem without rewriting the file.
+//!
+//! A blob file is made up of a header, the records and a footer. Each record saves the key and
+//! the value, the blob index in the LSM tree references the offset of the value. Only the blocks
+//! covered by values are punched, the headers of records are kept, so that the file could still be
+//! traversed after punching.
+
+use std::{
+    fs::{File, OpenOptions},
+    os::unix::fs::{FileExt, MetadataExt},
+    path::Path,
+};
+
+use crate::{Error, Result};
+
+const BLOB_MAGIC_NUMBER: u32 = 2395959;
+
+/// The magic number, version, column family id, flags, compression type and expiration range.
+const BLOB_HEADER_SIZE: u64 = 30;
+
+/// The magic number, blob count, expiration range and checksum.
+const BLOB_FOOTER_SIZE: u64 = 32;
+
+/// The key size, value size, expiration, header checksum and blob checksum.
+const BLOB_RECORD_HEADER_SIZE: u64 = 32;
+
+const CRC32C_MASK_DELTA: u32 = 0xa282_ead8;
+
+/// A record of blob file.
+#[derive(Debug)]
+pub struct BlobRecord {
+    pub key: Vec<u8>,
+    /// The offset of the value, which is referenced by the blob index.
+    pub value_offset: u64,
+    pub value_size: u64,
+}
+
+pub struct BlobFile {
+    file: File,
+    file_size: u64,
+    block_size: u64,
+}
+
+pub struct BlobRecords<'a> {
+    blob_file: &'a BlobFile,
+    offset: u64,
+}
+
+impl BlobFile {
+    /// Open a sealed blob file. `None` is returned if the file is still being written, or if it
+    /// is shared with checkpoints or backups by hard links, since the holes would be visible to
+    /// them too.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Option<BlobFile>> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let metadata = file.metadata()?;
+        let file_size = metadata.len();
+        if metadata.nlink() > 1 || file_size < BLOB_HEADER_SIZE + BLOB_FOOTER_SIZE {
+            return Ok(None);
+        }
+
+        let mut header = [0u8; BLOB_HEADER_SIZE as usize];
+        file.read_exact_at(&mut header, 0)?;
+        if decode_fixed32(&header[..4]) != BLOB_MAGIC_NUMBER {
+            return Err(Error::InvalidData("blob file header".into()));
+        }
+
+        // The footer is written once the file is sealed.
+        let mut footer = [0u8; BLOB_FOOTER_SIZE as usize];
+        file.read_exact_at(&mut footer, file_size - BLOB_FOOTER_SIZE)?;
+        if decode_fixed32(&footer[..4]) != BLOB_MAGIC_NUMBER
+            || masked_crc32c(&footer[..28]) != decode_fixed32(&footer[28..])
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(BlobFile {
+            file,
+            file_size,
+            block_size: metadata.blksize().max(1),
+        }))
+    }
+
+    /// Traverse the records in the order of offset.
+    pub fn records(&self) -> BlobRecords<'_> {
+        BlobRecords {
+            blob_file: self,
+            offset: BLOB_HEADER_SIZE,
+        }
+    }
+
+    /// Return the range of the blocks covered by the value of the record which are still
+    /// allocated, `None` is returned if there is nothing to reclaim.
+    pub fn reclaimable(&self, record: &BlobRecord) -> Result<Option<(u64, u64)>> {
+        let start = align_up(record.value_offset, self.block_size);
+        let end = align_down(record.value_offset + record.value_size, self.block_size);
+        if start >= end {
+            return Ok(None);
+        }
+        match next_data(&self.file, start)? {
+            Some(offset) if offset < end => Ok(Some((start, end))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Punch a hole over the range returned by [`BlobFile::reclaimable`], the file size is kept.
+    /// Returns the number of punched bytes.
+    pub fn punch(&self, (start, end): (u64, u64)) -> Result<u64> {
+        debug_assert!(start < end && end <= self.file_size);
+        punch_hole(&self.file, start, end - start)?;
+        Ok(end - start)
+    }
+
+    fn read_record(&self, offset: u64) -> Result<Option<(BlobRecord, u64)>> {
+        let records_end = self.file_size - BLOB_FOOTER_SIZE;
+        if offset + BLOB_RECORD_HEADER_SIZE > records_end {
+            return Ok(None);
+        }
+
+        let mut header = [0u8; BLOB_RECORD_HEADER_SIZE as usize];
+        self.file.read_exact_at(&mut header, offset)?;
+        if masked_crc32c(&header[..24]) != decode_fixed32(&header[24..28]) {
+            return Err(Error::InvalidData(format!(
+                "blob record header at {offset}"
+            )));
+        }
+        let key_size = decode_fixed64(&header[..8]);
+        let value_size = decode_fixed64(&header[8..16]);
+        let value_offset = offset + BLOB_RECORD_HEADER_SIZE + key_size;
+        let next_offset = value_offset + value_size;
+        if next_offset > records_end {
+            return Err(Error::InvalidData(format!("blob record at {offset}")));
+        }
+
+        let mut key = vec![0u8; key_size as usize];
+        self.file
+            .read_exact_at(&mut key, offset + BLOB_RECORD_HEADER_SIZE)?;
+        let record = BlobRecord {
+            key,
+            value_offset,
+            value_size,
+        };
+        Ok(Some((record, next_offset)))
+    }
+}
+
+impl<'a> Iterator for BlobRecords<'a> {
+    type Item = Result<BlobRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.blob_file.read_record(self.offset) {
+            Ok(Some((record, next_offset))) => {
+                self.offset = next_offset;
+                Some(Ok(record))
+            }
+            Ok(None) => None,
+            Err(err) => {
+                // Stop at the corrupted record.
+                self.offset = self.blob_file.file_size;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn next_data(file: &File, offset: u64) -> Result<Option<u64>> {
+    use std::os::unix::io::AsRawFd;
+
+    let pos = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, libc::SEEK_DATA) };
+    if pos >= 0 {
+        return Ok(Some(pos as u64));
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::ENXIO) {
+        // There is no data after the offset.
+        Ok(None)
+    } else {
+        Err(err.into())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn punch_hole(file: &File, offset: u64, len: u64) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+    let ret = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            mode,
+            offset as libc::off_t,
+            len as libc::off_t,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn next_data(_: &File, offset: u64) -> Result<Option<u64>> {
+    Ok(Some(offset))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn punch_hole(_: &File, _: u64, _: u64) -> Result<()> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+}
+
+#[inline]
+fn align_up(offset: u64, align: u64) -> u64 {
+    (offset + align - 1) / align * align
+}
+
+#[inline]
+fn align_down(offset: u64, align: u64) -> u64 {
+    offset / align * align
+}
+
+#[inline]
+fn decode_fixed32(buf: &[u8]) -> u32 {
+    u32::from_le_bytes(buf[..4].try_into().unwrap())
+}
+
+#[inline]
+fn decode_fixed64(buf: &[u8]) -> u64 {
+    u64::from_le_bytes(buf[..8].try_into().unwrap())
+}
+
+#[inline]
+fn masked_crc32c(buf: &[u8]) -> u32 {
+    let crc = crc32c::crc32c(buf);
+    ((crc >> 15) | (crc << 17)).wrapping_add(CRC32C_MASK_DELTA)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn punch_dead_values() {
+        let dir = TempDir::new("punch_dead_values").unwrap();
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        let db = rocksdb::DB::open(&opts, dir.path()).unwrap();
+        db.set_options(&[("enable_blob_files", "true"), ("min_blob_size", "1024")])
+            .unwrap();
+
+        let value = vec![7u8; 64 << 10];
+        for i in 0..4u8 {
+            db.put([b'k', i], &value).unwrap();
+        }
+        db.flush().unwrap();
+
+        let path = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| {
+                path.extension()
+                    .map(|ext| ext == "blob")
+                    .unwrap_or_default()
+            })
+            .unwrap();
+        let blob_file = BlobFile::open(&path).unwrap().unwrap();
+        let records = blob_file.records().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1].key, vec![b'k', 1]);
+        assert_eq!(records[1].value_size, value.len() as u64);
+
+        let range = blob_file.reclaimable(&records[1]).unwrap().unwrap();
+        assert_eq!(blob_file.punch(range).unwrap(), range.1 - range.0);
+        assert!(blob_file.reclaimable(&records[1]).unwrap().is_none());
+
+        // The file is still traversable, and the other values are intact.
+        assert_eq!(blob_file.records().count(), 4);
+        assert!(blob_file.reclaimable(&records[2]).unwrap().is_some());
+        assert_eq!(db.get([b'k', 2]).unwrap(), Some(value));
+    }
+}
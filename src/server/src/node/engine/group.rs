@@ -14,9 +14,9 @@
 
 use std::{
     cell::RefCell,
-    collections::HashMap,
-    ops::{Deref, DerefMut},
-    path::Path,
+    collections::{BTreeMap, HashMap},
+    ops::{Bound, Deref, DerefMut},
+    path::{Path, PathBuf},
     sync::{Arc, RwLock},
     time::{Duration, Instant},
 };
@@ -24,10 +24,18 @@ use std::{
 use engula_api::{server::v1::*, shard};
 use prost::Message;
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
+use super::{
+    apply_pool::{key_partition, ApplyPool},
+    range_tombstone::{self, RangeTombstones},
+};
 use crate::{bootstrap::INITIAL_EPOCH, serverpb::v1::*, Error, Result};
 
+/// The write batches of a group commit are applied in parallel only if there are enough of them,
+/// since the dispatching isn't free.
+const PARALLEL_APPLY_THRESHOLD: usize = 32;
+
 /// The collection id of local states, which allows commit without replicating.
 pub const LOCAL_COLLECTION_ID: u64 = 0;
 
@@ -37,6 +45,73 @@ pub struct EngineConfig {
     ///
     /// Default: disabled
     pub engine_slow_io_threshold_ms: Option<u64>,
+
+    /// Values not smaller than this size are separated from the LSM tree and saved in blob files,
+    /// the LSM tree only saves the references of them, which reduces the write amplification for
+    /// big values.
+    ///
+    /// Default: disabled
+    pub engine_min_blob_size: Option<u64>,
+
+    /// The target size of each blob file.
+    ///
+    /// Default: 256MB
+    pub engine_blob_file_size: Option<u64>,
+
+    /// The live values in the oldest fraction of blob files are relocated during compaction, so
+    /// that the space of garbage values is reclaimed.
+    ///
+    /// Default: 0.25
+    pub engine_blob_gc_age_cutoff: Option<f64>,
+
+    /// Holes are punched over the dead values of blob files periodically, once the disk space
+    /// occupied by blob files exceeds this ratio of the live values.
+    ///
+    /// Default: disabled
+    pub engine_blob_punch_hole_space_amp: Option<f64>,
+
+    /// The number of workers to apply the committed writes of groups in parallel, the writes of a
+    /// key are always applied in order.
+    ///
+    /// Default: disabled
+    pub engine_apply_workers: Option<usize>,
+}
+
+/// The RocksDB properties of a group engine exposed by metrics and the debug endpoint, see
+/// `rocksdb::properties` for the details.
+pub const ENGINE_PROPERTIES: &[&str] = &[
+    "rocksdb.num-files-at-level0",
+    "rocksdb.num-files-at-level1",
+    "rocksdb.num-files-at-level2",
+    "rocksdb.num-files-at-level3",
+    "rocksdb.num-files-at-level4",
+    "rocksdb.num-files-at-level5",
+    "rocksdb.num-files-at-level6",
+    "rocksdb.cur-size-all-mem-tables",
+    "rocksdb.estimate-pending-compaction-bytes",
+    "rocksdb.live-sst-files-size",
+    "rocksdb.total-sst-files-size",
+    "rocksdb.estimate-num-keys",
+    "rocksdb.live-blob-file-size",
+    "rocksdb.live-blob-file-garbage-size",
+];
+
+/// The exact size of the data belonging to a shard, all mvcc versions and tombstones are included.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ShardStats {
+    pub num_keys: u64,
+    pub num_bytes: u64,
+    /// The crc32 checksum of all key value pairs in order.
+    pub checksum: u32,
+}
+
+/// The space used by a shard, which is read from the properties of the column family of the
+/// shard without scanning.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct ShardSize {
+    pub estimate_num_keys: u64,
+    pub sst_file_bytes: u64,
+    pub mem_table_bytes: u64,
 }
 
 #[derive(Default)]
@@ -54,9 +129,14 @@ pub struct WriteBatch {
 
 /// A structure supports grouped data, metadata saving and retriving.
 ///
+/// The local states of a group (apply state, descriptor and migration state) are saved in the
+/// column family of the group, and the data of each shard is saved in a dedicated column family,
+/// so a removed shard is reclaimed by dropping its column family.
+///
 /// NOTE: Shard are managed by `GroupEngine` instead of a shard engine, because shards from
 /// different collections in the same group needs to persist on disk at the same time, to guarantee
-/// the accuracy of applied index.
+/// the accuracy of applied index. The writes of a group are committed in one write batch across
+/// the column families, and the flushes of column families are atomic, see `open_engine`.
 #[derive(Clone)]
 pub struct GroupEngine
 where
@@ -66,22 +146,56 @@ where
     name: String,
     raw_db: Arc<rocksdb::DB>,
     core: Arc<RwLock<GroupEngineCore>>,
+    apply_pool: Option<Arc<ApplyPool>>,
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 struct GroupEngineCore {
     group_desc: GroupDesc,
     shard_descs: HashMap<u64, ShardDesc>,
     migration_state: Option<MigrationState>,
+    shard_ranges: ShardRanges,
+}
+
+/// The key ranges of shards, which are used to dispatch the keys of replicated write batches to
+/// the column families of shards.
+#[derive(Clone, Default)]
+struct ShardRanges {
+    /// The start key of range to the end key and shard id.
+    ranges: BTreeMap<Vec<u8>, (Vec<u8>, u64)>,
+}
+
+/// The column families of a group engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawColumnFamily {
+    /// The column family saves the local states of the group.
+    Meta,
+    /// The column family saves the data of a shard.
+    Shard(u64),
 }
 
 /// Traverse the data of the group engine, but don't care about the data format.
 pub struct RawIterator<'a> {
     apply_state: ApplyState,
     descriptor: GroupDesc,
-    db_iter: rocksdb::DBIterator<'a>,
+    meta_iter: rocksdb::DBIterator<'a>,
+    shard_iters: Vec<(u64, rocksdb::DBIterator<'a>)>,
+}
+
+/// Traverse the data of all shards in the group engine in one pass, ordered by shard id and then
+/// by key. The entries are streamed from the iterators of shards created from the same snapshot,
+/// so all shards are read from the same view and the memory usage doesn't grow with the size of
+/// the group.
+pub struct GroupIterator<'a> {
+    /// The descriptor, range tombstones and iterator of the shards not iterated yet.
+    iters: std::vec::IntoIter<ShardIter<'a>>,
+    current: Option<ShardIter<'a>>,
+    // Declared after the iterators, so the snapshot is released after them.
+    _snapshot: rocksdb::Snapshot<'a>,
 }
 
+type ShardIter<'a> = (ShardDesc, Arc<Vec<RangeTombstone>>, rocksdb::DBIterator<'a>);
+
 enum SnapshotRange {
     Target {
         target_key: Vec<u8>,
@@ -110,6 +224,7 @@ pub struct Snapshot<'a> {
 pub struct SnapshotCore<'a> {
     expect_slot: Option<u32>,
     db_iter: rocksdb::DBIterator<'a>,
+    range_tombstones: Arc<Vec<RangeTombstone>>,
     current_key: Option<Vec<u8>>,
     cached_entry: Option<MvccEntry>,
 }
@@ -130,6 +245,8 @@ pub struct MvccEntry {
     slot: Option<u32>,
     user_key: Vec<u8>,
     value: Box<[u8]>,
+    /// Whether the value is deleted by a range tombstone, it is read as a tombstone.
+    deleted: bool,
 }
 
 #[derive(Debug)]
@@ -139,9 +256,32 @@ pub enum SnapshotMode<'a> {
     Prefix { key: &'a [u8] },
 }
 
-struct ColumnFamilyDecorator<'a, 'b> {
-    cf_handle: Arc<rocksdb::BoundColumnFamily<'b>>,
+/// A snapshot of a shard, which could be moved to other threads to compute the [`ShardStats`].
+pub struct ShardStatsSnapshot {
+    /// The iterator is created with the snapshot, so the files it reads are pinned, and the values
+    /// deleted by the later range tombstones aren't dropped by the compactions under it.
+    // Declared before `snapshot`, so the iterator is released before the snapshot.
+    iter: rocksdb::DBIterator<'static>,
+    range_tombstones: Arc<Vec<RangeTombstone>>,
+    snapshot: OwnedSnapshot,
+}
+
+/// A snapshot of the db which holds the reference of the db, so it is not bound to the lifetime
+/// of the db.
+struct OwnedSnapshot {
+    // Declared before `raw_db`, so the snapshot is released before the reference of the db.
+    snapshot: rocksdb::Snapshot<'static>,
+    raw_db: Arc<rocksdb::DB>,
+}
+
+struct ColumnFamilyDecorator<'a> {
+    engine: &'a GroupEngine,
+    shard_ranges: &'a ShardRanges,
+    meta_cf_handle: Arc<rocksdb::BoundColumnFamily<'a>>,
+    /// The column family and the stamp of range tombstones of shards.
+    shard_cf_handles: HashMap<u64, (Arc<rocksdb::BoundColumnFamily<'a>>, u64)>,
     wb: &'a mut rocksdb::WriteBatch,
+    error: Option<Error>,
 }
 
 struct SlowIoGuard {
@@ -178,11 +318,8 @@ impl GroupEngine {
             cfg: cfg.clone(),
             name,
             raw_db: raw_db.clone(),
-            core: Arc::new(RwLock::new(GroupEngineCore {
-                group_desc: desc.clone(),
-                shard_descs: Default::default(),
-                migration_state: None,
-            })),
+            core: Arc::new(RwLock::new(GroupEngineCore::new(desc.clone(), None))),
+            apply_pool: None,
         };
 
         // The group descriptor should be persisted into disk.
@@ -216,34 +353,110 @@ impl GroupEngine {
 
         let group_desc = internal::descriptor(&raw_db, &cf_handle)?;
         let migration_state = internal::migration_state(&raw_db, &cf_handle)?;
-        let mut shard_descs = internal::shard_descs(&group_desc);
-        if let Some(shard_desc) = migration_state.as_ref().map(|m| m.get_shard_desc()) {
-            shard_descs
-                .entry(shard_desc.id)
-                .or_insert_with(|| shard_desc.clone());
-        }
-        let core = GroupEngineCore {
-            migration_state,
-            group_desc,
-            shard_descs,
-        };
-
-        Ok(Some(GroupEngine {
+        let core = GroupEngineCore::new(group_desc, migration_state);
+        let engine = GroupEngine {
             cfg: cfg.clone(),
             name,
             raw_db: raw_db.clone(),
-            core: Arc::new(RwLock::new(core)),
-        }))
+            core: Arc::new(RwLock::new(core.clone())),
+            apply_pool: None,
+        };
+        for shard_id in core.shard_descs.keys() {
+            engine.shard_cf_handle_or_create(*shard_id)?;
+            // Blob options are dynamically changeable, so they are applied to the existing
+            // column families too.
+            let shard_cf_name = Self::shard_cf_name(&engine.name, *shard_id);
+            internal::set_blob_options(cfg, &raw_db, &shard_cf_name)?;
+            engine.load_range_tombstones(*shard_id)?;
+        }
+        engine.migrate_layout(&core)?;
+
+        Ok(Some(engine))
+    }
+
+    /// Apply the committed writes by the pool, see [`GroupEngine::parallel_group_commit`].
+    pub fn with_apply_pool(mut self, apply_pool: Option<Arc<ApplyPool>>) -> Self {
+        self.apply_pool = apply_pool;
+        self
     }
 
     /// Destory a group engine.
     pub async fn destory(group_id: u64, replica_id: u64, raw_db: Arc<rocksdb::DB>) -> Result<()> {
         let name = Self::cf_name(group_id, replica_id);
+        if let Some(cf_handle) = raw_db.cf_handle(&name) {
+            // The column families of shards are dropped before the group's, so that they are
+            // still reachable if the destruction is interrupted.
+            let group_desc = internal::descriptor(&raw_db, &cf_handle)?;
+            let migration_state = internal::migration_state(&raw_db, &cf_handle)?;
+            drop(cf_handle);
+            for shard_id in internal::shard_descs(&group_desc, migration_state.as_ref()).keys() {
+                let shard_cf_name = Self::shard_cf_name(&name, *shard_id);
+                if raw_db.cf_handle(&shard_cf_name).is_some() {
+                    raw_db.drop_cf(&shard_cf_name)?;
+                }
+                RangeTombstones::unregister(raw_db.path(), &shard_cf_name);
+            }
+        }
         raw_db.drop_cf(&name)?;
         info!("destory column family {}", name);
         Ok(())
     }
 
+    /// Visit the mvcc entries of all shards of the group engine whose meta column family is
+    /// `cf_name`, in the order of shard id. The db might be opened in read only mode by offline
+    /// tools, so only the raw db is required.
+    pub fn dump<F>(raw_db: &rocksdb::DB, cf_name: &str, mut f: F) -> Result<GroupDesc>
+    where
+        F: FnMut(u64, &MvccEntry) -> Result<()>,
+    {
+        use rocksdb::{Direction, IteratorMode, ReadOptions};
+
+        let cf_handle = raw_db
+            .cf_handle(cf_name)
+            .ok_or_else(|| Error::InvalidArgument(format!("no such column family {cf_name}")))?;
+        let group_desc = internal::descriptor(raw_db, &cf_handle)?;
+        let migration_state = internal::migration_state(raw_db, &cf_handle)?;
+        let shard_descs = internal::shard_descs(&group_desc, migration_state.as_ref());
+        let mut shard_descs = shard_descs.into_values().collect::<Vec<_>>();
+        shard_descs.sort_unstable_by_key(|desc| desc.id);
+
+        for desc in shard_descs {
+            // The column family is created by the first write of the shard.
+            let shard_cf_name = Self::shard_cf_name(cf_name, desc.id);
+            let Some(shard_cf_handle) = raw_db.cf_handle(&shard_cf_name) else {
+                continue;
+            };
+            let (start, end) = keys::shard_range(&desc);
+            let inner_mode = IteratorMode::From(&start, Direction::Forward);
+            let iter = raw_db.iterator_cf_opt(&shard_cf_handle, ReadOptions::default(), inner_mode);
+            for item in iter {
+                let (key, value) = item?;
+                if key.as_ref() >= end.as_slice() {
+                    break;
+                }
+                let entry = MvccEntry::new(shard::slot(&desc).is_some(), key, value);
+                f(desc.id, &entry)?;
+            }
+        }
+        Ok(group_desc)
+    }
+
+    /// Whether the raw key, which is saved in a blob file, exists in the column family of its
+    /// shard. `None` is returned if the key doesn't belong to any shard of the group engine.
+    pub fn contains_raw_key(&self, key: &[u8]) -> Result<Option<bool>> {
+        let shard_id = match self.core.read().unwrap().shard_ranges.find(key) {
+            Some(shard_id) => shard_id,
+            None => return Ok(None),
+        };
+        let Ok(cf_handle) = self.shard_cf_handle(shard_id) else {
+            return Ok(Some(false));
+        };
+        if !self.raw_db.key_may_exist_cf(&cf_handle, key) {
+            return Ok(Some(false));
+        }
+        Ok(Some(self.raw_db.get_pinned_cf(&cf_handle, key)?.is_some()))
+    }
+
     /// Return the migrate state.
     #[inline]
     pub fn migration_state(&self) -> Option<MigrationState> {
@@ -262,6 +475,15 @@ impl GroupEngine {
         internal::flushed_apply_state(&self.raw_db, &self.cf_handle())
     }
 
+    /// Flush the mem tables of the group, if the apply state of the index isn't persisted yet, so
+    /// that the writes up to the index survive crashes.
+    pub fn flush_until(&self, index: u64) -> Result<()> {
+        if self.flushed_apply_state()?.index < index {
+            self.raw_db.flush_cf(&self.cf_handle())?;
+        }
+        Ok(())
+    }
+
     /// Get key value from the corresponding shard.
     pub async fn get(&self, shard_id: u64, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let snapshot_mode = SnapshotMode::Key { key };
@@ -298,6 +520,29 @@ impl GroupEngine {
         Ok(())
     }
 
+    /// Put the key value pulled from the source group of migration. It is written before any range
+    /// tombstone of the shard in this group, so it isn't stamped when the write batch is committed.
+    pub fn put_migrated(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        key: &[u8],
+        value: &[u8],
+        version: u64,
+    ) -> Result<()> {
+        let desc = self.shard_desc(shard_id)?;
+        let collection_id = desc.collection_id;
+        debug_assert_ne!(collection_id, LOCAL_COLLECTION_ID);
+        debug_assert!(shard::belong_to(&desc, key));
+
+        wb.put(
+            keys::mvcc_key(collection_id, shard::slot(&desc), key, version),
+            values::stamped_data(value, 0),
+        );
+
+        Ok(())
+    }
+
     /// Logically delete key from the corresponding shard.
     pub fn tombstone(
         &self,
@@ -341,6 +586,109 @@ impl GroupEngine {
         Ok(())
     }
 
+    /// Delete the keys in `[start, end)` of the shard with a range tombstone, an empty `end` means
+    /// the end of the shard. The range is clamped by the key range of the shard, the deleted
+    /// values are hidden from snapshots and dropped by compactions, see `range_tombstone`.
+    ///
+    /// `seq` must be greater than the seq of any range tombstone applied before, eg. the index of
+    /// the raft log. The tombstone is written without WAL, it should be applied again from the
+    /// raft logs if the apply state isn't flushed.
+    pub fn delete_range(&self, shard_id: u64, start: &[u8], end: &[u8], seq: u64) -> Result<()> {
+        use rocksdb::WriteOptions;
+
+        let desc = self.shard_desc(shard_id)?;
+        let collection_id = desc.collection_id;
+        debug_assert_ne!(collection_id, LOCAL_COLLECTION_ID);
+
+        let slot = shard::slot(&desc);
+        let (shard_start, shard_end) = keys::shard_range(&desc);
+        let lower = std::cmp::max(keys::raw(collection_id, slot, start), shard_start);
+        let upper = if end.is_empty() {
+            shard_end
+        } else {
+            std::cmp::min(keys::raw(collection_id, slot, end), shard_end)
+        };
+        if lower >= upper {
+            return Ok(());
+        }
+
+        let tombstone = RangeTombstone {
+            start: lower,
+            end: upper,
+            seq,
+        };
+        // Block the snapshots until the range tombstone is visible.
+        let _core = self.core.write().unwrap();
+        let range_tombstones = self.range_tombstones(shard_id);
+        let cf_handle = self.cf_handle();
+        let mut wb = rocksdb::WriteBatch::default();
+        for covered in range_tombstones.covered_by(&tombstone) {
+            wb.delete_cf(&cf_handle, keys::range_tombstone(shard_id, covered.seq));
+        }
+        wb.put_cf(
+            &cf_handle,
+            keys::range_tombstone(shard_id, seq),
+            tombstone.encode_to_vec(),
+        );
+        let mut opts = WriteOptions::default();
+        opts.disable_wal(true);
+        let _slow_io_guard = self.cfg.engine_slow_io_threshold_ms.map(SlowIoGuard::new);
+        self.raw_db.write_opt(wb, &opts)?;
+        range_tombstones.insert(tombstone);
+        Ok(())
+    }
+
+    /// Rename `from` to `to` of the shard, all versions of `from` are purged and the value is put
+    /// into `to` with `version`. Returns false if `from` is not exists, or `to` is exists and
+    /// `only_if_absent` is set.
+    ///
+    /// The keys are read from the engine, so the writes of the previous commands should be
+    /// committed before.
+    pub fn rename(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        from: &[u8],
+        to: &[u8],
+        only_if_absent: bool,
+        version: u64,
+    ) -> Result<bool> {
+        let (value, versions) = self.versions(shard_id, from)?;
+        let Some(value) = value else {
+            return Ok(false);
+        };
+        if from == to {
+            return Ok(!only_if_absent);
+        }
+        if only_if_absent && self.versions(shard_id, to)?.0.is_some() {
+            return Ok(false);
+        }
+
+        for from_version in versions {
+            self.delete(wb, shard_id, from, from_version)?;
+        }
+        self.put(wb, shard_id, to, &value, version)?;
+        Ok(true)
+    }
+
+    /// Returns the latest value of the key and all versions of it.
+    fn versions(&self, shard_id: u64, key: &[u8]) -> Result<(Option<Vec<u8>>, Vec<u64>)> {
+        let snapshot_mode = SnapshotMode::Key { key };
+        let mut snapshot = self.snapshot(shard_id, snapshot_mode)?;
+        let mut value = None;
+        let mut versions = vec![];
+        if let Some(iter) = snapshot.mvcc_iter() {
+            for entry in iter? {
+                let entry = entry?;
+                if versions.is_empty() {
+                    value = entry.value().map(ToOwned::to_owned);
+                }
+                versions.push(entry.version());
+            }
+        }
+        Ok((value, versions))
+    }
+
     #[inline]
     pub fn commit(&self, wb: WriteBatch, states: WriteStates, persisted: bool) -> Result<()> {
         self.group_commit(&[wb], states, persisted)
@@ -351,19 +699,83 @@ impl GroupEngine {
         wbs: &[WriteBatch],
         states: WriteStates,
         persisted: bool,
+    ) -> Result<()> {
+        if states.descriptor.is_none() && states.migration_state.is_none() {
+            let core = self.core.read().unwrap();
+            return self.write_batches(&core.shard_ranges, wbs, &states, &[], persisted);
+        }
+
+        // The shards of group might be changed, the readers of core are blocked until the column
+        // families of shards are consistent with the descriptor.
+        let mut core = self.core.write().unwrap();
+        let mut next_core = core.clone();
+        next_core.apply_states(states.descriptor.clone(), states.migration_state.clone());
+        for shard_id in next_core.shard_descs.keys() {
+            self.shard_cf_handle_or_create(*shard_id)?;
+        }
+
+        // The writes of this commit might belong to the shards before or after it.
+        let shard_ranges = ShardRanges::new(
+            core.shard_descs
+                .values()
+                .chain(next_core.shard_descs.values()),
+        );
+        let removed_shards = core
+            .shard_descs
+            .keys()
+            .filter(|shard_id| !next_core.shard_descs.contains_key(shard_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.write_batches(&shard_ranges, wbs, &states, &removed_shards, persisted)?;
+
+        *core = next_core;
+        for shard_id in removed_shards {
+            info!(
+                "group engine {} drop the column family of shard {shard_id}",
+                self.name
+            );
+            self.drop_shard_cf(shard_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the batches and the states in one write. The range tombstones of `removed_shards` are
+    /// deleted in the same write, since their column families are dropped after it.
+    fn write_batches(
+        &self,
+        shard_ranges: &ShardRanges,
+        wbs: &[WriteBatch],
+        states: &WriteStates,
+        removed_shards: &[u64],
+        persisted: bool,
     ) -> Result<()> {
         use rocksdb::WriteOptions;
 
         let cf_handle = self.cf_handle();
         let mut inner_wb = rocksdb::WriteBatch::default();
         let mut decorator = ColumnFamilyDecorator {
-            cf_handle: cf_handle.clone(),
+            engine: self,
+            shard_ranges,
+            meta_cf_handle: cf_handle.clone(),
+            shard_cf_handles: HashMap::default(),
             wb: &mut inner_wb,
+            error: None,
         };
         for wb in wbs {
             wb.inner.iterate(&mut decorator);
         }
+        if let Some(err) = decorator.error.take() {
+            return Err(err);
+        }
         states.write(&mut inner_wb, &cf_handle);
+        for shard_id in removed_shards {
+            inner_wb.delete_range_cf(
+                &cf_handle,
+                keys::range_tombstone_prefix(*shard_id),
+                keys::range_tombstone_end(*shard_id),
+            );
+        }
 
         let mut opts = WriteOptions::default();
         if persisted {
@@ -372,18 +784,79 @@ impl GroupEngine {
             opts.disable_wal(true);
         }
 
-        {
-            let _slow_io_guard = self.cfg.engine_slow_io_threshold_ms.map(SlowIoGuard::new);
-            self.raw_db.write_opt(inner_wb, &opts)?;
-        }
+        let _slow_io_guard = self.cfg.engine_slow_io_threshold_ms.map(SlowIoGuard::new);
+        self.raw_db.write_opt(inner_wb, &opts)?;
+        Ok(())
+    }
 
-        if states.descriptor.is_some() || states.migration_state.is_some() {
-            self.apply_core_states(states.descriptor, states.migration_state);
-        }
+    /// Commit the write batches and states like [`GroupEngine::group_commit`] without persisting,
+    /// but the write batches are applied by the apply pool in parallel if there are enough of them.
+    ///
+    /// The write batches are partitioned by the keys they touch, so the writes of a key are applied
+    /// in order. A write batch touching multiple partitions is applied alone after the former
+    /// batches, so each write batch is still atomic. The states are committed last, so the flushed
+    /// apply state never covers the unapplied writes.
+    ///
+    /// The partitions are committed by separate writes, so the readers are blocked until all of
+    /// them are committed, as if the write batches were committed by a single write. Otherwise a
+    /// reader might see the writes of an entry before the writes of the former entries.
+    pub fn parallel_group_commit(&self, wbs: Vec<WriteBatch>, states: WriteStates) -> Result<()> {
+        let pool = match self.apply_pool.as_ref() {
+            Some(pool)
+                if wbs.len() >= PARALLEL_APPLY_THRESHOLD
+                    && states.descriptor.is_none()
+                    && states.migration_state.is_none() =>
+            {
+                pool
+            }
+            _ => return self.group_commit(&wbs, states, false),
+        };
 
+        let core = self.core.write().unwrap();
+        let shard_ranges = Arc::new(core.shard_ranges.clone());
+        let num_workers = pool.num_workers();
+        let new_partitions = || (0..num_workers).map(|_| Vec::new()).collect::<Vec<_>>();
+        let mut partitions = new_partitions();
+        for wb in wbs {
+            match write_batch_partition(&wb, num_workers) {
+                Some(idx) => partitions[idx].push(wb),
+                None => {
+                    let applying = std::mem::replace(&mut partitions, new_partitions());
+                    self.apply_partitions(pool, &shard_ranges, applying)?;
+                    self.write_batches(&shard_ranges, &[wb], &WriteStates::default(), &[], false)?;
+                }
+            }
+        }
+        self.apply_partitions(pool, &shard_ranges, partitions)?;
+        self.write_batches(&shard_ranges, &[], &states, &[], false)?;
+        drop(core);
         Ok(())
     }
 
+    fn apply_partitions(
+        &self,
+        pool: &ApplyPool,
+        shard_ranges: &Arc<ShardRanges>,
+        partitions: Vec<Vec<WriteBatch>>,
+    ) -> Result<()> {
+        let mut tasks = partitions
+            .into_iter()
+            .filter(|wbs| !wbs.is_empty())
+            .map(|wbs| {
+                let engine = self.clone();
+                let shard_ranges = shard_ranges.clone();
+                move || {
+                    engine.write_batches(&shard_ranges, &wbs, &WriteStates::default(), &[], false)
+                }
+            })
+            .collect::<Vec<_>>();
+        match tasks.len() {
+            0 => Ok(()),
+            1 => tasks.pop().unwrap()(),
+            _ => pool.run_all(tasks)?.into_iter().collect(),
+        }
+    }
+
     pub fn snapshot(&self, shard_id: u64, mode: SnapshotMode) -> Result<Snapshot> {
         use rocksdb::{Direction, IteratorMode, ReadOptions};
 
@@ -396,7 +869,8 @@ impl GroupEngine {
             SnapshotMode::Start {
                 start_key: Some(start_key),
             } => {
-                debug_assert!(shard::belong_to(&desc, start_key));
+                // The keys of a hash slot are ordered, so any key could be the start of it.
+                debug_assert!(shard::slot(&desc).is_some() || shard::belong_to(&desc, start_key));
                 keys::raw(collection_id, shard::slot(&desc), start_key)
             }
             SnapshotMode::Start { start_key: None } => {
@@ -412,75 +886,319 @@ impl GroupEngine {
                 keys::raw(collection_id, shard::slot(&desc), key)
             }
         };
+        // Range deletions are applied with the core locked, so the range tombstones are
+        // consistent with the iterator.
+        let core = self.core.read().unwrap();
+        let range_tombstones = self.range_tombstones(shard_id).snapshot();
         let inner_mode = IteratorMode::From(&key, Direction::Forward);
         let iter = self
             .raw_db
-            .iterator_cf_opt(&self.cf_handle(), opts, inner_mode);
-        Ok(Snapshot::new(collection_id, iter, mode, &desc))
+            .iterator_cf_opt(&self.shard_cf_handle(shard_id)?, opts, inner_mode);
+        drop(core);
+        Ok(Snapshot::new(
+            collection_id,
+            iter,
+            range_tombstones,
+            mode,
+            &desc,
+        ))
     }
 
     pub fn raw_iter(&self) -> Result<RawIterator> {
         use rocksdb::{IteratorMode, ReadOptions};
 
-        let opts = ReadOptions::default();
+        // All commits are blocked, so that the iterators of column families are created from the
+        // same version of the group.
+        let core = self.core.write().unwrap();
+        let meta_iter = self.raw_db.iterator_cf_opt(
+            &self.cf_handle(),
+            ReadOptions::default(),
+            IteratorMode::Start,
+        );
+        let mut shard_ids = core.shard_descs.keys().cloned().collect::<Vec<_>>();
+        shard_ids.sort_unstable();
+        let mut shard_iters = Vec::with_capacity(shard_ids.len());
+        for shard_id in shard_ids {
+            let iter = self.raw_db.iterator_cf_opt(
+                &self.shard_cf_handle(shard_id)?,
+                ReadOptions::default(),
+                IteratorMode::Start,
+            );
+            shard_iters.push((shard_id, iter));
+        }
+        drop(core);
+
+        RawIterator::new(meta_iter, shard_iters)
+    }
+
+    /// Returns a [`GroupIterator`] over the data of all shards of the group, including all mvcc
+    /// versions and tombstones.
+    pub fn shards_iter(&self) -> Result<GroupIterator> {
+        use rocksdb::{IteratorMode, ReadOptions};
+
+        // Range deletions are applied with the core locked, so the range tombstones are
+        // consistent with the iterators.
+        let core = self.core.read().unwrap();
+        let mut shards = core.group_desc.shards.clone();
+        shards.sort_unstable_by_key(|s| s.id);
+
+        // The iterators of shards are created at once from the same snapshot, so all shards are
+        // read from the same view, and the files are pinned before the values deleted by the later
+        // range tombstones are dropped by compactions.
+        let snapshot = self.raw_db.snapshot();
+        let mut iters = Vec::with_capacity(shards.len());
+        for desc in shards {
+            let mut opts = ReadOptions::default();
+            opts.set_snapshot(&snapshot);
+            let iter = self.raw_db.iterator_cf_opt(
+                &self.shard_cf_handle(desc.id)?,
+                opts,
+                IteratorMode::Start,
+            );
+            let range_tombstones = self.range_tombstones(desc.id).snapshot();
+            iters.push((desc, range_tombstones, iter));
+        }
+        drop(core);
+
+        Ok(GroupIterator {
+            iters: iters.into_iter(),
+            current: None,
+            _snapshot: snapshot,
+        })
+    }
+
+    /// Compute the [`ShardStats`] of all shards of the group, ordered by shard id. Unlike calling
+    /// [`GroupEngine::shard_stats`] for each shard, the stats are computed from the same view of
+    /// the group.
+    pub fn group_stats(&self) -> Result<Vec<(u64, ShardStats)>> {
+        let mut iter = self.shards_iter()?;
+        let mut group_stats: Vec<(u64, ShardStats)> = iter
+            .pending_shards()
+            .map(|s| (s.id, ShardStats::default()))
+            .collect();
+        let mut hashers = vec![crc32fast::Hasher::new(); group_stats.len()];
+        let mut idx = 0;
+        for item in iter {
+            let (shard_id, entry) = item?;
+            while group_stats[idx].0 != shard_id {
+                idx += 1;
+            }
+            // Skipped like `ShardStatsSnapshot::scan`.
+            if entry.deleted {
+                continue;
+            }
+            let stats = &mut group_stats[idx].1;
+            stats.num_keys += 1;
+            stats.num_bytes += (entry.key.len() + entry.value.len()) as u64;
+            hashers[idx].update(&entry.key);
+            hashers[idx].update(&entry.value);
+        }
+        for ((_, stats), hasher) in group_stats.iter_mut().zip(hashers) {
+            stats.checksum = hasher.finalize();
+        }
+        Ok(group_stats)
+    }
+
+    /// Compute the exact [`ShardStats`] by scanning the column family of the shard. Replicas with
+    /// the same applied index are expected to return the same stats.
+    pub fn shard_stats(&self, shard_id: u64) -> Result<ShardStats> {
+        self.shard_stats_snapshot(shard_id)?.scan()
+    }
+
+    /// Take a snapshot of the shard, the [`ShardStats`] are computed by
+    /// [`ShardStatsSnapshot::scan`] later, which could be called in another thread.
+    pub fn shard_stats_snapshot(&self, shard_id: u64) -> Result<ShardStatsSnapshot> {
+        use rocksdb::{IteratorMode, ReadOptions};
+
+        let cf_handle = self.shard_cf_handle(shard_id)?;
+        // The core is locked, so the snapshot doesn't see a partially applied parallel commit, see
+        // `GroupEngine::parallel_group_commit`.
+        let core = self.core.read().unwrap();
+        let range_tombstones = self.range_tombstones(shard_id).snapshot();
+        let snapshot = OwnedSnapshot::new(self.raw_db.clone());
+        drop(core);
+        let mut opts = ReadOptions::default();
+        opts.set_snapshot(&snapshot.snapshot);
         let iter = self
             .raw_db
-            .iterator_cf_opt(&self.cf_handle(), opts, IteratorMode::Start);
-        RawIterator::new(iter)
+            .iterator_cf_opt(&cf_handle, opts, IteratorMode::Start);
+        // SAFETY: the iterator only borrows the db and the snapshot, which are kept alive by
+        // `snapshot` until the iterator is released.
+        let iter = unsafe {
+            std::mem::transmute::<rocksdb::DBIterator<'_>, rocksdb::DBIterator<'static>>(iter)
+        };
+        Ok(ShardStatsSnapshot {
+            iter,
+            range_tombstones,
+            snapshot,
+        })
+    }
+
+    /// Return the [`ShardSize`] from the properties of the column family of the shard.
+    pub fn shard_size(&self, shard_id: u64) -> Result<ShardSize> {
+        let cf_handle = self.shard_cf_handle(shard_id)?;
+        let property = |name: &str| -> Result<u64> {
+            Ok(self
+                .raw_db
+                .property_int_value_cf(&cf_handle, name)?
+                .unwrap_or_default())
+        };
+        Ok(ShardSize {
+            estimate_num_keys: property("rocksdb.estimate-num-keys")?,
+            sst_file_bytes: property("rocksdb.live-sst-files-size")?,
+            mem_table_bytes: property("rocksdb.size-all-mem-tables")?,
+        })
+    }
+
+    /// Returns the approximate on-disk size in bytes of each shard of the group, estimated from
+    /// the SST files and memtables of the column families of the shards.
+    pub fn approximate_shard_sizes(&self) -> HashMap<u64, u64> {
+        self.descriptor()
+            .shards
+            .iter()
+            .filter_map(|desc| {
+                let size = self.shard_size(desc.id).ok()?;
+                Some((desc.id, size.sst_file_bytes + size.mem_table_bytes))
+            })
+            .collect()
+    }
+
+    /// Returns the value of a RocksDB property of the group engine, eg.
+    /// `rocksdb.num-files-at-level0`, which is summed over the column families of the group and
+    /// its shards. `None` is returned if the property is unknown.
+    pub fn property(&self, name: &str) -> Result<Option<u64>> {
+        let core = self.core.read().unwrap();
+        let mut value = self.raw_db.property_int_value_cf(&self.cf_handle(), name)?;
+        for shard_id in core.shard_descs.keys() {
+            let shard_cf_handle = self.shard_cf_handle(*shard_id)?;
+            if let Some(v) = self.raw_db.property_int_value_cf(&shard_cf_handle, name)? {
+                *value.get_or_insert(0) += v;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Returns the values of [`ENGINE_PROPERTIES`], the unknown properties are skipped.
+    pub fn properties(&self) -> Result<Vec<(&'static str, u64)>> {
+        let mut props = Vec::with_capacity(ENGINE_PROPERTIES.len());
+        for &name in ENGINE_PROPERTIES {
+            if let Some(value) = self.property(name)? {
+                props.push((name, value));
+            }
+        }
+        Ok(props)
     }
 
-    /// Ingest data into group engine.
-    pub fn ingest<P: AsRef<Path>>(&self, files: Vec<P>) -> Result<()> {
+    /// Ingest data into group engine, the existing data are replaced.
+    ///
+    /// The files of the local states are ingested into the column family of the group, and the
+    /// files of shards are ingested into the column families of the corresponding shards.
+    pub fn ingest(
+        &self,
+        meta_files: Vec<PathBuf>,
+        shard_files: HashMap<u64, Vec<PathBuf>>,
+    ) -> Result<()> {
         use rocksdb::{IngestExternalFileOptions, Options};
 
+        let mut core = self.core.write().unwrap();
+        for shard_id in core.shard_descs.keys() {
+            self.drop_shard_cf(*shard_id)?;
+        }
         self.raw_db.drop_cf(&self.name)?;
         self.raw_db.create_cf(&self.name, &Options::default())?;
 
         let opts = IngestExternalFileOptions::default();
         let cf_handle = self.cf_handle();
         self.raw_db
-            .ingest_external_file_cf_opts(&cf_handle, &opts, files)?;
+            .ingest_external_file_cf_opts(&cf_handle, &opts, meta_files)?;
 
         let group_desc = internal::descriptor(&self.raw_db, &cf_handle)?;
         let migration_state = internal::migration_state(&self.raw_db, &cf_handle)?;
-        self.apply_core_states(Some(group_desc), migration_state);
+        *core = GroupEngineCore::new(group_desc, migration_state);
+        for shard_id in core.shard_descs.keys() {
+            self.shard_cf_handle_or_create(*shard_id)?;
+            self.load_range_tombstones(*shard_id)?;
+        }
+        for (shard_id, files) in shard_files {
+            if !core.shard_descs.contains_key(&shard_id) {
+                warn!(
+                    "group engine {} ingest: skip files of unknown shard {shard_id}",
+                    self.name
+                );
+                continue;
+            }
+            let shard_cf_handle = self.shard_cf_handle(shard_id)?;
+            self.raw_db
+                .ingest_external_file_cf_opts(&shard_cf_handle, &opts, files)?;
+        }
+
+        // The snapshots sent by the legacy nodes carry the data of shards in the files of
+        // local states.
+        self.migrate_layout(&core)?;
 
         Ok(())
     }
 
-    pub fn apply_core_states(
-        &self,
-        descriptor: Option<GroupDesc>,
-        migration_state: Option<MigrationState>,
-    ) {
-        let mut core = self.core.write().unwrap();
-        if let Some(desc) = descriptor {
-            core.group_desc = desc;
+    /// Move the data of shards from the column family of the group into the column families of
+    /// shards.
+    ///
+    /// Before each shard has a dedicated column family, all data of a group were saved in the
+    /// column family of the group. The keys belonging to no shards are orphans and removed.
+    fn migrate_layout(&self, core: &GroupEngineCore) -> Result<()> {
+        use rocksdb::{Direction, IteratorMode, ReadOptions, WriteOptions};
+
+        const MIGRATE_BATCH_KEYS: usize = 1024;
+
+        let cf_handle = self.cf_handle();
+        let user_data_start = keys::user_data_start();
+        let mut iter = self.raw_db.iterator_cf_opt(
+            &cf_handle,
+            ReadOptions::default(),
+            IteratorMode::From(&user_data_start, Direction::Forward),
+        );
+        if iter.next().transpose()?.is_none() {
+            return Ok(());
         }
+        drop(iter);
 
-        // TODO(walter) remove shard desc if migration task is aborted.
-        if let Some(migration_state) = migration_state {
-            if migration_state.step == MigrationStep::Finished as i32
-                || migration_state.step == MigrationStep::Aborted as i32
-            {
-                core.migration_state = None;
-            } else {
-                core.migration_state = Some(migration_state);
+        let mut opts = WriteOptions::default();
+        opts.set_sync(true);
+        let mut wb = rocksdb::WriteBatch::default();
+        let mut num_keys = 0;
+        let mut num_orphans = 0;
+        let iter = self.raw_db.iterator_cf_opt(
+            &cf_handle,
+            ReadOptions::default(),
+            IteratorMode::From(&user_data_start, Direction::Forward),
+        );
+        for item in iter {
+            let (key, value) = item?;
+            match core.shard_ranges.find(&key) {
+                Some(shard_id) => {
+                    wb.put_cf(&self.shard_cf_handle(shard_id)?, &key, value);
+                    num_keys += 1;
+                }
+                None => num_orphans += 1,
+            }
+            wb.delete_cf(&cf_handle, &key);
+            if wb.len() >= MIGRATE_BATCH_KEYS {
+                self.raw_db.write_opt(std::mem::take(&mut wb), &opts)?;
             }
         }
-
-        core.shard_descs = internal::shard_descs(&core.group_desc);
-        if let Some(shard_desc) = core
-            .migration_state
-            .as_ref()
-            .map(|m| m.get_shard_desc().clone())
-        {
-            core.shard_descs.entry(shard_desc.id).or_insert(shard_desc);
+        if !wb.is_empty() {
+            self.raw_db.write_opt(wb, &opts)?;
         }
+
+        info!(
+            "group engine {} migrate {num_keys} keys into the column families of shards, \
+            {num_orphans} orphan keys are removed",
+            self.name
+        );
+        Ok(())
     }
 
     #[inline]
-    fn shard_desc(&self, shard_id: u64) -> Result<ShardDesc> {
+    pub fn shard_desc(&self, shard_id: u64) -> Result<ShardDesc> {
         self.core
             .read()
             .expect("read lock")
@@ -498,25 +1216,160 @@ impl GroupEngine {
     }
 
     #[inline]
-    fn cf_name(group_id: u64, replica_id: u64) -> String {
-        // Using the replica id avoids the problem of creating a new replica immediately after
-        // deleting the replica.
-        format!("{group_id}-{replica_id}")
+    fn shard_cf_handle(&self, shard_id: u64) -> Result<Arc<rocksdb::BoundColumnFamily>> {
+        self.raw_db
+            .cf_handle(&Self::shard_cf_name(&self.name, shard_id))
+            .ok_or(Error::ShardNotFound(shard_id))
     }
-}
 
-impl<'a> RawIterator<'a> {
-    fn new(mut db_iter: rocksdb::DBIterator<'a>) -> Result<Self> {
-        use rocksdb::IteratorMode;
+    fn shard_cf_handle_or_create(&self, shard_id: u64) -> Result<Arc<rocksdb::BoundColumnFamily>> {
+        use rocksdb::Options;
+
+        let name = Self::shard_cf_name(&self.name, shard_id);
+        if self.raw_db.cf_handle(&name).is_none() {
+            debug!(
+                "group engine {} create the column family of shard {shard_id}",
+                self.name
+            );
+            let opts = Self::cf_options(self.raw_db.path(), &name, &Options::default());
+            self.raw_db.create_cf(&name, &opts)?;
+            internal::set_blob_options(&self.cfg, &self.raw_db, &name)?;
+        }
+        Ok(self
+            .raw_db
+            .cf_handle(&name)
+            .expect("cf must exists because it just created"))
+    }
 
-        let apply_state = next_message(&mut db_iter, &keys::apply_state())?;
-        let descriptor = next_message(&mut db_iter, &keys::descriptor())?;
-        db_iter.set_mode(IteratorMode::Start);
+    fn drop_shard_cf(&self, shard_id: u64) -> Result<()> {
+        let name = Self::shard_cf_name(&self.name, shard_id);
+        if self.raw_db.cf_handle(&name).is_some() {
+            self.raw_db.drop_cf(&name)?;
+        }
+        RangeTombstones::unregister(self.raw_db.path(), &name);
+        Ok(())
+    }
+
+    /// Return the options to open the column family `cf_name`, the compaction filter of range
+    /// tombstones is installed for the column families of shards.
+    pub fn cf_options(db_path: &Path, cf_name: &str, opts: &rocksdb::Options) -> rocksdb::Options {
+        let mut opts = opts.clone();
+        if Self::is_shard_cf_name(cf_name) {
+            let range_tombstones = RangeTombstones::of(db_path, cf_name);
+            range_tombstone::set_compaction_filter(&mut opts, range_tombstones);
+        }
+        opts
+    }
+
+    #[inline]
+    fn range_tombstones(&self, shard_id: u64) -> Arc<RangeTombstones> {
+        let name = Self::shard_cf_name(&self.name, shard_id);
+        RangeTombstones::of(self.raw_db.path(), &name)
+    }
+
+    fn load_range_tombstones(&self, shard_id: u64) -> Result<()> {
+        let tombstones = internal::range_tombstones(&self.raw_db, &self.cf_handle(), shard_id)?;
+        self.range_tombstones(shard_id).reset(tombstones);
+        Ok(())
+    }
+
+    #[inline]
+    fn cf_name(group_id: u64, replica_id: u64) -> String {
+        // Using the replica id avoids the problem of creating a new replica immediately after
+        // deleting the replica.
+        format!("{group_id}-{replica_id}")
+    }
+
+    #[inline]
+    fn shard_cf_name(cf_name: &str, shard_id: u64) -> String {
+        format!("{cf_name}-{shard_id}")
+    }
+
+    /// Whether the column family is named by `shard_cf_name`, eg. `{group}-{replica}-{shard}`.
+    fn is_shard_cf_name(cf_name: &str) -> bool {
+        let parts = cf_name.split('-').collect::<Vec<_>>();
+        parts.len() == 3 && parts.iter().all(|part| part.parse::<u64>().is_ok())
+    }
+}
+
+impl GroupEngineCore {
+    fn new(group_desc: GroupDesc, migration_state: Option<MigrationState>) -> Self {
+        let shard_descs = internal::shard_descs(&group_desc, migration_state.as_ref());
+        let shard_ranges = ShardRanges::new(shard_descs.values());
+        GroupEngineCore {
+            group_desc,
+            shard_descs,
+            migration_state,
+            shard_ranges,
+        }
+    }
+
+    fn apply_states(
+        &mut self,
+        descriptor: Option<GroupDesc>,
+        migration_state: Option<MigrationState>,
+    ) {
+        if let Some(desc) = descriptor {
+            self.group_desc = desc;
+        }
+
+        if let Some(migration_state) = migration_state {
+            if migration_state.step == MigrationStep::Finished as i32
+                || migration_state.step == MigrationStep::Aborted as i32
+            {
+                self.migration_state = None;
+            } else {
+                self.migration_state = Some(migration_state);
+            }
+        }
+
+        self.shard_descs = internal::shard_descs(&self.group_desc, self.migration_state.as_ref());
+        self.shard_ranges = ShardRanges::new(self.shard_descs.values());
+    }
+}
+
+impl ShardRanges {
+    fn new<'a>(shard_descs: impl IntoIterator<Item = &'a ShardDesc>) -> Self {
+        let ranges = shard_descs
+            .into_iter()
+            .map(|desc| {
+                let (start, end) = keys::shard_range(desc);
+                (start, (end, desc.id))
+            })
+            .collect();
+        ShardRanges { ranges }
+    }
+
+    /// Find the shard which the raw key belongs to.
+    fn find(&self, key: &[u8]) -> Option<u64> {
+        let (_, (end, shard_id)) = self
+            .ranges
+            .range::<[u8], _>((Bound::Unbounded, Bound::Included(key)))
+            .next_back()?;
+        if key < end.as_slice() {
+            Some(*shard_id)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> RawIterator<'a> {
+    fn new(
+        mut meta_iter: rocksdb::DBIterator<'a>,
+        shard_iters: Vec<(u64, rocksdb::DBIterator<'a>)>,
+    ) -> Result<Self> {
+        use rocksdb::IteratorMode;
+
+        let apply_state = next_message(&mut meta_iter, &keys::apply_state())?;
+        let descriptor = next_message(&mut meta_iter, &keys::descriptor())?;
+        meta_iter.set_mode(IteratorMode::Start);
 
         Ok(RawIterator {
             apply_state,
             descriptor,
-            db_iter,
+            meta_iter,
+            shard_iters,
         })
     }
 
@@ -529,15 +1382,51 @@ impl<'a> RawIterator<'a> {
     pub fn descriptor(&self) -> &GroupDesc {
         &self.descriptor
     }
-}
 
-impl<'a> Iterator for RawIterator<'a> {
-    /// Key value pairs.
-    type Item = <rocksdb::DBIterator<'a> as Iterator>::Item;
+    /// Return the iterators of each column family of the group engine.
+    pub fn into_column_families(self) -> Vec<(RawColumnFamily, rocksdb::DBIterator<'a>)> {
+        let mut iters = Vec::with_capacity(self.shard_iters.len() + 1);
+        iters.push((RawColumnFamily::Meta, self.meta_iter));
+        for (shard_id, iter) in self.shard_iters {
+            iters.push((RawColumnFamily::Shard(shard_id), iter));
+        }
+        iters
+    }
+}
 
+impl<'a> GroupIterator<'a> {
+    /// Returns the shards which have not been iterated yet.
     #[inline]
+    pub fn pending_shards(&self) -> impl Iterator<Item = &ShardDesc> {
+        self.iters.as_slice().iter().map(|(desc, _, _)| desc)
+    }
+}
+
+impl<'a> Iterator for GroupIterator<'a> {
+    /// The shard id and the mvcc entry, the values deleted by range tombstones are returned as
+    /// tombstones.
+    type Item = Result<(u64, MvccEntry)>;
+
     fn next(&mut self) -> Option<Self::Item> {
-        self.db_iter.next()
+        loop {
+            if self.current.is_none() {
+                self.current = Some(self.iters.next()?);
+            }
+
+            let (desc, range_tombstones, db_iter) = self.current.as_mut().unwrap();
+            match db_iter.next() {
+                Some(Ok((key, value))) => {
+                    let with_slot = shard::slot(desc).is_some();
+                    let mut entry = MvccEntry::new(with_slot, key, value);
+                    if !range_tombstones.is_empty() {
+                        entry.mark_deleted(range_tombstones);
+                    }
+                    return Some(Ok((desc.id, entry)));
+                }
+                Some(Err(err)) => return Some(Err(err.into())),
+                None => self.current = None,
+            }
+        }
     }
 }
 
@@ -545,6 +1434,7 @@ impl<'a> Snapshot<'a> {
     fn new<'b>(
         collection_id: u64,
         db_iter: rocksdb::DBIterator<'a>,
+        range_tombstones: Arc<Vec<RangeTombstone>>,
         snapshot_mode: SnapshotMode<'b>,
         desc: &ShardDesc,
     ) -> Self {
@@ -579,6 +1469,7 @@ impl<'a> Snapshot<'a> {
             core: RefCell::new(SnapshotCore {
                 expect_slot,
                 db_iter,
+                range_tombstones,
                 current_key: None,
                 cached_entry: None,
             }),
@@ -651,7 +1542,11 @@ impl<'a> SnapshotCore<'a> {
             return None;
         }
 
-        self.cached_entry = Some(MvccEntry::new(self.expect_slot.is_some(), key, value));
+        let mut entry = MvccEntry::new(self.expect_slot.is_some(), key, value);
+        if !self.range_tombstones.is_empty() {
+            entry.mark_deleted(&self.range_tombstones);
+        }
+        self.cached_entry = Some(entry);
         Some(Ok(()))
     }
 
@@ -664,6 +1559,54 @@ impl<'a> SnapshotCore<'a> {
     }
 }
 
+impl ShardStatsSnapshot {
+    /// Scan the column family of the shard at the snapshot and compute the [`ShardStats`]. The
+    /// values deleted by range tombstones are skipped, since the compactions drop them at
+    /// different times on replicas.
+    pub fn scan(self) -> Result<ShardStats> {
+        let ShardStatsSnapshot {
+            iter,
+            range_tombstones,
+            snapshot,
+        } = self;
+        let mut stats = ShardStats::default();
+        let mut hasher = crc32fast::Hasher::new();
+        for item in iter {
+            let (key, value) = item?;
+            if let Some(stamp) = values::stamp(&value) {
+                if range_tombstone::is_deleted(&range_tombstones, &key, stamp) {
+                    continue;
+                }
+            }
+            stats.num_keys += 1;
+            stats.num_bytes += (key.len() + value.len()) as u64;
+            hasher.update(&key);
+            hasher.update(&value);
+        }
+        stats.checksum = hasher.finalize();
+        drop(snapshot);
+        Ok(stats)
+    }
+}
+
+impl OwnedSnapshot {
+    fn new(raw_db: Arc<rocksdb::DB>) -> Self {
+        let snapshot = raw_db.snapshot();
+        // SAFETY: the snapshot only borrows the db, which is kept alive by `raw_db` until the
+        // snapshot is released.
+        let snapshot = unsafe {
+            std::mem::transmute::<rocksdb::Snapshot<'_>, rocksdb::Snapshot<'static>>(snapshot)
+        };
+        OwnedSnapshot { snapshot, raw_db }
+    }
+}
+
+// SAFETY: the snapshots of rocksdb are immutable and thread safe.
+unsafe impl Send for OwnedSnapshot {}
+
+// SAFETY: the iterator is only used by the thread which owns the snapshot.
+unsafe impl Send for ShardStatsSnapshot {}
+
 impl<'a, 'b> Iterator for UserDataIterator<'a, 'b> {
     type Item = Result<MvccIterator<'a, 'b>>;
 
@@ -688,6 +1631,7 @@ impl MvccEntry {
             slot,
             user_key,
             value,
+            deleted: false,
         }
     }
 
@@ -717,20 +1661,25 @@ impl MvccEntry {
 
     /// Return value of this `MvccEntry`. `None` is returned if this entry is a tombstone.
     pub fn value(&self) -> Option<&[u8]> {
-        if self.value[0] == values::TOMBSTONE {
+        if self.deleted {
             None
         } else {
-            debug_assert_eq!(self.value[0], values::DATA);
-            Some(&self.value[1..])
+            values::payload(&self.value)
         }
     }
 
     pub fn is_tombstone(&self) -> bool {
-        self.value[0] == values::TOMBSTONE
+        self.deleted || self.value[0] == values::TOMBSTONE
     }
 
     pub fn is_data(&self) -> bool {
-        self.value[0] == values::DATA
+        !self.deleted && values::stamp(&self.value).is_some()
+    }
+
+    fn mark_deleted(&mut self, tombstones: &[RangeTombstone]) {
+        if let Some(stamp) = values::stamp(&self.value) {
+            self.deleted = range_tombstone::is_deleted(tombstones, &self.key, stamp);
+        }
     }
 }
 
@@ -758,9 +1707,12 @@ impl<'a> Default for SnapshotMode<'a> {
 }
 
 mod keys {
+    use super::{shard, ShardDesc};
+
     const APPLY_STATE: &[u8] = b"APPLY_STATE";
     const DESCRIPTOR: &[u8] = b"DESCRIPTOR";
     const MIGRATE_STATE: &[u8] = b"MIGRATE_STATE";
+    const RANGE_TOMBSTONE: &[u8] = b"RANGE_TOMBSTONE";
 
     #[inline]
     pub fn raw(collection_id: u64, slot: Option<u32>, key: &[u8]) -> Vec<u8> {
@@ -839,6 +1791,47 @@ mod keys {
         (buf, slot)
     }
 
+    /// Returns the key range `[start, end)` occupied by the shard. The ranges of shards are
+    /// disjoint: range partitions are ordered by the memcomparable user key, and hash partitions
+    /// are prefixed by the slot.
+    pub fn shard_range(desc: &ShardDesc) -> (Vec<u8>, Vec<u8>) {
+        let collection_id = desc.collection_id;
+        match shard::slot(desc) {
+            Some(slot) => {
+                let start = raw(collection_id, Some(slot), &[]);
+                let end = next_prefix(&start);
+                (start, end)
+            }
+            None => {
+                let start = raw(collection_id, None, &shard::start_key(desc));
+                let end_key = shard::end_key(desc);
+                let end = if end_key.is_empty() {
+                    next_prefix(&raw(collection_id, None, &[]))
+                } else {
+                    raw(collection_id, None, &end_key)
+                };
+                (start, end)
+            }
+        }
+    }
+
+    /// Returns the smallest key of user data, the keys of local states are less than it.
+    pub fn user_data_start() -> Vec<u8> {
+        next_prefix(&super::LOCAL_COLLECTION_ID.to_le_bytes())
+    }
+
+    /// Returns the smallest key which is greater than all keys with the specified prefix.
+    fn next_prefix(prefix: &[u8]) -> Vec<u8> {
+        let mut buf = prefix.to_owned();
+        while let Some(last) = buf.pop() {
+            if last != u8::MAX {
+                buf.push(last + 1);
+                return buf;
+            }
+        }
+        unreachable!("the prefix of user keys is never filled with 0xFF")
+    }
+
     #[inline]
     pub fn apply_state() -> Vec<u8> {
         let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + APPLY_STATE.len());
@@ -862,11 +1855,37 @@ mod keys {
         buf.extend_from_slice(MIGRATE_STATE);
         buf
     }
+
+    /// Returns the prefix of the range tombstones of the shard.
+    pub fn range_tombstone_prefix(shard_id: u64) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() * 3 + RANGE_TOMBSTONE.len());
+        buf.extend_from_slice(super::LOCAL_COLLECTION_ID.to_le_bytes().as_slice());
+        buf.extend_from_slice(RANGE_TOMBSTONE);
+        buf.extend_from_slice(shard_id.to_be_bytes().as_slice());
+        buf
+    }
+
+    #[inline]
+    pub fn range_tombstone(shard_id: u64, seq: u64) -> Vec<u8> {
+        let mut buf = range_tombstone_prefix(shard_id);
+        buf.extend_from_slice(seq.to_be_bytes().as_slice());
+        buf
+    }
+
+    #[inline]
+    pub fn range_tombstone_end(shard_id: u64) -> Vec<u8> {
+        next_prefix(&range_tombstone_prefix(shard_id))
+    }
 }
 
-mod values {
+pub(super) mod values {
     pub(super) const DATA: u8 = 0;
     pub(super) const TOMBSTONE: u8 = 1;
+    /// The data with the stamp of range tombstones, see `range_tombstone`. The unstamped data is
+    /// equivalent to the stamp 0.
+    pub(super) const STAMPED_DATA: u8 = 2;
+
+    const STAMP_SIZE: usize = core::mem::size_of::<u64>();
 
     #[inline]
     pub fn tombstone() -> &'static [u8] {
@@ -879,15 +1898,80 @@ mod values {
         buf.extend_from_slice(v);
         buf
     }
+
+    pub fn stamped_data(v: &[u8], stamp: u64) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(v.len() + 1 + STAMP_SIZE);
+        buf.push(STAMPED_DATA);
+        buf.extend_from_slice(stamp.to_be_bytes().as_slice());
+        buf.extend_from_slice(v);
+        buf
+    }
+
+    /// Return the stamp of the data, `None` is returned if the value is a tombstone.
+    pub fn stamp(value: &[u8]) -> Option<u64> {
+        match value[0] {
+            DATA => Some(0),
+            STAMPED_DATA => Some(u64::from_be_bytes(
+                value[1..1 + STAMP_SIZE].try_into().unwrap(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Return the user value of the data, `None` is returned if the value is a tombstone.
+    pub fn payload(value: &[u8]) -> Option<&[u8]> {
+        match value[0] {
+            DATA => Some(&value[1..]),
+            STAMPED_DATA => Some(&value[1 + STAMP_SIZE..]),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> ColumnFamilyDecorator<'a> {
+    /// Return the column family of the shard which the key belongs to, and the stamp of the
+    /// values of the shard. The keys belonging to no shards are kept in the column family of the
+    /// group, see `GroupEngine::migrate_layout`.
+    fn cf_handle(&mut self, key: &[u8]) -> Option<(Arc<rocksdb::BoundColumnFamily<'a>>, u64)> {
+        let Some(shard_id) = self.shard_ranges.find(key) else {
+            return Some((self.meta_cf_handle.clone(), 0));
+        };
+        if let Some((cf_handle, stamp)) = self.shard_cf_handles.get(&shard_id) {
+            return Some((cf_handle.clone(), *stamp));
+        }
+        match self.engine.shard_cf_handle_or_create(shard_id) {
+            Ok(cf_handle) => {
+                let stamp = self.engine.range_tombstones(shard_id).stamp();
+                self.shard_cf_handles
+                    .insert(shard_id, (cf_handle.clone(), stamp));
+                Some((cf_handle, stamp))
+            }
+            Err(err) => {
+                self.error.get_or_insert(err);
+                None
+            }
+        }
+    }
 }
 
-impl<'a, 'b> rocksdb::WriteBatchIterator for ColumnFamilyDecorator<'a, 'b> {
+impl<'a> rocksdb::WriteBatchIterator for ColumnFamilyDecorator<'a> {
     fn put(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
-        self.wb.put_cf(&self.cf_handle, key, value);
+        if let Some((cf_handle, stamp)) = self.cf_handle(&key) {
+            // The values are stamped when they are applied rather than evaluated, so that a
+            // value applied after a range deletion is never deleted by it.
+            if stamp > 0 && value.first() == Some(&values::DATA) {
+                self.wb
+                    .put_cf(&cf_handle, key, values::stamped_data(&value[1..], stamp));
+            } else {
+                self.wb.put_cf(&cf_handle, key, value);
+            }
+        }
     }
 
     fn delete(&mut self, key: Box<[u8]>) {
-        self.wb.delete_cf(&self.cf_handle, key);
+        if let Some((cf_handle, _)) = self.cf_handle(&key) {
+            self.wb.delete_cf(&cf_handle, key);
+        }
     }
 }
 
@@ -900,6 +1984,50 @@ impl WriteBatch {
     }
 }
 
+/// Collects the partitions of the keys of a write batch.
+struct KeyPartitioner {
+    num_partitions: usize,
+    partition: Option<usize>,
+    mixed: bool,
+}
+
+impl KeyPartitioner {
+    fn add(&mut self, key: &[u8]) {
+        let partition = key_partition(key, self.num_partitions);
+        match self.partition {
+            None => self.partition = Some(partition),
+            Some(p) if p != partition => self.mixed = true,
+            _ => {}
+        }
+    }
+}
+
+impl rocksdb::WriteBatchIterator for KeyPartitioner {
+    fn put(&mut self, key: Box<[u8]>, _value: Box<[u8]>) {
+        self.add(&key);
+    }
+
+    fn delete(&mut self, key: Box<[u8]>) {
+        self.add(&key);
+    }
+}
+
+/// Returns the partition of all keys of the write batch, `None` if the keys belong to multiple
+/// partitions.
+fn write_batch_partition(wb: &WriteBatch, num_partitions: usize) -> Option<usize> {
+    let mut partitioner = KeyPartitioner {
+        num_partitions,
+        partition: None,
+        mixed: false,
+    };
+    wb.inner.iterate(&mut partitioner);
+    if partitioner.mixed {
+        None
+    } else {
+        Some(partitioner.partition.unwrap_or_default())
+    }
+}
+
 impl Deref for WriteBatch {
     type Target = rocksdb::WriteBatch;
 
@@ -980,6 +2108,37 @@ mod internal {
         Ok(GroupDesc::decode(value.as_ref())?)
     }
 
+    /// Enable the key-value separation of the column family of a shard if it is configured. Blob
+    /// options are dynamically changeable, so they are applied to both created and reopened
+    /// column families.
+    pub(super) fn set_blob_options(
+        cfg: &EngineConfig,
+        db: &rocksdb::DB,
+        cf_name: &str,
+    ) -> Result<()> {
+        let Some(min_blob_size) = cfg.engine_min_blob_size else {
+            return Ok(());
+        };
+
+        let min_blob_size = min_blob_size.to_string();
+        let blob_file_size = cfg.engine_blob_file_size.unwrap_or(256 << 20).to_string();
+        let blob_gc_age_cutoff = cfg.engine_blob_gc_age_cutoff.unwrap_or(0.25).to_string();
+        let opts = [
+            ("enable_blob_files", "true"),
+            ("min_blob_size", min_blob_size.as_str()),
+            ("blob_file_size", blob_file_size.as_str()),
+            ("blob_compression_type", "kLZ4Compression"),
+            ("enable_blob_garbage_collection", "true"),
+            (
+                "blob_garbage_collection_age_cutoff",
+                blob_gc_age_cutoff.as_str(),
+            ),
+        ];
+        let cf_handle = db.cf_handle(cf_name).expect("column family handle");
+        db.set_options_cf(&cf_handle, &opts)?;
+        Ok(())
+    }
+
     pub(super) fn migration_state(
         db: &rocksdb::DB,
         cf_handle: &impl rocksdb::AsColumnFamilyRef,
@@ -1004,13 +2163,47 @@ mod internal {
         Ok(ApplyState::decode(value.as_ref())?)
     }
 
-    #[inline]
-    pub(super) fn shard_descs(group_desc: &GroupDesc) -> HashMap<u64, ShardDesc> {
-        group_desc
+    /// Return the range tombstones of the shard saved in the column family of the group.
+    pub(super) fn range_tombstones(
+        db: &rocksdb::DB,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+        shard_id: u64,
+    ) -> Result<Vec<RangeTombstone>> {
+        use rocksdb::{Direction, IteratorMode, ReadOptions};
+
+        let prefix = keys::range_tombstone_prefix(shard_id);
+        let iter = db.iterator_cf_opt(
+            cf_handle,
+            ReadOptions::default(),
+            IteratorMode::From(&prefix, Direction::Forward),
+        );
+        let mut tombstones = vec![];
+        for item in iter {
+            let (key, value) = item?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            tombstones.push(RangeTombstone::decode(value.as_ref())?);
+        }
+        Ok(tombstones)
+    }
+
+    /// Return the shards of the group, including the shard being migrated.
+    pub(super) fn shard_descs(
+        group_desc: &GroupDesc,
+        migration_state: Option<&MigrationState>,
+    ) -> HashMap<u64, ShardDesc> {
+        let mut shard_descs = group_desc
             .shards
             .iter()
             .map(|shard| (shard.id, shard.clone()))
-            .collect::<HashMap<_, _>>()
+            .collect::<HashMap<_, _>>();
+        if let Some(shard_desc) = migration_state.map(|m| m.get_shard_desc()) {
+            shard_descs
+                .entry(shard_desc.id)
+                .or_insert_with(|| shard_desc.clone());
+        }
+        shard_descs
     }
 }
 
@@ -1516,6 +2709,492 @@ mod tests {
         assert!(user_data_iter.next().is_none());
     }
 
+    #[test]
+    fn shard_stats_in_range() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor, 1, 1);
+
+        use shard_desc::*;
+        let wb = WriteBatch::default();
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![
+                    ShardDesc {
+                        id: 1,
+                        collection_id: 1,
+                        partition: Some(Partition::Range(RangePartition {
+                            start: vec![],
+                            end: b"b".to_vec(),
+                        })),
+                    },
+                    ShardDesc {
+                        id: 2,
+                        collection_id: 1,
+                        partition: Some(Partition::Range(RangePartition {
+                            start: b"b".to_vec(),
+                            end: vec![],
+                        })),
+                    },
+                ],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        group_engine.commit(wb, states, false).unwrap();
+
+        let mut wb = WriteBatch::default();
+        group_engine.put(&mut wb, 1, b"a", b"", 123).unwrap();
+        group_engine.tombstone(&mut wb, 1, b"a", 124).unwrap();
+        group_engine.put(&mut wb, 2, b"b", b"123", 123).unwrap();
+        group_engine.put(&mut wb, 2, b"b", b"124", 124).unwrap();
+        group_engine.put(&mut wb, 2, b"c", b"125", 125).unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+
+        let stats = group_engine.shard_stats(1).unwrap();
+        assert_eq!(stats.num_keys, 2);
+        let stats = group_engine.shard_stats(2).unwrap();
+        assert_eq!(stats.num_keys, 3);
+        assert!(stats.num_bytes > 0);
+
+        // Remove all keys of shard 2, the keys of shard 1 are not affected.
+        let mut wb = WriteBatch::default();
+        group_engine.delete(&mut wb, 2, b"b", 123).unwrap();
+        group_engine.delete(&mut wb, 2, b"b", 124).unwrap();
+        group_engine.delete(&mut wb, 2, b"c", 125).unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+        assert_eq!(group_engine.shard_stats(2).unwrap(), ShardStats::default());
+        assert_eq!(group_engine.shard_stats(1).unwrap().num_keys, 2);
+    }
+
+    #[test]
+    fn drop_removed_shard() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor, 1, 1);
+
+        let mut wb = WriteBatch::default();
+        group_engine.put(&mut wb, 1, b"a", b"", 123).unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+        let cf_name = GroupEngine::shard_cf_name(&group_engine.name, 1);
+        assert!(group_engine.raw_db.cf_handle(&cf_name).is_some());
+
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        group_engine
+            .commit(WriteBatch::default(), states, false)
+            .unwrap();
+        assert!(group_engine.raw_db.cf_handle(&cf_name).is_none());
+        assert!(matches!(
+            group_engine.shard_stats(1),
+            Err(Error::ShardNotFound(1))
+        ));
+    }
+
+    #[test]
+    fn delete_range_by_range_tombstone() {
+        use rocksdb::IteratorMode;
+
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor.clone(), 1, 1);
+
+        let mut wb = WriteBatch::default();
+        for key in [b"a", b"b", b"c"] {
+            group_engine.put(&mut wb, 1, key, b"1", 1).unwrap();
+        }
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+        group_engine.delete_range(1, b"b", b"", 10).unwrap();
+
+        // The values written after the range tombstone are not deleted.
+        let mut wb = WriteBatch::default();
+        group_engine.put(&mut wb, 1, b"c", b"2", 2).unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+
+        let engine = group_engine.clone();
+        executor.block_on(async move {
+            assert_eq!(engine.get(1, b"a").await.unwrap(), Some(b"1".to_vec()));
+            assert_eq!(engine.get(1, b"b").await.unwrap(), None);
+            assert_eq!(engine.get(1, b"c").await.unwrap(), Some(b"2".to_vec()));
+        });
+        let stats = group_engine.shard_stats(1).unwrap();
+        assert_eq!(stats.num_keys, 2);
+
+        // The deleted values are dropped by the compaction filter.
+        let cf_handle = group_engine.shard_cf_handle(1).unwrap();
+        group_engine.raw_db.flush_cf(&cf_handle).unwrap();
+        group_engine
+            .raw_db
+            .compact_range_cf(&cf_handle, None::<&[u8]>, None::<&[u8]>);
+        let num_raw_keys = group_engine
+            .raw_db
+            .iterator_cf(&cf_handle, IteratorMode::Start)
+            .count();
+        assert_eq!(num_raw_keys, 2);
+        assert_eq!(group_engine.shard_stats(1).unwrap(), stats);
+    }
+
+    #[test]
+    fn migrate_legacy_layout() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor.clone(), 1, 1);
+
+        // The data of shards were saved in the column family of the group.
+        let raw_db = group_engine.raw_db.clone();
+        let cf_handle = group_engine.cf_handle();
+        raw_db
+            .put_cf(
+                &cf_handle,
+                keys::mvcc_key(1, None, b"a", 1),
+                values::data(b"1"),
+            )
+            .unwrap();
+        raw_db
+            .put_cf(
+                &cf_handle,
+                keys::mvcc_key(2, None, b"b", 1),
+                values::data(b"2"),
+            )
+            .unwrap();
+        drop(cf_handle);
+        drop(group_engine);
+
+        let group_engine = executor.block_on(async move {
+            GroupEngine::open(&EngineConfig::default(), raw_db, 1, 1)
+                .await
+                .unwrap()
+                .unwrap()
+        });
+        let value = executor.block_on(group_engine.get(1, b"a")).unwrap();
+        assert_eq!(value, Some(b"1".to_vec()));
+
+        // The orphan key of collection 2 is removed.
+        use rocksdb::{Direction, IteratorMode};
+        let start = keys::user_data_start();
+        let mut iter = group_engine.raw_db.iterator_cf(
+            &group_engine.cf_handle(),
+            IteratorMode::From(&start, Direction::Forward),
+        );
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn shard_checksum_is_deterministic() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let engine_1 = create_engine(executor.clone(), 1, 1);
+        let engine_2 = create_engine(executor, 1, 1);
+
+        for engine in [&engine_1, &engine_2] {
+            let mut wb = WriteBatch::default();
+            engine.put(&mut wb, 1, b"a", b"", 123).unwrap();
+            engine.put(&mut wb, 1, b"b", b"123", 123).unwrap();
+            engine.commit(wb, WriteStates::default(), false).unwrap();
+        }
+        let stats = engine_1.shard_stats(1).unwrap();
+        assert_ne!(stats.checksum, 0);
+        assert_eq!(stats, engine_2.shard_stats(1).unwrap());
+
+        // The stats are computed from the data at the time the snapshot is taken.
+        let snapshot = engine_2.shard_stats_snapshot(1).unwrap();
+        let mut wb = WriteBatch::default();
+        engine_2.put(&mut wb, 1, b"b", b"124", 123).unwrap();
+        engine_2.commit(wb, WriteStates::default(), false).unwrap();
+        assert_eq!(stats, snapshot.scan().unwrap());
+        assert_ne!(stats.checksum, engine_2.shard_stats(1).unwrap().checksum);
+    }
+
+    #[test]
+    fn iterate_all_shards_in_order() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor, 1, 1);
+
+        use shard_desc::*;
+        let wb = WriteBatch::default();
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![
+                    ShardDesc {
+                        id: 2,
+                        collection_id: 1,
+                        partition: Some(Partition::Range(RangePartition {
+                            start: vec![],
+                            end: b"b".to_vec(),
+                        })),
+                    },
+                    ShardDesc {
+                        id: 1,
+                        collection_id: 1,
+                        partition: Some(Partition::Range(RangePartition {
+                            start: b"b".to_vec(),
+                            end: vec![],
+                        })),
+                    },
+                    ShardDesc {
+                        id: 3,
+                        collection_id: 2,
+                        partition: Some(Partition::Range(RangePartition {
+                            start: vec![],
+                            end: vec![],
+                        })),
+                    },
+                ],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        group_engine.commit(wb, states, false).unwrap();
+
+        let mut wb = WriteBatch::default();
+        group_engine.put(&mut wb, 2, b"a", b"1", 123).unwrap();
+        group_engine.tombstone(&mut wb, 2, b"a", 124).unwrap();
+        group_engine.put(&mut wb, 1, b"b", b"2", 123).unwrap();
+        group_engine.put(&mut wb, 1, b"c", b"3", 123).unwrap();
+        group_engine.put(&mut wb, 3, b"a", b"4", 123).unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+
+        let entries = group_engine
+            .shards_iter()
+            .unwrap()
+            .map(|item| {
+                let (shard_id, entry) = item.unwrap();
+                (shard_id, entry.user_key().to_owned(), entry.version())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                (1, b"b".to_vec(), 123),
+                (1, b"c".to_vec(), 123),
+                (2, b"a".to_vec(), 124),
+                (2, b"a".to_vec(), 123),
+                (3, b"a".to_vec(), 123),
+            ]
+        );
+
+        let group_stats = group_engine.group_stats().unwrap();
+        assert_eq!(group_stats.len(), 3);
+        for (shard_id, stats) in group_stats {
+            assert_eq!(stats, group_engine.shard_stats(shard_id).unwrap());
+        }
+    }
+
+    #[test]
+    fn flush_until_apply_state_persisted() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let tmp_dir = TempDir::new("engula").unwrap().into_path();
+        let db = crate::bootstrap::open_engine_with_default_config(tmp_dir.join("db")).unwrap();
+        let db = Arc::new(db);
+        executor.block_on(async move {
+            let group_engine = GroupEngine::create(&EngineConfig::default(), db, 1, 1)
+                .await
+                .unwrap();
+            let states = WriteStates {
+                apply_state: Some(ApplyState { index: 5, term: 1 }),
+                ..Default::default()
+            };
+            group_engine
+                .commit(WriteBatch::default(), states, false)
+                .unwrap();
+            assert_eq!(group_engine.flushed_apply_state().unwrap().index, 0);
+
+            group_engine.flush_until(5).unwrap();
+            assert_eq!(group_engine.flushed_apply_state().unwrap().index, 5);
+        });
+    }
+
+    #[test]
+    fn separate_big_values_into_blob_files() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let tmp_dir = TempDir::new("engula").unwrap().into_path();
+        let db = crate::bootstrap::open_engine_with_default_config(tmp_dir.join("db")).unwrap();
+        let db = Arc::new(db);
+        let cfg = EngineConfig {
+            engine_min_blob_size: Some(64),
+            ..Default::default()
+        };
+        executor.block_on(async move {
+            let group_engine = GroupEngine::create(&cfg, db.clone(), 1, 1).await.unwrap();
+            let states = WriteStates {
+                descriptor: Some(GroupDesc {
+                    id: 1,
+                    shards: vec![ShardDesc {
+                        id: 1,
+                        collection_id: 1,
+                        partition: Some(shard_desc::Partition::Range(
+                            shard_desc::RangePartition::default(),
+                        )),
+                    }],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            group_engine
+                .commit(WriteBatch::default(), states, false)
+                .unwrap();
+
+            let value = vec![b'v'; 1024];
+            let mut wb = WriteBatch::default();
+            group_engine.put(&mut wb, 1, b"big", &value, 1).unwrap();
+            group_engine.put(&mut wb, 1, b"small", b"v", 1).unwrap();
+            group_engine
+                .commit(wb, WriteStates::default(), false)
+                .unwrap();
+            db.flush_cf(&group_engine.shard_cf_handle(1).unwrap())
+                .unwrap();
+
+            assert_eq!(group_engine.get(1, b"big").await.unwrap(), Some(value));
+            assert_eq!(
+                group_engine.get(1, b"small").await.unwrap(),
+                Some(b"v".to_vec())
+            );
+
+            // Reopen the group engine and apply blob options again.
+            let group_engine = GroupEngine::open(&cfg, db.clone(), 1, 1)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(group_engine.shard_stats(1).unwrap().num_keys, 2);
+        });
+    }
+
+    #[test]
+    fn parallel_group_commit_keeps_key_order() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor.clone(), 1, 1)
+            .with_apply_pool(Some(Arc::new(ApplyPool::new(4))));
+
+        let mut wbs = vec![];
+        for i in 0..PARALLEL_APPLY_THRESHOLD * 4 {
+            let mut wb = WriteBatch::default();
+            let key = format!("key-{}", i % 10);
+            group_engine
+                .put(&mut wb, 1, key.as_bytes(), i.to_string().as_bytes(), 1)
+                .unwrap();
+            if i % 7 == 0 {
+                // Touches multiple partitions.
+                for j in 0..10 {
+                    let key = format!("multi-{j}");
+                    group_engine
+                        .put(&mut wb, 1, key.as_bytes(), i.to_string().as_bytes(), 1)
+                        .unwrap();
+                }
+            }
+            wbs.push(wb);
+        }
+        let last = PARALLEL_APPLY_THRESHOLD * 4 - 1;
+        group_engine
+            .parallel_group_commit(wbs, WriteStates::default())
+            .unwrap();
+
+        executor.block_on(async move {
+            for k in 0..10 {
+                let key = format!("key-{k}");
+                let expect = last - (last - k) % 10;
+                assert_eq!(
+                    group_engine.get(1, key.as_bytes()).await.unwrap(),
+                    Some(expect.to_string().into_bytes())
+                );
+            }
+            let expect = last - last % 7;
+            for j in 0..10 {
+                let key = format!("multi-{j}");
+                assert_eq!(
+                    group_engine.get(1, key.as_bytes()).await.unwrap(),
+                    Some(expect.to_string().into_bytes())
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn engine_properties() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor, 1, 1);
+
+        let mut wb = WriteBatch::default();
+        group_engine.put(&mut wb, 1, b"a", b"123", 123).unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+
+        assert!(group_engine.property("rocksdb.unknown").unwrap().is_none());
+        let props = group_engine.properties().unwrap();
+        // The column family of the group is flushed once it is created.
+        let level0 = props
+            .iter()
+            .find(|(name, _)| *name == "rocksdb.num-files-at-level0");
+        assert_eq!(level0, Some(&("rocksdb.num-files-at-level0", 1)));
+        assert!(props
+            .iter()
+            .any(|(name, value)| *name == "rocksdb.cur-size-all-mem-tables" && *value > 0));
+    }
+
+    #[test]
+    fn dump_mvcc_entries() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor, 1, 1);
+
+        let mut wb = WriteBatch::default();
+        group_engine.put(&mut wb, 1, b"a", b"123", 1).unwrap();
+        group_engine.tombstone(&mut wb, 1, b"a", 2).unwrap();
+        group_engine.put(&mut wb, 1, b"b", b"45", 3).unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+
+        let mut entries = vec![];
+        let desc = GroupEngine::dump(&group_engine.raw_db, &group_engine.name, |shard_id, e| {
+            let value_size = e.value().map(|v| v.len()).unwrap_or_default();
+            entries.push((
+                shard_id,
+                e.user_key().to_owned(),
+                e.version(),
+                value_size,
+                e.is_tombstone(),
+            ));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(desc.id, 1);
+        assert_eq!(
+            entries,
+            vec![
+                (1, b"a".to_vec(), 2, 0, true),
+                (1, b"a".to_vec(), 1, 3, false),
+                (1, b"b".to_vec(), 3, 2, false),
+            ]
+        );
+
+        assert!(GroupEngine::dump(&group_engine.raw_db, "2-2", |_, _| Ok(())).is_err());
+    }
+
     #[test]
     fn cf_id_irrelevant_write_batch() {
         let executor_owner = ExecutorOwner::new(1);
@@ -12,12 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod apply_pool;
+mod blob;
 mod group;
+mod range_tombstone;
 mod state;
 
 pub use self::{
+    apply_pool::ApplyPool,
+    blob::{BlobFile, BlobRecord},
     group::{
-        EngineConfig, GroupEngine, RawIterator, Snapshot, SnapshotMode, WriteBatch, WriteStates,
+        EngineConfig, GroupEngine, GroupIterator, MvccEntry, RawColumnFamily, RawIterator,
+        ShardSize, ShardStats, Snapshot, SnapshotMode, WriteBatch, WriteStates, ENGINE_PROPERTIES,
         LOCAL_COLLECTION_ID,
     },
     state::StateEngine,
@@ -0,0 +1,145 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The range tombstones of shards, which implement the range deletions of a group engine.
+//!
+//! A range deletion saves a [`RangeTombstone`] in the column family of the group, whose `seq` is
+//! the index of the applied command. The values are stamped with the largest `seq` of the range
+//! tombstones of their shard when they are written, so a value is deleted if its key is in the
+//! range of a tombstone with a greater `seq`. The deleted values are hidden from the snapshots of
+//! the group engine, and dropped by the compaction filter of the column family of the shard.
+//!
+//! The range tombstones are kept until the shard is removed from the group, since the older
+//! values of a key might still live in the lower levels.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use lazy_static::lazy_static;
+use rocksdb::compaction_filter::Decision;
+
+use super::group::values;
+use crate::serverpb::v1::RangeTombstone;
+
+lazy_static! {
+    /// The range tombstones of the column families of shards, keyed by the path of db and the
+    /// name of column family. The compaction filters are installed when the db is opened, before
+    /// the group engines load the range tombstones.
+    static ref REGISTRY: Mutex<HashMap<(PathBuf, String), Arc<RangeTombstones>>> =
+        Mutex::default();
+}
+
+/// The range tombstones of a shard.
+#[derive(Default)]
+pub(super) struct RangeTombstones {
+    tombstones: RwLock<Arc<Vec<RangeTombstone>>>,
+}
+
+impl RangeTombstones {
+    /// Return the range tombstones of the column family `cf_name`, which is registered if not
+    /// exists.
+    pub(super) fn of(db_path: &Path, cf_name: &str) -> Arc<RangeTombstones> {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .entry((db_path.to_owned(), cf_name.to_owned()))
+            .or_default()
+            .clone()
+    }
+
+    /// Unregister the range tombstones once the column family is dropped.
+    pub(super) fn unregister(db_path: &Path, cf_name: &str) {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .remove(&(db_path.to_owned(), cf_name.to_owned()));
+    }
+
+    #[inline]
+    pub(super) fn snapshot(&self) -> Arc<Vec<RangeTombstone>> {
+        self.tombstones.read().unwrap().clone()
+    }
+
+    /// The stamp of the values written now, which is the largest `seq` of the range tombstones.
+    pub(super) fn stamp(&self) -> u64 {
+        self.tombstones
+            .read()
+            .unwrap()
+            .iter()
+            .map(|t| t.seq)
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub(super) fn reset(&self, tombstones: Vec<RangeTombstone>) {
+        *self.tombstones.write().unwrap() = Arc::new(tombstones);
+    }
+
+    /// Return the range tombstones which are covered by `tombstone`, they are superseded once
+    /// `tombstone` is inserted.
+    pub(super) fn covered_by(&self, tombstone: &RangeTombstone) -> Vec<RangeTombstone> {
+        self.tombstones
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|t| is_covered(t, tombstone))
+            .cloned()
+            .collect()
+    }
+
+    pub(super) fn insert(&self, tombstone: RangeTombstone) {
+        let mut tombstones = self.tombstones.write().unwrap();
+        let mut next = tombstones
+            .iter()
+            .filter(|t| !is_covered(t, &tombstone))
+            .cloned()
+            .collect::<Vec<_>>();
+        next.push(tombstone);
+        *tombstones = Arc::new(next);
+    }
+}
+
+/// Whether the value of the raw key with `stamp` is deleted by the range tombstones.
+pub(super) fn is_deleted(tombstones: &[RangeTombstone], key: &[u8], stamp: u64) -> bool {
+    tombstones
+        .iter()
+        .any(|t| stamp < t.seq && t.start.as_slice() <= key && key < t.end.as_slice())
+}
+
+/// Install the compaction filter which drops the values deleted by the range tombstones. The
+/// point tombstones are kept, since they might still hide the values pulled by migration.
+pub(super) fn set_compaction_filter(opts: &mut rocksdb::Options, tombstones: Arc<RangeTombstones>) {
+    opts.set_compaction_filter(
+        "range_tombstone",
+        move |_level: u32, key: &[u8], value: &[u8]| {
+            let stamp = match values::stamp(value) {
+                Some(stamp) => stamp,
+                None => return Decision::Keep,
+            };
+            if is_deleted(&tombstones.snapshot(), key, stamp) {
+                Decision::Remove
+            } else {
+                Decision::Keep
+            }
+        },
+    );
+}
+
+#[inline]
+fn is_covered(t: &RangeTombstone, by: &RangeTombstone) -> bool {
+    by.start <= t.start && t.end <= by.end && t.seq <= by.seq
+}
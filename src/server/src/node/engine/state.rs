@@ -26,7 +26,9 @@ const STATE_CF_NAME: &str = "state";
 /// Local states:
 /// - node ident
 /// - root node descriptors
+/// - cluster features
 /// - replica states
+/// - replica tombstones
 ///
 /// NOTE: The group descriptors is stored in the corresponding GroupEngine, which is to ensure
 /// that both the changes of group descriptor and data are persisted to disk in atomic.
@@ -117,6 +119,43 @@ impl StateEngine {
         }
     }
 
+    /// Save the features activated in the cluster.
+    pub async fn save_cluster_features(&self, features: &ClusterFeatures) -> Result<()> {
+        use rocksdb::{WriteBatch, WriteOptions};
+
+        let cf_handle = self
+            .raw_db
+            .cf_handle(STATE_CF_NAME)
+            .expect("state column family");
+
+        let mut opts = WriteOptions::default();
+        opts.set_sync(true);
+        let mut wb = WriteBatch::default();
+        wb.put_cf(
+            &cf_handle,
+            keys::cluster_features(),
+            features.encode_to_vec(),
+        );
+        self.raw_db.write_opt(wb, &opts)?;
+
+        Ok(())
+    }
+
+    /// Load the features activated in the cluster, `None` is returned if they are never saved.
+    pub async fn load_cluster_features(&self) -> Result<Option<ClusterFeatures>> {
+        let cf_handle = self
+            .raw_db
+            .cf_handle(STATE_CF_NAME)
+            .expect("state column family");
+        match self
+            .raw_db
+            .get_pinned_cf(&cf_handle, keys::cluster_features())?
+        {
+            Some(value) => Ok(Some(ClusterFeatures::decode(value.as_ref())?)),
+            None => Ok(None),
+        }
+    }
+
     /// Save replica state.
     pub async fn save_replica_state(
         &self,
@@ -150,6 +189,108 @@ impl StateEngine {
         Ok(())
     }
 
+    /// Save the replica state to `ReplicaLocalState::Terminated` and the tombstone of the replica
+    /// atomically.
+    pub async fn terminate_replica(&self, tombstone: &ReplicaTombstone) -> Result<()> {
+        use rocksdb::{WriteBatch, WriteOptions};
+
+        let replica_meta = ReplicaMeta {
+            group_id: tombstone.group_id,
+            replica_id: tombstone.replica_id,
+            state: ReplicaLocalState::Terminated.into(),
+        };
+        let cf_handle = self
+            .raw_db
+            .cf_handle(STATE_CF_NAME)
+            .expect("state column family");
+
+        let mut opts = WriteOptions::default();
+        opts.set_sync(true);
+        let mut wb = WriteBatch::default();
+        wb.put_cf(
+            &cf_handle,
+            keys::replica_state(tombstone.replica_id).as_slice(),
+            replica_meta.encode_to_vec(),
+        );
+        wb.put_cf(
+            &cf_handle,
+            keys::replica_tombstone(tombstone.replica_id).as_slice(),
+            tombstone.encode_to_vec(),
+        );
+        self.raw_db.write_opt(wb, &opts)?;
+
+        Ok(())
+    }
+
+    /// Load the tombstone of replica. `None` is returned if the replica isn't removed or the
+    /// tombstone is purged.
+    pub async fn load_replica_tombstone(
+        &self,
+        replica_id: u64,
+    ) -> Result<Option<ReplicaTombstone>> {
+        let cf_handle = self
+            .raw_db
+            .cf_handle(STATE_CF_NAME)
+            .expect("state column family");
+        let key = keys::replica_tombstone(replica_id);
+        match self.raw_db.get_pinned_cf(&cf_handle, key.as_slice())? {
+            Some(value) => Ok(Some(
+                ReplicaTombstone::decode(value.as_ref()).expect("valid ReplicaTombstone format"),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Purge the tombstones of the replicas which are removed before `removed_before` (unix
+    /// seconds) and whose data are destroyed, together with the replica states. The purged
+    /// tombstones are returned.
+    pub async fn purge_replica_tombstones(
+        &self,
+        removed_before: u64,
+    ) -> Result<Vec<ReplicaTombstone>> {
+        use rocksdb::{Direction, IteratorMode, WriteBatch, WriteOptions};
+
+        let cf_handle = self
+            .raw_db
+            .cf_handle(STATE_CF_NAME)
+            .expect("state column family");
+        let mut purged = vec![];
+        let mut wb = WriteBatch::default();
+        let mode = IteratorMode::From(keys::replica_tombstone_prefix(), Direction::Forward);
+        for item in self.raw_db.iterator_cf(&cf_handle, mode) {
+            let (key, value) = item?;
+            if !key.starts_with(keys::replica_tombstone_prefix()) {
+                break;
+            }
+            let tombstone =
+                ReplicaTombstone::decode(value.as_ref()).expect("valid ReplicaTombstone format");
+            if tombstone.removed_at > removed_before {
+                continue;
+            }
+            let state_key = keys::replica_state(tombstone.replica_id);
+            if let Some(value) = self
+                .raw_db
+                .get_pinned_cf(&cf_handle, state_key.as_slice())?
+            {
+                let replica_meta =
+                    ReplicaMeta::decode(value.as_ref()).expect("valid ReplicaMeta format");
+                if replica_meta.state != ReplicaLocalState::Tombstone as i32 {
+                    // The data of replica is not destroyed yet.
+                    continue;
+                }
+                wb.delete_cf(&cf_handle, state_key.as_slice());
+            }
+            wb.delete_cf(&cf_handle, key.as_ref());
+            purged.push(tombstone);
+        }
+        if !purged.is_empty() {
+            let mut opts = WriteOptions::default();
+            opts.set_sync(true);
+            self.raw_db.write_opt(wb, &opts)?;
+        }
+        Ok(purged)
+    }
+
     /// Iterate group states.
     pub async fn iterate_replica_states(&self) -> ReplicaStateIterator<'_> {
         use rocksdb::{Direction, IteratorMode};
@@ -195,6 +336,8 @@ mod keys {
     const ROOT_DESCRIPTOR_KEY: &[u8] = &[0x2];
     const REPLICA_STATE_PREFIX: &[u8] = &[0x3];
     const REPLICA_STATE_END: &[u8] = &[0x4];
+    const CLUSTER_FEATURES_KEY: &[u8] = &[0x5];
+    const REPLICA_TOMBSTONE_PREFIX: &[u8] = &[0x6];
 
     pub fn node_ident() -> &'static [u8] {
         IDENT_KEY
@@ -204,6 +347,10 @@ mod keys {
         ROOT_DESCRIPTOR_KEY
     }
 
+    pub fn cluster_features() -> &'static [u8] {
+        CLUSTER_FEATURES_KEY
+    }
+
     pub fn replica_state_prefix() -> &'static [u8] {
         REPLICA_STATE_PREFIX
     }
@@ -219,6 +366,17 @@ mod keys {
         buf
     }
 
+    pub fn replica_tombstone_prefix() -> &'static [u8] {
+        REPLICA_TOMBSTONE_PREFIX
+    }
+
+    pub fn replica_tombstone(replica_id: u64) -> [u8; 9] {
+        let mut buf = [0; 9];
+        buf[..1].copy_from_slice(REPLICA_TOMBSTONE_PREFIX);
+        buf[1..].copy_from_slice(&replica_id.to_le_bytes());
+        buf
+    }
+
     /// Parse replica id from replica state key. `None` is returned if the prefix or length does not
     /// matched.
     pub fn parse_replica_id(key: &[u8]) -> Option<u64> {
@@ -0,0 +1,181 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use engula_api::server::v1::{DiskLatency, StorageHealth as StorageHealthStats};
+
+use super::metrics::*;
+
+/// The penalty of each checksum mismatch detected by scrubbing.
+const CHECKSUM_ERROR_PENALTY: f64 = 0.25;
+
+/// The max penalty of checksum mismatches, the remaining is left for the latency of disks.
+const MAX_CHECKSUM_ERROR_PENALTY: f64 = 0.5;
+
+/// The probing latency below it is considered healthy.
+const HEALTHY_LATENCY: Duration = Duration::from_millis(20);
+
+/// The probing latency above it is considered sick.
+const SICK_LATENCY: Duration = Duration::from_millis(500);
+
+/// The weight of the latest sample of the moving average latency.
+const LATENCY_EWMA_ALPHA: f64 = 0.3;
+
+/// Collects the checksum mismatches reported by the scrubber and the latency of probing disks,
+/// and summarizes them into a health score reported to root by heartbeats.
+#[derive(Default)]
+pub struct StorageHealth {
+    scrubbed_bytes: AtomicU64,
+    checksum_errors: AtomicU64,
+    /// The moving average latency of each mount, in microseconds.
+    disks: Mutex<BTreeMap<PathBuf, f64>>,
+}
+
+impl StorageHealth {
+    pub fn on_scrubbed(&self, bytes: u64) {
+        NODE_SCRUB_BYTES_TOTAL.inc_by(bytes);
+        self.scrubbed_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn on_checksum_error(&self) {
+        NODE_SCRUB_CHECKSUM_ERRORS_TOTAL.inc();
+        self.checksum_errors.fetch_add(1, Ordering::Relaxed);
+        NODE_STORAGE_HEALTH_SCORE.set(self.score());
+    }
+
+    pub fn observe_latency(&self, mount: &Path, latency: Duration) {
+        NODE_DISK_PROBE_DURATION_SECONDS_VEC
+            .with_label_values(&[&mount.display().to_string()])
+            .observe(latency.as_secs_f64());
+
+        let sample = latency.as_micros() as f64;
+        {
+            let mut disks = self.disks.lock().unwrap();
+            let avg = disks.entry(mount.to_owned()).or_insert(sample);
+            *avg = LATENCY_EWMA_ALPHA * sample + (1.0 - LATENCY_EWMA_ALPHA) * *avg;
+        }
+        NODE_STORAGE_HEALTH_SCORE.set(self.score());
+    }
+
+    /// Returns the health score in [0, 1], 1 means the disks are healthy.
+    pub fn score(&self) -> f64 {
+        let checksum_errors = self.checksum_errors.load(Ordering::Relaxed) as f64;
+        let checksum_penalty =
+            (checksum_errors * CHECKSUM_ERROR_PENALTY).min(MAX_CHECKSUM_ERROR_PENALTY);
+
+        // The slowest disk dominates, since the replicas are spread over all of them.
+        let latency = self
+            .disks
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .fold(0.0, f64::max);
+        let healthy = HEALTHY_LATENCY.as_micros() as f64;
+        let sick = SICK_LATENCY.as_micros() as f64;
+        let latency_penalty = ((latency - healthy) / (sick - healthy)).clamp(0.0, 1.0)
+            * (1.0 - MAX_CHECKSUM_ERROR_PENALTY);
+
+        (1.0 - checksum_penalty - latency_penalty).clamp(0.0, 1.0)
+    }
+
+    pub fn stats(&self) -> StorageHealthStats {
+        let disks = self
+            .disks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(mount, latency)| DiskLatency {
+                mount: mount.display().to_string(),
+                latency_us: *latency as u64,
+            })
+            .collect();
+        StorageHealthStats {
+            score: self.score() as f32,
+            checksum_errors: self.checksum_errors.load(Ordering::Relaxed),
+            scrubbed_bytes: self.scrubbed_bytes.load(Ordering::Relaxed),
+            disks,
+        }
+    }
+}
+
+/// Returns the total and available space of the file system which the path lives on, in bytes.
+pub fn disk_space(path: &Path) -> std::io::Result<(u64, u64)> {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    let fragment_size = stat.f_frsize as u64;
+    Ok((
+        stat.f_blocks as u64 * fragment_size,
+        stat.f_bavail as u64 * fragment_size,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_health_score() {
+        let health = StorageHealth::default();
+        assert_eq!(health.score(), 1.0);
+
+        let mount = Path::new("/data");
+        health.observe_latency(mount, Duration::from_millis(1));
+        assert_eq!(health.score(), 1.0);
+
+        health.on_checksum_error();
+        assert_eq!(health.score(), 1.0 - CHECKSUM_ERROR_PENALTY);
+        for _ in 0..8 {
+            health.on_checksum_error();
+        }
+        assert_eq!(health.score(), 1.0 - MAX_CHECKSUM_ERROR_PENALTY);
+
+        // The latency of disk grows up slowly.
+        health.observe_latency(mount, Duration::from_secs(1));
+        let score = health.score();
+        assert!(score > 0.0 && score < 1.0 - MAX_CHECKSUM_ERROR_PENALTY);
+        for _ in 0..32 {
+            health.observe_latency(mount, Duration::from_secs(1));
+        }
+        assert_eq!(health.score(), 0.0);
+
+        let stats = health.stats();
+        assert_eq!(stats.checksum_errors, 9);
+        assert_eq!(stats.disks.len(), 1);
+        assert_eq!(stats.disks[0].mount, "/data");
+    }
+
+    #[test]
+    fn disk_space_of_temp_dir() {
+        let (total, available) = disk_space(&std::env::temp_dir()).unwrap();
+        assert!(total > 0);
+        assert!(available <= total);
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reclaims the space of the dead values in blob files by punching holes over them, without
+//! rewriting the files.
+//!
+//! RocksDB only tracks the garbage of blob files per file, so the job finds the dead values by
+//! itself: a value is dead once its key no longer exists in the column family of the shard, since
+//! the keys of group engines are never rewritten with the same version. A value is only punched
+//! if it is found dead in two consecutive rounds, so that the snapshots and the compaction outputs
+//! which aren't installed yet never read a hole.
+//!
+//! The job is triggered once the disk space occupied by blob files exceeds
+//! `EngineConfig::engine_blob_punch_hole_space_amp` of the live values.
+
+use std::{
+    collections::HashSet,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    node::{
+        engine::{BlobFile, EngineConfig, GroupEngine},
+        metrics::NODE_BLOB_PUNCHED_BYTES_TOTAL,
+        route_table::ReplicaRouteTable,
+    },
+    runtime::{registry::group, TaskPriority},
+    Provider, Result,
+};
+
+const BLOB_GC_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Skip if the blob files occupy less disk space, the space to reclaim doesn't pay for the scan.
+const BLOB_GC_MIN_ALLOCATED_BYTES: u64 = 64 << 20;
+
+struct BlobCollector {
+    db_path: PathBuf,
+    space_amp: f64,
+    /// The values found dead in the last round, identified by the file number and the offset.
+    candidates: HashSet<(u64, u64)>,
+}
+
+pub(crate) fn setup(cfg: &EngineConfig, provider: &Provider, replica_table: ReplicaRouteTable) {
+    let space_amp = match cfg.engine_blob_punch_hole_space_amp {
+        Some(amp) if cfg.engine_min_blob_size.is_some() && amp > 1.0 => amp,
+        _ => return,
+    };
+
+    let executor = provider.executor.clone();
+    let mut collector = BlobCollector {
+        db_path: provider.db_path.clone(),
+        space_amp,
+        candidates: HashSet::default(),
+    };
+    provider.executor.spawn_named(
+        group::NODE,
+        "blob-gc",
+        None,
+        TaskPriority::IoLow,
+        async move {
+            loop {
+                crate::runtime::time::sleep(BLOB_GC_INTERVAL).await;
+                let engines = replica_table
+                    .replicas()
+                    .iter()
+                    .map(|replica| replica.group_engine())
+                    .collect::<Vec<_>>();
+                collector = executor
+                    .spawn_blocking(move || {
+                        if let Err(err) = collector.collect(&engines) {
+                            warn!("collect blob garbage: {err:?}");
+                        }
+                        collector
+                    })
+                    .await;
+            }
+        },
+    );
+}
+
+impl BlobCollector {
+    fn collect(&mut self, engines: &[GroupEngine]) -> Result<()> {
+        let blob_files = self.blob_files()?;
+        let allocated_bytes = blob_files
+            .iter()
+            .map(|(_, _, metadata)| metadata.blocks() * 512)
+            .sum::<u64>();
+        let mut live_bytes = 0;
+        for engine in engines {
+            let size = engine.property("rocksdb.live-blob-file-size")?;
+            let garbage = engine.property("rocksdb.live-blob-file-garbage-size")?;
+            live_bytes += size
+                .unwrap_or_default()
+                .saturating_sub(garbage.unwrap_or_default());
+        }
+        if !exceeds_space_amp(allocated_bytes, live_bytes, self.space_amp) {
+            self.candidates.clear();
+            return Ok(());
+        }
+
+        info!(
+            "punch the dead values of blob files, {allocated_bytes} bytes are allocated for \
+            {live_bytes} live bytes"
+        );
+        let mut candidates = HashSet::default();
+        let mut punched_bytes = 0;
+        for (file_number, path, metadata) in blob_files {
+            // The blob files just written might not be installed yet.
+            let modified = metadata.modified()?;
+            if SystemTime::now()
+                .duration_since(modified)
+                .map(|elapsed| elapsed < BLOB_GC_INTERVAL)
+                .unwrap_or(true)
+            {
+                continue;
+            }
+            match self.punch_file(engines, file_number, &path, &mut candidates) {
+                Ok(bytes) => punched_bytes += bytes,
+                Err(err) => warn!("punch blob file {}: {err:?}", path.display()),
+            }
+        }
+        self.candidates = candidates;
+        NODE_BLOB_PUNCHED_BYTES_TOTAL.inc_by(punched_bytes);
+        info!("punch the dead values of blob files, {punched_bytes} bytes are reclaimed");
+        Ok(())
+    }
+
+    fn punch_file(
+        &self,
+        engines: &[GroupEngine],
+        file_number: u64,
+        path: &Path,
+        candidates: &mut HashSet<(u64, u64)>,
+    ) -> Result<u64> {
+        let blob_file = match BlobFile::open(path)? {
+            Some(blob_file) => blob_file,
+            None => return Ok(0),
+        };
+        let mut punched_bytes = 0;
+        for record in blob_file.records() {
+            let record = record?;
+            let range = match blob_file.reclaimable(&record)? {
+                Some(range) => range,
+                None => continue,
+            };
+            if !is_dead(engines, &record.key)? {
+                continue;
+            }
+            let id = (file_number, record.value_offset);
+            if self.candidates.contains(&id) {
+                punched_bytes += blob_file.punch(range)?;
+            } else {
+                candidates.insert(id);
+            }
+        }
+        Ok(punched_bytes)
+    }
+
+    /// Return the file number, path and metadata of blob files.
+    fn blob_files(&self) -> Result<Vec<(u64, PathBuf, std::fs::Metadata)>> {
+        let mut blob_files = vec![];
+        for entry in std::fs::read_dir(&self.db_path)? {
+            let path = entry?.path();
+            if path.extension().map(|ext| ext != "blob").unwrap_or(true) {
+                continue;
+            }
+            let file_number = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(stem) => match stem.parse() {
+                    Ok(file_number) => file_number,
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+            // The obsolete files might be deleted concurrently.
+            if let Ok(metadata) = std::fs::metadata(&path) {
+                blob_files.push((file_number, path, metadata));
+            }
+        }
+        Ok(blob_files)
+    }
+}
+
+/// Whether the value of the key is dead. The keys which don't belong to any shard are skipped,
+/// eg. the replica is being destroyed.
+fn is_dead(engines: &[GroupEngine], key: &[u8]) -> Result<bool> {
+    let mut found_shard = false;
+    for engine in engines {
+        match engine.contains_raw_key(key)? {
+            Some(true) => return Ok(false),
+            Some(false) => found_shard = true,
+            None => {}
+        }
+    }
+    Ok(found_shard)
+}
+
+fn exceeds_space_amp(allocated_bytes: u64, live_bytes: u64, space_amp: f64) -> bool {
+    allocated_bytes >= BLOB_GC_MIN_ALLOCATED_BYTES
+        && allocated_bytes as f64 > live_bytes as f64 * space_amp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blob_space_amp() {
+        let allocated_bytes = BLOB_GC_MIN_ALLOCATED_BYTES * 4;
+        assert!(exceeds_space_amp(allocated_bytes, allocated_bytes / 4, 2.0));
+        assert!(!exceeds_space_amp(allocated_bytes, allocated_bytes, 2.0));
+        assert!(exceeds_space_amp(allocated_bytes, 0, 2.0));
+        // Too few blob bytes to reclaim.
+        assert!(!exceeds_space_amp(1024, 0, 2.0));
+    }
+}
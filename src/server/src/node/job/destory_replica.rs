@@ -20,7 +20,7 @@ use crate::{
     node::{metrics::*, GroupEngine, StateEngine},
     raftgroup::destory_storage,
     record_latency,
-    runtime::TaskPriority,
+    runtime::{registry::group, TaskPriority},
     serverpb::v1::ReplicaLocalState,
     Error, Provider, Result,
 };
@@ -35,15 +35,20 @@ pub(crate) fn setup(
     let tag = &group_id.to_le_bytes();
     let state_engine = provider.state_engine.clone();
     let raw_db = provider.raw_db.clone();
-    provider
-        .executor
-        .spawn(Some(tag), TaskPriority::IoLow, async move {
+    let name = format!("destory-replica-{group_id}-{replica_id}");
+    provider.executor.spawn_named(
+        group::NODE,
+        name,
+        Some(tag),
+        TaskPriority::IoLow,
+        async move {
             if let Err(err) =
                 destory_replica(group_id, replica_id, state_engine, raw_db, raft_engine).await
             {
                 error!("destory group engine: {}, group {}", err, group_id);
             }
-        });
+        },
+    );
 }
 
 async fn destory_replica(
@@ -54,6 +59,7 @@ async fn destory_replica(
     raft_engine: Arc<raft_engine::Engine>,
 ) -> Result<()> {
     record_latency!(take_destory_replica_metrics());
+    remove_retry_metrics(group_id);
     match GroupEngine::destory(group_id, replica_id, raw_db).await {
         Ok(()) => {}
         Err(Error::RocksDb(err)) if err.to_string().contains("Invalid column family") => {}
@@ -0,0 +1,131 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{path::Path, time::Duration};
+
+use tracing::{debug, warn};
+
+use crate::{
+    node::{
+        memory::{MemoryTracker, Subsystem},
+        metrics::NODE_MEMORY_BUDGET_EXCEEDED_TOTAL,
+    },
+    runtime::{registry::group, TaskPriority},
+    Provider, Result,
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Samples the memory usage of the engine periodically, and reclaims memory once the budget is
+/// exceeded: the registered reclaimers are asked first, then the largest memtables are flushed.
+///
+/// The block cache is only accounted, it is already bounded by `db.block_cache_size`.
+pub(crate) fn setup(provider: &Provider) {
+    let executor = provider.executor.clone();
+    let tracker = provider.memory_tracker.clone();
+    let raw_db = provider.raw_db.clone();
+    let db_path = provider.db_path.clone();
+    provider.executor.spawn_named(
+        group::NODE,
+        "memory-budget",
+        None,
+        TaskPriority::IoLow,
+        async move {
+            loop {
+                crate::runtime::time::sleep(SAMPLE_INTERVAL).await;
+                let cloned_tracker = tracker.clone();
+                let cloned_db = raw_db.clone();
+                let cloned_path = db_path.clone();
+                if let Err(err) = executor
+                    .spawn_blocking(move || {
+                        enforce_budget(&cloned_tracker, &cloned_db, &cloned_path)
+                    })
+                    .await
+                {
+                    warn!("enforce memory budget: {err:?}");
+                }
+            }
+        },
+    );
+}
+
+fn enforce_budget(tracker: &MemoryTracker, raw_db: &rocksdb::DB, db_path: &Path) -> Result<()> {
+    let mut memtables = sample_memtables(raw_db, db_path)?;
+    tracker.set(
+        Subsystem::Memtable,
+        memtables.iter().map(|(_, size)| *size).sum(),
+    );
+    tracker.set(
+        Subsystem::BlockCache,
+        property_value(raw_db.property_value("rocksdb.block-cache-usage")?),
+    );
+
+    let mut excess = tracker.excess();
+    if excess == 0 {
+        return Ok(());
+    }
+
+    NODE_MEMORY_BUDGET_EXCEEDED_TOTAL.inc();
+    warn!(
+        "memory usage {} exceeds the budget {}, reclaim {excess} bytes",
+        tracker.total_usage(),
+        tracker.budget()
+    );
+    excess = excess.saturating_sub(tracker.reclaim(excess));
+
+    // Flush the largest memtables first.
+    memtables.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+    let cf_names = memtables
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    for (name, size) in memtables {
+        if excess == 0 || size == 0 {
+            break;
+        }
+        if let Some(cf_handle) = raw_db.cf_handle(&name) {
+            debug!("flush memtable of {name} with {size} bytes to reclaim memory");
+            // The shards of a group are written without WAL, so their column families are
+            // flushed before the column family of the group, which saves the apply state.
+            let shard_cf_prefix = format!("{name}-");
+            for shard_cf_name in cf_names.iter().filter(|n| n.starts_with(&shard_cf_prefix)) {
+                if let Some(shard_cf_handle) = raw_db.cf_handle(shard_cf_name) {
+                    raw_db.flush_cf(&shard_cf_handle)?;
+                }
+            }
+            raw_db.flush_cf(&cf_handle)?;
+            excess = excess.saturating_sub(size);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the memtable size of each column family.
+fn sample_memtables(raw_db: &rocksdb::DB, db_path: &Path) -> Result<Vec<(String, usize)>> {
+    let cf_names = rocksdb::DB::list_cf(&rocksdb::Options::default(), db_path)?;
+    let mut memtables = Vec::with_capacity(cf_names.len());
+    for name in cf_names {
+        if let Some(cf_handle) = raw_db.cf_handle(&name) {
+            let value = raw_db.property_value_cf(&cf_handle, "rocksdb.cur-size-all-mem-tables")?;
+            memtables.push((name, property_value(value)));
+        }
+    }
+    Ok(memtables)
+}
+
+fn property_value(value: Option<String>) -> usize {
+    value
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or_default()
+}
@@ -12,8 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod blob_gc;
 mod destory_replica;
+mod memory_budget;
+mod reaper;
 mod report_state;
+mod scrub;
+mod webhook;
 
+pub(crate) use blob_gc::setup as setup_blob_gc;
 pub(crate) use destory_replica::setup as setup_destory_replica;
+pub(crate) use memory_budget::setup as setup_memory_budget;
+pub(crate) use reaper::setup as setup_reaper;
 pub(crate) use report_state::{setup as setup_report_state, StateChannel};
+pub(crate) use scrub::setup as setup_scrub;
+pub(crate) use webhook::setup as setup_webhook;
@@ -0,0 +1,285 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tracing::{debug, info, warn};
+
+use crate::{
+    node::{metrics::*, NodeConfig},
+    runtime::{registry::group, TaskPriority},
+    serverpb::v1::ReplicaLocalState,
+    Provider, Result,
+};
+
+const REAP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The files younger than this are never considered orphaned, since they might be created by an
+/// in-progress flush, compaction or snapshot transfer.
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// The name of the dir under the root dir of node, which holds the quarantined files.
+pub(crate) const QUARANTINE_DIR: &str = "quarantine";
+
+/// Moves the orphaned files of the data directory into the quarantine dir, and deletes them once
+/// they have been quarantined longer than `reaper_retention_sec`. The orphaned files are:
+/// - the temp files of db left by the former crashes,
+/// - the snapshot dirs of the replicas which are destroyed or not exist.
+///
+/// The SST files are never touched: those not in the current version might still be referenced by
+/// the older versions, snapshots or iterators, and RocksDB deletes them once they are obsolete.
+pub(crate) fn setup(cfg: &NodeConfig, provider: &Provider) {
+    if cfg.reaper_retention_sec == 0 {
+        return;
+    }
+
+    let root_dir = provider.db_path.parent().unwrap_or_else(|| Path::new("."));
+    let reaper = Arc::new(Reaper {
+        db_path: provider.db_path.clone(),
+        snap_path: provider.log_path.join("snap"),
+        quarantine_dir: root_dir.join(QUARANTINE_DIR),
+        min_age: ORPHAN_MIN_AGE,
+        retention: Duration::from_secs(cfg.reaper_retention_sec),
+    });
+    let executor = provider.executor.clone();
+    let state_engine = provider.state_engine.clone();
+    provider.executor.spawn_named(
+        group::NODE,
+        "reaper",
+        None,
+        TaskPriority::IoLow,
+        async move {
+            loop {
+                crate::runtime::time::sleep(REAP_INTERVAL).await;
+                let mut live_replicas = HashSet::default();
+                for item in state_engine.iterate_replica_states().await {
+                    match item {
+                        Ok((_, replica_id, state)) if state != ReplicaLocalState::Tombstone => {
+                            live_replicas.insert(replica_id);
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            warn!("reaper iterate replica states: {err:?}");
+                            live_replicas.clear();
+                            break;
+                        }
+                    }
+                }
+                if live_replicas.is_empty() {
+                    // Nothing is reaped unless the replicas of node are known.
+                    continue;
+                }
+                let cloned_reaper = reaper.clone();
+                if let Err(err) = executor
+                    .spawn_blocking(move || cloned_reaper.reap(&live_replicas))
+                    .await
+                {
+                    warn!("reap orphaned files: {err:?}");
+                }
+            }
+        },
+    );
+}
+
+struct Reaper {
+    db_path: PathBuf,
+    snap_path: PathBuf,
+    quarantine_dir: PathBuf,
+    min_age: Duration,
+    retention: Duration,
+}
+
+impl Reaper {
+    fn reap(&self, live_replicas: &HashSet<u64>) -> Result<()> {
+        let now = SystemTime::now();
+        let orphans = self.collect_orphans(live_replicas, now)?;
+        if !orphans.is_empty() {
+            std::fs::create_dir_all(&self.quarantine_dir)?;
+        }
+        let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        for (name, path) in orphans {
+            let size = disk_usage(&path)?;
+            let target = self.quarantine_dir.join(format!("{secs}-{name}"));
+            // The quarantine dir might be on another device, the orphan is left as it is.
+            if let Err(err) = std::fs::rename(&path, &target) {
+                warn!("quarantine orphaned file {}: {err}", path.display());
+                continue;
+            }
+            info!(
+                "quarantine orphaned file {} with {size} bytes to {}",
+                path.display(),
+                target.display()
+            );
+            NODE_REAPER_QUARANTINED_BYTES_TOTAL.inc_by(size);
+        }
+        self.purge(secs)
+    }
+
+    /// Returns the orphaned files and dirs, with the names used in the quarantine dir.
+    fn collect_orphans(
+        &self,
+        live_replicas: &HashSet<u64>,
+        now: SystemTime,
+    ) -> Result<Vec<(String, PathBuf)>> {
+        let mut orphans = vec![];
+        for entry in std::fs::read_dir(&self.db_path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_orphan = name.ends_with(".dbtmp") || name.ends_with(".tmp");
+            if is_orphan && entry.file_type()?.is_file() && self.is_aged(&entry.path(), now)? {
+                orphans.push((name, entry.path()));
+            }
+        }
+
+        if self.snap_path.exists() {
+            for entry in std::fs::read_dir(&self.snap_path)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let replica_id = match name.parse::<u64>() {
+                    Ok(replica_id) => replica_id,
+                    Err(_) => continue,
+                };
+                if !live_replicas.contains(&replica_id)
+                    && entry.file_type()?.is_dir()
+                    && self.is_aged(&entry.path(), now)?
+                {
+                    orphans.push((format!("snap-{name}"), entry.path()));
+                }
+            }
+        }
+        Ok(orphans)
+    }
+
+    fn is_aged(&self, path: &Path, now: SystemTime) -> Result<bool> {
+        let modified = std::fs::metadata(path)?.modified()?;
+        Ok(now.duration_since(modified).unwrap_or_default() >= self.min_age)
+    }
+
+    /// Delete the quarantined files which have been retained long enough.
+    fn purge(&self, now_secs: u64) -> Result<()> {
+        if !self.quarantine_dir.exists() {
+            return Ok(());
+        }
+        let mut reclaimed_bytes = 0;
+        for entry in std::fs::read_dir(&self.quarantine_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let quarantined_secs = match name.split_once('-').map(|(s, _)| s.parse::<u64>()) {
+                Some(Ok(secs)) => secs,
+                _ => {
+                    debug!("skip unknown file {name} in quarantine dir");
+                    continue;
+                }
+            };
+            if now_secs.saturating_sub(quarantined_secs) < self.retention.as_secs() {
+                continue;
+            }
+            let path = entry.path();
+            let size = disk_usage(&path)?;
+            if entry.file_type()?.is_dir() {
+                std::fs::remove_dir_all(&path)?;
+            } else {
+                std::fs::remove_file(&path)?;
+            }
+            reclaimed_bytes += size;
+        }
+        if reclaimed_bytes > 0 {
+            info!("reaper reclaims {reclaimed_bytes} bytes of quarantined files");
+            NODE_REAPER_RECLAIMED_BYTES_TOTAL.inc_by(reclaimed_bytes);
+        }
+        Ok(())
+    }
+}
+
+/// The total size of the file, or the files in the dir recursively.
+fn disk_usage(path: &Path) -> Result<u64> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        size += disk_usage(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::bootstrap::open_engine_with_default_config;
+
+    #[test]
+    fn quarantine_and_purge_orphans() {
+        let dir = TempDir::new("quarantine_and_purge_orphans").unwrap();
+        let db_path = dir.path().join("db");
+        let snap_path = dir.path().join("log").join("snap");
+        let raw_db = Arc::new(open_engine_with_default_config(&db_path).unwrap());
+        raw_db.put(b"key", b"value").unwrap();
+        raw_db.flush().unwrap();
+        let live_files = raw_db.live_files().unwrap();
+        assert!(!live_files.is_empty());
+
+        // The SST files unknown by the current version are left to RocksDB.
+        std::fs::write(db_path.join("999999.sst"), b"unknown").unwrap();
+        std::fs::write(db_path.join("999998.dbtmp"), b"temp").unwrap();
+        std::fs::create_dir_all(snap_path.join("1").join("0")).unwrap();
+        std::fs::create_dir_all(snap_path.join("2").join("0")).unwrap();
+        std::fs::write(snap_path.join("2").join("0").join("DATA"), b"snapshot").unwrap();
+
+        let reaper = Reaper {
+            db_path: db_path.clone(),
+            snap_path: snap_path.clone(),
+            quarantine_dir: dir.path().join(QUARANTINE_DIR),
+            min_age: Duration::ZERO,
+            retention: Duration::from_secs(3600),
+        };
+        let live_replicas = HashSet::from([1]);
+        reaper.reap(&live_replicas).unwrap();
+
+        assert!(db_path.join("999999.sst").exists());
+        assert!(!db_path.join("999998.dbtmp").exists());
+        assert!(snap_path.join("1").exists());
+        assert!(!snap_path.join("2").exists());
+        for file in &live_files {
+            assert!(db_path.join(file.name.trim_start_matches('/')).exists());
+        }
+        let quarantined = std::fs::read_dir(&reaper.quarantine_dir).unwrap().count();
+        assert_eq!(quarantined, 2);
+        assert_eq!(raw_db.get(b"key").unwrap(), Some(b"value".to_vec()));
+
+        // The quarantined files are retained until the retention delay passes.
+        let now_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        reaper.purge(now_secs).unwrap();
+        assert_eq!(
+            std::fs::read_dir(&reaper.quarantine_dir).unwrap().count(),
+            2
+        );
+        reaper.purge(now_secs + 3600).unwrap();
+        assert_eq!(
+            std::fs::read_dir(&reaper.quarantine_dir).unwrap().count(),
+            0
+        );
+    }
+}
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
 use engula_api::server::v1::{
     report_request::GroupUpdates, GroupDesc, ReplicaState, ReportRequest, ScheduleState,
@@ -21,77 +21,181 @@ use engula_client::RootClient;
 use futures::{channel::mpsc, StreamExt};
 use tracing::warn;
 
-use crate::{node::metrics::take_report_metrics, record_latency, runtime::TaskPriority, Provider};
+use crate::{
+    node::metrics::take_report_metrics,
+    record_latency,
+    runtime::{registry::group, TaskPriority},
+    Provider,
+};
+
+/// The soft limit of the size of a report request.
+const MAX_REPORT_SIZE: usize = 32 * 1024;
 
 #[derive(Clone)]
 pub struct StateChannel {
     sender: mpsc::UnboundedSender<GroupUpdates>,
 }
 
-pub(crate) fn setup(provider: &Provider) -> StateChannel {
+/// The kind of an update, only the latest update of each kind of a group is reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum UpdateKind {
+    GroupDesc,
+    ReplicaState(u64),
+    ScheduleState,
+}
+
+type UpdateVersion = ((u64 /* group */, UpdateKind), u64 /* version */);
+
+/// The updates which are not acked by root yet, the later update replaces the former one of the
+/// same kind, so a report only carries the deltas since the last ack.
+#[derive(Default)]
+struct PendingUpdates {
+    next_version: u64,
+    updates: BTreeMap<(u64, UpdateKind), (u64 /* version */, GroupUpdates)>,
+}
+
+pub(crate) fn setup(provider: &Provider, node_id: u64) -> StateChannel {
     let (sender, receiver) = mpsc::unbounded();
 
     let client = provider.root_client.clone();
-    provider
-        .executor
-        .spawn(None, TaskPriority::IoHigh, async move {
-            report_state_worker(receiver, client).await;
-        });
+    provider.executor.spawn_named(
+        group::NODE,
+        "report-state",
+        None,
+        TaskPriority::IoHigh,
+        async move {
+            report_state_worker(receiver, client, node_id).await;
+        },
+    );
 
     StateChannel::new(sender)
 }
 
+/// Report the state updates of all replicas of this node to root in batches. The updates are
+/// retransmitted until they are acked, and each report is sequenced in the session of this
+/// process, so root could skip the stale reports which are delayed.
 async fn report_state_worker(
     mut receiver: mpsc::UnboundedReceiver<GroupUpdates>,
     root_client: RootClient,
+    node_id: u64,
 ) {
-    while let Some(updates) = wait_state_updates(&mut receiver).await {
-        let req = ReportRequest { updates };
+    let session = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let mut sequence = 0;
+    let mut pending = PendingUpdates::default();
+    let mut interval = 1;
+    while wait_state_updates(&mut receiver, &mut pending).await {
+        sequence += 1;
+        let (updates, versions) = pending.take_batch(MAX_REPORT_SIZE);
+        let req = ReportRequest {
+            updates,
+            node_id,
+            session,
+            sequence,
+        };
         record_latency!(take_report_metrics());
-        report_state_updates(&root_client, req).await;
+        match root_client.report(&req).await {
+            Ok(_) => {
+                pending.ack(versions);
+                interval = 1;
+            }
+            Err(e) => {
+                warn!("report state updates: {e}");
+                crate::runtime::time::sleep(Duration::from_millis(interval)).await;
+                interval = std::cmp::min(interval * 2, 120);
+            }
+        }
     }
 }
 
-/// Wait until at least a new request is received or the channel is closed. Returns `None` if the
-/// channel is closed.
+/// Wait until there are pending updates, all received updates are merged into the pending
+/// updates. Returns false if the channel is closed.
 async fn wait_state_updates(
     receiver: &mut mpsc::UnboundedReceiver<GroupUpdates>,
-) -> Option<Vec<GroupUpdates>> {
-    use prost::Message;
-
+    pending: &mut PendingUpdates,
+) -> bool {
     // TODO(walter) skip root group?
-    if let Some(update) = receiver.next().await {
-        let mut size = update.encoded_len();
-        let mut updates = vec![update];
-        while size < 32 * 1024 {
-            match receiver.try_next() {
-                Ok(Some(update)) => {
-                    size += update.encoded_len();
-                    updates.push(update);
-                }
-                _ => break,
-            }
+    if pending.is_empty() {
+        match receiver.next().await {
+            Some(update) => pending.merge(update),
+            None => return false,
+        }
+    }
+    loop {
+        match receiver.try_next() {
+            Ok(Some(update)) => pending.merge(update),
+            Ok(None) => return false,
+            Err(_) => return true,
         }
-        return Some(updates);
     }
-    None
 }
 
-/// Issue report rpc request to root server.
-///
-/// This function is executed synchronously, and it will not affect normal reporting, because
-/// a node has only a small number of replicas, and the replica state changes are not frequent.
-/// Using a synchronous method can simplify the sequence problem introduced by asynchronous
-/// reporting.
-///
-/// If one day you find that reporting has become a bottleneck, you can consider optimizing this
-/// code.
-async fn report_state_updates(root_client: &RootClient, request: ReportRequest) {
-    let mut interval = 1;
-    while let Err(e) = root_client.report(&request).await {
-        warn!("report state updates: {e}");
-        crate::runtime::time::sleep(Duration::from_millis(interval)).await;
-        interval = std::cmp::min(interval * 2, 120);
+impl PendingUpdates {
+    fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    fn merge(&mut self, update: GroupUpdates) {
+        let group_id = update.group_id;
+        if let Some(group_desc) = update.group_desc {
+            let update = GroupUpdates {
+                group_id,
+                group_desc: Some(group_desc),
+                ..Default::default()
+            };
+            self.insert((group_id, UpdateKind::GroupDesc), update);
+        }
+        if let Some(replica_state) = update.replica_state {
+            let kind = UpdateKind::ReplicaState(replica_state.replica_id);
+            let update = GroupUpdates {
+                group_id,
+                replica_state: Some(replica_state),
+                ..Default::default()
+            };
+            self.insert((group_id, kind), update);
+        }
+        if let Some(schedule_state) = update.schedule_state {
+            let update = GroupUpdates {
+                group_id,
+                schedule_state: Some(schedule_state),
+                ..Default::default()
+            };
+            self.insert((group_id, UpdateKind::ScheduleState), update);
+        }
+    }
+
+    fn insert(&mut self, key: (u64, UpdateKind), update: GroupUpdates) {
+        self.next_version += 1;
+        self.updates.insert(key, (self.next_version, update));
+    }
+
+    /// Returns a batch of updates whose size is about `max_size`, and the versions of them to ack.
+    fn take_batch(&self, max_size: usize) -> (Vec<GroupUpdates>, Vec<UpdateVersion>) {
+        use prost::Message;
+
+        let mut size = 0;
+        let mut updates = vec![];
+        let mut versions = vec![];
+        for (key, (version, update)) in &self.updates {
+            if size >= max_size {
+                break;
+            }
+            size += update.encoded_len();
+            updates.push(update.clone());
+            versions.push((*key, *version));
+        }
+        (updates, versions)
+    }
+
+    /// Remove the acked updates, unless they are replaced by the newer updates.
+    fn ack(&mut self, versions: Vec<UpdateVersion>) {
+        for (key, version) in versions {
+            if matches!(self.updates.get(&key), Some((v, _)) if *v == version) {
+                self.updates.remove(&key);
+            }
+        }
     }
 }
 
@@ -130,3 +234,65 @@ impl StateChannel {
         self.sender.clone().start_send(update).unwrap_or_default();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replica_update(group_id: u64, replica_id: u64, term: u64) -> GroupUpdates {
+        GroupUpdates {
+            group_id,
+            replica_state: Some(ReplicaState {
+                group_id,
+                replica_id,
+                term,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_and_ack_pending_updates() {
+        let mut pending = PendingUpdates::default();
+        pending.merge(replica_update(1, 1, 1));
+        pending.merge(replica_update(1, 1, 2));
+        pending.merge(replica_update(1, 2, 1));
+        pending.merge(GroupUpdates {
+            group_id: 2,
+            group_desc: Some(GroupDesc::default()),
+            schedule_state: Some(ScheduleState::default()),
+            ..Default::default()
+        });
+
+        // The former state of replica 1 is replaced.
+        let (updates, versions) = pending.take_batch(MAX_REPORT_SIZE);
+        assert_eq!(updates.len(), 4);
+        assert_eq!(updates[0].replica_state.as_ref().unwrap().term, 2);
+        assert!(updates.iter().all(|u| {
+            u.group_desc.is_some() as u8
+                + u.replica_state.is_some() as u8
+                + u.schedule_state.is_some() as u8
+                == 1
+        }));
+
+        // The updates received after the batch is taken are not acked.
+        pending.merge(replica_update(1, 2, 3));
+        pending.ack(versions);
+        let (updates, _) = pending.take_batch(MAX_REPORT_SIZE);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].replica_state.as_ref().unwrap().term, 3);
+    }
+
+    #[test]
+    fn take_batch_with_size_limit() {
+        let mut pending = PendingUpdates::default();
+        for group_id in 0..100 {
+            pending.merge(replica_update(group_id, 1, 1));
+        }
+        let (updates, versions) = pending.take_batch(1);
+        assert_eq!(updates.len(), 1);
+        pending.ack(versions);
+        assert_eq!(pending.updates.len(), 99);
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io::Write,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, error, warn};
+
+use crate::{
+    node::{health::StorageHealth, NodeConfig},
+    runtime::{registry::group, TaskPriority},
+    Provider, Result,
+};
+
+const SCRUB_INTERVAL: Duration = Duration::from_secs(60);
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// The name of the file written to probe the latency of disks.
+const PROBE_FILE: &str = "DISK_PROBE";
+
+const PROBE_BYTES: usize = 4096;
+
+/// Probes the latency of the disks of node and verifies the checksums of a fraction of SST bytes
+/// periodically, the results are summarized by [`StorageHealth`] and reported by heartbeats.
+pub(crate) fn setup(cfg: &NodeConfig, provider: &Provider) {
+    let executor = provider.executor.clone();
+    let health = provider.storage_health.clone();
+    let mut scrubber = Scrubber {
+        raw_db: provider.raw_db.clone(),
+        ratio_per_day: cfg.scrub_ratio_per_day,
+        cursor: None,
+        credit: 0.0,
+    };
+    let dirs = vec![provider.db_path.clone(), provider.log_path.clone()];
+    provider.executor.spawn_named(
+        group::NODE,
+        "scrub",
+        None,
+        TaskPriority::IoLow,
+        async move {
+            let mounts = distinct_mounts(&dirs);
+            loop {
+                crate::runtime::time::sleep(SCRUB_INTERVAL).await;
+                let cloned_health = health.clone();
+                let cloned_mounts = mounts.clone();
+                let (returned, result) = executor
+                    .spawn_blocking(move || {
+                        for (mount, dir) in &cloned_mounts {
+                            match probe_disk(dir) {
+                                Ok(latency) => cloned_health.observe_latency(mount, latency),
+                                Err(err) => warn!("probe disk {}: {err:?}", dir.display()),
+                            }
+                        }
+                        let result = scrubber.scrub(&cloned_health);
+                        (scrubber, result)
+                    })
+                    .await;
+                scrubber = returned;
+                if let Err(err) = result {
+                    warn!("scrub sst files: {err:?}");
+                }
+            }
+        },
+    );
+}
+
+struct Scrubber {
+    raw_db: Arc<rocksdb::DB>,
+    ratio_per_day: f64,
+    /// The name of the last scrubbed file, the files are scrubbed in the order of names.
+    cursor: Option<String>,
+    /// The bytes allowed to scrub, it is accumulated every round.
+    credit: f64,
+}
+
+impl Scrubber {
+    fn scrub(&mut self, health: &StorageHealth) -> Result<()> {
+        if self.ratio_per_day <= 0.0 {
+            return Ok(());
+        }
+
+        let mut files = self.raw_db.live_files()?;
+        if files.is_empty() {
+            return Ok(());
+        }
+        files.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+        let total_bytes = files.iter().map(|f| f.size as f64).sum::<f64>();
+        self.credit +=
+            total_bytes * self.ratio_per_day * SCRUB_INTERVAL.as_secs_f64() / SECONDS_PER_DAY;
+        self.credit = self.credit.min(total_bytes);
+
+        let start = match &self.cursor {
+            Some(cursor) => files.partition_point(|f| &f.name <= cursor),
+            None => 0,
+        };
+        for idx in 0..files.len() {
+            let file = &files[(start + idx) % files.len()];
+            if self.credit < file.size as f64 {
+                break;
+            }
+            self.credit -= file.size as f64;
+            self.cursor = Some(file.name.clone());
+            self.scrub_file(health, file)?;
+        }
+        Ok(())
+    }
+
+    /// Read all keys in the range of the file with checksums verified, bypassing the block cache
+    /// so that the blocks are read from disk.
+    fn scrub_file(&self, health: &StorageHealth, file: &rocksdb::LiveFile) -> Result<()> {
+        let cf_handle = match self.raw_db.cf_handle(&file.column_family_name) {
+            Some(cf_handle) => cf_handle,
+            None => return Ok(()),
+        };
+
+        let mut opts = rocksdb::ReadOptions::default();
+        opts.set_verify_checksums(true);
+        opts.fill_cache(false);
+        let mut iter = self.raw_db.raw_iterator_cf_opt(&cf_handle, opts);
+        match &file.start_key {
+            Some(start_key) => iter.seek(start_key),
+            None => iter.seek_to_first(),
+        }
+        while let Some(key) = iter.key() {
+            if matches!(&file.end_key, Some(end_key) if key > end_key.as_slice()) {
+                break;
+            }
+            iter.next();
+        }
+
+        match iter.status() {
+            Ok(()) => {
+                debug!("scrub sst file {} with {} bytes", file.name, file.size);
+                health.on_scrubbed(file.size as u64);
+                Ok(())
+            }
+            Err(err) if err.to_string().starts_with("Corruption") => {
+                error!("scrub sst file {}: {err}", file.name);
+                health.on_checksum_error();
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Write and sync a small file to measure the latency of disk.
+fn probe_disk(dir: &Path) -> Result<Duration> {
+    let path = dir.join(PROBE_FILE);
+    let start = Instant::now();
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(&[0u8; PROBE_BYTES])?;
+    file.sync_all()?;
+    Ok(start.elapsed())
+}
+
+/// Returns the mount points of the dirs, and a dir on each of them.
+fn distinct_mounts(dirs: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+    let mut mounts: Vec<(PathBuf, PathBuf)> = vec![];
+    for dir in dirs {
+        match mount_point(dir) {
+            Ok(mount) => {
+                if !mounts.iter().any(|(m, _)| *m == mount) {
+                    mounts.push((mount, dir.clone()));
+                }
+            }
+            Err(err) => warn!("find mount point of {}: {err:?}", dir.display()),
+        }
+    }
+    mounts
+}
+
+/// The mount point is the topmost ancestor of the path on the same device.
+fn mount_point(path: &Path) -> Result<PathBuf> {
+    let path = path.canonicalize()?;
+    let dev = std::fs::metadata(&path)?.dev();
+    let mut mount = path.as_path();
+    while let Some(parent) = mount.parent() {
+        if std::fs::metadata(parent)?.dev() != dev {
+            break;
+        }
+        mount = parent;
+    }
+    Ok(mount.to_owned())
+}
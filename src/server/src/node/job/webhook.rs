@@ -0,0 +1,121 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{sync::Arc, time::Duration};
+
+use tracing::{debug, error, warn};
+
+use crate::{
+    node::{
+        metrics::*,
+        webhook::{QueuedEvent, WebhookQueue},
+    },
+    runtime::{registry::group, TaskPriority},
+    Provider,
+};
+
+const DELIVER_BATCH_SIZE: usize = 64;
+
+const DELIVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Deliver the events of [`WebhookQueue`] in the order of enqueuing, a failed delivery is retried
+/// with exponential backoff until it succeeds, so each event is delivered at least once.
+pub(crate) fn setup(provider: &Provider, queue: Arc<WebhookQueue>) {
+    provider.executor.spawn_named(
+        group::NODE,
+        "webhook",
+        None,
+        TaskPriority::IoLow,
+        async move {
+            let client = hyper::Client::new();
+            loop {
+                let pending = match queue.pending(DELIVER_BATCH_SIZE) {
+                    Ok(pending) => pending,
+                    Err(err) => {
+                        error!("read pending webhook events: {err:?}");
+                        crate::runtime::time::sleep(MAX_BACKOFF).await;
+                        continue;
+                    }
+                };
+                if pending.is_empty() {
+                    queue.wait().await;
+                    continue;
+                }
+
+                for (seq, event) in pending {
+                    let mut backoff = INITIAL_BACKOFF;
+                    loop {
+                        match deliver(&client, &event).await {
+                            Ok(()) => {
+                                NODE_WEBHOOK_DELIVERED_TOTAL.inc();
+                                break;
+                            }
+                            Err(DeliverError::InvalidUrl(err)) => {
+                                // Retrying never succeeds, the event is dropped.
+                                error!("drop webhook event {seq} with url {}: {err}", event.url);
+                                NODE_WEBHOOK_DROPPED_TOTAL.inc();
+                                break;
+                            }
+                            Err(DeliverError::Failed(err)) => {
+                                warn!("deliver webhook event {seq} to {}: {err}", event.url);
+                                NODE_WEBHOOK_FAILURES_TOTAL.inc();
+                                crate::runtime::time::sleep(backoff).await;
+                                backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+                            }
+                        }
+                    }
+                    if let Err(err) = queue.ack(seq) {
+                        error!("ack webhook event {seq}: {err:?}");
+                    }
+                }
+            }
+        },
+    );
+}
+
+enum DeliverError {
+    InvalidUrl(String),
+    Failed(String),
+}
+
+async fn deliver(
+    client: &hyper::Client<hyper::client::HttpConnector>,
+    event: &QueuedEvent,
+) -> Result<(), DeliverError> {
+    let uri = event
+        .url
+        .parse::<hyper::Uri>()
+        .map_err(|err| DeliverError::InvalidUrl(err.to_string()))?;
+    let body = serde_json::to_vec(&event.event).expect("WebhookEvent is serializable");
+    let req = hyper::Request::post(uri)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(body))
+        .map_err(|err| DeliverError::InvalidUrl(err.to_string()))?;
+    let resp = tokio::time::timeout(DELIVER_TIMEOUT, client.request(req))
+        .await
+        .map_err(|_| DeliverError::Failed("timeout".to_owned()))?
+        .map_err(|err| DeliverError::Failed(err.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(DeliverError::Failed(format!(
+            "response status {}",
+            resp.status()
+        )));
+    }
+    debug!("deliver webhook event to {}", event.url);
+    Ok(())
+}
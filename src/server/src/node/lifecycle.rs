@@ -0,0 +1,63 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::Serialize;
+
+/// The lifecycle of node, it is used to answer the readiness probes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum LifecycleState {
+    /// The node is recovering replicas or joining the cluster.
+    Bootstrapping = 0,
+    Serving = 1,
+    /// The node is transferring leaderships out before exiting.
+    ShuttingDown = 2,
+}
+
+#[derive(Default)]
+pub struct Lifecycle {
+    state: AtomicU8,
+}
+
+impl Lifecycle {
+    pub fn state(&self) -> LifecycleState {
+        match self.state.load(Ordering::Acquire) {
+            0 => LifecycleState::Bootstrapping,
+            1 => LifecycleState::Serving,
+            _ => LifecycleState::ShuttingDown,
+        }
+    }
+
+    /// Advance to the state, the lifecycle never goes backward.
+    pub fn advance(&self, state: LifecycleState) {
+        self.state.fetch_max(state as u8, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lifecycle_never_goes_backward() {
+        let lifecycle = Lifecycle::default();
+        assert_eq!(lifecycle.state(), LifecycleState::Bootstrapping);
+        lifecycle.advance(LifecycleState::ShuttingDown);
+        lifecycle.advance(LifecycleState::Serving);
+        assert_eq!(lifecycle.state(), LifecycleState::ShuttingDown);
+    }
+}
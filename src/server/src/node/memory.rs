@@ -0,0 +1,215 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use prometheus::IntGauge;
+
+use super::metrics::*;
+
+/// The subsystems whose memory usage is accounted by [`MemoryTracker`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    Memtable = 0,
+    BlockCache,
+    RaftEntryCache,
+    MigrationBuffer,
+    ConnectionBuffer,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 5] = [
+        Subsystem::Memtable,
+        Subsystem::BlockCache,
+        Subsystem::RaftEntryCache,
+        Subsystem::MigrationBuffer,
+        Subsystem::ConnectionBuffer,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Subsystem::Memtable => "memtable",
+            Subsystem::BlockCache => "block_cache",
+            Subsystem::RaftEntryCache => "raft_entry_cache",
+            Subsystem::MigrationBuffer => "migration_buffer",
+            Subsystem::ConnectionBuffer => "connection_buffer",
+        }
+    }
+}
+
+/// A subsystem which could release memory on demand, when the global budget is exceeded.
+pub trait Reclaimer: Send + Sync {
+    /// Try to release `bytes` memory, returns the bytes actually released.
+    fn reclaim(&self, bytes: usize) -> usize;
+}
+
+/// Accounts the memory usage of each subsystem of a node, against a global budget.
+///
+/// The usages of memtables and block cache are sampled from the engine periodically, the others
+/// are reported by the subsystems themselves, see [`MemoryTracker::track`].
+pub struct MemoryTracker {
+    /// The budget of the total memory usage, 0 means unlimited.
+    budget: usize,
+    usages: [AtomicUsize; Subsystem::ALL.len()],
+    gauges: [IntGauge; Subsystem::ALL.len()],
+    reclaimers: Mutex<Vec<Arc<dyn Reclaimer>>>,
+}
+
+/// Releases the tracked memory once dropped.
+pub struct MemoryGuard {
+    tracker: Arc<MemoryTracker>,
+    subsystem: Subsystem,
+    bytes: usize,
+}
+
+impl MemoryTracker {
+    pub fn new(budget: usize) -> Self {
+        NODE_MEMORY_BUDGET_BYTES.set(budget as i64);
+        MemoryTracker {
+            budget,
+            usages: Default::default(),
+            gauges: Subsystem::ALL
+                .map(|s| NODE_MEMORY_USAGE_BYTES_VEC.with_label_values(&[s.as_str()])),
+            reclaimers: Mutex::default(),
+        }
+    }
+
+    #[inline]
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    #[inline]
+    pub fn usage(&self, subsystem: Subsystem) -> usize {
+        self.usages[subsystem as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn total_usage(&self) -> usize {
+        Subsystem::ALL.iter().map(|s| self.usage(*s)).sum()
+    }
+
+    /// Returns the bytes of the total usage exceeds the budget.
+    pub fn excess(&self) -> usize {
+        if self.budget == 0 {
+            return 0;
+        }
+        self.total_usage().saturating_sub(self.budget)
+    }
+
+    pub fn consume(&self, subsystem: Subsystem, bytes: usize) {
+        let idx = subsystem as usize;
+        let usage = self.usages[idx].fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.gauges[idx].set(usage as i64);
+    }
+
+    pub fn release(&self, subsystem: Subsystem, bytes: usize) {
+        let idx = subsystem as usize;
+        let usage = self.usages[idx].fetch_sub(bytes, Ordering::Relaxed) - bytes;
+        self.gauges[idx].set(usage as i64);
+    }
+
+    /// Replace the usage of a sampled subsystem.
+    pub fn set(&self, subsystem: Subsystem, bytes: usize) {
+        let idx = subsystem as usize;
+        self.usages[idx].store(bytes, Ordering::Relaxed);
+        self.gauges[idx].set(bytes as i64);
+    }
+
+    /// Account `bytes` to the subsystem until the returned guard is dropped.
+    pub fn track(self: &Arc<Self>, subsystem: Subsystem, bytes: usize) -> MemoryGuard {
+        self.consume(subsystem, bytes);
+        MemoryGuard {
+            tracker: self.clone(),
+            subsystem,
+            bytes,
+        }
+    }
+
+    pub fn register_reclaimer(&self, reclaimer: Arc<dyn Reclaimer>) {
+        self.reclaimers.lock().unwrap().push(reclaimer);
+    }
+
+    /// Ask the registered reclaimers to release `bytes` memory, returns the bytes released.
+    pub fn reclaim(&self, bytes: usize) -> usize {
+        let reclaimers = self.reclaimers.lock().unwrap().clone();
+        let mut released = 0;
+        for reclaimer in reclaimers {
+            if released >= bytes {
+                break;
+            }
+            released += reclaimer.reclaim(bytes - released);
+        }
+        NODE_MEMORY_RECLAIMED_BYTES_TOTAL.inc_by(released as u64);
+        released
+    }
+}
+
+impl Drop for MemoryGuard {
+    fn drop(&mut self) {
+        self.tracker.release(self.subsystem, self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedReclaimer {
+        tracker: Arc<MemoryTracker>,
+    }
+
+    impl Reclaimer for FixedReclaimer {
+        fn reclaim(&self, bytes: usize) -> usize {
+            let bytes = std::cmp::min(bytes, self.tracker.usage(Subsystem::RaftEntryCache));
+            self.tracker.release(Subsystem::RaftEntryCache, bytes);
+            bytes
+        }
+    }
+
+    #[test]
+    fn track_and_release() {
+        let tracker = Arc::new(MemoryTracker::new(0));
+        {
+            let _guard = tracker.track(Subsystem::MigrationBuffer, 100);
+            tracker.consume(Subsystem::ConnectionBuffer, 20);
+            assert_eq!(tracker.usage(Subsystem::MigrationBuffer), 100);
+            assert_eq!(tracker.total_usage(), 120);
+        }
+        assert_eq!(tracker.usage(Subsystem::MigrationBuffer), 0);
+        tracker.release(Subsystem::ConnectionBuffer, 20);
+        assert_eq!(tracker.total_usage(), 0);
+
+        // No budget.
+        tracker.set(Subsystem::Memtable, usize::MAX / 2);
+        assert_eq!(tracker.excess(), 0);
+    }
+
+    #[test]
+    fn reclaim_exceeded_budget() {
+        let tracker = Arc::new(MemoryTracker::new(1000));
+        tracker.set(Subsystem::Memtable, 800);
+        tracker.consume(Subsystem::RaftEntryCache, 300);
+        assert_eq!(tracker.excess(), 100);
+
+        tracker.register_reclaimer(Arc::new(FixedReclaimer {
+            tracker: tracker.clone(),
+        }));
+        assert_eq!(tracker.reclaim(tracker.excess()), 100);
+        assert_eq!(tracker.excess(), 0);
+        assert_eq!(tracker.usage(Subsystem::RaftEntryCache), 200);
+    }
+}
@@ -18,6 +18,12 @@ use prometheus::*;
 lazy_static! {
     pub static ref NODE_RETRY_TOTAL: IntCounter =
         register_int_counter!("node_retry_total", "The total retries of node",).unwrap();
+    pub static ref NODE_REPLICA_RETRY_TOTAL_VEC: IntCounterVec = register_int_counter_vec!(
+        "node_replica_retry_total",
+        "The total retries of each replica, labeled by the kind of error",
+        &["group", "type"]
+    )
+    .unwrap();
     pub static ref NODE_DESTORY_REPLICA_TOTAL: IntCounter = register_int_counter!(
         "node_destory_replica_total",
         "The total destory replica of node"
@@ -45,6 +51,98 @@ lazy_static! {
         exponential_buckets(0.00005, 1.8, 26).unwrap(),
     )
     .unwrap();
+    pub static ref NODE_BLOB_PUNCHED_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "node_blob_punched_bytes_total",
+        "The total bytes of the dead values punched out of blob files"
+    )
+    .unwrap();
+    pub static ref NODE_ENGINE_PROPERTY_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "node_engine_property",
+        "The sum of RocksDB properties of all group engines in node",
+        &["name"]
+    )
+    .unwrap();
+    pub static ref NODE_MEMORY_USAGE_BYTES_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "node_memory_usage_bytes",
+        "The memory usage of each subsystem in node",
+        &["subsystem"]
+    )
+    .unwrap();
+    pub static ref NODE_MEMORY_BUDGET_BYTES: IntGauge =
+        register_int_gauge!("node_memory_budget_bytes", "The memory budget of node").unwrap();
+    pub static ref NODE_MEMORY_BUDGET_EXCEEDED_TOTAL: IntCounter = register_int_counter!(
+        "node_memory_budget_exceeded_total",
+        "The total times the memory budget of node is exceeded"
+    )
+    .unwrap();
+    pub static ref NODE_MEMORY_RECLAIMED_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "node_memory_reclaimed_bytes_total",
+        "The total bytes released by the reclaimers of node"
+    )
+    .unwrap();
+    pub static ref NODE_SCRUB_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "node_scrub_bytes_total",
+        "The total bytes of SST files verified by the scrubber of node"
+    )
+    .unwrap();
+    pub static ref NODE_SCRUB_CHECKSUM_ERRORS_TOTAL: IntCounter = register_int_counter!(
+        "node_scrub_checksum_errors_total",
+        "The total checksum mismatches detected by the scrubber of node"
+    )
+    .unwrap();
+    pub static ref NODE_REAPER_QUARANTINED_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "node_reaper_quarantined_bytes_total",
+        "The total bytes of orphaned files moved into the quarantine dir by the reaper of node"
+    )
+    .unwrap();
+    pub static ref NODE_REAPER_RECLAIMED_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "node_reaper_reclaimed_bytes_total",
+        "The total bytes of quarantined files deleted by the reaper of node"
+    )
+    .unwrap();
+    pub static ref NODE_WEBHOOK_DELIVERED_TOTAL: IntCounter = register_int_counter!(
+        "node_webhook_delivered_total",
+        "The total webhook events delivered by node"
+    )
+    .unwrap();
+    pub static ref NODE_WEBHOOK_FAILURES_TOTAL: IntCounter = register_int_counter!(
+        "node_webhook_failures_total",
+        "The total failed deliveries of webhook events, which are retried later"
+    )
+    .unwrap();
+    pub static ref NODE_WEBHOOK_DROPPED_TOTAL: IntCounter = register_int_counter!(
+        "node_webhook_dropped_total",
+        "The total webhook events dropped since the url is invalid"
+    )
+    .unwrap();
+    pub static ref NODE_DISK_PROBE_DURATION_SECONDS_VEC: HistogramVec = register_histogram_vec!(
+        "node_disk_probe_duration_seconds",
+        "The intervals of probing the disks of node",
+        &["mount"],
+        exponential_buckets(0.00005, 1.8, 26).unwrap(),
+    )
+    .unwrap();
+    pub static ref NODE_STORAGE_HEALTH_SCORE: Gauge = register_gauge!(
+        "node_storage_health_score",
+        "The health score of the storage of node, 1 means healthy"
+    )
+    .unwrap();
+}
+
+pub fn take_retry_metrics(group_id: u64, kind: &str) {
+    NODE_RETRY_TOTAL.inc();
+    NODE_REPLICA_RETRY_TOTAL_VEC
+        .with_label_values(&[&group_id.to_string(), kind])
+        .inc();
+}
+
+pub fn remove_retry_metrics(group_id: u64) {
+    let group = group_id.to_string();
+    for kind in ["service_busy", "group_not_ready", "epoch_not_match"] {
+        NODE_REPLICA_RETRY_TOTAL_VEC
+            .remove_label_values(&[&group, kind])
+            .unwrap_or_default();
+    }
 }
 
 pub fn take_destory_replica_metrics() -> &'static Histogram {
@@ -20,7 +20,10 @@ use futures::{channel::mpsc, StreamExt};
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    node::Replica, runtime::sync::WaitGroup, serverpb::v1::*, NodeConfig, Provider, Result,
+    node::{memory::MemoryTracker, Replica},
+    runtime::sync::WaitGroup,
+    serverpb::v1::*,
+    NodeConfig, Provider, Result,
 };
 
 #[derive(Debug)]
@@ -31,8 +34,6 @@ pub struct ForwardCtx {
 }
 
 struct MigrationCoordinator {
-    cfg: NodeConfig,
-
     replica_id: u64,
     group_id: u64,
 
@@ -40,6 +41,8 @@ struct MigrationCoordinator {
 
     client: MigrateClient,
     desc: MigrationDesc,
+
+    memory_tracker: Arc<MemoryTracker>,
 }
 
 #[derive(Clone)]
@@ -48,14 +51,13 @@ pub struct MigrateController {
 }
 
 struct MigrateControllerShared {
-    cfg: NodeConfig,
     provider: Arc<Provider>,
 }
 
 impl MigrateController {
-    pub(crate) fn new(cfg: NodeConfig, provider: Arc<Provider>) -> Self {
+    pub(crate) fn new(provider: Arc<Provider>) -> Self {
         MigrateController {
-            shared: Arc::new(MigrateControllerShared { cfg, provider }),
+            shared: Arc::new(MigrateControllerShared { provider }),
         }
     }
 
@@ -97,12 +99,12 @@ impl MigrateController {
                         ctrl.shared.provider.conn_manager.clone(),
                     );
                     coord = Some(MigrationCoordinator {
-                        cfg: ctrl.shared.cfg.clone(),
                         replica_id,
                         group_id,
                         replica: replica.clone(),
                         client,
                         desc: desc.clone(),
+                        memory_tracker: ctrl.shared.provider.memory_tracker.clone(),
                     });
                 }
                 coord.as_mut().unwrap().next_step(state).await;
@@ -141,14 +143,18 @@ impl MigrateController {
         F: Future<Output = T> + Send + 'static,
         T: Send + 'static,
     {
-        use crate::runtime::TaskPriority;
+        use crate::runtime::{registry::group, TaskPriority};
 
         let tag_owner = group_id.to_le_bytes();
         let tag = Some(tag_owner.as_slice());
-        self.shared
-            .provider
-            .executor
-            .spawn(tag, TaskPriority::IoHigh, future);
+        let name = format!("migration-{group_id}");
+        self.shared.provider.executor.spawn_named(
+            group::NODE,
+            name,
+            tag,
+            TaskPriority::IoHigh,
+            future,
+        );
     }
 }
 
@@ -289,24 +295,8 @@ impl MigrationCoordinator {
     }
 
     async fn clean_orphan_shard(&self) {
-        use super::gc::remove_shard;
-
-        let group_engine = self.replica.group_engine();
-        if let Err(e) = remove_shard(
-            &self.cfg,
-            self.replica.as_ref(),
-            group_engine,
-            self.desc.get_shard_id(),
-        )
-        .await
-        {
-            error!(replica = self.replica_id,
-                group = self.group_id,
-                desc = %self.desc,
-                "remove migrated shard from source group: {}", e);
-            return;
-        }
-
+        // The column family of the migrated shard is dropped by each replica once the migration
+        // is finished, see `GroupEngine::group_commit`.
         self.clean_migration_state().await;
     }
 
@@ -316,6 +306,7 @@ impl MigrationCoordinator {
             self.replica.as_ref(),
             &self.desc,
             last_migrated_key,
+            &self.memory_tracker,
         )
         .await
         {
@@ -13,7 +13,6 @@
 // limitations under the License.
 
 mod ctrl;
-mod gc;
 mod pull;
 
 pub(crate) use self::{
@@ -23,7 +23,11 @@ use engula_client::MigrateClient;
 use futures::StreamExt;
 
 use crate::{
-    node::{metrics::take_pull_shard_metrics, Replica},
+    node::{
+        memory::{MemoryTracker, Subsystem},
+        metrics::take_pull_shard_metrics,
+        Replica,
+    },
     record_latency, Result,
 };
 
@@ -32,12 +36,16 @@ pub async fn pull_shard(
     replica: &Replica,
     desc: &MigrationDesc,
     last_migrated_key: Vec<u8>,
+    memory_tracker: &Arc<MemoryTracker>,
 ) -> Result<()> {
+    use prost::Message;
+
     record_latency!(take_pull_shard_metrics());
     let shard_id = desc.get_shard_id();
     let mut streaming = client.retryable_pull(shard_id, last_migrated_key).await?;
     while let Some(shard_chunk) = streaming.next().await {
         let shard_chunk = shard_chunk?;
+        let _guard = memory_tracker.track(Subsystem::MigrationBuffer, shard_chunk.encoded_len());
         replica.ingest(shard_id, shard_chunk, false).await?;
     }
     Ok(())
@@ -13,17 +13,22 @@
 // limitations under the License.
 
 pub mod engine;
+pub mod health;
 mod job;
+pub mod lifecycle;
+pub mod memory;
 mod metrics;
 pub mod migrate;
 pub mod replica;
 pub mod resolver;
 pub mod route_table;
+pub mod watch;
+mod webhook;
 
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use engula_api::server::v1::*;
@@ -32,10 +37,14 @@ use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
 
 use self::{
-    engine::EngineConfig,
+    engine::{ApplyPool, EngineConfig},
     job::StateChannel,
+    lifecycle::{Lifecycle, LifecycleState},
+    memory::MemoryTracker,
     migrate::{MigrateController, ShardChunkStream},
     replica::ReplicaConfig,
+    watch::KeyWatchStream,
+    webhook::WebhookQueue,
 };
 pub use self::{
     engine::{GroupEngine, StateEngine},
@@ -44,6 +53,7 @@ pub use self::{
 };
 use crate::{
     bootstrap::ROOT_GROUP_ID,
+    feature::{supported_features, Feature},
     node::replica::{fsm::GroupStateMachine, ExecCtx, LeaseState, LeaseStateObserver, ReplicaInfo},
     raftgroup::{snap::RecycleSnapMode, RaftManager, RaftNodeFacade, TransportManager},
     runtime::{sync::WaitGroup, Executor},
@@ -59,10 +69,40 @@ pub struct NodeConfig {
     /// Default: 64KB.
     pub shard_chunk_size: usize,
 
-    /// The limit number of keys for gc shard after migration.
+    /// The requests sent by this node whose encoded size exceed this threshold, and the
+    /// responses to the peers accept it, are compressed with gzip. 0 means disabled.
     ///
-    /// Default: 256.
-    pub shard_gc_keys: usize,
+    /// Default: 0.
+    #[serde(default)]
+    pub compress_threshold: usize,
+
+    /// The zone of this node, the collections could prefer leaders in a zone.
+    ///
+    /// Default: "".
+    #[serde(default)]
+    pub zone: String,
+
+    /// The budget of the memory used by memtables, block cache, raft entry cache, migration and
+    /// connection buffers. Once it is exceeded, the caches are evicted and the memtables are
+    /// flushed. 0 means unlimited.
+    ///
+    /// Default: 0.
+    #[serde(default)]
+    pub memory_budget: usize,
+
+    /// The fraction of SST bytes whose checksums are verified by the background scrubber per
+    /// day, eg 0.1 means all files are verified every ten days. 0 means disabled.
+    ///
+    /// Default: 0.
+    #[serde(default)]
+    pub scrub_ratio_per_day: f64,
+
+    /// The orphaned files of the data directory are moved into the quarantine dir, and deleted
+    /// after they have been quarantined for this many seconds. 0 means the reaper is disabled.
+    ///
+    /// Default: 0.
+    #[serde(default)]
+    pub reaper_retention_sec: u64,
 
     #[serde(default)]
     pub replica: ReplicaConfig,
@@ -113,6 +153,14 @@ where
 
     /// A lock is used to ensure serialization of create/terminate replica operations.
     replica_mutation: Arc<Mutex<()>>,
+
+    lifecycle: Arc<Lifecycle>,
+
+    /// The workers to apply the committed writes of groups in parallel, shared by all replicas.
+    apply_pool: Option<Arc<ApplyPool>>,
+
+    /// The events to notify the webhooks of collections.
+    webhooks: Arc<WebhookQueue>,
 }
 
 impl Node {
@@ -128,8 +176,17 @@ impl Node {
             &provider.log_path,
             provider.executor.clone(),
             trans_mgr,
+            provider.memory_tracker.clone(),
+            provider.feature_gates.clone(),
         )?;
-        let migrate_ctrl = MigrateController::new(cfg.node.clone(), provider.clone());
+        let migrate_ctrl = MigrateController::new(provider.clone());
+        let apply_pool = cfg
+            .node
+            .engine
+            .engine_apply_workers
+            .filter(|&workers| workers > 1)
+            .map(|workers| Arc::new(ApplyPool::new(workers)));
+        let webhooks = Arc::new(WebhookQueue::open(provider.raw_db.clone())?);
         Ok(Node {
             cfg: cfg.node,
             provider,
@@ -139,6 +196,9 @@ impl Node {
             migrate_ctrl,
             node_state: Arc::new(Mutex::new(NodeState::default())),
             replica_mutation: Arc::default(),
+            lifecycle: Arc::default(),
+            apply_pool,
+            webhooks,
         })
     }
 
@@ -153,7 +213,18 @@ impl Node {
         );
 
         node_state.ident = Some(node_ident.to_owned());
-        node_state.channel = Some(setup_report_state(self.provider.as_ref()));
+        node_state.channel = Some(setup_report_state(
+            self.provider.as_ref(),
+            node_ident.node_id,
+        ));
+        setup_memory_budget(self.provider.as_ref());
+        setup_scrub(&self.cfg, self.provider.as_ref());
+        setup_reaper(&self.cfg, self.provider.as_ref());
+        setup_webhook(self.provider.as_ref(), self.webhooks.clone());
+
+        if let Some(features) = self.provider.state_engine.load_cluster_features().await? {
+            self.provider.feature_gates.activate(&features.active);
+        }
 
         let node_id = node_ident.node_id;
         let it = self.provider.state_engine.iterate_replica_states().await;
@@ -194,6 +265,12 @@ impl Node {
             node_state.serving_groups.insert(group_id);
         }
 
+        setup_blob_gc(
+            &self.cfg.engine,
+            self.provider.as_ref(),
+            self.replica_route_table.clone(),
+        );
+
         Ok(())
     }
 
@@ -214,6 +291,20 @@ impl Node {
         if self.check_replica_existence(group_id, replica_id).await? {
             return Ok(());
         }
+        if let Some(tombstone) = self
+            .provider
+            .state_engine
+            .load_replica_tombstone(replica_id)
+            .await?
+        {
+            warn!(
+                "group {group_id} create replica {replica_id}: replica is destroyed at term {}",
+                tombstone.term
+            );
+            return Err(Error::InvalidArgument(format!(
+                "replica {replica_id} of group {group_id} is destroyed"
+            )));
+        }
 
         // To ensure crash-recovery consistency, first create raft metadata, and then save replica
         // state. In this way, even if the node is restarted before the group is
@@ -283,6 +374,7 @@ impl Node {
             }
         };
 
+        let term = replica.replica_state().term;
         replica.shutdown(actual_desc).await?;
         self.replica_route_table.remove(group_id);
         self.raft_route_table.delete(replica_id);
@@ -299,10 +391,17 @@ impl Node {
 
         wait_group.wait().await;
 
-        // This replica is shutdowned, we need to update and persisted states.
+        // This replica is shutdowned, we need to update and persisted states. The tombstone
+        // prevents the replica from being recreated by delayed messages or stale snapshots.
+        let tombstone = ReplicaTombstone {
+            group_id,
+            replica_id,
+            term,
+            removed_at: unix_secs(),
+        };
         self.provider
             .state_engine
-            .save_replica_state(group_id, replica_id, ReplicaLocalState::Terminated)
+            .terminate_replica(&tombstone)
             .await?;
 
         self.raft_mgr
@@ -322,6 +421,29 @@ impl Node {
         Ok(())
     }
 
+    /// Purge the tombstones of the destroyed replicas which are removed longer than
+    /// `safety_window` ago, after that no message or snapshot of them is expected. Returns the
+    /// purged tombstones.
+    pub async fn purge_replica_tombstones(
+        &self,
+        safety_window: Duration,
+    ) -> Result<Vec<ReplicaTombstone>> {
+        let _mut_guard = self.replica_mutation.lock().await;
+        let removed_before = unix_secs().saturating_sub(safety_window.as_secs());
+        let purged = self
+            .provider
+            .state_engine
+            .purge_replica_tombstones(removed_before)
+            .await?;
+        for tombstone in &purged {
+            info!(
+                "group {} purge tombstone of replica {}, removed at {}",
+                tombstone.group_id, tombstone.replica_id, tombstone.removed_at
+            );
+        }
+        Ok(purged)
+    }
+
     /// Open, recover replica and start serving.
     async fn serve_replica(
         &self,
@@ -339,7 +461,8 @@ impl Node {
             desc.id,
             local_state,
         )
-        .await?;
+        .await?
+        .with_apply_pool(self.apply_pool.clone());
         let wait_group = WaitGroup::new();
         let (sender, receiver) = mpsc::unbounded();
 
@@ -373,6 +496,7 @@ impl Node {
             raft_node.clone(),
             group_engine,
             move_replicas_provider.clone(),
+            self.cfg.replica.clone(),
         );
         let replica = Arc::new(replica);
         self.replica_route_table.update(replica.clone());
@@ -423,6 +547,21 @@ impl Node {
         }
     }
 
+    /// Activate and persist the cluster features synced from root.
+    pub async fn update_features(&self, features: ClusterFeatures) -> Result<()> {
+        let gates = &self.provider.feature_gates;
+        let all_active = features
+            .active
+            .iter()
+            .filter_map(|name| Feature::from_name(name))
+            .all(|f| gates.is_active(f));
+        if !all_active {
+            self.state_engine().save_cluster_features(&features).await?;
+            gates.activate(&features.active);
+        }
+        Ok(())
+    }
+
     pub async fn reload_root_from_engine(&self) -> Result<()> {
         let root_desc = self
             .state_engine()
@@ -433,7 +572,11 @@ impl Node {
         Ok(())
     }
 
-    pub async fn execute_request(&self, request: &GroupRequest) -> Result<GroupResponse> {
+    pub async fn execute_request(
+        &self,
+        request: &GroupRequest,
+        deadline: Option<Instant>,
+    ) -> Result<GroupResponse> {
         use self::replica::retry::forwardable_execute;
 
         let replica = match self.replica_route_table.find(request.group_id) {
@@ -443,7 +586,25 @@ impl Node {
             }
         };
 
-        forwardable_execute(&self.migrate_ctrl, &replica, &ExecCtx::default(), request).await
+        let feature = request
+            .request
+            .as_ref()
+            .and_then(|r| r.request.as_ref())
+            .and_then(Feature::required_by);
+        if let Some(feature) = feature {
+            self.provider.feature_gates.check(feature)?;
+        }
+
+        if let Some(req) = request.request.as_ref().and_then(|r| r.request.as_ref()) {
+            let replica_state = replica.replica_state();
+            if webhook::is_write_request(req) && replica_state.role == RaftRole::Leader as i32 {
+                self.webhooks
+                    .enqueue_writes(&self.provider.router, &replica.descriptor(), req)?;
+            }
+        }
+
+        let exec_ctx = ExecCtx::with_deadline(deadline);
+        forwardable_execute(&self.migrate_ctrl, &replica, &exec_ctx, request).await
     }
 
     pub async fn pull_shard_chunks(&self, request: PullRequest) -> Result<ShardChunkStream> {
@@ -486,6 +647,7 @@ impl Node {
         let group_request = GroupRequest {
             group_id: request.group_id,
             epoch: 0,
+            stale_read: false,
             request: request.request,
         };
 
@@ -497,6 +659,36 @@ impl Node {
         })
     }
 
+    /// Watch the changes of the key, or the keys with the prefix, from the leader replica of the
+    /// group.
+    pub fn watch_key(&self, request: WatchKeyRequest) -> Result<KeyWatchStream> {
+        let replica = match self.replica_route_table.find(request.group_id) {
+            Some(replica) => replica,
+            None => {
+                return Err(Error::GroupNotFound(request.group_id));
+            }
+        };
+        KeyWatchStream::new(replica, request)
+    }
+
+    /// Returns the shard checksum computed by the local replica of the group.
+    pub fn collect_checksum(
+        &self,
+        request: &CollectChecksumRequest,
+    ) -> Result<CollectChecksumResponse> {
+        let replica = match self.replica_route_table.find(request.group_id) {
+            Some(replica) => replica,
+            None => {
+                return Err(Error::GroupNotFound(request.group_id));
+            }
+        };
+
+        let checksum = replica
+            .shard_checksum(request.shard_id)
+            .filter(|c| c.checksum_id == request.checksum_id);
+        Ok(CollectChecksumResponse { checksum })
+    }
+
     // This request is issued by dest group.
     pub async fn migrate(&self, request: MigrateRequest) -> Result<MigrateResponse> {
         let desc = request
@@ -567,6 +759,87 @@ impl Node {
         &self.raft_mgr
     }
 
+    #[inline]
+    pub fn lifecycle(&self) -> &Lifecycle {
+        &self.lifecycle
+    }
+
+    /// Transfer the leaderships of all replicas of this node to the other voters, it returns once
+    /// no leader left or the `timeout` is exceeded.
+    pub async fn transfer_leaders_out(&self, timeout: Duration) {
+        self.lifecycle.advance(LifecycleState::ShuttingDown);
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut num_leaders = 0;
+            for replica in self.replica_route_table.replicas() {
+                if replica.replica_info().is_terminated()
+                    || replica.replica_state().role != RaftRole::Leader as i32
+                {
+                    continue;
+                }
+                let replica_id = replica.replica_info().replica_id;
+                let transferee = replica
+                    .descriptor()
+                    .replicas
+                    .iter()
+                    .find(|r| r.id != replica_id && r.role == ReplicaRole::Voter as i32)
+                    .map(|r| r.id);
+                let Some(transferee) = transferee else {
+                    continue;
+                };
+                num_leaders += 1;
+                if let Err(err) = replica.transfer_leader(transferee).await {
+                    warn!(
+                        "group {} transfer leadership to {transferee}: {err}",
+                        replica.replica_info().group_id
+                    );
+                }
+            }
+
+            if num_leaders == 0 {
+                info!("all leaderships are transferred out");
+                return;
+            }
+            if Instant::now() >= deadline {
+                warn!("transfer leaderships out timeout, {num_leaders} leaders left");
+                return;
+            }
+            crate::runtime::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    #[inline]
+    pub fn memory_tracker(&self) -> &Arc<MemoryTracker> {
+        &self.provider.memory_tracker
+    }
+
+    /// Refresh the metrics of the [`engine::ENGINE_PROPERTIES`], the values of all group engines
+    /// in this node are summed.
+    pub fn refresh_engine_metrics(&self) {
+        let mut sums: HashMap<&'static str, u64> = HashMap::default();
+        for replica in self.replica_route_table.replicas() {
+            match replica.group_engine().properties() {
+                Ok(props) => {
+                    for (name, value) in props {
+                        *sums.entry(name).or_default() += value;
+                    }
+                }
+                Err(err) => {
+                    warn!(
+                        "group {} read engine properties: {err:?}",
+                        replica.replica_info().group_id
+                    );
+                }
+            }
+        }
+        for (name, value) in sums {
+            metrics::NODE_ENGINE_PROPERTY_VEC
+                .with_label_values(&[name])
+                .set(value as i64);
+        }
+    }
+
     pub async fn collect_stats(&self, _req: &CollectStatsRequest) -> CollectStatsResponse {
         // TODO(walter) add read/write qps.
         let mut ns = NodeStats::default();
@@ -598,6 +871,7 @@ impl Node {
                         shard_count: descriptor.shards.len() as u64,
                         read_qps: 0.,
                         write_qps: 0.,
+                        shard_sizes: replica.group_engine().approximate_shard_sizes(),
                     };
                     group_stats.push(gs);
                 }
@@ -611,6 +885,19 @@ impl Node {
             }
         }
 
+        ns.storage_health = Some(self.provider.storage_health.stats());
+        ns.features = supported_features();
+        match health::disk_space(&self.provider.db_path) {
+            Ok((total, available)) => {
+                ns.total_space = total;
+                ns.available_space = available;
+            }
+            Err(err) => warn!(
+                "collect disk space of {}: {err:?}",
+                self.provider.db_path.display()
+            ),
+        }
+
         CollectStatsResponse {
             node_stats: Some(ns),
             group_stats,
@@ -721,7 +1008,11 @@ impl Default for NodeConfig {
     fn default() -> Self {
         NodeConfig {
             shard_chunk_size: 64 * 1024 * 1024,
-            shard_gc_keys: 256,
+            compress_threshold: 0,
+            zone: String::default(),
+            memory_budget: 0,
+            scrub_ratio_per_day: 0.0,
+            reaper_retention_sec: 0,
             replica: ReplicaConfig::default(),
             engine: EngineConfig::default(),
         }
@@ -764,6 +1055,7 @@ async fn start_raft_group(
     let fsm = GroupStateMachine::new(
         cfg.replica.clone(),
         info.clone(),
+        raft_mgr.executor().clone(),
         group_engine.clone(),
         state_observer.clone(),
     );
@@ -779,6 +1071,13 @@ async fn start_raft_group(
         .await
 }
 
+fn unix_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 #[cfg(test)]
 mod tests {
     use std::{path::PathBuf, time::Duration};
@@ -955,6 +1254,56 @@ mod tests {
         });
     }
 
+    /// A removed replica couldn't be recreated until its tombstone is purged.
+    #[test]
+    fn recreate_removed_replica_rejected_by_tombstone() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+
+        let tmp_dir = TempDir::new("recreate_removed_replica_rejected_by_tombstone").unwrap();
+        executor_owner.executor().block_on(async {
+            let node = create_node(tmp_dir.path().to_owned(), executor.clone()).await;
+
+            let group_id = 2;
+            let replica_id = 2;
+            let group = GroupDesc {
+                id: group_id,
+                epoch: INITIAL_EPOCH,
+                shards: vec![],
+                replicas: vec![],
+            };
+            node.create_replica(replica_id, group.clone())
+                .await
+                .unwrap();
+            let ident = NodeIdent {
+                cluster_id: vec![],
+                node_id: 1,
+            };
+            node.bootstrap(&ident).await.unwrap();
+
+            crate::runtime::time::sleep(Duration::from_millis(10)).await;
+
+            node.remove_replica(replica_id, &group).await.unwrap();
+            let result = node.create_replica(replica_id, group.clone()).await;
+            assert!(
+                matches!(result, Err(Error::InvalidArgument(msg)) if msg.contains("destroyed"))
+            );
+
+            // The tombstones within the safety window are kept.
+            let purged = node
+                .purge_replica_tombstones(Duration::from_secs(3600))
+                .await
+                .unwrap();
+            assert!(purged.is_empty());
+
+            // Wait until the data of replica is destroyed.
+            crate::runtime::time::sleep(Duration::from_millis(100)).await;
+            let purged = node.purge_replica_tombstones(Duration::ZERO).await.unwrap();
+            assert_eq!(purged.len(), 1);
+            assert_eq!(purged[0].replica_id, replica_id);
+        });
+    }
+
     #[test]
     fn try_add_replicas_in_the_same_group() {
         let executor_owner = ExecutorOwner::new(1);
@@ -1088,6 +1437,7 @@ mod tests {
                     put: Some(PutRequest {
                         key: vec![0u8; 10],
                         value: vec![0u8; 10],
+                        ..Default::default()
                     }),
                 });
                 replica.execute(&mut ctx, &request).await.unwrap();
@@ -1169,6 +1519,7 @@ mod tests {
                     put: Some(PutRequest {
                         key: vec![0u8; 10],
                         value: vec![0u8; 10],
+                        ..Default::default()
                     }),
                 });
                 replica.execute(&mut ctx, &request).await.unwrap();
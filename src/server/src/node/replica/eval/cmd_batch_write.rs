@@ -29,13 +29,17 @@ pub async fn batch_write(
     }
 
     let mut wb = WriteBatch::default();
+    let mut epoch_at_least = 0;
     for req in &req.deletes {
         let del = req
             .delete
             .as_ref()
             .ok_or_else(|| Error::InvalidArgument("ShardDeleteRequest::delete is None".into()))?;
         if exec_ctx.is_migrating_shard(req.shard_id) {
-            panic!("BatchWrite does not support migrating shard");
+            return Err(Error::InvalidArgument(format!(
+                "BatchWrite does not support migrating shard {}",
+                req.shard_id
+            )));
         }
         group_engine.delete(&mut wb, req.shard_id, &del.key, super::FLAT_KEY_VERSION)?;
     }
@@ -45,7 +49,10 @@ pub async fn batch_write(
             .as_ref()
             .ok_or_else(|| Error::InvalidArgument("ShardPutRequest::put is None".into()))?;
         if exec_ctx.is_migrating_shard(req.shard_id) {
-            panic!("BatchWrite does not support migrating shard");
+            return Err(Error::InvalidArgument(format!(
+                "BatchWrite does not support migrating shard {}",
+                req.shard_id
+            )));
         }
         group_engine.put(
             &mut wb,
@@ -54,11 +61,16 @@ pub async fn batch_write(
             &put.value,
             super::FLAT_KEY_VERSION,
         )?;
+        if let Some(cond) = put.condition.as_ref() {
+            super::cmd_put::check_group_condition(exec_ctx, cond)?;
+            epoch_at_least = std::cmp::max(epoch_at_least, cond.epoch_at_least);
+        }
     }
     Ok(Some(EvalResult {
         batch: Some(WriteBatchRep {
             data: wb.data().to_owned(),
         }),
+        epoch_at_least,
         ..Default::default()
     }))
 }
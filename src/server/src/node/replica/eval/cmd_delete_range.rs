@@ -0,0 +1,52 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use engula_api::server::v1::ShardDeleteRangeRequest;
+
+use crate::{
+    node::{migrate::ForwardCtx, replica::ExecCtx},
+    serverpb::v1::{EvalResult, SyncOp},
+    Error, Result,
+};
+
+pub fn delete_range(exec_ctx: &ExecCtx, req: &ShardDeleteRangeRequest) -> Result<EvalResult> {
+    if !req.end.is_empty() && req.start >= req.end {
+        return Err(Error::InvalidArgument(
+            "ShardDeleteRangeRequest::start must be less than end".into(),
+        ));
+    }
+
+    // The range tombstone isn't pulled by the dest group of migration, so it is forwarded. The
+    // values pulled later are older than it, so they are deleted by it in the dest group.
+    if let Some(desc) = exec_ctx.migration_desc.as_ref() {
+        let shard_id = desc.shard_desc.as_ref().unwrap().id;
+        if shard_id == req.shard_id {
+            let forward_ctx = ForwardCtx {
+                shard_id,
+                dest_group_id: desc.dest_group_id,
+                payloads: vec![],
+            };
+            return Err(Error::Forward(forward_ctx));
+        }
+    }
+
+    Ok(EvalResult {
+        op: Some(SyncOp::delete_range(
+            req.shard_id,
+            req.start.clone(),
+            req.end.clone(),
+        )),
+        ..Default::default()
+    })
+}
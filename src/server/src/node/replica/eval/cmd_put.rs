@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use engula_api::server::v1::ShardPutRequest;
+use engula_api::{server::v1::ShardPutRequest, v1::WriteCondition};
 
 use crate::{
     node::{
@@ -34,6 +34,10 @@ pub async fn put(
         .as_ref()
         .ok_or_else(|| Error::InvalidArgument("ShardPutRequest::put is None".into()))?;
 
+    if let Some(cond) = put.condition.as_ref() {
+        check_group_condition(exec_ctx, cond)?;
+    }
+
     if let Some(desc) = exec_ctx.migration_desc.as_ref() {
         let shard_id = desc.shard_desc.as_ref().unwrap().id;
         if shard_id == req.shard_id {
@@ -58,6 +62,23 @@ pub async fn put(
         batch: Some(WriteBatchRep {
             data: wb.data().to_owned(),
         }),
+        epoch_at_least: put
+            .condition
+            .as_ref()
+            .map(|c| c.epoch_at_least)
+            .unwrap_or_default(),
         ..Default::default()
     })
 }
+
+/// Reject the write if it is not served by the group required by the condition. The group of a
+/// replica never changes, so it is checked when evaluating.
+pub(super) fn check_group_condition(exec_ctx: &ExecCtx, cond: &WriteCondition) -> Result<()> {
+    if cond.group_id != 0 && cond.group_id != exec_ctx.group_id {
+        return Err(Error::ConditionNotMet(format!(
+            "the write is served by group {} instead of {}",
+            exec_ctx.group_id, cond.group_id
+        )));
+    }
+    Ok(())
+}
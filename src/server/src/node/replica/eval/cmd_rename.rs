@@ -0,0 +1,67 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use engula_api::{server::v1::ShardRenameRequest, shard};
+
+use crate::{
+    node::{engine::GroupEngine, replica::ExecCtx},
+    serverpb::v1::{EvalResult, SyncOp},
+    Error, Result,
+};
+
+/// Evaluate the rename request. The keys are checked here to respond to the client, but the
+/// rename is applied by reading the keys again, since they might be modified before this command
+/// is applied.
+pub async fn rename(
+    exec_ctx: &ExecCtx,
+    group_engine: &GroupEngine,
+    req: &ShardRenameRequest,
+) -> Result<Option<EvalResult>> {
+    let desc = group_engine
+        .descriptor()
+        .shards
+        .into_iter()
+        .find(|s| s.id == req.shard_id)
+        .ok_or(Error::ShardNotFound(req.shard_id))?;
+    if !shard::belong_to(&desc, &req.from) || !shard::belong_to(&desc, &req.to) {
+        return Err(Error::InvalidArgument(format!(
+            "the keys of rename are not belong to shard {}",
+            req.shard_id
+        )));
+    }
+
+    // The migration is not aware of the rename, so wait until the migration is finished.
+    if exec_ctx.is_migrating_shard(req.shard_id) {
+        return Err(Error::ServiceIsBusy("migration"));
+    }
+
+    if group_engine.get(req.shard_id, &req.from).await?.is_none() {
+        return Ok(None);
+    }
+    if req.only_if_absent
+        && (req.from == req.to || group_engine.get(req.shard_id, &req.to).await?.is_some())
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(EvalResult {
+        op: Some(SyncOp::rename(
+            req.shard_id,
+            req.from.clone(),
+            req.to.clone(),
+            req.only_if_absent,
+        )),
+        ..Default::default()
+    }))
+}
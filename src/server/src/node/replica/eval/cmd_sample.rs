@@ -0,0 +1,77 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use engula_api::{
+    chunk::is_chunk_key,
+    server::v1::{ShardData, ShardSampleRequest, ShardSampleResponse},
+};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    node::engine::{GroupEngine, SnapshotMode},
+    Result,
+};
+
+/// The upper bound of the samples of a request, to limit the size of the response.
+const MAX_SAMPLE_COUNT: usize = 1 << 16;
+
+/// Draw a uniform random sample of the live key-value pairs of the shard. The shard is iterated
+/// on the replica with reservoir sampling, so only the samples are transferred. The chunks of large
+/// values are skipped like [`super::scan`].
+pub async fn sample(engine: &GroupEngine, req: &ShardSampleRequest) -> Result<ShardSampleResponse> {
+    // TODO(walter) shall I support migrating?
+    let count = std::cmp::min(req.count as usize, MAX_SAMPLE_COUNT);
+    let mut rng = rand::thread_rng();
+    let mut samples = Vec::with_capacity(count);
+    let mut total = 0;
+    let mut snapshot = engine.snapshot(req.shard_id, SnapshotMode::Start { start_key: None })?;
+    for mvcc_iter in snapshot.iter() {
+        let mut mvcc_iter = mvcc_iter?;
+        let entry = match mvcc_iter.next() {
+            Some(entry) => entry?,
+            None => continue,
+        };
+        let value = match entry.value() {
+            Some(value) if !is_chunk_key(entry.user_key()) => value,
+            _ => continue,
+        };
+        total += 1;
+        let slot = if samples.len() < count {
+            samples.len()
+        } else {
+            match rng.gen_range(0..total) {
+                idx if idx < count => idx,
+                _ => continue,
+            }
+        };
+        let data = ShardData {
+            key: entry.user_key().to_owned(),
+            value: value.to_owned(),
+            version: entry.version(),
+        };
+        if slot == samples.len() {
+            samples.push(data);
+        } else {
+            samples[slot] = data;
+        }
+    }
+
+    // The positions of reservoir are related to the order of keys, so any prefix of the shuffled
+    // samples is still a uniform sample.
+    samples.shuffle(&mut rng);
+    Ok(ShardSampleResponse {
+        samples,
+        total: total as u64,
+    })
+}
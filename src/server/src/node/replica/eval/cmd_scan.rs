@@ -0,0 +1,91 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use engula_api::{
+    chunk::is_chunk_key,
+    server::v1::{
+        shard_desc::{Partition, RangePartition},
+        ShardData, ShardScanRequest, ShardScanResponse,
+    },
+};
+
+use crate::{
+    node::engine::{GroupEngine, SnapshotMode},
+    Result,
+};
+
+/// The upper bound of the key-value pairs of a request, to limit the size of the response.
+const MAX_SCAN_LIMIT: usize = 1 << 16;
+
+/// Read the key-value pairs of the shard in `[start, end)`, ordered by key. The requested range
+/// is clamped to the range of shard, and the rest of it is returned as the resume key.
+///
+/// The chunks of large values are skipped, the manifests saved in the user keys are returned
+/// instead, see `engula_api::chunk`.
+pub async fn scan(engine: &GroupEngine, req: &ShardScanRequest) -> Result<ShardScanResponse> {
+    // TODO(walter) shall I support migrating?
+    let desc = engine.shard_desc(req.shard_id)?;
+    let limit = match req.limit as usize {
+        0 => MAX_SCAN_LIMIT,
+        limit => std::cmp::min(limit, MAX_SCAN_LIMIT),
+    };
+    let (start, shard_end) = match desc.partition.as_ref() {
+        Some(Partition::Range(RangePartition { start, end })) => (
+            std::cmp::max(start.as_slice(), req.start.as_slice()),
+            end.as_slice(),
+        ),
+        _ => (req.start.as_slice(), [].as_slice()),
+    };
+
+    let mut resp = ShardScanResponse::default();
+    if !shard_end.is_empty() && is_before_end(shard_end, &req.end) {
+        // The shard ends before the requested end, eg the shard is split.
+        resp.resume_key = shard_end.to_owned();
+        if !is_before_end(start, shard_end) {
+            return Ok(resp);
+        }
+    }
+
+    let start_key = if start.is_empty() { None } else { Some(start) };
+    let mut snapshot = engine.snapshot(req.shard_id, SnapshotMode::Start { start_key })?;
+    for mvcc_iter in snapshot.iter() {
+        let mut mvcc_iter = mvcc_iter?;
+        let entry = match mvcc_iter.next() {
+            Some(entry) => entry?,
+            None => continue,
+        };
+        if !is_before_end(entry.user_key(), &req.end) {
+            break;
+        }
+        let value = match entry.value() {
+            Some(value) if !is_chunk_key(entry.user_key()) => value,
+            _ => continue,
+        };
+        if resp.data.len() == limit {
+            resp.resume_key = entry.user_key().to_owned();
+            break;
+        }
+        resp.data.push(ShardData {
+            key: entry.user_key().to_owned(),
+            value: value.to_owned(),
+            version: entry.version(),
+        });
+    }
+    Ok(resp)
+}
+
+#[inline]
+fn is_before_end(key: &[u8], end: &[u8]) -> bool {
+    end.is_empty() || key < end
+}
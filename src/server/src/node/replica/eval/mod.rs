@@ -15,20 +15,26 @@
 mod cmd_accept_shard;
 mod cmd_batch_write;
 mod cmd_delete;
+mod cmd_delete_range;
 mod cmd_get;
 mod cmd_move_replicas;
 mod cmd_prefix_list;
 mod cmd_put;
+mod cmd_rename;
+mod cmd_sample;
+mod cmd_scan;
 
 use engula_api::server::v1::ShardDesc;
 
 pub use self::{
-    cmd_accept_shard::accept_shard, cmd_batch_write::batch_write, cmd_delete::delete, cmd_get::get,
-    cmd_move_replicas::move_replicas, cmd_prefix_list::prefix_list, cmd_put::put,
+    cmd_accept_shard::accept_shard, cmd_batch_write::batch_write, cmd_delete::delete,
+    cmd_delete_range::delete_range, cmd_get::get, cmd_move_replicas::move_replicas,
+    cmd_prefix_list::prefix_list, cmd_put::put, cmd_rename::rename, cmd_sample::sample,
+    cmd_scan::scan,
 };
 use crate::serverpb::v1::EvalResult;
 
-const FLAT_KEY_VERSION: u64 = u64::MAX - 1;
+pub const FLAT_KEY_VERSION: u64 = u64::MAX - 1;
 pub const MIGRATING_KEY_VERSION: u64 = 0;
 
 pub fn add_shard(shard: ShardDesc) -> EvalResult {
@@ -11,13 +11,16 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use engula_api::server::v1::GroupDesc;
 use tracing::{debug, error, info};
 
 use crate::{
-    node::{engine::RawIterator, replica::ReplicaConfig, GroupEngine},
+    node::{engine::RawColumnFamily, replica::ReplicaConfig, GroupEngine},
     raftgroup::SnapshotBuilder,
     serverpb::v1::ApplyState,
     Error, Result,
@@ -38,33 +41,44 @@ impl GroupSnapshotBuilder {
 impl SnapshotBuilder for GroupSnapshotBuilder {
     async fn checkpoint(&self, base_dir: &Path) -> Result<(ApplyState, GroupDesc)> {
         std::fs::create_dir_all(base_dir)?;
-        let mut iter = self.engine.raw_iter()?;
-        for i in 0.. {
-            if write_partial_to_file(&self.cfg, &mut iter, base_dir, i)
-                .await?
-                .is_none()
-            {
-                break;
+        let iter = self.engine.raw_iter()?;
+        let apply_state = iter.apply_state().clone();
+        let descriptor = iter.descriptor().clone();
+        for (cf, mut db_iter) in iter.into_column_families() {
+            let prefix = match cf {
+                RawColumnFamily::Meta => META_FILE_PREFIX.to_owned(),
+                RawColumnFamily::Shard(shard_id) => format!("{SHARD_FILE_PREFIX}{shard_id}-"),
+            };
+            for i in 0.. {
+                let file = base_dir.join(format!("{prefix}{i}.sst"));
+                if write_partial_to_file(&self.cfg, &mut db_iter, &file)
+                    .await?
+                    .is_none()
+                {
+                    break;
+                }
             }
         }
 
-        let apply_state = iter.apply_state().clone();
-        let descriptor = iter.descriptor().clone();
         Ok((apply_state, descriptor))
     }
 }
 
+/// The prefix of the files of the local states, followed by the file number.
+const META_FILE_PREFIX: &str = "meta-";
+
+/// The prefix of the files of a shard, followed by the shard id and the file number.
+const SHARD_FILE_PREFIX: &str = "shard-";
+
 /// Write partial of the iterator's data to the file, return `None` if all data is written.
 async fn write_partial_to_file(
     cfg: &ReplicaConfig,
-    iter: &mut RawIterator<'_>,
-    base_dir: &Path,
-    file_no: usize,
+    iter: &mut rocksdb::DBIterator<'_>,
+    file: &Path,
 ) -> Result<Option<()>> {
     use rocksdb::{Options, SstFileWriter};
 
     let opts = Options::default();
-    let file = base_dir.join(format!("{}.sst", file_no));
     let mut writer: Option<SstFileWriter> = None;
     let mut index = 0;
     for item in iter.by_ref() {
@@ -72,7 +86,7 @@ async fn write_partial_to_file(
         if writer.is_none() {
             debug!("create sst file: {}", file.display());
             let raw_writer = SstFileWriter::create(&opts);
-            raw_writer.open(&file)?;
+            raw_writer.open(file)?;
             writer = Some(raw_writer);
         }
 
@@ -108,20 +122,25 @@ pub fn apply_snapshot(engine: &GroupEngine, replica_id: u64, snap_dir: &Path) ->
         )));
     }
 
-    let mut files = vec![];
+    let mut meta_files = vec![];
+    let mut shard_files: HashMap<u64, Vec<PathBuf>> = HashMap::default();
     for entry in snap_dir.read_dir()? {
         let entry = entry?;
         let path = entry.path();
-        if is_sst_file(&path) {
-            debug!(
-                "replica {replica_id} apply snapshot with sst file {}",
-                path.display()
-            );
-            files.push(path.file_name().unwrap().to_owned());
+        if !is_sst_file(&path) {
+            continue;
+        }
+        debug!(
+            "replica {replica_id} apply snapshot with sst file {}",
+            path.display()
+        );
+        match parse_file_name(&path)? {
+            RawColumnFamily::Meta => meta_files.push(path),
+            RawColumnFamily::Shard(shard_id) => shard_files.entry(shard_id).or_default().push(path),
         }
     }
 
-    if files.is_empty() {
+    if meta_files.is_empty() {
         error!(
             "replica {replica_id} apply snapshot {}: snap dir is empty",
             snap_dir.display()
@@ -133,14 +152,13 @@ pub fn apply_snapshot(engine: &GroupEngine, replica_id: u64, snap_dir: &Path) ->
     }
 
     debug!(
-        "replica {replica_id} apply snapshot {} found {} sst files",
+        "replica {replica_id} apply snapshot {} found {} sst files of local states and {} shards",
         snap_dir.display(),
-        files.len()
+        meta_files.len(),
+        shard_files.len(),
     );
 
-    files.sort_unstable();
-    let files = files.into_iter().map(|f| snap_dir.join(f)).collect();
-    engine.ingest(files)?;
+    engine.ingest(meta_files, shard_files)?;
 
     info!(
         "replica {replica_id} apply snapshot {}, apply state {:?}",
@@ -151,6 +169,25 @@ pub fn apply_snapshot(engine: &GroupEngine, replica_id: u64, snap_dir: &Path) ->
     Ok(())
 }
 
+/// Parse the column family which the file belongs to. The snapshots of the legacy layout save
+/// all data in files named by the file number, they are ingested as the files of local states.
+fn parse_file_name(path: &Path) -> Result<RawColumnFamily> {
+    let invalid_name = || Error::InvalidData(format!("invalid snapshot file {}", path.display()));
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(invalid_name)?;
+    if let Some(suffix) = stem.strip_prefix(SHARD_FILE_PREFIX) {
+        let (shard_id, _) = suffix.split_once('-').ok_or_else(invalid_name)?;
+        let shard_id = shard_id.parse().map_err(|_| invalid_name())?;
+        Ok(RawColumnFamily::Shard(shard_id))
+    } else {
+        let file_no = stem.strip_prefix(META_FILE_PREFIX).unwrap_or(stem);
+        file_no.parse::<usize>().map_err(|_| invalid_name())?;
+        Ok(RawColumnFamily::Meta)
+    }
+}
+
 #[inline]
 fn is_sst_file<P: AsRef<Path>>(path: P) -> bool {
     let path = path.as_ref();
@@ -272,4 +309,43 @@ mod tests {
             apply_snapshot(&engine, 1, &data).unwrap();
         });
     }
+
+    #[test]
+    fn apply_snapshot_of_shards() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        executor.block_on(async {
+            let tmp_dir = TempDir::new("apply_snapshot_of_shards")
+                .unwrap()
+                .into_path();
+            let snap_dir = tmp_dir.join("snap");
+            let engine = create_engine(&tmp_dir.join("db1"), 1, 1).await;
+            put_data(&engine, 1, "key", 128);
+
+            let cfg = ReplicaConfig {
+                snap_file_size: 64 * 1024,
+                ..Default::default()
+            };
+            let builder = GroupSnapshotBuilder::new(cfg, engine);
+            builder.checkpoint(&snap_dir).await.unwrap();
+            assert!(snap_dir.join("meta-0.sst").is_file());
+            assert!(snap_dir.join("shard-1-1.sst").is_file());
+
+            let target = create_engine(&tmp_dir.join("db2"), 1, 2).await;
+            apply_snapshot(&target, 1, &snap_dir).unwrap();
+            assert_eq!(target.descriptor().shards[0].id, 1);
+            let value = target.get(1, b"key-127").await.unwrap();
+            assert_eq!(value, Some(vec![0; 1024]));
+        });
+    }
+
+    #[test]
+    fn parse_snapshot_file_name() {
+        let parse = |name: &str| parse_file_name(Path::new(name)).ok();
+        assert_eq!(parse("0.sst"), Some(RawColumnFamily::Meta));
+        assert_eq!(parse("meta-12.sst"), Some(RawColumnFamily::Meta));
+        assert_eq!(parse("shard-3-0.sst"), Some(RawColumnFamily::Shard(3)));
+        assert_eq!(parse("shard-3.sst"), None);
+        assert_eq!(parse("unknown.sst"), None);
+    }
 }
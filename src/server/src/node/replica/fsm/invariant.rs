@@ -0,0 +1,301 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The invariants of `GroupDesc` transitions. The checks are only enabled in debug builds (and
+//! tests), a violation means that the descriptors of replicas might diverge, so it panics with the
+//! detailed diff of the transition.
+
+use std::{collections::HashSet, fmt::Write};
+
+use engula_api::server::v1::{ChangeReplicas, GroupDesc, ReplicaRole};
+
+use super::{ChangeReplicaKind, SHARD_UPDATE_DELTA};
+
+/// The cause of a `GroupDesc` transition.
+#[derive(Debug)]
+pub(super) enum DescChange<'a> {
+    /// A conf change entry.
+    ChangeReplicas(&'a ChangeReplicas),
+    /// A proposal which might add or migrate shards.
+    Proposal,
+}
+
+/// Validate the transition of `GroupDesc` from `prev` to `next`, panic if any invariant is
+/// violated. It does nothing if debug assertions are disabled.
+pub(super) fn check_desc_transition(prev: &GroupDesc, next: &GroupDesc, change: DescChange<'_>) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    if let Err(violation) = validate_desc_transition(prev, next, &change) {
+        let group_id = prev.id;
+        let diff = desc_diff(prev, next);
+        panic!(
+            "group {group_id} descriptor transition violates invariant: {violation}, \
+            caused by {change:?}\n{diff}"
+        );
+    }
+}
+
+fn validate_desc_transition(
+    prev: &GroupDesc,
+    next: &GroupDesc,
+    change: &DescChange<'_>,
+) -> Result<(), String> {
+    if prev.id != next.id {
+        return Err(format!("group id changed to {}", next.id));
+    }
+    if next.epoch < prev.epoch {
+        return Err("epoch decreased".to_owned());
+    }
+    if let Some(id) = find_duplicated(next.shards.iter().map(|s| s.id)) {
+        return Err(format!("shard {id} is duplicated"));
+    }
+    if let Some(id) = find_duplicated(next.replicas.iter().map(|r| r.id)) {
+        return Err(format!("replica {id} is duplicated"));
+    }
+
+    let shards_changed = prev.shards != next.shards;
+    let replicas_changed = prev.replicas != next.replicas;
+    match change {
+        DescChange::ChangeReplicas(cc) => {
+            if shards_changed {
+                return Err("shards changed by conf change".to_owned());
+            }
+            if next.epoch == prev.epoch {
+                return Err("conf change without epoch bump".to_owned());
+            }
+            validate_replicas_change(prev, next, cc)?;
+        }
+        DescChange::Proposal => {
+            if replicas_changed {
+                return Err("replicas changed without conf change".to_owned());
+            }
+            if shards_changed && next.epoch < prev.epoch + SHARD_UPDATE_DELTA {
+                return Err("shards changed without epoch bump".to_owned());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The replicas changed by a conf change must be consistent with the conf change: a simple or
+/// enter joint change only touches the listed replicas, and leaving joint only resolves the joint
+/// roles.
+fn validate_replicas_change(
+    prev: &GroupDesc,
+    next: &GroupDesc,
+    cc: &ChangeReplicas,
+) -> Result<(), String> {
+    let changed = prev
+        .replicas
+        .iter()
+        .chain(next.replicas.iter())
+        .map(|r| r.id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter(|id| {
+            let before = prev.replicas.iter().find(|r| r.id == *id);
+            let after = next.replicas.iter().find(|r| r.id == *id);
+            before != after
+        });
+
+    match ChangeReplicaKind::new(cc) {
+        ChangeReplicaKind::LeaveJoint => {
+            for id in changed {
+                let before = prev.replicas.iter().find(|r| r.id == id);
+                let after = next.replicas.iter().find(|r| r.id == id);
+                let (Some(before), Some(after)) = (before, after) else {
+                    return Err(format!("replica {id} added or removed by leaving joint"));
+                };
+                let expect = match ReplicaRole::from_i32(before.role) {
+                    Some(ReplicaRole::IncomingVoter) => ReplicaRole::Voter,
+                    Some(ReplicaRole::DemotingVoter) => ReplicaRole::Learner,
+                    _ => return Err(format!("replica {id} isn't in joint but changed")),
+                };
+                if after.role != expect as i32 || after.node_id != before.node_id {
+                    return Err(format!("replica {id} isn't resolved to {expect:?}"));
+                }
+            }
+        }
+        ChangeReplicaKind::Simple | ChangeReplicaKind::EnterJoint => {
+            for id in changed {
+                if !cc.changes.iter().any(|c| c.replica_id == id) {
+                    return Err(format!("replica {id} changed but not in conf change"));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn find_duplicated(mut ids: impl Iterator<Item = u64>) -> Option<u64> {
+    let mut seen = HashSet::new();
+    ids.find(|&id| !seen.insert(id))
+}
+
+/// Describe the difference between two descriptors.
+fn desc_diff(prev: &GroupDesc, next: &GroupDesc) -> String {
+    let mut diff = String::new();
+    writeln!(diff, "  epoch: {} => {}", prev.epoch, next.epoch).unwrap();
+    for shard in &prev.shards {
+        if !next.shards.contains(shard) {
+            writeln!(diff, "- shard {shard:?}").unwrap();
+        }
+    }
+    for shard in &next.shards {
+        if !prev.shards.contains(shard) {
+            writeln!(diff, "+ shard {shard:?}").unwrap();
+        }
+    }
+    for replica in &prev.replicas {
+        if !next.replicas.contains(replica) {
+            writeln!(diff, "- replica {replica:?}").unwrap();
+        }
+    }
+    for replica in &next.replicas {
+        if !prev.replicas.contains(replica) {
+            writeln!(diff, "+ replica {replica:?}").unwrap();
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use engula_api::server::v1::{ChangeReplica, ChangeReplicaType, ReplicaDesc, ShardDesc};
+
+    use super::*;
+
+    fn replica(id: u64, role: ReplicaRole) -> ReplicaDesc {
+        ReplicaDesc {
+            id,
+            node_id: id,
+            role: role as i32,
+        }
+    }
+
+    fn shard(id: u64) -> ShardDesc {
+        ShardDesc {
+            id,
+            ..Default::default()
+        }
+    }
+
+    fn desc(epoch: u64, shards: Vec<ShardDesc>, replicas: Vec<ReplicaDesc>) -> GroupDesc {
+        GroupDesc {
+            id: 1,
+            epoch,
+            shards,
+            replicas,
+        }
+    }
+
+    fn change(replica_id: u64, change_type: ChangeReplicaType) -> ChangeReplica {
+        ChangeReplica {
+            change_type: change_type as i32,
+            replica_id,
+            node_id: replica_id,
+        }
+    }
+
+    #[test]
+    fn valid_transitions() {
+        let voters = vec![replica(1, ReplicaRole::Voter)];
+        let prev = desc(1, vec![shard(1)], voters.clone());
+
+        // Add shard.
+        let next = desc(
+            1 + SHARD_UPDATE_DELTA,
+            vec![shard(1), shard(2)],
+            voters.clone(),
+        );
+        assert!(validate_desc_transition(&prev, &next, &DescChange::Proposal).is_ok());
+
+        // Simple conf change.
+        let cc = ChangeReplicas {
+            changes: vec![change(2, ChangeReplicaType::Add)],
+        };
+        let next = desc(
+            2,
+            vec![shard(1)],
+            vec![
+                replica(1, ReplicaRole::Voter),
+                replica(2, ReplicaRole::Voter),
+            ],
+        );
+        assert!(validate_desc_transition(&prev, &next, &DescChange::ChangeReplicas(&cc)).is_ok());
+
+        // Leave joint.
+        let prev = desc(
+            2,
+            vec![shard(1)],
+            vec![
+                replica(1, ReplicaRole::DemotingVoter),
+                replica(2, ReplicaRole::IncomingVoter),
+            ],
+        );
+        let next = desc(
+            3,
+            vec![shard(1)],
+            vec![
+                replica(1, ReplicaRole::Learner),
+                replica(2, ReplicaRole::Voter),
+            ],
+        );
+        let cc = ChangeReplicas::default();
+        assert!(validate_desc_transition(&prev, &next, &DescChange::ChangeReplicas(&cc)).is_ok());
+    }
+
+    #[test]
+    fn invalid_transitions() {
+        let voters = vec![replica(1, ReplicaRole::Voter)];
+        let prev = desc(10, vec![shard(1)], voters.clone());
+
+        // Epoch decreased.
+        let next = desc(9, vec![shard(1)], voters.clone());
+        assert!(validate_desc_transition(&prev, &next, &DescChange::Proposal).is_err());
+
+        // Shards changed without epoch bump.
+        let next = desc(11, vec![shard(1), shard(2)], voters.clone());
+        assert!(validate_desc_transition(&prev, &next, &DescChange::Proposal).is_err());
+
+        // Duplicated shards.
+        let next = desc(10 + SHARD_UPDATE_DELTA, vec![shard(1), shard(1)], voters);
+        assert!(validate_desc_transition(&prev, &next, &DescChange::Proposal).is_err());
+
+        // Replicas changed without conf change.
+        let next = desc(11, vec![shard(1)], vec![]);
+        assert!(validate_desc_transition(&prev, &next, &DescChange::Proposal).is_err());
+
+        // Replicas changed but not listed in the conf change.
+        let cc = ChangeReplicas {
+            changes: vec![change(2, ChangeReplicaType::Add)],
+        };
+        let next = desc(11, vec![shard(1)], vec![replica(3, ReplicaRole::Voter)]);
+        assert!(validate_desc_transition(&prev, &next, &DescChange::ChangeReplicas(&cc)).is_err());
+
+        // Leave joint changes a non-joint replica.
+        let cc = ChangeReplicas::default();
+        let next = desc(11, vec![shard(1)], vec![replica(1, ReplicaRole::Learner)]);
+        assert!(validate_desc_transition(&prev, &next, &DescChange::ChangeReplicas(&cc)).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "shards changed without epoch bump")]
+    fn panic_on_violation() {
+        let prev = desc(1, vec![shard(1)], vec![]);
+        let next = desc(1, vec![], vec![]);
+        check_desc_transition(&prev, &next, DescChange::Proposal);
+    }
+}
@@ -13,24 +13,27 @@
 // limitations under the License.
 
 mod checkpoint;
+mod invariant;
 
 use std::{collections::HashSet, path::Path, sync::Arc};
 
 use engula_api::server::v1::{
     ChangeReplica, ChangeReplicaType, ChangeReplicas, GroupDesc, MigrationDesc, ReplicaDesc,
-    ReplicaRole,
+    ReplicaRole, ShardChecksum,
 };
 use tracing::{info, trace, warn};
 
+use self::invariant::{check_desc_transition, DescChange};
 use super::{ReplicaConfig, ReplicaInfo};
 use crate::{
     node::engine::{GroupEngine, WriteBatch, WriteStates},
     raftgroup::{ApplyEntry, SnapshotBuilder, StateMachine},
+    runtime::Executor,
     serverpb::v1::*,
-    Result,
+    Error, Result,
 };
 
-const SHARD_UPDATE_DELTA: u64 = 1 << 32;
+pub(crate) const SHARD_UPDATE_DELTA: u64 = 1 << 32;
 const CONFIG_CHANGE_DELTA: u64 = 1;
 
 #[derive(Debug)]
@@ -50,6 +53,10 @@ pub trait StateMachineObserver: Send + Sync {
 
     /// This function will be called once the migrate state changes.
     fn on_migrate_state_updated(&mut self, migrate_state: Option<MigrationState>);
+
+    /// Returns a callback which will be called once the checksum of a shard is computed. The
+    /// checksums are computed in background, so the callback might be called in another thread.
+    fn checksum_reporter(&self) -> Box<dyn FnOnce(ShardChecksum) + Send>;
 }
 
 pub struct GroupStateMachine
@@ -58,6 +65,7 @@ where
 {
     cfg: ReplicaConfig,
     info: Arc<ReplicaInfo>,
+    executor: Executor,
 
     group_engine: GroupEngine,
     observer: Box<dyn StateMachineObserver>,
@@ -75,15 +83,18 @@ impl GroupStateMachine {
     pub fn new(
         cfg: ReplicaConfig,
         info: Arc<ReplicaInfo>,
+        executor: Executor,
         group_engine: GroupEngine,
         observer: Box<dyn StateMachineObserver>,
     ) -> Self {
         let apply_state = group_engine
             .flushed_apply_state()
             .expect("access flushed index");
+        info.advance_applied(apply_state.index);
         GroupStateMachine {
             cfg,
             info,
+            executor,
             group_engine,
             observer,
             plugged_write_batches: Vec::default(),
@@ -98,7 +109,8 @@ impl GroupStateMachine {
 impl GroupStateMachine {
     fn apply_change_replicas(&mut self, change_replicas: ChangeReplicas) -> Result<()> {
         let local_id = self.info.replica_id;
-        let mut desc = self.descriptor();
+        let prev_desc = self.descriptor();
+        let mut desc = prev_desc.clone();
         match ChangeReplicaKind::new(&change_replicas) {
             ChangeReplicaKind::LeaveJoint => apply_leave_joint(local_id, &mut desc),
             ChangeReplicaKind::EnterJoint => {
@@ -109,6 +121,11 @@ impl GroupStateMachine {
             }
         }
         desc.epoch += CONFIG_CHANGE_DELTA;
+        check_desc_transition(
+            &prev_desc,
+            &desc,
+            DescChange::ChangeReplicas(&change_replicas),
+        );
         self.desc_updated = true;
         self.plugged_write_states.descriptor = Some(desc);
 
@@ -121,21 +138,26 @@ impl GroupStateMachine {
         }
 
         if let Some(op) = eval_result.op {
-            let mut desc = self.descriptor();
+            let prev_desc = self.descriptor();
+            let mut desc = prev_desc.clone();
             if let Some(AddShard { shard: Some(shard) }) = op.add_shard {
-                for existed_shard in &desc.shards {
-                    if existed_shard.id == shard.id {
-                        todo!("shard {} already existed in group", shard.id);
-                    }
+                if desc.shards.iter().any(|s| s.id == shard.id) {
+                    // The proposals of a retried creation might be both accepted.
+                    info!(
+                        "group {} shard {} already existed, skip adding",
+                        self.info.group_id, shard.id
+                    );
+                } else {
+                    info!("group {} add shard {}", self.info.group_id, shard.id);
+                    self.desc_updated = true;
+                    desc.epoch += SHARD_UPDATE_DELTA;
+                    desc.shards.push(shard);
                 }
-                info!("group {} add shard {}", self.info.group_id, shard.id);
-                self.desc_updated = true;
-                desc.epoch += SHARD_UPDATE_DELTA;
-                desc.shards.push(shard);
             }
             if let Some(m) = op.migration {
                 self.apply_migration_event(m, &mut desc);
             }
+            check_desc_transition(&prev_desc, &desc, DescChange::Proposal);
 
             // Any sync_op will update group desc.
             self.plugged_write_states.descriptor = Some(desc);
@@ -144,6 +166,132 @@ impl GroupStateMachine {
         Ok(())
     }
 
+    /// Compute the checksum of the shard at the specified index. The plugged writes of the
+    /// previous entries are committed first, so that all replicas compute the checksum over the
+    /// same data. Only a snapshot is taken here, the shard is scanned in the blocking pool of the
+    /// executor so the following entries are not blocked.
+    fn apply_compute_checksum(
+        &mut self,
+        index: u64,
+        compute_checksum: ComputeChecksum,
+    ) -> Result<()> {
+        if self.plugged_write_states.apply_state.is_some() {
+            self.finish_plug()?;
+        }
+
+        let shard_id = compute_checksum.shard_id;
+        let checksum_id = compute_checksum.checksum_id;
+        let snapshot = match self.group_engine.shard_stats_snapshot(shard_id) {
+            Ok(snapshot) => snapshot,
+            Err(crate::Error::ShardNotFound(_)) => {
+                // The shard might be migrated before this command is applied.
+                warn!(
+                    replica = self.info.replica_id,
+                    group = self.info.group_id,
+                    "compute checksum of shard {shard_id}: shard not found"
+                );
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+
+        let (replica_id, group_id) = (self.info.replica_id, self.info.group_id);
+        let reporter = self.observer.checksum_reporter();
+        self.executor.spawn_blocking(move || match snapshot.scan() {
+            Ok(stats) => reporter(ShardChecksum {
+                checksum_id,
+                shard_id,
+                index,
+                checksum: stats.checksum,
+                num_keys: stats.num_keys,
+            }),
+            Err(err) => {
+                warn!(
+                    replica = replica_id,
+                    group = group_id,
+                    "compute checksum of shard {shard_id}: {err:?}"
+                );
+            }
+        });
+        Ok(())
+    }
+
+    /// Delete a range of keys of the shard with a range tombstone whose seq is the index of this
+    /// entry. The plugged writes of the previous entries are committed first, so that they are
+    /// deleted by the range tombstone.
+    ///
+    /// The values already pulled by the dest group of migration aren't deleted by the range
+    /// tombstone, so the deletions of a shard migrating out are rejected by
+    /// [`StateMachine::check_condition`], and forwarded once they are evaluated again.
+    fn apply_delete_range(&mut self, index: u64, delete_range: DeleteRange) -> Result<()> {
+        if self.plugged_write_states.apply_state.is_some() {
+            self.finish_plug()?;
+        }
+
+        let shard_id = delete_range.shard_id;
+        match self.group_engine.delete_range(
+            shard_id,
+            &delete_range.start,
+            &delete_range.end,
+            index,
+        ) {
+            Ok(()) => Ok(()),
+            Err(crate::Error::ShardNotFound(_)) => {
+                // The shard might be migrated before this command is applied.
+                warn!(
+                    replica = self.info.replica_id,
+                    group = self.info.group_id,
+                    "delete range of shard {shard_id}: shard not found"
+                );
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Rename a key of the shard. The plugged writes of the previous entries are committed first,
+    /// since the keys are read from the engine.
+    fn apply_rename(&mut self, rename: Rename) -> Result<()> {
+        if self.plugged_write_states.apply_state.is_some() {
+            self.finish_plug()?;
+        }
+
+        let shard_id = rename.shard_id;
+        let mut wb = WriteBatch::default();
+        match self.group_engine.rename(
+            &mut wb,
+            shard_id,
+            &rename.from,
+            &rename.to,
+            rename.only_if_absent,
+            super::eval::FLAT_KEY_VERSION,
+        ) {
+            Ok(true) => {
+                self.plugged_write_batches.push(wb);
+                Ok(())
+            }
+            Ok(false) => {
+                // The keys are modified after the command is evaluated.
+                warn!(
+                    replica = self.info.replica_id,
+                    group = self.info.group_id,
+                    "rename key of shard {shard_id}: the keys are modified concurrently, skip it"
+                );
+                Ok(())
+            }
+            Err(crate::Error::ShardNotFound(_)) => {
+                // The shard might be migrated before this command is applied.
+                warn!(
+                    replica = self.info.replica_id,
+                    group = self.info.group_id,
+                    "rename key of shard {shard_id}: shard not found"
+                );
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     fn apply_migration_event(&mut self, migration: Migration, group_desc: &mut GroupDesc) {
         let event = MigrationEvent::from_i32(migration.event).expect("unknown migration event");
         if let Some(desc) = migration.migration_desc.as_ref() {
@@ -274,6 +422,20 @@ impl GroupStateMachine {
             .expect("access flushed index")
     }
 
+    /// Whether the shard is being migrated out of this group, the plugged migration state is also
+    /// taken into account.
+    fn is_migrating_out_shard(&self, shard_id: u64) -> bool {
+        self.plugged_write_states
+            .migration_state
+            .clone()
+            .or_else(|| self.group_engine.migration_state())
+            .map(|s| {
+                s.get_shard_id() == shard_id
+                    && s.get_migration_desc().src_group_id == self.info.group_id
+            })
+            .unwrap_or_default()
+    }
+
     #[inline]
     fn must_migration_state(&self) -> MigrationState {
         self.plugged_write_states
@@ -295,6 +457,25 @@ impl StateMachine for GroupStateMachine {
         Ok(())
     }
 
+    fn check_condition(&self, eval_result: &EvalResult) -> Result<()> {
+        let epoch = self.descriptor().epoch;
+        if epoch < eval_result.epoch_at_least {
+            return Err(Error::ConditionNotMet(format!(
+                "group {} epoch {epoch} is less than {}",
+                self.info.group_id, eval_result.epoch_at_least
+            )));
+        }
+        let op = eval_result.op.as_ref();
+        if let Some(delete_range) = op.and_then(|op| op.delete_range.as_ref()) {
+            // The migration might be set up after the deletion is evaluated, the proposer retries
+            // and forwards it to the dest group.
+            if self.is_migrating_out_shard(delete_range.shard_id) {
+                return Err(Error::ServiceIsBusy("migration"));
+            }
+        }
+        Ok(())
+    }
+
     fn apply(&mut self, index: u64, term: u64, entry: ApplyEntry) -> Result<()> {
         trace!("apply entry index {} term {}", index, term);
         match entry {
@@ -303,7 +484,16 @@ impl StateMachine for GroupStateMachine {
                 self.apply_change_replicas(change_replicas)?;
             }
             ApplyEntry::Proposal { eval_result } => {
-                self.apply_proposal(eval_result)?;
+                let op = eval_result.op.as_ref();
+                if let Some(compute_checksum) = op.and_then(|op| op.compute_checksum.clone()) {
+                    self.apply_compute_checksum(index, compute_checksum)?;
+                } else if let Some(delete_range) = op.and_then(|op| op.delete_range.clone()) {
+                    self.apply_delete_range(index, delete_range)?;
+                } else if let Some(rename) = op.and_then(|op| op.rename.clone()) {
+                    self.apply_rename(rename)?;
+                } else {
+                    self.apply_proposal(eval_result)?;
+                }
             }
         }
         self.plugged_write_states.apply_state = Some(ApplyState { index, term });
@@ -312,16 +502,15 @@ impl StateMachine for GroupStateMachine {
     }
 
     fn finish_plug(&mut self) -> Result<()> {
-        let Some(ApplyState { term, .. }) = self.plugged_write_states.apply_state else {
+        let Some(ApplyState { index, term }) = self.plugged_write_states.apply_state else {
             panic!("invoke GroupStateMachine::finish_plug but WriteStates::apply_states is None");
         };
-        self.group_engine.group_commit(
-            self.plugged_write_batches.as_slice(),
+        self.group_engine.parallel_group_commit(
+            std::mem::take(&mut self.plugged_write_batches),
             std::mem::take(&mut self.plugged_write_states),
-            false,
         )?;
-        self.plugged_write_batches.clear();
         self.flush_updated_events(term);
+        self.info.advance_applied(index);
 
         Ok(())
     }
@@ -332,6 +521,7 @@ impl StateMachine for GroupStateMachine {
             .on_descriptor_updated(self.group_engine.descriptor());
         let apply_state = self.flushed_apply_state();
         self.observer.on_term_updated(apply_state.term);
+        self.info.advance_applied(apply_state.index);
         Ok(())
     }
 
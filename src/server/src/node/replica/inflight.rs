@@ -0,0 +1,260 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use engula_api::server::v1::group_request_union::Request;
+use serde::Serialize;
+use tokio::sync::{watch, Notify};
+
+use crate::{Error, Result};
+
+/// The information of an executing request, used for debugging.
+#[derive(Debug, Clone, Serialize)]
+pub struct InflightRequestInfo {
+    pub group_id: u64,
+    pub id: u64,
+    pub kind: &'static str,
+    pub shard_id: Option<u64>,
+    pub age_ms: u64,
+}
+
+/// A registry of requests executing in a replica.
+///
+/// Before leadership transferring or shutdown, the registry could be drained: new requests are
+/// rejected with `Error::NotLeader`, and the outstanding requests are waited for or canceled.
+/// The drainings are reference-counted, so the overlapped leadership transferring won't accept
+/// new requests while any of them is still draining.
+pub struct InflightRequests {
+    group_id: u64,
+    inner: Mutex<InflightRequestsInner>,
+    drained: Notify,
+    canceled: watch::Sender<bool>,
+}
+
+#[derive(Default)]
+struct InflightRequestsInner {
+    next_id: u64,
+    /// The number of outstanding [`DrainGuard`]s.
+    draining: usize,
+    canceled: bool,
+    requests: HashMap<u64, InflightRequest>,
+}
+
+struct InflightRequest {
+    kind: &'static str,
+    shard_id: Option<u64>,
+    start: Instant,
+}
+
+/// Accept new requests again once dropped, unless other drainings are still in progress.
+pub struct DrainGuard {
+    registry: Arc<InflightRequests>,
+}
+
+/// Unregister the request from registry once dropped.
+pub struct InflightGuard {
+    registry: Arc<InflightRequests>,
+    id: u64,
+    canceled: watch::Receiver<bool>,
+}
+
+impl InflightRequests {
+    pub fn new(group_id: u64) -> Self {
+        let (canceled, _) = watch::channel(false);
+        InflightRequests {
+            group_id,
+            inner: Mutex::default(),
+            drained: Notify::new(),
+            canceled,
+        }
+    }
+
+    /// Register an executing request, `Error::NotLeader` is returned if the registry is draining.
+    pub fn register(self: &Arc<Self>, request: &Request) -> Result<InflightGuard> {
+        let (kind, shard_id) = describe_request(request);
+        let mut inner = self.inner.lock().unwrap();
+        if inner.draining > 0 || inner.canceled {
+            return Err(Error::NotLeader(self.group_id, 0, None));
+        }
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.requests.insert(
+            id,
+            InflightRequest {
+                kind,
+                shard_id,
+                start: Instant::now(),
+            },
+        );
+        Ok(InflightGuard {
+            registry: self.clone(),
+            id,
+            canceled: self.canceled.subscribe(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().requests.len()
+    }
+
+    /// Returns the executing requests, ordered by the age descending.
+    pub fn requests(&self) -> Vec<InflightRequestInfo> {
+        let inner = self.inner.lock().unwrap();
+        let mut requests = inner
+            .requests
+            .iter()
+            .map(|(id, r)| InflightRequestInfo {
+                group_id: self.group_id,
+                id: *id,
+                kind: r.kind,
+                shard_id: r.shard_id,
+                age_ms: r.start.elapsed().as_millis() as u64,
+            })
+            .collect::<Vec<_>>();
+        requests.sort_by(|a, b| b.age_ms.cmp(&a.age_ms));
+        requests
+    }
+
+    /// Reject new requests until the returned guard is dropped.
+    pub fn start_draining(self: &Arc<Self>) -> DrainGuard {
+        self.inner.lock().unwrap().draining += 1;
+        DrainGuard {
+            registry: self.clone(),
+        }
+    }
+
+    /// Reject new requests and cancel all outstanding requests which are not proposed yet, with
+    /// `Error::NotLeader`. The registry never accepts requests again.
+    pub fn cancel_all(&self) {
+        self.inner.lock().unwrap().canceled = true;
+        self.canceled.send_replace(true);
+    }
+
+    fn unregister(&self, id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.requests.remove(&id);
+        if inner.requests.is_empty() {
+            self.drained.notify_waiters();
+        }
+    }
+}
+
+impl DrainGuard {
+    /// Wait until all outstanding requests are finished, returns `false` if it is timeout.
+    pub async fn wait(&self, timeout: Duration) -> bool {
+        let wait_drained = async {
+            loop {
+                let notified = self.registry.drained.notified();
+                if self.registry.len() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, wait_drained).await.is_ok()
+    }
+}
+
+impl Drop for DrainGuard {
+    fn drop(&mut self) {
+        self.registry.inner.lock().unwrap().draining -= 1;
+    }
+}
+
+impl InflightGuard {
+    /// Wait until the request is canceled.
+    pub async fn canceled(&mut self) -> Error {
+        while !*self.canceled.borrow() {
+            if self.canceled.changed().await.is_err() {
+                futures::future::pending::<()>().await;
+            }
+        }
+        Error::NotLeader(self.registry.group_id, 0, None)
+    }
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        self.registry.unregister(self.id);
+    }
+}
+
+fn describe_request(request: &Request) -> (&'static str, Option<u64>) {
+    match request {
+        Request::Get(req) => ("get", Some(req.shard_id)),
+        Request::Put(req) => ("put", Some(req.shard_id)),
+        Request::Delete(req) => ("delete", Some(req.shard_id)),
+        Request::DeleteRange(req) => ("delete_range", Some(req.shard_id)),
+        Request::Rename(req) => ("rename", Some(req.shard_id)),
+        Request::PrefixList(req) => ("prefix_list", Some(req.shard_id)),
+        Request::Scan(req) => ("scan", Some(req.shard_id)),
+        Request::Sample(req) => ("sample", Some(req.shard_id)),
+        Request::BatchWrite(_) => ("batch_write", None),
+        Request::CreateShard(req) => ("create_shard", req.shard.as_ref().map(|s| s.id)),
+        Request::ChangeReplicas(_) => ("change_replicas", None),
+        Request::AcceptShard(req) => ("accept_shard", req.shard_desc.as_ref().map(|s| s.id)),
+        Request::Transfer(_) => ("transfer", None),
+        Request::MoveReplicas(_) => ("move_replicas", None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engula_api::server::v1::ShardPrefixListRequest;
+
+    use super::*;
+    use crate::runtime::ExecutorOwner;
+
+    #[test]
+    fn drain_inflight_requests() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async {
+            let registry = Arc::new(InflightRequests::new(1));
+            let request = Request::PrefixList(ShardPrefixListRequest {
+                shard_id: 2,
+                prefix: vec![],
+            });
+            let guard = registry.register(&request).unwrap();
+            let requests = registry.requests();
+            assert_eq!(requests.len(), 1);
+            assert_eq!(requests[0].kind, "prefix_list");
+            assert_eq!(requests[0].shard_id, Some(2));
+
+            let drain = registry.start_draining();
+            assert!(!drain.wait(Duration::from_millis(1)).await);
+            assert!(matches!(
+                registry.register(&request),
+                Err(Error::NotLeader(1, 0, None))
+            ));
+            drop(guard);
+            assert!(drain.wait(Duration::from_millis(1)).await);
+
+            // The overlapped draining keeps rejecting new requests.
+            let other_drain = registry.start_draining();
+            drop(drain);
+            assert!(registry.register(&request).is_err());
+            drop(other_drain);
+
+            let mut guard = registry.register(&request).unwrap();
+            registry.cancel_all();
+            assert!(matches!(guard.canceled().await, Error::NotLeader(..)));
+        });
+    }
+}
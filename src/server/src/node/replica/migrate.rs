@@ -84,8 +84,13 @@ impl Replica {
 
         let mut wb = WriteBatch::default();
         for data in &chunk.data {
-            self.group_engine
-                .put(&mut wb, shard_id, &data.key, &data.value, data.version)?;
+            self.group_engine.put_migrated(
+                &mut wb,
+                shard_id,
+                &data.key,
+                &data.value,
+                data.version,
+            )?;
         }
 
         let sync_op = if !forwarded {
@@ -101,30 +106,7 @@ impl Replica {
                 data: wb.data().to_owned(),
             }),
             op: sync_op,
-        };
-        self.raft_node.clone().propose(eval_result).await?;
-
-        Ok(())
-    }
-
-    pub async fn delete_chunks(&self, shard_id: u64, keys: &[(Vec<u8>, u64)]) -> Result<()> {
-        if keys.is_empty() {
-            return Ok(());
-        }
-
-        let _acl_guard = self.take_read_acl_guard().await;
-        self.check_migrating_request_early(shard_id)?;
-
-        let mut wb = WriteBatch::default();
-        for (key, version) in keys {
-            self.group_engine.delete(&mut wb, shard_id, key, *version)?;
-        }
-
-        let eval_result = EvalResult {
-            batch: Some(WriteBatchRep {
-                data: wb.data().to_owned(),
-            }),
-            op: None,
+            ..Default::default()
         };
         self.raft_node.clone().propose(eval_result).await?;
 
@@ -176,6 +158,7 @@ impl Replica {
         let eval_result = EvalResult {
             batch: None,
             op: Some(sync_op),
+            ..Default::default()
         };
         self.raft_node.clone().propose(eval_result).await?;
 
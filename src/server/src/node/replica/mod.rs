@@ -12,8 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-mod eval;
+pub(crate) mod eval;
 pub mod fsm;
+mod inflight;
 mod migrate;
 pub mod retry;
 mod state;
@@ -21,16 +22,22 @@ mod state;
 use std::{
     sync::{atomic::AtomicI32, Arc, Mutex},
     task::Poll,
+    time::{Duration, Instant},
 };
 
 use engula_api::{
     server::v1::{group_request_union::Request, group_response_union::Response, *},
-    v1::{DeleteResponse, GetResponse, PutResponse},
+    v1::{DeleteResponse, GetResponse, PutResponse, WriteConcern},
 };
+use engula_client::RetryPolicy;
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use tracing::{info, warn};
 
-pub use self::state::{LeaseState, LeaseStateObserver};
+use self::inflight::InflightRequests;
+pub use self::{
+    inflight::InflightRequestInfo,
+    state::{LeaseState, LeaseStateObserver},
+};
 use super::engine::GroupEngine;
 pub use crate::raftgroup::RaftNodeFacade as RaftSender;
 use crate::{
@@ -64,6 +71,33 @@ pub struct ReplicaConfig {
     /// Default: 64MB.
     pub snap_file_size: u64,
 
+    /// Writes with larger keys are rejected with `Error::KeyTooLarge`, it should be larger than
+    /// the limit of clients since chunked values use longer keys.
+    ///
+    /// Default: 128KB.
+    #[serde(default = "default_max_key_size")]
+    pub max_key_size: usize,
+
+    /// Puts with larger values are rejected with `Error::ValueTooLarge`.
+    ///
+    /// Default: 128MB.
+    #[serde(default = "default_max_value_size")]
+    pub max_value_size: usize,
+
+    /// Batch writes whose total size of keys and values exceeds it are rejected with
+    /// `Error::BatchTooLarge`.
+    ///
+    /// Default: 256MB.
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+
+    /// The policy of retrying the requests rejected by busy or not ready replicas, the retries are
+    /// bounded by the deadline of requests too.
+    ///
+    /// Default: unlimited attempts, backoff from 200us to 50ms
+    #[serde(default = "default_retry_policy")]
+    pub retry: RetryPolicy,
+
     #[serde(skip)]
     pub testing_knobs: ReplicaTestingKnobs,
 }
@@ -73,6 +107,7 @@ pub struct ReplicaInfo {
     pub group_id: u64,
     pub node_id: u64,
     local_state: AtomicI32,
+    applied: tokio::sync::watch::Sender<u64>,
 }
 
 enum MetaAclGuard<'a> {
@@ -90,6 +125,11 @@ pub struct ExecCtx {
     pub forward_shard_id: Option<u64>,
     /// The epoch of `GroupDesc` carried in this request.
     pub epoch: u64,
+    /// The deadline of this request, retrying will be stopped once it is exceeded.
+    pub deadline: Option<Instant>,
+    /// The read-only request is served by the local replica without checking the leadership,
+    /// see `GroupRequest::stale_read`.
+    pub stale_read: bool,
 
     /// The migration desc, filled by `check_request_early`.
     migration_desc: Option<MigrationDesc>,
@@ -105,8 +145,13 @@ where
     lease_state: Arc<Mutex<LeaseState>>,
     move_replicas_provider: Arc<MoveReplicasProvider>,
     meta_acl: Arc<tokio::sync::RwLock<()>>,
+    inflight: Arc<InflightRequests>,
+    cfg: ReplicaConfig,
 }
 
+/// The max duration to wait for inflight requests before transferring leadership.
+const TRANSFER_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
 impl Replica {
     /// Create new instance of the specified raft node.
     pub async fn create(
@@ -138,7 +183,9 @@ impl Replica {
         raft_node: RaftNodeFacade,
         group_engine: GroupEngine,
         move_replicas_provider: Arc<MoveReplicasProvider>,
+        cfg: ReplicaConfig,
     ) -> Self {
+        let inflight = Arc::new(InflightRequests::new(info.group_id));
         Replica {
             info,
             group_engine,
@@ -146,6 +193,8 @@ impl Replica {
             lease_state,
             move_replicas_provider,
             meta_acl: Arc::default(),
+            inflight,
+            cfg,
         }
     }
 
@@ -153,6 +202,7 @@ impl Replica {
     pub async fn shutdown(&self, _actual_desc: &GroupDesc) -> Result<()> {
         // TODO(walter) check actual desc.
         self.info.terminate();
+        self.inflight.cancel_all();
         self.raft_node.clone().terminate();
 
         {
@@ -175,9 +225,10 @@ impl Replica {
             return Err(Error::GroupNotFound(self.info.group_id));
         }
 
+        self.check_request_size(request)?;
         let _acl_guard = self.take_acl_guard(request).await;
         self.check_request_early(exec_ctx, request)?;
-        self.evaluate_command(exec_ctx, request).await
+        self.evaluate_inflight_command(exec_ctx, request).await
     }
 
     /// Execute group request. instead of be blocked, it will returns `Error::ServiceIsBusy` if
@@ -191,11 +242,12 @@ impl Replica {
             return Err(Error::GroupNotFound(self.info.group_id));
         }
 
+        self.check_request_size(request)?;
         let _acl_guard = self
             .try_take_acl_guard(request)
             .ok_or(Error::ServiceIsBusy("try_take_acl_guard"))?;
         self.check_request_early(&mut exec_ctx, request)?;
-        self.evaluate_command(&exec_ctx, request).await
+        self.evaluate_inflight_command(&exec_ctx, request).await
     }
 
     pub async fn on_leader(&self, source: &'static str, immediate: bool) -> Result<Option<u64>> {
@@ -270,6 +322,32 @@ impl Replica {
         self.lease_state.lock().unwrap().schedule_state.clone()
     }
 
+    /// Returns the requests executing in this replica.
+    #[inline]
+    pub fn inflight_requests(&self) -> Vec<InflightRequestInfo> {
+        self.inflight.requests()
+    }
+
+    /// Propose a command to compute the checksum of the shard, the checksum is computed by all
+    /// replicas at the index of the command.
+    pub async fn compute_checksum(&self, shard_id: u64, checksum_id: u64) -> Result<()> {
+        self.check_leader_early()?;
+        let _acl_guard = self.take_read_acl_guard().await;
+        let eval_result = EvalResult {
+            op: Some(SyncOp::compute_checksum(shard_id, checksum_id)),
+            ..Default::default()
+        };
+        self.raft_node.clone().propose(eval_result).await?;
+        Ok(())
+    }
+
+    /// Returns the latest checksum of the shard computed by this replica.
+    #[inline]
+    pub fn shard_checksum(&self, shard_id: u64) -> Option<ShardChecksum> {
+        let lease_state = self.lease_state.lock().unwrap();
+        lease_state.checksums.get(&shard_id).cloned()
+    }
+
     pub async fn monitor(&self) -> Result<ReplicaPerfContext> {
         let take_acl_guard = perf_point_micros();
         let _acl_guard = self.take_read_acl_guard().await;
@@ -315,8 +393,63 @@ impl Replica {
         }
     }
 
-    /// Delegates the eval method for the given `Request`.
-    async fn evaluate_command(&self, exec_ctx: &ExecCtx, request: &Request) -> Result<Response> {
+    /// Register the request to the inflight registry and evaluate it, the evaluation is aborted
+    /// once the request is canceled before it is proposed.
+    async fn evaluate_inflight_command(
+        &self,
+        exec_ctx: &ExecCtx,
+        request: &Request,
+    ) -> Result<Response> {
+        if matches!(request, Request::Transfer(_)) {
+            // Transferring will wait for the inflight requests.
+            let (_, resp) = self.evaluate_command(exec_ctx, request).await?;
+            return Ok(resp);
+        }
+
+        let mut inflight = self.inflight.register(request)?;
+        let (eval_result, resp) = if is_change_meta_request(request) {
+            // The config changes are proposed during evaluation.
+            self.evaluate_command(exec_ctx, request).await?
+        } else {
+            tokio::select! {
+                result = self.evaluate_command(exec_ctx, request) => result?,
+                err = inflight.canceled() => return Err(err),
+            }
+        };
+        // Once handed to raft, the proposal might be committed even if this replica is shutting
+        // down, so it is waited for instead of being canceled; otherwise the client would retry
+        // a write which might be applied.
+        self.propose_command(exec_ctx, request, eval_result, resp)
+            .await
+    }
+
+    /// Transfer the leadership to the `transferee`, the inflight requests are drained before
+    /// transferring.
+    pub async fn transfer_leader(&self, transferee: u64) -> Result<()> {
+        info!(
+            replica = self.info.replica_id,
+            group = self.info.group_id,
+            "transfer leadership to {transferee}",
+        );
+        let drain = self.inflight.start_draining();
+        if !drain.wait(TRANSFER_DRAIN_TIMEOUT).await {
+            warn!(
+                replica = self.info.replica_id,
+                group = self.info.group_id,
+                "transfer leadership with {} inflight requests",
+                self.inflight.len()
+            );
+        }
+        self.raft_node.clone().transfer_leader(transferee)
+    }
+
+    /// Delegates the eval method for the given `Request`, returns the [`EvalResult`] to propose
+    /// and the response.
+    async fn evaluate_command(
+        &self,
+        exec_ctx: &ExecCtx,
+        request: &Request,
+    ) -> Result<(Option<EvalResult>, Response)> {
         let (eval_result_opt, resp) = match &request {
             Request::Get(req) => {
                 let value = eval::get(exec_ctx, &self.group_engine, req).await?;
@@ -325,29 +458,55 @@ impl Replica {
             }
             Request::Put(req) => {
                 let eval_result = eval::put(exec_ctx, &self.group_engine, req).await?;
-                (Some(eval_result), Response::Put(PutResponse {}))
+                (Some(eval_result), Response::Put(PutResponse::default()))
             }
             Request::Delete(req) => {
                 let eval_result = eval::delete(exec_ctx, &self.group_engine, req).await?;
                 (Some(eval_result), Response::Delete(DeleteResponse {}))
             }
+            Request::DeleteRange(req) => {
+                let eval_result = eval::delete_range(exec_ctx, req)?;
+                (
+                    Some(eval_result),
+                    Response::DeleteRange(DeleteRangeResponse {}),
+                )
+            }
+            Request::Rename(req) => {
+                let eval_result = eval::rename(exec_ctx, &self.group_engine, req).await?;
+                let resp = RenameResponse {
+                    renamed: eval_result.is_some(),
+                };
+                (eval_result, Response::Rename(resp))
+            }
             Request::PrefixList(req) => {
                 let eval_result = eval::prefix_list(&self.group_engine, req).await?;
                 (None, Response::PrefixList(eval_result))
             }
+            Request::Scan(req) => {
+                let eval_result = eval::scan(&self.group_engine, req).await?;
+                (None, Response::Scan(eval_result))
+            }
+            Request::Sample(req) => {
+                let eval_result = eval::sample(&self.group_engine, req).await?;
+                (None, Response::Sample(eval_result))
+            }
             Request::BatchWrite(req) => {
                 let eval_result = eval::batch_write(exec_ctx, &self.group_engine, req).await?;
                 (eval_result, Response::BatchWrite(BatchWriteResponse {}))
             }
             Request::CreateShard(req) => {
-                // TODO(walter) check the existing of shard.
                 let shard = req
                     .shard
                     .as_ref()
                     .cloned()
                     .ok_or_else(|| Error::InvalidArgument("CreateShard::shard".into()))?;
                 let resp = CreateShardResponse {};
-                (Some(eval::add_shard(shard)), Response::CreateShard(resp))
+                // The creation is retried by the root if the response is lost.
+                if self.group_engine.shard_desc(shard.id).is_ok() {
+                    (None, Response::CreateShard(resp))
+                } else {
+                    (Some(eval::add_shard(shard)), Response::CreateShard(resp))
+                }
             }
             Request::ChangeReplicas(req) => {
                 if let Some(change) = &req.change_replicas {
@@ -369,19 +528,35 @@ impl Replica {
                 (Some(eval_result), Response::AcceptShard(resp))
             }
             Request::Transfer(req) => {
-                info!(
-                    replica = self.info.replica_id,
-                    group = self.info.group_id,
-                    "transfer leadership to {}",
-                    req.transferee
-                );
-                self.raft_node.clone().transfer_leader(req.transferee)?;
-                return Ok(Response::Transfer(TransferResponse {}));
+                self.transfer_leader(req.transferee).await?;
+                (None, Response::Transfer(TransferResponse {}))
             }
         };
+        Ok((eval_result_opt, resp))
+    }
 
+    /// Propose the [`EvalResult`] and wait until it is applied.
+    async fn propose_command(
+        &self,
+        exec_ctx: &ExecCtx,
+        request: &Request,
+        eval_result_opt: Option<EvalResult>,
+        mut resp: Response,
+    ) -> Result<Response> {
         if let Some(eval_result) = eval_result_opt {
-            self.raft_node.clone().propose(eval_result).await?;
+            let index = self.raft_node.clone().propose(eval_result).await?;
+            if let Request::Put(ShardPutRequest { put: Some(put), .. }) = request {
+                if put.write_concern() == WriteConcern::Flushed {
+                    self.group_engine.flush_until(index)?;
+                }
+            }
+            if let Response::Put(put) = &mut resp {
+                // The index of raft log is used as the fencing token, it increases monotonically
+                // within the group, see `WriteCondition::group_id`.
+                put.epoch = exec_ctx.epoch;
+                put.fencing_token = index;
+                put.group_id = exec_ctx.group_id;
+            }
         }
 
         Ok(resp)
@@ -392,6 +567,16 @@ impl Replica {
         exec_ctx.group_id = group_id;
         exec_ctx.replica_id = self.info.replica_id;
         let lease_state = self.lease_state.lock().unwrap();
+        if exec_ctx.stale_read && is_read_only_request(req) {
+            // Followers and learners serve stale reads with the local state, the client only
+            // needs to know whether its descriptor is outdated.
+            return if exec_ctx.epoch < lease_state.descriptor.epoch {
+                Err(Error::EpochNotMatch(lease_state.descriptor.clone()))
+            } else {
+                Ok(())
+            };
+        }
+
         if !lease_state.is_raft_leader() {
             Err(Error::NotLeader(
                 group_id,
@@ -422,7 +607,72 @@ impl Replica {
         }
     }
 
-    fn check_leader_early(&self) -> Result<()> {
+    /// Reject the writes whose key, value or batch exceeds the limits of [`ReplicaConfig`], so
+    /// that oversized payloads don't fail deep in raft.
+    fn check_request_size(&self, request: &Request) -> Result<()> {
+        let check_key = |key: &[u8]| {
+            if key.len() > self.cfg.max_key_size {
+                Err(Error::KeyTooLarge(key.len(), self.cfg.max_key_size))
+            } else {
+                Ok(())
+            }
+        };
+        let check_put = |put: &ShardPutRequest| {
+            if let Some(put) = put.put.as_ref() {
+                check_key(&put.key)?;
+                if put.value.len() > self.cfg.max_value_size {
+                    return Err(Error::ValueTooLarge(
+                        put.value.len(),
+                        self.cfg.max_value_size,
+                    ));
+                }
+            }
+            Ok(())
+        };
+        let check_delete = |delete: &ShardDeleteRequest| {
+            delete
+                .delete
+                .as_ref()
+                .map(|d| check_key(&d.key))
+                .unwrap_or(Ok(()))
+        };
+        match request {
+            Request::Put(req) => check_put(req),
+            Request::Delete(req) => check_delete(req),
+            Request::DeleteRange(req) => {
+                check_key(&req.start)?;
+                check_key(&req.end)
+            }
+            Request::Rename(req) => {
+                check_key(&req.from)?;
+                check_key(&req.to)
+            }
+            Request::BatchWrite(req) => {
+                req.puts.iter().try_for_each(check_put)?;
+                req.deletes.iter().try_for_each(check_delete)?;
+                let put_size = req
+                    .puts
+                    .iter()
+                    .filter_map(|p| p.put.as_ref())
+                    .map(|p| p.key.len() + p.value.len())
+                    .sum::<usize>();
+                let delete_size = req
+                    .deletes
+                    .iter()
+                    .filter_map(|d| d.delete.as_ref())
+                    .map(|d| d.key.len())
+                    .sum::<usize>();
+                let batch_size = put_size + delete_size;
+                if batch_size > self.cfg.max_batch_size {
+                    return Err(Error::BatchTooLarge(batch_size, self.cfg.max_batch_size));
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_leader_early(&self) -> Result<()> {
         let lease_state = self.lease_state.lock().unwrap();
         if !lease_state.is_ready_for_serving() {
             Err(Error::NotLeader(
@@ -440,11 +690,13 @@ impl ReplicaInfo {
     pub fn new(replica_desc: &ReplicaDesc, group_id: u64, local_state: ReplicaLocalState) -> Self {
         let replica_id = replica_desc.id;
         let node_id = replica_desc.node_id;
+        let (applied, _) = tokio::sync::watch::channel(0);
         ReplicaInfo {
             replica_id,
             node_id,
             group_id,
             local_state: AtomicI32::new(local_state.into()),
+            applied,
         }
     }
 
@@ -476,6 +728,9 @@ impl ReplicaInfo {
                 Err(new_state) => local_state = new_state,
             }
         }
+
+        // Wake up the subscribers, so they could observe the terminated state.
+        self.applied.send_modify(|_| {});
     }
 
     pub fn as_normal_state(&self) {
@@ -486,6 +741,17 @@ impl ReplicaInfo {
         self.local_state
             .store(ReplicaLocalState::Normal as i32, Ordering::SeqCst);
     }
+
+    /// Subscribe the applied index of the replica, the subscribers are notified once the applied
+    /// writes are committed to the group engine.
+    pub fn subscribe_applied(&self) -> tokio::sync::watch::Receiver<u64> {
+        self.applied.subscribe()
+    }
+
+    #[inline]
+    pub(crate) fn advance_applied(&self, index: u64) {
+        self.applied.send_replace(index);
+    }
 }
 
 impl ExecCtx {
@@ -496,6 +762,13 @@ impl ExecCtx {
         }
     }
 
+    pub fn with_deadline(deadline: Option<Instant>) -> Self {
+        ExecCtx {
+            deadline,
+            ..Default::default()
+        }
+    }
+
     pub fn forward(shard_id: u64) -> Self {
         ExecCtx {
             forward_shard_id: Some(shard_id),
@@ -521,11 +794,35 @@ impl Default for ReplicaConfig {
     fn default() -> Self {
         ReplicaConfig {
             snap_file_size: 64 * 1024 * 1024 * 1024,
+            max_key_size: default_max_key_size(),
+            max_value_size: default_max_value_size(),
+            max_batch_size: default_max_batch_size(),
+            retry: default_retry_policy(),
             testing_knobs: ReplicaTestingKnobs::default(),
         }
     }
 }
 
+fn default_max_key_size() -> usize {
+    128 * 1024
+}
+
+fn default_max_value_size() -> usize {
+    128 * 1024 * 1024
+}
+
+fn default_max_batch_size() -> usize {
+    256 * 1024 * 1024
+}
+
+fn default_retry_policy() -> RetryPolicy {
+    RetryPolicy {
+        initial_backoff_us: 200,
+        max_backoff_us: 50_000,
+        ..Default::default()
+    }
+}
+
 pub(self) fn is_change_meta_request(request: &Request) -> bool {
     match request {
         Request::ChangeReplicas(_)
@@ -536,7 +833,18 @@ pub(self) fn is_change_meta_request(request: &Request) -> bool {
         Request::Get(_)
         | Request::Put(_)
         | Request::Delete(_)
+        | Request::DeleteRange(_)
+        | Request::Rename(_)
         | Request::BatchWrite(_)
-        | Request::PrefixList(_) => false,
+        | Request::PrefixList(_)
+        | Request::Scan(_)
+        | Request::Sample(_) => false,
     }
 }
+
+pub(self) fn is_read_only_request(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::Get(_) | Request::PrefixList(_) | Request::Scan(_) | Request::Sample(_)
+    )
+}
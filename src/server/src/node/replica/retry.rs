@@ -12,16 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::Duration;
-
 use engula_api::{
     server::v1::{group_request_union::Request, *},
     shard,
 };
+use engula_client::Backoff;
 
 use super::{ExecCtx, Replica};
 use crate::{
-    node::{metrics::NODE_RETRY_TOTAL, migrate::MigrateController},
+    node::{metrics::take_retry_metrics, migrate::MigrateController},
     Error, Result,
 };
 
@@ -53,6 +52,7 @@ async fn execute_internal(
 ) -> Result<GroupResponse> {
     let mut exec_ctx = exec_ctx.clone();
     exec_ctx.epoch = request.epoch;
+    exec_ctx.stale_read = request.stale_read;
 
     let request = request
         .request
@@ -60,7 +60,8 @@ async fn execute_internal(
         .and_then(|request| request.request.as_ref())
         .ok_or_else(|| Error::InvalidArgument("GroupRequest::request is None".into()))?;
 
-    // TODO(walter) detect group request timeout.
+    let group_id = replica.replica_info().group_id;
+    let mut backoff = Backoff::new(&replica.cfg.retry, exec_ctx.deadline);
     let mut freshed_descriptor = None;
     loop {
         exec_ctx.reset();
@@ -81,24 +82,38 @@ async fn execute_internal(
                     panic!("receive forward response but no migration controller set");
                 }
             }
-            Err(Error::ServiceIsBusy(_)) | Err(Error::GroupNotReady(_)) => {
+            Err(err @ Error::ServiceIsBusy(_)) | Err(err @ Error::GroupNotReady(_)) => {
                 // sleep and retry.
-                NODE_RETRY_TOTAL.inc();
-                crate::runtime::time::sleep(Duration::from_micros(200)).await;
+                let kind = if matches!(err, Error::ServiceIsBusy(_)) {
+                    "service_busy"
+                } else {
+                    "group_not_ready"
+                };
+                take_retry_metrics(group_id, kind);
+                match backoff.next_delay() {
+                    Some(delay) => crate::runtime::time::sleep(delay).await,
+                    None if backoff.is_expired() => {
+                        return Err(Error::DeadlineExceeded(format!(
+                            "group {group_id} retry request: {err}"
+                        )))
+                    }
+                    // The attempts are exhausted.
+                    None => return Err(err),
+                }
             }
             Err(Error::EpochNotMatch(desc)) => {
                 if is_executable(&desc, request) {
                     debug_assert_ne!(desc.epoch, exec_ctx.epoch);
                     exec_ctx.epoch = desc.epoch;
                     freshed_descriptor = Some(desc);
-                    NODE_RETRY_TOTAL.inc();
+                    take_retry_metrics(group_id, "epoch_not_match");
                     continue;
                 }
 
                 return Err(Error::EpochNotMatch(desc));
             }
             Err(Error::ShardNotFound(shard_id)) => {
-                if exec_ctx.forward_shard_id.is_none() {
+                if exec_ctx.forward_shard_id.is_none() && !exec_ctx.stale_read {
                     panic!(
                         "shard {shard_id} is not found in group {} for serving request {request:?} epoch {}",
                         replica.replica_info().group_id,
@@ -107,8 +122,9 @@ async fn execute_internal(
                 }
 
                 // This is forwarding request and the target shard might be migrated to another
-                // group. Return `EpochNotMatch` in this case to enforce client retrying with fresh
-                // group descriptor.
+                // group, or it is a stale read and the local replica lags behind. Return
+                // `EpochNotMatch` in this case to enforce client retrying with fresh group
+                // descriptor.
                 //
                 // NOTES: the `accurate_epoch` should set to `true` for forwarding requests.
                 return Err(Error::EpochNotMatch(replica.descriptor()));
@@ -133,6 +149,13 @@ fn is_executable(descriptor: &GroupDesc, request: &Request) -> bool {
             Request::PrefixList(req) => {
                 is_target_shard_exists(descriptor, req.shard_id, &req.prefix)
             }
+            Request::Scan(req) => descriptor.shards.iter().any(|s| s.id == req.shard_id),
+            Request::DeleteRange(req) => descriptor.shards.iter().any(|s| s.id == req.shard_id),
+            Request::Sample(req) => descriptor.shards.iter().any(|s| s.id == req.shard_id),
+            Request::Rename(req) => {
+                is_target_shard_exists(descriptor, req.shard_id, &req.from)
+                    && is_target_shard_exists(descriptor, req.shard_id, &req.to)
+            }
             Request::BatchWrite(req) => {
                 for delete in &req.deletes {
                     if !is_target_shard_exists(
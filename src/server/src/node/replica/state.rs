@@ -19,7 +19,7 @@ use std::{
 };
 
 use engula_api::server::v1::{
-    GroupDesc, MigrationDesc, RaftRole, ReplicaDesc, ReplicaState, ScheduleState,
+    GroupDesc, MigrationDesc, RaftRole, ReplicaDesc, ReplicaState, ScheduleState, ShardChecksum,
 };
 use futures::channel::mpsc;
 use tracing::info;
@@ -40,6 +40,8 @@ pub struct LeaseState {
     pub migration_state_subscriber: mpsc::UnboundedSender<MigrationState>,
     pub schedule_state: ScheduleState,
     pub leader_subscribers: HashMap<&'static str, Waker>,
+    /// The latest computed checksum of each shard.
+    pub checksums: HashMap</* shard_id */ u64, ShardChecksum>,
 }
 
 /// A struct that observes changes to `GroupDesc` and `ReplicaState` , and broadcasts those changes
@@ -66,6 +68,7 @@ impl LeaseState {
             schedule_state: ScheduleState::default(),
             replica_state: ReplicaState::default(),
             leader_subscribers: HashMap::default(),
+            checksums: HashMap::default(),
         }
     }
 
@@ -234,6 +237,14 @@ impl StateMachineObserver for LeaseStateObserver {
             }
         }
     }
+
+    fn checksum_reporter(&self) -> Box<dyn FnOnce(ShardChecksum) + Send> {
+        let lease_state = self.lease_state.clone();
+        Box::new(move |checksum| {
+            let mut lease_state = lease_state.lock().unwrap();
+            lease_state.checksums.insert(checksum.shard_id, checksum);
+        })
+    }
 }
 
 impl ScheduleStateObserver for LeaseStateObserver {
@@ -0,0 +1,201 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeMap,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use engula_api::{chunk::is_chunk_key, server::v1::*, shard};
+use futures::{stream::BoxStream, StreamExt};
+use tokio::sync::watch;
+
+use super::{engine::SnapshotMode, Replica};
+use crate::{Error, Result};
+
+/// `KeyWatchStream` streams the changes of a key, or of the keys with a prefix, once they are
+/// applied by the leader replica.
+///
+/// The watched values are read again whenever the group applies new entries, and only the
+/// differences to the values sent before are streamed, so it is intended for small sets of
+/// config-style keys. The chunks of large values are skipped, the manifests saved in the user
+/// keys are streamed instead.
+pub struct KeyWatchStream {
+    stream: BoxStream<'static, std::result::Result<WatchKeyResponse, tonic::Status>>,
+}
+
+struct WatchState {
+    shard_id: u64,
+    key: Vec<u8>,
+    prefix: bool,
+    replica: Arc<Replica>,
+    applied: watch::Receiver<u64>,
+    /// The values sent to the watcher, `None` if nothing is sent yet.
+    values: Option<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl KeyWatchStream {
+    pub fn new(replica: Arc<Replica>, request: WatchKeyRequest) -> Result<Self> {
+        let applied = replica.replica_info().subscribe_applied();
+        let state = WatchState {
+            shard_id: request.shard_id,
+            key: request.key,
+            prefix: request.prefix,
+            replica,
+            applied,
+            values: None,
+        };
+        state.check_serving()?;
+        // The in-flight future is kept by the stream across polls, so the waker registered on the
+        // applied index isn't dropped.
+        let stream = futures::stream::unfold(state, |mut state| async move {
+            let resp = state.next_changes().await.map_err(Into::into);
+            Some((resp, state))
+        });
+        Ok(KeyWatchStream {
+            stream: stream.boxed(),
+        })
+    }
+}
+
+impl WatchState {
+    /// Check that this replica is still the leader and the watched key still belongs to the shard
+    /// of this group.
+    fn check_serving(&self) -> Result<()> {
+        let info = self.replica.replica_info();
+        if info.is_terminated() {
+            return Err(Error::GroupNotFound(info.group_id));
+        }
+        self.replica.check_leader_early()?;
+        let desc = self.replica.descriptor();
+        let owned = desc
+            .shards
+            .iter()
+            .find(|s| s.id == self.shard_id)
+            .map(|s| shard::belong_to(s, &self.key))
+            .unwrap_or_default();
+        if !owned {
+            // The shard is moved out of the group, the watcher should resolve it again.
+            return Err(Error::EpochNotMatch(desc));
+        }
+        Ok(())
+    }
+
+    fn read_values(&self) -> Result<BTreeMap<Vec<u8>, Vec<u8>>> {
+        let snapshot_mode = if self.prefix {
+            SnapshotMode::Prefix { key: &self.key }
+        } else {
+            SnapshotMode::Key { key: &self.key }
+        };
+        let engine = self.replica.group_engine();
+        let mut snapshot = engine.snapshot(self.shard_id, snapshot_mode)?;
+        let mut values = BTreeMap::new();
+        for mvcc_iter in snapshot.iter() {
+            let mut mvcc_iter = mvcc_iter?;
+            if let Some(entry) = mvcc_iter.next() {
+                let entry = entry?;
+                match entry.value() {
+                    Some(value) if !is_chunk_key(entry.user_key()) => {
+                        values.insert(entry.user_key().to_owned(), value.to_owned());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    async fn next_changes(&mut self) -> Result<WatchKeyResponse> {
+        loop {
+            self.check_serving()?;
+            let version = *self.applied.borrow_and_update();
+            let values = self.read_values()?;
+            let (changes, first) = match self.values.as_ref() {
+                Some(last) => (diff_values(last, &values), false),
+                None => (diff_values(&BTreeMap::default(), &values), true),
+            };
+            self.values = Some(values);
+            if first || !changes.is_empty() {
+                return Ok(WatchKeyResponse { changes, version });
+            }
+            if self.applied.changed().await.is_err() {
+                return Err(Error::GroupNotFound(self.replica.replica_info().group_id));
+            }
+        }
+    }
+}
+
+impl futures::Stream for KeyWatchStream {
+    type Item = std::result::Result<WatchKeyResponse, tonic::Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.stream.poll_next_unpin(cx)
+    }
+}
+
+fn diff_values(
+    last: &BTreeMap<Vec<u8>, Vec<u8>>,
+    values: &BTreeMap<Vec<u8>, Vec<u8>>,
+) -> Vec<KeyChange> {
+    let mut changes = values
+        .iter()
+        .filter(|(key, value)| last.get(*key) != Some(*value))
+        .map(|(key, value)| KeyChange {
+            key: key.clone(),
+            value: value.clone(),
+            deleted: false,
+        })
+        .collect::<Vec<_>>();
+    changes.extend(
+        last.keys()
+            .filter(|key| !values.contains_key(*key))
+            .map(|key| KeyChange {
+                key: key.clone(),
+                value: vec![],
+                deleted: true,
+            }),
+    );
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_watched_values() {
+        let kv = |k: &[u8], v: &[u8]| (k.to_vec(), v.to_vec());
+        let last = BTreeMap::from([kv(b"a", b"1"), kv(b"b", b"2"), kv(b"c", b"3")]);
+        let values = BTreeMap::from([kv(b"a", b"1"), kv(b"b", b"4"), kv(b"d", b"5")]);
+        let changes = diff_values(&last, &values);
+        let changes = changes
+            .iter()
+            .map(|c| (c.key.as_slice(), c.value.as_slice(), c.deleted))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (b"b".as_slice(), b"4".as_slice(), false),
+                (b"d".as_slice(), b"5".as_slice(), false),
+                (b"c".as_slice(), b"".as_slice(), true),
+            ]
+        );
+
+        let changes = diff_values(&BTreeMap::default(), &values);
+        assert_eq!(changes.len(), 3);
+        assert!(diff_values(&values, &values).is_empty());
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use engula_api::server::v1::{group_request_union::Request, GroupDesc};
+use engula_client::Router;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::Result;
+
+const WEBHOOK_CF_NAME: &str = "webhook";
+
+/// The kind of write notified by webhooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WebhookOp {
+    Put,
+    Delete,
+    DeleteRange,
+    Rename,
+}
+
+/// The body POSTed to the webhook of a collection, the keys are hex encoded. The values aren't
+/// included, the receivers are expected to read the latest values if needed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct WebhookEvent {
+    pub collection_id: u64,
+    pub op: WebhookOp,
+    pub key: String,
+    /// The exclusive end of `DeleteRange`, empty means the end of the shard.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub end: String,
+    /// The target key of `Rename`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub to: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct QueuedEvent {
+    pub url: String,
+    pub event: WebhookEvent,
+}
+
+/// A persisted queue of the webhook events to deliver, ordered by the sequence of enqueuing.
+///
+/// The events are enqueued before the writes are proposed, so that each committed write is
+/// delivered at least once even if the node crashes. A write that fails after that might be
+/// notified as well, which is harmless for invalidating caches.
+pub(crate) struct WebhookQueue {
+    raw_db: Arc<rocksdb::DB>,
+    next_seq: AtomicU64,
+    notify: Notify,
+}
+
+impl WebhookQueue {
+    pub fn open(raw_db: Arc<rocksdb::DB>) -> Result<Self> {
+        if raw_db.cf_handle(WEBHOOK_CF_NAME).is_none() {
+            let mut opts = rocksdb::Options::default();
+            opts.create_missing_column_families(true);
+            raw_db.create_cf(WEBHOOK_CF_NAME, &opts)?;
+        }
+        let next_seq = {
+            let cf_handle = raw_db
+                .cf_handle(WEBHOOK_CF_NAME)
+                .expect("webhook column family");
+            match raw_db
+                .iterator_cf(&cf_handle, rocksdb::IteratorMode::End)
+                .next()
+            {
+                Some(item) => decode_seq(&item?.0) + 1,
+                None => 0,
+            }
+        };
+        Ok(WebhookQueue {
+            raw_db,
+            next_seq: AtomicU64::new(next_seq),
+            notify: Notify::new(),
+        })
+    }
+
+    /// Enqueue the events of the writes of the request, whose collections have webhooks.
+    pub fn enqueue_writes(
+        &self,
+        router: &Router,
+        desc: &GroupDesc,
+        request: &Request,
+    ) -> Result<()> {
+        let events = collect_events(router, desc, request);
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let cf_handle = self
+            .raw_db
+            .cf_handle(WEBHOOK_CF_NAME)
+            .expect("webhook column family");
+        let mut wb = rocksdb::WriteBatch::default();
+        for event in &events {
+            let seq = self.next_seq.fetch_add(1, Ordering::AcqRel);
+            let value = serde_json::to_vec(event).expect("QueuedEvent is serializable");
+            wb.put_cf(&cf_handle, seq.to_be_bytes(), value);
+        }
+        let mut opts = rocksdb::WriteOptions::default();
+        opts.set_sync(true);
+        self.raw_db.write_opt(wb, &opts)?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Returns at most `limit` pending events in the order of enqueuing.
+    pub fn pending(&self, limit: usize) -> Result<Vec<(u64, QueuedEvent)>> {
+        let cf_handle = self
+            .raw_db
+            .cf_handle(WEBHOOK_CF_NAME)
+            .expect("webhook column family");
+        let mut events = vec![];
+        for item in self
+            .raw_db
+            .iterator_cf(&cf_handle, rocksdb::IteratorMode::Start)
+            .take(limit)
+        {
+            let (key, value) = item?;
+            let event = serde_json::from_slice(&value).expect("valid QueuedEvent format");
+            events.push((decode_seq(&key), event));
+        }
+        Ok(events)
+    }
+
+    /// Remove the delivered event.
+    pub fn ack(&self, seq: u64) -> Result<()> {
+        let cf_handle = self
+            .raw_db
+            .cf_handle(WEBHOOK_CF_NAME)
+            .expect("webhook column family");
+        self.raw_db.delete_cf(&cf_handle, seq.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Wait until new events are enqueued.
+    pub async fn wait(&self) {
+        self.notify.notified().await;
+    }
+}
+
+/// Returns whether the request writes keys, which might be notified by webhooks.
+pub(crate) fn is_write_request(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::Put(_)
+            | Request::Delete(_)
+            | Request::DeleteRange(_)
+            | Request::Rename(_)
+            | Request::BatchWrite(_)
+    )
+}
+
+fn decode_seq(key: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&key[..8]);
+    u64::from_be_bytes(buf)
+}
+
+fn collect_events(router: &Router, desc: &GroupDesc, request: &Request) -> Vec<QueuedEvent> {
+    let mut writes: Vec<(u64, WebhookOp, &[u8], &[u8])> = vec![];
+    match request {
+        Request::Put(req) => {
+            if let Some(put) = req.put.as_ref() {
+                writes.push((req.shard_id, WebhookOp::Put, put.key.as_slice(), &[]));
+            }
+        }
+        Request::Delete(req) => {
+            if let Some(delete) = req.delete.as_ref() {
+                writes.push((req.shard_id, WebhookOp::Delete, delete.key.as_slice(), &[]));
+            }
+        }
+        Request::DeleteRange(req) => {
+            writes.push((
+                req.shard_id,
+                WebhookOp::DeleteRange,
+                req.start.as_slice(),
+                req.end.as_slice(),
+            ));
+        }
+        Request::Rename(req) => {
+            writes.push((
+                req.shard_id,
+                WebhookOp::Rename,
+                req.from.as_slice(),
+                req.to.as_slice(),
+            ));
+        }
+        Request::BatchWrite(req) => {
+            for delete in &req.deletes {
+                if let Some(d) = delete.delete.as_ref() {
+                    writes.push((delete.shard_id, WebhookOp::Delete, d.key.as_slice(), &[]));
+                }
+            }
+            for put in &req.puts {
+                if let Some(p) = put.put.as_ref() {
+                    writes.push((put.shard_id, WebhookOp::Put, p.key.as_slice(), &[]));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let mut events = vec![];
+    for (shard_id, op, key, extra) in writes {
+        let collection_id = match desc.shards.iter().find(|s| s.id == shard_id) {
+            Some(shard) => shard.collection_id,
+            None => continue,
+        };
+        let url = match router.find_collection(collection_id) {
+            Ok(co) if !co.webhook_url.is_empty() => co.webhook_url,
+            _ => continue,
+        };
+        let (end, to) = match op {
+            WebhookOp::DeleteRange => (to_hex(extra), String::new()),
+            WebhookOp::Rename => (String::new(), to_hex(extra)),
+            _ => (String::new(), String::new()),
+        };
+        events.push(QueuedEvent {
+            url,
+            event: WebhookEvent {
+                collection_id,
+                op,
+                key: to_hex(key),
+                end,
+                to,
+            },
+        });
+    }
+    events
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::bootstrap::open_engine_with_default_config;
+
+    fn event(key: &str) -> QueuedEvent {
+        QueuedEvent {
+            url: "http://127.0.0.1/hook".to_owned(),
+            event: WebhookEvent {
+                collection_id: 1,
+                op: WebhookOp::Put,
+                key: key.to_owned(),
+                end: String::new(),
+                to: String::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn webhook_queue_persisted_in_order() {
+        let dir = TempDir::new("webhook_queue_persisted_in_order").unwrap();
+        let raw_db = Arc::new(open_engine_with_default_config(dir.path()).unwrap());
+        let queue = WebhookQueue::open(raw_db.clone()).unwrap();
+        let cf_handle = raw_db.cf_handle(WEBHOOK_CF_NAME).unwrap();
+        for (seq, key) in ["61", "62", "63"].iter().enumerate() {
+            let value = serde_json::to_vec(&event(key)).unwrap();
+            raw_db
+                .put_cf(&cf_handle, (seq as u64).to_be_bytes(), value)
+                .unwrap();
+        }
+        drop(cf_handle);
+
+        let pending = queue.pending(2).unwrap();
+        assert_eq!(pending, vec![(0, event("61")), (1, event("62"))]);
+        queue.ack(0).unwrap();
+        drop(queue);
+
+        // The pending events survive restarting, and the sequence continues.
+        let queue = WebhookQueue::open(raw_db).unwrap();
+        assert_eq!(queue.next_seq.load(Ordering::Acquire), 3);
+        let pending = queue.pending(8).unwrap();
+        assert_eq!(pending, vec![(1, event("62")), (2, event("63"))]);
+    }
+}
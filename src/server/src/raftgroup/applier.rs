@@ -28,14 +28,14 @@ use super::{fsm::StateMachine, monitor::ApplierPerfContext, storage::Storage, Ap
 use crate::{
     raftgroup::{metrics::*, monitor::record_perf_point},
     record_latency,
-    serverpb::v1::{EntryId, EvalResult},
+    serverpb::v1::EntryId,
     Error, Result,
 };
 
 struct ProposalContext {
     index: u64,
     term: u64,
-    sender: oneshot::Sender<Result<()>>,
+    sender: oneshot::Sender<Result<u64>>,
 }
 
 /// Cache the descriptor of other replicas in the same group.
@@ -49,6 +49,8 @@ pub struct Applier<M: StateMachine> {
     group_id: u64,
 
     proposal_queue: VecDeque<ProposalContext>,
+    /// The proposals which are skipped since the conditions aren't satisfied.
+    rejected_proposals: HashMap<u64, Error>,
 
     next_read_state_index: usize,
     read_requests: HashMap<Vec<u8>, Vec<oneshot::Sender<Result<()>>>>,
@@ -65,6 +67,7 @@ impl<M: StateMachine> Applier<M> {
         Applier {
             group_id,
             proposal_queue: VecDeque::default(),
+            rejected_proposals: HashMap::default(),
             next_read_state_index: 0,
             read_requests: HashMap::default(),
             read_states: Vec::default(),
@@ -78,7 +81,7 @@ impl<M: StateMachine> Applier<M> {
         &mut self,
         index: u64,
         term: u64,
-        sender: oneshot::Sender<Result<()>>,
+        sender: oneshot::Sender<Result<u64>>,
     ) {
         let ctx = ProposalContext {
             index,
@@ -229,22 +232,24 @@ impl<M: StateMachine> Applier<M> {
     }
 
     fn apply_normal_entry(&mut self, entry: Entry) {
-        use prost::Message;
-
         assert!(matches!(entry.get_entry_type(), EntryType::EntryNormal));
 
-        let eval_result = EvalResult::decode(&*entry.data).expect("Entry::data is EvalResult");
+        let eval_result = super::codec::decode_proposal(&entry);
+        let apply_entry = match self.state_machine.check_condition(&eval_result) {
+            Ok(()) => ApplyEntry::Proposal { eval_result },
+            Err(err) => {
+                self.rejected_proposals.insert(entry.index, err);
+                ApplyEntry::Empty
+            }
+        };
         self.state_machine
-            .apply(
-                entry.index,
-                entry.term,
-                ApplyEntry::Proposal { eval_result },
-            )
+            .apply(entry.index, entry.term, apply_entry)
             .expect("apply normal entry");
     }
 
     #[inline]
     fn response_proposal(&mut self, index: u64, term: u64) {
+        let rejected = self.rejected_proposals.remove(&index);
         if self
             .proposal_queue
             .front()
@@ -253,8 +258,11 @@ impl<M: StateMachine> Applier<M> {
         {
             let ctx = self.proposal_queue.pop_front().unwrap();
             if ctx.term == term {
-                // TODO(walter) support user defined result.
-                ctx.sender.send(Ok(())).unwrap_or_default();
+                let result = match rejected {
+                    Some(err) => Err(err),
+                    None => Ok(index),
+                };
+                ctx.sender.send(result).unwrap_or_default();
             } else {
                 ctx.sender
                     .send(Err(Error::NotLeader(self.group_id, term, None)))
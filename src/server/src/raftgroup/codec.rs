@@ -0,0 +1,92 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+
+use prost::Message;
+use raft::prelude::Entry;
+
+use crate::serverpb::v1::EvalResult;
+
+/// The context of normal entries whose data are compressed by zstd. The entries proposed without
+/// compression have empty context, so that they are still readable by the replicas which don't
+/// know entry compression.
+const ENTRY_CONTEXT_ZSTD: &[u8] = b"zstd";
+
+const ENTRY_COMPRESSION_LEVEL: i32 = 1;
+
+/// Encode the proposal into the data and context of a normal entry. The data is compressed if its
+/// size exceeds `compression_threshold`, and 0 means never.
+pub fn encode_proposal(
+    eval_result: &EvalResult,
+    compression_threshold: usize,
+) -> (Vec<u8>, Vec<u8>) {
+    let data = eval_result.encode_to_vec();
+    if compression_threshold == 0 || data.len() <= compression_threshold {
+        return (data, vec![]);
+    }
+    match zstd::bulk::compress(&data, ENTRY_COMPRESSION_LEVEL) {
+        Ok(compressed) if compressed.len() < data.len() => {
+            (compressed, ENTRY_CONTEXT_ZSTD.to_vec())
+        }
+        _ => (data, vec![]),
+    }
+}
+
+/// Returns the data of a normal entry, which is decompressed if required.
+pub fn entry_data(entry: &Entry) -> std::io::Result<Cow<'_, [u8]>> {
+    if entry.context.as_slice() == ENTRY_CONTEXT_ZSTD {
+        Ok(Cow::Owned(zstd::stream::decode_all(entry.data.as_slice())?))
+    } else {
+        Ok(Cow::Borrowed(entry.data.as_slice()))
+    }
+}
+
+/// Decode the proposal from a normal entry.
+pub fn decode_proposal(entry: &Entry) -> EvalResult {
+    let data = entry_data(entry).expect("Entry::data is compressed by zstd");
+    EvalResult::decode(&*data).expect("Entry::data is EvalResult")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serverpb::v1::WriteBatchRep;
+
+    fn eval_result(len: usize) -> EvalResult {
+        EvalResult {
+            batch: Some(WriteBatchRep {
+                data: vec![b'a'; len],
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compress_large_proposal() {
+        for (len, threshold, compressed) in
+            [(1024, 0, false), (1024, 4096, false), (4096, 1024, true)]
+        {
+            let expect = eval_result(len);
+            let (data, context) = encode_proposal(&expect, threshold);
+            assert_eq!(!context.is_empty(), compressed);
+            let entry = Entry {
+                data,
+                context,
+                ..Default::default()
+            };
+            assert_eq!(decode_proposal(&entry), expect);
+        }
+    }
+}
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::Instant;
+use std::{sync::Arc, time::Instant};
 
 use engula_api::server::v1::ChangeReplicas;
 use futures::channel::{mpsc, oneshot};
@@ -20,7 +20,7 @@ use futures::channel::{mpsc, oneshot};
 use super::{
     metrics::*,
     worker::{RaftGroupState, Request},
-    ReadPolicy, WorkerPerfContext,
+    RaftGroupHealth, RaftGroupStats, ReadPolicy, WorkerPerfContext,
 };
 use crate::{
     record_latency,
@@ -35,23 +35,24 @@ where
     Self: Send,
 {
     request_sender: mpsc::Sender<Request>,
+    stats: Arc<RaftGroupStats>,
 }
 
 impl RaftNodeFacade {
     /// Open the existed raft node.
-    pub fn open(sender: mpsc::Sender<Request>) -> Self {
+    pub fn open(sender: mpsc::Sender<Request>, stats: Arc<RaftGroupStats>) -> Self {
         RaftNodeFacade {
             request_sender: sender,
+            stats,
         }
     }
 
     /// Submit a data to replicate, and returns corresponding future value.
     ///
     /// Once the data is applied to the [`StateMachine`], the value of future will be set to
-    /// [`Ok(())`]. The future is set to specific error if the data cannot be applied.
-    ///
-    /// TODO(walter) support return user defined error.
-    pub async fn propose(&mut self, eval_result: EvalResult) -> Result<()> {
+    /// the index of the applied entry. The future is set to specific error if the data cannot be
+    /// applied, or the conditions are rejected by [`StateMachine::check_condition`].
+    pub async fn propose(&mut self, eval_result: EvalResult) -> Result<u64> {
         let start_at = Instant::now();
         let (sender, receiver) = oneshot::channel();
 
@@ -62,7 +63,9 @@ impl RaftNodeFacade {
         };
 
         self.send(request)?;
-        take_propose_metrics(start_at, receiver.await?)
+        let result = take_propose_metrics(start_at, receiver.await?);
+        self.stats.on_proposed(start_at.elapsed(), result.is_ok());
+        result
     }
 
     /// Execute reading operations with the specified read policy.
@@ -94,7 +97,8 @@ impl RaftNodeFacade {
         let request = Request::ChangeConfig { change, sender };
         self.send(request)?;
 
-        receiver.await?
+        receiver.await??;
+        Ok(())
     }
 
     pub async fn raft_group_state(&mut self) -> Option<RaftGroupState> {
@@ -111,6 +115,12 @@ impl RaftNodeFacade {
         }
     }
 
+    /// Return the raft level statistics and health score of this group.
+    #[inline]
+    pub fn health(&self) -> RaftGroupHealth {
+        self.stats.health()
+    }
+
     pub fn report_unreachable(&mut self, target_id: u64) {
         RAFTGROUP_UNREACHABLE_TOTAL.inc();
         self.send(Request::Unreachable { target_id })
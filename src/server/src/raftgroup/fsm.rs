@@ -32,6 +32,12 @@ pub enum ApplyEntry {
 pub trait StateMachine: Send {
     fn start_plug(&mut self) -> Result<()>;
     fn apply(&mut self, index: u64, term: u64, entry: ApplyEntry) -> Result<()>;
+
+    /// Check the conditions of the proposal against the applied states, the proposal is skipped
+    /// and the error is returned to the proposer if the conditions aren't satisfied.
+    fn check_condition(&self, _eval_result: &EvalResult) -> Result<()> {
+        Ok(())
+    }
     fn finish_plug(&mut self) -> Result<()>;
 
     fn apply_snapshot(&mut self, snap_dir: &Path) -> Result<()>;
@@ -126,6 +126,11 @@ lazy_static! {
         "The total bytes of send snapshot of raftgroup",
     )
     .unwrap();
+    pub static ref RAFTGROUP_SEND_SNAPSHOT_SAVED_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "raftgroup_send_snapshot_saved_bytes_total",
+        "The total bytes of send snapshot of raftgroup saved by compression and dedup",
+    )
+    .unwrap();
 }
 
 lazy_static! {
@@ -227,6 +232,52 @@ lazy_static! {
     .unwrap();
 }
 
+// Per group metrics, see `RaftGroupStats`.
+lazy_static! {
+    pub static ref RAFTGROUP_GROUP_TERM_CHANGE_TOTAL_VEC: IntCounterVec =
+        register_int_counter_vec!(
+            "raftgroup_group_term_change_total",
+            "The total of term changes of each raftgroup",
+            &["group"]
+        )
+        .unwrap();
+    pub static ref RAFTGROUP_GROUP_ELECTION_TOTAL_VEC: IntCounterVec = register_int_counter_vec!(
+        "raftgroup_group_election_total",
+        "The total of elections campaigned by each raftgroup",
+        &["group"]
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_GROUP_SEND_SNAPSHOT_TOTAL_VEC: IntCounterVec =
+        register_int_counter_vec!(
+            "raftgroup_group_send_snapshot_total",
+            "The total of snapshot sent by each raftgroup",
+            &["group"]
+        )
+        .unwrap();
+    pub static ref RAFTGROUP_GROUP_APPLY_LAG_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "raftgroup_group_apply_lag",
+        "The number of committed but not applied entries of each raftgroup",
+        &["group"]
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_GROUP_PROPOSE_LATENCY_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "raftgroup_group_propose_latency_micros",
+        "The moving average of propose latency of each raftgroup, in micros",
+        &["group"]
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_ENTRY_CACHE_HIT_TOTAL: IntCounter = register_int_counter!(
+        "raftgroup_entry_cache_hit_total",
+        "The total of fetching entries served by the entry cache of raftgroup"
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_ENTRY_CACHE_MISS_TOTAL: IntCounter = register_int_counter!(
+        "raftgroup_entry_cache_miss_total",
+        "The total of fetching entries which read the log engine"
+    )
+    .unwrap();
+}
+
 pub fn take_read_metrics(read_policy: ReadPolicy) -> &'static Histogram {
     match read_policy {
         ReadPolicy::LeaseRead => {
@@ -241,10 +292,10 @@ pub fn take_read_metrics(read_policy: ReadPolicy) -> &'static Histogram {
     }
 }
 
-pub fn take_propose_metrics(start_at: Instant, result: Result<()>) -> Result<()> {
+pub fn take_propose_metrics<T>(start_at: Instant, result: Result<T>) -> Result<T> {
     let elapsed = elapsed_seconds(start_at);
     match &result {
-        Ok(()) => {
+        Ok(_) => {
             RAFTGROUP_PROPOSE_TOTAL.ok.inc();
             RAFTGROUP_PROPOSE_DURATION_SECONDS.ok.observe(elapsed);
         }
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 mod applier;
+mod codec;
 mod facade;
 mod fsm;
 mod metrics;
@@ -33,16 +34,19 @@ use tracing::{debug, warn};
 
 use self::worker::RaftWorker;
 pub use self::{
+    codec::entry_data,
     facade::RaftNodeFacade,
     fsm::{ApplyEntry, SnapshotBuilder, StateMachine},
     monitor::*,
     snap::SnapManager,
-    storage::{destory as destory_storage, write_initial_state},
+    storage::{destory as destory_storage, fetch_all_entries, write_initial_state},
     transport::{retrive_snapshot, AddressResolver, TransportManager},
     worker::{RaftGroupState, StateObserver},
 };
 use crate::{
-    runtime::{sync::WaitGroup, Executor, TaskPriority},
+    feature::FeatureGates,
+    node::memory::MemoryTracker,
+    runtime::{registry, sync::WaitGroup, Executor, TaskPriority},
     Result,
 };
 
@@ -94,6 +98,33 @@ pub struct RaftConfig {
     /// Default: false
     pub enable_log_recycle: bool,
 
+    /// Compress the data of proposals larger than the threshold before appending and
+    /// replicating them, 0 means disabled. The compressed entries can't be applied by the
+    /// replicas of older versions, so they are proposed only after the `entry_compression`
+    /// feature is activated by all nodes of the cluster.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub entry_compression_threshold: usize,
+
+    /// The capacity of the entry caches of all groups, the applied entries are retained until it
+    /// is exceeded, so that the followers catching up don't hit disk.
+    ///
+    /// Default: 64MB
+    pub entry_cache_capacity: Option<usize>,
+
+    /// The number of concurrent streams to receive the files of a snapshot, a stream is resumed
+    /// from the received bytes once it is interrupted.
+    ///
+    /// Default: 1
+    pub snapshot_streams: Option<usize>,
+
+    /// Compress the chunks of snapshots received by this node with zstd, it trades CPU for the
+    /// traffic of rebuilding replicas.
+    ///
+    /// Default: true
+    pub snapshot_compression: bool,
+
     #[serde(skip)]
     pub testing_knobs: RaftTestingKnobs,
 }
@@ -117,6 +148,8 @@ pub struct RaftManager {
     engine: Arc<raft_engine::Engine>,
     transport_mgr: TransportManager,
     snap_mgr: SnapManager,
+    memory_tracker: Arc<MemoryTracker>,
+    feature_gates: Arc<FeatureGates>,
 }
 
 impl RaftManager {
@@ -125,6 +158,8 @@ impl RaftManager {
         log_path: &Path,
         executor: Executor,
         transport_mgr: TransportManager,
+        memory_tracker: Arc<MemoryTracker>,
+        feature_gates: Arc<FeatureGates>,
     ) -> Result<Self> {
         use raft_engine::{Config, Engine};
         let engine_dir = log_path.join("engine");
@@ -145,6 +180,8 @@ impl RaftManager {
             engine,
             transport_mgr,
             snap_mgr,
+            memory_tracker,
+            feature_gates,
         })
     }
 
@@ -179,15 +216,20 @@ impl RaftManager {
     ) -> Result<RaftNodeFacade> {
         let worker =
             RaftWorker::open(group_id, replica_id, node_id, state_machine, self, observer).await?;
-        let facade = RaftNodeFacade::open(worker.request_sender());
+        let facade = RaftNodeFacade::open(worker.request_sender(), worker.stats());
 
         let tag = &group_id.to_le_bytes();
-        self.executor
-            .spawn(Some(tag), TaskPriority::High, async move {
+        self.executor.spawn_named(
+            registry::group::RAFT,
+            format!("raft-worker-{group_id}-{replica_id}"),
+            Some(tag),
+            TaskPriority::High,
+            async move {
                 // TODO(walter) handle result.
                 worker.run().await.unwrap();
                 drop(wait_group);
-            });
+            },
+        );
         Ok(facade)
     }
 }
@@ -203,6 +245,10 @@ impl Default for RaftConfig {
             max_inflight_msgs: 10 * 1000,
             engine_slow_io_threshold_ms: None,
             enable_log_recycle: false,
+            entry_compression_threshold: 0,
+            entry_cache_capacity: None,
+            snapshot_streams: None,
+            snapshot_compression: true,
             testing_knobs: RaftTestingKnobs::default(),
         }
     }
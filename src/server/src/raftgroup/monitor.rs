@@ -11,10 +11,18 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::mem::MaybeUninit;
+use std::{
+    mem::MaybeUninit,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
+use engula_api::server::v1::RaftRole;
+use prometheus::{IntCounter, IntGauge};
 use serde::Serialize;
 
+use super::metrics::*;
+
 #[derive(Clone, Default, Debug, Serialize)]
 pub struct WorkerPerfContext {
     pub advance: AdvancePerfContext,
@@ -47,6 +55,224 @@ pub struct AdvancePerfContext {
     pub send_message: u64,
 }
 
+/// The raft level statistics of a group, shared by the raft worker and facades.
+///
+/// Besides the accumulated counters, a few recent counters are maintained and decayed by the
+/// worker periodically, they are used to compute the health score of the group.
+pub struct RaftGroupStats {
+    group_id: u64,
+    term: AtomicU64,
+    term_changes: AtomicU64,
+    elections: AtomicU64,
+    recent_elections: AtomicU64,
+    snapshot_sends: AtomicU64,
+    recent_snapshot_sends: AtomicU64,
+    proposals: AtomicU64,
+    failed_proposals: AtomicU64,
+    recent_failed_proposals: AtomicU64,
+    /// The moving average of propose latency, in micros.
+    propose_latency: AtomicU64,
+    apply_lag: AtomicU64,
+
+    term_change_total: IntCounter,
+    election_total: IntCounter,
+    send_snapshot_total: IntCounter,
+    apply_lag_gauge: IntGauge,
+    propose_latency_gauge: IntGauge,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RaftGroupHealth {
+    pub term: u64,
+    pub term_changes: u64,
+    pub elections: u64,
+    pub recent_elections: u64,
+    pub snapshot_sends: u64,
+    pub recent_snapshot_sends: u64,
+    pub proposals: u64,
+    pub failed_proposals: u64,
+    pub recent_failed_proposals: u64,
+    pub propose_latency_micros: u64,
+    pub apply_lag: u64,
+    /// The health score of group, in range `[0, 100]`, the higher the healthier.
+    pub score: u64,
+}
+
+impl RaftGroupStats {
+    pub fn new(group_id: u64) -> Self {
+        let label = group_id.to_string();
+        RaftGroupStats {
+            group_id,
+            term: AtomicU64::default(),
+            term_changes: AtomicU64::default(),
+            elections: AtomicU64::default(),
+            recent_elections: AtomicU64::default(),
+            snapshot_sends: AtomicU64::default(),
+            recent_snapshot_sends: AtomicU64::default(),
+            proposals: AtomicU64::default(),
+            failed_proposals: AtomicU64::default(),
+            recent_failed_proposals: AtomicU64::default(),
+            propose_latency: AtomicU64::default(),
+            apply_lag: AtomicU64::default(),
+            term_change_total: RAFTGROUP_GROUP_TERM_CHANGE_TOTAL_VEC.with_label_values(&[&label]),
+            election_total: RAFTGROUP_GROUP_ELECTION_TOTAL_VEC.with_label_values(&[&label]),
+            send_snapshot_total: RAFTGROUP_GROUP_SEND_SNAPSHOT_TOTAL_VEC
+                .with_label_values(&[&label]),
+            apply_lag_gauge: RAFTGROUP_GROUP_APPLY_LAG_VEC.with_label_values(&[&label]),
+            propose_latency_gauge: RAFTGROUP_GROUP_PROPOSE_LATENCY_VEC.with_label_values(&[&label]),
+        }
+    }
+
+    pub(super) fn on_state_updated(&self, term: u64, role: RaftRole) {
+        let prev_term = self.term.swap(term, Ordering::Relaxed);
+        if prev_term != 0 && prev_term != term {
+            self.term_changes.fetch_add(1, Ordering::Relaxed);
+            self.term_change_total.inc();
+        }
+        if role == RaftRole::Candidate {
+            self.elections.fetch_add(1, Ordering::Relaxed);
+            self.recent_elections.fetch_add(1, Ordering::Relaxed);
+            self.election_total.inc();
+        }
+    }
+
+    pub(super) fn on_snapshot_sent(&self) {
+        self.snapshot_sends.fetch_add(1, Ordering::Relaxed);
+        self.recent_snapshot_sends.fetch_add(1, Ordering::Relaxed);
+        self.send_snapshot_total.inc();
+    }
+
+    pub(super) fn on_proposed(&self, elapsed: Duration, success: bool) {
+        self.proposals.fetch_add(1, Ordering::Relaxed);
+        if !success {
+            self.failed_proposals.fetch_add(1, Ordering::Relaxed);
+            self.recent_failed_proposals.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let sample = elapsed.as_micros() as u64;
+        let moving_average =
+            |avg: u64| avg - avg / PROPOSE_LATENCY_SMOOTHING + sample / PROPOSE_LATENCY_SMOOTHING;
+        let prev = self
+            .propose_latency
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |avg| {
+                Some(moving_average(avg))
+            })
+            .unwrap_or_default();
+        self.propose_latency_gauge.set(moving_average(prev) as i64);
+    }
+
+    pub(super) fn on_apply_lag(&self, lag: u64) {
+        self.apply_lag.store(lag, Ordering::Relaxed);
+        self.apply_lag_gauge.set(lag as i64);
+    }
+
+    /// Halve the recent counters, so that the influence of old events is gradually eliminated.
+    pub(super) fn decay(&self) {
+        for counter in [
+            &self.recent_elections,
+            &self.recent_snapshot_sends,
+            &self.recent_failed_proposals,
+        ] {
+            counter
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| Some(v / 2))
+                .unwrap_or_default();
+        }
+    }
+
+    pub fn health(&self) -> RaftGroupHealth {
+        let mut health = RaftGroupHealth {
+            term: self.term.load(Ordering::Relaxed),
+            term_changes: self.term_changes.load(Ordering::Relaxed),
+            elections: self.elections.load(Ordering::Relaxed),
+            recent_elections: self.recent_elections.load(Ordering::Relaxed),
+            snapshot_sends: self.snapshot_sends.load(Ordering::Relaxed),
+            recent_snapshot_sends: self.recent_snapshot_sends.load(Ordering::Relaxed),
+            proposals: self.proposals.load(Ordering::Relaxed),
+            failed_proposals: self.failed_proposals.load(Ordering::Relaxed),
+            recent_failed_proposals: self.recent_failed_proposals.load(Ordering::Relaxed),
+            propose_latency_micros: self.propose_latency.load(Ordering::Relaxed),
+            apply_lag: self.apply_lag.load(Ordering::Relaxed),
+            score: 0,
+        };
+        health.score = health_score(&health);
+        health
+    }
+}
+
+impl Drop for RaftGroupStats {
+    fn drop(&mut self) {
+        let label = self.group_id.to_string();
+        RAFTGROUP_GROUP_TERM_CHANGE_TOTAL_VEC
+            .remove_label_values(&[&label])
+            .unwrap_or_default();
+        RAFTGROUP_GROUP_ELECTION_TOTAL_VEC
+            .remove_label_values(&[&label])
+            .unwrap_or_default();
+        RAFTGROUP_GROUP_SEND_SNAPSHOT_TOTAL_VEC
+            .remove_label_values(&[&label])
+            .unwrap_or_default();
+        RAFTGROUP_GROUP_APPLY_LAG_VEC
+            .remove_label_values(&[&label])
+            .unwrap_or_default();
+        RAFTGROUP_GROUP_PROPOSE_LATENCY_VEC
+            .remove_label_values(&[&label])
+            .unwrap_or_default();
+    }
+}
+
+/// The weight of old samples in the moving average of propose latency, a new sample accounts
+/// for `1 / PROPOSE_LATENCY_SMOOTHING` of the average.
+const PROPOSE_LATENCY_SMOOTHING: u64 = 8;
+
+/// The score of a group without any recent trouble.
+const MAX_HEALTH_SCORE: u64 = 100;
+
+/// The penalty of each recent election, a group keeps electing can't serve requests.
+const ELECTION_PENALTY: u64 = 20;
+
+/// The upper bound of the penalty of recent elections.
+const MAX_ELECTION_PENALTY: u64 = 60;
+
+/// The penalty of sending snapshots recently, a follower is lagging far behind.
+const SNAPSHOT_PENALTY: u64 = 10;
+
+/// The penalty of failing proposals recently.
+const FAILED_PROPOSAL_PENALTY: u64 = 10;
+
+/// The number of committed but not applied entries which indicates a slow applier.
+const SLOW_APPLY_LAG: u64 = 100;
+const SLOW_APPLY_PENALTY: u64 = 10;
+
+/// The number of committed but not applied entries which indicates a stuck applier.
+const STUCK_APPLY_LAG: u64 = 1000;
+const STUCK_APPLY_PENALTY: u64 = 20;
+
+/// The moving average of propose latency which indicates a slow group, in micros.
+const SLOW_PROPOSE_LATENCY_MICROS: u64 = 100 * 1000;
+const SLOW_PROPOSE_PENALTY: u64 = 10;
+
+fn health_score(health: &RaftGroupHealth) -> u64 {
+    let mut penalty = std::cmp::min(
+        health.recent_elections * ELECTION_PENALTY,
+        MAX_ELECTION_PENALTY,
+    );
+    if health.recent_snapshot_sends > 0 {
+        penalty += SNAPSHOT_PENALTY;
+    }
+    if health.recent_failed_proposals > 0 {
+        penalty += FAILED_PROPOSAL_PENALTY;
+    }
+    if health.apply_lag > STUCK_APPLY_LAG {
+        penalty += STUCK_APPLY_PENALTY;
+    } else if health.apply_lag > SLOW_APPLY_LAG {
+        penalty += SLOW_APPLY_PENALTY;
+    }
+    if health.propose_latency_micros > SLOW_PROPOSE_LATENCY_MICROS {
+        penalty += SLOW_PROPOSE_PENALTY;
+    }
+    MAX_HEALTH_SCORE.saturating_sub(penalty)
+}
+
 #[inline]
 pub(crate) fn record_perf_point(hold: &mut u64) {
     *hold = perf_point_micros();
@@ -64,3 +290,34 @@ pub(crate) fn perf_point_micros() -> u64 {
         0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raft_group_health_score() {
+        let stats = RaftGroupStats::new(u64::MAX);
+        assert_eq!(stats.health().score, 100);
+
+        stats.on_state_updated(1, RaftRole::Follower);
+        stats.on_state_updated(2, RaftRole::Candidate);
+        stats.on_state_updated(3, RaftRole::Candidate);
+        let health = stats.health();
+        assert_eq!(health.term_changes, 2);
+        assert_eq!(health.elections, 2);
+        assert_eq!(health.score, 60);
+
+        stats.on_apply_lag(2000);
+        stats.on_snapshot_sent();
+        assert_eq!(stats.health().score, 30);
+
+        stats.decay();
+        stats.decay();
+        stats.on_apply_lag(0);
+        let health = stats.health();
+        assert_eq!(health.elections, 2);
+        assert_eq!(health.recent_elections, 0);
+        assert_eq!(health.score, 100);
+    }
+}
@@ -94,6 +94,7 @@ where
             conf_state,
             mgr.engine.clone(),
             mgr.snap_mgr.clone(),
+            mgr.memory_tracker.clone(),
         )
         .await?;
         try_reset_storage_state(replica_id, &mgr.snap_mgr, &mgr.engine, &mut storage).await?;
@@ -126,7 +127,7 @@ where
         &mut self,
         data: Vec<u8>,
         context: Vec<u8>,
-        sender: oneshot::Sender<Result<()>>,
+        sender: oneshot::Sender<Result<u64>>,
     ) {
         if self.raw_node.raft.state != StateRole::Leader {
             sender
@@ -159,7 +160,7 @@ where
         &mut self,
         context: Vec<u8>,
         cc: impl ConfChangeI,
-        sender: oneshot::Sender<Result<()>>,
+        sender: oneshot::Sender<Result<u64>>,
     ) {
         if self.raw_node.raft.state != StateRole::Leader {
             sender
@@ -510,7 +511,7 @@ mod tests {
 
     use super::*;
     use crate::{
-        node::RaftRouteTable,
+        node::{memory::MemoryTracker, RaftRouteTable},
         raftgroup::{write_initial_state, AddressResolver, TransportManager},
         runtime::ExecutorOwner,
         serverpb::v1::{ApplyState, EvalResult, SnapshotMeta},
@@ -614,6 +615,7 @@ mod tests {
                 ConfState::default(),
                 engine.clone(),
                 snap_mgr.clone(),
+                Arc::new(MemoryTracker::new(0)),
             )
             .await
             .unwrap();
@@ -675,6 +677,7 @@ mod tests {
                 ConfState::default(),
                 engine.clone(),
                 snap_mgr.clone(),
+                Arc::new(MemoryTracker::new(0)),
             )
             .await
             .unwrap();
@@ -747,6 +750,7 @@ mod tests {
                 ConfState::default(),
                 engine.clone(),
                 snap_mgr.clone(),
+                Arc::new(MemoryTracker::new(0)),
             )
             .await
             .unwrap();
@@ -875,6 +879,7 @@ mod tests {
                 engine: engine.clone(),
                 transport_mgr,
                 snap_mgr: snap_mgr.clone(),
+                memory_tracker: Arc::new(MemoryTracker::new(0)),
             };
 
             // 1. initial storage with log entries in [0, 100), all entries are committed.
@@ -0,0 +1,182 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The blocks of snapshot files, they are the unit of compression and deduplication.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs::File,
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+};
+
+use super::SnapshotGuard;
+use crate::{serverpb::v1::SnapshotFile, Error, Result};
+
+/// The size of blocks, except the last block of a file, it is also the size of snapshot chunks.
+pub const BLOCK_SIZE: usize = 32 * 1024;
+
+const BLOCK_HASH_SIZE: usize = 16;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+pub fn block_hash(block: &[u8]) -> Vec<u8> {
+    blake3::hash(block).as_bytes()[..BLOCK_HASH_SIZE].to_vec()
+}
+
+/// Read a block from the file, it is shorter than [`BLOCK_SIZE`] only if the end of file is
+/// reached.
+pub fn read_block(file: &mut File) -> std::io::Result<Vec<u8>> {
+    let mut block = vec![0; BLOCK_SIZE];
+    let mut num_read = 0;
+    while num_read < BLOCK_SIZE {
+        match file.read(&mut block[num_read..]) {
+            Ok(0) => break,
+            Ok(n) => num_read += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    block.truncate(num_read);
+    Ok(block)
+}
+
+/// Compress the block with zstd. The previous block of the file is used as the raw content
+/// dictionary, since the adjacent blocks of a file usually share lots of content.
+pub fn compress_block(block: &[u8], dict: &[u8]) -> Result<Vec<u8>> {
+    let mut compressor = zstd::bulk::Compressor::with_dictionary(COMPRESSION_LEVEL, dict)?;
+    Ok(compressor.compress(block)?)
+}
+
+pub fn decompress_block(data: &[u8], size: usize, dict: &[u8]) -> Result<Vec<u8>> {
+    let mut decompressor = zstd::bulk::Decompressor::with_dictionary(dict)?;
+    let block = decompressor.decompress(data, size)?;
+    if block.len() != size {
+        return Err(Error::InvalidData(format!(
+            "invalid size of decompressed block, expect {size}, but got {}",
+            block.len()
+        )));
+    }
+    Ok(block)
+}
+
+struct BlockLocation {
+    path: PathBuf,
+    offset: u64,
+    size: usize,
+}
+
+/// The blocks of the local snapshots of a group, the blocks of an incoming snapshot found here are
+/// copied rather than transferred.
+#[derive(Default)]
+pub struct BlockIndex {
+    /// The snapshots are not recycled until the index is dropped.
+    _snapshots: Vec<SnapshotGuard>,
+    blocks: HashMap<Vec<u8>, BlockLocation>,
+}
+
+impl BlockIndex {
+    pub fn new(snapshots: Vec<SnapshotGuard>) -> Self {
+        let mut blocks = HashMap::new();
+        for snapshot in &snapshots {
+            for file in &snapshot.meta.files {
+                let path = snapshot.base_dir.join(OsStr::from_bytes(&file.name));
+                for (idx, hash) in file.block_hashes.iter().enumerate() {
+                    let offset = (idx * BLOCK_SIZE) as u64;
+                    let size = std::cmp::min(file.size.saturating_sub(offset), BLOCK_SIZE as u64);
+                    blocks.entry(hash.clone()).or_insert_with(|| BlockLocation {
+                        path: path.clone(),
+                        offset,
+                        size: size as usize,
+                    });
+                }
+            }
+        }
+        BlockIndex {
+            _snapshots: snapshots,
+            blocks,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Returns the hashes of the blocks of the files which could be copied locally.
+    pub fn known_blocks(&self, files: &[SnapshotFile]) -> Vec<Vec<u8>> {
+        files
+            .iter()
+            .flat_map(|f| f.block_hashes.iter())
+            .filter(|hash| self.blocks.contains_key(*hash))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Read the block of the hash, the content is verified against the hash.
+    pub fn read(&self, hash: &[u8]) -> Result<Vec<u8>> {
+        let location = self
+            .blocks
+            .get(hash)
+            .ok_or_else(|| Error::InvalidData("the known block is not found".to_string()))?;
+        let mut file = File::open(&location.path)?;
+        file.seek(SeekFrom::Start(location.offset))?;
+        let mut block = read_block(&mut file)?;
+        block.truncate(location.size);
+        if block_hash(&block) != hash {
+            return Err(Error::InvalidData(format!(
+                "the block at {} of {} is corrupted",
+                location.offset,
+                location.path.display()
+            )));
+        }
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compress_block_with_previous_block() {
+        let mut seed = 1u64;
+        let prev = (0..BLOCK_SIZE)
+            .map(|_| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (seed >> 56) as u8
+            })
+            .collect::<Vec<_>>();
+        let mut block = prev.clone();
+        block[100] = 0xFF;
+
+        let compressed = compress_block(&block, &prev).unwrap();
+        let standalone = compress_block(&block, &[]).unwrap();
+        assert!(compressed.len() < standalone.len());
+        assert_eq!(
+            decompress_block(&compressed, block.len(), &prev).unwrap(),
+            block
+        );
+        assert_eq!(
+            decompress_block(&standalone, block.len(), &[]).unwrap(),
+            block
+        );
+        assert!(decompress_block(&compressed, block.len() - 1, &prev).is_err());
+    }
+}
@@ -18,17 +18,15 @@ use futures::{channel::mpsc, SinkExt};
 use prost::Message;
 use tracing::{error, info};
 
-use super::{SnapManager, SNAP_DATA};
+use super::{
+    block::{block_hash, read_block, BLOCK_SIZE},
+    SnapManager, SNAP_DATA,
+};
 use crate::{
-    raftgroup::{
-        fsm::SnapshotBuilder,
-        metrics::*,
-        snap::{SNAP_META, SNAP_TEMP},
-        worker::Request,
-        StateMachine,
-    },
+    fs::create_atomic,
+    raftgroup::{fsm::SnapshotBuilder, metrics::*, snap::SNAP_META, worker::Request, StateMachine},
     record_latency,
-    runtime::{Executor, TaskPriority},
+    runtime::{registry::group, Executor, TaskPriority},
     serverpb::v1::{SnapshotFile, SnapshotMeta},
     Result,
 };
@@ -41,21 +39,28 @@ pub fn dispatch_creating_snap_task(
     snap_mgr: SnapManager,
 ) {
     let builder = state_machine.snapshot_builder();
-    executor.spawn(None, TaskPriority::IoLow, async move {
-        match create_snapshot(replica_id, &snap_mgr, builder).await {
-            Ok(_) => {
-                info!("replica {replica_id} create snapshot success");
-            }
-            Err(err) => {
-                error!("replica {replica_id} create snapshot: {err}");
-            }
-        };
-
-        sender
-            .send(Request::CreateSnapshotFinished)
-            .await
-            .unwrap_or_default();
-    });
+    let name = format!("create-snapshot-{replica_id}");
+    executor.spawn_named(
+        group::SNAPSHOT,
+        name,
+        None,
+        TaskPriority::IoLow,
+        async move {
+            match create_snapshot(replica_id, &snap_mgr, builder).await {
+                Ok(_) => {
+                    info!("replica {replica_id} create snapshot success");
+                }
+                Err(err) => {
+                    error!("replica {replica_id} create snapshot: {err}");
+                }
+            };
+
+            sender
+                .send(Request::CreateSnapshotFinished)
+                .await
+                .unwrap_or_default();
+        },
+    );
 }
 
 /// Create new snapshot and returns snapshot id.
@@ -108,52 +113,31 @@ pub(super) async fn create_snapshot(
 }
 
 pub(super) async fn stable_snapshot_meta(base_dir: &Path, snap_meta: &SnapshotMeta) -> Result<()> {
-    use std::{fs::OpenOptions, io::Write};
-
     let content = snap_meta.encode_to_vec();
-
-    let tmp = base_dir.join(SNAP_TEMP);
-    let mut file = OpenOptions::new().write(true).create(true).open(&tmp)?;
-    file.write_all(&content)?;
-    file.sync_all()?;
-    drop(file);
-
-    let meta = base_dir.join(SNAP_META);
-    std::fs::rename(tmp, meta)?;
-
-    std::fs::File::open(base_dir)?.sync_all()?;
-
-    Ok(())
+    create_atomic(&base_dir.join(SNAP_META), &content)
 }
 
 async fn read_file_meta(filename: &Path) -> Result<SnapshotFile> {
-    use std::{
-        fs::OpenOptions,
-        io::{ErrorKind, Read},
-    };
+    use std::fs::OpenOptions;
 
-    let mut buf = vec![0; 4096];
     let mut file = OpenOptions::new().read(true).open(filename)?;
     let mut hasher = crc32fast::Hasher::new();
+    let mut block_hashes = vec![];
 
     let mut size: u64 = 0;
-    let mut count = 0;
     loop {
-        let n = match file.read(&mut buf) {
-            Ok(n) => n,
-            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-            Err(e) => return Err(e.into()),
-        };
-        if n == 0 {
+        let block = read_block(&mut file)?;
+        if block.is_empty() {
             break;
         }
 
-        size += n as u64;
-        count += 1;
-        hasher.update(&buf[..n]);
-        if count % 10 == 0 {
-            crate::runtime::yield_now().await;
+        size += block.len() as u64;
+        hasher.update(&block);
+        block_hashes.push(block_hash(&block));
+        if block.len() < BLOCK_SIZE {
+            break;
         }
+        crate::runtime::yield_now().await;
     }
 
     let name = if filename.file_name().unwrap() == SNAP_DATA {
@@ -167,5 +151,6 @@ async fn read_file_meta(filename: &Path) -> Result<SnapshotFile> {
         name: name.to_str().unwrap().as_bytes().to_owned(),
         crc32,
         size,
+        block_hashes,
     })
 }
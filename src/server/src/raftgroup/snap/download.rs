@@ -15,71 +15,164 @@
 use std::{
     ffi::OsString,
     fs::File,
+    ops::Range,
     os::unix::ffi::OsStringExt,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use engula_api::server::v1::ReplicaDesc;
 use futures::{channel::mpsc, SinkExt, StreamExt};
 use raft::eraftpb::Message;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use super::SnapManager;
+use super::{
+    block::{decompress_block, BlockIndex},
+    progress::{TransferDirection, TransferProgress},
+    SnapManager,
+};
 use crate::{
     raftgroup::{metrics::*, retrive_snapshot, worker::Request, TransportManager},
     record_latency,
-    runtime::{Executor, TaskPriority},
-    serverpb::v1::{snapshot_chunk, SnapshotChunk, SnapshotFile, SnapshotMeta},
+    runtime::{registry::group, Executor, TaskPriority},
+    serverpb::v1::{snapshot_chunk, SnapshotChunk, SnapshotFile, SnapshotMeta, SnapshotRequest},
     Error, Result,
 };
 
+/// The max times to resume an interrupted snapshot stream.
+const MAX_RESUME_TIMES: u64 = 8;
+
+/// The base interval before resuming an interrupted snapshot stream.
+const RESUME_BACKOFF: Duration = Duration::from_millis(200);
+
+/// The options of downloading snapshots, see [`crate::raftgroup::RaftConfig`].
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadOptions {
+    pub streams: usize,
+    pub compression: bool,
+}
+
 struct PartialFile {
     meta: SnapshotFile,
     file: File,
     size: usize,
     crc32: crc32fast::Hasher,
+    /// The last block received in the current stream, it is the dictionary of the next compressed
+    /// block.
+    last_block: Vec<u8>,
 }
 
+/// Receives the snapshot files of a stream. The received files and the bytes of the partial file
+/// are the checkpoint to resume the stream once it is interrupted.
 struct SnapshotBuilder {
     replica_id: u64,
     base_dir: PathBuf,
     meta: SnapshotMeta,
     file_name: Vec<u8>,
     file: Option<PartialFile>,
+    /// The files of the snapshot, it is known once the snapshot meta is received.
+    remote_files: Vec<SnapshotFile>,
+    /// Whether the total files of the progress is accumulated by the received file metas, since
+    /// it is unknown when transferring in a single stream.
+    accumulate_files: bool,
+}
+
+struct DownloadContext<'a> {
+    tran_mgr: &'a TransportManager,
+    from_replica: ReplicaDesc,
+    snapshot_id: Vec<u8>,
+    progress: &'a TransferProgress,
+    compression: bool,
 }
 
 impl SnapshotBuilder {
-    fn new(replica_id: u64, base_dir: &Path) -> Self {
+    fn new(replica_id: u64, base_dir: &Path, accumulate_files: bool) -> Self {
         SnapshotBuilder {
             replica_id,
             base_dir: base_dir.to_owned(),
             meta: SnapshotMeta::default(),
             file_name: vec![],
             file: None,
+            remote_files: vec![],
+            accumulate_files,
         }
     }
 
-    async fn append(&mut self, chunk: SnapshotChunk) -> Result<()> {
+    /// Returns the index of the file to resume, relative to the first file of the stream, and
+    /// the bytes already received of it.
+    fn checkpoint(&self) -> (usize, u64) {
+        let offset = self
+            .file
+            .as_ref()
+            .map(|f| f.size as u64)
+            .unwrap_or_default();
+        (self.meta.files.len(), offset)
+    }
+
+    async fn append(
+        &mut self,
+        chunk: SnapshotChunk,
+        progress: &TransferProgress,
+        blocks: &BlockIndex,
+    ) -> Result<()> {
         match chunk.value {
-            Some(snapshot_chunk::Value::File(file)) => self.switch_file(file).await,
-            Some(snapshot_chunk::Value::ChunkData(data)) => match self.file.as_mut() {
-                Some(file) => {
-                    RAFTGROUP_DOWNLOAD_SNAPSHOT_BYTES_TOTAL.inc_by(data.len() as u64);
-                    file.write_all(&data).await
+            Some(snapshot_chunk::Value::File(file)) => {
+                if let Some(partial) = self.file.as_mut().filter(|_| self.file_name == file.name) {
+                    // The stream is resumed from the partial file, the sender compresses the
+                    // blocks of the new stream without dictionary.
+                    partial.last_block.clear();
+                    return Ok(());
                 }
-                None => Err(Error::InvalidData("missing file meta".to_string())),
-            },
+                if self.accumulate_files {
+                    progress.add_files(1, file.size);
+                }
+                self.switch_file(file, progress).await
+            }
+            Some(snapshot_chunk::Value::ChunkData(data)) => {
+                RAFTGROUP_DOWNLOAD_SNAPSHOT_BYTES_TOTAL.inc_by(data.len() as u64);
+                self.append_block(data, progress).await
+            }
+            Some(snapshot_chunk::Value::CompressedChunk(chunk)) => {
+                RAFTGROUP_DOWNLOAD_SNAPSHOT_BYTES_TOTAL.inc_by(chunk.data.len() as u64);
+                let dict = match self.file.as_ref() {
+                    Some(file) => file.last_block.as_slice(),
+                    None => return Err(Error::InvalidData("missing file meta".to_string())),
+                };
+                let block = decompress_block(&chunk.data, chunk.size as usize, dict)?;
+                self.append_block(block, progress).await
+            }
+            Some(snapshot_chunk::Value::KnownBlock(hash)) => {
+                let block = blocks.read(&hash)?;
+                self.append_block(block, progress).await
+            }
             Some(snapshot_chunk::Value::Meta(meta)) => {
                 self.meta.apply_state = meta.apply_state;
                 self.meta.group_desc = meta.group_desc;
+                self.remote_files = meta.files;
                 Ok(())
             }
             None => Ok(()),
         }
     }
 
-    async fn switch_file(&mut self, file_meta: SnapshotFile) -> Result<()> {
-        self.finish_partial_file().await?;
+    async fn append_block(&mut self, block: Vec<u8>, progress: &TransferProgress) -> Result<()> {
+        match self.file.as_mut() {
+            Some(file) => {
+                progress.on_bytes_transferred(block.len() as u64);
+                file.write_all(&block).await?;
+                file.last_block = block;
+                Ok(())
+            }
+            None => Err(Error::InvalidData("missing file meta".to_string())),
+        }
+    }
+
+    async fn switch_file(
+        &mut self,
+        file_meta: SnapshotFile,
+        progress: &TransferProgress,
+    ) -> Result<()> {
+        self.finish_partial_file(progress).await?;
 
         let name = file_meta.name.clone();
         let str = OsString::from_vec(name.clone());
@@ -96,18 +189,13 @@ impl SnapshotBuilder {
         Ok(())
     }
 
-    async fn finish_partial_file(&mut self) -> Result<()> {
+    async fn finish_partial_file(&mut self, progress: &TransferProgress) -> Result<()> {
         if let Some(file) = self.file.take() {
             self.meta.files.push(file.finish().await?);
+            progress.on_file_transferred();
         }
         Ok(())
     }
-
-    async fn finish(mut self) -> Result<SnapshotMeta> {
-        self.finish_partial_file().await?;
-        super::create::stable_snapshot_meta(&self.base_dir, &self.meta).await?;
-        Ok(self.meta)
-    }
 }
 
 impl PartialFile {
@@ -128,6 +216,7 @@ impl PartialFile {
             file,
             size: 0,
             crc32: crc32fast::Hasher::new(),
+            last_block: vec![],
         })
     }
 
@@ -165,31 +254,40 @@ impl PartialFile {
 pub fn dispatch_downloading_snap_task(
     executor: &Executor,
     replica_id: u64,
+    opts: DownloadOptions,
     mut sender: mpsc::Sender<Request>,
     snap_mgr: SnapManager,
     tran_mgr: TransportManager,
     from_replica: ReplicaDesc,
     mut msg: Message,
 ) {
-    executor.spawn(None, TaskPriority::IoLow, async move {
-        match download_snap(replica_id, tran_mgr, snap_mgr, from_replica, &msg).await {
-            Ok(snap_id) => {
-                msg.snapshot.as_mut().unwrap().data = snap_id;
-                let request = Request::InstallSnapshot { msg };
-                sender.send(request).await.unwrap_or_default();
-            }
-            Err(err) => {
-                error!("replica {replica_id} download snapshot: {err}");
-                let request = Request::RejectSnapshot { msg };
-                sender.send(request).await.unwrap_or_default();
-            }
-        };
-    });
+    let name = format!("download-snapshot-{replica_id}");
+    executor.spawn_named(
+        group::SNAPSHOT,
+        name,
+        None,
+        TaskPriority::IoLow,
+        async move {
+            match download_snap(replica_id, opts, tran_mgr, snap_mgr, from_replica, &msg).await {
+                Ok(snap_id) => {
+                    msg.snapshot.as_mut().unwrap().data = snap_id;
+                    let request = Request::InstallSnapshot { msg };
+                    sender.send(request).await.unwrap_or_default();
+                }
+                Err(err) => {
+                    error!("replica {replica_id} download snapshot: {err}");
+                    let request = Request::RejectSnapshot { msg };
+                    sender.send(request).await.unwrap_or_default();
+                }
+            };
+        },
+    );
 }
 
 /// Download snapshot from target and returns the local snapshot id.
 async fn download_snap(
     replica_id: u64,
+    opts: DownloadOptions,
     tran_mgr: TransportManager,
     snap_mgr: SnapManager,
     from_replica: ReplicaDesc,
@@ -199,31 +297,279 @@ async fn download_snap(
     assert!(msg.has_snapshot() && !msg.get_snapshot().is_empty());
     let snapshot = msg.get_snapshot();
     let snapshot_id = snapshot.data.clone();
-    let chunk_stream = retrive_snapshot(&tran_mgr, from_replica, snapshot_id).await?;
-    save_snapshot(&snap_mgr, replica_id, chunk_stream).await
+    let progress = snap_mgr.register_transfer(replica_id, TransferDirection::Receive, &snapshot_id);
+    let ctx = DownloadContext {
+        tran_mgr: &tran_mgr,
+        from_replica,
+        snapshot_id,
+        progress: &progress,
+        compression: opts.compression,
+    };
+
+    let base_dir = snap_mgr.create(replica_id);
+    info!(
+        "replica {replica_id} save incoming snapshot chunk stream into {}",
+        base_dir.display()
+    );
+    std::fs::create_dir_all(&base_dir)?;
+
+    let snap_meta =
+        receive_concurrently(&ctx, &snap_mgr, replica_id, &base_dir, opts.streams).await?;
+
+    super::create::stable_snapshot_meta(&base_dir, &snap_meta).await?;
+    Ok(snap_mgr.install(replica_id, &base_dir, &snap_meta))
+}
+
+/// Fetch the snapshot meta first, then receive the files in concurrent streams. The blocks of the
+/// files found in the local snapshots of the group are copied rather than transferred.
+async fn receive_concurrently(
+    ctx: &DownloadContext<'_>,
+    snap_mgr: &SnapManager,
+    replica_id: u64,
+    base_dir: &Path,
+    streams: usize,
+) -> Result<SnapshotMeta> {
+    let mut meta_builder = SnapshotBuilder::new(replica_id, base_dir, false);
+    let no_blocks = BlockIndex::default();
+    receive_files(ctx, &mut meta_builder, 0..0, true, &no_blocks, &[]).await?;
+    let remote_files = std::mem::take(&mut meta_builder.remote_files);
+    let mut snap_meta = meta_builder.meta;
+
+    let blocks = match &snap_meta.group_desc {
+        Some(desc) => BlockIndex::new(snap_mgr.group_snapshots(desc.id)),
+        None => BlockIndex::default(),
+    };
+    let known_blocks = blocks.known_blocks(&remote_files);
+    if !known_blocks.is_empty() {
+        info!(
+            "replica {replica_id} copies {} known blocks of snapshot from local",
+            known_blocks.len()
+        );
+    }
+
+    let ranges = split_files(&remote_files, streams);
+    debug!(
+        "replica {replica_id} receive {} snapshot files in {} streams",
+        remote_files.len(),
+        ranges.len()
+    );
+    ctx.progress.set_streams(ranges.len());
+    ctx.progress.add_files(
+        remote_files.len(),
+        remote_files.iter().map(|f| f.size).sum(),
+    );
+
+    let mut builders = ranges
+        .iter()
+        .map(|_| SnapshotBuilder::new(replica_id, base_dir, false))
+        .collect::<Vec<_>>();
+    let receivings = builders.iter_mut().zip(ranges).map(|(builder, range)| {
+        let (blocks, known_blocks) = (&blocks, known_blocks.as_slice());
+        async move {
+            receive_files(ctx, builder, range, false, blocks, known_blocks).await?;
+            builder.finish_partial_file(ctx.progress).await
+        }
+    });
+    futures::future::try_join_all(receivings).await?;
+
+    for builder in builders {
+        snap_meta.files.extend(builder.meta.files);
+    }
+    let received = snap_meta.files.iter().map(|f| &f.name);
+    if !received.eq(remote_files.iter().map(|f| &f.name)) {
+        return Err(Error::InvalidData(
+            "the received snapshot files are not matched with meta".to_string(),
+        ));
+    }
+    Ok(snap_meta)
+}
+
+/// Receive the files in `range` (all files if it is empty) into the builder, the stream is
+/// resumed from the checkpoint of builder if it is interrupted.
+async fn receive_files(
+    ctx: &DownloadContext<'_>,
+    builder: &mut SnapshotBuilder,
+    range: Range<usize>,
+    meta_only: bool,
+    blocks: &BlockIndex,
+    known_blocks: &[Vec<u8>],
+) -> Result<()> {
+    let mut resume_times = 0;
+    loop {
+        let (file_index, file_offset) = builder.checkpoint();
+        let request = SnapshotRequest {
+            snapshot_id: ctx.snapshot_id.clone(),
+            file_index: (range.start + file_index) as u64,
+            file_offset,
+            file_end: range.end as u64,
+            meta_only,
+            compression: ctx.compression,
+            known_blocks: known_blocks.to_owned(),
+            ..Default::default()
+        };
+        match receive_chunks(ctx, builder, request, blocks).await {
+            Ok(()) => return Ok(()),
+            Err(err) if is_interrupted(&err) && resume_times < MAX_RESUME_TIMES => {
+                resume_times += 1;
+                ctx.progress.on_retry();
+                warn!(
+                    "replica {} snapshot stream is interrupted: {err}, resume from file {} offset {file_offset}",
+                    builder.replica_id,
+                    range.start + file_index,
+                );
+                crate::runtime::time::sleep(RESUME_BACKOFF * resume_times as u32).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+async fn receive_chunks(
+    ctx: &DownloadContext<'_>,
+    builder: &mut SnapshotBuilder,
+    request: SnapshotRequest,
+    blocks: &BlockIndex,
+) -> Result<()> {
+    let mut chunk_stream =
+        retrive_snapshot(ctx.tran_mgr, ctx.from_replica.clone(), request).await?;
+    while let Some(resp) = chunk_stream.next().await {
+        builder.append(resp?, ctx.progress, blocks).await?;
+    }
+    Ok(())
+}
+
+/// Whether the snapshot stream is interrupted by network, rather than rejected by the peer.
+fn is_interrupted(err: &Error) -> bool {
+    use tonic::Code;
+
+    match err {
+        Error::Transport(_) => true,
+        Error::Rpc(status) => matches!(
+            status.code(),
+            Code::Unavailable
+                | Code::Unknown
+                | Code::Cancelled
+                | Code::Aborted
+                | Code::DeadlineExceeded
+                | Code::Internal
+        ),
+        _ => false,
+    }
+}
+
+/// Split files into at most `streams` continuous ranges with similar bytes.
+fn split_files(files: &[SnapshotFile], streams: usize) -> Vec<Range<usize>> {
+    let streams = std::cmp::min(streams, files.len());
+    if streams == 0 {
+        return vec![];
+    }
+
+    let total = files.iter().map(|f| f.size).sum::<u64>();
+    let expect = total / streams as u64;
+    let mut ranges = Vec::with_capacity(streams);
+    let mut start = 0;
+    let mut size = 0;
+    for (idx, file) in files.iter().enumerate() {
+        size += file.size;
+        let remaining_files = files.len() - idx - 1;
+        let remaining_streams = streams - ranges.len() - 1;
+        if remaining_streams > 0 && (size >= expect || remaining_files == remaining_streams) {
+            ranges.push(start..idx + 1);
+            start = idx + 1;
+            size = 0;
+        }
+    }
+    ranges.push(start..files.len());
+    ranges
 }
 
+#[cfg(test)]
 pub(super) async fn save_snapshot<S>(
     snap_mgr: &SnapManager,
     replica_id: u64,
     mut chunk_stream: S,
+    blocks: &BlockIndex,
 ) -> Result<Vec<u8>>
 where
     S: futures::Stream<Item = std::result::Result<SnapshotChunk, tonic::Status>> + Unpin,
 {
     let base_dir = snap_mgr.create(replica_id);
-    info!(
-        "replica {replica_id} save incoming snapshot chunk stream into {}",
-        base_dir.display()
-    );
-
     std::fs::create_dir_all(&base_dir)?;
-    let mut snap_builder = SnapshotBuilder::new(replica_id, &base_dir);
+    let progress = snap_mgr.register_transfer(replica_id, TransferDirection::Receive, b"");
+    let mut snap_builder = SnapshotBuilder::new(replica_id, &base_dir, true);
     while let Some(resp) = chunk_stream.next().await {
         let chunk = resp?;
-        snap_builder.append(chunk).await?;
+        snap_builder.append(chunk, &progress, blocks).await?;
     }
 
-    let snap_meta = snap_builder.finish().await?;
-    Ok(snap_mgr.install(replica_id, &base_dir, &snap_meta))
+    snap_builder.finish_partial_file(&progress).await?;
+    super::create::stable_snapshot_meta(&base_dir, &snap_builder.meta).await?;
+    Ok(snap_mgr.install(replica_id, &base_dir, &snap_builder.meta))
+}
+
+/// Like `save_snapshot`, but the stream sent by `from_snap_mgr` is interrupted every
+/// `chunks_per_stream` chunks, and resumed from the checkpoint until the snapshot meta is received.
+#[cfg(test)]
+pub(super) async fn save_snapshot_with_interruptions(
+    snap_mgr: &SnapManager,
+    replica_id: u64,
+    from_snap_mgr: &SnapManager,
+    from_replica_id: u64,
+    snapshot_id: Vec<u8>,
+    chunks_per_stream: usize,
+) -> Result<Vec<u8>> {
+    let base_dir = snap_mgr.create(replica_id);
+    std::fs::create_dir_all(&base_dir)?;
+    let progress = snap_mgr.register_transfer(replica_id, TransferDirection::Receive, b"");
+    let mut snap_builder = SnapshotBuilder::new(replica_id, &base_dir, true);
+    loop {
+        let (file_index, file_offset) = snap_builder.checkpoint();
+        let request = SnapshotRequest {
+            replica_id: from_replica_id,
+            snapshot_id: snapshot_id.clone(),
+            file_index: file_index as u64,
+            file_offset,
+            ..Default::default()
+        };
+        let chunk_stream = super::send::send_snapshot(from_snap_mgr, request).await?;
+        let mut chunk_stream = chunk_stream.take(chunks_per_stream);
+        while let Some(resp) = chunk_stream.next().await {
+            snap_builder
+                .append(resp?, &progress, &BlockIndex::default())
+                .await?;
+        }
+        if snap_builder.meta.apply_state.is_some() {
+            break;
+        }
+    }
+
+    snap_builder.finish_partial_file(&progress).await?;
+    super::create::stable_snapshot_meta(&base_dir, &snap_builder.meta).await?;
+    Ok(snap_mgr.install(replica_id, &base_dir, &snap_builder.meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(sizes: &[u64]) -> Vec<SnapshotFile> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(idx, size)| SnapshotFile {
+                name: idx.to_string().into_bytes(),
+                size: *size,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_snapshot_files() {
+        assert!(split_files(&[], 4).is_empty());
+        assert_eq!(split_files(&files(&[1, 2, 3]), 1), vec![0..3]);
+        assert_eq!(split_files(&files(&[1, 2, 3]), 8), vec![0..1, 1..2, 2..3]);
+        assert_eq!(split_files(&files(&[10, 1, 1, 10]), 2), vec![0..2, 2..4]);
+        assert_eq!(split_files(&files(&[1, 1, 1, 10]), 2), vec![0..3, 3..4]);
+    }
 }
@@ -13,8 +13,10 @@
 // limitations under the License.
 
 pub mod apply;
+pub mod block;
 pub mod create;
 pub mod download;
+pub mod progress;
 pub mod send;
 
 use std::{
@@ -29,6 +31,7 @@ use futures::{channel::mpsc, StreamExt};
 use raft::prelude::{Snapshot, SnapshotMetadata};
 use tracing::{error, info, warn};
 
+use self::progress::{TransferDirection, TransferGuard, TransferRegistry, TransferStatus};
 pub use self::{create::dispatch_creating_snap_task, download::dispatch_downloading_snap_task};
 use crate::{
     runtime::{Executor, TaskPriority},
@@ -37,9 +40,12 @@ use crate::{
 };
 
 const SNAP_DATA: &str = "DATA";
-const SNAP_TEMP: &str = "TEMP";
 const SNAP_META: &str = "META";
 
+/// The latest snapshot of a removed replica is retained for this long, so that the blocks of it
+/// could be reused if the group is added back to this node soon, eg. after a transient outage.
+const RETIRED_SNAPSHOT_RETENTION: Duration = Duration::from_secs(600);
+
 #[derive(Debug)]
 pub enum RecycleSnapMode {
     RequiredIndex(u64),
@@ -70,6 +76,8 @@ pub struct ReplicaSnapManager {
     base_dir: PathBuf,
     next_snapshot_index: usize,
     snapshots: Vec<SnapshotInfo>,
+    /// The replica is removed, the latest snapshot is retained as the source of blocks.
+    retired_at: Option<Instant>,
 }
 
 #[derive(Clone)]
@@ -84,6 +92,7 @@ struct SnapManagerShared {
     root_dir: PathBuf,
     min_keep_intervals: Duration,
     inner: Mutex<SnapManagerInner>,
+    transfers: TransferRegistry,
 }
 
 struct SnapManagerInner {
@@ -103,6 +112,7 @@ impl SnapManager {
                     sender,
                     replicas: HashMap::default(),
                 }),
+                transfers: TransferRegistry::default(),
             }),
         }
     }
@@ -174,10 +184,28 @@ impl SnapManager {
                 root_dir: root_dir.to_owned(),
                 min_keep_intervals: Duration::from_secs(180),
                 inner: Mutex::new(SnapManagerInner { sender, replicas }),
+                transfers: TransferRegistry::default(),
             }),
         })
     }
 
+    /// Register a snapshot transfer, its progress is visible until the guard is dropped.
+    pub fn register_transfer(
+        &self,
+        replica_id: u64,
+        direction: TransferDirection,
+        snapshot_id: &[u8],
+    ) -> TransferGuard {
+        self.shared
+            .transfers
+            .register(replica_id, direction, snapshot_id)
+    }
+
+    /// Returns the progress of the ongoing snapshot transfers.
+    pub fn transfers(&self) -> Vec<TransferStatus> {
+        self.shared.transfers.transfers()
+    }
+
     /// Mark group as creating, and return a dir to save snapshot.
     pub fn create(&self, replica_id: u64) -> PathBuf {
         let mut inner = self.shared.inner.lock().unwrap();
@@ -264,25 +292,79 @@ impl SnapManager {
                     })
                     .map(|info| info.base_dir)
                     .collect::<Vec<_>>(),
-                RecycleSnapMode::All => {
-                    let snapshots = replica
+                RecycleSnapMode::All if replica.retired_at.is_none() => {
+                    let num_recycled = replica.snapshots.len().saturating_sub(1);
+                    replica.retired_at = Some(now);
+                    replica
                         .snapshots
-                        .iter()
-                        .map(|info| info.base_dir.clone())
-                        .collect::<Vec<_>>();
-                    inner.replicas.remove(&replica_id);
-                    snapshots
+                        .drain(..num_recycled)
+                        .map(|info| info.base_dir)
+                        .collect::<Vec<_>>()
                 }
+                RecycleSnapMode::All => vec![],
             };
+            let mut snapshots = snapshots
+                .into_iter()
+                .map(|dir| (replica_id, dir))
+                .collect::<Vec<_>>();
+            snapshots.extend(inner.drain_expired_retired(now));
             (inner.sender.clone(), snapshots)
         };
 
-        for snap_dir in snapshots {
+        for (replica_id, snap_dir) in snapshots {
             sender
                 .start_send((replica_id, snap_dir))
                 .unwrap_or_default();
         }
     }
+
+    /// Lock the local snapshots of the group, including the ones retained for removed replicas,
+    /// whose blocks could be reused by an incoming snapshot of the group.
+    pub fn group_snapshots(&self, group_id: u64) -> Vec<SnapshotGuard> {
+        let mut inner = self.shared.inner.lock().unwrap();
+        let mut guards = vec![];
+        for (replica_id, replica) in &mut inner.replicas {
+            for info in &mut replica.snapshots {
+                let same_group = matches!(&info.meta.group_desc, Some(desc) if desc.id == group_id);
+                if same_group && info.meta.files.iter().any(|f| !f.block_hashes.is_empty()) {
+                    info.ref_count += 1;
+                    guards.push(SnapshotGuard {
+                        replica_id: *replica_id,
+                        info: info.clone(),
+                        manager: self.clone(),
+                    });
+                }
+            }
+        }
+        guards
+    }
+}
+
+impl SnapManagerInner {
+    /// Remove the retired replicas which are retained long enough, and returns their snapshots.
+    fn drain_expired_retired(&mut self, now: Instant) -> Vec<(u64, PathBuf)> {
+        let expired = self
+            .replicas
+            .iter()
+            .filter(|(_, replica)| {
+                matches!(replica.retired_at, Some(at) if at + RETIRED_SNAPSHOT_RETENTION < now)
+                    && replica.snapshots.iter().all(|info| info.ref_count == 0)
+            })
+            .map(|(replica_id, _)| *replica_id)
+            .collect::<Vec<_>>();
+        let mut snapshots = vec![];
+        for replica_id in expired {
+            if let Some(replica) = self.replicas.remove(&replica_id) {
+                snapshots.extend(
+                    replica
+                        .snapshots
+                        .into_iter()
+                        .map(|info| (replica_id, info.base_dir)),
+                );
+            }
+        }
+        snapshots
+    }
 }
 
 impl ReplicaSnapManager {
@@ -292,6 +374,7 @@ impl ReplicaSnapManager {
             base_dir,
             next_snapshot_index: 0,
             snapshots: vec![],
+            retired_at: None,
         }
     }
 
@@ -417,7 +500,7 @@ mod tests {
     use crate::{
         raftgroup::SnapshotBuilder,
         runtime::{time::sleep, ExecutorOwner},
-        serverpb::v1::ApplyState,
+        serverpb::v1::{ApplyState, SnapshotRequest},
     };
 
     struct SimpleSnapshotBuilder {
@@ -532,15 +615,26 @@ mod tests {
             let snap_id = build_snapshot(&snap_manager, replica_id, 0, content.clone()).await;
 
             // Send snapshot on leader side.
-            let snapshot_chunk_stream = send::send_snapshot(&snap_manager, replica_id, snap_id)
-                .await
-                .unwrap();
+            let snapshot_chunk_stream = send::send_snapshot(
+                &snap_manager,
+                SnapshotRequest {
+                    replica_id,
+                    snapshot_id: snap_id,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
 
             // Save snapshot on follower side.
-            let new_snap_id =
-                download::save_snapshot(&snap_manager, replica_id + 1, snapshot_chunk_stream)
-                    .await
-                    .unwrap();
+            let new_snap_id = download::save_snapshot(
+                &snap_manager,
+                replica_id + 1,
+                snapshot_chunk_stream,
+                &block::BlockIndex::default(),
+            )
+            .await
+            .unwrap();
 
             info!("new snap id is {new_snap_id:?}");
 
@@ -579,15 +673,26 @@ mod tests {
                 .unwrap();
 
             // Send snapshot on leader side.
-            let snapshot_chunk_stream = send::send_snapshot(&snap_manager, replica_id, snap_id)
-                .await
-                .unwrap();
+            let snapshot_chunk_stream = send::send_snapshot(
+                &snap_manager,
+                SnapshotRequest {
+                    replica_id,
+                    snapshot_id: snap_id,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
 
             // Save snapshot on follower side.
-            let new_snap_id =
-                download::save_snapshot(&snap_manager, replica_id + 1, snapshot_chunk_stream)
-                    .await
-                    .unwrap();
+            let new_snap_id = download::save_snapshot(
+                &snap_manager,
+                replica_id + 1,
+                snapshot_chunk_stream,
+                &block::BlockIndex::default(),
+            )
+            .await
+            .unwrap();
 
             info!("new snap id is {new_snap_id:?}");
 
@@ -605,6 +710,140 @@ mod tests {
         });
     }
 
+    #[test]
+    fn resume_interrupted_snapshot_stream() {
+        let owner = ExecutorOwner::new(1);
+        let executor = owner.executor();
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("download-snapshot-resume").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager = SnapManager::recovery(&executor, &root_dir).unwrap();
+
+            // Prepare snapshot
+            let content_1 = vec![1, 2, 3, 4, 5, 6, 7, 1];
+            let content_2 = vec![1, 2, 3, 4, 5, 6, 7, 2];
+
+            let builder: Box<dyn SnapshotBuilder> = Box::new(MultiFilesSnapshotBuilder {
+                index: 1,
+                content_1: content_1.clone(),
+                content_2: content_2.clone(),
+            });
+            let snap_id = create::create_snapshot(replica_id, &snap_manager, builder)
+                .await
+                .unwrap();
+
+            // The stream is interrupted every 3 chunks, and resumed from the checkpoint.
+            let new_snap_id = download::save_snapshot_with_interruptions(
+                &snap_manager,
+                replica_id + 1,
+                &snap_manager,
+                replica_id,
+                snap_id,
+                3,
+            )
+            .await
+            .unwrap();
+
+            // Validate snapshot content.
+            let snap = snap_manager.lock_snap(replica_id + 1, &new_snap_id);
+            assert!(snap.is_some());
+            let snap = snap.unwrap();
+            let data = snap.base_dir.join(SNAP_DATA);
+            let received_content = std::fs::read_to_string(data.join("1")).unwrap();
+            assert_eq!(received_content.as_bytes(), content_1.as_slice());
+            let received_content = std::fs::read_to_string(data.join("2")).unwrap();
+            assert_eq!(received_content.as_bytes(), content_2.as_slice());
+
+            // All transfers are unregistered once finished.
+            assert!(snap_manager.transfers().is_empty());
+        });
+    }
+
+    #[test]
+    fn send_compressed_snapshot_with_known_blocks() {
+        let owner = ExecutorOwner::new(1);
+        let executor = owner.executor();
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("download-snapshot-known-blocks").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager = SnapManager::recovery(&executor, &root_dir).unwrap();
+
+            // The second snapshot shares the first and the last blocks with the first one.
+            let block_size = block::BLOCK_SIZE;
+            let old_content = (0..block_size * 3 + 10)
+                .map(|i| (i / 7) as u8)
+                .collect::<Vec<_>>();
+            let mut content = old_content.clone();
+            content[block_size + 1] = 0xFF;
+            content.extend_from_slice(&[1, 2, 3]);
+            let old_snap_id = build_snapshot(&snap_manager, replica_id, 1, old_content).await;
+            let snap_id = build_snapshot(&snap_manager, replica_id, 2, content.clone()).await;
+
+            let old_snap = snap_manager.lock_snap(replica_id, &old_snap_id).unwrap();
+            let blocks = block::BlockIndex::new(vec![old_snap]);
+            let files = snap_manager
+                .lock_snap(replica_id, &snap_id)
+                .unwrap()
+                .meta
+                .files
+                .clone();
+            let known_blocks = blocks.known_blocks(&files);
+            assert_eq!(known_blocks.len(), 2);
+
+            let snapshot_chunk_stream = send::send_snapshot(
+                &snap_manager,
+                SnapshotRequest {
+                    replica_id,
+                    snapshot_id: snap_id,
+                    compression: true,
+                    known_blocks,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            let new_snap_id = download::save_snapshot(
+                &snap_manager,
+                replica_id + 1,
+                snapshot_chunk_stream,
+                &blocks,
+            )
+            .await
+            .unwrap();
+
+            let snap = snap_manager
+                .lock_snap(replica_id + 1, &new_snap_id)
+                .unwrap();
+            let received_content = std::fs::read(snap.base_dir.join(SNAP_DATA)).unwrap();
+            assert_eq!(received_content, content);
+        });
+    }
+
+    #[test]
+    fn retain_latest_snapshot_of_removed_replica() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("snap-retire").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager = SnapManager::new(root_dir.path().to_owned());
+
+            let snap_id_1 = build_snapshot(&snap_manager, replica_id, 1, vec![1]).await;
+            let snap_id_2 = build_snapshot(&snap_manager, replica_id, 2, vec![2]).await;
+
+            snap_manager.recycle_snapshots(replica_id, RecycleSnapMode::All);
+            assert!(snap_manager.lock_snap(replica_id, &snap_id_1).is_none());
+            assert!(snap_manager.lock_snap(replica_id, &snap_id_2).is_some());
+            assert_eq!(snap_manager.group_snapshots(0).len(), 1);
+            assert!(snap_manager.group_snapshots(1).is_empty());
+        });
+    }
+
     #[test]
     fn recycle() {
         let owner = ExecutorOwner::new(1);
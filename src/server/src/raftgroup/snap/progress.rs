@@ -0,0 +1,175 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferDirection {
+    Send,
+    Receive,
+}
+
+/// The progress of a snapshot transfer, it is exposed by the admin api.
+#[derive(Debug, Serialize)]
+pub struct TransferStatus {
+    pub replica_id: u64,
+    pub direction: TransferDirection,
+    pub snapshot_id: String,
+    pub streams: u64,
+    pub total_files: u64,
+    pub transferred_files: u64,
+    pub total_bytes: u64,
+    pub transferred_bytes: u64,
+    pub retries: u64,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Clone, Default)]
+pub(super) struct TransferRegistry {
+    inner: Arc<Mutex<RegistryInner>>,
+}
+
+#[derive(Default)]
+struct RegistryInner {
+    next_id: u64,
+    transfers: HashMap<u64, Arc<TransferProgress>>,
+}
+
+pub struct TransferProgress {
+    replica_id: u64,
+    direction: TransferDirection,
+    snapshot_id: String,
+    started_at: Instant,
+
+    streams: AtomicU64,
+    total_files: AtomicU64,
+    transferred_files: AtomicU64,
+    total_bytes: AtomicU64,
+    transferred_bytes: AtomicU64,
+    retries: AtomicU64,
+}
+
+/// Unregister the transfer once dropped.
+pub struct TransferGuard {
+    id: u64,
+    progress: Arc<TransferProgress>,
+    registry: TransferRegistry,
+}
+
+impl TransferRegistry {
+    pub fn register(
+        &self,
+        replica_id: u64,
+        direction: TransferDirection,
+        snapshot_id: &[u8],
+    ) -> TransferGuard {
+        let progress = Arc::new(TransferProgress {
+            replica_id,
+            direction,
+            snapshot_id: String::from_utf8_lossy(snapshot_id).into_owned(),
+            started_at: Instant::now(),
+            streams: AtomicU64::new(1),
+            total_files: AtomicU64::default(),
+            transferred_files: AtomicU64::default(),
+            total_bytes: AtomicU64::default(),
+            transferred_bytes: AtomicU64::default(),
+            retries: AtomicU64::default(),
+        });
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.transfers.insert(id, progress.clone());
+        TransferGuard {
+            id,
+            progress,
+            registry: self.clone(),
+        }
+    }
+
+    pub fn transfers(&self) -> Vec<TransferStatus> {
+        let inner = self.inner.lock().unwrap();
+        let mut transfers = inner
+            .transfers
+            .iter()
+            .map(|(id, progress)| (*id, progress.status()))
+            .collect::<Vec<_>>();
+        transfers.sort_unstable_by_key(|(id, _)| *id);
+        transfers.into_iter().map(|(_, status)| status).collect()
+    }
+}
+
+impl TransferProgress {
+    pub fn set_streams(&self, streams: usize) {
+        self.streams.store(streams as u64, Ordering::Relaxed);
+    }
+
+    /// Add files to transfer, the total is unknown until the snapshot meta is received, when
+    /// transferring in a single stream.
+    pub fn add_files(&self, files: usize, bytes: u64) {
+        self.total_files.fetch_add(files as u64, Ordering::Relaxed);
+        self.total_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn on_file_transferred(&self) {
+        self.transferred_files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn on_bytes_transferred(&self, bytes: u64) {
+        self.transferred_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn on_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn status(&self) -> TransferStatus {
+        TransferStatus {
+            replica_id: self.replica_id,
+            direction: self.direction,
+            snapshot_id: self.snapshot_id.clone(),
+            streams: self.streams.load(Ordering::Relaxed),
+            total_files: self.total_files.load(Ordering::Relaxed),
+            transferred_files: self.transferred_files.load(Ordering::Relaxed),
+            total_bytes: self.total_bytes.load(Ordering::Relaxed),
+            transferred_bytes: self.transferred_bytes.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            elapsed_ms: self.started_at.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+impl std::ops::Deref for TransferGuard {
+    type Target = TransferProgress;
+
+    fn deref(&self) -> &Self::Target {
+        &self.progress
+    }
+}
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        let mut inner = self.registry.inner.lock().unwrap();
+        inner.transfers.remove(&self.id);
+    }
+}
@@ -12,9 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::{
+    collections::HashSet,
     ffi::OsStr,
     fs::File,
-    io::Read,
+    io::{Seek, SeekFrom},
     os::unix::ffi::OsStrExt,
     pin::Pin,
     task::{Context, Poll},
@@ -22,10 +23,14 @@ use std::{
 
 use tracing::debug;
 
-use super::{SnapManager, SnapshotGuard};
+use super::{
+    block::{block_hash, compress_block, read_block, BLOCK_SIZE},
+    progress::{TransferDirection, TransferGuard},
+    SnapManager, SnapshotGuard,
+};
 use crate::{
     raftgroup::metrics::*,
-    serverpb::v1::{snapshot_chunk, SnapshotChunk},
+    serverpb::v1::{snapshot_chunk, CompressedChunk, SnapshotChunk, SnapshotRequest},
     Error, Result,
 };
 
@@ -35,64 +40,98 @@ pub struct SnapshotChunkStream {
     info: SnapshotGuard,
     file: Option<File>,
     file_index: usize,
+    /// The offset to resume, of the first file to send.
+    file_offset: u64,
+    /// The files in `[file_index, file_end)` are sent, the meta is sent only if `file_end` is the
+    /// number of files.
+    file_end: usize,
+    progress: TransferGuard,
+    compression: bool,
+    /// The blocks the receiver already has, only their hashes are sent.
+    known_blocks: HashSet<Vec<u8>>,
+    /// The previous block of the file sent in this stream, it is the dictionary to compress the
+    /// next block.
+    last_block: Vec<u8>,
 }
 
 pub async fn send_snapshot(
     snap_mgr: &SnapManager,
-    replica_id: u64,
-    snapshot_id: Vec<u8>,
+    request: SnapshotRequest,
 ) -> Result<SnapshotChunkStream> {
-    let snapshot_info = match snap_mgr.lock_snap(replica_id, &snapshot_id) {
+    let replica_id = request.replica_id;
+    let snapshot_info = match snap_mgr.lock_snap(replica_id, &request.snapshot_id) {
         Some(snap_info) => snap_info,
         None => {
             return Err(Error::InvalidArgument("no such snapshot".to_string()));
         }
     };
 
+    let num_files = snapshot_info.meta.files.len();
+    let (file_index, file_end) = if request.meta_only {
+        (num_files, num_files)
+    } else if request.file_end == 0 {
+        (request.file_index as usize, num_files)
+    } else {
+        (request.file_index as usize, request.file_end as usize)
+    };
+    if file_index > file_end || file_end > num_files {
+        return Err(Error::InvalidArgument(format!(
+            "snapshot files [{file_index}, {file_end}) out of range, total {num_files}"
+        )));
+    }
+
+    let progress =
+        snap_mgr.register_transfer(replica_id, TransferDirection::Send, &request.snapshot_id);
+    let files = &snapshot_info.meta.files[file_index..file_end];
+    progress.add_files(
+        files.len(),
+        files
+            .iter()
+            .map(|f| f.size)
+            .sum::<u64>()
+            .saturating_sub(request.file_offset),
+    );
+
     RAFTGROUP_SEND_SNAPSHOT_TOTAL.inc();
-    Ok(SnapshotChunkStream::new(snapshot_info))
+    Ok(SnapshotChunkStream {
+        info: snapshot_info,
+        file: None,
+        file_index,
+        file_offset: request.file_offset,
+        file_end,
+        progress,
+        compression: request.compression,
+        known_blocks: request.known_blocks.into_iter().collect(),
+        last_block: vec![],
+    })
 }
 
 impl SnapshotChunkStream {
-    fn new(info: SnapshotGuard) -> Self {
-        SnapshotChunkStream {
-            info,
-            file: None,
-            file_index: 0,
-        }
-    }
-
     fn next_chunk(&mut self) -> Option<SnapResult> {
         use std::{fs::OpenOptions, io::ErrorKind};
 
         match self.file.as_mut() {
             // Send snapshot file chunk.
             Some(file) => {
-                let num_bytes = 32 * 1024;
-                let mut num_read = 0;
-                let mut chunk_data = vec![0; num_bytes];
-                while num_read < num_bytes {
-                    let n = match file.read(&mut chunk_data[num_read..]) {
-                        Ok(n) => n,
-                        Err(err) if err.kind() == ErrorKind::Interrupted => {
-                            continue;
-                        }
-                        Err(err) => return Some(Err(err.into())),
-                    };
-                    num_read += n;
-                    if n == 0 {
-                        self.file = None;
-                        self.file_index += 1;
-                        break;
-                    }
+                let block = match read_block(file) {
+                    Ok(block) => block,
+                    Err(err) => return Some(Err(err.into())),
+                };
+                if block.len() < BLOCK_SIZE {
+                    self.file = None;
+                    self.file_index += 1;
+                    self.progress.on_file_transferred();
                 }
-                chunk_data.truncate(num_read);
-                RAFTGROUP_SEND_SNAPSHOT_BYTES_TOTAL.inc_by(num_read as u64);
-                let value = snapshot_chunk::Value::ChunkData(chunk_data);
+                self.progress.on_bytes_transferred(block.len() as u64);
+                let value = match self.encode_block(&block) {
+                    Ok(value) => value,
+                    Err(err) => return Some(Err(err.into())),
+                };
+                self.last_block = block;
                 Some(Ok(SnapshotChunk { value: Some(value) }))
             }
             // Open new file and send file meta.
-            None if self.file_index < self.info.meta.files.len() => {
+            None if self.file_index < self.file_end => {
                 let file_meta = &self.info.meta.files[self.file_index];
                 let path = self.info.base_dir.join(OsStr::from_bytes(&file_meta.name)); // Eg: `DATA/1.sst`.
                 debug!(
@@ -101,15 +140,26 @@ impl SnapshotChunkStream {
                     file_meta.crc32,
                     file_meta.size
                 );
-                match OpenOptions::new().read(true).open(&path) {
-                    Ok(file) => self.file = Some(file),
+                let mut file = match OpenOptions::new().read(true).open(&path) {
+                    Ok(file) => file,
                     Err(err) => return Some(Err(err.into())),
+                };
+                // Resume the file from the offset received by the peer.
+                let offset = std::mem::take(&mut self.file_offset);
+                if offset > 0 {
+                    if let Err(err) = file.seek(SeekFrom::Start(offset)) {
+                        return Some(Err(err.into()));
+                    }
                 }
+                self.file = Some(file);
+                self.last_block.clear();
                 let value = snapshot_chunk::Value::File(file_meta.to_owned());
                 Some(Ok(SnapshotChunk { value: Some(value) }))
             }
             // Send snapshot meta.
-            None if self.file_index == self.info.meta.files.len() => {
+            None if self.file_index == self.file_end
+                && self.file_end == self.info.meta.files.len() =>
+            {
                 self.file_index += 1;
                 let value = snapshot_chunk::Value::Meta(self.info.meta.clone());
                 Some(Ok(SnapshotChunk { value: Some(value) }))
@@ -118,6 +168,32 @@ impl SnapshotChunkStream {
             None => None,
         }
     }
+
+    /// Encode the block as the hash if the receiver has it, or the compressed data if it is
+    /// smaller.
+    fn encode_block(&self, block: &[u8]) -> Result<snapshot_chunk::Value> {
+        if !block.is_empty() && !self.known_blocks.is_empty() {
+            let hash = block_hash(block);
+            if self.known_blocks.contains(&hash) {
+                RAFTGROUP_SEND_SNAPSHOT_SAVED_BYTES_TOTAL.inc_by(block.len() as u64);
+                return Ok(snapshot_chunk::Value::KnownBlock(hash));
+            }
+        }
+        if self.compression && !block.is_empty() {
+            let data = compress_block(block, &self.last_block)?;
+            if data.len() < block.len() {
+                let saved_bytes = block.len() - data.len();
+                RAFTGROUP_SEND_SNAPSHOT_BYTES_TOTAL.inc_by(data.len() as u64);
+                RAFTGROUP_SEND_SNAPSHOT_SAVED_BYTES_TOTAL.inc_by(saved_bytes as u64);
+                return Ok(snapshot_chunk::Value::CompressedChunk(CompressedChunk {
+                    data,
+                    size: block.len() as u32,
+                }));
+            }
+        }
+        RAFTGROUP_SEND_SNAPSHOT_BYTES_TOTAL.inc_by(block.len() as u64);
+        Ok(snapshot_chunk::Value::ChunkData(block.to_owned()))
+    }
 }
 
 impl futures::Stream for SnapshotChunkStream {
@@ -24,12 +24,21 @@ use raft::{prelude::*, GetEntriesContext, RaftState};
 use raft_engine::{Command, Engine, LogBatch, MessageExt};
 use tracing::{debug, error, info};
 
-use super::{node::WriteTask, snap::SnapManager, RaftConfig};
+use super::{
+    metrics::{RAFTGROUP_ENTRY_CACHE_HIT_TOTAL, RAFTGROUP_ENTRY_CACHE_MISS_TOTAL},
+    node::WriteTask,
+    snap::SnapManager,
+    RaftConfig,
+};
 use crate::{
+    node::memory::{MemoryTracker, Subsystem},
     serverpb::v1::{EntryId, EvalResult, RaftLocalState},
     Result,
 };
 
+/// The default capacity of the entry caches of all groups in a node.
+const DEFAULT_ENTRY_CACHE_CAPACITY: usize = 64 << 20;
+
 #[derive(Clone)]
 pub struct MessageExtTyped;
 
@@ -45,8 +54,15 @@ impl MessageExt for MessageExtTyped {
 /// are stored in `EntryCache`. Therefore, it can be guaranteed that in addition to replicate
 /// entries, other operations in raft-rs that need to read entries and their attributes directly
 /// should hit the EntryCache directly, and do not need to be read from disk.
+///
+/// The applied entries are also retained, so that the followers catching up within the window
+/// don't hit disk. They are evicted once the entry caches of all groups exceed the capacity, or
+/// the memory budget of node is exceeded.
 struct EntryCache {
     entries: VecDeque<Entry>,
+    /// The encoded size of the cached entries.
+    size: usize,
+    memory_tracker: Arc<MemoryTracker>,
 }
 
 /// The implementation of [`raft::Storage`].
@@ -56,6 +72,7 @@ pub struct Storage {
     first_index: u64,
     last_index: u64,
     cache: EntryCache,
+    cache_capacity: usize,
     local_state: RaftLocalState,
     hard_state: HardState,
     initial_conf_state: RefCell<Option<ConfState>>,
@@ -74,6 +91,7 @@ impl Storage {
         conf_state: ConfState,
         engine: Arc<Engine>,
         snap_mgr: SnapManager,
+        memory_tracker: Arc<MemoryTracker>,
     ) -> Result<Self> {
         let hard_state = engine
             .get_message::<HardState>(replica_id, keys::HARD_STATE_KEY)?
@@ -120,9 +138,9 @@ impl Storage {
                 None,
                 &mut entries,
             )?;
-            EntryCache::with_entries(entries)
+            EntryCache::with_entries(entries, memory_tracker)
         } else {
-            EntryCache::new()
+            EntryCache::new(memory_tracker)
         };
 
         debug!(
@@ -137,6 +155,9 @@ impl Storage {
             first_index,
             last_index,
             cache,
+            cache_capacity: cfg
+                .entry_cache_capacity
+                .unwrap_or(DEFAULT_ENTRY_CACHE_CAPACITY),
             hard_state,
             initial_conf_state: RefCell::new(Some(conf_state)),
             local_state,
@@ -185,7 +206,7 @@ impl Storage {
                     &self.hard_state,
                 )
                 .unwrap();
-            self.cache = EntryCache::new();
+            self.cache.clear();
             self.first_index = metadata.index + 1;
             self.last_index = metadata.index;
             self.local_state = raft_local_state;
@@ -202,7 +223,7 @@ impl Storage {
 
     #[inline]
     pub fn post_apply(&mut self, applied_index: u64) {
-        self.cache.drains_to(applied_index);
+        self.cache.drains_to(applied_index, self.cache_capacity);
     }
 
     #[inline]
@@ -215,6 +236,7 @@ impl Storage {
         // `term()` allowed range is `[first_index-1, last_index]`.
         let truncated_index = to.checked_sub(1).unwrap();
         let term = self.term(truncated_index).unwrap();
+        self.cache.compact_to(to);
         let local_state = RaftLocalState {
             replica_id: self.replica_id,
             last_truncated: Some(EntryId {
@@ -314,6 +336,7 @@ impl raft::Storage for Storage {
         let max_size = max_size.into().unwrap_or(u64::MAX) as usize;
         let cache_low = self.cache.first_index().unwrap_or(u64::MAX);
         if cache_low > low {
+            RAFTGROUP_ENTRY_CACHE_MISS_TOTAL.inc();
             // TODO(walter) support load entries asynchronously.
             let end = std::cmp::min(cache_low, high);
             if let Err(e) = self.engine.fetch_entries_to::<MessageExtTyped>(
@@ -336,6 +359,9 @@ impl raft::Storage for Storage {
         }
 
         if cache_low < high {
+            if cache_low <= low {
+                RAFTGROUP_ENTRY_CACHE_HIT_TOTAL.inc();
+            }
             let fetched_size = entries.iter().map(|e| e.data.len()).sum::<usize>();
             self.cache.fetch_entries_to(
                 std::cmp::max(low, cache_low),
@@ -410,16 +436,18 @@ impl raft::Storage for Storage {
 }
 
 impl EntryCache {
-    fn new() -> Self {
+    fn new(memory_tracker: Arc<MemoryTracker>) -> Self {
         EntryCache {
             entries: VecDeque::default(),
+            size: 0,
+            memory_tracker,
         }
     }
 
-    fn with_entries(entries: Vec<Entry>) -> Self {
-        EntryCache {
-            entries: entries.into(),
-        }
+    fn with_entries(entries: Vec<Entry>, memory_tracker: Arc<MemoryTracker>) -> Self {
+        let mut cache = EntryCache::new(memory_tracker);
+        cache.append(&entries);
+        cache
     }
 
     fn first_index(&self) -> Option<u64> {
@@ -479,7 +507,12 @@ impl EntryCache {
                 let truncate_to = cache_len
                     .checked_sub((cache_last_index - first_index + 1) as usize)
                     .unwrap_or_default();
-                self.entries.drain(truncate_to..);
+                let released = self
+                    .entries
+                    .drain(truncate_to..)
+                    .map(|e| e.encoded_len())
+                    .sum::<usize>();
+                self.release(released);
             } else if cache_last_index + 1 < first_index {
                 panic!(
                     "EntryCache::append unexpected hole: {} < {}",
@@ -488,15 +521,61 @@ impl EntryCache {
             }
         }
 
+        let consumed = entries.iter().map(|e| e.encoded_len()).sum::<usize>();
+        self.size += consumed;
+        self.memory_tracker
+            .consume(Subsystem::RaftEntryCache, consumed);
         self.entries.extend(entries.iter().cloned());
     }
 
-    pub fn drains_to(&mut self, applied_index: u64) {
+    /// Evicts the applied entries (except the last one) if the entry caches of all groups exceed
+    /// the capacity, or the memory budget of node is exceeded.
+    pub fn drains_to(&mut self, applied_index: u64, capacity: usize) {
+        if self.memory_tracker.usage(Subsystem::RaftEntryCache) <= capacity
+            && self.memory_tracker.excess() == 0
+        {
+            return;
+        }
         if let Some(cache_low) = self.entries.front().map(Entry::get_index) {
-            let len = applied_index.checked_sub(cache_low).unwrap() as usize;
-            self.entries.drain(0..(len / 2));
+            let len = applied_index.saturating_sub(cache_low) as usize;
+            self.drain_front(len);
         }
     }
+
+    /// Invalidates the entries compacted from the log engine, whose index is less than `to`.
+    pub fn compact_to(&mut self, to: u64) {
+        if let Some(cache_low) = self.entries.front().map(Entry::get_index) {
+            let len = std::cmp::min(to.saturating_sub(cache_low) as usize, self.entries.len());
+            self.drain_front(len);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.release(self.size);
+    }
+
+    fn drain_front(&mut self, len: usize) {
+        let released = self
+            .entries
+            .drain(..len)
+            .map(|e| e.encoded_len())
+            .sum::<usize>();
+        self.release(released);
+    }
+
+    fn release(&mut self, bytes: usize) {
+        self.size -= bytes;
+        self.memory_tracker
+            .release(Subsystem::RaftEntryCache, bytes);
+    }
+}
+
+impl Drop for EntryCache {
+    fn drop(&mut self) {
+        self.memory_tracker
+            .release(Subsystem::RaftEntryCache, self.size);
+    }
 }
 
 /// Write raft initial states into log engine.  All previous data of this raft will be clean first.
@@ -616,6 +695,24 @@ pub mod keys {
     pub const LOCAL_STATE_KEY: &[u8] = b"local_state";
 }
 
+/// Read all entries of the replica saved in the raft engine, it is used by offline tools.
+pub fn fetch_all_entries(engine: &Engine, replica_id: u64) -> Result<Vec<Entry>> {
+    let mut entries = vec![];
+    if let (Some(first_index), Some(last_index)) = (
+        engine.first_index(replica_id),
+        engine.last_index(replica_id),
+    ) {
+        engine.fetch_entries_to::<MessageExtTyped>(
+            replica_id,
+            first_index,
+            last_index + 1,
+            None,
+            &mut entries,
+        )?;
+    }
+    Ok(entries)
+}
+
 fn other_store_error(e: raft_engine::Error) -> raft::Error {
     raft::Error::Store(raft::StorageError::Other(Box::new(e)))
 }
@@ -631,6 +728,14 @@ mod tests {
     use super::*;
     use crate::runtime::*;
 
+    /// The applied entries are evicted from the entry cache immediately.
+    fn no_entry_cache_config() -> RaftConfig {
+        RaftConfig {
+            entry_cache_capacity: Some(0),
+            ..Default::default()
+        }
+    }
+
     fn mocked_entries(select_term: Option<u64>) -> Vec<(u64, u64)> {
         let entries = vec![
             // term 1
@@ -741,6 +846,7 @@ mod tests {
             ConfState::default(),
             engine.clone(),
             snap_mgr,
+            Arc::new(MemoryTracker::new(0)),
         )
         .await
         .unwrap();
@@ -793,6 +899,7 @@ mod tests {
             ConfState::default(),
             engine.clone(),
             snap_mgr.clone(),
+            Arc::new(MemoryTracker::new(0)),
         )
         .await
         .unwrap();
@@ -829,6 +936,7 @@ mod tests {
             ConfState::default(),
             engine.clone(),
             snap_mgr,
+            Arc::new(MemoryTracker::new(0)),
         )
         .await
         .unwrap();
@@ -854,12 +962,13 @@ mod tests {
 
         let snap_mgr = SnapManager::new(dir.path().join("snap"));
         let mut storage = Storage::open(
-            &RaftConfig::default(),
+            &no_entry_cache_config(),
             1,
             0,
             ConfState::default(),
             engine.clone(),
             snap_mgr,
+            Arc::new(MemoryTracker::new(0)),
         )
         .await
         .unwrap();
@@ -947,12 +1056,13 @@ mod tests {
 
             let snap_mgr = SnapManager::new(dir.path().join("snap"));
             let mut storage = Storage::open(
-                &RaftConfig::default(),
+                &no_entry_cache_config(),
                 1,
                 0,
                 ConfState::default(),
                 engine.clone(),
                 snap_mgr,
+                Arc::new(MemoryTracker::new(0)),
             )
             .await
             .unwrap();
@@ -1046,11 +1156,41 @@ mod tests {
         ];
 
         for t in tests {
-            let mut cache = EntryCache::new();
+            let mut cache = EntryCache::new(Arc::new(MemoryTracker::new(0)));
             cache.append(&make_entries(t.base));
             cache.append(&make_entries(t.append));
 
             assert_eq!(cache.entries, make_entries(t.expect));
         }
     }
+
+    #[test]
+    fn entry_cache_retains_applied_entries_within_capacity() {
+        let tracker = Arc::new(MemoryTracker::new(0));
+        let mut cache = EntryCache::new(tracker.clone());
+        cache.append(&make_entries(vec![(1, 1), (2, 1), (3, 1), (4, 1)]));
+        let size = tracker.usage(Subsystem::RaftEntryCache);
+        assert!(size > 0);
+
+        // Within the capacity, the applied entries are retained.
+        cache.drains_to(3, size);
+        assert_eq!(cache.first_index(), Some(1));
+
+        // Exceeds the capacity, the applied entries except the last one are evicted.
+        cache.drains_to(3, 0);
+        assert_eq!(cache.first_index(), Some(3));
+        assert!(tracker.usage(Subsystem::RaftEntryCache) < size);
+
+        // The compacted entries are invalidated.
+        cache.compact_to(4);
+        assert_eq!(cache.first_index(), Some(4));
+
+        // Truncated by conflicting entries.
+        cache.append(&make_entries(vec![(4, 2)]));
+        assert_eq!(cache.entries, make_entries(vec![(4, 2)]));
+        assert_eq!(tracker.usage(Subsystem::RaftEntryCache), cache.size);
+
+        drop(cache);
+        assert_eq!(tracker.usage(Subsystem::RaftEntryCache), 0);
+    }
 }
@@ -178,15 +178,12 @@ impl StreamingTask {
 pub async fn retrive_snapshot(
     trans_mgr: &TransportManager,
     target_replica: ReplicaDesc,
-    snapshot_id: Vec<u8>,
+    mut request: SnapshotRequest,
 ) -> Result<impl futures::Stream<Item = std::result::Result<SnapshotChunk, tonic::Status>>> {
     let node_desc = resolve_address(&*trans_mgr.resolver, target_replica.node_id).await?;
     let address = format!("http://{}", node_desc.addr);
     let mut client = RaftClient::connect(address).await?;
-    let request = SnapshotRequest {
-        replica_id: target_replica.id,
-        snapshot_id,
-    };
+    request.replica_id = target_replica.id;
     let resp = client.retrieve_snapshot(request).await?;
     Ok(resp.into_inner())
 }
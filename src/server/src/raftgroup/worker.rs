@@ -33,13 +33,14 @@ use super::{
     applier::{Applier, ReplicaCache},
     fsm::StateMachine,
     metrics::*,
-    monitor::WorkerPerfContext,
+    monitor::{RaftGroupStats, WorkerPerfContext},
     node::RaftNode,
     snap::{apply::apply_snapshot, RecycleSnapMode, SnapManager},
     transport::{Channel, TransportManager},
     RaftManager, ReadPolicy,
 };
 use crate::{
+    feature::{Feature, FeatureGates},
     raftgroup::monitor::record_perf_point,
     record_latency,
     runtime::Executor,
@@ -55,7 +56,7 @@ pub enum Request {
     Propose {
         eval_result: EvalResult,
         start: Instant,
-        sender: oneshot::Sender<Result<()>>,
+        sender: oneshot::Sender<Result<u64>>,
     },
     CreateSnapshotFinished,
     InstallSnapshot {
@@ -66,7 +67,7 @@ pub enum Request {
     },
     ChangeConfig {
         change: ChangeReplicas,
-        sender: oneshot::Sender<Result<()>>,
+        sender: oneshot::Sender<Result<u64>>,
     },
     Transfer {
         transferee: u64,
@@ -110,6 +111,9 @@ pub struct RaftGroupState {
     pub peers: HashMap<u64, PeerState>,
 }
 
+/// The number of ticks to decay the recent counters of `RaftGroupStats`.
+const STATS_DECAY_TICKS: u64 = 120;
+
 /// An abstraction for observing raft roles and state changes.
 pub trait StateObserver: Send {
     fn on_state_updated(&mut self, leader_id: u64, voted_for: u64, term: u64, role: RaftRole);
@@ -129,12 +133,16 @@ struct AdvanceImpl<'a> {
     snap_mgr: &'a SnapManager,
     observer: &'a mut Box<dyn StateObserver>,
     replica_cache: &'a mut ReplicaCache,
+    stats: &'a RaftGroupStats,
 }
 
 impl<'a> super::node::AdvanceTemplate for AdvanceImpl<'a> {
     fn send_messages(&mut self, msgs: Vec<Message>) {
         let mut seperated_msgs: HashMap<u64, Vec<Message>> = HashMap::default();
         for msg in msgs {
+            if msg.get_msg_type() == MessageType::MsgSnapshot {
+                self.stats.on_snapshot_sent();
+            }
             seperated_msgs
                 .entry(msg.to)
                 .or_insert_with(Vec::default)
@@ -165,6 +173,7 @@ impl<'a> super::node::AdvanceTemplate for AdvanceImpl<'a> {
     }
 
     fn on_state_updated(&mut self, leader_id: u64, voted_for: u64, term: u64, role: RaftRole) {
+        self.stats.on_state_updated(term, role);
         self.observer
             .on_state_updated(leader_id, voted_for, term, role);
     }
@@ -197,8 +206,11 @@ where
     trans_mgr: TransportManager,
     snap_mgr: SnapManager,
     engine: Arc<Engine>,
+    feature_gates: Arc<FeatureGates>,
     observer: Box<dyn StateObserver>,
     replica_cache: ReplicaCache,
+    stats: Arc<RaftGroupStats>,
+    ticks: u64,
 
     marker: PhantomData<M>,
 }
@@ -231,6 +243,8 @@ where
         replica_cache.insert(desc.clone());
         replica_cache.batch_insert(&state_machine.descriptor().replicas);
         let raft_node = RaftNode::new(group_id, replica_id, raft_mgr, state_machine).await?;
+        let stats = Arc::new(RaftGroupStats::new(group_id));
+        stats.on_state_updated(raft_node.raft().term, RaftRole::Follower);
 
         let (mut request_sender, request_receiver) =
             mpsc::channel(raft_mgr.cfg.max_inflight_requests);
@@ -255,8 +269,11 @@ where
             trans_mgr: raft_mgr.transport_mgr.clone(),
             snap_mgr: raft_mgr.snap_mgr.clone(),
             engine: raft_mgr.engine.clone(),
+            feature_gates: raft_mgr.feature_gates.clone(),
             observer,
             replica_cache,
+            stats,
+            ticks: 0,
             marker: PhantomData,
         })
     }
@@ -266,6 +283,11 @@ where
         self.request_sender.clone()
     }
 
+    #[inline]
+    pub fn stats(&self) -> Arc<RaftGroupStats> {
+        self.stats.clone()
+    }
+
     /// Poll requests and messages, forward both to `RaftNode`, and advance `RaftNode`.
     pub async fn run(mut self) -> Result<()> {
         debug!(
@@ -302,6 +324,10 @@ where
                 _ = interval.tick().fuse() => {
                     self.raft_node.tick();
                     self.compact_log(ctx);
+                    self.ticks += 1;
+                    if self.ticks % STATS_DECAY_TICKS == 0 {
+                        self.stats.decay();
+                    }
                 },
                 request = self.request_receiver.next() => if let Some(req) = request {
                     self.handle_request(ctx, req)?;
@@ -337,6 +363,7 @@ where
             snap_mgr: &self.snap_mgr,
             observer: &mut self.observer,
             replica_cache: &mut self.replica_cache,
+            stats: &self.stats,
         };
         if let Some(write_task) = self
             .raft_node
@@ -453,6 +480,10 @@ where
                 super::snap::dispatch_downloading_snap_task(
                     &self.executor,
                     self.desc.id,
+                    super::snap::download::DownloadOptions {
+                        streams: self.cfg.snapshot_streams.unwrap_or(1),
+                        compression: self.cfg.snapshot_compression,
+                    },
                     self.request_sender.clone(),
                     self.snap_mgr.clone(),
                     self.trans_mgr.clone(),
@@ -473,18 +504,22 @@ where
         ctx: &mut WorkerContext,
         eval_result: EvalResult,
         start: Instant,
-        sender: oneshot::Sender<Result<()>>,
+        sender: oneshot::Sender<Result<u64>>,
     ) {
-        use prost::Message;
-
-        let data = eval_result.encode_to_vec();
+        // The compressed entries are unreadable for the replicas of older versions.
+        let compression_threshold = if self.feature_gates.is_active(Feature::EntryCompression) {
+            self.cfg.entry_compression_threshold
+        } else {
+            0
+        };
+        let (data, context) = super::codec::encode_proposal(&eval_result, compression_threshold);
         ctx.accumulated_bytes += data.len();
         ctx.perf_ctx.num_proposal += 1;
-        self.raft_node.propose(data, vec![], sender);
+        self.raft_node.propose(data, context, sender);
         RAFTGROUP_WORKER_REQUEST_IN_QUEUE_DURATION_SECONDS.observe(elapsed_seconds(start));
     }
 
-    fn handle_conf_change(&mut self, change: ChangeReplicas, sender: oneshot::Sender<Result<()>>) {
+    fn handle_conf_change(&mut self, change: ChangeReplicas, sender: oneshot::Sender<Result<u64>>) {
         let cc = super::encode_to_conf_change(change);
         self.raft_node.propose_conf_change(vec![], cc, sender);
     }
@@ -509,6 +544,11 @@ where
         let mut to = self.raft_node.mut_state_machine().flushed_index();
 
         let status = self.raft_node.raft_status();
+        self.stats.on_apply_lag(
+            self.raft_node
+                .committed_index()
+                .saturating_sub(status.applied),
+        );
         if status.ss.raft_state == StateRole::Leader {
             if let Some(min_matched_index) = status
                 .progress
@@ -18,8 +18,8 @@ use engula_api::server::v1::{GroupDesc, NodeDesc};
 use serde::{Deserialize, Serialize};
 
 use self::{
-    policy_leader_cnt::LeaderCountPolicy, policy_replica_cnt::ReplicaCountPolicy,
-    policy_shard_cnt::ShardCountPolicy, source::NodeFilter,
+    policy_leader_cnt::LeaderCountPolicy, policy_read_replica::ReadReplicaPolicy,
+    policy_replica_cnt::ReplicaCountPolicy, policy_shard_cnt::ShardCountPolicy, source::NodeFilter,
 };
 use super::{metrics, OngoingStats, RootShared};
 use crate::{bootstrap::REPLICA_PER_GROUP, Result};
@@ -28,12 +28,24 @@ use crate::{bootstrap::REPLICA_PER_GROUP, Result};
 mod sim_test;
 
 mod policy_leader_cnt;
+mod policy_read_replica;
 mod policy_replica_cnt;
 mod policy_shard_cnt;
 mod source;
 
 pub use source::{AllocSource, SysAllocSource};
 
+/// The nodes whose storage penalty exceed it are considered sick, see
+/// `NodeCapacity::storage_penalty`.
+const SICK_STORAGE_PENALTY: f64 = 0.5;
+
+/// The nodes whose used space exceed this fraction of the total space are considered full.
+const FULL_SPACE_USAGE: f64 = 0.9;
+
+/// The nodes expected to be full within it are considered to be full soon, see
+/// `AllocSource::time_to_full`.
+const FULL_SOON_HORIZON: Duration = Duration::from_secs(2 * 60 * 60);
+
 #[derive(Clone, Debug)]
 pub enum ReplicaRoleAction {
     Replica(ReplicaAction),
@@ -63,6 +75,11 @@ pub enum LeaderAction {
     Shed(TransferLeader),
 }
 
+#[derive(Clone, Debug)]
+pub enum ReadReplicaAction {
+    Add(AddReadReplica),
+}
+
 #[derive(Debug, Clone)]
 pub struct TransferLeader {
     pub group: u64,
@@ -70,6 +87,7 @@ pub struct TransferLeader {
     pub src_replica: u64,
     pub target_node: u64,
     pub target_replica: u64,
+    pub reason: &'static str,
 }
 
 #[derive(Clone, Debug)]
@@ -80,6 +98,13 @@ pub struct ReallocateReplica {
     pub target_node: NodeDesc,
 }
 
+#[derive(Clone, Debug)]
+pub struct AddReadReplica {
+    pub group: u64,
+    pub zone: String,
+    pub target_node: NodeDesc,
+}
+
 #[derive(Clone, Debug)]
 pub struct ReallocateShard {
     pub shard: u64,
@@ -105,6 +130,17 @@ pub struct RootConfig {
     pub heartbeat_timeout_sec: u64,
     pub schedule_interval_sec: u64,
     pub max_create_group_retry_before_rollback: u64,
+
+    /// The number of schedule decisions kept in the root metadata, the oldest ones are removed.
+    ///
+    /// Default: 1024
+    pub schedule_history_size: Option<usize>,
+
+    /// The groups whose leader states are not received by the root within this duration are
+    /// probed actively, to repair the drift caused by lost reports and heartbeats.
+    ///
+    /// Default: 120
+    pub stale_group_state_threshold_sec: Option<u64>,
 }
 
 impl Default for RootConfig {
@@ -119,6 +155,8 @@ impl Default for RootConfig {
             heartbeat_timeout_sec: 4,
             schedule_interval_sec: 3,
             max_create_group_retry_before_rollback: 10,
+            schedule_history_size: None,
+            stale_group_state_threshold_sec: None,
         }
     }
 }
@@ -127,6 +165,10 @@ impl RootConfig {
     pub fn heartbeat_interval(&self) -> Duration {
         Duration::from_secs(self.liveness_threshold_sec - self.heartbeat_timeout_sec)
     }
+
+    pub fn stale_group_state_threshold(&self) -> Duration {
+        Duration::from_secs(self.stale_group_state_threshold_sec.unwrap_or(120))
+    }
 }
 
 #[derive(Clone)]
@@ -249,6 +291,17 @@ impl<T: AllocSource> Allocator<T> {
         ShardCountPolicy::with(self.alloc_source.to_owned()).allocate_shard(n)
     }
 
+    /// Compute the learners to add, for the read replica zones of collections.
+    pub async fn compute_read_replica_action(&self) -> Result<Vec<ReadReplicaAction>> {
+        if !self.config.enable_replica_balance {
+            return Ok(vec![]);
+        }
+
+        // always follow compute_replica_action() so no need refresh
+        // self.alloc_source.refresh_all().await?;
+        ReadReplicaPolicy::with(self.alloc_source.to_owned()).compute_placement()
+    }
+
     pub async fn compute_leader_action(&self) -> Result<Vec<LeaderAction>> {
         if !self.config.enable_leader_balance {
             return Ok(vec![]);
@@ -298,8 +351,85 @@ impl<T: AllocSource> Allocator<T> {
     }
 }
 
+impl<T: AllocSource> Allocator<T> {
+    /// Describe the scores of the nodes involved in the action, before and after applying it.
+    pub fn describe_replica_role_scores(&self, action: &ReplicaRoleAction) -> (String, String) {
+        let nodes = self.alloc_source.nodes(NodeFilter::All);
+        let capacity = |id: u64| {
+            nodes
+                .iter()
+                .find(|n| n.id == id)
+                .and_then(|n| n.capacity.clone())
+                .unwrap_or_default()
+        };
+        let (kind, src, dest, src_cnt, dest_cnt) = match action {
+            ReplicaRoleAction::Replica(ReplicaAction::Migrate(action)) => {
+                let (src, dest) = (action.source_node, action.target_node.id);
+                let (src_cnt, dest_cnt) =
+                    (capacity(src).replica_count, capacity(dest).replica_count);
+                ("replicas", src, dest, src_cnt, dest_cnt)
+            }
+            ReplicaRoleAction::Leader(LeaderAction::Shed(action)) => {
+                let (src, dest) = (action.src_node, action.target_node);
+                let (src_cnt, dest_cnt) = (capacity(src).leader_count, capacity(dest).leader_count);
+                ("leaders", src, dest, src_cnt, dest_cnt)
+            }
+            ReplicaRoleAction::Leader(LeaderAction::Noop) => return Default::default(),
+        };
+        (
+            format!("node {src} {kind} {src_cnt}, node {dest} {kind} {dest_cnt}"),
+            format!(
+                "node {src} {kind} {}, node {dest} {kind} {}",
+                src_cnt.saturating_sub(1),
+                dest_cnt + 1
+            ),
+        )
+    }
+
+    /// Describe the shard counts of the groups involved in the action, before and after applying
+    /// it.
+    pub fn describe_shard_scores(&self, action: &ShardAction) -> (String, String) {
+        let ShardAction::Migrate(action) = action;
+        let groups = self.alloc_source.groups();
+        let shards = |id: u64| groups.get(&id).map(|g| g.shards.len()).unwrap_or_default();
+        let (src, dest) = (action.source_group, action.target_group);
+        let (src_cnt, dest_cnt) = (shards(src), shards(dest));
+        (
+            format!("group {src} shards {src_cnt}, group {dest} shards {dest_cnt}"),
+            format!(
+                "group {src} shards {}, group {dest} shards {}",
+                src_cnt.saturating_sub(1),
+                dest_cnt + 1
+            ),
+        )
+    }
+}
+
 // Allocate Group's replica between nodes.
 impl<T: AllocSource> Allocator<T> {}
 
 // Allocate Group leader replica.
 impl<T: AllocSource> Allocator<T> {}
+
+fn is_storage_sick(n: &NodeDesc) -> bool {
+    n.capacity
+        .as_ref()
+        .map(|c| c.storage_penalty > SICK_STORAGE_PENALTY)
+        .unwrap_or_default()
+}
+
+/// Returns whether the node is full now, or will be full soon by the trend of its available space.
+fn is_running_out_of_space(n: &NodeDesc, time_to_full: Option<Duration>) -> bool {
+    let is_full = n
+        .capacity
+        .as_ref()
+        .map(|c| {
+            c.total_space > 0
+                && (c.available_space as f64) < c.total_space as f64 * (1.0 - FULL_SPACE_USAGE)
+        })
+        .unwrap_or_default();
+    is_full
+        || time_to_full
+            .map(|d| d < FULL_SOON_HORIZON)
+            .unwrap_or_default()
+}
@@ -14,7 +14,7 @@
 
 use std::{cmp::Ordering, collections::HashMap, sync::Arc};
 
-use engula_api::server::v1::{NodeDesc, RaftRole, ReplicaDesc, ReplicaRole};
+use engula_api::server::v1::{GroupDesc, NodeDesc, RaftRole, ReplicaDesc, ReplicaRole};
 use tracing::debug;
 
 use super::{source::NodeFilter, AllocSource, BalanceStatus, LeaderAction, TransferLeader};
@@ -41,8 +41,13 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
     }
 
     pub fn compute_balance(&self) -> Result<LeaderAction> {
-        let mean = self.mean_leader_count(NodeFilter::Schedulable);
         let candidate_nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
+        let group_zones = self.group_leader_zones();
+        if let Some(action) = self.try_honor_leader_zone(&candidate_nodes, &group_zones) {
+            return Ok(LeaderAction::Shed(action));
+        }
+
+        let mean = self.mean_leader_count(NodeFilter::Schedulable);
         let ranked_nodes = Self::rank_nodes_for_leader(candidate_nodes, mean);
         debug!(
             scored_nodes = ?ranked_nodes.iter().map(|(n, s)| format!("{}-{}({:?})", n.id, n.capacity.as_ref().unwrap().leader_count, s)).collect::<Vec<_>>(),
@@ -53,7 +58,9 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
             .iter()
             .filter(|(_, s)| *s == BalanceStatus::Overfull)
         {
-            if let Some(descision) = self.try_descrease_node_leader_count(n, &ranked_nodes, mean)? {
+            if let Some(descision) =
+                self.try_descrease_node_leader_count(n, &ranked_nodes, &group_zones, mean)?
+            {
                 match descision {
                     TransferDescision::TransferOnly {
                         group,
@@ -68,6 +75,7 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
                             src_replica,
                             target_node,
                             target_replica,
+                            reason: "leader count balance",
                         }));
                     }
                 }
@@ -80,6 +88,7 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
         &self,
         n: &NodeDesc,
         ranked_nodes: &[(NodeDesc, BalanceStatus)],
+        group_zones: &HashMap<u64, String>,
         mean: f64,
     ) -> Result<Option<TransferDescision>> {
         let node_replicas = self.alloc_source.node_replicas(&n.id);
@@ -123,6 +132,12 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
                 if Self::leader_balance_state(sim_count, mean) == BalanceStatus::Overfull {
                     continue;
                 }
+                if let Some(zone) = group_zones.get(group_id) {
+                    // Don't move the leader out of the preferred zone.
+                    if &n.zone == zone && &target_node.zone != zone {
+                        continue;
+                    }
+                }
                 let target_replica = exist_replica_in_nodes.get(&target_node.id);
                 if target_replica.is_none() {
                     continue;
@@ -140,6 +155,80 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
         Ok(None)
     }
 
+    /// Transfer the leader of a group to its preferred zone, the voter on the node with least
+    /// leaders in that zone is chosen.
+    fn try_honor_leader_zone(
+        &self,
+        candidate_nodes: &[NodeDesc],
+        group_zones: &HashMap<u64, String>,
+    ) -> Option<TransferLeader> {
+        let groups = self.alloc_source.groups();
+        let nodes = candidate_nodes
+            .iter()
+            .map(|n| (n.id, n))
+            .collect::<HashMap<_, _>>();
+        for (group_id, zone) in group_zones {
+            let group = match groups.get(group_id) {
+                Some(group) => group,
+                None => continue,
+            };
+            let leader = match group.replicas.iter().find(|r| self.is_leader(r.id)) {
+                Some(leader) => leader,
+                None => continue,
+            };
+            if nodes.get(&leader.node_id).map(|n| &n.zone) == Some(zone) {
+                continue;
+            }
+
+            let target = group
+                .replicas
+                .iter()
+                .filter(|r| r.id != leader.id && r.role == ReplicaRole::Voter as i32)
+                .filter(|r| self.alloc_source.replica_state(&r.id).is_some())
+                .filter_map(|r| nodes.get(&r.node_id).map(|n| (r, *n)))
+                .filter(|(_, n)| &n.zone == zone)
+                .min_by_key(|(_, n)| n.capacity.as_ref().unwrap().leader_count);
+            if let Some((target_replica, target_node)) = target {
+                debug!(
+                    group = group_id,
+                    zone = zone.as_str(),
+                    "transfer leader to the preferred zone"
+                );
+                return Some(TransferLeader {
+                    group: *group_id,
+                    src_node: leader.node_id,
+                    src_replica: leader.id,
+                    target_node: target_node.id,
+                    target_replica: target_replica.id,
+                    reason: "leader zone affinity",
+                });
+            }
+        }
+        None
+    }
+
+    fn is_leader(&self, replica_id: u64) -> bool {
+        self.alloc_source
+            .replica_state(&replica_id)
+            .map(|s| s.role == RaftRole::Leader as i32)
+            .unwrap_or_default()
+    }
+
+    /// The preferred leader zones of groups. A group has preferred zone only if all the collections
+    /// of its shards, which declare the preference, prefer the same zone.
+    fn group_leader_zones(&self) -> HashMap<u64, String> {
+        let leader_zones = self.alloc_source.leader_zones();
+        if leader_zones.is_empty() {
+            return HashMap::default();
+        }
+        self.alloc_source
+            .groups()
+            .values()
+            .filter(|g| g.id != ROOT_GROUP_ID)
+            .filter_map(|g| group_leader_zone(g, &leader_zones).map(|zone| (g.id, zone)))
+            .collect()
+    }
+
     fn rank_nodes_for_leader(ns: Vec<NodeDesc>, mean_cnt: f64) -> Vec<(NodeDesc, BalanceStatus)> {
         let mut with_status = ns
             .into_iter()
@@ -187,3 +276,16 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
         total_leaders / (nodes.len() as f64)
     }
 }
+
+fn group_leader_zone(group: &GroupDesc, leader_zones: &HashMap<u64, String>) -> Option<String> {
+    let mut preferred: Option<&String> = None;
+    for shard in &group.shards {
+        if let Some(zone) = leader_zones.get(&shard.collection_id) {
+            match preferred {
+                Some(preferred) if preferred != zone => return None,
+                _ => preferred = Some(zone),
+            }
+        }
+    }
+    preferred.cloned()
+}
@@ -0,0 +1,104 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use engula_api::server::v1::{GroupDesc, NodeDesc};
+
+use super::{source::NodeFilter, *};
+use crate::{bootstrap::ROOT_GROUP_ID, Result};
+
+/// Places a learner replica of the groups in each read replica zone of their collections, so
+/// that the clients of these zones serve eventually consistent reads locally.
+pub struct ReadReplicaPolicy<T: AllocSource> {
+    alloc_source: Arc<T>,
+}
+
+impl<T: AllocSource> ReadReplicaPolicy<T> {
+    pub fn with(alloc_source: Arc<T>) -> Self {
+        Self { alloc_source }
+    }
+
+    pub fn compute_placement(&self) -> Result<Vec<ReadReplicaAction>> {
+        let read_replica_zones = self.alloc_source.read_replica_zones();
+        if read_replica_zones.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let nodes = self.alloc_source.nodes(NodeFilter::All);
+        let node_zones = nodes
+            .iter()
+            .map(|n| (n.id, n.zone.as_str()))
+            .collect::<HashMap<_, _>>();
+        let candidate_nodes = self
+            .alloc_source
+            .nodes(NodeFilter::Schedulable)
+            .into_iter()
+            .filter(|n| !self.is_avoided(n))
+            .collect::<Vec<_>>();
+
+        let mut groups = self.alloc_source.groups().into_values().collect::<Vec<_>>();
+        groups.sort_by_key(|g| g.id);
+        let mut actions = Vec::new();
+        for group in groups.iter().filter(|g| g.id != ROOT_GROUP_ID) {
+            let placed_zones = group
+                .replicas
+                .iter()
+                .filter_map(|r| node_zones.get(&r.node_id).cloned())
+                .collect::<HashSet<_>>();
+            for zone in group_read_replica_zones(group, &read_replica_zones) {
+                if placed_zones.contains(zone.as_str()) {
+                    continue;
+                }
+                let target = candidate_nodes
+                    .iter()
+                    .filter(|n| n.zone == zone)
+                    .filter(|n| !group.replicas.iter().any(|r| r.node_id == n.id))
+                    .min_by_key(|n| (n.capacity.as_ref().unwrap().replica_count, n.id));
+                if let Some(target) = target {
+                    actions.push(ReadReplicaAction::Add(AddReadReplica {
+                        group: group.id,
+                        zone,
+                        target_node: target.to_owned(),
+                    }));
+                }
+            }
+        }
+        Ok(actions)
+    }
+
+    fn is_avoided(&self, n: &NodeDesc) -> bool {
+        is_storage_sick(n) || is_running_out_of_space(n, self.alloc_source.time_to_full(&n.id))
+    }
+}
+
+/// The read replica zones required by the collections of the shards in the group.
+fn group_read_replica_zones(
+    group: &GroupDesc,
+    read_replica_zones: &HashMap<u64, Vec<String>>,
+) -> Vec<String> {
+    let mut zones = group
+        .shards
+        .iter()
+        .filter_map(|s| read_replica_zones.get(&s.collection_id))
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+    zones.sort_unstable();
+    zones.dedup();
+    zones
+}
@@ -18,7 +18,7 @@ use std::{
     sync::Arc,
 };
 
-use engula_api::server::v1::{NodeDesc, ReplicaDesc};
+use engula_api::server::v1::{NodeDesc, ReplicaDesc, ReplicaRole};
 
 use super::{source::NodeFilter, *};
 use crate::{bootstrap::ROOT_GROUP_ID, root::OngoingStats, Result};
@@ -46,11 +46,14 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         // skip the nodes already have group replicas.
         candidate_nodes.retain(|n| !existing_replica_nodes.iter().any(|rn| *rn == n.id));
 
-        // sort by alloc score
+        // sort by alloc score, the nodes with sick storage or running out of space are allocated
+        // at last.
         candidate_nodes.sort_by(|n1, n2| {
-            self.node_alloc_score(n2)
-                .partial_cmp(&self.node_alloc_score(n1))
-                .unwrap()
+            self.is_avoided(n1).cmp(&self.is_avoided(n2)).then_with(|| {
+                self.node_alloc_score(n2)
+                    .partial_cmp(&self.node_alloc_score(n1))
+                    .unwrap()
+            })
         });
 
         Ok(candidate_nodes.into_iter().take(wanted_count).collect())
@@ -110,6 +113,9 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
             if *state != BalanceStatus::Underfull {
                 break;
             }
+            if self.is_avoided(target) {
+                continue;
+            }
             let sim_count = (self.node_replica_count(target) + 1) as f64;
             if Self::node_balance_state(sim_count, mean) == BalanceStatus::Overfull {
                 continue;
@@ -135,8 +141,10 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         self.alloc_source
             .node_replicas(&src.id)
             .into_iter()
-            .find(|(_, g)| {
-                if *g == ROOT_GROUP_ID {
+            .find(|(r, g)| {
+                // The learners are read replicas placed in the specified zones, see
+                // `ReadReplicaPolicy`.
+                if *g == ROOT_GROUP_ID || r.role != ReplicaRole::Voter as i32 {
                     return false;
                 }
                 if let Some(exist_nodes) = group_nodes.get(g) {
@@ -200,6 +208,11 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         BalanceStatus::Balanced
     }
 
+    /// The nodes with sick storage or running out of space are avoided as placement targets.
+    fn is_avoided(&self, n: &NodeDesc) -> bool {
+        is_storage_sick(n) || is_running_out_of_space(n, self.alloc_source.time_to_full(&n.id))
+    }
+
     fn node_alloc_score(&self, n: &NodeDesc) -> f64 {
         // TODO: add more rule to calculate score.
         -(self.node_replica_count(n) as f64)
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{cmp::Ordering, sync::Arc};
+use std::{cmp::Ordering, collections::HashMap, sync::Arc};
 
 use engula_api::server::v1::{GroupDesc, ShardDesc};
 use tracing::debug;
@@ -20,6 +20,13 @@ use tracing::debug;
 use super::{AllocSource, ReallocateShard, ShardAction};
 use crate::{bootstrap::ROOT_GROUP_ID, root::allocator::BalanceStatus, Result};
 
+/// A group is considered oversized if its size exceeds the mean by this ratio.
+const SHARD_SIZE_IMBALANCE_RATIO: f64 = 0.5;
+
+/// The groups whose size differs less than it are not balanced by size, so that tiny shards
+/// aren't moved around.
+const MIN_SIZE_TO_BALANCE: u64 = 64 << 20;
+
 pub struct ShardCountPolicy<T: AllocSource> {
     alloc_source: Arc<T>,
 }
@@ -57,7 +64,72 @@ impl<T: AllocSource> ShardCountPolicy<T> {
             }
         }
 
-        Ok(vec![])
+        Ok(self.compute_size_balance(mean_cnt).into_iter().collect())
+    }
+
+    /// Balance the size of groups once the shard counts are balanced, by moving a shard from the
+    /// largest oversized group to the smallest group whose shard count isn't above the mean.
+    fn compute_size_balance(&self, mean_cnt: f64) -> Option<ShardAction> {
+        let reported = self.alloc_source.shard_sizes();
+        let groups = self.current_user_groups();
+        // Only the groups with reported sizes are involved, the sizes of shards moved away are
+        // dropped until the next report.
+        let mut sized_groups = groups
+            .into_iter()
+            .filter_map(|g| {
+                let sizes = reported.get(&g.id)?;
+                let sizes = g
+                    .shards
+                    .iter()
+                    .map(|s| (s.id, sizes.get(&s.id).cloned().unwrap_or_default()))
+                    .collect::<HashMap<_, _>>();
+                Some((g, sizes))
+            })
+            .collect::<Vec<_>>();
+        if sized_groups.len() < 2 {
+            return None;
+        }
+        sized_groups.sort_by_key(|(_, sizes)| sizes.values().sum::<u64>());
+
+        let total_size = sized_groups
+            .iter()
+            .map(|(_, sizes)| sizes.values().sum::<u64>())
+            .sum::<u64>();
+        let mean_size = total_size as f64 / sized_groups.len() as f64;
+        let (src_group, src_sizes) = sized_groups.last().unwrap();
+        let src_size = src_sizes.values().sum::<u64>();
+        if (src_size as f64) <= mean_size * (1.0 + SHARD_SIZE_IMBALANCE_RATIO) {
+            return None;
+        }
+
+        for (target, target_sizes) in &sized_groups[..sized_groups.len() - 1] {
+            // The count policy moves a small shard back if the target becomes overfull.
+            if target.shards.len() as f64 > mean_cnt {
+                continue;
+            }
+            let target_size = target_sizes.values().sum::<u64>();
+            let delta = src_size.saturating_sub(target_size);
+            if delta < MIN_SIZE_TO_BALANCE {
+                break;
+            }
+            // Moving a shard larger than half of the delta just reverses the imbalance.
+            let shard = src_sizes
+                .iter()
+                .filter(|(_, &size)| size > 0 && size <= delta / 2)
+                .max_by_key(|(_, &size)| size)
+                .map(|(&id, _)| id)?;
+            debug!(
+                "balance group size, move shard {shard} from group {} ({src_size} bytes) to \
+                 group {} ({target_size} bytes)",
+                src_group.id, target.id,
+            );
+            return Some(ShardAction::Migrate(ReallocateShard {
+                shard,
+                source_group: src_group.id,
+                target_group: target.id,
+            }));
+        }
+        None
     }
 
     fn mean_shard_count(&self) -> f64 {
@@ -134,9 +206,17 @@ impl<T: AllocSource> ShardCountPolicy<T> {
         src_group: &GroupDesc,
         _target_group: &GroupDesc,
     ) -> Option<ShardDesc> {
-        let replicas = src_group.shards.to_owned();
-        // TODO: ranking shards and choose the preferred one
-        replicas.get(0).map(ToOwned::to_owned)
+        // Prefer the smallest shard, so that the count is balanced with less data moved.
+        let sizes = self
+            .alloc_source
+            .shard_sizes()
+            .remove(&src_group.id)
+            .unwrap_or_default();
+        src_group
+            .shards
+            .iter()
+            .min_by_key(|s| sizes.get(&s.id).cloned().unwrap_or(u64::MAX))
+            .map(ToOwned::to_owned)
     }
 
     fn current_user_groups(&self) -> Vec<GroupDesc> {
@@ -54,8 +54,12 @@ fn sim_boostrap_join_node_balance() {
                 cpu_nums: 2.0,
                 replica_count: 1,
                 leader_count: 1,
+                storage_penalty: 0.0,
+                total_space: 0,
+                available_space: 0,
             }),
             status: NodeStatus::Active as i32,
+            ..Default::default()
         }]);
         p.set_replica_states(vec![ReplicaState {
             replica_id: 1,
@@ -80,8 +84,12 @@ fn sim_boostrap_join_node_balance() {
                     cpu_nums: 2.0,
                     replica_count: 0,
                     leader_count: 0,
+                    storage_penalty: 0.0,
+                    total_space: 0,
+                    available_space: 0,
                 }),
                 status: NodeStatus::Active as i32,
+                ..Default::default()
             },
             NodeDesc {
                 id: 3,
@@ -90,8 +98,12 @@ fn sim_boostrap_join_node_balance() {
                     cpu_nums: 2.0,
                     replica_count: 0,
                     leader_count: 0,
+                    storage_penalty: 0.0,
+                    total_space: 0,
+                    available_space: 0,
                 }),
                 status: NodeStatus::Active as i32,
+                ..Default::default()
             },
         ]);
         p.set_nodes(nodes);
@@ -234,8 +246,12 @@ fn sim_boostrap_join_node_balance() {
                 cpu_nums: 2.0,
                 replica_count: 0,
                 leader_count: 0,
+                storage_penalty: 0.0,
+                total_space: 0,
+                available_space: 0,
             }),
             status: NodeStatus::Active as i32,
+            ..Default::default()
         }]);
         p.set_nodes(nodes);
         p.display();
@@ -401,10 +417,332 @@ fn sim_boostrap_join_node_balance() {
     });
 }
 
+#[test]
+fn sim_leader_prefer_zone() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        p.set_nodes(
+            ["a", "b", "c"]
+                .iter()
+                .enumerate()
+                .map(|(idx, zone)| NodeDesc {
+                    id: idx as u64 + 1,
+                    zone: zone.to_string(),
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: 2.0,
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        p.set_groups(vec![GroupDesc {
+            id: 1,
+            epoch: 0,
+            shards: vec![ShardDesc {
+                id: 1,
+                collection_id: 10,
+                ..Default::default()
+            }],
+            replicas: (1..=3)
+                .map(|id| ReplicaDesc {
+                    id,
+                    node_id: id,
+                    role: ReplicaRole::Voter.into(),
+                })
+                .collect(),
+        }]);
+        p.set_replica_states(
+            (1..=3)
+                .map(|id| ReplicaState {
+                    replica_id: id,
+                    group_id: 1,
+                    term: 1,
+                    role: if id == 1 {
+                        RaftRole::Leader.into()
+                    } else {
+                        RaftRole::Follower.into()
+                    },
+                    node_id: id,
+                    ..Default::default()
+                })
+                .collect(),
+        );
+
+        // No preference, the leaders are balanced.
+        let acts = a.compute_leader_action().await.unwrap();
+        assert!(acts.is_empty());
+
+        p.set_leader_zones(HashMap::from([(10, "c".to_owned())]));
+        let acts = a.compute_leader_action().await.unwrap();
+        assert!(matches!(
+            acts.as_slice(),
+            [LeaderAction::Shed(TransferLeader {
+                group: 1,
+                src_replica: 1,
+                target_replica: 3,
+                reason: "leader zone affinity",
+                ..
+            })]
+        ));
+    });
+}
+
+#[test]
+fn sim_allocate_avoid_sick_storage() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        // Node 1 has the fewest replicas, but its disks are sick.
+        p.set_nodes(
+            [(1, 0.8), (2, 0.0), (3, 0.1)]
+                .iter()
+                .map(|(id, storage_penalty)| NodeDesc {
+                    id: *id,
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: 2.0,
+                        replica_count: *id,
+                        storage_penalty: *storage_penalty,
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    ..Default::default()
+                })
+                .collect(),
+        );
+
+        let nodes = a.allocate_group_replica(vec![], 2).await.unwrap();
+        assert_eq!(nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![2, 3]);
+
+        // The sick nodes are still allocated if there is no other choice.
+        let nodes = a.allocate_group_replica(vec![2], 2).await.unwrap();
+        assert_eq!(nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![3, 1]);
+    });
+}
+
+#[test]
+fn sim_allocate_avoid_filling_nodes() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        // Node 1 has the fewest replicas, but it is full; node 2 is filling quickly.
+        p.set_nodes(
+            [(1, 5), (2, 500), (3, 500), (4, 500)]
+                .iter()
+                .map(|(id, available_space)| NodeDesc {
+                    id: *id,
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: 2.0,
+                        replica_count: *id,
+                        total_space: 1000,
+                        available_space: *available_space,
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        p.time_to_full
+            .lock()
+            .unwrap()
+            .insert(2, Duration::from_secs(60 * 30));
+        p.time_to_full
+            .lock()
+            .unwrap()
+            .insert(3, Duration::from_secs(60 * 60 * 24));
+
+        let nodes = a.allocate_group_replica(vec![], 2).await.unwrap();
+        assert_eq!(nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![3, 4]);
+
+        let nodes = a.allocate_group_replica(vec![3, 4], 2).await.unwrap();
+        assert_eq!(nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![1, 2]);
+    });
+}
+
+#[test]
+fn sim_place_read_replica_in_zone() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        p.set_nodes(
+            ["a", "a", "a", "b", "b"]
+                .iter()
+                .enumerate()
+                .map(|(idx, zone)| NodeDesc {
+                    id: idx as u64 + 1,
+                    zone: zone.to_string(),
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: 2.0,
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        let group = |id: u64, collection_id: u64, nodes: &[u64]| GroupDesc {
+            id,
+            epoch: 0,
+            shards: vec![ShardDesc {
+                id,
+                collection_id,
+                ..Default::default()
+            }],
+            replicas: nodes
+                .iter()
+                .map(|node_id| ReplicaDesc {
+                    id: id * 10 + node_id,
+                    node_id: *node_id,
+                    role: ReplicaRole::Voter.into(),
+                })
+                .collect(),
+        };
+        p.set_groups(vec![
+            group(1, 10, &[1, 2, 3]),
+            group(2, 20, &[1, 4, 5]),
+            group(3, 30, &[5]),
+        ]);
+
+        // No read replica zones.
+        let acts = a.compute_read_replica_action().await.unwrap();
+        assert!(acts.is_empty());
+
+        // The node with fewest replicas in the zone is chosen.
+        p.set_read_replica_zones(HashMap::from([(10, vec!["b".to_owned()])]));
+        let acts = a.compute_read_replica_action().await.unwrap();
+        assert!(matches!(
+            acts.as_slice(),
+            [ReadReplicaAction::Add(AddReadReplica {
+                group: 1,
+                target_node: NodeDesc { id: 4, .. },
+                ..
+            })]
+        ));
+
+        // The group has a replica in the zone already.
+        p.set_read_replica_zones(HashMap::from([(20, vec!["b".to_owned()])]));
+        let acts = a.compute_read_replica_action().await.unwrap();
+        assert!(acts.is_empty());
+    });
+}
+
+#[test]
+fn sim_balance_shard_size() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        p.set_nodes(
+            (1..=3)
+                .map(|id| NodeDesc {
+                    id,
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: 2.0,
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        let group = |id: u64, shards: &[u64]| GroupDesc {
+            id,
+            epoch: 0,
+            shards: shards
+                .iter()
+                .map(|shard_id| ShardDesc {
+                    id: *shard_id,
+                    ..Default::default()
+                })
+                .collect(),
+            replicas: (1..=3)
+                .map(|node_id| ReplicaDesc {
+                    id: id * 10 + node_id,
+                    node_id,
+                    role: ReplicaRole::Voter.into(),
+                })
+                .collect(),
+        };
+        p.set_groups(vec![
+            group(1, &[1, 2]),
+            group(2, &[3, 4]),
+            group(3, &[5, 6]),
+        ]);
+
+        // The shard counts are balanced and no size is reported.
+        let acts = a.compute_shard_action().await.unwrap();
+        assert!(acts.is_empty());
+
+        const MB: u64 = 1 << 20;
+        p.set_shard_sizes(HashMap::from([
+            (1, HashMap::from([(1, 1024 * MB), (2, 600 * MB)])),
+            (2, HashMap::from([(3, 100 * MB), (4, 100 * MB)])),
+            (3, HashMap::from([(5, 50 * MB), (6, 50 * MB)])),
+        ]));
+
+        // The largest shard within half of the delta is moved to the smallest group.
+        let acts = a.compute_shard_action().await.unwrap();
+        assert!(matches!(
+            acts.as_slice(),
+            [ShardAction::Migrate(ReallocateShard {
+                shard: 2,
+                source_group: 1,
+                target_group: 3,
+            })]
+        ));
+        p.move_shards(1, 3, 2);
+        p.set_shard_sizes(HashMap::from([
+            (1, HashMap::from([(1, 1024 * MB)])),
+            (2, HashMap::from([(3, 100 * MB), (4, 100 * MB)])),
+            (
+                3,
+                HashMap::from([(5, 50 * MB), (6, 50 * MB), (2, 600 * MB)]),
+            ),
+        ]));
+
+        // The shard count is rebalanced by moving the smallest shard back.
+        let acts = a.compute_shard_action().await.unwrap();
+        assert!(matches!(
+            acts.as_slice(),
+            [ShardAction::Migrate(ReallocateShard {
+                shard: 5,
+                source_group: 3,
+                target_group: 1,
+            })]
+        ));
+    });
+}
+
 pub struct MockInfoProvider {
     nodes: Arc<Mutex<Vec<NodeDesc>>>,
     groups: Arc<Mutex<GroupInfo>>,
     replicas: Arc<Mutex<HashMap<u64, ReplicaState>>>,
+    leader_zones: Arc<Mutex<HashMap<u64, String>>>,
+    read_replica_zones: Arc<Mutex<HashMap<u64, Vec<String>>>>,
+    time_to_full: Arc<Mutex<HashMap<u64, Duration>>>,
+    shard_sizes: Arc<Mutex<HashMap<u64, HashMap<u64, u64>>>>,
     shard_id_gen: AtomicU64,
 }
 
@@ -420,6 +758,10 @@ impl MockInfoProvider {
             nodes: Default::default(),
             groups: Default::default(),
             replicas: Default::default(),
+            leader_zones: Default::default(),
+            read_replica_zones: Default::default(),
+            time_to_full: Default::default(),
+            shard_sizes: Default::default(),
             shard_id_gen: AtomicU64::new(1),
         }
     }
@@ -459,6 +801,22 @@ impl AllocSource for MockInfoProvider {
         let replica_info = self.replicas.lock().unwrap();
         replica_info.iter().map(|e| e.1.to_owned()).collect()
     }
+
+    fn leader_zones(&self) -> HashMap<u64, String> {
+        self.leader_zones.lock().unwrap().clone()
+    }
+
+    fn read_replica_zones(&self) -> HashMap<u64, Vec<String>> {
+        self.read_replica_zones.lock().unwrap().clone()
+    }
+
+    fn time_to_full(&self, node_id: &u64) -> Option<Duration> {
+        self.time_to_full.lock().unwrap().get(node_id).cloned()
+    }
+
+    fn shard_sizes(&self) -> HashMap<u64, HashMap<u64, u64>> {
+        self.shard_sizes.lock().unwrap().clone()
+    }
 }
 
 impl MockInfoProvider {
@@ -546,6 +904,18 @@ impl MockInfoProvider {
         let _ = std::mem::replace(&mut *replicas, id_to_state);
     }
 
+    fn set_leader_zones(&self, leader_zones: HashMap<u64, String>) {
+        *self.leader_zones.lock().unwrap() = leader_zones;
+    }
+
+    fn set_read_replica_zones(&self, read_replica_zones: HashMap<u64, Vec<String>>) {
+        *self.read_replica_zones.lock().unwrap() = read_replica_zones;
+    }
+
+    fn set_shard_sizes(&self, shard_sizes: HashMap<u64, HashMap<u64, u64>>) {
+        *self.shard_sizes.lock().unwrap() = shard_sizes;
+    }
+
     pub fn move_replica(&self, replica_id: u64, node: u64) {
         let mut groups = self.groups();
         for group in groups.values_mut() {
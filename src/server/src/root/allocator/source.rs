@@ -13,14 +13,27 @@
 // limitations under the License.
 
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, VecDeque},
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use engula_api::server::v1::*;
 
 use super::RootShared;
-use crate::{root::liveness::Liveness, Result};
+use crate::{
+    root::{liveness::Liveness, shard_size::ShardSizes},
+    Result,
+};
+
+/// The samples of capacity older than it are dropped.
+const CAPACITY_HISTORY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// The min interval between two samples of capacity.
+const CAPACITY_SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The min number of samples required to forecast the trend of capacity.
+const MIN_CAPACITY_SAMPLES: usize = 3;
 
 pub enum NodeFilter {
     All,
@@ -43,16 +56,36 @@ pub trait AllocSource {
     fn replica_state(&self, replica_id: &u64) -> Option<ReplicaState>;
 
     fn replica_states(&self) -> Vec<ReplicaState>;
+
+    /// The preferred leader zones of collections, the collections without preference are
+    /// skipped.
+    fn leader_zones(&self) -> HashMap<u64, String>;
+
+    /// The read replica zones of collections, the collections without read replicas are
+    /// skipped.
+    fn read_replica_zones(&self) -> HashMap<u64, Vec<String>>;
+
+    /// The estimated duration before the disk of node is full, by the trend of its available
+    /// space. `None` is returned if the node isn't filling up or there isn't enough history.
+    fn time_to_full(&self, node_id: &u64) -> Option<Duration>;
+
+    /// The approximate sizes of shards reported by the leader of each group, the groups without
+    /// report are skipped.
+    fn shard_sizes(&self) -> HashMap<u64 /* group */, HashMap<u64 /* shard */, u64>>;
 }
 
 #[derive(Clone)]
 pub struct SysAllocSource {
     root: Arc<RootShared>,
     liveness: Arc<Liveness>,
+    shard_sizes: Arc<ShardSizes>,
 
     nodes: Arc<Mutex<Vec<NodeDesc>>>,
     groups: Arc<Mutex<GroupInfo>>,
     replicas: Arc<Mutex<ReplicaInfo>>,
+    leader_zones: Arc<Mutex<HashMap<u64, String>>>,
+    read_replica_zones: Arc<Mutex<HashMap<u64, Vec<String>>>>,
+    capacity_history: Arc<Mutex<HashMap<u64, CapacityHistory>>>,
 }
 
 #[derive(Default)]
@@ -66,14 +99,28 @@ struct ReplicaInfo {
     replicas: HashMap<u64, ReplicaState>,
 }
 
+/// A short history of the available space of a node.
+#[derive(Default)]
+struct CapacityHistory {
+    samples: VecDeque<(Instant, u64)>,
+}
+
 impl SysAllocSource {
-    pub fn new(root: Arc<RootShared>, liveness: Arc<Liveness>) -> Self {
+    pub fn new(
+        root: Arc<RootShared>,
+        liveness: Arc<Liveness>,
+        shard_sizes: Arc<ShardSizes>,
+    ) -> Self {
         Self {
             root,
             liveness,
+            shard_sizes,
             nodes: Default::default(),
             groups: Default::default(),
             replicas: Default::default(),
+            leader_zones: Default::default(),
+            read_replica_zones: Default::default(),
+            capacity_history: Default::default(),
         }
     }
 }
@@ -87,6 +134,8 @@ impl AllocSource for SysAllocSource {
         crate::runtime::yield_now().await;
         self.reload_replica_status().await?;
         crate::runtime::yield_now().await;
+        self.reload_collection_zones().await?;
+        crate::runtime::yield_now().await;
         Ok(())
     }
 
@@ -138,16 +187,47 @@ impl AllocSource for SysAllocSource {
             .map(|e| e.1.to_owned())
             .collect()
     }
+
+    fn leader_zones(&self) -> HashMap<u64, String> {
+        self.leader_zones.lock().unwrap().clone()
+    }
+
+    fn read_replica_zones(&self) -> HashMap<u64, Vec<String>> {
+        self.read_replica_zones.lock().unwrap().clone()
+    }
+
+    fn time_to_full(&self, node_id: &u64) -> Option<Duration> {
+        let history = self.capacity_history.lock().unwrap();
+        history.get(node_id).and_then(CapacityHistory::time_to_full)
+    }
+
+    fn shard_sizes(&self) -> HashMap<u64, HashMap<u64, u64>> {
+        self.shard_sizes.groups()
+    }
 }
 
 impl SysAllocSource {
     async fn reload_nodes(&self) -> Result<()> {
         let schema = self.root.schema()?;
         let cur_nodes = schema.list_node().await?;
+        self.record_capacity(&cur_nodes, Instant::now());
         self.set_nodes(cur_nodes);
         Ok(())
     }
 
+    fn record_capacity(&self, nodes: &[NodeDesc], now: Instant) {
+        let mut history = self.capacity_history.lock().unwrap();
+        history.retain(|id, _| nodes.iter().any(|n| n.id == *id));
+        for n in nodes {
+            if let Some(cap) = n.capacity.as_ref().filter(|c| c.total_space > 0) {
+                history
+                    .entry(n.id)
+                    .or_default()
+                    .record(now, cap.available_space);
+            }
+        }
+    }
+
     fn set_nodes(&self, ns: Vec<NodeDesc>) {
         let mut nodes = self.nodes.lock().unwrap();
         let _ = std::mem::replace(&mut *nodes, ns);
@@ -192,6 +272,24 @@ impl SysAllocSource {
         Ok(())
     }
 
+    async fn reload_collection_zones(&self) -> Result<()> {
+        let schema = self.root.schema()?;
+        let collections = schema.list_collection().await?;
+        let leader_zones = collections
+            .iter()
+            .filter(|c| !c.leader_zone.is_empty())
+            .map(|c| (c.id, c.leader_zone.clone()))
+            .collect();
+        let read_replica_zones = collections
+            .into_iter()
+            .filter(|c| !c.read_replica_zones.is_empty())
+            .map(|c| (c.id, c.read_replica_zones))
+            .collect();
+        *self.leader_zones.lock().unwrap() = leader_zones;
+        *self.read_replica_zones.lock().unwrap() = read_replica_zones;
+        Ok(())
+    }
+
     #[allow(dead_code)]
     fn set_replica_states(&self, rs: Vec<ReplicaState>) {
         let mut replicas = self.replicas.lock().unwrap();
@@ -207,3 +305,96 @@ impl SysAllocSource {
         );
     }
 }
+
+impl CapacityHistory {
+    fn record(&mut self, now: Instant, available_space: u64) {
+        if let Some((last, _)) = self.samples.back() {
+            if now.saturating_duration_since(*last) < CAPACITY_SAMPLE_INTERVAL {
+                return;
+            }
+        }
+        self.samples.push_back((now, available_space));
+        while let Some((first, _)) = self.samples.front() {
+            if now.saturating_duration_since(*first) <= CAPACITY_HISTORY_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// The bytes consumed per second, it is the slope of the least squares fit of the samples.
+    fn fill_rate(&self) -> Option<f64> {
+        if self.samples.len() < MIN_CAPACITY_SAMPLES {
+            return None;
+        }
+
+        let (start, _) = self.samples.front()?;
+        let points = self
+            .samples
+            .iter()
+            .map(|(at, available)| {
+                let x = at.saturating_duration_since(*start).as_secs_f64();
+                (x, -(*available as f64))
+            })
+            .collect::<Vec<_>>();
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let (mut cov, mut var) = (0.0, 0.0);
+        for (x, y) in &points {
+            cov += (x - mean_x) * (y - mean_y);
+            var += (x - mean_x) * (x - mean_x);
+        }
+        if var == 0.0 {
+            return None;
+        }
+        Some(cov / var)
+    }
+
+    fn time_to_full(&self) -> Option<Duration> {
+        let rate = self.fill_rate().filter(|r| *r > 0.0)?;
+        let (_, available) = self.samples.back()?;
+        let secs = *available as f64 / rate;
+        // The nodes filling up extremely slowly are treated as not filling.
+        if secs >= u32::MAX as f64 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forecast_time_to_full() {
+        let start = Instant::now();
+        let mut history = CapacityHistory::default();
+        history.record(start, 1000);
+        history.record(start + Duration::from_secs(10), 900);
+        assert_eq!(
+            history.samples.len(),
+            1,
+            "the sample within interval is skipped"
+        );
+        history.record(start + CAPACITY_SAMPLE_INTERVAL, 900);
+        assert!(history.time_to_full().is_none());
+
+        // Consume 100 bytes per minute.
+        history.record(start + CAPACITY_SAMPLE_INTERVAL * 2, 800);
+        let time_to_full = history.time_to_full().unwrap();
+        let expect = CAPACITY_SAMPLE_INTERVAL * 8;
+        assert!(time_to_full > expect * 3 / 4 && time_to_full < expect * 5 / 4);
+
+        // The space is released.
+        for i in 3..6 {
+            history.record(start + CAPACITY_SAMPLE_INTERVAL * i, 1000);
+        }
+        assert!(history.time_to_full().is_none());
+
+        // The old samples are dropped.
+        history.record(start + CAPACITY_HISTORY_WINDOW * 2, 1000);
+        assert_eq!(history.samples.len(), 1);
+    }
+}
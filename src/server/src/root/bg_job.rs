@@ -13,9 +13,10 @@
 // limitations under the License.
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     sync::{atomic, Arc, Mutex},
     task::{Poll, Waker},
+    time::Duration,
 };
 
 use engula_api::server::v1::{GroupDesc, ReplicaDesc, ReplicaRole, RootDesc, ShardDesc};
@@ -33,6 +34,14 @@ use crate::{
     Result,
 };
 
+/// The max number of shards created concurrently by a create collection job.
+const CREATE_SHARD_BATCH_SIZE: usize = 32;
+
+const CREATE_SHARD_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The create collection job is rolled back once creating shards fails this many times.
+const CREATE_SHARD_MAX_RETRIES: u64 = 20;
+
 pub struct Jobs {
     core: JobCore,
 }
@@ -148,33 +157,77 @@ impl Jobs {
         job_id: u64,
         create_collection: &mut CreateCollectionJob,
     ) -> Result<()> {
-        loop {
-            let shard = create_collection.wait_create.pop();
-            if shard.is_none() {
-                break;
-            }
-            let shard = shard.unwrap();
-            let groups = self.core.alloc.place_group_for_shard(1).await?;
-            if groups.is_empty() {
-                return Err(crate::Error::ResourceExhausted("no engouth groups".into()));
+        while !create_collection.wait_create.is_empty() {
+            let batch_size = create_collection
+                .wait_create
+                .len()
+                .min(CREATE_SHARD_BATCH_SIZE);
+            let batch_start = create_collection.wait_create.len() - batch_size;
+            let batch = create_collection.wait_create[batch_start..].to_vec();
+
+            // Persist the placement before creating, so that a resumed job won't create the
+            // shards in other groups.
+            let unassigned = batch
+                .iter()
+                .filter(|s| !create_collection.shard_groups.contains_key(&s.id))
+                .collect::<Vec<_>>();
+            if !unassigned.is_empty() {
+                let groups = self.core.alloc.place_group_for_shard(usize::MAX).await?;
+                if groups.is_empty() {
+                    return Err(crate::Error::ResourceExhausted("no engouth groups".into()));
+                }
+                let placement =
+                    assign_shard_groups(&groups, &create_collection.shard_groups, &unassigned);
+                create_collection.shard_groups.extend(placement);
+                self.save_create_collection(job_id, create_collection)
+                    .await?;
             }
-            let group = groups.first().unwrap();
+
             info!(
-                "try create shard at group {}, shards: {}",
-                group.id,
-                group.shards.len()
+                "try create {} shards, remaining {}",
+                batch.len(),
+                create_collection.wait_create.len()
             );
-            if let Err(err) = self.try_create_shard(group.id, &shard).await {
-                error!(group=group.id, shard=shard.id, err=?err, "create collection shard error and try to rollback");
+            let results = futures::future::join_all(batch.iter().map(|shard| {
+                let group_id = create_collection.shard_groups[&shard.id];
+                async move { (group_id, self.try_create_shard(group_id, shard).await) }
+            }))
+            .await;
+
+            let mut last_err = None;
+            create_collection.wait_create.truncate(batch_start);
+            for (shard, (group_id, result)) in batch.into_iter().zip(results) {
+                match result {
+                    Ok(()) => create_collection.wait_cleanup.push(shard),
+                    Err(err) => {
+                        warn!(group=group_id, shard=shard.id, err=?err, "create collection shard error");
+                        last_err = Some(err);
+                        create_collection.wait_create.push(shard);
+                    }
+                }
+            }
+            if let Some(err) = last_err {
                 create_collection.remark = format!("{err:?}");
-                create_collection.wait_cleanup.push(shard);
+                if create_collection.create_retry < CREATE_SHARD_MAX_RETRIES {
+                    metrics::RECONCILE_RETRY_TASK_TOTAL
+                        .create_collection_shards
+                        .inc();
+                    create_collection.create_retry += 1;
+                    self.save_create_collection(job_id, create_collection)
+                        .await?;
+                    crate::runtime::time::sleep(CREATE_SHARD_RETRY_INTERVAL).await;
+                    continue;
+                }
+                error!(err=?err, "create collection shard error and try to rollback");
+                // The failed shards might be created if only the responses are lost.
+                let failed = std::mem::take(&mut create_collection.wait_create);
+                create_collection.wait_cleanup.extend(failed);
                 create_collection.status =
                     CreateCollectionJobStatus::CreateCollectionRollbacking as i32;
                 self.save_create_collection(job_id, create_collection)
                     .await?;
                 return Ok(());
             }
-            create_collection.wait_cleanup.push(shard);
             self.save_create_collection(job_id, create_collection)
                 .await?;
         }
@@ -569,6 +622,7 @@ impl Jobs {
 
 impl Jobs {
     async fn try_create_shard(&self, group_id: u64, desc: &ShardDesc) -> Result<()> {
+        self.core.root_shared.fence().await?;
         let mut group_client = GroupClient::lazy(
             group_id,
             self.core.root_shared.provider.router.clone(),
@@ -584,6 +638,7 @@ impl Jobs {
         replica_id: &u64,
         group: GroupDesc,
     ) -> Result<()> {
+        self.core.root_shared.fence().await?;
         let client = self
             .core
             .root_shared
@@ -595,6 +650,7 @@ impl Jobs {
     }
 
     async fn try_remove_replica(&self, group: u64, replica: u64) -> Result<()> {
+        self.core.root_shared.fence().await?;
         let schema = self.core.root_shared.schema()?;
         let rs = schema.get_replica_state(group, replica).await?.ok_or(
             crate::Error::AbortScheduleTask("source replica already has be destroyed"),
@@ -826,6 +882,36 @@ impl JobCore {
     }
 }
 
+/// Assign the shards to the groups with the fewest shards, counting the shards already assigned
+/// but not yet created in the groups.
+fn assign_shard_groups(
+    groups: &[GroupDesc],
+    assigned: &HashMap<u64 /* shard */, u64 /* group */>,
+    shards: &[&ShardDesc],
+) -> Vec<(u64, u64)> {
+    let mut shard_counts = groups
+        .iter()
+        .map(|g| (g.id, g.shards.len()))
+        .collect::<HashMap<_, _>>();
+    for (shard_id, group_id) in assigned {
+        let group = groups.iter().find(|g| g.id == *group_id);
+        if group.map(|g| g.shards.iter().all(|s| s.id != *shard_id)) == Some(true) {
+            *shard_counts.get_mut(group_id).unwrap() += 1;
+        }
+    }
+
+    let mut placement = Vec::with_capacity(shards.len());
+    for shard in shards {
+        let (&group_id, count) = shard_counts
+            .iter_mut()
+            .min_by_key(|(id, count)| (**count, **id))
+            .expect("groups is not empty");
+        *count += 1;
+        placement.push((shard.id, group_id));
+    }
+    placement
+}
+
 fn res_key(job: &BackgroundJob) -> Option<Vec<u8>> {
     match job.job.as_ref().unwrap() {
         background_job::Job::CreateCollection(job) => {
@@ -841,3 +927,39 @@ fn res_key(job: &BackgroundJob) -> Option<Vec<u8>> {
         background_job::Job::CreateOneGroup(_) | background_job::Job::PurgeDatabase(_) => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assign_shards_to_fewest_groups() {
+        let group = |id: u64, shards: &[u64]| GroupDesc {
+            id,
+            shards: shards
+                .iter()
+                .map(|id| ShardDesc {
+                    id: *id,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let groups = vec![group(1, &[1, 2]), group(2, &[3]), group(3, &[])];
+        let shards = (10..14)
+            .map(|id| ShardDesc {
+                id,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let shards = shards.iter().collect::<Vec<_>>();
+
+        let placement = assign_shard_groups(&groups, &HashMap::default(), &shards);
+        assert_eq!(placement, vec![(10, 3), (11, 2), (12, 3), (13, 1)]);
+
+        // The assigned but not yet created shards are counted.
+        let assigned = HashMap::from([(20, 3), (21, 3), (3, 2)]);
+        let placement = assign_shard_groups(&groups, &assigned, &shards[..2]);
+        assert_eq!(placement, vec![(10, 2), (11, 1)]);
+    }
+}
@@ -12,7 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::HashSet, ops::Add, sync::Arc, vec};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Add,
+    sync::Arc,
+    vec,
+};
 
 use engula_api::server::v1::{
     watch_response::{update_event, UpdateEvent},
@@ -24,10 +29,19 @@ use tracing::{info, trace, warn};
 use super::{HeartbeatTask, Root, Schema};
 use crate::{
     bootstrap::ROOT_GROUP_ID,
+    feature::activatable_features,
     root::{metrics, schema::ReplicaNodes},
     Result,
 };
 
+/// The changes of storage penalty below it are not persisted, to avoid updating node descriptors
+/// on every heartbeat.
+const STORAGE_PENALTY_TOLERANCE: f64 = 0.05;
+
+/// The changes of available space below this fraction of the total space are not persisted, for
+/// the same reason.
+const AVAILABLE_SPACE_TOLERANCE: f64 = 0.005;
+
 impl Root {
     pub async fn send_heartbeat(&self, schema: Arc<Schema>, tasks: &[HeartbeatTask]) -> Result<()> {
         let cur_node_id = self.current_node_id();
@@ -39,6 +53,8 @@ impl Root {
 
         info!("sending heartbeat to {:?}", &nodes);
 
+        let features = self.activate_features(&schema, &all_nodes).await?;
+
         let mut piggybacks = Vec::new();
 
         // TODO: no need piggyback root info everytime.
@@ -56,6 +72,7 @@ impl Root {
             piggybacks.push(PiggybackRequest {
                 info: Some(piggyback_request::Info::SyncRoot(SyncRootRequest {
                     root: Some(root),
+                    features: Some(features),
                 })),
             });
             piggybacks.push(PiggybackRequest {
@@ -111,7 +128,9 @@ impl Root {
             let n = nodes.get(i).unwrap();
             match resp {
                 Ok(res) => {
-                    self.liveness.renew(n.id);
+                    if let Some(staleness) = self.liveness.renew(n.id) {
+                        metrics::HEARTBEAT_STALENESS_SECONDS.observe(staleness.as_secs_f64());
+                    }
                     for resp in &res.piggybacks {
                         match resp.info.as_ref().unwrap() {
                             piggyback_response::Info::SyncRoot(_)
@@ -142,6 +161,7 @@ impl Root {
                 crate::runtime::yield_now().await;
             }
         }
+        record_placement_metrics(&all_nodes, &groups);
         self.heartbeat_queue
             .try_schedule(
                 heartbeat_tasks,
@@ -152,26 +172,137 @@ impl Root {
         Ok(())
     }
 
+    /// Probe the replicas of the groups whose leader states are not received within the
+    /// threshold, and reconcile the returned states, so that the drifts of leadership and
+    /// descriptors are repaired without waiting for the next report.
+    pub(super) async fn refresh_stale_groups(&self, schema: &Schema) -> Result<()> {
+        let groups = schema.list_group().await?;
+        let group_ids = groups.iter().map(|g| g.id).collect::<Vec<_>>();
+        let stale_groups = self
+            .group_freshness
+            .take_stale_groups(&group_ids, self.cfg.stale_group_state_threshold());
+        if stale_groups.is_empty() {
+            return Ok(());
+        }
+
+        // Probe each node once, with all stale groups it serves.
+        let mut probes: HashMap<u64, Vec<u64>> = HashMap::new();
+        for group in groups.iter().filter(|g| stale_groups.contains(&g.id)) {
+            metrics::STALE_GROUP_PROBE_TOTAL.inc();
+            for replica in &group.replicas {
+                probes.entry(replica.node_id).or_default().push(group.id);
+            }
+        }
+        info!(groups = ?stale_groups, "probe the replicas of stale groups");
+
+        let mut handles = Vec::with_capacity(probes.len());
+        for (node_id, group_ids) in probes {
+            let Some(node) = schema.get_node(node_id).await? else {
+                continue;
+            };
+            let client = self.get_node_client(node.addr.to_owned()).await?;
+            let handle = self.shared.provider.executor.spawn(
+                None,
+                crate::runtime::TaskPriority::Low,
+                async move {
+                    client
+                        .root_heartbeat(HeartbeatRequest {
+                            piggybacks: vec![PiggybackRequest {
+                                info: Some(piggyback_request::Info::CollectGroupDetail(
+                                    CollectGroupDetailRequest { groups: group_ids },
+                                )),
+                            }],
+                            timestamp: 0, // TODO: use hlc
+                        })
+                        .await
+                },
+            );
+            handles.push((node_id, handle));
+        }
+
+        for (node_id, handle) in handles {
+            match handle.await {
+                Ok(res) => {
+                    for resp in &res.piggybacks {
+                        if let Some(piggyback_response::Info::CollectGroupDetail(ref resp)) =
+                            resp.info
+                        {
+                            self.handle_group_detail(schema, resp, &groups).await?;
+                        }
+                    }
+                }
+                Err(err) => {
+                    metrics::STALE_GROUP_PROBE_FAIL_TOTAL.inc();
+                    warn!(node = node_id, err = ?err, "probe stale groups error");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Activate the features supported by all alive nodes, and returns the activated features.
+    async fn activate_features(
+        &self,
+        schema: &Schema,
+        nodes: &[NodeDesc],
+    ) -> Result<ClusterFeatures> {
+        let cluster_features = schema.cluster_features().await?;
+        let nodes = nodes
+            .iter()
+            .filter(|n| n.status != NodeStatus::Decommissioned as i32)
+            .map(|n| n.features.as_slice());
+        let features = activatable_features(&cluster_features.active, nodes);
+        if features.is_empty() {
+            return Ok(cluster_features);
+        }
+        info!("activate cluster features {features:?}");
+        schema.activate_features(&features).await
+    }
+
     async fn handle_collect_stats(
         &self,
         schema: &Schema,
         resp: &CollectStatsResponse,
         node: &NodeDesc,
     ) -> Result<()> {
+        for gs in &resp.group_stats {
+            // Only the leaders return the group stats.
+            self.shard_sizes
+                .update(gs.group_id, gs.shard_sizes.to_owned());
+        }
         if let Some(ns) = &resp.node_stats {
             let mut node = node.to_owned();
             let _timer = super::metrics::HEARTBEAT_HANDLE_NODE_STATS_DURATION_SECONDS.start_timer();
             let new_group_count = ns.group_count as u64;
             let new_leader_count = ns.leader_count as u64;
+            let new_storage_penalty = ns
+                .storage_health
+                .as_ref()
+                .map(|h| (1.0 - h.score as f64).clamp(0.0, 1.0))
+                .unwrap_or_default();
             let mut cap = node.capacity.take().unwrap();
-            if new_group_count != cap.replica_count || new_leader_count != cap.leader_count {
+            let space_delta = ns.available_space.abs_diff(cap.available_space) as f64;
+            if new_group_count != cap.replica_count
+                || new_leader_count != cap.leader_count
+                || (new_storage_penalty - cap.storage_penalty).abs() >= STORAGE_PENALTY_TOLERANCE
+                || ns.total_space != cap.total_space
+                || space_delta >= (ns.total_space as f64 * AVAILABLE_SPACE_TOLERANCE).max(1.0)
+                || ns.features != node.features
+            {
                 super::metrics::HEARTBEAT_UPDATE_NODE_STATS_TOTAL.inc();
                 cap.replica_count = new_group_count;
                 cap.leader_count = new_leader_count;
+                cap.storage_penalty = new_storage_penalty;
+                cap.total_space = ns.total_space;
+                cap.available_space = ns.available_space;
+                node.features = ns.features.clone();
                 info!(
                     node = node.id,
                     replica_count = cap.replica_count,
                     leader_count = cap.leader_count,
+                    storage_penalty = cap.storage_penalty,
+                    available_space = cap.available_space,
+                    features = ?node.features,
                     "update node stats by heartbeat response",
                 );
                 node.capacity = Some(cap);
@@ -190,6 +321,8 @@ impl Root {
         let _timer = super::metrics::HEARTBEAT_HANDLE_GROUP_DETAIL_DURATION_SECONDS.start_timer();
         let mut update_events = Vec::new();
         for desc in &resp.group_descs {
+            // Only the leaders return the descriptors.
+            self.group_freshness.touch(desc.id);
             if let Some(ex) = groups.iter().find(|g| g.id == desc.id) {
                 if desc.epoch <= ex.epoch {
                     continue;
@@ -244,12 +377,12 @@ impl Root {
             changed_group_states.insert(state.group_id);
         }
 
-        let mut states = schema.list_group_state().await?; // TODO: fix poor performance.
-        states.retain(|s| changed_group_states.contains(&s.group_id));
-        for state in states {
-            update_events.push(UpdateEvent {
-                event: Some(update_event::Event::GroupState(state)),
-            })
+        for group_id in changed_group_states {
+            if let Some(state) = schema.get_group_state(group_id).await? {
+                update_events.push(UpdateEvent {
+                    event: Some(update_event::Event::GroupState(state)),
+                })
+            }
         }
 
         if !update_events.is_empty() {
@@ -265,3 +398,19 @@ impl Root {
         Ok(())
     }
 }
+
+/// Export the number of groups and the number of replicas placed on each node.
+fn record_placement_metrics(nodes: &[NodeDesc], groups: &[GroupDesc]) {
+    let mut replicas: HashMap<u64, i64> = nodes.iter().map(|n| (n.id, 0)).collect();
+    for replica in groups.iter().flat_map(|g| &g.replicas) {
+        *replicas.entry(replica.node_id).or_default() += 1;
+    }
+    metrics::ROOT_GROUP_TOTAL.set(groups.len() as i64);
+    // Remove the nodes which have left the cluster.
+    metrics::ROOT_NODE_REPLICA_TOTAL.reset();
+    for (node_id, count) in replicas {
+        metrics::ROOT_NODE_REPLICA_TOTAL
+            .with_label_values(&[&node_id.to_string()])
+            .set(count);
+    }
+}
@@ -55,21 +55,26 @@ impl Liveness {
         })
     }
 
-    pub fn renew(&self, node_id: u64) {
+    /// Renew the liveness of the node, returns the elapsed time since the previous renewal, or
+    /// `None` if the node is first seen.
+    pub fn renew(&self, node_id: u64) -> Option<Duration> {
         let mut nodes = self.nodes.lock().unwrap();
         let entry = nodes.entry(node_id);
         match entry {
             hash_map::Entry::Occupied(mut ent) => {
                 let renew = self.new_expiration();
                 let ent = ent.get_mut();
+                let staleness = renew.saturating_sub(ent.expiration);
                 if ent.expiration < renew {
                     ent.expiration = renew
                 }
+                Some(Duration::from_millis(staleness as u64))
             }
             hash_map::Entry::Vacant(ent) => {
                 ent.insert(NodeLiveness {
                     expiration: self.new_expiration(),
                 });
+                None
             }
         }
     }
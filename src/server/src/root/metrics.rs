@@ -23,6 +23,51 @@ lazy_static! {
         "the node as root leader count"
     )
     .unwrap();
+    pub static ref ROOT_GROUP_TOTAL: IntGauge = register_int_gauge!(
+        "root_group_total",
+        "the number of groups in the cluster, exported by the root leader"
+    )
+    .unwrap();
+    pub static ref ROOT_NODE_REPLICA_TOTAL: IntGaugeVec = register_int_gauge_vec!(
+        "root_node_replica_total",
+        "the number of replicas placed on each node, exported by the root leader",
+        &["node"]
+    )
+    .unwrap();
+    pub static ref ROOT_FENCED_COMMAND_TOTAL: IntCounter = register_int_counter!(
+        "root_fenced_command_total",
+        "The total commands rejected because the root leadership is deposed"
+    )
+    .unwrap();
+}
+
+// metadata operations.
+
+make_static_metric! {
+    pub struct MetadataOpDuration: Histogram {
+        "type" => {
+            create_database,
+            delete_database,
+            list_database,
+            get_database,
+            create_collection,
+            delete_collection,
+            list_collection,
+            get_collection,
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref METADATA_OP_DURATION_SECONDS_VEC: HistogramVec = register_histogram_vec!(
+        "root_metadata_op_duration_seconds",
+        "the duration of metadata operations of root service",
+        &["type"],
+        exponential_buckets(0.00005, 1.8, 26).unwrap(),
+    )
+    .unwrap();
+    pub static ref METADATA_OP_DURATION_SECONDS: MetadataOpDuration =
+        MetadataOpDuration::from(&METADATA_OP_DURATION_SECONDS_VEC);
 }
 
 // bootstrap root.
@@ -52,6 +97,8 @@ make_static_metric! {
             shed_group_leaders,
             shed_root_leader,
             create_group,
+            add_read_replica,
+            create_collection_shards,
         }
     }
     pub struct ReconcileScheduleHandleTaskDuration: Histogram {
@@ -63,6 +110,7 @@ make_static_metric! {
             create_collection_shards,
             shed_group_leaders,
             shed_root_leader,
+            add_read_replica,
         }
     }
     pub struct ReconcileScheduleCreateGroupStepDuration: Histogram {
@@ -121,6 +169,11 @@ lazy_static! {
     .unwrap();
     pub static ref RECONCILE_ALREADY_BALANCED_INFO: ReconcileScheduleBalanceInfo =
         ReconcileScheduleBalanceInfo::from(&RECONCILE_ALREADY_BALANCED_INFO_VEC);
+    pub static ref RECONCILE_PENDING_ACTIONS: IntGauge = register_int_gauge!(
+        "root_reconcile_pending_actions",
+        "the number of replica movements reported by groups but not yet finished"
+    )
+    .unwrap();
     pub static ref RECONCILE_SCHEDULER_TASK_QUEUE_SIZE: IntGauge = register_int_gauge!(
         "root_reconcile_scheduler_task_queue_size",
         "the size of scheduler task queue size during each reconcile step"
@@ -232,6 +285,12 @@ lazy_static! {
         exponential_buckets(0.00005, 1.8, 26).unwrap(),
     )
     .unwrap();
+    pub static ref HEARTBEAT_STALENESS_SECONDS: Histogram = register_histogram!(
+        "root_heartbeat_staleness_seconds",
+        "the elapsed time since the previous successful heartbeat of a node",
+        exponential_buckets(0.001, 2.0, 20).unwrap(),
+    )
+    .unwrap();
     pub static ref HEARTBEAT_NODES_BATCH_SIZE: IntGauge = register_int_gauge!(
         "root_heartbeat_nodes_batch_size",
         "the number of nodes be sent in one heartbeat step"
@@ -249,6 +308,16 @@ lazy_static! {
         exponential_buckets(0.00005, 1.8, 26).unwrap(),
     )
     .unwrap();
+    pub static ref STALE_GROUP_PROBE_TOTAL: IntCounter = register_int_counter!(
+        "root_stale_group_probe_total",
+        "the count of groups probed because their leader states are stale",
+    )
+    .unwrap();
+    pub static ref STALE_GROUP_PROBE_FAIL_TOTAL: IntCounter = register_int_counter!(
+        "root_stale_group_probe_fail_total",
+        "the count of failed probe requests sent to the replicas of stale groups",
+    )
+    .unwrap();
     pub static ref HEARTBEAT_UPDATE_NODE_STATS_TOTAL: IntCounter = register_int_counter!(
         "root_heartbeat_update_node_stats_total",
         "the count of real update node stats after receive heartbeat response",
@@ -18,8 +18,11 @@ mod collector;
 mod heartbeat;
 mod liveness;
 mod metrics;
+mod refresh;
+mod report;
 mod schedule;
 mod schema;
+mod shard_size;
 mod store;
 mod watch;
 
@@ -35,22 +38,30 @@ use engula_api::{
 use engula_client::NodeClient;
 use tokio::time::Instant;
 use tokio_util::time::delay_queue;
-use tracing::{error, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
 pub(crate) use self::schema::*;
 pub use self::{
     allocator::RootConfig,
     collector::RootCollector,
+    report::ReportSequence,
     watch::{WatchHub, Watcher, WatcherInitializer},
 };
 use self::{
-    allocator::SysAllocSource, bg_job::Jobs, diagnosis::Metadata, schedule::ReconcileScheduler,
-    schema::ReplicaNodes, store::RootStore,
+    allocator::SysAllocSource,
+    bg_job::Jobs,
+    diagnosis::{Metadata, Topology},
+    refresh::GroupFreshness,
+    report::ReportSessions,
+    schedule::ReconcileScheduler,
+    schema::ReplicaNodes,
+    shard_size::ShardSizes,
+    store::RootStore,
 };
 use crate::{
     bootstrap::{ROOT_GROUP_ID, SHARD_MAX, SHARD_MIN},
     node::{Node, Replica, ReplicaRouteTable},
-    runtime::{self, TaskPriority},
+    runtime::{self, registry, TaskPriority},
     serverpb::v1::{background_job::Job, reconcile_task, *},
     Config, Error, Provider, Result,
 };
@@ -65,12 +76,16 @@ pub struct Root {
     heartbeat_queue: Arc<HeartbeatQueue>,
     ongoing_stats: Arc<OngoingStats>,
     jobs: Arc<Jobs>,
+    report_sessions: Arc<ReportSessions>,
+    group_freshness: Arc<GroupFreshness>,
+    shard_sizes: Arc<ShardSizes>,
 }
 
 pub struct RootShared {
     provider: Arc<Provider>,
     node_ident: NodeIdent,
     local_addr: String,
+    local_zone: String,
     cfg_cpu_nums: u32,
     core: Mutex<Option<RootCore>>,
     watcher_hub: Arc<WatchHub>,
@@ -83,20 +98,63 @@ impl RootShared {
             .map(|c| c.schema.clone())
             .ok_or_else(|| Error::NotRootLeader(RootDesc::default(), 0, None))
     }
+
+    /// Returns the lease of the root leadership held by this node.
+    pub fn lease(&self) -> Option<RootLease> {
+        let core = self.core.lock().unwrap();
+        core.as_ref().map(|c| RootLease {
+            node_id: self.node_ident.node_id,
+            term: c.term,
+        })
+    }
+
+    /// Ensure that this node is still the root leader of the term it stepped in, so that a deposed
+    /// root leader can't issue commands conflicting with the new one. It should be called before
+    /// sending any command which changes the cluster to nodes.
+    pub async fn fence(&self) -> Result<()> {
+        let (term, replica) = {
+            let core = self.core.lock().unwrap();
+            let core = core
+                .as_ref()
+                .ok_or_else(|| Error::NotRootLeader(RootDesc::default(), 0, None))?;
+            (core.term, core.replica.clone())
+        };
+        // Confirm the leadership with a quorum, so that a partitioned leader is fenced too.
+        replica.check_lease().await?;
+        match replica.on_leader("root", true).await? {
+            Some(current_term) if current_term == term => Ok(()),
+            _ => {
+                metrics::ROOT_FENCED_COMMAND_TOTAL.inc();
+                Err(Error::NotRootLeader(RootDesc::default(), term, None))
+            }
+        }
+    }
+}
+
+/// The leadership of root service, the term is the raft term of root group when this node
+/// stepped leader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct RootLease {
+    pub node_id: u64,
+    pub term: u64,
 }
 
 struct RootCore {
     schema: Arc<Schema>,
+    term: u64,
+    replica: Arc<Replica>,
 }
 
 impl Root {
     pub(crate) fn new(provider: Arc<Provider>, node_ident: &NodeIdent, cfg: Config) -> Self {
         let local_addr = cfg.addr.clone();
+        let local_zone = cfg.node.zone.clone();
         let cfg_cpu_nums = cfg.cpu_nums;
         let ongoing_stats = Arc::new(OngoingStats::default());
         let shared = Arc::new(RootShared {
             provider,
             local_addr,
+            local_zone,
             cfg_cpu_nums,
             core: Mutex::new(None),
             node_ident: node_ident.to_owned(),
@@ -105,7 +163,12 @@ impl Root {
         let liveness = Arc::new(liveness::Liveness::new(Duration::from_secs(
             cfg.root.liveness_threshold_sec,
         )));
-        let info = Arc::new(SysAllocSource::new(shared.clone(), liveness.to_owned()));
+        let shard_sizes = Arc::new(ShardSizes::default());
+        let info = Arc::new(SysAllocSource::new(
+            shared.clone(),
+            liveness.to_owned(),
+            shard_sizes.to_owned(),
+        ));
         let alloc = Arc::new(allocator::Allocator::new(
             info,
             ongoing_stats.clone(),
@@ -135,6 +198,9 @@ impl Root {
             heartbeat_queue,
             ongoing_stats,
             jobs,
+            report_sessions: Arc::default(),
+            group_freshness: Arc::default(),
+            shard_sizes,
         }
     }
 
@@ -148,27 +214,36 @@ impl Root {
 
     pub async fn bootstrap(&self, node: &Node) -> Result<Vec<NodeDesc>> {
         let root = self.clone();
-        self.shared
-            .provider
-            .executor
-            .spawn(None, TaskPriority::Middle, async move {
+        self.shared.provider.executor.spawn_named(
+            registry::group::ROOT,
+            "root-heartbeat",
+            None,
+            TaskPriority::Middle,
+            async move {
                 root.run_heartbeat().await;
-            });
+            },
+        );
         let root = self.clone();
-        self.shared
-            .provider
-            .executor
-            .spawn(None, TaskPriority::Low, async move {
+        self.shared.provider.executor.spawn_named(
+            registry::group::ROOT,
+            "root-background-jobs",
+            None,
+            TaskPriority::Low,
+            async move {
                 root.run_background_jobs().await;
-            });
+            },
+        );
         let replica_table = node.replica_table().clone();
         let root = self.clone();
-        self.shared
-            .provider
-            .executor
-            .spawn(None, TaskPriority::Middle, async move {
+        self.shared.provider.executor.spawn_named(
+            registry::group::ROOT,
+            "root-schedule",
+            None,
+            TaskPriority::Middle,
+            async move {
                 root.run_schedule(replica_table).await;
-            });
+            },
+        );
 
         if let Some(replica) = node.replica_table().current_root_replica(None) {
             let engine = replica.group_engine();
@@ -182,6 +257,10 @@ impl Root {
         self.shared.schema()
     }
 
+    pub fn lease(&self) -> Option<RootLease> {
+        self.shared.lease()
+    }
+
     pub fn watcher_hub(&self) -> Arc<WatchHub> {
         self.shared.watcher_hub.clone()
     }
@@ -196,12 +275,13 @@ impl Root {
             let root_replica = fetch_root_replica(&replica_table).await;
 
             // Wait the current root replica becomes a leader.
-            if let Ok(Some(_)) = root_replica.on_leader("root", false).await {
+            if let Ok(Some(term)) = root_replica.on_leader("root", false).await {
                 match self
                     .step_leader(
                         &self.shared.local_addr,
                         self.shared.cfg_cpu_nums,
                         root_replica,
+                        term,
                         &mut bootstrapped,
                     )
                     .await
@@ -231,6 +311,9 @@ impl Root {
                         warn!(err = ?err, "send heartbeat meet error");
                     }
                 }
+                if let Err(err) = self.refresh_stale_groups(&schema).await {
+                    warn!(err = ?err, "refresh stale groups meet error");
+                }
             }
             runtime::time::sleep(Duration::from_secs(1)).await;
         }
@@ -256,6 +339,7 @@ impl Root {
         local_addr: &str,
         cfg_cpu_nums: u32,
         root_replica: Arc<Replica>,
+        term: u64,
         bootstrapped: &mut bool,
     ) -> Result<()> {
         let store = Arc::new(RootStore::new(root_replica.to_owned()));
@@ -267,6 +351,7 @@ impl Root {
             if let Err(err) = schema
                 .try_bootstrap_root(
                     local_addr,
+                    &self.shared.local_zone,
                     cfg_cpu_nums,
                     self.shared.node_ident.cluster_id.clone(),
                 )
@@ -283,17 +368,21 @@ impl Root {
             let mut core = self.shared.core.lock().unwrap();
             *core = Some(RootCore {
                 schema: Arc::new(schema.to_owned()),
+                term,
+                replica: root_replica.clone(),
             });
         }
         self::metrics::LEADER_STATE_INFO.set(1);
 
         self.ongoing_stats.reset();
+        self.group_freshness.reset();
+        self.shard_sizes.reset();
         self.heartbeat_queue.enable(true).await;
         self.jobs.on_step_leader().await?;
 
         let node_id = self.shared.node_ident.node_id;
         info!(
-            "node {node_id} step root service leader, heartbeat_interval: {:?}, liveness_threshold: {:?}",
+            "node {node_id} step root service leader at term {term}, heartbeat_interval: {:?}, liveness_threshold: {:?}",
             self.cfg.heartbeat_interval(),
             Duration::from_secs(self.cfg.liveness_threshold_sec),
         );
@@ -310,7 +399,11 @@ impl Root {
             )
             .await;
 
-        while let Ok(Some(_)) = root_replica.to_owned().on_leader("root", true).await {
+        while let Ok(Some(current_term)) = root_replica.to_owned().on_leader("root", true).await {
+            if current_term != term {
+                // Step leader again with the new term, so that the lease is renewed.
+                break;
+            }
             let next_interval = self.scheduler.step_one().await;
             crate::runtime::time::sleep(next_interval).await;
             self.scheduler.wait_one_heartbeat_tick().await;
@@ -329,6 +422,8 @@ impl Root {
         }
 
         self::metrics::LEADER_STATE_INFO.set(0);
+        self::metrics::ROOT_GROUP_TOTAL.set(0);
+        self::metrics::ROOT_NODE_REPLICA_TOTAL.reset();
 
         Ok(())
     }
@@ -383,6 +478,7 @@ impl Root {
                     task: Some(reconcile_task::Task::ShedRoot(ShedRootLeaderTask {
                         node_id,
                     })),
+                    ..Default::default()
                 })
                 .await;
             return Err(crate::Error::InvalidArgument(
@@ -408,6 +504,7 @@ impl Root {
         self.scheduler
             .setup_task(ReconcileTask {
                 task: Some(reconcile_task::Task::ShedLeader(ShedLeaderTask { node_id })),
+                ..Default::default()
             })
             .await;
 
@@ -452,6 +549,8 @@ impl Root {
                         "status": state,
                         "wait_create": wait_create,
                         "wait_cleanup": wait_cleanup,
+                        "assigned": c.shard_groups.len(),
+                        "retry_count": c.create_retry,
                     })
                 }
                 Job::CreateOneGroup(c) => {
@@ -495,6 +594,29 @@ impl Root {
         Ok(json!({"ongoing": ongoing, "history": history}).to_string())
     }
 
+    pub async fn schedule_history(&self) -> Result<String> {
+        use serde_json::json;
+
+        let schema = self.schema()?;
+        let decisions = schema
+            .list_schedule_decision()
+            .await?
+            .iter()
+            .map(|d| {
+                json!({
+                    "id": d.id,
+                    "timestamp": d.timestamp,
+                    "action": d.action,
+                    "reason": d.reason,
+                    "scores_before": d.scores_before,
+                    "scores_after": d.scores_after,
+                    "outcome": d.outcome,
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(json!({ "decisions": decisions }).to_string())
+    }
+
     pub async fn info(&self) -> Result<Metadata> {
         let schema = self.schema()?;
         let nodes = schema.list_node().await?;
@@ -620,6 +742,96 @@ impl Root {
         })
     }
 
+    /// Returns the placement of nodes, groups and shards, and the ongoing migrations.
+    pub async fn topology(&self) -> Result<Topology> {
+        let schema = self.schema()?;
+        let nodes = schema.list_node().await?;
+        let groups = schema.list_group().await?;
+        let states = schema.list_replica_state().await?;
+        let tasks = self.scheduler.pending_tasks().await;
+
+        use diagnosis::*;
+
+        let leader_of = |g: &GroupDesc| {
+            states
+                .iter()
+                .filter(|s| s.group_id == g.id && s.role == RaftRole::Leader as i32)
+                .max_by_key(|s| s.term)
+                .map(|s| s.replica_id)
+        };
+
+        let mut shards = groups
+            .iter()
+            .flat_map(|g| {
+                g.shards.iter().map(|s| TopologyShard {
+                    id: s.id,
+                    collection: s.collection_id,
+                    group: g.id,
+                })
+            })
+            .collect::<Vec<_>>();
+        shards.sort_unstable_by_key(|s| s.id);
+
+        let mut migrations = vec![];
+        for task in tasks.iter().filter_map(|t| t.task.as_ref()) {
+            match task {
+                reconcile_task::Task::MigrateShard(t) => migrations.push(TopologyMigration {
+                    kind: "shard".to_owned(),
+                    target: t.shard,
+                    from: t.src_group,
+                    to: t.dest_group,
+                }),
+                reconcile_task::Task::ReallocateReplica(t) => migrations.push(TopologyMigration {
+                    kind: "replica".to_owned(),
+                    target: t.src_replica,
+                    from: t.src_node,
+                    to: t.dest_node.as_ref().map(|n| n.id).unwrap_or_default(),
+                }),
+                _ => {}
+            }
+        }
+
+        Ok(Topology {
+            nodes: nodes
+                .iter()
+                .map(|n| {
+                    let cap = n.capacity.clone().unwrap_or_default();
+                    TopologyNode {
+                        id: n.id,
+                        addr: n.addr.to_owned(),
+                        zone: n.zone.to_owned(),
+                        status: NodeStatus::from_i32(n.status)
+                            .map(|s| s.as_str_name().to_owned())
+                            .unwrap_or_default(),
+                        replica_count: cap.replica_count,
+                        leader_count: cap.leader_count,
+                    }
+                })
+                .collect(),
+            groups: groups
+                .iter()
+                .map(|g| TopologyGroup {
+                    id: g.id,
+                    epoch: g.epoch,
+                    leader: leader_of(g),
+                    replicas: g
+                        .replicas
+                        .iter()
+                        .map(|r| TopologyReplica {
+                            id: r.id,
+                            node: r.node_id,
+                            role: ReplicaRole::from_i32(r.role)
+                                .map(|r| r.as_str_name().to_owned())
+                                .unwrap_or_default(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            shards,
+            migrations,
+        })
+    }
+
     async fn get_node_client(&self, addr: String) -> Result<NodeClient> {
         let client = self.shared.provider.conn_manager.get_node_client(addr)?;
         Ok(client)
@@ -628,6 +840,9 @@ impl Root {
 
 impl Root {
     pub async fn create_database(&self, name: String) -> Result<DatabaseDesc> {
+        let _timer = metrics::METADATA_OP_DURATION_SECONDS
+            .create_database
+            .start_timer();
         let desc = self
             .schema()?
             .create_database(DatabaseDesc {
@@ -645,6 +860,9 @@ impl Root {
     }
 
     pub async fn delete_database(&self, name: &str) -> Result<()> {
+        let _timer = metrics::METADATA_OP_DURATION_SECONDS
+            .delete_database
+            .start_timer();
         let db = self.get_database(name).await?;
         if db.is_none() {
             return Err(Error::DatabaseNotFound(name.to_owned()));
@@ -684,7 +902,15 @@ impl Root {
         name: String,
         database: String,
         partition: Option<co_req::Partition>,
+        max_key_size: u64,
+        max_value_size: u64,
+        leader_zone: String,
+        webhook_url: String,
+        read_replica_zones: Vec<String>,
     ) -> Result<CollectionDesc> {
+        let _timer = metrics::METADATA_OP_DURATION_SECONDS
+            .create_collection
+            .start_timer();
         let schema = self.schema()?;
         let db = schema
             .get_database(&database)
@@ -703,6 +929,11 @@ impl Root {
                         co_desc::Partition::Range(co_desc::RangePartition {})
                     }
                 }),
+                max_key_size,
+                max_value_size,
+                leader_zone,
+                webhook_url,
+                read_replica_zones,
                 ..Default::default()
             })
             .await?;
@@ -786,6 +1017,9 @@ impl Root {
     }
 
     pub async fn delete_collection(&self, name: &str, database: &DatabaseDesc) -> Result<()> {
+        let _timer = metrics::METADATA_OP_DURATION_SECONDS
+            .delete_collection
+            .start_timer();
         let schema = self.schema()?;
         let db = self
             .get_database(&database.name)
@@ -832,14 +1066,23 @@ impl Root {
     }
 
     pub async fn list_database(&self) -> Result<Vec<DatabaseDesc>> {
+        let _timer = metrics::METADATA_OP_DURATION_SECONDS
+            .list_database
+            .start_timer();
         self.schema()?.list_database().await
     }
 
     pub async fn get_database(&self, name: &str) -> Result<Option<DatabaseDesc>> {
+        let _timer = metrics::METADATA_OP_DURATION_SECONDS
+            .get_database
+            .start_timer();
         self.schema()?.get_database(name).await
     }
 
     pub async fn list_collection(&self, database: &DatabaseDesc) -> Result<Vec<CollectionDesc>> {
+        let _timer = metrics::METADATA_OP_DURATION_SECONDS
+            .list_collection
+            .start_timer();
         let schema = self.schema()?;
         let db = schema
             .get_database(&database.name)
@@ -859,6 +1102,9 @@ impl Root {
         name: &str,
         database: &DatabaseDesc,
     ) -> Result<Option<CollectionDesc>> {
+        let _timer = metrics::METADATA_OP_DURATION_SECONDS
+            .get_collection
+            .start_timer();
         let db = self
             .get_database(&database.name)
             .await?
@@ -882,13 +1128,31 @@ impl Root {
     pub async fn join(
         &self,
         addr: String,
+        zone: String,
         capacity: NodeCapacity,
-    ) -> Result<(Vec<u8>, NodeDesc, RootDesc)> {
+        features: Vec<String>,
+    ) -> Result<(Vec<u8>, NodeDesc, RootDesc, ClusterFeatures)> {
         let schema = self.schema()?;
+
+        // The node running an older binary can't serve the activated features.
+        let cluster_features = schema.cluster_features().await?;
+        let missing = cluster_features
+            .active
+            .iter()
+            .filter(|f| !features.contains(f))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "node {addr} doesn't support the activated features {missing:?}"
+            )));
+        }
+
         let node = schema
             .add_node(NodeDesc {
                 addr,
+                zone,
                 capacity: Some(capacity),
+                features,
                 ..Default::default()
             })
             .await?;
@@ -909,13 +1173,23 @@ impl Root {
             .try_schedule(vec![HeartbeatTask { node_id: node.id }], Instant::now())
             .await;
         info!(node = node.id, addr = ?node.addr, "new node join cluster");
-        Ok((cluster_id, node, root))
+        Ok((cluster_id, node, root, cluster_features))
     }
 
-    pub async fn report(&self, updates: Vec<GroupUpdates>) -> Result<()> {
+    pub async fn report(&self, sequence: ReportSequence, updates: Vec<GroupUpdates>) -> Result<()> {
         // mock report doesn't work.
         // return Ok(());
 
+        if !self.report_sessions.accept(&sequence) {
+            debug!(
+                node = sequence.node_id,
+                session = sequence.session,
+                sequence = sequence.sequence,
+                "skip the stale report"
+            );
+            return Ok(());
+        }
+
         let ongoing_stats = self.ongoing_stats.clone();
         let schema = self.schema()?;
         let mut update_events = Vec::new();
@@ -955,6 +1229,9 @@ impl Root {
                 ongoing_stats.handle_update(&[sched_state], None);
             }
 
+            if u.group_desc.is_some() {
+                self.group_freshness.touch(u.group_id);
+            }
             if let Some(desc) = group_desc {
                 info!(
                     group = desc.id,
@@ -988,15 +1265,18 @@ impl Root {
             }
         }
 
-        let mut states = schema.list_group_state().await?; // TODO: fix poor performance.
-        states.retain(|s| changed_group_states.contains(&s.group_id));
-        for state in states {
-            update_events.push(UpdateEvent {
-                event: Some(update_event::Event::GroupState(state)),
-            })
+        changed_group_states.sort_unstable();
+        changed_group_states.dedup();
+        for group_id in changed_group_states {
+            if let Some(state) = schema.get_group_state(group_id).await? {
+                update_events.push(UpdateEvent {
+                    event: Some(update_event::Event::GroupState(state)),
+                })
+            }
         }
 
         self.watcher_hub().notify_updates(update_events).await;
+        self.report_sessions.commit(&sequence);
 
         Ok(())
     }
@@ -1222,6 +1502,7 @@ impl OngoingStats {
             let mut inner = self.sched_stats.lock().unwrap();
             if inner.replace_state(state_updates) {
                 inner.rebuild_view();
+                metrics::RECONCILE_PENDING_ACTIONS.set(inner.pending_actions() as i64);
             }
         }
         if job_updates.is_some() {
@@ -1252,6 +1533,7 @@ impl OngoingStats {
             let mut inner = self.sched_stats.lock().unwrap();
             inner.raw_group_delta.clear();
             inner.node_view.clear();
+            metrics::RECONCILE_PENDING_ACTIONS.set(0);
         }
         {
             let mut inner = self.job_stats.lock().unwrap();
@@ -1311,6 +1593,13 @@ impl SchedStats {
         }
         self.node_view = new_node_view;
     }
+
+    fn pending_actions(&self) -> usize {
+        self.raw_group_delta
+            .values()
+            .map(|r| r.incoming.len() + r.outgoing.len())
+            .sum()
+    }
 }
 
 #[cfg(test)]
@@ -1372,6 +1661,52 @@ mod root_test {
         });
     }
 
+    #[test]
+    fn fence_deposed_root_leader() {
+        use std::time::Duration;
+
+        use crate::{runtime::time::sleep, Error};
+
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let tmp_dir = TempDir::new("fence_deposed_root_leader").unwrap();
+        let config = Config {
+            root_dir: tmp_dir.path().to_owned(),
+            ..Default::default()
+        };
+
+        let ident = NodeIdent {
+            cluster_id: vec![],
+            node_id: 1,
+        };
+
+        let (root, node) = create_root_and_node(&config, executor.to_owned(), &ident);
+        executor.block_on(async {
+            bootstrap_cluster(&node, "0.0.0.0:8888").await.unwrap();
+            node.bootstrap(&ident).await.unwrap();
+            root.bootstrap(&node).await.unwrap();
+
+            let lease = loop {
+                if let Some(lease) = root.lease() {
+                    break lease;
+                }
+                sleep(Duration::from_millis(100)).await;
+            };
+            root.shared.fence().await.unwrap();
+
+            // The root replica is still the leader, but of a term newer than the one this node
+            // stepped in, eg. it was deposed and elected again. The stale lease must not be used
+            // to issue commands.
+            if let Some(core) = root.shared.core.lock().unwrap().as_mut() {
+                core.term = lease.term - 1;
+            }
+            assert!(matches!(
+                root.shared.fence().await,
+                Err(Error::NotRootLeader(..))
+            ));
+        });
+    }
+
     #[test]
     fn bootstrap_pending_root_replica() {
         let executor_owner = ExecutorOwner::new(1);
@@ -1527,4 +1862,58 @@ pub mod diagnosis {
         pub id: u64,
         pub partition: String,
     }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Topology {
+        pub nodes: Vec<TopologyNode>,
+        pub groups: Vec<TopologyGroup>,
+        pub shards: Vec<TopologyShard>,
+        pub migrations: Vec<TopologyMigration>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct TopologyNode {
+        pub id: u64,
+        pub addr: String,
+        pub zone: String,
+        pub status: String,
+        pub replica_count: u64,
+        pub leader_count: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct TopologyGroup {
+        pub id: u64,
+        pub epoch: u64,
+        /// The id of the leader replica, it is reported by heartbeats so it might be stale.
+        pub leader: Option<u64>,
+        pub replicas: Vec<TopologyReplica>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct TopologyReplica {
+        pub id: u64,
+        pub node: u64,
+        pub role: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct TopologyShard {
+        pub id: u64,
+        pub collection: u64,
+        pub group: u64,
+    }
+
+    /// An ongoing migration, moves a shard between groups or a replica between nodes.
+    #[derive(Serialize, Deserialize)]
+    pub struct TopologyMigration {
+        /// Either `shard` or `replica`.
+        pub kind: String,
+        /// The id of the shard or replica being moved.
+        pub target: u64,
+        /// The source group of a shard, or the source node of a replica.
+        pub from: u64,
+        /// The target group of a shard, or the target node of a replica.
+        pub to: u64,
+    }
 }
@@ -0,0 +1,99 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Tracks the last time the leader state of each group was received by the root, to find the
+/// groups whose states might drift after lost reports and heartbeats.
+#[derive(Default)]
+pub(super) struct GroupFreshness {
+    refreshed: Mutex<HashMap<u64 /* group */, Instant>>,
+}
+
+impl GroupFreshness {
+    /// Record that the state of the group is refreshed.
+    pub fn touch(&self, group_id: u64) {
+        self.touch_at(group_id, Instant::now());
+    }
+
+    /// Returns the groups not refreshed within `threshold`. A group seen for the first time is
+    /// considered fresh, and all returned groups are touched so that they are not probed again
+    /// until the threshold elapses.
+    pub fn take_stale_groups(&self, group_ids: &[u64], threshold: Duration) -> Vec<u64> {
+        self.take_stale_groups_at(group_ids, threshold, Instant::now())
+    }
+
+    pub fn reset(&self) {
+        self.refreshed.lock().unwrap().clear();
+    }
+
+    fn touch_at(&self, group_id: u64, now: Instant) {
+        let mut refreshed = self.refreshed.lock().unwrap();
+        let entry = refreshed.entry(group_id).or_insert(now);
+        if *entry < now {
+            *entry = now;
+        }
+    }
+
+    fn take_stale_groups_at(
+        &self,
+        group_ids: &[u64],
+        threshold: Duration,
+        now: Instant,
+    ) -> Vec<u64> {
+        let mut refreshed = self.refreshed.lock().unwrap();
+        refreshed.retain(|id, _| group_ids.contains(id));
+        let mut stale_groups = Vec::new();
+        for &group_id in group_ids {
+            let last = refreshed.entry(group_id).or_insert(now);
+            if now.saturating_duration_since(*last) >= threshold {
+                *last = now;
+                stale_groups.push(group_id);
+            }
+        }
+        stale_groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_stale_groups() {
+        let freshness = GroupFreshness::default();
+        let threshold = Duration::from_secs(10);
+        let now = Instant::now();
+        assert!(freshness
+            .take_stale_groups_at(&[1, 2], threshold, now)
+            .is_empty());
+
+        freshness.touch_at(1, now + Duration::from_secs(5));
+        let stale = freshness.take_stale_groups_at(&[1, 2], threshold, now + threshold);
+        assert_eq!(stale, vec![2]);
+
+        // The probed group is not stale again until the threshold elapses.
+        let stale = freshness.take_stale_groups_at(&[1, 2], threshold, now + threshold * 3 / 2);
+        assert_eq!(stale, vec![1]);
+
+        // The removed groups are forgotten.
+        let stale = freshness.take_stale_groups_at(&[2], threshold, now + threshold * 3);
+        assert_eq!(stale, vec![2]);
+        assert_eq!(freshness.refreshed.lock().unwrap().len(), 1);
+    }
+}
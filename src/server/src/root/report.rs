@@ -0,0 +1,88 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, sync::Mutex};
+
+/// The sequence of a node report, see `ReportRequest`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReportSequence {
+    pub node_id: u64,
+    pub session: u64,
+    pub sequence: u64,
+}
+
+/// The last applied report sequences of nodes. It is only maintained in memory, since the updates
+/// are idempotent, it only needs to drop the stale retransmissions.
+#[derive(Default)]
+pub(super) struct ReportSessions {
+    applied: Mutex<HashMap<u64 /* node */, (u64 /* session */, u64 /* sequence */)>>,
+}
+
+impl ReportSessions {
+    /// Returns false if a newer report of the same session has been applied.
+    pub fn accept(&self, seq: &ReportSequence) -> bool {
+        if seq.sequence == 0 {
+            return true;
+        }
+        let applied = self.applied.lock().unwrap();
+        match applied.get(&seq.node_id) {
+            Some(&(session, sequence)) if session == seq.session => sequence < seq.sequence,
+            _ => true,
+        }
+    }
+
+    /// Record that the report is applied.
+    pub fn commit(&self, seq: &ReportSequence) {
+        if seq.sequence == 0 {
+            return;
+        }
+        let mut applied = self.applied.lock().unwrap();
+        let entry = applied.entry(seq.node_id).or_default();
+        if entry.0 != seq.session || entry.1 < seq.sequence {
+            *entry = (seq.session, seq.sequence);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seq(session: u64, sequence: u64) -> ReportSequence {
+        ReportSequence {
+            node_id: 1,
+            session,
+            sequence,
+        }
+    }
+
+    #[test]
+    fn skip_stale_reports() {
+        let sessions = ReportSessions::default();
+        assert!(sessions.accept(&seq(1, 2)));
+        sessions.commit(&seq(1, 2));
+        assert!(!sessions.accept(&seq(1, 1)));
+        assert!(!sessions.accept(&seq(1, 2)));
+        assert!(sessions.accept(&seq(1, 3)));
+
+        // Unsequenced reports are always accepted.
+        assert!(sessions.accept(&seq(1, 0)));
+
+        // The node is restarted.
+        assert!(sessions.accept(&seq(2, 1)));
+        sessions.commit(&seq(2, 1));
+        assert!(!sessions.accept(&seq(2, 1)));
+        assert!(sessions.accept(&seq(1, 3)));
+    }
+}
@@ -12,7 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::LinkedList, sync::Arc};
+use std::{
+    collections::LinkedList,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use engula_api::server::v1::*;
 use engula_client::GroupClient;
@@ -27,9 +34,13 @@ use crate::{
     Result,
 };
 
+/// The number of schedule decisions kept in root metadata if it is not configured.
+const DEFAULT_SCHEDULE_HISTORY_SIZE: usize = 1024;
+
 pub struct ReconcileScheduler {
     ctx: ScheduleContext,
     tasks: Mutex<LinkedList<ReconcileTask>>,
+    last_decision_id: AtomicU64,
 }
 
 pub struct ScheduleContext {
@@ -46,6 +57,7 @@ impl ReconcileScheduler {
         Self {
             ctx,
             tasks: Default::default(),
+            last_decision_id: AtomicU64::new(0),
         }
     }
 
@@ -68,6 +80,11 @@ impl ReconcileScheduler {
         info!(len = tasks.len(), task=?task, "setup new reconcile task")
     }
 
+    /// Returns the tasks which are not finished yet.
+    pub async fn pending_tasks(&self) -> Vec<ReconcileTask> {
+        self.tasks.lock().await.iter().cloned().collect()
+    }
+
     async fn is_empty(&self) -> bool {
         self.tasks.lock().await.is_empty()
     }
@@ -89,6 +106,11 @@ impl ReconcileScheduler {
         if !shard_actions.is_empty() {
             return Ok(true);
         }
+
+        let read_replica_actions = self.ctx.alloc.compute_read_replica_action().await?;
+        if !read_replica_actions.is_empty() {
+            return Ok(true);
+        }
         Ok(false)
     }
 
@@ -123,13 +145,30 @@ impl ReconcileScheduler {
 
         let ractions = self.comput_replica_role_action().await?;
         let sactions = self.ctx.alloc.compute_shard_action().await?;
-        if ractions.is_empty() && sactions.is_empty() {
+        let read_actions = self.ctx.alloc.compute_read_replica_action().await?;
+        if ractions.is_empty() && sactions.is_empty() && read_actions.is_empty() {
             return Ok(!self.is_empty().await);
         }
 
         for action in ractions {
+            let (scores_before, scores_after) =
+                self.ctx.alloc.describe_replica_role_scores(&action);
             match action {
                 ReplicaRoleAction::Replica(ReplicaAction::Migrate(action)) => {
+                    let decision_id = self
+                        .record_decision(
+                            format!(
+                                "migrate replica {} of group {} from node {} to node {}",
+                                action.source_replica,
+                                action.group,
+                                action.source_node,
+                                action.target_node.id
+                            ),
+                            "replica count balance",
+                            scores_before,
+                            scores_after,
+                        )
+                        .await;
                     self.setup_task(ReconcileTask {
                         task: Some(reconcile_task::Task::ReallocateReplica(
                             ReallocateReplicaTask {
@@ -140,10 +179,22 @@ impl ReconcileScheduler {
                                 dest_replica: None,
                             },
                         )),
+                        decision_id,
                     })
                     .await;
                 }
                 ReplicaRoleAction::Leader(LeaderAction::Shed(action)) => {
+                    let decision_id = self
+                        .record_decision(
+                            format!(
+                                "transfer leader of group {} from node {} to node {}",
+                                action.group, action.src_node, action.target_node
+                            ),
+                            action.reason,
+                            scores_before,
+                            scores_after,
+                        )
+                        .await;
                     self.setup_task(ReconcileTask {
                         task: Some(reconcile_task::Task::TransferGroupLeader(
                             TransferGroupLeaderTask {
@@ -153,6 +204,7 @@ impl ReconcileScheduler {
                                 dest_node: action.target_node,
                             },
                         )),
+                        decision_id,
                     })
                     .await;
                 }
@@ -161,13 +213,53 @@ impl ReconcileScheduler {
         }
 
         for action in sactions {
+            let (scores_before, scores_after) = self.ctx.alloc.describe_shard_scores(&action);
             let ShardAction::Migrate(action) = action;
+            let decision_id = self
+                .record_decision(
+                    format!(
+                        "migrate shard {} from group {} to group {}",
+                        action.shard, action.source_group, action.target_group
+                    ),
+                    "shard count balance",
+                    scores_before,
+                    scores_after,
+                )
+                .await;
             self.setup_task(ReconcileTask {
                 task: Some(reconcile_task::Task::MigrateShard(MigrateShardTask {
                     shard: action.shard,
                     src_group: action.source_group,
                     dest_group: action.target_group,
                 })),
+                decision_id,
+            })
+            .await;
+        }
+
+        for action in read_actions {
+            let ReadReplicaAction::Add(action) = action;
+            if self.has_pending_read_replica(action.group).await {
+                continue;
+            }
+            let decision_id = self
+                .record_decision(
+                    format!(
+                        "add read replica of group {} in zone {} on node {}",
+                        action.group, action.zone, action.target_node.id
+                    ),
+                    "read replica zone",
+                    String::default(),
+                    String::default(),
+                )
+                .await;
+            self.setup_task(ReconcileTask {
+                task: Some(reconcile_task::Task::AddReadReplica(AddReadReplicaTask {
+                    group: action.group,
+                    dest_node: Some(action.target_node),
+                    dest_replica: None,
+                })),
+                decision_id,
             })
             .await;
         }
@@ -175,6 +267,16 @@ impl ReconcileScheduler {
         Ok(!self.is_empty().await)
     }
 
+    /// The read replicas are added one by one for each group, since the zones of replicas aren't
+    /// changed until the pending task is finished.
+    async fn has_pending_read_replica(&self, group: u64) -> bool {
+        self.tasks
+            .lock()
+            .await
+            .iter()
+            .any(|t| matches!(&t.task, Some(Task::AddReadReplica(task)) if task.group == group))
+    }
+
     pub async fn comput_replica_role_action(&self) -> Result<Vec<ReplicaRoleAction>> {
         let mut actions = Vec::new();
         let replica_actions = self.ctx.alloc.compute_replica_action().await?;
@@ -215,6 +317,78 @@ impl ReconcileScheduler {
     }
 }
 
+impl ReconcileScheduler {
+    /// Persist a schedule decision into root metadata, returns the id of decision or 0 if it is
+    /// not recorded.
+    async fn record_decision(
+        &self,
+        action: String,
+        reason: &str,
+        scores_before: String,
+        scores_after: String,
+    ) -> u64 {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut last_id = self.last_decision_id.load(Ordering::Acquire);
+        let id = loop {
+            let id = std::cmp::max(timestamp.as_micros() as u64, last_id + 1);
+            match self.last_decision_id.compare_exchange(
+                last_id,
+                id,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break id,
+                Err(actual) => last_id = actual,
+            }
+        };
+        let decision = ScheduleDecision {
+            id,
+            timestamp: timestamp.as_millis() as u64,
+            action,
+            reason: reason.to_owned(),
+            scores_before,
+            scores_after,
+            outcome: "scheduled".to_owned(),
+        };
+        let retention = self
+            .ctx
+            .cfg
+            .schedule_history_size
+            .unwrap_or(DEFAULT_SCHEDULE_HISTORY_SIZE);
+        let rs = match self.ctx.shared.schema() {
+            Ok(schema) => schema.put_schedule_decision(decision, retention).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = rs {
+            warn!("record schedule decision {id}: {err:?}");
+            return 0;
+        }
+        id
+    }
+
+    async fn update_decision_outcome(&self, task: &mut ReconcileTask, outcome: String) {
+        if task.decision_id == 0 {
+            return;
+        }
+        let rs = match self.ctx.shared.schema() {
+            Ok(schema) => {
+                schema
+                    .update_schedule_decision(task.decision_id, outcome)
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        if let Err(err) = rs {
+            warn!(
+                "update outcome of schedule decision {}: {err:?}",
+                task.decision_id
+            );
+        }
+    }
+}
+
 impl ReconcileScheduler {
     async fn advance_tasks(&self) -> bool {
         let mut task = self.tasks.lock().await;
@@ -226,14 +400,22 @@ impl ReconcileScheduler {
             let rs = self.ctx.handle_task(task).await;
             match rs {
                 Ok((true /* ack */, immediately_next)) => {
+                    self.update_decision_outcome(task, "finished".to_owned())
+                        .await;
                     cursor.remove_current();
                     if !immediately_next {
                         nowait_next = false
                     }
                 }
+                Err(err) => {
+                    Self::record_retry(task);
+                    self.update_decision_outcome(task, format!("retrying: {err}"))
+                        .await;
+                    cursor.move_next();
+                }
                 _ => {
                     Self::record_retry(task);
-                    // ack == false or meet error, skip current task and retry later.
+                    // ack == false, skip current task and retry later.
                     cursor.move_next();
                 }
             }
@@ -277,6 +459,12 @@ impl ReconcileScheduler {
                     .shed_root_leader
                     .start_timer()
             }
+            Task::AddReadReplica(_) => {
+                metrics::RECONCILE_HANDLE_TASK_TOTAL.add_read_replica.inc();
+                metrics::RECONCILE_HANDLE_TASK_DURATION_SECONDS
+                    .add_read_replica
+                    .start_timer()
+            }
         }
     }
 
@@ -291,6 +479,7 @@ impl ReconcileScheduler {
             }
             Task::ShedLeader(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.shed_group_leaders.inc(),
             Task::ShedRoot(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.shed_root_leader.inc(),
+            Task::AddReadReplica(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.add_read_replica.inc(),
         }
     }
 }
@@ -332,6 +521,65 @@ impl ScheduleContext {
             }
             Task::ShedLeader(shed_leader) => self.handle_shed_leader(shed_leader).await,
             Task::ShedRoot(shed_root) => self.handle_shed_root(shed_root).await,
+            Task::AddReadReplica(add_read_replica) => {
+                self.handle_add_read_replica(add_read_replica).await
+            }
+        }
+    }
+
+    async fn handle_add_read_replica(
+        &self,
+        task: &mut AddReadReplicaTask,
+    ) -> Result<(
+        bool, /* ack current */
+        bool, /* immediately step next tick */
+    )> {
+        let schema = self.shared.schema()?;
+        let group = task.group;
+        let dest_node = task.dest_node.as_ref().unwrap().to_owned();
+        if schema.get_group(group).await?.is_none() {
+            warn!(
+                group = group,
+                "group not found abort add read replica task."
+            );
+            return Ok((true, false));
+        }
+
+        // The replica id is persisted in task, so that the retries don't leak replicas.
+        if task.dest_replica.is_none() {
+            task.dest_replica = Some(ReplicaDesc {
+                id: schema.next_replica_id().await?,
+                node_id: dest_node.id,
+                role: ReplicaRole::Learner as i32,
+            });
+        }
+        let dest_replica = task.dest_replica.as_ref().unwrap().to_owned();
+
+        info!(
+            group = group,
+            dest_node = dest_node.id,
+            replica = dest_replica.id,
+            "start add read replica"
+        );
+        match self.try_add_learner(group, &dest_node, &dest_replica).await {
+            Ok(()) => Ok((true, false)),
+            Err(crate::Error::AlreadyExists(_)) | Err(crate::Error::EpochNotMatch(_)) => {
+                warn!(
+                    group = group,
+                    dest_node = dest_node.id,
+                    "add read replica task aborted due to replica already changed"
+                );
+                Ok((true, false))
+            }
+            Err(err) => {
+                warn!(
+                    group = group,
+                    dest_node = dest_node.id,
+                    err = ?err,
+                    "add read replica meet error and retry later"
+                );
+                Err(err)
+            }
         }
     }
 
@@ -660,6 +908,7 @@ impl ScheduleContext {
         incoming_replica: ReplicaDesc,
         outgoing_replica: ReplicaDesc,
     ) -> Result<ScheduleState> {
+        self.shared.fence().await?;
         let mut group_client = GroupClient::lazy(
             group,
             self.shared.provider.router.clone(),
@@ -671,7 +920,37 @@ impl ScheduleContext {
         Ok(current_state)
     }
 
+    async fn try_add_learner(
+        &self,
+        group: u64,
+        dest_node: &NodeDesc,
+        dest_replica: &ReplicaDesc,
+    ) -> Result<()> {
+        self.shared.fence().await?;
+        let node_client = self
+            .shared
+            .provider
+            .conn_manager
+            .get_node_client(dest_node.addr.clone())?;
+        let desc = GroupDesc {
+            id: group,
+            ..Default::default()
+        };
+        node_client.create_replica(dest_replica.id, desc).await?;
+
+        let mut group_client = GroupClient::lazy(
+            group,
+            self.shared.provider.router.clone(),
+            self.shared.provider.conn_manager.clone(),
+        );
+        group_client
+            .add_learner(dest_replica.id, dest_replica.node_id)
+            .await?;
+        Ok(())
+    }
+
     async fn try_transfer_leader(&self, group: u64, target_replica: u64) -> Result<()> {
+        self.shared.fence().await?;
         let mut group_client = GroupClient::lazy(
             group,
             self.shared.provider.router.clone(),
@@ -682,6 +961,7 @@ impl ScheduleContext {
     }
 
     async fn try_migrate_shard(&self, src_group: u64, target_group: u64, shard: u64) -> Result<()> {
+        self.shared.fence().await?;
         let src_group =
             self.get_group_leader(src_group)
                 .await?
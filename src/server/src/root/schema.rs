@@ -33,11 +33,12 @@ use tracing::{info, warn};
 use super::store::RootStore;
 use crate::{
     bootstrap::*,
+    feature::supported_features,
     node::{
         engine::{SnapshotMode, LOCAL_COLLECTION_ID},
         GroupEngine,
     },
-    serverpb::v1::BackgroundJob,
+    serverpb::v1::{BackgroundJob, ScheduleDecision},
     Error, Provider, Result,
 };
 
@@ -78,6 +79,8 @@ const META_NODE_ID_KEY: &str = "node_id";
 const META_REPLICA_ID_KEY: &str = "replica_id";
 const META_SHARD_ID_KEY: &str = "shard_id";
 const META_JOB_ID_KEY: &str = "job_id";
+const META_SCHEDULE_DECISION_PREFIX: &str = "schedule_decision/";
+const META_CLUSTER_FEATURES_KEY: &str = "cluster_features";
 
 lazy_static::lazy_static! {
     pub static ref SYSTEM_COLLECTION_SHARD: BTreeMap<u64, u64> = BTreeMap::from([
@@ -433,36 +436,12 @@ impl Schema {
     }
 
     pub async fn list_group_state(&self) -> Result<Vec<GroupState>> {
-        let mut states: HashMap<u64, GroupState> = HashMap::new();
-        for state in self.list_replica_state().await? {
-            match states.entry(state.group_id) {
-                Entry::Occupied(mut ent) => {
-                    let group = ent.get_mut();
-                    if state.role == RaftRole::Leader as i32 {
-                        group.leader_id = Some(state.replica_id);
-                    } else if group.leader_id == Some(state.replica_id) {
-                        group.leader_id = None;
-                    }
-                    group
-                        .replicas
-                        .retain(|desc| desc.replica_id != state.replica_id);
-                    group.replicas.push(state);
-                }
-                Entry::Vacant(ent) => {
-                    let leader_id = if state.role == RaftRole::Leader as i32 {
-                        Some(state.replica_id)
-                    } else {
-                        None
-                    };
-                    ent.insert(GroupState {
-                        group_id: state.group_id,
-                        leader_id,
-                        replicas: vec![state],
-                    });
-                }
-            }
-        }
-        Ok(states.into_iter().map(|(_, v)| v).collect())
+        Ok(fold_group_states(self.list_replica_state().await?))
+    }
+
+    /// Returns the state of a group, which is folded from the replica states of the group only.
+    pub async fn get_group_state(&self, group_id: u64) -> Result<Option<GroupState>> {
+        Ok(fold_group_states(self.group_replica_states(group_id).await?).pop())
     }
 
     pub async fn get_root_desc(&self) -> Result<RootDesc> {
@@ -662,6 +641,98 @@ impl Schema {
             .map_err(|_| Error::InvalidData("backgroud job".into()))?;
         Ok(Some(job))
     }
+
+    /// Returns the features activated in the cluster.
+    pub async fn cluster_features(&self) -> Result<ClusterFeatures> {
+        match self.get_meta(META_CLUSTER_FEATURES_KEY.as_bytes()).await? {
+            Some(val) => ClusterFeatures::decode(&*val)
+                .map_err(|_| Error::InvalidData("cluster features".into())),
+            None => Ok(ClusterFeatures::default()),
+        }
+    }
+
+    pub async fn activate_features(&self, features: &[String]) -> Result<ClusterFeatures> {
+        let mut cluster_features = self.cluster_features().await?;
+        for feature in features {
+            if !cluster_features.active.contains(feature) {
+                cluster_features.active.push(feature.to_owned());
+            }
+        }
+        self.batch_write(
+            PutBatchBuilder::default()
+                .put_meta(
+                    META_CLUSTER_FEATURES_KEY.as_bytes().to_vec(),
+                    cluster_features.encode_to_vec(),
+                )
+                .build(),
+        )
+        .await?;
+        Ok(cluster_features)
+    }
+
+    /// Save the schedule decision, and remove the oldest ones if there are more than `retention`
+    /// decisions.
+    pub async fn put_schedule_decision(
+        &self,
+        decision: ScheduleDecision,
+        retention: usize,
+    ) -> Result<()> {
+        self.batch_write(
+            PutBatchBuilder::default()
+                .put_meta(schedule_decision_key(decision.id), decision.encode_to_vec())
+                .build(),
+        )
+        .await?;
+
+        let decisions = self.list_schedule_decision().await?;
+        if decisions.len() > retention {
+            let expired = decisions.len() - retention;
+            for decision in &decisions[..expired] {
+                self.delete(
+                    SYSTEM_MATE_COLLECTION_ID,
+                    &schedule_decision_key(decision.id),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Update the outcome of an existing schedule decision.
+    pub async fn update_schedule_decision(&self, id: u64, outcome: String) -> Result<()> {
+        let key = schedule_decision_key(id);
+        let val = match self.get_meta(&key).await? {
+            Some(val) => val,
+            None => return Ok(()),
+        };
+        let mut decision = ScheduleDecision::decode(&*val)
+            .map_err(|_| Error::InvalidData("schedule decision".into()))?;
+        decision.outcome = outcome;
+        self.batch_write(
+            PutBatchBuilder::default()
+                .put_meta(key, decision.encode_to_vec())
+                .build(),
+        )
+        .await
+    }
+
+    /// List the schedule decisions, ordered by id.
+    pub async fn list_schedule_decision(&self) -> Result<Vec<ScheduleDecision>> {
+        let vals = self
+            .list_prefix(
+                SYSTEM_MATE_COLLECTION_ID,
+                META_SCHEDULE_DECISION_PREFIX.as_bytes(),
+            )
+            .await?;
+        let mut decisions = Vec::with_capacity(vals.len());
+        for val in vals {
+            let decision = ScheduleDecision::decode(&*val)
+                .map_err(|_| Error::InvalidData("schedule decision".into()))?;
+            decisions.push(decision);
+        }
+        decisions.sort_by_key(|d| d.id);
+        Ok(decisions)
+    }
 }
 
 pub struct ReplicaNodes(pub Vec<NodeDesc>);
@@ -687,6 +758,7 @@ impl Schema {
     pub async fn try_bootstrap_root(
         &mut self,
         addr: &str,
+        zone: &str,
         cfg_cpu_nums: u32,
         cluster_id: Vec<u8>,
     ) -> Result<()> {
@@ -718,14 +790,28 @@ impl Schema {
         batch.put_node(NodeDesc {
             id: FIRST_NODE_ID,
             addr: addr.into(),
+            zone: zone.into(),
             capacity: Some(NodeCapacity {
                 cpu_nums: cfg_cpu_nums as f64,
                 replica_count: 1,
                 leader_count: 0,
+                storage_penalty: 0.0,
+                total_space: 0,
+                available_space: 0,
             }),
             status: NodeStatus::Active as i32,
+            features: supported_features(),
         });
 
+        // All features supported by the first node are activated in a new cluster.
+        batch.put_meta(
+            META_CLUSTER_FEATURES_KEY.as_bytes().to_vec(),
+            ClusterFeatures {
+                active: supported_features(),
+            }
+            .encode_to_vec(),
+        );
+
         batch.put_group(GroupDesc {
             id: ROOT_GROUP_ID,
             epoch: INITIAL_EPOCH,
@@ -816,6 +902,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(self_collection);
 
@@ -826,6 +913,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(db_collection);
 
@@ -836,6 +924,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(meta_collection);
 
@@ -846,6 +935,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(node_collection);
 
@@ -856,6 +946,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(group_collection);
 
@@ -866,6 +957,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(replica_state_collection);
 
@@ -876,6 +968,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(job_collection);
 
@@ -886,6 +979,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(job_history_collection);
     }
@@ -1042,7 +1136,11 @@ impl PutBatchBuilder {
             .cloned()
             .map(|(shard_id, key, value)| ShardPutRequest {
                 shard_id,
-                put: Some(PutRequest { key, value }),
+                put: Some(PutRequest {
+                    key,
+                    value,
+                    ..Default::default()
+                }),
             })
             .collect::<Vec<_>>();
         BatchWriteRequest {
@@ -1124,6 +1222,46 @@ impl PutBatchBuilder {
     }
 }
 
+#[inline]
+fn fold_group_states(replica_states: Vec<ReplicaState>) -> Vec<GroupState> {
+    let mut states: HashMap<u64, GroupState> = HashMap::new();
+    for state in replica_states {
+        match states.entry(state.group_id) {
+            Entry::Occupied(mut ent) => {
+                let group = ent.get_mut();
+                if state.role == RaftRole::Leader as i32 {
+                    group.leader_id = Some(state.replica_id);
+                } else if group.leader_id == Some(state.replica_id) {
+                    group.leader_id = None;
+                }
+                group
+                    .replicas
+                    .retain(|desc| desc.replica_id != state.replica_id);
+                group.replicas.push(state);
+            }
+            Entry::Vacant(ent) => {
+                let leader_id = if state.role == RaftRole::Leader as i32 {
+                    Some(state.replica_id)
+                } else {
+                    None
+                };
+                ent.insert(GroupState {
+                    group_id: state.group_id,
+                    leader_id,
+                    replicas: vec![state],
+                });
+            }
+        }
+    }
+    states.into_iter().map(|(_, v)| v).collect()
+}
+
+fn schedule_decision_key(id: u64) -> Vec<u8> {
+    let mut buf = META_SCHEDULE_DECISION_PREFIX.as_bytes().to_vec();
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf
+}
+
 #[inline]
 fn collection_key(database_id: u64, collection_name: &str) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + collection_name.len());
@@ -0,0 +1,37 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, sync::Mutex};
+
+/// The approximate sizes of the shards of each group, reported by the group leaders with the
+/// heartbeat responses.
+#[derive(Default)]
+pub(super) struct ShardSizes {
+    groups: Mutex<HashMap<u64 /* group */, HashMap<u64 /* shard */, u64>>>,
+}
+
+impl ShardSizes {
+    /// Replace the shard sizes of the group with the latest report.
+    pub fn update(&self, group_id: u64, shard_sizes: HashMap<u64, u64>) {
+        self.groups.lock().unwrap().insert(group_id, shard_sizes);
+    }
+
+    pub fn groups(&self) -> HashMap<u64, HashMap<u64, u64>> {
+        self.groups.lock().unwrap().clone()
+    }
+
+    pub fn reset(&self) {
+        self.groups.lock().unwrap().clear();
+    }
+}
@@ -41,7 +41,11 @@ impl RootStore {
     pub async fn put(&self, shard_id: u64, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
         self.submit_request(Put(ShardPutRequest {
             shard_id,
-            put: Some(PutRequest { key, value }),
+            put: Some(PutRequest {
+                key,
+                value,
+                ..Default::default()
+            }),
         }))
         .await?;
         Ok(())
@@ -106,6 +110,7 @@ impl RootStore {
         let request = GroupRequest {
             group_id: ROOT_GROUP_ID,
             epoch,
+            stale_read: false,
             request: Some(GroupRequestUnion { request: Some(req) }),
         };
 
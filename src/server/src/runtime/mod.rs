@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 mod metrics;
+pub mod registry;
 mod shutdown;
 pub mod sync;
 pub mod time;
@@ -27,8 +28,8 @@ use pin_project::pin_project;
 use serde::{Deserialize, Serialize};
 pub use tokio::select;
 
-use self::metrics::*;
 pub use self::shutdown::{Shutdown, ShutdownNotifier};
+use self::{metrics::*, registry::TaskToken};
 
 #[derive(Debug)]
 pub enum TaskPriority {
@@ -44,6 +45,11 @@ pub enum TaskPriority {
 pub struct ExecutorConfig {
     pub event_interval: Option<u32>,
     pub global_event_interval: Option<u32>,
+
+    /// Log a warning with the spawn backtrace if a named task hasn't yielded for this duration.
+    ///
+    /// Default: disabled
+    pub watchdog_threshold_ms: Option<u64>,
 }
 
 /// A handle that awaits the result of a task.
@@ -98,6 +104,9 @@ impl ExecutorOwner {
             })
             .build()
             .expect("build tokio runtime");
+        if let Some(threshold) = cfg.watchdog_threshold_ms {
+            registry::start_watchdog(Duration::from_millis(threshold));
+        }
         ExecutorOwner { runtime }
     }
 
@@ -131,6 +140,29 @@ impl Executor {
         JoinHandle { inner }
     }
 
+    /// Spawns a named task of the task group, see [`registry`] for details.
+    pub fn spawn_named<F, T>(
+        &self,
+        group: &'static str,
+        name: impl Into<String>,
+        tag: Option<&[u8]>,
+        priority: TaskPriority,
+        future: F,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        // TODO(walter) support per thread task set.
+        let _ = tag;
+        take_spawn_metrics(priority);
+        let token = TaskToken::register(group, name.into());
+        let inner = self
+            .handle
+            .spawn(FutureWrapper::with_token(future, Some(token)));
+        JoinHandle { inner }
+    }
+
     /// Dispatch a task.
     ///
     /// [`tag`]: specify the tag of task, the underlying scheduler should ensure that all tasks
@@ -218,13 +250,19 @@ struct FutureWrapper<F: Future> {
     #[pin]
     inner: F,
     state: TaskState,
+    token: Option<TaskToken>,
 }
 
 impl<F: Future> FutureWrapper<F> {
     fn new(inner: F) -> Self {
+        Self::with_token(inner, None)
+    }
+
+    fn with_token(inner: F, token: Option<TaskToken>) -> Self {
         FutureWrapper {
             state: TaskState::First(Instant::now()),
             inner,
+            token,
         }
     }
 }
@@ -243,7 +281,13 @@ impl<F: Future> Future for FutureWrapper<F> {
         };
 
         let start = Instant::now();
+        if let Some(token) = this.token.as_ref() {
+            token.enter_poll();
+        }
         let output = Pin::new(&mut this.inner).poll(cx);
+        if let Some(token) = this.token.as_ref() {
+            token.leave_poll();
+        }
         let elapsed = start.elapsed();
         EXECUTOR_TASK_POLL_DURATION_SECONDS.observe(elapsed.as_secs_f64());
         if !should_skip_slow_log::<F>() && elapsed >= Duration::from_micros(1000) {
@@ -0,0 +1,210 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A registry of named tasks, so that long running tasks could be listed and the tasks which
+//! haven't yielded for a long time are reported by the watchdog.
+
+use std::{
+    backtrace::Backtrace,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, Once,
+    },
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tracing::warn;
+
+/// The task groups of the server.
+pub mod group {
+    pub const RAFT: &str = "raft";
+    pub const ROOT: &str = "root";
+    pub const SCHEDULE: &str = "schedule";
+    pub const SNAPSHOT: &str = "snapshot";
+    pub const NODE: &str = "node";
+}
+
+lazy_static! {
+    static ref REGISTRY: TaskRegistry = TaskRegistry::default();
+    static ref EPOCH: Instant = Instant::now();
+}
+
+#[derive(Default)]
+struct TaskRegistry {
+    next_id: AtomicU64,
+    tasks: Mutex<HashMap<u64, Arc<TaskSlot>>>,
+}
+
+struct TaskSlot {
+    id: u64,
+    group: &'static str,
+    name: String,
+    spawned_at: Instant,
+    /// The micros since [`EPOCH`] when the current poll starts, 0 means the task isn't polling.
+    poll_start_us: AtomicU64,
+    /// Whether the current poll has been reported by the watchdog.
+    reported: AtomicBool,
+    /// The backtrace of the spawn site, it is captured only if `RUST_BACKTRACE` is set.
+    backtrace: Backtrace,
+}
+
+/// The summary of a named task.
+#[derive(Debug, Serialize)]
+pub struct TaskInfo {
+    pub id: u64,
+    pub group: &'static str,
+    pub name: String,
+    pub elapsed_ms: u64,
+    /// How long the current poll has executed, `None` if the task is not polling.
+    pub polling_ms: Option<u64>,
+}
+
+/// The registration of a named task, the task is unregistered once it is dropped.
+pub(super) struct TaskToken {
+    slot: Arc<TaskSlot>,
+}
+
+impl TaskToken {
+    pub(super) fn register(group: &'static str, name: String) -> Self {
+        let id = REGISTRY.next_id.fetch_add(1, Ordering::Relaxed);
+        let slot = Arc::new(TaskSlot {
+            id,
+            group,
+            name,
+            spawned_at: Instant::now(),
+            poll_start_us: AtomicU64::new(0),
+            reported: AtomicBool::new(false),
+            backtrace: Backtrace::capture(),
+        });
+        REGISTRY.tasks.lock().unwrap().insert(id, slot.clone());
+        TaskToken { slot }
+    }
+
+    #[inline]
+    pub(super) fn enter_poll(&self) {
+        let now = std::cmp::max(EPOCH.elapsed().as_micros() as u64, 1);
+        self.slot.poll_start_us.store(now, Ordering::Release);
+    }
+
+    #[inline]
+    pub(super) fn leave_poll(&self) {
+        self.slot.poll_start_us.store(0, Ordering::Release);
+        self.slot.reported.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Drop for TaskToken {
+    fn drop(&mut self) {
+        REGISTRY.tasks.lock().unwrap().remove(&self.slot.id);
+    }
+}
+
+impl TaskSlot {
+    fn polling_duration(&self) -> Option<Duration> {
+        match self.poll_start_us.load(Ordering::Acquire) {
+            0 => None,
+            start => {
+                let now = EPOCH.elapsed().as_micros() as u64;
+                Some(Duration::from_micros(now.saturating_sub(start)))
+            }
+        }
+    }
+}
+
+/// List the named tasks in the order of spawning.
+pub fn tasks() -> Vec<TaskInfo> {
+    let mut tasks = REGISTRY
+        .tasks
+        .lock()
+        .unwrap()
+        .values()
+        .map(|slot| TaskInfo {
+            id: slot.id,
+            group: slot.group,
+            name: slot.name.clone(),
+            elapsed_ms: slot.spawned_at.elapsed().as_millis() as u64,
+            polling_ms: slot.polling_duration().map(|d| d.as_millis() as u64),
+        })
+        .collect::<Vec<_>>();
+    tasks.sort_unstable_by_key(|t| t.id);
+    tasks
+}
+
+/// Start a watchdog thread, which logs a warning with the spawn backtrace if a named task hasn't
+/// yielded for `threshold`. Only the first call takes effect.
+pub fn start_watchdog(threshold: Duration) {
+    static START: Once = Once::new();
+    START.call_once(|| {
+        let interval = std::cmp::max(threshold / 2, Duration::from_millis(100));
+        std::thread::Builder::new()
+            .name("watchdog".to_owned())
+            .spawn(move || loop {
+                std::thread::sleep(interval);
+                check_stuck_tasks(threshold);
+            })
+            .expect("spawn watchdog thread");
+    });
+}
+
+fn check_stuck_tasks(threshold: Duration) -> usize {
+    let slots = REGISTRY
+        .tasks
+        .lock()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut num_reported = 0;
+    for slot in slots {
+        let Some(duration) = slot.polling_duration() else {
+            continue;
+        };
+        if duration >= threshold && !slot.reported.swap(true, Ordering::Relaxed) {
+            warn!(
+                "task {} {} has not yielded for {duration:?}, spawned at:\n{}",
+                slot.group, slot.name, slot.backtrace
+            );
+            num_reported += 1;
+        }
+    }
+    num_reported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_and_report_stuck_tasks() {
+        let token = TaskToken::register(group::NODE, "test-task".to_owned());
+        let id = token.slot.id;
+        let info = tasks().into_iter().find(|t| t.id == id).unwrap();
+        assert_eq!(info.name, "test-task");
+        assert!(info.polling_ms.is_none());
+
+        token.enter_poll();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(check_stuck_tasks(Duration::from_millis(10)) >= 1);
+        // Each stuck poll is reported once.
+        assert!(token.slot.reported.load(Ordering::Relaxed));
+        token.leave_poll();
+        assert!(!token.slot.reported.load(Ordering::Relaxed));
+
+        drop(token);
+        assert!(tasks().into_iter().all(|t| t.id != id));
+    }
+}
@@ -67,6 +67,18 @@ impl ShutdownNotifier {
             .expect("failed to listen ctrl c event");
     }
 
+    /// Wait for ctrl c or the `SIGTERM` signal, which is sent by the process managers (eg.
+    /// kubernetes) to stop the process.
+    pub async fn terminate_signal(self) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate()).expect("failed to listen sigterm event");
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res.expect("failed to listen ctrl c event"),
+            _ = sigterm.recv() => {}
+        }
+    }
+
     pub fn subscribe(&self) -> Shutdown {
         Shutdown::new(self.core.clone())
     }
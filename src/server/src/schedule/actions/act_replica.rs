@@ -78,11 +78,8 @@ impl Action for CreateReplicas {
                     self.interval_ms = 50;
                     self.replicas.pop();
                 }
-                Err(engula_client::Error::Rpc(status))
-                    if engula_client::error::retryable_rpc_err(&status)
-                        && self.retry_count < 30 =>
-                {
-                    debug!("group {group_id} replica {replica_id} task {task_id} create replica {r:?}: {status}");
+                Err(e) if e.is_retryable() && self.retry_count < 30 => {
+                    debug!("group {group_id} replica {replica_id} task {task_id} create replica {r:?}: {e}");
                     self.retry_count += 1;
                     self.interval_ms = std::cmp::min(self.interval_ms * 2, 1000);
                     return ActionState::Pending(Some(Duration::from_millis(self.interval_ms)));
@@ -135,10 +132,8 @@ impl Action for RemoveReplica {
                 info!("group {group_id} replica {replica_id} task {task_id} remove replica {replica:?} success");
                 return ActionState::Done;
             }
-            Err(engula_client::Error::Rpc(status))
-                if engula_client::error::retryable_rpc_err(&status) && self.retry_count < 3 =>
-            {
-                debug!("group {group_id} replica {replica_id} task {task_id} remove replica {replica:?}: {status}");
+            Err(e) if e.is_retryable() && self.retry_count < 3 => {
+                debug!("group {group_id} replica {replica_id} task {task_id} remove replica {replica:?}: {e}");
                 self.retry_count += 1;
                 ActionState::Pending(Some(Duration::from_secs(30)))
             }
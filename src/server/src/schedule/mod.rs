@@ -13,6 +13,7 @@
 // limitations under the License.
 mod actions;
 mod event_source;
+mod metrics;
 mod provider;
 mod scheduler;
 mod setup;
@@ -20,7 +20,7 @@ use tracing::debug;
 use super::ScheduleStateObserver;
 use crate::{
     node::{replica::ReplicaConfig, Replica},
-    runtime::{sync::WaitGroup, TaskPriority},
+    runtime::{registry::group, sync::WaitGroup, TaskPriority},
     schedule::{
         event_source::EventSource,
         provider::{GroupProviders, MoveReplicasProvider},
@@ -47,17 +47,24 @@ pub(crate) fn setup_scheduler(
     let group_id = replica.replica_info().group_id;
     let tag = &group_id.to_le_bytes();
     let executor = provider.executor.clone();
-    executor.spawn(Some(tag), TaskPriority::Low, async move {
-        scheduler_main(
-            cfg,
-            replica,
-            provider,
-            group_providers,
-            schedule_state_observer,
-        )
-        .await;
-        drop(wait_group);
-    });
+    let name = format!("scheduler-{group_id}");
+    executor.spawn_named(
+        group::SCHEDULE,
+        name,
+        Some(tag),
+        TaskPriority::Low,
+        async move {
+            scheduler_main(
+                cfg,
+                replica,
+                provider,
+                group_providers,
+                schedule_state_observer,
+            )
+            .await;
+            drop(wait_group);
+        },
+    );
 }
 
 async fn scheduler_main(
@@ -111,7 +118,9 @@ async fn allocate_group_tasks(scheduler: &mut Scheduler, providers: Arc<GroupPro
         Box::new(PromoteGroup::new(providers.clone())),
         Box::new(DurableGroup::new(providers.clone())),
         Box::new(RemoveOrphanReplica::new(providers.clone())),
-        Box::new(ReplicaMigration::new(providers)),
+        Box::new(ReplicaMigration::new(providers.clone())),
+        Box::new(ConsistencyCheck::new(providers.clone())),
+        Box::new(SweepOrphanChunks::new(providers)),
     ];
     scheduler.install_tasks(tasks);
 }
@@ -0,0 +1,212 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use engula_api::{
+    chunk,
+    server::v1::{group_request_union::Request, *},
+    shard,
+    v1::DeleteRequest,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    node::{
+        engine::{GroupEngine, SnapshotMode},
+        replica::ExecCtx,
+    },
+    schedule::{
+        metrics::*,
+        provider::GroupProviders,
+        scheduler::ScheduleContext,
+        task::{Task, TaskState},
+        tasks::SWEEP_ORPHAN_CHUNKS_TASK_ID,
+    },
+    Result,
+};
+
+/// The interval between two sweeps of a group.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// The interval between two batches of the same sweep.
+const SWEEP_BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The max number of keys scanned by each batch.
+const SWEEP_BATCH_KEYS: usize = 1024;
+
+/// The chunks written within this duration are skipped, because the manifests of them might be
+/// still inflight.
+const SWEEP_GRACE_PERIOD: Duration = Duration::from_secs(3600);
+
+/// Reclaims the orphan chunks of large values, eg. the chunks of failed writes, or the chunks of
+/// values replaced by clients without removing them. A chunk is an orphan if the manifest saved
+/// in its user key doesn't reference it.
+pub struct SweepOrphanChunks {
+    providers: Arc<GroupProviders>,
+    next_shard_idx: usize,
+    /// The last key scanned of the shard being swept.
+    last_key: Option<Vec<u8>>,
+}
+
+impl SweepOrphanChunks {
+    pub fn new(providers: Arc<GroupProviders>) -> SweepOrphanChunks {
+        SweepOrphanChunks {
+            providers,
+            next_shard_idx: 0,
+            last_key: None,
+        }
+    }
+
+    async fn sweep(
+        &mut self,
+        ctx: &mut ScheduleContext<'_>,
+        desc: &GroupDesc,
+        shard_desc: &ShardDesc,
+    ) -> Result<()> {
+        let shard_id = shard_desc.id;
+        let group_engine = ctx.replica.group_engine();
+        let expired_write_id = SystemTime::now()
+            .checked_sub(SWEEP_GRACE_PERIOD)
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        let (chunk_keys, last_key) = scan_chunk_keys(
+            &group_engine,
+            shard_id,
+            self.last_key.as_deref(),
+            expired_write_id,
+        )?;
+        self.last_key = last_key;
+
+        let mut orphans = Vec::default();
+        let mut manifest: Option<(Vec<u8>, Option<u64>)> = None;
+        for chunk_key in chunk_keys {
+            let (user_key, write_id) = chunk::parse_chunk_key(&chunk_key).unwrap();
+            if !shard::belong_to(shard_desc, user_key) {
+                // The manifest is saved in another shard.
+                continue;
+            }
+            let cached = matches!(&manifest, Some((key, _)) if key == user_key);
+            if !cached {
+                let value = group_engine.get(shard_id, user_key).await?;
+                let manifest_write_id = value.as_deref().and_then(chunk::manifest_write_id);
+                manifest = Some((user_key.to_owned(), manifest_write_id));
+            }
+            if manifest.as_ref().unwrap().1 != Some(write_id) {
+                orphans.push(chunk_key);
+            }
+        }
+        if orphans.is_empty() {
+            return Ok(());
+        }
+
+        let num_orphans = orphans.len();
+        let req = Request::BatchWrite(BatchWriteRequest {
+            deletes: orphans
+                .into_iter()
+                .map(|key| ShardDeleteRequest {
+                    shard_id,
+                    delete: Some(DeleteRequest { key }),
+                })
+                .collect(),
+            puts: vec![],
+        });
+        let mut exec_ctx = ExecCtx::with_epoch(desc.epoch);
+        ctx.replica.execute(&mut exec_ctx, &req).await?;
+        SCHEDULE_SWEEP_ORPHAN_CHUNKS_TOTAL.inc_by(num_orphans as u64);
+        info!(
+            "group {} replica {} remove {num_orphans} orphan chunks of shard {shard_id}",
+            ctx.group_id, ctx.replica_id
+        );
+        Ok(())
+    }
+}
+
+#[crate::async_trait]
+impl Task for SweepOrphanChunks {
+    fn id(&self) -> u64 {
+        SWEEP_ORPHAN_CHUNKS_TASK_ID
+    }
+
+    async fn poll(&mut self, ctx: &mut ScheduleContext<'_>) -> TaskState {
+        let desc = self.providers.descriptor.descriptor();
+        if desc.shards.is_empty() || ctx.replica.migration_state().is_some() {
+            self.last_key = None;
+            return TaskState::Pending(Some(SWEEP_INTERVAL));
+        }
+
+        let shard_desc = desc.shards[self.next_shard_idx % desc.shards.len()].clone();
+        if let Err(e) = self.sweep(ctx, &desc, &shard_desc).await {
+            warn!(
+                "group {} replica {} sweep orphan chunks of shard {}: {e}",
+                ctx.group_id, ctx.replica_id, shard_desc.id
+            );
+            self.last_key = None;
+        }
+        if self.last_key.is_some() {
+            return TaskState::Pending(Some(SWEEP_BATCH_INTERVAL));
+        }
+
+        debug!(
+            "group {} replica {} orphan chunks of shard {} are swept",
+            ctx.group_id, ctx.replica_id, shard_desc.id
+        );
+        self.next_shard_idx = self.next_shard_idx.wrapping_add(1);
+        if self.next_shard_idx % desc.shards.len() == 0 {
+            TaskState::Pending(Some(SWEEP_INTERVAL))
+        } else {
+            TaskState::Pending(Some(SWEEP_BATCH_INTERVAL))
+        }
+    }
+}
+
+/// Scan a batch of keys after `start_key`, returns the chunk keys written before
+/// `expired_write_id`, and the last key scanned if the shard isn't exhausted.
+fn scan_chunk_keys(
+    group_engine: &GroupEngine,
+    shard_id: u64,
+    start_key: Option<&[u8]>,
+    expired_write_id: u64,
+) -> Result<(Vec<Vec<u8>>, Option<Vec<u8>>)> {
+    let mut chunk_keys = Vec::default();
+    let mut num_keys = 0;
+    let mut snapshot = group_engine.snapshot(shard_id, SnapshotMode::Start { start_key })?;
+    for key_iter in snapshot.iter() {
+        let mut key_iter = key_iter?;
+        // Only the latest version is checked.
+        let Some(entry) = key_iter.next() else {
+            continue;
+        };
+        let entry = entry?;
+        if Some(entry.user_key()) == start_key {
+            continue;
+        }
+        if entry.value().is_some() {
+            if let Some((_, write_id)) = chunk::parse_chunk_key(entry.user_key()) {
+                if write_id < expired_write_id {
+                    chunk_keys.push(entry.user_key().to_owned());
+                }
+            }
+        }
+        num_keys += 1;
+        if num_keys >= SWEEP_BATCH_KEYS {
+            return Ok((chunk_keys, Some(entry.user_key().to_owned())));
+        }
+    }
+    Ok((chunk_keys, None))
+}
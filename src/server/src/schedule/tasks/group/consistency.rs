@@ -0,0 +1,194 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use engula_api::server::v1::*;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    schedule::{
+        metrics::*,
+        provider::GroupProviders,
+        scheduler::ScheduleContext,
+        task::{Task, TaskState},
+        tasks::CONSISTENCY_CHECK_TASK_ID,
+    },
+    Provider,
+};
+
+/// The interval between two consistency checks of a group.
+const CHECK_INTERVAL: Duration = Duration::from_secs(600);
+
+/// The interval of polling the checksums of replicas.
+const COLLECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Replicas which don't report checksums within this duration are skipped, eg. they are lagging
+/// behind or installing snapshots.
+const COLLECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+enum CheckState {
+    Idle,
+    Collecting {
+        shard_id: u64,
+        checksum_id: u64,
+        start_at: Instant,
+    },
+}
+
+/// Verifies the consistency of replicas in background. The shards of the group are checked in
+/// turn: the leader proposes a `ComputeChecksum` command, so that all replicas compute the
+/// checksum of the shard at the same raft index, and then collects and compares these checksums.
+pub struct ConsistencyCheck {
+    providers: Arc<GroupProviders>,
+    next_shard_idx: usize,
+    state: CheckState,
+}
+
+impl ConsistencyCheck {
+    pub fn new(providers: Arc<GroupProviders>) -> ConsistencyCheck {
+        ConsistencyCheck {
+            providers,
+            next_shard_idx: 0,
+            state: CheckState::Idle,
+        }
+    }
+
+    async fn start_check(&mut self, ctx: &mut ScheduleContext<'_>, desc: &GroupDesc) -> TaskState {
+        let shard_id = desc.shards[self.next_shard_idx % desc.shards.len()].id;
+        self.next_shard_idx = self.next_shard_idx.wrapping_add(1);
+
+        let checksum_id = rand::random::<u64>();
+        if let Err(e) = ctx.replica.compute_checksum(shard_id, checksum_id).await {
+            warn!(
+                "group {} replica {} compute checksum of shard {shard_id}: {e}",
+                ctx.group_id, ctx.replica_id
+            );
+            return TaskState::Pending(Some(CHECK_INTERVAL));
+        }
+
+        SCHEDULE_CONSISTENCY_CHECK_TOTAL.inc();
+        self.state = CheckState::Collecting {
+            shard_id,
+            checksum_id,
+            start_at: Instant::now(),
+        };
+        TaskState::Pending(Some(COLLECT_INTERVAL))
+    }
+
+    async fn collect_and_compare(
+        &mut self,
+        ctx: &mut ScheduleContext<'_>,
+        desc: &GroupDesc,
+        shard_id: u64,
+        checksum_id: u64,
+        start_at: Instant,
+    ) -> TaskState {
+        let group_id = ctx.group_id;
+        let replica_id = ctx.replica_id;
+
+        let mut checksums = Vec::with_capacity(desc.replicas.len());
+        let mut missing = Vec::default();
+        for r in &desc.replicas {
+            let result = if r.id == replica_id {
+                Ok(ctx
+                    .replica
+                    .shard_checksum(shard_id)
+                    .filter(|c| c.checksum_id == checksum_id))
+            } else {
+                collect_checksum(&ctx.provider, group_id, r, shard_id, checksum_id).await
+            };
+            match result {
+                Ok(Some(checksum)) => checksums.push((r.id, checksum)),
+                Ok(None) => missing.push(r.id),
+                Err(e) => {
+                    debug!("group {group_id} replica {replica_id} collect checksum of shard {shard_id} from replica {}: {e}", r.id);
+                    missing.push(r.id);
+                }
+            }
+        }
+
+        if !missing.is_empty() && start_at.elapsed() < COLLECT_TIMEOUT {
+            return TaskState::Pending(Some(COLLECT_INTERVAL));
+        }
+        self.state = CheckState::Idle;
+
+        if !missing.is_empty() {
+            info!("group {group_id} replica {replica_id} consistency check of shard {shard_id} skips replicas {missing:?}");
+        }
+
+        // The checksum of leader is the baseline, since it serves the requests.
+        let Some((_, expect)) = checksums.iter().find(|(id, _)| *id == replica_id).cloned() else {
+            return TaskState::Pending(Some(CHECK_INTERVAL));
+        };
+        for (id, checksum) in &checksums {
+            if checksum.checksum != expect.checksum || checksum.num_keys != expect.num_keys {
+                SCHEDULE_CONSISTENCY_CHECK_MISMATCH_TOTAL.inc();
+                error!(
+                    "group {group_id} replica {id} is inconsistent with leader {replica_id}, shard {shard_id} index {} checksum {} num keys {}, but leader's checksum {} num keys {}",
+                    checksum.index, checksum.checksum, checksum.num_keys, expect.checksum, expect.num_keys,
+                );
+            }
+        }
+
+        TaskState::Pending(Some(CHECK_INTERVAL))
+    }
+}
+
+#[crate::async_trait]
+impl Task for ConsistencyCheck {
+    fn id(&self) -> u64 {
+        CONSISTENCY_CHECK_TASK_ID
+    }
+
+    async fn poll(&mut self, ctx: &mut ScheduleContext<'_>) -> TaskState {
+        let desc = self.providers.descriptor.descriptor();
+        if desc.shards.is_empty() {
+            return TaskState::Pending(Some(CHECK_INTERVAL));
+        }
+
+        match self.state {
+            CheckState::Idle => self.start_check(ctx, &desc).await,
+            CheckState::Collecting {
+                shard_id,
+                checksum_id,
+                start_at,
+            } => {
+                self.collect_and_compare(ctx, &desc, shard_id, checksum_id, start_at)
+                    .await
+            }
+        }
+    }
+}
+
+async fn collect_checksum(
+    provider: &Provider,
+    group_id: u64,
+    r: &ReplicaDesc,
+    shard_id: u64,
+    checksum_id: u64,
+) -> std::result::Result<Option<ShardChecksum>, engula_client::Error> {
+    let addr = provider.router.find_node_addr(r.node_id)?;
+    let client = provider.conn_manager.get_node_client(addr)?;
+    let req = CollectChecksumRequest {
+        group_id,
+        shard_id,
+        checksum_id,
+    };
+    let resp = client.collect_checksum(req).await?;
+    Ok(resp.checksum)
+}
@@ -12,7 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
 use engula_api::server::v1::*;
 use tracing::{debug, error, info};
@@ -270,7 +274,7 @@ impl DurableGroup {
             debug_assert!(stats.offline_voters.is_empty());
             debug_assert!(stats.offline_learners.is_empty());
             debug_assert_eq!(stats.online_voters.len(), num_required);
-            self.remove_learners(ctx, stats.peers, stats.offline_learners)
+            self.remove_learners(ctx, stats.peers, stats.online_learners)
                 .await;
             return TaskState::Pending(Some(Duration::from_secs(30)));
         }
@@ -278,6 +282,20 @@ impl DurableGroup {
         TaskState::Pending(Some(Duration::from_secs(1)))
     }
 
+    /// The zones where the read replicas of the group are placed, see
+    /// `CollectionDesc::read_replica_zones`.
+    fn read_replica_zones(&self, ctx: &ScheduleContext<'_>) -> HashSet<String> {
+        let router = &ctx.provider.router;
+        self.providers
+            .descriptor
+            .descriptor()
+            .shards
+            .iter()
+            .filter_map(|s| router.find_collection(s.collection_id).ok())
+            .flat_map(|c| c.read_replica_zones)
+            .collect()
+    }
+
     fn select_dismiss_voters(
         &mut self,
         ctx: &mut ScheduleContext<'_>,
@@ -325,6 +343,7 @@ impl Task for DurableGroup {
         }
 
         let lost_peers = self.providers.raft_state.lost_peers();
+        let read_replica_zones = self.read_replica_zones(ctx);
         let mut stats = ReplicaStats::default();
         for r in &replicas {
             if ctx.group_lock_table.is_replica_locked(r.id) {
@@ -347,6 +366,15 @@ impl Task for DurableGroup {
                 ReplicaRole::Learner => {
                     if lost_peers.contains(&r.id) {
                         stats.offline_learners.insert(r.id, r.clone());
+                    } else if ctx
+                        .provider
+                        .router
+                        .find_node_zone(r.node_id)
+                        .map(|zone| read_replica_zones.contains(&zone))
+                        .unwrap_or_default()
+                    {
+                        // The read replicas are maintained by root, they are neither promoted
+                        // nor removed here.
                     } else {
                         stats.online_learners.insert(r.id, r.clone());
                     }
@@ -19,6 +19,11 @@ pub mod v1 {
 
     tonic::include_proto!("serverpb.v1");
 
+    /// The encoded `FileDescriptorSet` of the server protos, it is used by the gRPC reflection
+    /// service.
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("serverpb_descriptor");
+
     pub type ApplyState = EntryId;
     pub type MigrationEvent = migration::Event;
 
@@ -52,6 +57,48 @@ pub mod v1 {
                 ..Default::default()
             })
         }
+
+        #[inline]
+        pub fn compute_checksum(shard_id: u64, checksum_id: u64) -> Box<Self> {
+            Box::new(SyncOp {
+                compute_checksum: Some(ComputeChecksum {
+                    shard_id,
+                    checksum_id,
+                }),
+                ..Default::default()
+            })
+        }
+
+        #[inline]
+        pub fn delete_range(shard_id: u64, start: Vec<u8>, end: Vec<u8>) -> Box<Self> {
+            Box::new(SyncOp {
+                delete_range: Some(DeleteRange {
+                    shard_id,
+                    start,
+                    end,
+                }),
+                ..Default::default()
+            })
+        }
+
+        #[inline]
+        pub fn rename(
+            shard_id: u64,
+            from: Vec<u8>,
+            to: Vec<u8>,
+            only_if_absent: bool,
+        ) -> Box<Self> {
+            Box::new(SyncOp {
+                rename: Some(Rename {
+                    shard_id,
+                    from,
+                    to,
+                    only_if_absent,
+                }),
+                ..Default::default()
+            })
+        }
+
         #[inline]
         pub fn ingest(key: Vec<u8>) -> Box<Self> {
             Box::new(SyncOp {
@@ -0,0 +1,53 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tonic::codegen::*;
+
+use crate::{Error, Result, Server};
+
+/// The number of entries returned if the `limit` param is not specified.
+const DEFAULT_LIMIT: usize = 100;
+
+pub(super) struct AuditHandle {
+    server: Server,
+}
+
+impl AuditHandle {
+    pub fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[crate::async_trait]
+impl super::service::HttpHandle for AuditHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let limit = match params.get("limit") {
+            Some(limit) => limit
+                .parse::<usize>()
+                .map_err(|_| Error::InvalidArgument("illegal limit".into()))?,
+            None => DEFAULT_LIMIT,
+        };
+        let entries = self.server.audit.recent(limit);
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&entries).unwrap())
+            .unwrap())
+    }
+}
@@ -41,7 +41,14 @@ impl super::service::HttpHandle for CordonHandle {
             .ok_or_else(|| crate::Error::InvalidArgument("node_id is required".into()))?
             .parse::<u64>()
             .map_err(|_| crate::Error::InvalidArgument("illegal node_id".into()))?;
-        self.server.root.cordon_node(node_id).await?;
+        let record = self
+            .server
+            .audit
+            .begin("admin", "cordon_node", node_id.to_string())
+            .await?;
+        let res = self.server.root.cordon_node(node_id).await;
+        record.finish(&res);
+        res?;
         Ok(http::Response::builder()
             .status(http::StatusCode::OK)
             .body("".to_owned())
@@ -71,7 +78,14 @@ impl super::service::HttpHandle for UncordonHandle {
             .ok_or_else(|| crate::Error::InvalidArgument("node_id is required".into()))?
             .parse::<u64>()
             .map_err(|_| crate::Error::InvalidArgument("illegal node_id".into()))?;
-        self.server.root.uncordon_node(node_id).await?;
+        let record = self
+            .server
+            .audit
+            .begin("admin", "uncordon_node", node_id.to_string())
+            .await?;
+        let res = self.server.root.uncordon_node(node_id).await;
+        record.finish(&res);
+        res?;
         Ok(http::Response::builder()
             .status(http::StatusCode::OK)
             .body("".to_owned())
@@ -101,7 +115,14 @@ impl super::service::HttpHandle for DrainHandle {
             .ok_or_else(|| crate::Error::InvalidArgument("node_id is required".into()))?
             .parse::<u64>()
             .map_err(|_| crate::Error::InvalidArgument("illegal node_id".into()))?;
-        self.server.root.begin_drain(node_id).await?;
+        let record = self
+            .server
+            .audit
+            .begin("admin", "drain_node", node_id.to_string())
+            .await?;
+        let res = self.server.root.begin_drain(node_id).await;
+        record.finish(&res);
+        res?;
         Ok(http::Response::builder()
             .status(http::StatusCode::OK)
             .body("".to_owned())
@@ -138,3 +159,29 @@ impl super::service::HttpHandle for StatusHandle {
             .unwrap())
     }
 }
+
+pub(super) struct RootLeaseHandle {
+    server: Server,
+}
+
+impl RootLeaseHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for RootLeaseHandle {
+    async fn call(&self, _: &str, _: &HashMap<String, String>) -> Result<http::Response<String>> {
+        let body = match self.server.root.lease() {
+            Some(lease) => {
+                json!({ "node_id": lease.node_id, "term": lease.term, "is_leader": true })
+            }
+            None => json!({ "node_id": self.server.root.current_node_id(), "is_leader": false }),
+        };
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(body.to_string())
+            .unwrap())
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use engula_api::server::v1::RaftRole;
+use serde_json::json;
+use tonic::codegen::*;
+
+use crate::{Error, Result, Server};
+
+/// Describe the replica and raft health of a group served by this node.
+pub(super) struct GroupHandle {
+    server: Server,
+}
+
+impl GroupHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for GroupHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let group_id = params
+            .get("group_id")
+            .ok_or_else(|| Error::InvalidArgument("group_id is required".into()))?
+            .parse::<u64>()
+            .map_err(|_| Error::InvalidArgument("illegal group_id".into()))?;
+
+        let replica = self
+            .server
+            .node
+            .replica_table()
+            .find(group_id)
+            .ok_or(Error::GroupNotFound(group_id))?;
+
+        let info = replica.replica_info();
+        let desc = replica.descriptor();
+        let state = replica.replica_state();
+        let role = RaftRole::from_i32(state.role).unwrap_or(RaftRole::Follower);
+        let health = replica.raft_node().health();
+        let group_engine = replica.group_engine();
+        let mut shards = Vec::with_capacity(desc.shards.len());
+        for shard in &desc.shards {
+            let size = group_engine.shard_size(shard.id)?;
+            shards.push(json!({
+                "id": shard.id,
+                "collection_id": shard.collection_id,
+                "size": size,
+            }));
+        }
+        let body = json!({
+            "group_id": group_id,
+            "replica_id": info.replica_id,
+            "epoch": desc.epoch,
+            "num_shards": desc.shards.len(),
+            "num_replicas": desc.replicas.len(),
+            "role": format!("{:?}", role).to_uppercase(),
+            "term": state.term,
+            "raft": health,
+            "shards": shards,
+            "engine": group_engine.properties()?.into_iter().collect::<HashMap<_, _>>(),
+        });
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(body.to_string())
+            .unwrap())
+    }
+}
@@ -16,6 +16,14 @@ use std::collections::HashMap;
 
 use tonic::codegen::*;
 
+use crate::{
+    node::lifecycle::LifecycleState, raftgroup::snap::progress::TransferDirection, Server,
+};
+
+/// The replicas lag behind more than this number of entries are considered catching up.
+const READY_APPLY_LAG: u64 = 1024;
+
+/// The liveness probe, it succeeds as long as the admin service is reachable.
 pub(super) struct HealthHandle;
 
 #[crate::async_trait]
@@ -31,3 +39,64 @@ impl super::service::HttpHandle for HealthHandle {
             .unwrap())
     }
 }
+
+/// The readiness probe, it succeeds only if the node is serving requests and its replicas are
+/// caught up, eg. no snapshot is receiving and the apply lags are small.
+pub(super) struct ReadyHandle {
+    server: Server,
+}
+
+impl ReadyHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+
+    fn not_ready_reason(&self) -> Option<String> {
+        let node = &self.server.node;
+        let state = node.lifecycle().state();
+        if state != LifecycleState::Serving {
+            return Some(format!("node is {state:?}"));
+        }
+
+        let receiving = node
+            .raft_manager()
+            .snapshot_manager()
+            .transfers()
+            .into_iter()
+            .filter(|t| t.direction == TransferDirection::Receive)
+            .count();
+        if receiving > 0 {
+            return Some(format!("{receiving} snapshots are receiving"));
+        }
+
+        for replica in node.replica_table().replicas() {
+            let apply_lag = replica.raft_node().health().apply_lag;
+            if apply_lag > READY_APPLY_LAG {
+                return Some(format!(
+                    "group {} is catching up, apply lag {apply_lag}",
+                    replica.replica_info().group_id
+                ));
+            }
+        }
+        None
+    }
+}
+
+#[crate::async_trait]
+impl super::service::HttpHandle for ReadyHandle {
+    async fn call(
+        &self,
+        _: &str,
+        _: &HashMap<String, String>,
+    ) -> crate::Result<http::Response<String>> {
+        let resp = match self.not_ready_reason() {
+            None => http::Response::builder()
+                .status(http::StatusCode::OK)
+                .body("Ok\n".to_owned()),
+            Some(reason) => http::Response::builder()
+                .status(http::StatusCode::SERVICE_UNAVAILABLE)
+                .body(format!("{reason}\n")),
+        };
+        Ok(resp.unwrap())
+    }
+}
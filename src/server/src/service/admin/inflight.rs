@@ -0,0 +1,61 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tonic::codegen::*;
+
+use crate::{Error, Result, Server};
+
+/// Dump the requests executing in replicas of this node, filtered by `group_id` if specified.
+pub(super) struct InflightHandle {
+    server: Server,
+}
+
+impl InflightHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for InflightHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let replica_table = self.server.node.replica_table();
+        let replicas = match params.get("group_id") {
+            Some(group_id) => {
+                let group_id = group_id
+                    .parse::<u64>()
+                    .map_err(|_| Error::InvalidArgument("illegal group_id".into()))?;
+                vec![replica_table
+                    .find(group_id)
+                    .ok_or(Error::GroupNotFound(group_id))?]
+            }
+            None => replica_table.replicas(),
+        };
+
+        let requests = replicas
+            .iter()
+            .flat_map(|r| r.inflight_requests())
+            .collect::<Vec<_>>();
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&requests).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
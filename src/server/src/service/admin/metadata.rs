@@ -38,23 +38,7 @@ impl super::service::HttpHandle for MetadataHandle {
         let info = match self.server.root.info().await {
             Ok(info) => serde_json::to_string(&info).unwrap(),
             Err(e @ crate::Error::NotRootLeader(..)) => {
-                let root_desc = self.server.node.get_root().await;
-                let node = root_desc.root_nodes.get(0);
-                if node.is_none() {
-                    return Err(e);
-                }
-                if node.as_ref().unwrap().id == self.server.root.current_node_id() {
-                    return Err(e);
-                }
-                let resp = http::Response::builder()
-                    .status(http::StatusCode::PERMANENT_REDIRECT)
-                    .header(
-                        http::header::LOCATION,
-                        format!("http://{}{}", node.unwrap().addr, path),
-                    )
-                    .body("".into())
-                    .unwrap();
-                return Ok(resp);
+                return redirect_to_root_leader(&self.server, path, e).await;
             }
             Err(e) => return Err(e),
         };
@@ -64,3 +48,25 @@ impl super::service::HttpHandle for MetadataHandle {
             .unwrap())
     }
 }
+
+/// Redirect the request to the first root node, which is likely the leader. The error is returned
+/// if the current node is the first root node.
+pub(super) async fn redirect_to_root_leader(
+    server: &Server,
+    path: &str,
+    err: crate::Error,
+) -> crate::Result<http::Response<String>> {
+    let root_desc = server.node.get_root().await;
+    let node = match root_desc.root_nodes.get(0) {
+        Some(node) if node.id != server.root.current_node_id() => node,
+        _ => return Err(err),
+    };
+    Ok(http::Response::builder()
+        .status(http::StatusCode::PERMANENT_REDIRECT)
+        .header(
+            http::header::LOCATION,
+            format!("http://{}{}", node.addr, path),
+        )
+        .body("".into())
+        .unwrap())
+}
@@ -29,19 +29,20 @@ lazy_static! {
 }
 
 pub(super) struct MetricsHandle {
+    server: Server,
     collector: RootCollector,
 }
 
 impl MetricsHandle {
     pub fn new(server: Server) -> Self {
-        let collector = RootCollector::new("", server);
+        let collector = RootCollector::new("", server.clone());
         match &prometheus::register(Box::new(collector.clone())) {
             Err(err) if matches!(err, prometheus::Error::AlreadyReg) => {}
             r => {
                 r.as_ref().unwrap();
             }
         }
-        Self { collector }
+        Self { server, collector }
     }
 }
 
@@ -60,6 +61,7 @@ impl super::service::HttpHandle for MetricsHandle {
     ) -> crate::Result<http::Response<String>> {
         METRICS_RPC_REQUESTS_TOTAL.inc();
         self.collector.try_refresh().await;
+        self.server.node.refresh_engine_metrics();
         let encoder = TextEncoder::new();
         let metric_families = prometheus::gather();
         let content = encoder
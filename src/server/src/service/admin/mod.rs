@@ -12,30 +12,50 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod audit;
 mod cluster;
+mod group;
 mod health;
+mod inflight;
 mod job;
 mod metadata;
 mod metrics;
 mod monitor;
+mod profile;
+mod schedule;
 mod service;
+mod snapshot;
+mod tasks;
+mod tombstone;
+mod topology;
 
 pub use self::service::AdminService;
 use self::service::Router;
 use crate::Server;
 
-pub fn make_admin_service(server: Server) -> AdminService {
+pub fn make_admin_service(server: Server, enable_pprof: bool) -> AdminService {
     let router = Router::empty()
         .route(
             "/metrics",
             self::metrics::MetricsHandle::new(server.to_owned()),
         )
+        .route("/audit", self::audit::AuditHandle::new(server.to_owned()))
         .route("/job", self::job::JobHandle::new(server.to_owned()))
+        .route(
+            "/schedule_history",
+            self::schedule::ScheduleHistoryHandle::new(server.to_owned()),
+        )
         .route(
             "/metadata",
             self::metadata::MetadataHandle::new(server.to_owned()),
         )
+        .route(
+            "/topology",
+            self::topology::TopologyHandle::new(server.to_owned()),
+        )
         .route("/health", self::health::HealthHandle)
+        .route("/ready", self::health::ReadyHandle::new(server.to_owned()))
+        .route("/tasks", self::tasks::TasksHandle)
         .route(
             "/cordon",
             self::cluster::CordonHandle::new(server.to_owned()),
@@ -49,7 +69,33 @@ pub fn make_admin_service(server: Server) -> AdminService {
             "/node_status",
             self::cluster::StatusHandle::new(server.to_owned()),
         )
-        .route("/monitor", self::monitor::MonitorHandle::new(server));
-    let api = Router::nest("/admin", router);
+        .route(
+            "/root_lease",
+            self::cluster::RootLeaseHandle::new(server.to_owned()),
+        )
+        .route("/group", self::group::GroupHandle::new(server.to_owned()))
+        .route(
+            "/inflight",
+            self::inflight::InflightHandle::new(server.to_owned()),
+        )
+        .route(
+            "/monitor",
+            self::monitor::MonitorHandle::new(server.to_owned()),
+        )
+        .route(
+            "/snapshot",
+            self::snapshot::SnapshotHandle::new(server.to_owned()),
+        )
+        .route(
+            "/purge_tombstones",
+            self::tombstone::PurgeTombstoneHandle::new(server.to_owned()),
+        );
+    let mut api = Router::nest("/admin", router);
+    if enable_pprof {
+        let router = Router::empty()
+            .route("/profile", self::profile::ProfileHandle::new(server))
+            .route("/heap", self::profile::HeapProfileHandle);
+        api = api.merge(Router::nest("/debug/pprof", router));
+    }
     AdminService::new(api)
 }
@@ -0,0 +1,164 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, time::Duration};
+
+use prost::Message;
+use tonic::codegen::*;
+
+use crate::{Error, Result, Server};
+
+const DEFAULT_PROFILE_SECONDS: u64 = 10;
+const MAX_PROFILE_SECONDS: u64 = 300;
+const DEFAULT_PROFILE_FREQUENCY: i32 = 99;
+
+/// Samples the CPU of the node for `seconds`, and responds a flamegraph in SVG, or a pprof
+/// protobuf if `format=proto` is specified.
+pub(super) struct ProfileHandle {
+    server: Server,
+}
+
+impl ProfileHandle {
+    pub fn new(server: Server) -> Self {
+        ProfileHandle { server }
+    }
+
+    async fn profile(&self, params: &HashMap<String, String>) -> Result<pprof::Report> {
+        let seconds = parse_param(params, "seconds", DEFAULT_PROFILE_SECONDS)?;
+        if seconds == 0 || seconds > MAX_PROFILE_SECONDS {
+            return Err(Error::InvalidArgument(format!(
+                "seconds should be in range [1, {MAX_PROFILE_SECONDS}]"
+            )));
+        }
+        let frequency = parse_param(params, "frequency", DEFAULT_PROFILE_FREQUENCY)?;
+        if frequency <= 0 {
+            return Err(Error::InvalidArgument(
+                "frequency should be positive".into(),
+            ));
+        }
+
+        self.server
+            .node
+            .executor()
+            .spawn_blocking(move || {
+                let guard = pprof::ProfilerGuardBuilder::default()
+                    .frequency(frequency)
+                    .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+                    .build()
+                    .map_err(profile_error)?;
+                std::thread::sleep(Duration::from_secs(seconds));
+                guard.report().build().map_err(profile_error)
+            })
+            .await
+    }
+}
+
+#[crate::async_trait]
+impl super::service::HttpHandle for ProfileHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let report = self.profile(params).await?;
+        let mut body = Vec::new();
+        report.flamegraph(&mut body).map_err(profile_error)?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, "image/svg+xml")
+            .body(String::from_utf8_lossy(&body).into_owned())
+            .unwrap())
+    }
+
+    async fn call_bytes(
+        &self,
+        path: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<Vec<u8>>> {
+        if params.get("format").map(String::as_str) != Some("proto") {
+            return Ok(self.call(path, params).await?.map(String::into_bytes));
+        }
+
+        let report = self.profile(params).await?;
+        let profile = report.pprof().map_err(profile_error)?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, "application/octet-stream")
+            .body(profile.encode_to_vec())
+            .unwrap())
+    }
+}
+
+/// Dumps the heap profile of jemalloc in text, which could be analyzed by `jeprof`. It requires the
+/// `jemalloc` feature, and the process should be started with `MALLOC_CONF=prof:true`.
+pub(super) struct HeapProfileHandle;
+
+#[crate::async_trait]
+impl super::service::HttpHandle for HeapProfileHandle {
+    async fn call(&self, _: &str, _: &HashMap<String, String>) -> Result<http::Response<String>> {
+        let profile = dump_heap_profile()?;
+        let profile =
+            String::from_utf8(profile).map_err(|_| Error::InvalidData("heap profile".into()))?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(profile)
+            .unwrap())
+    }
+}
+
+#[cfg(feature = "jemalloc")]
+fn dump_heap_profile() -> Result<Vec<u8>> {
+    use std::ffi::CString;
+
+    use tikv_jemalloc_ctl::raw;
+
+    let enabled = unsafe { raw::read::<bool>(b"opt.prof\0") }.map_err(profile_error)?;
+    if !enabled {
+        return Err(Error::InvalidArgument(
+            "heap profiling is disabled, start the node with MALLOC_CONF=prof:true".into(),
+        ));
+    }
+
+    let path = std::env::temp_dir().join(format!("engula-heap-{}.prof", uuid::Uuid::new_v4()));
+    let c_path = CString::new(path.to_string_lossy().as_bytes())
+        .map_err(|e| Error::InvalidArgument(e.to_string()))?;
+    unsafe { raw::write(b"prof.dump\0", c_path.as_ptr()) }.map_err(profile_error)?;
+    let profile = std::fs::read(&path);
+    let _ = std::fs::remove_file(&path);
+    Ok(profile?)
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn dump_heap_profile() -> Result<Vec<u8>> {
+    Err(Error::InvalidArgument(
+        "heap profiling requires the `jemalloc` feature".into(),
+    ))
+}
+
+fn parse_param<T: std::str::FromStr>(
+    params: &HashMap<String, String>,
+    name: &str,
+    default_value: T,
+) -> Result<T> {
+    match params.get(name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| Error::InvalidArgument(format!("invalid {name} {value}"))),
+        None => Ok(default_value),
+    }
+}
+
+fn profile_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> Error {
+    Error::Io(std::io::Error::new(std::io::ErrorKind::Other, err))
+}
@@ -20,7 +20,7 @@ use std::{
 
 use tonic::{
     body::BoxBody,
-    codegen::{empty_body, http, BoxFuture, Service},
+    codegen::{empty_body, http, BoxFuture, Bytes, Service},
     transport::NamedService,
 };
 
@@ -31,6 +31,15 @@ pub(super) trait HttpHandle: Send + Sync {
         path: &str,
         params: &HashMap<String, String>,
     ) -> crate::Result<http::Response<String>>;
+
+    /// Like `call`, but for handles whose response body is not UTF-8 text.
+    async fn call_bytes(
+        &self,
+        path: &str,
+        params: &HashMap<String, String>,
+    ) -> crate::Result<http::Response<Vec<u8>>> {
+        Ok(self.call(path, params).await?.map(String::into_bytes))
+    }
 }
 
 pub(super) struct Router {
@@ -112,6 +121,11 @@ impl Router {
         Router { handles }
     }
 
+    pub fn merge(mut self, r: Router) -> Self {
+        self.handles.extend(r.handles);
+        self
+    }
+
     pub fn route(mut self, path: &str, handle: impl HttpHandle + 'static) -> Self {
         if path.is_empty() || !path.starts_with('/') {
             panic!("Paths must start with a `/`");
@@ -137,11 +151,11 @@ impl Router {
             }
         };
 
-        let resp = match handle.call(path, &params).await {
+        let resp = match handle.call_bytes(path, &params).await {
             Ok(resp) => resp.map(boxed),
             Err(e) => http::Response::builder()
                 .status(http::StatusCode::INTERNAL_SERVER_ERROR)
-                .body(boxed(e.to_string()))
+                .body(boxed(e.to_string().into_bytes()))
                 .unwrap(),
         };
 
@@ -149,8 +163,10 @@ impl Router {
     }
 }
 
-fn boxed(body: String) -> BoxBody {
-    use http_body::Body;
+fn boxed(body: Vec<u8>) -> BoxBody {
+    use http_body::{Body, Full};
 
-    body.map_err(|_| panic!("")).boxed_unsync()
+    Full::<Bytes>::from(body)
+        .map_err(|_| panic!(""))
+        .boxed_unsync()
 }
@@ -0,0 +1,47 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tonic::codegen::*;
+
+use crate::{Result, Server};
+
+/// Dump the progress of the ongoing snapshot transfers of this node.
+pub(super) struct SnapshotHandle {
+    server: Server,
+}
+
+impl SnapshotHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for SnapshotHandle {
+    async fn call(&self, _: &str, _: &HashMap<String, String>) -> Result<http::Response<String>> {
+        let transfers = self
+            .server
+            .node
+            .raft_manager()
+            .snapshot_manager()
+            .transfers();
+        let info = serde_json::json!({ "transfers": transfers });
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(info.to_string())
+            .unwrap())
+    }
+}
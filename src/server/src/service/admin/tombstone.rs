@@ -0,0 +1,81 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, time::Duration};
+
+use tonic::codegen::*;
+
+use crate::{Result, Server};
+
+/// The default safety window of purging the tombstones of the removed replicas.
+const DEFAULT_SAFETY_WINDOW_SEC: u64 = 7 * 24 * 60 * 60;
+
+/// Purge the tombstones of the destroyed replicas of this node, which are removed longer than
+/// `safety_window_sec` ago.
+pub(super) struct PurgeTombstoneHandle {
+    server: Server,
+}
+
+impl PurgeTombstoneHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for PurgeTombstoneHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let safety_window_sec = match params.get("safety_window_sec") {
+            Some(value) => value
+                .parse::<u64>()
+                .map_err(|_| crate::Error::InvalidArgument("illegal safety_window_sec".into()))?,
+            None => DEFAULT_SAFETY_WINDOW_SEC,
+        };
+        let record = self
+            .server
+            .audit
+            .begin(
+                "admin",
+                "purge_tombstones",
+                format!("safety_window_sec={safety_window_sec}"),
+            )
+            .await?;
+        let res = self
+            .server
+            .node
+            .purge_replica_tombstones(Duration::from_secs(safety_window_sec))
+            .await;
+        record.finish(&res);
+        let purged = res?
+            .into_iter()
+            .map(|t| {
+                serde_json::json!({
+                    "group_id": t.group_id,
+                    "replica_id": t.replica_id,
+                    "term": t.term,
+                    "removed_at": t.removed_at,
+                })
+            })
+            .collect::<Vec<_>>();
+        let info = serde_json::json!({ "purged": purged });
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(info.to_string())
+            .unwrap())
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tonic::codegen::*;
+
+use super::metadata::redirect_to_root_leader;
+use crate::Server;
+
+/// Returns the topology of the cluster as a JSON document. The `ETag` header carries the version
+/// of the document; if the `version` param equals to it, `304 Not Modified` is returned without
+/// body, so that dashboards could poll it cheaply.
+pub(super) struct TopologyHandle {
+    server: Server,
+}
+
+impl TopologyHandle {
+    pub fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[crate::async_trait]
+impl super::service::HttpHandle for TopologyHandle {
+    async fn call(
+        &self,
+        path: &str,
+        params: &HashMap<String, String>,
+    ) -> crate::Result<http::Response<String>> {
+        let body = match self.server.root.topology().await {
+            Ok(topology) => serde_json::to_string(&topology).unwrap(),
+            Err(e @ crate::Error::NotRootLeader(..)) => {
+                return redirect_to_root_leader(&self.server, path, e).await;
+            }
+            Err(e) => return Err(e),
+        };
+        let version = format!("{:08x}", crc32fast::hash(body.as_bytes()));
+        let builder =
+            http::Response::builder().header(http::header::ETAG, format!("\"{version}\""));
+        if params.get("version") == Some(&version) {
+            return Ok(builder
+                .status(http::StatusCode::NOT_MODIFIED)
+                .body("".into())
+                .unwrap());
+        }
+        Ok(builder
+            .status(http::StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .unwrap())
+    }
+}
@@ -0,0 +1,420 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use engula_client::{AppError, AppResult, Collection, EngulaClient};
+use http_body::Body as _;
+use serde_json::json;
+use tonic::{
+    body::BoxBody,
+    codegen::{http, BoxFuture, Service},
+    transport::{server::TcpConnectInfo, Body, NamedService},
+};
+
+use super::{proxy::mutation_target, ProxyServer};
+use crate::audit::AuditLog;
+
+/// A HTTP/JSON front-end of the proxy service, for the environments where gRPC clients are
+/// inconvenient, eg. curl and serverless functions.
+///
+/// - `GET /v1/db/{db}/collection/{co}/key/{key}` returns the value in the response body.
+/// - `PUT /v1/db/{db}/collection/{co}/key/{key}` saves the request body as the value.
+/// - `DELETE /v1/db/{db}/collection/{co}/key/{key}` deletes the key.
+/// - `GET /v1/db/{db}/collection/{co}/scan?start=..&end=..&limit=..` returns the key-value pairs in
+///   `[start, end)` as `{"kvs": [{"key": "..", "value": ".."}], "next": ".."}`, where `next` is the
+///   start of the following page, or null if the range is exhausted.
+///
+/// The path segments and query values are percent-decoded, and the keys and values in the scan
+/// responses are percent-encoded, so binary keys are supported. Errors are returned as JSON
+/// objects like `{"error": "..."}`.
+///
+/// If any auth tokens are configured, requests must carry one of them in an
+/// `Authorization: Bearer <token>` header, otherwise 401 is returned.
+#[derive(Clone)]
+pub struct HttpProxyService {
+    client: EngulaClient,
+    audit: Arc<AuditLog>,
+    auth_tokens: Arc<Vec<String>>,
+    /// The opened collections, so that serving a request doesn't need to resolve the database
+    /// and collection from root. An entry is evicted once a request on it fails, in case the
+    /// collection is dropped or recreated.
+    collections: Arc<Mutex<HashMap<(String, String), Collection>>>,
+}
+
+/// The number of key-value pairs returned by a scan request without `limit`.
+const DEFAULT_SCAN_LIMIT: usize = 100;
+
+/// The max number of key-value pairs returned by a scan request.
+const MAX_SCAN_LIMIT: usize = 1000;
+
+enum Route {
+    Key(String, String, Vec<u8>),
+    Scan(String, String),
+}
+
+impl HttpProxyService {
+    pub fn new(proxy_server: ProxyServer, auth_tokens: Vec<String>) -> Self {
+        HttpProxyService {
+            client: proxy_server.client,
+            audit: proxy_server.audit,
+            auth_tokens: Arc::new(auth_tokens),
+            collections: Arc::default(),
+        }
+    }
+
+    async fn open_collection(&self, db: &str, co: &str) -> AppResult<Collection> {
+        let name = (db.to_owned(), co.to_owned());
+        if let Some(collection) = self.collections.lock().unwrap().get(&name) {
+            return Ok(collection.clone());
+        }
+        let collection = self
+            .client
+            .open_database(name.0.clone())
+            .await?
+            .open_collection(name.1.clone())
+            .await?;
+        self.collections
+            .lock()
+            .unwrap()
+            .insert(name, collection.clone());
+        Ok(collection)
+    }
+
+    fn evict_collection(&self, db: &str, co: &str) {
+        self.collections
+            .lock()
+            .unwrap()
+            .remove(&(db.to_owned(), co.to_owned()));
+    }
+
+    fn is_authorized<B>(&self, req: &http::Request<B>) -> bool {
+        if self.auth_tokens.is_empty() {
+            return true;
+        }
+        req.headers()
+            .get(http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| self.auth_tokens.iter().any(|t| t == token))
+            .unwrap_or_default()
+    }
+}
+
+impl Service<http::Request<Body>> for HttpProxyService {
+    type Response = http::Response<BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            let resp = match handle(&service, req).await {
+                Ok(resp) => resp,
+                Err(err) => error_response(err),
+            };
+            Ok(resp)
+        })
+    }
+}
+
+impl NamedService for HttpProxyService {
+    const NAME: &'static str = "v1";
+}
+
+async fn handle(
+    service: &HttpProxyService,
+    req: http::Request<Body>,
+) -> AppResult<http::Response<BoxBody>> {
+    if !service.is_authorized(&req) {
+        let body = json!({ "error": "unauthorized" });
+        return Ok(response(
+            http::StatusCode::UNAUTHORIZED,
+            body.to_string().into_bytes(),
+        ));
+    }
+
+    let principal = req
+        .extensions()
+        .get::<TcpConnectInfo>()
+        .and_then(|info| info.remote_addr())
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
+    let path = req.uri().path();
+    let route = parse_path(path).ok_or_else(|| AppError::NotFound(format!("path {path}")))?;
+    let method = req.method().clone();
+    let (db, co, key) = match route {
+        Route::Key(db, co, key) => {
+            if method != http::Method::GET
+                && method != http::Method::PUT
+                && method != http::Method::DELETE
+            {
+                return Ok(response(http::StatusCode::METHOD_NOT_ALLOWED, vec![]));
+            }
+            (db, co, Some(key))
+        }
+        Route::Scan(db, co) => {
+            if method != http::Method::GET {
+                return Ok(response(http::StatusCode::METHOD_NOT_ALLOWED, vec![]));
+            }
+            (db, co, None)
+        }
+    };
+
+    let collection = service.open_collection(&db, &co).await?;
+    let res = match key {
+        Some(key) => handle_key(&service.audit, &principal, &collection, method, key, req).await,
+        None => handle_scan(&collection, req.uri().query().unwrap_or_default()).await,
+    };
+    if res.is_err() {
+        service.evict_collection(&db, &co);
+    }
+    res
+}
+
+async fn handle_key(
+    audit: &AuditLog,
+    principal: &str,
+    collection: &Collection,
+    method: http::Method,
+    key: Vec<u8>,
+    req: http::Request<Body>,
+) -> AppResult<http::Response<BoxBody>> {
+    match method {
+        http::Method::GET => match collection.get(key).await? {
+            Some(value) => Ok(response(http::StatusCode::OK, value)),
+            None => Ok(error_response(AppError::NotFound("key".to_owned()))),
+        },
+        http::Method::PUT => {
+            let value = read_body(req.into_body()).await?;
+            let target = mutation_target(&collection.desc(), &key);
+            let record = audit
+                .begin_data_mutation(principal, "put", target)
+                .await
+                .map_err(|err| AppError::Internal(Box::new(err)))?;
+            let res = collection.put(key, value).await;
+            record.finish(&res);
+            res?;
+            Ok(response(http::StatusCode::OK, vec![]))
+        }
+        _ => {
+            let target = mutation_target(&collection.desc(), &key);
+            let record = audit
+                .begin_data_mutation(principal, "delete", target)
+                .await
+                .map_err(|err| AppError::Internal(Box::new(err)))?;
+            let res = collection.delete(key).await;
+            record.finish(&res);
+            res?;
+            Ok(response(http::StatusCode::OK, vec![]))
+        }
+    }
+}
+
+async fn handle_scan(collection: &Collection, query: &str) -> AppResult<http::Response<BoxBody>> {
+    let (start, end, limit) = parse_scan_query(query)
+        .ok_or_else(|| AppError::InvalidArgument(format!("scan query {query}")))?;
+    let kvs = collection.scan(start, end, limit).await?;
+    let next = if kvs.len() == limit {
+        // The smallest key greater than the last returned one.
+        kvs.last().map(|(key, _)| {
+            let mut next = key.clone();
+            next.push(0);
+            percent_encode(&next)
+        })
+    } else {
+        None
+    };
+    let kvs = kvs
+        .iter()
+        .map(|(key, value)| json!({ "key": percent_encode(key), "value": percent_encode(value) }))
+        .collect::<Vec<_>>();
+    let body = json!({ "kvs": kvs, "next": next });
+    Ok(response(
+        http::StatusCode::OK,
+        body.to_string().into_bytes(),
+    ))
+}
+
+async fn read_body(mut body: Body) -> AppResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| AppError::InvalidArgument(format!("read body: {e}")))?;
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf)
+}
+
+fn error_response(err: AppError) -> http::Response<BoxBody> {
+    let status = match &err {
+        AppError::NotFound(_) => http::StatusCode::NOT_FOUND,
+        AppError::AlreadyExists(_) => http::StatusCode::CONFLICT,
+        AppError::ConditionNotMet(_) => http::StatusCode::PRECONDITION_FAILED,
+        AppError::InvalidArgument(_) => http::StatusCode::BAD_REQUEST,
+        AppError::DeadlineExceeded(_) => http::StatusCode::GATEWAY_TIMEOUT,
+        AppError::KeyTooLarge(..) | AppError::ValueTooLarge(..) | AppError::BatchTooLarge(..) => {
+            http::StatusCode::PAYLOAD_TOO_LARGE
+        }
+        AppError::Network(_) | AppError::Internal(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let body = json!({ "error": err.to_string() });
+    response(status, body.to_string().into_bytes())
+}
+
+fn response(status: http::StatusCode, body: Vec<u8>) -> http::Response<BoxBody> {
+    let body = Body::from(body)
+        .map_err(|e| tonic::Status::internal(e.to_string()))
+        .boxed_unsync();
+    http::Response::builder().status(status).body(body).unwrap()
+}
+
+/// Parse `/v1/db/{db}/collection/{co}/key/{key}` and `/v1/db/{db}/collection/{co}/scan`.
+fn parse_path(path: &str) -> Option<Route> {
+    let segments = path.strip_prefix("/v1/")?.split('/').collect::<Vec<_>>();
+    match segments.as_slice() {
+        ["db", db, "collection", co, "key", key] => {
+            let db = String::from_utf8(percent_decode(db)?).ok()?;
+            let co = String::from_utf8(percent_decode(co)?).ok()?;
+            Some(Route::Key(db, co, percent_decode(key)?))
+        }
+        ["db", db, "collection", co, "scan"] => {
+            let db = String::from_utf8(percent_decode(db)?).ok()?;
+            let co = String::from_utf8(percent_decode(co)?).ok()?;
+            Some(Route::Scan(db, co))
+        }
+        _ => None,
+    }
+}
+
+/// Parse the `start`, `end` and `limit` of a scan request, the absent `start` and `end` mean
+/// the begin and the end of the collection.
+fn parse_scan_query(query: &str) -> Option<(Vec<u8>, Vec<u8>, usize)> {
+    let mut start = vec![];
+    let mut end = vec![];
+    let mut limit = DEFAULT_SCAN_LIMIT;
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match name {
+            "start" => start = percent_decode(value)?,
+            "end" => end = percent_decode(value)?,
+            "limit" => limit = value.parse().ok()?,
+            _ => return None,
+        }
+    }
+    if limit == 0 || limit > MAX_SCAN_LIMIT {
+        return None;
+    }
+    Some((start, end, limit))
+}
+
+fn percent_encode(input: &[u8]) -> String {
+    let mut buf = String::with_capacity(input.len());
+    for &b in input {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            buf.push(b as char);
+        } else {
+            buf.push_str(&format!("%{b:02X}"));
+        }
+    }
+    buf
+}
+
+fn percent_decode(input: &str) -> Option<Vec<u8>> {
+    let bytes = input.as_bytes();
+    let mut buf = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            let hex = std::str::from_utf8(hex).ok()?;
+            buf.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            buf.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_key_path(path: &str) -> Option<(String, String, Vec<u8>)> {
+        match parse_path(path)? {
+            Route::Key(db, co, key) => Some((db, co, key)),
+            Route::Scan(..) => None,
+        }
+    }
+
+    #[test]
+    fn parse_data_path() {
+        assert_eq!(
+            parse_key_path("/v1/db/db1/collection/co1/key/a%00%ffb"),
+            Some(("db1".to_owned(), "co1".to_owned(), b"a\x00\xffb".to_vec()))
+        );
+        assert_eq!(
+            parse_key_path("/v1/db/db%201/collection/co/key/"),
+            Some(("db 1".to_owned(), "co".to_owned(), vec![]))
+        );
+        assert!(parse_path("/v1/db/db1/collection/co1").is_none());
+        assert!(parse_path("/v1/db/db1/collection/co1/key/a/b").is_none());
+        assert!(parse_path("/v1/db/db1/collection/co1/key/%f").is_none());
+        assert!(parse_path("/v1/db/db1/collection/co1/key/%+f").is_none());
+        assert!(parse_path("/admin/metrics").is_none());
+        assert!(matches!(
+            parse_path("/v1/db/db1/collection/co%2F1/scan"),
+            Some(Route::Scan(db, co)) if db == "db1" && co == "co/1"
+        ));
+        assert!(parse_path("/v1/db/db1/collection/co1/scan/a").is_none());
+    }
+
+    #[test]
+    fn parse_scan_query_params() {
+        assert_eq!(
+            parse_scan_query(""),
+            Some((vec![], vec![], DEFAULT_SCAN_LIMIT))
+        );
+        assert_eq!(
+            parse_scan_query("start=a%00&end=b&limit=10"),
+            Some((b"a\x00".to_vec(), b"b".to_vec(), 10))
+        );
+        assert_eq!(parse_scan_query("limit=0"), None);
+        assert_eq!(
+            parse_scan_query(&format!("limit={}", MAX_SCAN_LIMIT + 1)),
+            None
+        );
+        assert_eq!(parse_scan_query("limit=x"), None);
+        assert_eq!(parse_scan_query("prefix=a"), None);
+    }
+
+    #[test]
+    fn percent_encode_roundtrip() {
+        let input = b"a b/\x00\xff-_.~".to_vec();
+        assert_eq!(percent_encode(&input), "a%20b%2F%00%FF-_.~");
+        assert_eq!(percent_decode(&percent_encode(&input)), Some(input));
+    }
+}
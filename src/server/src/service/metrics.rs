@@ -22,7 +22,11 @@ make_static_metric! {
             get,
             put,
             delete,
+            delete_range,
+            rename,
             list,
+            scan,
+            sample,
             transfer,
             batch_write,
             accept_shard,
@@ -36,7 +40,11 @@ make_static_metric! {
             get,
             put,
             delete,
+            delete_range,
+            rename,
             list,
+            scan,
+            sample,
             transfer,
             batch_write,
             accept_shard,
@@ -85,10 +93,26 @@ pub fn take_group_request_metrics(request: &GroupRequest) -> Option<&'static His
             NODE_SERVICE_GROUP_REQUEST_TOTAL.delete.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.delete)
         }
+        Some(Request::DeleteRange(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.delete_range.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.delete_range)
+        }
+        Some(Request::Rename(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.rename.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.rename)
+        }
         Some(Request::PrefixList(_)) => {
             NODE_SERVICE_GROUP_REQUEST_TOTAL.list.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.list)
         }
+        Some(Request::Scan(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.scan.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.scan)
+        }
+        Some(Request::Sample(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.sample.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.sample)
+        }
         Some(Request::BatchWrite(_)) => {
             NODE_SERVICE_GROUP_REQUEST_TOTAL.batch_write.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.batch_write)
@@ -176,6 +200,8 @@ simple_node_method!(root_heartbeat);
 simple_node_method!(migrate);
 simple_node_method!(pull);
 simple_node_method!(forward);
+simple_node_method!(collect_checksum);
+simple_node_method!(watch_key);
 
 macro_rules! simple_root_method {
     ($name: ident) => {
@@ -12,8 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 pub mod admin;
+pub mod http_proxy;
 mod metrics;
 pub mod node;
+pub mod pgwire;
 pub mod proxy;
 pub mod raft;
 pub mod root;
@@ -23,8 +25,10 @@ use std::{sync::Arc, time::Duration};
 use engula_client::{ClientOptions, EngulaClient};
 
 use crate::{
+    audit::AuditLog,
     node::{resolver::AddressResolver, Node},
     root::Root,
+    runtime::Executor,
     Provider,
 };
 
@@ -33,18 +37,21 @@ pub struct Server {
     pub node: Arc<Node>,
     pub root: Root,
     pub address_resolver: Arc<AddressResolver>,
+    pub audit: Arc<AuditLog>,
 }
 
 #[derive(Clone)]
 pub struct ProxyServer {
     pub client: engula_client::EngulaClient,
+    pub executor: Executor,
+    pub audit: Arc<AuditLog>,
 }
 
 impl ProxyServer {
     pub(crate) fn new(provider: &Provider) -> Self {
         let opts = ClientOptions {
             connect_timeout: Some(Duration::from_millis(250)),
-            timeout: None,
+            ..Default::default()
         };
         ProxyServer {
             client: EngulaClient::build(
@@ -53,6 +60,8 @@ impl ProxyServer {
                 provider.root_client.clone(),
                 provider.conn_manager.clone(),
             ),
+            executor: provider.executor.clone(),
+            audit: provider.audit.clone(),
         }
     }
 }
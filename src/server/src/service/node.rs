@@ -12,12 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::{Duration, Instant};
+
 use engula_api::server::v1::*;
 use tonic::{Request, Response, Status};
 
 use super::metrics::*;
 use crate::{
-    node::migrate::ShardChunkStream,
+    node::{memory::Subsystem, migrate::ShardChunkStream, watch::KeyWatchStream},
     record_latency, record_latency_opt,
     runtime::{DispatchHandle, TaskPriority},
     Error, Server,
@@ -26,13 +28,19 @@ use crate::{
 #[tonic::async_trait]
 impl node_server::Node for Server {
     type PullStream = ShardChunkStream;
+    type WatchKeyStream = KeyWatchStream;
 
     async fn batch(
         &self,
         request: Request<BatchRequest>,
     ) -> Result<Response<BatchResponse>, Status> {
+        let deadline = request_deadline(&request);
         let batch_request = request.into_inner();
         record_latency!(take_batch_request_metrics(&batch_request));
+        let _guard = self.node.memory_tracker().track(
+            Subsystem::ConnectionBuffer,
+            prost::Message::encoded_len(&batch_request),
+        );
         if batch_request.requests.len() == 1 {
             let request = batch_request
                 .requests
@@ -41,12 +49,13 @@ impl node_server::Node for Server {
                 .expect("already checked");
             let server = self.clone();
             let response =
-                Box::pin(async move { server.submit_group_request(&request).await }).await;
+                Box::pin(async move { server.submit_group_request(&request, deadline).await })
+                    .await;
             Ok(Response::new(BatchResponse {
                 responses: vec![response],
             }))
         } else {
-            let handles = self.submit_group_requests(batch_request.requests);
+            let handles = self.submit_group_requests(batch_request.requests, deadline);
             let mut responses = Vec::with_capacity(handles.len());
             for handle in handles {
                 responses.push(handle.await);
@@ -167,6 +176,26 @@ impl node_server::Node for Server {
         let resp = self.node.forward(req).await?;
         Ok(Response::new(resp))
     }
+
+    async fn collect_checksum(
+        &self,
+        request: Request<CollectChecksumRequest>,
+    ) -> Result<Response<CollectChecksumResponse>, Status> {
+        record_latency!(take_collect_checksum_request_metrics());
+        let req = request.into_inner();
+        let resp = self.node.collect_checksum(&req)?;
+        Ok(Response::new(resp))
+    }
+
+    async fn watch_key(
+        &self,
+        request: Request<WatchKeyRequest>,
+    ) -> Result<Response<Self::WatchKeyStream>, Status> {
+        record_latency!(take_watch_key_request_metrics());
+        let req = request.into_inner();
+        let stream = self.node.watch_key(req)?;
+        Ok(Response::new(stream))
+    }
 }
 
 impl Server {
@@ -174,13 +203,20 @@ impl Server {
         if let Some(root) = req.root {
             self.node.update_root(root).await?;
         }
+        if let Some(features) = req.features {
+            self.node.update_features(features).await?;
+        }
         Ok(SyncRootResponse {})
     }
 
-    async fn submit_group_request(&self, request: &GroupRequest) -> GroupResponse {
+    async fn submit_group_request(
+        &self,
+        request: &GroupRequest,
+        deadline: Option<Instant>,
+    ) -> GroupResponse {
         record_latency_opt!(take_group_request_metrics(request));
         self.node
-            .execute_request(request)
+            .execute_request(request, deadline)
             .await
             .unwrap_or_else(error_to_response)
     }
@@ -188,6 +224,7 @@ impl Server {
     fn submit_group_requests(
         &self,
         requests: Vec<GroupRequest>,
+        deadline: Option<Instant>,
     ) -> Vec<DispatchHandle<GroupResponse>> {
         let mut handles = Vec::with_capacity(requests.len());
         for request in requests.into_iter() {
@@ -196,7 +233,7 @@ impl Server {
             let handle = self.node.executor().dispatch(
                 Some(task_tag.as_slice()),
                 TaskPriority::Middle,
-                async move { server.submit_group_request(&request).await },
+                async move { server.submit_group_request(&request, deadline).await },
             );
             handles.push(handle);
         }
@@ -204,6 +241,23 @@ impl Server {
     }
 }
 
+/// Parse the deadline of request from the `grpc-timeout` header.
+fn request_deadline<T>(request: &Request<T>) -> Option<Instant> {
+    let timeout = request.metadata().get("grpc-timeout")?.to_str().ok()?;
+    let (value, unit) = timeout.split_at(timeout.len().checked_sub(1)?);
+    let value = value.parse::<u64>().ok()?;
+    let timeout = match unit {
+        "H" => Duration::from_secs(value * 60 * 60),
+        "M" => Duration::from_secs(value * 60),
+        "S" => Duration::from_secs(value),
+        "m" => Duration::from_millis(value),
+        "u" => Duration::from_micros(value),
+        "n" => Duration::from_nanos(value),
+        _ => return None,
+    };
+    Some(Instant::now() + timeout)
+}
+
 fn error_to_response(err: Error) -> GroupResponse {
     GroupResponse {
         response: None,
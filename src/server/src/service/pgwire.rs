@@ -0,0 +1,731 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An experimental, read-only Postgres wire protocol façade, so that the collections could be
+//! inspected with psql and BI tools.
+//!
+//! Each collection is exposed as the table `{database}.{collection}` with the columns
+//! `key bytea`, `value bytea` and `version bigint`. Only the simple query protocol and the
+//! statements like the following are supported, the key predicates are translated to the range
+//! of a collection scan:
+//!
+//! ```sql
+//! SELECT key, value FROM db.co WHERE key >= 'a' AND key < '\x62' LIMIT 10;
+//! ```
+//!
+//! The literals starting with `\x` are decoded as hex bytea, the others are taken as UTF-8
+//! bytes.
+
+use std::sync::Arc;
+
+use engula_client::{AppError, Collection};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{debug, warn};
+
+use super::ProxyServer;
+use crate::{runtime::TaskPriority, Error, Result};
+
+const PROTOCOL_VERSION_3: i32 = 196608;
+const SSL_REQUEST_CODE: i32 = 80877103;
+const GSSENC_REQUEST_CODE: i32 = 80877104;
+const CANCEL_REQUEST_CODE: i32 = 80877102;
+
+/// The max size of a startup packet or a frontend message.
+const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+/// The number of rows read from the collection by each scan.
+const SCAN_PAGE_SIZE: usize = 1000;
+
+const BYTEA_OID: i32 = 17;
+const INT8_OID: i32 = 20;
+
+#[derive(Clone)]
+pub struct PgWireServer {
+    proxy_server: ProxyServer,
+    auth_tokens: Arc<Vec<String>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Column {
+    Key,
+    Value,
+    Version,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Select {
+    columns: Vec<Column>,
+    database: String,
+    collection: String,
+    /// The range of keys `[start, end)`, an empty `end` means the end of the collection.
+    start: Vec<u8>,
+    end: Vec<u8>,
+    limit: Option<usize>,
+}
+
+/// An error reported to the client with the SQLSTATE code.
+#[derive(Debug, PartialEq, Eq)]
+struct PgError {
+    code: &'static str,
+    message: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(usize),
+    Symbol(&'static str),
+}
+
+impl PgWireServer {
+    /// Serve the pgwire connections with the client of the proxy service. If any auth tokens
+    /// are configured, one of them is required as the password.
+    pub fn new(proxy_server: ProxyServer, auth_tokens: Vec<String>) -> Self {
+        PgWireServer {
+            proxy_server,
+            auth_tokens: Arc::new(auth_tokens),
+        }
+    }
+
+    /// Accept the connections of the listener in background.
+    pub fn spawn(self, listener: TcpListener) {
+        let executor = self.proxy_server.executor.clone();
+        executor.spawn(None, TaskPriority::Middle, async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(err) => {
+                        warn!("pgwire accept connection: {err:?}");
+                        continue;
+                    }
+                };
+                let server = self.clone();
+                self.proxy_server
+                    .executor
+                    .spawn(None, TaskPriority::Low, async move {
+                        if let Err(err) = server.serve_conn(stream).await {
+                            debug!("pgwire connection {peer} is closed: {err:?}");
+                        }
+                    });
+            }
+        });
+    }
+
+    async fn serve_conn(&self, mut stream: TcpStream) -> Result<()> {
+        stream.set_nodelay(true)?;
+        if !self.startup(&mut stream).await? {
+            return Ok(());
+        }
+
+        let mut buf = Vec::new();
+        let mut ignore_till_sync = false;
+        loop {
+            let tag = match stream.read_u8().await {
+                Ok(tag) => tag,
+                Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(err) => return Err(err.into()),
+            };
+            let body = read_body(&mut stream).await?;
+            match tag {
+                b'Q' => {
+                    let sql = read_cstr(&body)?;
+                    if let Err(err) = self.query(&mut stream, &mut buf, &sql).await {
+                        write_error(&mut buf, &err);
+                    }
+                    write_ready_for_query(&mut buf);
+                }
+                b'X' => return Ok(()),
+                b'S' => {
+                    ignore_till_sync = false;
+                    write_ready_for_query(&mut buf);
+                }
+                _ if ignore_till_sync => {}
+                _ => {
+                    // The messages of the extended query protocol, the following ones are
+                    // discarded until the next Sync.
+                    ignore_till_sync = true;
+                    let err = PgError {
+                        code: "0A000",
+                        message: "only the simple query protocol is supported".to_owned(),
+                    };
+                    write_error(&mut buf, &err);
+                }
+            }
+            stream.write_all(&buf).await?;
+            buf.clear();
+        }
+    }
+
+    /// Handle the startup and authentication of a connection, returns false if the connection
+    /// should be closed.
+    async fn startup(&self, stream: &mut TcpStream) -> Result<bool> {
+        loop {
+            let body = read_body(stream).await?;
+            let code = read_i32(&body, 0)?;
+            match code {
+                SSL_REQUEST_CODE | GSSENC_REQUEST_CODE => {
+                    // Encryption is not supported, the client could continue in plaintext.
+                    stream.write_all(b"N").await?;
+                }
+                CANCEL_REQUEST_CODE => return Ok(false),
+                PROTOCOL_VERSION_3 => break,
+                _ => {
+                    return Err(Error::InvalidArgument(format!(
+                        "unsupported pgwire protocol version {code}"
+                    )))
+                }
+            }
+        }
+
+        let mut buf = Vec::new();
+        if !self.auth_tokens.is_empty() {
+            // AuthenticationCleartextPassword.
+            write_message(&mut buf, b'R', &3i32.to_be_bytes());
+            stream.write_all(&buf).await?;
+            buf.clear();
+
+            let tag = stream.read_u8().await?;
+            let body = read_body(stream).await?;
+            let authorized = tag == b'p' && {
+                let password = read_cstr(&body)?;
+                self.auth_tokens.iter().any(|t| *t == password)
+            };
+            if !authorized {
+                let err = PgError {
+                    code: "28P01",
+                    message: "password authentication failed".to_owned(),
+                };
+                write_error(&mut buf, &err);
+                stream.write_all(&buf).await?;
+                return Ok(false);
+            }
+        }
+
+        // AuthenticationOk.
+        write_message(&mut buf, b'R', &0i32.to_be_bytes());
+        for (name, value) in [
+            ("server_version", "14.0 (engula)"),
+            ("server_encoding", "UTF8"),
+            ("client_encoding", "UTF8"),
+            ("DateStyle", "ISO, MDY"),
+            ("integer_datetimes", "on"),
+            ("standard_conforming_strings", "on"),
+        ] {
+            let mut body = Vec::new();
+            put_cstr(&mut body, name);
+            put_cstr(&mut body, value);
+            write_message(&mut buf, b'S', &body);
+        }
+        write_ready_for_query(&mut buf);
+        stream.write_all(&buf).await?;
+        Ok(true)
+    }
+
+    async fn query(
+        &self,
+        stream: &mut TcpStream,
+        buf: &mut Vec<u8>,
+        sql: &str,
+    ) -> std::result::Result<(), PgError> {
+        if sql.trim().trim_end_matches(';').trim().is_empty() {
+            // EmptyQueryResponse.
+            write_message(buf, b'I', &[]);
+            return Ok(());
+        }
+
+        let select = parse_select(sql)?;
+        let collection = self
+            .proxy_server
+            .client
+            .open_database(select.database.clone())
+            .await
+            .map_err(PgError::from)?
+            .open_collection(select.collection.clone())
+            .await
+            .map_err(PgError::from)?;
+        write_row_description(buf, &select.columns);
+        let rows = scan(stream, buf, &collection, &select).await?;
+        let mut body = Vec::new();
+        put_cstr(&mut body, &format!("SELECT {rows}"));
+        write_message(buf, b'C', &body);
+        Ok(())
+    }
+}
+
+/// Scan the rows page by page and send them, returns the number of rows.
+async fn scan(
+    stream: &mut TcpStream,
+    buf: &mut Vec<u8>,
+    collection: &Collection,
+    select: &Select,
+) -> std::result::Result<usize, PgError> {
+    let mut remaining = select.limit.unwrap_or(usize::MAX);
+    let mut cursor = select.start.clone();
+    let mut rows = 0;
+    while remaining > 0 && (select.end.is_empty() || cursor < select.end) {
+        let page_size = std::cmp::min(remaining, SCAN_PAGE_SIZE);
+        let page = collection
+            .scan_with_version(cursor.clone(), select.end.clone(), page_size)
+            .await
+            .map_err(PgError::from)?;
+        for (key, value, version) in &page {
+            write_data_row(buf, &select.columns, key, value, *version);
+        }
+        rows += page.len();
+        remaining -= page.len();
+        stream.write_all(buf).await.map_err(|err| PgError {
+            code: "08006",
+            message: err.to_string(),
+        })?;
+        buf.clear();
+
+        match page.last() {
+            Some((key, _, _)) if page.len() == page_size => {
+                cursor = key.clone();
+                cursor.push(0);
+            }
+            _ => break,
+        }
+    }
+    Ok(rows)
+}
+
+impl From<AppError> for PgError {
+    fn from(err: AppError) -> Self {
+        let code = match &err {
+            AppError::NotFound(_) => "42P01",
+            AppError::InvalidArgument(_) => "22023",
+            AppError::DeadlineExceeded(_) => "57014",
+            _ => "XX000",
+        };
+        PgError {
+            code,
+            message: err.to_string(),
+        }
+    }
+}
+
+fn syntax_error(message: impl Into<String>) -> PgError {
+    PgError {
+        code: "42601",
+        message: message.into(),
+    }
+}
+
+fn parse_select(sql: &str) -> std::result::Result<Select, PgError> {
+    let mut tokens = tokenize(sql)?.into_iter();
+    let mut next = || tokens.next();
+
+    let expect_keyword = |token: Option<Token>, keyword: &str| match token {
+        Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword) => Ok(()),
+        other => Err(syntax_error(format!("expect {keyword}, but got {other:?}"))),
+    };
+
+    expect_keyword(next(), "select")?;
+    let mut columns = Vec::new();
+    let from = loop {
+        let column = match next() {
+            Some(Token::Symbol("*")) => {
+                columns.extend([Column::Key, Column::Value, Column::Version]);
+                next()
+            }
+            Some(Token::Ident(ident)) => {
+                columns.push(parse_column(&ident)?);
+                next()
+            }
+            other => return Err(syntax_error(format!("expect column, but got {other:?}"))),
+        };
+        match column {
+            Some(Token::Symbol(",")) => continue,
+            other => break other,
+        }
+    };
+    expect_keyword(from, "from")?;
+
+    let database = match next() {
+        Some(Token::Ident(ident)) => ident,
+        other => return Err(syntax_error(format!("expect table, but got {other:?}"))),
+    };
+    let collection = match (next(), next()) {
+        (Some(Token::Symbol(".")), Some(Token::Ident(ident))) => ident,
+        _ => {
+            return Err(syntax_error(
+                "the table must be qualified as database.collection",
+            ))
+        }
+    };
+
+    let mut select = Select {
+        columns,
+        database,
+        collection,
+        start: vec![],
+        end: vec![],
+        limit: None,
+    };
+    let mut token = next();
+    if matches!(&token, Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("where")) {
+        loop {
+            match next() {
+                Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("key") => {}
+                other => {
+                    return Err(syntax_error(format!(
+                        "only the predicates on key are supported, but got {other:?}"
+                    )))
+                }
+            }
+            let op = match next() {
+                Some(Token::Symbol(op)) => op,
+                other => return Err(syntax_error(format!("expect operator, but got {other:?}"))),
+            };
+            let value = match next() {
+                Some(Token::Str(value)) => decode_literal(&value)?,
+                other => return Err(syntax_error(format!("expect literal, but got {other:?}"))),
+            };
+            select.restrict(op, value)?;
+            token = next();
+            match &token {
+                Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("and") => continue,
+                _ => break,
+            }
+        }
+    }
+    if matches!(&token, Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("limit")) {
+        match next() {
+            Some(Token::Number(limit)) => select.limit = Some(limit),
+            other => return Err(syntax_error(format!("expect number, but got {other:?}"))),
+        }
+        token = next();
+    }
+    if matches!(token, Some(Token::Symbol(";"))) {
+        token = next();
+    }
+    if let Some(token) = token {
+        return Err(syntax_error(format!("unexpected {token:?}")));
+    }
+    Ok(select)
+}
+
+fn parse_column(ident: &str) -> std::result::Result<Column, PgError> {
+    match ident.to_ascii_lowercase().as_str() {
+        "key" => Ok(Column::Key),
+        "value" => Ok(Column::Value),
+        "version" => Ok(Column::Version),
+        _ => Err(PgError {
+            code: "42703",
+            message: format!("column {ident} does not exist"),
+        }),
+    }
+}
+
+impl Select {
+    /// Narrow the key range by the predicate `key {op} {value}`.
+    fn restrict(&mut self, op: &str, value: Vec<u8>) -> std::result::Result<(), PgError> {
+        let successor = |mut key: Vec<u8>| {
+            key.push(0);
+            key
+        };
+        let (start, end) = match op {
+            "=" => (Some(value.clone()), Some(successor(value))),
+            ">=" => (Some(value), None),
+            ">" => (Some(successor(value)), None),
+            "<" => (None, Some(value)),
+            "<=" => (None, Some(successor(value))),
+            _ => return Err(syntax_error(format!("unsupported operator {op}"))),
+        };
+        if let Some(start) = start {
+            self.start = std::cmp::max(std::mem::take(&mut self.start), start);
+        }
+        if let Some(end) = end {
+            if self.end.is_empty() || end < self.end {
+                self.end = end;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn tokenize(sql: &str) -> std::result::Result<Vec<Token>, PgError> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\'') if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                            value.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(syntax_error("unterminated string literal")),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            '"' => {
+                let mut ident = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            ident.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => ident.push(c),
+                        None => return Err(syntax_error("unterminated quoted identifier")),
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            c if c.is_ascii_digit() => {
+                let mut number = c.to_string();
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    number.push(c);
+                }
+                let number = number
+                    .parse()
+                    .map_err(|_| syntax_error(format!("invalid number {number}")))?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    ident.push(c);
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            '<' | '>' if chars.peek() == Some(&'=') => {
+                chars.next();
+                tokens.push(Token::Symbol(if c == '<' { "<=" } else { ">=" }));
+            }
+            '*' => tokens.push(Token::Symbol("*")),
+            ',' => tokens.push(Token::Symbol(",")),
+            '.' => tokens.push(Token::Symbol(".")),
+            ';' => tokens.push(Token::Symbol(";")),
+            '=' => tokens.push(Token::Symbol("=")),
+            '<' => tokens.push(Token::Symbol("<")),
+            '>' => tokens.push(Token::Symbol(">")),
+            _ => return Err(syntax_error(format!("unexpected character {c}"))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Decode a string literal, those starting with `\x` are hex encoded bytea.
+fn decode_literal(value: &str) -> std::result::Result<Vec<u8>, PgError> {
+    let Some(hex) = value.strip_prefix("\\x") else {
+        return Ok(value.as_bytes().to_vec());
+    };
+    let invalid = || PgError {
+        code: "22P02",
+        message: format!("invalid hexadecimal bytea {value}"),
+    };
+    if hex.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// Encode a bytea in the hex text format.
+fn encode_bytea(value: &[u8]) -> Vec<u8> {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut buf = Vec::with_capacity(2 + value.len() * 2);
+    buf.extend_from_slice(b"\\x");
+    for b in value {
+        buf.push(HEX[(b >> 4) as usize]);
+        buf.push(HEX[(b & 0xf) as usize]);
+    }
+    buf
+}
+
+async fn read_body(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let len = stream.read_i32().await?;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|len| (4..=MAX_MESSAGE_SIZE).contains(len))
+        .ok_or_else(|| Error::InvalidArgument(format!("invalid pgwire message size {len}")))?;
+    let mut body = vec![0; len - 4];
+    stream.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+fn read_i32(body: &[u8], offset: usize) -> Result<i32> {
+    body.get(offset..offset + 4)
+        .map(|b| i32::from_be_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| Error::InvalidArgument("truncated pgwire message".to_owned()))
+}
+
+fn read_cstr(body: &[u8]) -> Result<String> {
+    let end = body
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or_else(|| Error::InvalidArgument("unterminated pgwire string".to_owned()))?;
+    String::from_utf8(body[..end].to_vec())
+        .map_err(|_| Error::InvalidArgument("invalid utf8 pgwire string".to_owned()))
+}
+
+fn put_cstr(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(0);
+}
+
+fn write_message(buf: &mut Vec<u8>, tag: u8, body: &[u8]) {
+    buf.push(tag);
+    buf.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+    buf.extend_from_slice(body);
+}
+
+fn write_ready_for_query(buf: &mut Vec<u8>) {
+    write_message(buf, b'Z', b"I");
+}
+
+fn write_error(buf: &mut Vec<u8>, err: &PgError) {
+    let mut body = Vec::new();
+    for (field, value) in [
+        (b'S', "ERROR"),
+        (b'V', "ERROR"),
+        (b'C', err.code),
+        (b'M', err.message.as_str()),
+    ] {
+        body.push(field);
+        put_cstr(&mut body, value);
+    }
+    body.push(0);
+    write_message(buf, b'E', &body);
+}
+
+fn write_row_description(buf: &mut Vec<u8>, columns: &[Column]) {
+    let mut body = Vec::new();
+    body.extend_from_slice(&(columns.len() as i16).to_be_bytes());
+    for column in columns {
+        let (name, oid, size) = match column {
+            Column::Key => ("key", BYTEA_OID, -1i16),
+            Column::Value => ("value", BYTEA_OID, -1),
+            Column::Version => ("version", INT8_OID, 8),
+        };
+        put_cstr(&mut body, name);
+        body.extend_from_slice(&0i32.to_be_bytes()); // table oid
+        body.extend_from_slice(&0i16.to_be_bytes()); // column attribute number
+        body.extend_from_slice(&oid.to_be_bytes());
+        body.extend_from_slice(&size.to_be_bytes());
+        body.extend_from_slice(&(-1i32).to_be_bytes()); // type modifier
+        body.extend_from_slice(&0i16.to_be_bytes()); // text format
+    }
+    write_message(buf, b'T', &body);
+}
+
+fn write_data_row(buf: &mut Vec<u8>, columns: &[Column], key: &[u8], value: &[u8], version: u64) {
+    let mut body = Vec::new();
+    body.extend_from_slice(&(columns.len() as i16).to_be_bytes());
+    for column in columns {
+        let field = match column {
+            Column::Key => encode_bytea(key),
+            Column::Value => encode_bytea(value),
+            Column::Version => version.to_string().into_bytes(),
+        };
+        body.extend_from_slice(&(field.len() as i32).to_be_bytes());
+        body.extend_from_slice(&field);
+    }
+    write_message(buf, b'D', &body);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(sql: &str) -> Select {
+        parse_select(sql).unwrap()
+    }
+
+    #[test]
+    fn parse_select_statements() {
+        assert_eq!(
+            select("select * from db.co"),
+            Select {
+                columns: vec![Column::Key, Column::Value, Column::Version],
+                database: "db".to_owned(),
+                collection: "co".to_owned(),
+                start: vec![],
+                end: vec![],
+                limit: None,
+            }
+        );
+        let sql = concat!(
+            r#"SELECT key, version FROM "my db"."co.1" "#,
+            r#"WHERE key >= 'a' AND key < '\x6200' LIMIT 10;"#,
+        );
+        assert_eq!(
+            select(sql),
+            Select {
+                columns: vec![Column::Key, Column::Version],
+                database: "my db".to_owned(),
+                collection: "co.1".to_owned(),
+                start: b"a".to_vec(),
+                end: b"b\x00".to_vec(),
+                limit: Some(10),
+            }
+        );
+
+        let s = select("select value from db.co where key = 'it''s'");
+        assert_eq!(s.start, b"it's".to_vec());
+        assert_eq!(s.end, b"it's\x00".to_vec());
+
+        // The tighter bounds are kept.
+        let s = select("select key from db.co where key > 'b' and key >= 'a' and key <= 'y'");
+        assert_eq!(s.start, b"b\x00".to_vec());
+        assert_eq!(s.end, b"y\x00".to_vec());
+
+        assert_eq!(parse_select("select * from co").unwrap_err().code, "42601");
+        assert_eq!(
+            parse_select("select ttl from db.co").unwrap_err().code,
+            "42703"
+        );
+        assert_eq!(
+            parse_select("select * from db.co where value = 'a'")
+                .unwrap_err()
+                .code,
+            "42601"
+        );
+        assert_eq!(
+            parse_select("select * from db.co where key = '\\x6'")
+                .unwrap_err()
+                .code,
+            "22P02"
+        );
+        assert!(parse_select("select * from db.co limit 1 offset 2").is_err());
+        assert!(parse_select("delete from db.co").is_err());
+    }
+
+    #[test]
+    fn encode_bytea_in_hex() {
+        assert_eq!(encode_bytea(b""), b"\\x".to_vec());
+        assert_eq!(encode_bytea(b"a\x00\xff"), b"\\x6100ff".to_vec());
+        assert_eq!(decode_literal("\\x6100ff").unwrap(), b"a\x00\xff".to_vec());
+    }
+}
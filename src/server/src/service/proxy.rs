@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ::engula_client::{Collection, Database};
+use ::engula_client::{Collection, CollectionOptions, Database};
 use engula_api::v1::*;
 use tonic::{Request, Response, Status};
 
@@ -77,6 +77,10 @@ impl engula_server::Engula for ProxyServer {
             collection_request_union::Request, collection_response_union::Response,
         };
 
+        let principal = request
+            .remote_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
         let request = request.into_inner();
         let request = request.request.ok_or_else(|| {
             Error::InvalidArgument("DatabaseRequest::request is required".to_owned())
@@ -92,8 +96,26 @@ impl engula_server::Engula for ProxyServer {
         record_latency!(take_database_request_metrics(&request));
         let resp = match request {
             Request::Get(req) => Response::Get(self.handle_get(collection, req).await?),
-            Request::Put(req) => Response::Put(self.handle_put(collection, req).await?),
-            Request::Delete(req) => Response::Delete(self.handle_delete(collection, req).await?),
+            Request::Put(req) => {
+                let target = mutation_target(&collection, &req.key);
+                let record = self
+                    .audit
+                    .begin_data_mutation(&principal, "put", target)
+                    .await?;
+                let res = self.handle_put(collection, req).await;
+                record.finish(&res);
+                Response::Put(res?)
+            }
+            Request::Delete(req) => {
+                let target = mutation_target(&collection, &req.key);
+                let record = self
+                    .audit
+                    .begin_data_mutation(&principal, "delete", target)
+                    .await?;
+                let res = self.handle_delete(collection, req).await;
+                record.finish(&res);
+                Response::Delete(res?)
+            }
         };
         Ok(tonic::Response::new(DatabaseResponse {
             response: Some(CollectionResponse {
@@ -196,8 +218,15 @@ impl ProxyServer {
         })?;
         let name = req.name;
         let database = Database::new(self.client.clone(), desc, None);
+        let opts = CollectionOptions {
+            max_key_size: req.max_key_size,
+            max_value_size: req.max_value_size,
+            leader_zone: req.leader_zone,
+            webhook_url: req.webhook_url,
+            read_replica_zones: req.read_replica_zones,
+        };
         let collection = database
-            .create_collection(name, Some(partition.into()))
+            .create_collection_with_options(name, Some(partition.into()), opts)
             .await?;
         Ok(CreateCollectionResponse {
             collection: Some(collection.desc()),
@@ -242,8 +271,13 @@ impl ProxyServer {
         req: PutRequest,
     ) -> Result<PutResponse, Status> {
         let collection = Collection::new(self.client.clone(), desc, None);
+        if let Some(condition) = req.condition {
+            return Ok(collection
+                .put_with_condition(req.key, req.value, condition)
+                .await?);
+        }
         collection.put(req.key, req.value).await?;
-        Ok(PutResponse {})
+        Ok(PutResponse::default())
     }
 
     async fn handle_delete(
@@ -256,3 +290,8 @@ impl ProxyServer {
         Ok(DeleteResponse {})
     }
 }
+
+/// Returns `{database id}/{collection name}/{escaped key}` for the audit log.
+pub(super) fn mutation_target(desc: &CollectionDesc, key: &[u8]) -> String {
+    format!("{}/{}/{}", desc.db, desc.name, key.escape_ascii())
+}
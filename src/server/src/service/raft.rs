@@ -76,7 +76,7 @@ impl raft_server::Raft for Server {
         let request = request.into_inner();
         let snap_mgr = self.node.raft_manager().snapshot_manager();
 
-        let stream = send_snapshot(snap_mgr, request.replica_id, request.snapshot_id).await?;
+        let stream = send_snapshot(snap_mgr, request).await?;
         Ok(Response::new(stream))
     }
 }
@@ -16,7 +16,11 @@ use engula_api::{server::v1::*, v1::*};
 use tonic::{Request, Response, Status};
 
 use super::metrics::*;
-use crate::{record_latency, root::Watcher, Error, Result, Server};
+use crate::{
+    record_latency,
+    root::{ReportSequence, Watcher},
+    Error, Result, Server,
+};
 
 #[tonic::async_trait]
 impl root_server::Root for Server {
@@ -27,9 +31,20 @@ impl root_server::Root for Server {
         req: Request<AdminRequest>,
     ) -> std::result::Result<Response<AdminResponse>, Status> {
         record_latency!(take_admin_request_metrics());
+        let principal = req
+            .remote_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
         let req = req.into_inner();
-        let res = self.handle_admin(req).await?;
-        Ok(Response::new(res))
+        let record = match admin_action(&req) {
+            Some((action, target)) => Some(self.audit.begin(&principal, action, target).await?),
+            None => None,
+        };
+        let res = self.handle_admin(req).await;
+        if let Some(record) = record {
+            record.finish(&res);
+        }
+        Ok(Response::new(res?))
     }
 
     async fn watch(
@@ -53,13 +68,18 @@ impl root_server::Root for Server {
         let capacity = request
             .capacity
             .ok_or_else(|| Error::InvalidArgument("capacity is required".into()))?;
-        let (cluster_id, node, root) = self
-            .wrap(self.root.join(request.addr, capacity).await)
+        let (cluster_id, node, root, features) = self
+            .wrap(
+                self.root
+                    .join(request.addr, request.zone, capacity, request.features)
+                    .await,
+            )
             .await?;
         Ok::<Response<JoinNodeResponse>, Status>(Response::new(JoinNodeResponse {
             cluster_id,
             node_id: node.id,
             root: Some(root),
+            features: Some(features),
         }))
     }
 
@@ -69,7 +89,13 @@ impl root_server::Root for Server {
     ) -> std::result::Result<Response<ReportResponse>, Status> {
         record_latency!(take_report_request_metrics());
         let request = request.into_inner();
-        self.wrap(self.root.report(request.updates).await).await?;
+        let sequence = ReportSequence {
+            node_id: request.node_id,
+            session: request.session,
+            sequence: request.sequence,
+        };
+        self.wrap(self.root.report(sequence, request.updates).await)
+            .await?;
         Ok(Response::new(ReportResponse {}))
     }
 
@@ -88,6 +114,13 @@ impl root_server::Root for Server {
             .await?;
         Ok(Response::new(AllocReplicaResponse { replicas }))
     }
+
+    async fn get_capabilities(
+        &self,
+        _request: Request<GetCapabilitiesRequest>,
+    ) -> std::result::Result<Response<GetCapabilitiesResponse>, Status> {
+        Ok(Response::new(GetCapabilitiesResponse::default()))
+    }
 }
 
 impl Server {
@@ -189,7 +222,16 @@ impl Server {
         })?;
         let desc = self
             .root
-            .create_collection(req.name, database.name, req.partition)
+            .create_collection(
+                req.name,
+                database.name,
+                req.partition,
+                req.max_key_size,
+                req.max_value_size,
+                req.leader_zone,
+                req.webhook_url,
+                req.read_replica_zones,
+            )
             .await?;
         Ok(CreateCollectionResponse {
             collection: Some(desc),
@@ -251,3 +293,25 @@ impl Server {
         }
     }
 }
+
+/// Returns the action and target of the admin request which changes the cluster, they are
+/// recorded in the audit log.
+fn admin_action(req: &AdminRequest) -> Option<(&'static str, String)> {
+    use admin_request_union::Request;
+
+    let database_name =
+        |desc: &Option<DatabaseDesc>| desc.as_ref().map(|d| d.name.clone()).unwrap_or_default();
+    match req.request.as_ref()?.request.as_ref()? {
+        Request::CreateDatabase(req) => Some(("create_database", req.name.clone())),
+        Request::DeleteDatabase(req) => Some(("delete_database", req.name.clone())),
+        Request::CreateCollection(req) => Some((
+            "create_collection",
+            format!("{}/{}", database_name(&req.database), req.name),
+        )),
+        Request::DeleteCollection(req) => Some((
+            "delete_collection",
+            format!("{}/{}", database_name(&req.database), req.name),
+        )),
+        _ => None,
+    }
+}
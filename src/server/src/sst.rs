@@ -0,0 +1,786 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A reader of the block-based table files written by the engine, so that a table file could be
+//! inspected and verified offline without opening the database, eg. when the database refuses to
+//! open because of a corrupted file.
+//!
+//! The format versions 1 to 5 are supported, with the compression types LZ4 and Zstd used by
+//! [`crate::DbConfig`], and the binary search and two-level indexes.
+
+use std::{fs::File, hash::Hasher, os::unix::fs::FileExt, path::Path};
+
+use crate::{Error, Result};
+
+const BLOCK_BASED_TABLE_MAGIC: u64 = 0x88e2_41b7_85f4_cff7;
+const LEGACY_BLOCK_BASED_TABLE_MAGIC: u64 = 0xdb47_7524_8b80_fb57;
+
+/// The size of the footer since format version 1: the checksum type, two block handles padded to
+/// 40 bytes, the format version and the magic.
+const FOOTER_SIZE: usize = 53;
+const MAX_FORMAT_VERSION: u32 = 5;
+
+/// A block is followed by the compression type and the checksum.
+const BLOCK_TRAILER_SIZE: u64 = 5;
+
+const CRC32C_MASK_DELTA: u32 = 0xa282_ead8;
+const XXH3_LAST_BYTE_PRIME: u32 = 0x6b90_83d9;
+
+const NO_COMPRESSION: u8 = 0x0;
+const LZ4_COMPRESSION: u8 = 0x4;
+const LZ4HC_COMPRESSION: u8 = 0x5;
+const ZSTD_COMPRESSION: u8 = 0x7;
+const ZSTD_NOT_FINAL_COMPRESSION: u8 = 0x40;
+
+/// The index types of `rocksdb.block.based.table.index.type`.
+const BINARY_SEARCH_INDEX: u32 = 0;
+const HASH_SEARCH_INDEX: u32 = 1;
+const TWO_LEVEL_INDEX: u32 = 2;
+
+/// The value type of range deletions in internal keys.
+const RANGE_DELETION_TYPE: u8 = 0xf;
+
+const PROPERTIES_BLOCK: &str = "rocksdb.properties";
+const RANGE_DEL_BLOCK: &str = "rocksdb.range_del";
+
+const PROP_NUM_ENTRIES: &str = "rocksdb.num.entries";
+const PROP_NUM_RANGE_DELETIONS: &str = "rocksdb.num.range-deletions";
+const PROP_NUM_DATA_BLOCKS: &str = "rocksdb.num.data.blocks";
+const PROP_INDEX_KEY_IS_USER_KEY: &str = "rocksdb.index.key.is.user.key";
+const PROP_INDEX_VALUE_IS_DELTA_ENCODED: &str = "rocksdb.index.value.is.delta.encoded";
+const PROP_INDEX_TYPE: &str = "rocksdb.block.based.table.index.type";
+
+/// The properties which are saved as varint64, the others are strings except the index type.
+const NUMERIC_PROPERTIES: &[&str] = &[
+    "rocksdb.data.size",
+    "rocksdb.index.size",
+    "rocksdb.index.partitions",
+    "rocksdb.top-level.index.size",
+    PROP_INDEX_KEY_IS_USER_KEY,
+    PROP_INDEX_VALUE_IS_DELTA_ENCODED,
+    "rocksdb.filter.size",
+    "rocksdb.raw.key.size",
+    "rocksdb.raw.value.size",
+    PROP_NUM_DATA_BLOCKS,
+    PROP_NUM_ENTRIES,
+    "rocksdb.num.filter_entries",
+    "rocksdb.deleted.keys",
+    "rocksdb.merge.operands",
+    PROP_NUM_RANGE_DELETIONS,
+    "rocksdb.format.version",
+    "rocksdb.fixed.key.length",
+    "rocksdb.column.family.id",
+    "rocksdb.creation.time",
+    "rocksdb.oldest.key.time",
+    "rocksdb.file.creation.time",
+    "rocksdb.slow.compression.estimated.data.size",
+    "rocksdb.fast.compression.estimated.data.size",
+    "rocksdb.external_sst_file.version",
+    "rocksdb.external_sst_file.global_seqno",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockHandle {
+    pub offset: u64,
+    pub size: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumType {
+    None,
+    Crc32c,
+    XxHash,
+    XxHash64,
+    Xxh3,
+}
+
+#[derive(Clone, Debug)]
+pub struct Footer {
+    pub checksum_type: ChecksumType,
+    pub metaindex: BlockHandle,
+    pub index: BlockHandle,
+    pub format_version: u32,
+}
+
+/// An entry of a data block or the range deletion block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SstEntry {
+    pub user_key: Vec<u8>,
+    pub sequence: u64,
+    pub value_type: u8,
+    /// The value, or the end key of a range deletion.
+    pub value: Vec<u8>,
+}
+
+/// An entry of the index, the key is not less than the keys of the data block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub user_key: Vec<u8>,
+    pub handle: BlockHandle,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VerifyReport {
+    pub blocks: usize,
+    pub entries: u64,
+    /// False if the table file is written without checksums.
+    pub checksum_verified: bool,
+}
+
+pub struct SstReader {
+    file: File,
+    file_size: u64,
+    footer: Footer,
+    meta_blocks: Vec<(String, BlockHandle)>,
+    properties: Vec<(String, Vec<u8>)>,
+}
+
+impl SstReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let footer = read_footer(&file, file_size)?;
+        let mut reader = SstReader {
+            file,
+            file_size,
+            footer,
+            meta_blocks: vec![],
+            properties: vec![],
+        };
+
+        let metaindex = reader.read_block(reader.footer.metaindex)?;
+        for (name, value) in decode_entries(&metaindex)? {
+            let name = String::from_utf8(name)
+                .map_err(|_| invalid("the name of meta block is not utf8"))?;
+            let handle = decode_handle(&value, &mut 0)?;
+            reader.meta_blocks.push((name, handle));
+        }
+        if let Some(handle) = reader.meta_block(PROPERTIES_BLOCK) {
+            let properties = reader.read_block(handle)?;
+            for (name, value) in decode_entries(&properties)? {
+                let name = String::from_utf8(name)
+                    .map_err(|_| invalid("the name of property is not utf8"))?;
+                reader.properties.push((name, value));
+            }
+        }
+        Ok(reader)
+    }
+
+    pub fn footer(&self) -> &Footer {
+        &self.footer
+    }
+
+    pub fn meta_blocks(&self) -> &[(String, BlockHandle)] {
+        &self.meta_blocks
+    }
+
+    /// Returns the raw table properties, see [`format_property`].
+    pub fn properties(&self) -> &[(String, Vec<u8>)] {
+        &self.properties
+    }
+
+    pub fn property_u64(&self, name: &str) -> Option<u64> {
+        let (_, value) = self.properties.iter().find(|(n, _)| n == name)?;
+        if name == PROP_INDEX_TYPE {
+            return value
+                .get(..4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as u64);
+        }
+        decode_varint64(value, &mut 0).ok()
+    }
+
+    /// Returns the entries of the index, one per data block, the partitions of a two-level index
+    /// are flattened.
+    pub fn index(&self) -> Result<Vec<IndexEntry>> {
+        let index_type = self
+            .property_u64(PROP_INDEX_TYPE)
+            .map(|v| v as u32)
+            .unwrap_or(BINARY_SEARCH_INDEX);
+        let top_level = self.read_index_block(self.footer.index)?;
+        match index_type {
+            BINARY_SEARCH_INDEX | HASH_SEARCH_INDEX => Ok(top_level),
+            TWO_LEVEL_INDEX => {
+                let mut entries = vec![];
+                for partition in top_level {
+                    entries.extend(self.read_index_block(partition.handle)?);
+                }
+                Ok(entries)
+            }
+            _ => Err(invalid(format!("unsupported index type {index_type}"))),
+        }
+    }
+
+    /// Calls `f` on the entries whose user keys are in `[start, end)` ordered by key, an empty
+    /// `end` means the end of the table. The scan stops once `f` returns false.
+    pub fn scan<F>(&self, start: &[u8], end: &[u8], mut f: F) -> Result<()>
+    where
+        F: FnMut(SstEntry) -> bool,
+    {
+        for index_entry in self.index()? {
+            if index_entry.user_key.as_slice() < start {
+                continue;
+            }
+            for entry in self.read_data_block(index_entry.handle)? {
+                if entry.user_key.as_slice() < start {
+                    continue;
+                }
+                if !end.is_empty() && entry.user_key.as_slice() >= end {
+                    return Ok(());
+                }
+                if !f(entry) {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the range deletions, whose values are the end keys.
+    pub fn range_deletions(&self) -> Result<Vec<SstEntry>> {
+        match self.meta_block(RANGE_DEL_BLOCK) {
+            Some(handle) => self.read_data_block(handle),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Read all blocks to verify their checksums, and check that the entries are ordered and
+    /// consistent with the table properties.
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let mut report = VerifyReport {
+            blocks: 2, // The metaindex and the properties are read when opening.
+            entries: 0,
+            checksum_verified: self.footer.checksum_type != ChecksumType::None,
+        };
+        for (name, handle) in &self.meta_blocks {
+            if name != PROPERTIES_BLOCK {
+                // Only the checksums of filters are verified, their contents are not decoded.
+                self.read_block(*handle)?;
+                report.blocks += 1;
+            }
+        }
+
+        let index = self.index()?;
+        report.blocks += 1;
+        if let Some(expected) = self.property_u64(PROP_NUM_DATA_BLOCKS) {
+            if expected != index.len() as u64 {
+                return Err(invalid(format!(
+                    "{} data blocks are indexed, but {expected} are recorded",
+                    index.len()
+                )));
+            }
+        }
+
+        let mut prev: Option<SstEntry> = None;
+        for index_entry in &index {
+            let entries = self.read_data_block(index_entry.handle)?;
+            report.blocks += 1;
+            for entry in entries {
+                if let Some(prev) = &prev {
+                    if compare_internal_key(prev, &entry) != std::cmp::Ordering::Less {
+                        return Err(invalid(format!(
+                            "the key {:?}@{} is not ordered after {:?}@{}",
+                            entry.user_key, entry.sequence, prev.user_key, prev.sequence
+                        )));
+                    }
+                }
+                if entry.user_key > index_entry.user_key {
+                    return Err(invalid(format!(
+                        "the key {:?} exceeds the index key {:?} of the data block at {}",
+                        entry.user_key, index_entry.user_key, index_entry.handle.offset
+                    )));
+                }
+                report.entries += 1;
+                prev = Some(entry);
+            }
+        }
+
+        if let Some(expected) = self.property_u64(PROP_NUM_ENTRIES) {
+            // The number of entries counts the range deletions too.
+            let range_deletions = self.property_u64(PROP_NUM_RANGE_DELETIONS).unwrap_or(0);
+            if expected != report.entries + range_deletions {
+                return Err(invalid(format!(
+                    "{} entries and {range_deletions} range deletions are read, but {expected} \
+                     entries are recorded",
+                    report.entries
+                )));
+            }
+        }
+        Ok(report)
+    }
+
+    fn meta_block(&self, name: &str) -> Option<BlockHandle> {
+        self.meta_blocks
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, handle)| *handle)
+    }
+
+    fn read_index_block(&self, handle: BlockHandle) -> Result<Vec<IndexEntry>> {
+        let key_is_user_key = self.property_u64(PROP_INDEX_KEY_IS_USER_KEY).unwrap_or(0) != 0;
+        let value_is_delta_encoded = self
+            .property_u64(PROP_INDEX_VALUE_IS_DELTA_ENCODED)
+            .unwrap_or(0)
+            != 0;
+        let contents = self.read_block(handle)?;
+        decode_index_entries(&contents, value_is_delta_encoded)?
+            .into_iter()
+            .map(|(key, handle)| {
+                let user_key = if key_is_user_key {
+                    key
+                } else {
+                    parse_internal_key(&key)?.0.to_vec()
+                };
+                Ok(IndexEntry { user_key, handle })
+            })
+            .collect()
+    }
+
+    fn read_data_block(&self, handle: BlockHandle) -> Result<Vec<SstEntry>> {
+        let contents = self.read_block(handle)?;
+        decode_entries(&contents)?
+            .into_iter()
+            .map(|(key, value)| {
+                let (user_key, sequence, value_type) = parse_internal_key(&key)?;
+                Ok(SstEntry {
+                    user_key: user_key.to_vec(),
+                    sequence,
+                    value_type,
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// Read the block, verify its checksum and returns the uncompressed contents.
+    fn read_block(&self, handle: BlockHandle) -> Result<Vec<u8>> {
+        let len = handle
+            .size
+            .checked_add(BLOCK_TRAILER_SIZE)
+            .filter(|len| {
+                handle
+                    .offset
+                    .checked_add(*len)
+                    .map(|end| end <= self.file_size)
+                    .unwrap_or_default()
+            })
+            .ok_or_else(|| invalid(format!("the block {handle:?} exceeds the file")))?;
+        let mut buf = vec![0; len as usize];
+        self.file.read_exact_at(&mut buf, handle.offset)?;
+
+        let (data, trailer) = buf.split_at(handle.size as usize);
+        let compression = trailer[0];
+        let expected = u32::from_le_bytes(trailer[1..5].try_into().unwrap());
+        if let Some(actual) = compute_checksum(self.footer.checksum_type, data, compression) {
+            if actual != expected {
+                return Err(invalid(format!(
+                    "the checksum of block {handle:?} mismatches, expect {expected:#x}, \
+                     actual {actual:#x}"
+                )));
+            }
+        }
+        decompress(compression, data, self.footer.format_version)
+    }
+}
+
+/// Format a property for printing, the numeric properties are decoded.
+pub fn format_property(name: &str, value: &[u8]) -> String {
+    if NUMERIC_PROPERTIES.contains(&name) {
+        if let Ok(v) = decode_varint64(value, &mut 0) {
+            return v.to_string();
+        }
+    }
+    if name == PROP_INDEX_TYPE && value.len() == 4 {
+        return u32::from_le_bytes(value.try_into().unwrap()).to_string();
+    }
+    escape(value)
+}
+
+/// Escape the non-printable bytes, eg. `\x00`.
+pub fn escape(value: &[u8]) -> String {
+    value
+        .iter()
+        .flat_map(|b| std::ascii::escape_default(*b))
+        .map(char::from)
+        .collect()
+}
+
+fn invalid(msg: impl Into<String>) -> Error {
+    Error::InvalidData(format!("sst: {}", msg.into()))
+}
+
+fn read_footer(file: &File, file_size: u64) -> Result<Footer> {
+    if file_size < FOOTER_SIZE as u64 {
+        return Err(invalid("the file is too short to be a table file"));
+    }
+    let mut buf = [0u8; FOOTER_SIZE];
+    file.read_exact_at(&mut buf, file_size - FOOTER_SIZE as u64)?;
+
+    let magic = u64::from_le_bytes(buf[FOOTER_SIZE - 8..].try_into().unwrap());
+    if magic == LEGACY_BLOCK_BASED_TABLE_MAGIC {
+        return Err(invalid("the legacy format version 0 is not supported"));
+    }
+    if magic != BLOCK_BASED_TABLE_MAGIC {
+        return Err(invalid(format!(
+            "unknown magic {magic:#x}, it isn't a block-based table"
+        )));
+    }
+    let format_version =
+        u32::from_le_bytes(buf[FOOTER_SIZE - 12..FOOTER_SIZE - 8].try_into().unwrap());
+    if format_version == 0 || format_version > MAX_FORMAT_VERSION {
+        return Err(invalid(format!(
+            "format version {format_version} is not supported"
+        )));
+    }
+    let checksum_type = match buf[0] {
+        0 => ChecksumType::None,
+        1 => ChecksumType::Crc32c,
+        2 => ChecksumType::XxHash,
+        3 => ChecksumType::XxHash64,
+        4 => ChecksumType::Xxh3,
+        v => return Err(invalid(format!("unknown checksum type {v}"))),
+    };
+    let handles = &buf[1..FOOTER_SIZE - 12];
+    let mut pos = 0;
+    let metaindex = decode_handle(handles, &mut pos)?;
+    let index = decode_handle(handles, &mut pos)?;
+    Ok(Footer {
+        checksum_type,
+        metaindex,
+        index,
+        format_version,
+    })
+}
+
+/// Returns the checksum of the block data and its compression type, or `None` if the table is
+/// written without checksums.
+fn compute_checksum(checksum_type: ChecksumType, data: &[u8], last_byte: u8) -> Option<u32> {
+    match checksum_type {
+        ChecksumType::None => None,
+        ChecksumType::Crc32c => {
+            let crc = crc32c::crc32c_append(crc32c::crc32c(data), &[last_byte]);
+            Some(((crc >> 15) | (crc << 17)).wrapping_add(CRC32C_MASK_DELTA))
+        }
+        ChecksumType::XxHash => {
+            let mut hasher = twox_hash::XxHash32::with_seed(0);
+            hasher.write(data);
+            hasher.write(&[last_byte]);
+            Some(hasher.finish() as u32)
+        }
+        ChecksumType::XxHash64 => {
+            let mut hasher = twox_hash::XxHash64::with_seed(0);
+            hasher.write(data);
+            hasher.write(&[last_byte]);
+            Some(hasher.finish() as u32)
+        }
+        ChecksumType::Xxh3 => {
+            // XXH3 isn't extended with the last byte, it is mixed in instead.
+            let v = twox_hash::xxh3::hash64(data) as u32;
+            Some(v ^ (last_byte as u32).wrapping_mul(XXH3_LAST_BYTE_PRIME))
+        }
+    }
+}
+
+fn decompress(compression: u8, data: &[u8], format_version: u32) -> Result<Vec<u8>> {
+    if compression == NO_COMPRESSION {
+        return Ok(data.to_vec());
+    }
+    if format_version < 2 {
+        return Err(invalid(format!(
+            "compressed blocks of format version {format_version} are not supported"
+        )));
+    }
+    // The compressed data is prefixed with the uncompressed size.
+    let mut pos = 0;
+    let size = decode_varint32(data, &mut pos)? as usize;
+    let input = &data[pos..];
+    let contents = match compression {
+        LZ4_COMPRESSION | LZ4HC_COMPRESSION => lz4_flex::block::decompress(input, size)
+            .map_err(|e| invalid(format!("decompress lz4 block: {e}")))?,
+        ZSTD_COMPRESSION | ZSTD_NOT_FINAL_COMPRESSION => zstd::bulk::decompress(input, size)
+            .map_err(|e| invalid(format!("decompress zstd block: {e}")))?,
+        _ => {
+            return Err(invalid(format!(
+                "compression type {compression:#x} is not supported"
+            )))
+        }
+    };
+    if contents.len() != size {
+        return Err(invalid(format!(
+            "the block is decompressed to {} bytes, but {size} bytes are expected",
+            contents.len()
+        )));
+    }
+    Ok(contents)
+}
+
+/// Returns the end of the entries and the restart points of a block.
+fn block_layout(contents: &[u8]) -> Result<(usize, Vec<usize>)> {
+    let read_u32 = |pos: usize| {
+        contents
+            .get(pos..pos + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| invalid("the block is truncated"))
+    };
+    let footer_pos = contents
+        .len()
+        .checked_sub(4)
+        .ok_or_else(|| invalid("the block is truncated"))?;
+    let packed = read_u32(footer_pos)?;
+    let mut restarts_end = footer_pos;
+    if packed & (1 << 31) != 0 {
+        // The data block hash index is placed between the restarts and the footer, with the
+        // number of buckets at its end.
+        let num_buckets_pos = footer_pos
+            .checked_sub(2)
+            .ok_or_else(|| invalid("the block is truncated"))?;
+        let num_buckets =
+            u16::from_le_bytes(contents[num_buckets_pos..footer_pos].try_into().unwrap());
+        restarts_end = num_buckets_pos
+            .checked_sub(num_buckets as usize)
+            .ok_or_else(|| invalid("the block is truncated"))?;
+    }
+    let num_restarts = (packed & !(1 << 31)) as usize;
+    let entries_end = num_restarts
+        .checked_mul(4)
+        .and_then(|len| restarts_end.checked_sub(len))
+        .ok_or_else(|| invalid("the restarts exceed the block"))?;
+    let restarts = (0..num_restarts)
+        .map(|i| read_u32(entries_end + i * 4).map(|v| v as usize))
+        .collect::<Result<Vec<_>>>()?;
+    Ok((entries_end, restarts))
+}
+
+/// Decode the entries of the blocks whose values are prefixed with their lengths.
+fn decode_entries(contents: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let (entries_end, _) = block_layout(contents)?;
+    let contents = &contents[..entries_end];
+    let mut pos = 0;
+    let mut key = Vec::new();
+    let mut entries = Vec::new();
+    while pos < contents.len() {
+        let shared = decode_varint32(contents, &mut pos)? as usize;
+        let non_shared = decode_varint32(contents, &mut pos)? as usize;
+        let value_len = decode_varint32(contents, &mut pos)? as usize;
+        decode_key(contents, &mut pos, &mut key, shared, non_shared)?;
+        let value = take(contents, &mut pos, value_len)?;
+        entries.push((key.clone(), value.to_vec()));
+    }
+    Ok(entries)
+}
+
+/// Decode the entries of an index block, whose values are block handles. If the values are delta
+/// encoded, the entries have no value lengths, and only the entries at the restart points have
+/// full block handles, the others have the size deltas to their previous ones, since the blocks
+/// are continuous.
+fn decode_index_entries(
+    contents: &[u8],
+    value_is_delta_encoded: bool,
+) -> Result<Vec<(Vec<u8>, BlockHandle)>> {
+    let (entries_end, restarts) = block_layout(contents)?;
+    let contents = &contents[..entries_end];
+    let mut pos = 0;
+    let mut key = Vec::new();
+    let mut entries: Vec<(Vec<u8>, BlockHandle)> = Vec::new();
+    while pos < contents.len() {
+        let is_restart = restarts.binary_search(&pos).is_ok();
+        let shared = decode_varint32(contents, &mut pos)? as usize;
+        let non_shared = decode_varint32(contents, &mut pos)? as usize;
+        if !value_is_delta_encoded {
+            let value_len = decode_varint32(contents, &mut pos)? as usize;
+            decode_key(contents, &mut pos, &mut key, shared, non_shared)?;
+            let value = take(contents, &mut pos, value_len)?;
+            entries.push((key.clone(), decode_handle(value, &mut 0)?));
+            continue;
+        }
+
+        decode_key(contents, &mut pos, &mut key, shared, non_shared)?;
+        let handle = match entries.last() {
+            Some((_, prev)) if !is_restart => {
+                let delta = decode_varsigned64(contents, &mut pos)?;
+                let size = (prev.size as i64)
+                    .checked_add(delta)
+                    .filter(|size| *size >= 0)
+                    .ok_or_else(|| invalid("the delta of block size is out of range"))?;
+                BlockHandle {
+                    offset: prev.offset + prev.size + BLOCK_TRAILER_SIZE,
+                    size: size as u64,
+                }
+            }
+            _ => decode_handle(contents, &mut pos)?,
+        };
+        entries.push((key.clone(), handle));
+    }
+    Ok(entries)
+}
+
+fn decode_key(
+    contents: &[u8],
+    pos: &mut usize,
+    key: &mut Vec<u8>,
+    shared: usize,
+    non_shared: usize,
+) -> Result<()> {
+    if shared > key.len() {
+        return Err(invalid("the shared prefix exceeds the previous key"));
+    }
+    key.truncate(shared);
+    key.extend_from_slice(take(contents, pos, non_shared)?);
+    Ok(())
+}
+
+/// Returns the user key, the sequence and the value type of an internal key.
+fn parse_internal_key(key: &[u8]) -> Result<(&[u8], u64, u8)> {
+    let user_key_len = key
+        .len()
+        .checked_sub(8)
+        .ok_or_else(|| invalid("the internal key is too short"))?;
+    let packed = u64::from_le_bytes(key[user_key_len..].try_into().unwrap());
+    Ok((&key[..user_key_len], packed >> 8, packed as u8))
+}
+
+/// Internal keys are ordered by user keys ascending, then sequences and types descending.
+fn compare_internal_key(a: &SstEntry, b: &SstEntry) -> std::cmp::Ordering {
+    a.user_key
+        .cmp(&b.user_key)
+        .then_with(|| (b.sequence, b.value_type).cmp(&(a.sequence, a.value_type)))
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let end = pos
+        .checked_add(len)
+        .filter(|end| *end <= data.len())
+        .ok_or_else(|| invalid("the entry exceeds the block"))?;
+    let slice = &data[*pos..end];
+    *pos = end;
+    Ok(slice)
+}
+
+fn decode_handle(data: &[u8], pos: &mut usize) -> Result<BlockHandle> {
+    let offset = decode_varint64(data, pos)?;
+    let size = decode_varint64(data, pos)?;
+    Ok(BlockHandle { offset, size })
+}
+
+fn decode_varint64(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data
+            .get(*pos)
+            .ok_or_else(|| invalid("the varint is truncated"))?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("the varint is too long"))
+}
+
+fn decode_varint32(data: &[u8], pos: &mut usize) -> Result<u32> {
+    u32::try_from(decode_varint64(data, pos)?).map_err(|_| invalid("the varint32 overflows"))
+}
+
+fn decode_varsigned64(data: &[u8], pos: &mut usize) -> Result<i64> {
+    let v = decode_varint64(data, pos)?;
+    Ok((v >> 1) as i64 ^ -((v & 1) as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use rocksdb::{
+        BlockBasedIndexType, BlockBasedOptions, DBCompressionType, Options, SstFileWriter,
+    };
+    use tempdir::TempDir;
+
+    use super::*;
+
+    const NUM_KEYS: usize = 2000;
+
+    fn write_sst(path: &Path, compression: DBCompressionType) {
+        let mut blk_opts = BlockBasedOptions::default();
+        blk_opts.set_index_type(BlockBasedIndexType::TwoLevelIndexSearch);
+        blk_opts.set_block_size(256);
+        let mut opts = Options::default();
+        opts.set_compression_type(compression);
+        opts.set_block_based_table_factory(&blk_opts);
+
+        let mut writer = SstFileWriter::create(&opts);
+        writer.open(path).unwrap();
+        for i in 0..NUM_KEYS {
+            let key = format!("key-{i:06}");
+            writer
+                .put(key.as_bytes(), key.repeat(4).as_bytes())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn read_and_verify_sst() {
+        let dir = TempDir::new("read_and_verify_sst").unwrap();
+        for (name, compression) in [
+            ("none.sst", DBCompressionType::None),
+            ("lz4.sst", DBCompressionType::Lz4),
+            ("zstd.sst", DBCompressionType::Zstd),
+        ] {
+            let path = dir.path().join(name);
+            write_sst(&path, compression);
+
+            let reader = SstReader::open(&path).unwrap();
+            assert_eq!(reader.property_u64(PROP_NUM_ENTRIES), Some(NUM_KEYS as u64));
+            assert!(reader.index().unwrap().len() > 1);
+
+            let mut keys = vec![];
+            reader
+                .scan(b"key-000100", b"key-000200", |entry| {
+                    assert_eq!(entry.value, entry.user_key.repeat(4));
+                    keys.push(entry.user_key);
+                    true
+                })
+                .unwrap();
+            assert_eq!(keys.len(), 100);
+            assert_eq!(keys[0], b"key-000100".to_vec());
+            assert_eq!(keys[99], b"key-000199".to_vec());
+
+            let report = reader.verify().unwrap();
+            assert_eq!(report.entries, NUM_KEYS as u64);
+            assert!(report.checksum_verified);
+        }
+    }
+
+    #[test]
+    fn detect_corrupted_block() {
+        let dir = TempDir::new("detect_corrupted_block").unwrap();
+        let path = dir.path().join("corrupted.sst");
+        write_sst(&path, DBCompressionType::None);
+
+        // Flip a byte of the first data block.
+        let mut contents = std::fs::read(&path).unwrap();
+        contents[16] ^= 0xff;
+        std::fs::write(&path, contents).unwrap();
+
+        let reader = SstReader::open(&path).unwrap();
+        assert!(matches!(reader.verify(), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn decode_varints() {
+        let mut pos = 0;
+        assert_eq!(decode_varint64(&[0xac, 0x02], &mut pos).unwrap(), 300);
+        assert_eq!(pos, 2);
+        assert_eq!(decode_varsigned64(&[0x03], &mut 0).unwrap(), -2);
+        assert_eq!(decode_varsigned64(&[0x04], &mut 0).unwrap(), 2);
+        assert!(decode_varint64(&[0x80], &mut 0).is_err());
+    }
+}
@@ -0,0 +1,720 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The standalone mode serves a single group backed directly by the engine, without raft and
+//! root coordination. The databases and collections are saved in a catalog shard of the group,
+//! each collection is served by a shard covering the whole key space. It is intended for local
+//! development and CI, the clients detect it by `GetCapabilities` and skip the router.
+
+use std::sync::Arc;
+
+use engula_api::{
+    server::v1::{
+        group_request_union::Request, group_response_union::Response, node_server::NodeServer,
+        root_server::RootServer, *,
+    },
+    v1::*,
+};
+use prost::Message;
+use tonic::Status;
+use tracing::info;
+
+use crate::{
+    bootstrap::{
+        open_engine, FIRST_NODE_ID, FIRST_REPLICA_ID, INIT_USER_GROUP_ID, SHARD_MAX, SHARD_MIN,
+    },
+    node::{
+        engine::{GroupEngine, SnapshotMode, WriteBatch, WriteStates, LOCAL_COLLECTION_ID},
+        migrate::ShardChunkStream,
+        replica::{eval, fsm::SHARD_UPDATE_DELTA, ExecCtx},
+        watch::KeyWatchStream,
+    },
+    root::Watcher,
+    runtime::{Executor, Shutdown},
+    serverpb::v1::{ApplyState, EvalResult},
+    Config, Error, Result,
+};
+
+const STANDALONE_GROUP_ID: u64 = INIT_USER_GROUP_ID;
+const STANDALONE_REPLICA_ID: u64 = FIRST_REPLICA_ID;
+const STANDALONE_NODE_ID: u64 = FIRST_NODE_ID;
+
+/// The shard (and the collection) saving the catalog of databases and collections.
+const CATALOG_SHARD_ID: u64 = LOCAL_COLLECTION_ID + 1;
+/// The ids of databases and collections are allocated from here, the shard of a collection shares
+/// the id of the collection.
+const FIRST_USER_ID: u64 = 1024;
+
+const CATALOG_NEXT_ID_KEY: &[u8] = b"next_id";
+const CATALOG_DATABASE_PREFIX: &[u8] = b"database/";
+const CATALOG_COLLECTION_PREFIX: &[u8] = b"collection/";
+
+/// The main entrance of the standalone mode, see [`crate::Config::standalone`].
+pub fn run(config: Config, executor: Executor, shutdown: Shutdown) -> Result<()> {
+    executor.block_on(async {
+        crate::layout::upgrade_layout(&config.root_dir)?;
+        let raw_db = Arc::new(open_engine(&config.db, config.root_dir.join("db"))?);
+        let server = StandaloneServer::open(&config, raw_db).await?;
+
+        info!(
+            "standalone server starts serving requests at {}",
+            config.addr
+        );
+        bootstrap_services(&config, server, shutdown).await?;
+
+        // All writes are persisted with WAL, so the memtables needn't be flushed here.
+        info!("standalone server stops serving requests");
+        Ok(())
+    })
+}
+
+async fn bootstrap_services(
+    config: &Config,
+    server: StandaloneServer,
+    shutdown: Shutdown,
+) -> Result<()> {
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    let listener = TcpListener::bind(&config.addr).await?;
+    let listener = TcpListenerStream::new(listener);
+    let server = Server::builder()
+        .add_service(NodeServer::new(server.clone()))
+        .add_service(RootServer::new(server))
+        .serve_with_incoming(listener);
+
+    crate::runtime::select! {
+        res = server => { res? }
+        _ = shutdown => {}
+    };
+    Ok(())
+}
+
+#[derive(Clone)]
+struct StandaloneServer {
+    node: NodeDesc,
+    engine: GroupEngine,
+    /// Serializes the writes, and holds the index of the last write.
+    applied_index: Arc<tokio::sync::Mutex<u64>>,
+}
+
+impl StandaloneServer {
+    async fn open(config: &Config, raw_db: Arc<rocksdb::DB>) -> Result<Self> {
+        let cfg = &config.node.engine;
+        let engine = match GroupEngine::open(
+            cfg,
+            raw_db.clone(),
+            STANDALONE_GROUP_ID,
+            STANDALONE_REPLICA_ID,
+        )
+        .await?
+        {
+            Some(engine) => engine,
+            None => {
+                GroupEngine::create(cfg, raw_db, STANDALONE_GROUP_ID, STANDALONE_REPLICA_ID).await?
+            }
+        };
+        let mut applied_index = engine.flushed_apply_state()?.index;
+
+        let mut desc = engine.descriptor();
+        let mut updated = false;
+        if desc.replicas.is_empty() {
+            desc.replicas.push(ReplicaDesc {
+                id: STANDALONE_REPLICA_ID,
+                node_id: STANDALONE_NODE_ID,
+                role: ReplicaRole::Voter as i32,
+            });
+            desc.epoch += 1;
+            updated = true;
+        }
+        if !desc.shards.iter().any(|s| s.id == CATALOG_SHARD_ID) {
+            desc.shards.push(whole_range_shard(CATALOG_SHARD_ID));
+            desc.epoch += SHARD_UPDATE_DELTA;
+            updated = true;
+        }
+        if updated {
+            commit(
+                &engine,
+                &mut applied_index,
+                WriteBatch::default(),
+                Some(desc),
+            )?;
+        }
+
+        Ok(StandaloneServer {
+            node: NodeDesc {
+                id: STANDALONE_NODE_ID,
+                addr: config.addr.clone(),
+                ..Default::default()
+            },
+            engine,
+            applied_index: Arc::new(tokio::sync::Mutex::new(applied_index)),
+        })
+    }
+
+    async fn execute(&self, request: &GroupRequest) -> Result<GroupResponse> {
+        if request.group_id != STANDALONE_GROUP_ID {
+            return Err(Error::GroupNotFound(request.group_id));
+        }
+        let request = request
+            .request
+            .as_ref()
+            .and_then(|r| r.request.as_ref())
+            .ok_or_else(|| Error::InvalidArgument("GroupRequest::request".into()))?;
+
+        let mut exec_ctx = ExecCtx::with_epoch(self.engine.descriptor().epoch);
+        exec_ctx.group_id = STANDALONE_GROUP_ID;
+        exec_ctx.replica_id = STANDALONE_REPLICA_ID;
+        let resp = match request {
+            Request::Get(req) => {
+                let value = eval::get(&exec_ctx, &self.engine, req).await?;
+                Response::Get(GetResponse { value })
+            }
+            Request::PrefixList(req) => {
+                Response::PrefixList(eval::prefix_list(&self.engine, req).await?)
+            }
+            Request::Sample(req) => Response::Sample(eval::sample(&self.engine, req).await?),
+            Request::Scan(req) => Response::Scan(eval::scan(&self.engine, req).await?),
+            _ => self.execute_write(&exec_ctx, request).await?,
+        };
+        Ok(GroupResponse {
+            response: Some(GroupResponseUnion {
+                response: Some(resp),
+            }),
+            error: None,
+        })
+    }
+
+    async fn execute_write(&self, exec_ctx: &ExecCtx, request: &Request) -> Result<Response> {
+        let mut applied_index = self.applied_index.lock().await;
+        let (eval_result, mut resp) = match request {
+            Request::Put(req) => {
+                let eval_result = eval::put(exec_ctx, &self.engine, req).await?;
+                (Some(eval_result), Response::Put(PutResponse::default()))
+            }
+            Request::Delete(req) => {
+                let eval_result = eval::delete(exec_ctx, &self.engine, req).await?;
+                (Some(eval_result), Response::Delete(DeleteResponse {}))
+            }
+            Request::DeleteRange(req) => {
+                let eval_result = eval::delete_range(exec_ctx, req)?;
+                (
+                    Some(eval_result),
+                    Response::DeleteRange(DeleteRangeResponse {}),
+                )
+            }
+            Request::Rename(req) => {
+                let eval_result = eval::rename(exec_ctx, &self.engine, req).await?;
+                let resp = RenameResponse {
+                    renamed: eval_result.is_some(),
+                };
+                (eval_result, Response::Rename(resp))
+            }
+            Request::BatchWrite(req) => {
+                let eval_result = eval::batch_write(exec_ctx, &self.engine, req).await?;
+                (eval_result, Response::BatchWrite(BatchWriteResponse {}))
+            }
+            _ => {
+                return Err(Error::InvalidArgument(
+                    "the request is not supported in standalone mode".into(),
+                ))
+            }
+        };
+
+        if let Some(eval_result) = eval_result {
+            self.apply(&mut applied_index, eval_result)?;
+            if let Response::Put(put) = &mut resp {
+                put.epoch = exec_ctx.epoch;
+                put.fencing_token = *applied_index;
+                put.group_id = STANDALONE_GROUP_ID;
+            }
+        }
+        Ok(resp)
+    }
+
+    /// Apply the evaluated result to the engine, like what `GroupStateMachine` does for the
+    /// committed proposals.
+    fn apply(&self, applied_index: &mut u64, eval_result: EvalResult) -> Result<()> {
+        let epoch = self.engine.descriptor().epoch;
+        if epoch < eval_result.epoch_at_least {
+            return Err(Error::ConditionNotMet(format!(
+                "group {STANDALONE_GROUP_ID} epoch {epoch} is less than {}",
+                eval_result.epoch_at_least
+            )));
+        }
+
+        let mut wb = eval_result
+            .batch
+            .map(|wb| WriteBatch::new(&wb.data))
+            .unwrap_or_default();
+        if let Some(op) = eval_result.op {
+            if let Some(delete_range) = op.delete_range {
+                self.engine.delete_range(
+                    delete_range.shard_id,
+                    &delete_range.start,
+                    &delete_range.end,
+                )?;
+                commit(&self.engine, applied_index, wb, None)?;
+                // The range tombstone is written without WAL.
+                return self.engine.flush_until(*applied_index);
+            }
+            if let Some(rename) = op.rename {
+                self.engine.rename(
+                    &mut wb,
+                    rename.shard_id,
+                    &rename.from,
+                    &rename.to,
+                    rename.only_if_absent,
+                    eval::FLAT_KEY_VERSION,
+                )?;
+            }
+        }
+        commit(&self.engine, applied_index, wb, None)
+    }
+
+    async fn handle_admin(&self, req: AdminRequestUnion) -> Result<AdminResponseUnion> {
+        use admin_request_union::Request;
+        use admin_response_union::Response;
+
+        let req = req
+            .request
+            .ok_or_else(|| Error::InvalidArgument("AdminRequestUnion".into()))?;
+        let res = match req {
+            Request::CreateDatabase(req) => {
+                let database = Some(self.create_database(req.name).await?);
+                Response::CreateDatabase(CreateDatabaseResponse { database })
+            }
+            Request::DeleteDatabase(req) => {
+                self.delete_database(&req.name).await?;
+                Response::DeleteDatabase(DeleteDatabaseResponse {})
+            }
+            Request::GetDatabase(req) => {
+                let database = self.get_database(&req.name)?;
+                Response::GetDatabase(GetDatabaseResponse { database })
+            }
+            Request::ListDatabases(_) => {
+                let databases = self.list_catalog(CATALOG_DATABASE_PREFIX)?;
+                Response::ListDatabases(ListDatabasesResponse { databases })
+            }
+            Request::CreateCollection(req) => {
+                let collection = Some(self.create_collection(req).await?);
+                Response::CreateCollection(CreateCollectionResponse { collection })
+            }
+            Request::DeleteCollection(req) => {
+                let db = self.must_get_database(req.database)?;
+                self.delete_collection(&db, &req.name).await?;
+                Response::DeleteCollection(DeleteCollectionResponse {})
+            }
+            Request::GetCollection(req) => {
+                let db = self.must_get_database(req.database)?;
+                let collection = self.get_catalog(&collection_key(db.id, &req.name))?;
+                Response::GetCollection(GetCollectionResponse { collection })
+            }
+            Request::ListCollections(req) => {
+                let db = self.must_get_database(req.database)?;
+                let collections = self.list_catalog(&collection_key(db.id, ""))?;
+                Response::ListCollections(ListCollectionsResponse { collections })
+            }
+            Request::UpdateDatabase(_) | Request::UpdateCollection(_) => {
+                return Err(Error::InvalidArgument(
+                    "the request is not supported in standalone mode".into(),
+                ));
+            }
+        };
+        Ok(AdminResponseUnion {
+            response: Some(res),
+        })
+    }
+
+    async fn create_database(&self, name: String) -> Result<DatabaseDesc> {
+        let mut applied_index = self.applied_index.lock().await;
+        if self.get_database(&name)?.is_some() {
+            return Err(Error::AlreadyExists(format!("database {name}")));
+        }
+
+        let mut wb = WriteBatch::default();
+        let desc = DatabaseDesc {
+            id: self.next_id(&mut wb).await?,
+            name,
+        };
+        self.put_catalog(&mut wb, &database_key(&desc.name), &desc)?;
+        commit(&self.engine, &mut applied_index, wb, None)?;
+        Ok(desc)
+    }
+
+    async fn delete_database(&self, name: &str) -> Result<()> {
+        let db = self
+            .get_database(name)?
+            .ok_or_else(|| Error::DatabaseNotFound(name.to_owned()))?;
+        let collections: Vec<CollectionDesc> = self.list_catalog(&collection_key(db.id, ""))?;
+        for collection in collections {
+            self.delete_collection(&db, &collection.name).await?;
+        }
+
+        let mut applied_index = self.applied_index.lock().await;
+        let mut wb = WriteBatch::default();
+        self.engine.delete(
+            &mut wb,
+            CATALOG_SHARD_ID,
+            &database_key(name),
+            eval::FLAT_KEY_VERSION,
+        )?;
+        commit(&self.engine, &mut applied_index, wb, None)
+    }
+
+    async fn create_collection(&self, req: CreateCollectionRequest) -> Result<CollectionDesc> {
+        use collection_desc::{HashPartition, Partition, RangePartition};
+        use create_collection_request::Partition as ReqPartition;
+
+        let db = self.must_get_database(req.database)?;
+        let mut applied_index = self.applied_index.lock().await;
+        let key = collection_key(db.id, &req.name);
+        if self.get_catalog::<CollectionDesc>(&key)?.is_some() {
+            return Err(Error::AlreadyExists(format!("collection {}", req.name)));
+        }
+
+        // The partition is kept as the metadata only, a shard covering the whole key space
+        // serves the collection.
+        let mut wb = WriteBatch::default();
+        let collection = CollectionDesc {
+            id: self.next_id(&mut wb).await?,
+            name: req.name,
+            db: db.id,
+            partition: req.partition.map(|p| match p {
+                ReqPartition::Hash(hash) => Partition::Hash(HashPartition { slots: hash.slots }),
+                ReqPartition::Range(_) => Partition::Range(RangePartition {}),
+            }),
+            max_key_size: req.max_key_size,
+            max_value_size: req.max_value_size,
+            leader_zone: req.leader_zone,
+            webhook_url: req.webhook_url,
+            read_replica_zones: req.read_replica_zones,
+        };
+        self.put_catalog(&mut wb, &key, &collection)?;
+
+        let mut desc = self.engine.descriptor();
+        desc.shards.push(whole_range_shard(collection.id));
+        desc.epoch += SHARD_UPDATE_DELTA;
+        commit(&self.engine, &mut applied_index, wb, Some(desc))?;
+        info!(
+            "standalone create collection {} of database {}, id {}",
+            collection.name, db.name, collection.id
+        );
+        Ok(collection)
+    }
+
+    async fn delete_collection(&self, db: &DatabaseDesc, name: &str) -> Result<()> {
+        let mut applied_index = self.applied_index.lock().await;
+        let key = collection_key(db.id, name);
+        let collection = match self.get_catalog::<CollectionDesc>(&key)? {
+            Some(collection) => collection,
+            None => return Ok(()),
+        };
+
+        self.engine.delete_range(collection.id, &[], &[])?;
+        let mut wb = WriteBatch::default();
+        self.engine
+            .delete(&mut wb, CATALOG_SHARD_ID, &key, eval::FLAT_KEY_VERSION)?;
+        let mut desc = self.engine.descriptor();
+        desc.shards.retain(|s| s.id != collection.id);
+        desc.epoch += SHARD_UPDATE_DELTA;
+        commit(&self.engine, &mut applied_index, wb, Some(desc))?;
+        // The range tombstone is written without WAL.
+        self.engine.flush_until(*applied_index)
+    }
+
+    fn get_database(&self, name: &str) -> Result<Option<DatabaseDesc>> {
+        self.get_catalog(&database_key(name))
+    }
+
+    fn must_get_database(&self, database: Option<DatabaseDesc>) -> Result<DatabaseDesc> {
+        let name = database
+            .map(|d| d.name)
+            .ok_or_else(|| Error::InvalidArgument("database is required".into()))?;
+        self.get_database(&name)?
+            .ok_or(Error::DatabaseNotFound(name))
+    }
+
+    /// Allocate an id for database or collection, the caller should hold the write lock.
+    async fn next_id(&self, wb: &mut WriteBatch) -> Result<u64> {
+        let id = match self
+            .engine
+            .get(CATALOG_SHARD_ID, CATALOG_NEXT_ID_KEY)
+            .await?
+        {
+            Some(value) => {
+                let bytes = value
+                    .try_into()
+                    .map_err(|_| Error::InvalidData("catalog next id".into()))?;
+                u64::from_le_bytes(bytes)
+            }
+            None => FIRST_USER_ID,
+        };
+        self.engine.put(
+            wb,
+            CATALOG_SHARD_ID,
+            CATALOG_NEXT_ID_KEY,
+            &(id + 1).to_le_bytes(),
+            eval::FLAT_KEY_VERSION,
+        )?;
+        Ok(id)
+    }
+
+    fn put_catalog<T: Message>(&self, wb: &mut WriteBatch, key: &[u8], value: &T) -> Result<()> {
+        self.engine.put(
+            wb,
+            CATALOG_SHARD_ID,
+            key,
+            &value.encode_to_vec(),
+            eval::FLAT_KEY_VERSION,
+        )
+    }
+
+    fn get_catalog<T: Message + Default>(&self, key: &[u8]) -> Result<Option<T>> {
+        let mut snapshot = self
+            .engine
+            .snapshot(CATALOG_SHARD_ID, SnapshotMode::Key { key })?;
+        if let Some(mvcc) = snapshot.iter().next() {
+            for entry in mvcc? {
+                let entry = entry?;
+                if let Some(value) = entry.value() {
+                    let value =
+                        T::decode(value).map_err(|_| Error::InvalidData("catalog".into()))?;
+                    return Ok(Some(value));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn list_catalog<T: Message + Default>(&self, prefix: &[u8]) -> Result<Vec<T>> {
+        let mut snapshot = self
+            .engine
+            .snapshot(CATALOG_SHARD_ID, SnapshotMode::Prefix { key: prefix })?;
+        let mut values = Vec::new();
+        for mvcc in snapshot.iter() {
+            for entry in mvcc? {
+                let entry = entry?;
+                if let Some(value) = entry.value() {
+                    values
+                        .push(T::decode(value).map_err(|_| Error::InvalidData("catalog".into()))?);
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[tonic::async_trait]
+impl root_server::Root for StandaloneServer {
+    type WatchStream = Watcher;
+
+    async fn admin(
+        &self,
+        req: tonic::Request<AdminRequest>,
+    ) -> std::result::Result<tonic::Response<AdminResponse>, Status> {
+        let req = req
+            .into_inner()
+            .request
+            .ok_or_else(|| Error::InvalidArgument("AdminRequest".into()))?;
+        let response = Some(self.handle_admin(req).await?);
+        Ok(tonic::Response::new(AdminResponse { response }))
+    }
+
+    async fn watch(
+        &self,
+        _req: tonic::Request<WatchRequest>,
+    ) -> std::result::Result<tonic::Response<Self::WatchStream>, Status> {
+        Err(unsupported("watch"))
+    }
+
+    async fn join(
+        &self,
+        _req: tonic::Request<JoinNodeRequest>,
+    ) -> std::result::Result<tonic::Response<JoinNodeResponse>, Status> {
+        Err(unsupported("join"))
+    }
+
+    async fn report(
+        &self,
+        _req: tonic::Request<ReportRequest>,
+    ) -> std::result::Result<tonic::Response<ReportResponse>, Status> {
+        Err(unsupported("report"))
+    }
+
+    async fn alloc_replica(
+        &self,
+        _req: tonic::Request<AllocReplicaRequest>,
+    ) -> std::result::Result<tonic::Response<AllocReplicaResponse>, Status> {
+        Err(unsupported("alloc_replica"))
+    }
+
+    async fn get_capabilities(
+        &self,
+        _req: tonic::Request<GetCapabilitiesRequest>,
+    ) -> std::result::Result<tonic::Response<GetCapabilitiesResponse>, Status> {
+        Ok(tonic::Response::new(GetCapabilitiesResponse {
+            standalone: true,
+            node: Some(self.node.clone()),
+            group: Some(self.engine.descriptor()),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl node_server::Node for StandaloneServer {
+    type PullStream = ShardChunkStream;
+    type WatchKeyStream = KeyWatchStream;
+
+    async fn batch(
+        &self,
+        request: tonic::Request<BatchRequest>,
+    ) -> std::result::Result<tonic::Response<BatchResponse>, Status> {
+        let request = request.into_inner();
+        let mut responses = Vec::with_capacity(request.requests.len());
+        for req in &request.requests {
+            let resp = self.execute(req).await.unwrap_or_else(|err| GroupResponse {
+                response: None,
+                error: Some(err.into()),
+            });
+            responses.push(resp);
+        }
+        Ok(tonic::Response::new(BatchResponse { responses }))
+    }
+
+    async fn get_root(
+        &self,
+        _request: tonic::Request<GetRootRequest>,
+    ) -> std::result::Result<tonic::Response<GetRootResponse>, Status> {
+        Err(unsupported("get_root"))
+    }
+
+    async fn create_replica(
+        &self,
+        _request: tonic::Request<CreateReplicaRequest>,
+    ) -> std::result::Result<tonic::Response<CreateReplicaResponse>, Status> {
+        Err(unsupported("create_replica"))
+    }
+
+    async fn remove_replica(
+        &self,
+        _request: tonic::Request<RemoveReplicaRequest>,
+    ) -> std::result::Result<tonic::Response<RemoveReplicaResponse>, Status> {
+        Err(unsupported("remove_replica"))
+    }
+
+    async fn root_heartbeat(
+        &self,
+        _request: tonic::Request<HeartbeatRequest>,
+    ) -> std::result::Result<tonic::Response<HeartbeatResponse>, Status> {
+        Err(unsupported("root_heartbeat"))
+    }
+
+    async fn migrate(
+        &self,
+        _request: tonic::Request<MigrateRequest>,
+    ) -> std::result::Result<tonic::Response<MigrateResponse>, Status> {
+        Err(unsupported("migrate"))
+    }
+
+    async fn pull(
+        &self,
+        _request: tonic::Request<PullRequest>,
+    ) -> std::result::Result<tonic::Response<Self::PullStream>, Status> {
+        Err(unsupported("pull"))
+    }
+
+    async fn forward(
+        &self,
+        _request: tonic::Request<ForwardRequest>,
+    ) -> std::result::Result<tonic::Response<ForwardResponse>, Status> {
+        Err(unsupported("forward"))
+    }
+
+    async fn collect_checksum(
+        &self,
+        _request: tonic::Request<CollectChecksumRequest>,
+    ) -> std::result::Result<tonic::Response<CollectChecksumResponse>, Status> {
+        Err(unsupported("collect_checksum"))
+    }
+
+    async fn watch_key(
+        &self,
+        _request: tonic::Request<WatchKeyRequest>,
+    ) -> std::result::Result<tonic::Response<Self::WatchKeyStream>, Status> {
+        Err(unsupported("watch_key"))
+    }
+}
+
+/// Commit the writes and the descriptor with the next apply index. The WAL is enabled, since
+/// there are no raft logs to replay.
+fn commit(
+    engine: &GroupEngine,
+    applied_index: &mut u64,
+    wb: WriteBatch,
+    descriptor: Option<GroupDesc>,
+) -> Result<()> {
+    let index = *applied_index + 1;
+    let states = WriteStates {
+        apply_state: Some(ApplyState { index, term: 0 }),
+        descriptor,
+        ..Default::default()
+    };
+    engine.commit(wb, states, true)?;
+    *applied_index = index;
+    Ok(())
+}
+
+fn whole_range_shard(id: u64) -> ShardDesc {
+    ShardDesc {
+        id,
+        collection_id: id,
+        partition: Some(shard_desc::Partition::Range(shard_desc::RangePartition {
+            start: SHARD_MIN.clone(),
+            end: SHARD_MAX.clone(),
+        })),
+    }
+}
+
+fn database_key(name: &str) -> Vec<u8> {
+    [CATALOG_DATABASE_PREFIX, name.as_bytes()].concat()
+}
+
+fn collection_key(db_id: u64, name: &str) -> Vec<u8> {
+    [
+        CATALOG_COLLECTION_PREFIX,
+        &db_id.to_be_bytes(),
+        b"/",
+        name.as_bytes(),
+    ]
+    .concat()
+}
+
+fn unsupported(rpc: &str) -> Status {
+    Status::unimplemented(format!("{rpc} is not supported in standalone mode"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalog_keys() {
+        assert!(collection_key(1, "").starts_with(CATALOG_COLLECTION_PREFIX));
+        assert!(collection_key(1, "co").starts_with(&collection_key(1, "")));
+        assert!(!collection_key(12, "co").starts_with(&collection_key(1, "")));
+        assert!(!database_key("db").starts_with(CATALOG_COLLECTION_PREFIX));
+    }
+}
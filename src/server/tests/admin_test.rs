@@ -200,6 +200,128 @@ fn admin_basic() {
     })
 }
 
+#[test]
+fn root_lease_held_by_one_node() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("admin-root-lease");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let addrs = nodes.values().cloned().collect::<Vec<_>>();
+        loop {
+            let mut leases = Vec::new();
+            for addr in &addrs {
+                let resp = reqwest::get(format!("http://{addr}/admin/root_lease"))
+                    .await
+                    .unwrap();
+                let lease: serde_json::Value = resp.json().await.unwrap();
+                if lease["is_leader"].as_bool().unwrap() {
+                    leases.push(lease);
+                }
+            }
+            assert!(leases.len() <= 1, "dueling roots {leases:?}");
+            if let Some(lease) = leases.pop() {
+                assert!(lease["term"].as_u64().unwrap() > 0);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+}
+
+#[test]
+fn pprof_endpoints_gated_by_config() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("admin-pprof-disabled");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(1).await;
+        let addr = nodes.values().next().unwrap();
+        let resp = reqwest::get(format!("http://{addr}/debug/pprof/profile?seconds=1"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let mut ctx = TestContext::new("admin-pprof-enabled");
+        ctx.disable_all_balance();
+        ctx.enable_pprof();
+        let nodes = ctx.bootstrap_servers(1).await;
+        let addr = nodes.values().next().unwrap();
+        let resp = reqwest::get(format!("http://{addr}/debug/pprof/profile?seconds=1"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        assert!(resp.text().await.unwrap().contains("<svg"));
+
+        let resp = reqwest::get(format!(
+            "http://{addr}/debug/pprof/profile?seconds=1&format=proto"
+        ))
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        assert!(!resp.bytes().await.unwrap().is_empty());
+    })
+}
+
+#[test]
+fn topology_with_version() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("admin-topology");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let addrs = nodes.values().cloned().collect::<Vec<_>>();
+        let root_addr = find_root(addrs).await;
+
+        let url = format!("http://{root_addr}/admin/topology");
+        let resp = reqwest::get(&url).await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        let etag = resp.headers()[reqwest::header::ETAG]
+            .to_str()
+            .unwrap()
+            .trim_matches('"')
+            .to_owned();
+        let topology: diagnosis::Topology = resp.json().await.unwrap();
+        assert_eq!(topology.nodes.len(), 3);
+        assert!(topology.groups.iter().any(|g| g.id == 0));
+        assert!(!topology.shards.is_empty());
+
+        let resp = reqwest::get(format!("{url}?version={etag}")).await.unwrap();
+        if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+            assert!(resp.bytes().await.unwrap().is_empty());
+        } else {
+            // The topology is changed by heartbeats in the meantime.
+            assert_eq!(resp.status(), reqwest::StatusCode::OK);
+            let quoted = format!("\"{etag}\"");
+            assert_ne!(resp.headers()[reqwest::header::ETAG], quoted.as_str());
+        }
+    })
+}
+
+#[test]
+fn liveness_and_readiness_probes() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("admin-probes");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        for addr in nodes.values() {
+            let resp = reqwest::get(format!("http://{addr}/admin/health"))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+            // The replicas might be catching up right after bootstrapping.
+            loop {
+                let resp = reqwest::get(format!("http://{addr}/admin/ready"))
+                    .await
+                    .unwrap();
+                if resp.status() == reqwest::StatusCode::OK {
+                    break;
+                }
+                assert_eq!(resp.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    })
+}
+
 fn collection_key(database_id: u64, collection_name: &str) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + collection_name.len());
     buf.extend_from_slice(database_id.to_le_bytes().as_slice());
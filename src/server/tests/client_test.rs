@@ -15,7 +15,12 @@ mod helper;
 
 use std::time::Duration;
 
-use engula_client::{AppError, ClientOptions, Partition};
+use engula_api::v1::{WriteConcern, WriteCondition};
+use engula_client::{
+    AppError, BatchOptions, BatchWriteOp, ClientOptions, EngulaClient, KeyOutcome, Partition,
+    ReadConsistency,
+};
+use futures::StreamExt;
 use tracing::info;
 
 use crate::helper::{client::*, context::*, init::setup_panic_hook, runtime::*};
@@ -36,6 +41,7 @@ fn to_unreachable_peers() {
         let opts = ClientOptions {
             connect_timeout: Some(Duration::from_millis(50)),
             timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
         };
         let client = c.app_client_with_options(opts).await;
         let db = client.create_database("test_db".to_string()).await.unwrap();
@@ -182,3 +188,747 @@ fn request_to_offline_leader() {
         }
     })
 }
+
+#[test]
+fn put_and_get_large_value_in_chunks() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__put_and_get_large_value_in_chunks");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let opts = ClientOptions {
+            chunk_size: Some(1024),
+            ..Default::default()
+        };
+        let client = c.app_client_with_options(opts).await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        let k = "key".as_bytes().to_vec();
+        let v = (0..10000u32).map(|v| v as u8).collect::<Vec<_>>();
+        co.put(k.clone(), v.clone()).await.unwrap();
+        assert_eq!(co.get(k.clone()).await.unwrap(), Some(v));
+
+        // Replace with a small value.
+        co.put(k.clone(), b"value".to_vec()).await.unwrap();
+        assert_eq!(co.get(k.clone()).await.unwrap(), Some(b"value".to_vec()));
+
+        co.put(k.clone(), vec![1u8; 4096]).await.unwrap();
+        co.delete(k.clone()).await.unwrap();
+        assert_eq!(co.get(k).await.unwrap(), None);
+    });
+}
+
+#[test]
+fn scan_key_range() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__scan_key_range");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let opts = ClientOptions {
+            chunk_size: Some(1024),
+            ..Default::default()
+        };
+        let client = c.app_client_with_options(opts).await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Range))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+        for i in 0..20u32 {
+            let key = format!("key-{i:02}").into_bytes();
+            co.put(key, i.to_be_bytes().to_vec()).await.unwrap();
+        }
+        // The chunks of large values are invisible to scans.
+        let large_value = vec![1u8; 4096];
+        co.put(b"key-10".to_vec(), large_value.clone())
+            .await
+            .unwrap();
+
+        let kvs = co.scan(vec![], vec![], 100).await.unwrap();
+        assert_eq!(kvs.len(), 20);
+        assert!(kvs.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(kvs[10], (b"key-10".to_vec(), large_value));
+
+        let kvs = co
+            .scan(b"key-05".to_vec(), b"key-15".to_vec(), 3)
+            .await
+            .unwrap();
+        let keys = kvs.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![b"key-05".to_vec(), b"key-06".to_vec(), b"key-07".to_vec()]
+        );
+    });
+}
+
+#[test]
+fn scan_key_range_across_hash_shards() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__scan_key_range_across_hash_shards");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+        for i in 0..20u32 {
+            let key = format!("key-{i:02}").into_bytes();
+            co.put(key, i.to_be_bytes().to_vec()).await.unwrap();
+        }
+
+        let kvs = co.scan(vec![], vec![], 100).await.unwrap();
+        assert_eq!(kvs.len(), 20);
+        assert!(kvs.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let kvs = co
+            .scan(b"key-05".to_vec(), b"key-15".to_vec(), 3)
+            .await
+            .unwrap();
+        let keys = kvs.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![b"key-05".to_vec(), b"key-06".to_vec(), b"key-07".to_vec()]
+        );
+    });
+}
+
+#[test]
+fn concurrent_writes_with_auto_batcher() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__concurrent_writes_with_auto_batcher");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let opts = ClientOptions {
+            batch_window: Some(Duration::from_millis(2)),
+            ..Default::default()
+        };
+        let client = c.app_client_with_options(opts).await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        let puts = (0..100u32).map(|i| {
+            let co = co.clone();
+            async move {
+                co.put(i.to_be_bytes().to_vec(), i.to_le_bytes().to_vec())
+                    .await
+            }
+        });
+        for result in futures::future::join_all(puts).await {
+            result.unwrap();
+        }
+        for i in 0..100u32 {
+            let value = co.get(i.to_be_bytes().to_vec()).await.unwrap();
+            assert_eq!(value, Some(i.to_le_bytes().to_vec()));
+        }
+
+        let deletes = (0..50u32).map(|i| {
+            let co = co.clone();
+            async move { co.delete(i.to_be_bytes().to_vec()).await }
+        });
+        for result in futures::future::join_all(deletes).await {
+            result.unwrap();
+        }
+        for i in 0..100u32 {
+            let value = co.get(i.to_be_bytes().to_vec()).await.unwrap();
+            assert_eq!(value.is_some(), i >= 50);
+        }
+    });
+}
+
+#[test]
+fn put_and_get_with_compression() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__put_and_get_with_compression");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let opts = ClientOptions {
+            compress_threshold: Some(1024),
+            ..Default::default()
+        };
+        let client = c.app_client_with_options(opts).await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        let small = b"value".to_vec();
+        co.put(b"small".to_vec(), small.clone()).await.unwrap();
+        assert_eq!(co.get(b"small".to_vec()).await.unwrap(), Some(small));
+
+        let large = vec![7u8; 64 << 10];
+        co.put(b"large".to_vec(), large.clone()).await.unwrap();
+        assert_eq!(co.get(b"large".to_vec()).await.unwrap(), Some(large));
+    });
+}
+
+#[test]
+fn read_through_client_cache() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__read_through_client_cache");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let opts = ClientOptions {
+            cache_capacity: Some(16),
+            cache_ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let client = c.app_client_with_options(opts).await;
+        let other = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+        let other_co = other
+            .open_database("test_db".to_string())
+            .await
+            .unwrap()
+            .open_collection("test_co".to_string())
+            .await
+            .unwrap();
+
+        let key = b"key".to_vec();
+        co.put(key.clone(), b"v1".to_vec()).await.unwrap();
+        assert_eq!(co.get(key.clone()).await.unwrap(), Some(b"v1".to_vec()));
+
+        // The writes of the same client invalidate the cache.
+        co.put(key.clone(), b"v2".to_vec()).await.unwrap();
+        assert_eq!(co.get(key.clone()).await.unwrap(), Some(b"v2".to_vec()));
+
+        // The writes of other clients are observed after invalidating.
+        other_co.put(key.clone(), b"v3".to_vec()).await.unwrap();
+        assert_eq!(co.get(key.clone()).await.unwrap(), Some(b"v2".to_vec()));
+        co.invalidate(&key);
+        assert_eq!(co.get(key.clone()).await.unwrap(), Some(b"v3".to_vec()));
+
+        // Caching is disabled for the collection with zero ttl.
+        let co = co.with_cache_ttl(Duration::ZERO);
+        other_co.delete(key.clone()).await.unwrap();
+        assert_eq!(co.get(key.clone()).await.unwrap(), None);
+    });
+}
+
+#[test]
+fn eventual_read_without_zone_replica() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__eventual_read_without_zone_replica");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let opts = ClientOptions {
+            zone: Some("remote".to_owned()),
+            ..Default::default()
+        };
+        let client = c.app_client_with_options(opts).await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        // There is no replica in the zone of client, the reads fall back to the leaders.
+        let key = b"key".to_vec();
+        co.put(key.clone(), b"value".to_vec()).await.unwrap();
+        for consistency in [ReadConsistency::Strong, ReadConsistency::Eventual] {
+            let value = co
+                .get_with_consistency(key.clone(), consistency)
+                .await
+                .unwrap();
+            assert_eq!(value, Some(b"value".to_vec()));
+        }
+    });
+}
+
+#[test]
+fn watch_key_and_prefix_changes() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__watch_key_and_prefix_changes");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Range))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        co.put(b"config/a".to_vec(), b"1".to_vec()).await.unwrap();
+        let mut key_watcher = co.watch(b"config/a".to_vec()).unwrap();
+        let mut prefix_watcher = co.watch_prefix(b"config/".to_vec()).unwrap();
+
+        // The current values are emitted first.
+        let event = key_watcher.next().await.unwrap().unwrap();
+        assert_eq!(event.key, b"config/a".to_vec());
+        assert_eq!(event.value, Some(b"1".to_vec()));
+        let event = prefix_watcher.next().await.unwrap().unwrap();
+        assert_eq!(event.value, Some(b"1".to_vec()));
+
+        co.put(b"other".to_vec(), b"x".to_vec()).await.unwrap();
+        co.put(b"config/a".to_vec(), b"2".to_vec()).await.unwrap();
+        let event = key_watcher.next().await.unwrap().unwrap();
+        assert_eq!(event.value, Some(b"2".to_vec()));
+        let event = prefix_watcher.next().await.unwrap().unwrap();
+        assert_eq!(event.value, Some(b"2".to_vec()));
+
+        co.put(b"config/b".to_vec(), b"3".to_vec()).await.unwrap();
+        let event = prefix_watcher.next().await.unwrap().unwrap();
+        assert_eq!(event.key, b"config/b".to_vec());
+        assert_eq!(event.value, Some(b"3".to_vec()));
+
+        co.delete(b"config/a".to_vec()).await.unwrap();
+        let event = key_watcher.next().await.unwrap().unwrap();
+        assert_eq!(event.key, b"config/a".to_vec());
+        assert_eq!(event.value, None);
+        let event = prefix_watcher.next().await.unwrap().unwrap();
+        assert_eq!(event.key, b"config/a".to_vec());
+        assert_eq!(event.value, None);
+
+        // The watcher is woken by a change made after it has returned pending.
+        let write = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            co.put(b"config/a".to_vec(), b"4".to_vec()).await.unwrap();
+        };
+        let watch = tokio::time::timeout(Duration::from_secs(10), key_watcher.next());
+        let (event, _) = futures::join!(watch, write);
+        let event = event.expect("watcher is woken").unwrap().unwrap();
+        assert_eq!(event.value, Some(b"4".to_vec()));
+    });
+}
+
+#[test]
+fn scan_collection_by_partitions() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__scan_collection_by_partitions");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        let mut expect = Vec::new();
+        for i in 0..100u32 {
+            let key = format!("key-{i:03}").into_bytes();
+            let value = format!("value-{i}").into_bytes();
+            co.put(key.clone(), value.clone()).await.unwrap();
+            expect.push((key, value));
+        }
+
+        let partitions = co.scan_partitions().await.unwrap();
+        assert_eq!(partitions.len(), 3);
+        for (idx, partition) in partitions.iter().enumerate() {
+            assert_eq!(partition.slot, Some(idx as u32));
+            assert!(partition.range.is_none());
+            assert!(partition.leader_addr.is_some());
+        }
+
+        let tasks = partitions.iter().map(|partition| {
+            let co = co.clone();
+            async move {
+                let mut kvs = Vec::new();
+                let mut stream = Box::pin(co.scan_partition(partition).await?);
+                while let Some(chunk) = stream.next().await {
+                    kvs.extend(chunk?);
+                }
+                Ok::<_, AppError>(kvs)
+            }
+        });
+        let mut got = futures::future::try_join_all(tasks)
+            .await
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        got.sort_unstable();
+        assert_eq!(got, expect);
+    });
+}
+
+#[test]
+fn sample_collection() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__sample_collection");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        assert!(co.sample(10).await.unwrap().is_empty());
+        for i in 0..100u32 {
+            let key = format!("key-{i:03}").into_bytes();
+            co.put(key, i.to_le_bytes().to_vec()).await.unwrap();
+        }
+
+        let samples = co.sample(10).await.unwrap();
+        assert_eq!(samples.len(), 10);
+        for (key, value) in &samples {
+            let i = u32::from_le_bytes(value.as_slice().try_into().unwrap());
+            assert_eq!(key, &format!("key-{i:03}").into_bytes());
+        }
+        assert_eq!(co.sample(1000).await.unwrap().len(), 100);
+    });
+}
+
+#[test]
+fn batch_get_and_write_across_groups() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__batch_get_and_write_across_groups");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        let mut writes = (0..20u32)
+            .map(|i| BatchWriteOp::Put(format!("key-{i}").into_bytes(), i.to_le_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        writes.push(BatchWriteOp::Delete(b"key-0".to_vec()));
+        writes.push(BatchWriteOp::Put(vec![0; 128 << 10], vec![]));
+        let outcomes = co
+            .batch_write(writes.clone(), BatchOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(outcomes.len(), writes.len());
+        assert!(outcomes[..21]
+            .iter()
+            .all(|o| matches!(o, KeyOutcome::Written)));
+        assert!(matches!(
+            outcomes[21],
+            KeyOutcome::Failed(AppError::KeyTooLarge(..))
+        ));
+
+        let opts = BatchOptions {
+            all_or_nothing: true,
+        };
+        assert!(matches!(
+            co.batch_write(writes, opts.clone()).await,
+            Err(AppError::KeyTooLarge(..))
+        ));
+
+        let keys = (0..21u32)
+            .map(|i| format!("key-{i}").into_bytes())
+            .collect::<Vec<_>>();
+        let outcomes = co.batch_get(keys, opts).await.unwrap();
+        // The delete is the last write of the key.
+        assert!(matches!(outcomes[0], KeyOutcome::NotFound));
+        for (i, outcome) in outcomes.iter().enumerate().take(20).skip(1) {
+            match outcome {
+                KeyOutcome::Value(value) => assert_eq!(value, &(i as u32).to_le_bytes().to_vec()),
+                _ => panic!("unexpected outcome of key {i}: {outcome:?}"),
+            }
+        }
+        assert!(matches!(outcomes[20], KeyOutcome::NotFound));
+
+        let outcomes = co
+            .batch()
+            .put(b"key-0".to_vec(), b"value".to_vec())
+            .get(b"key-1".to_vec())
+            .delete(b"key-2".to_vec())
+            .get(b"key-20".to_vec())
+            .execute()
+            .await
+            .unwrap();
+        assert!(matches!(
+            outcomes.as_slice(),
+            [
+                KeyOutcome::Written,
+                KeyOutcome::Value(_),
+                KeyOutcome::Written,
+                KeyOutcome::NotFound
+            ]
+        ));
+        assert_eq!(co.get(b"key-2".to_vec()).await.unwrap(), None);
+    });
+}
+
+#[test]
+fn access_data_by_http_proxy() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__access_data_by_http_proxy");
+        ctx.disable_all_balance();
+        ctx.enable_proxy_service();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let addr = nodes.get(&0).unwrap().clone();
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        let url = format!("http://{addr}/v1/db/test_db/collection/test_co/key/a%00b");
+        let http = reqwest::Client::new();
+        let resp = http.put(&url).body("value").send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            co.get(b"a\x00b".to_vec()).await.unwrap(),
+            Some(b"value".to_vec())
+        );
+
+        let resp = http.get(&url).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        assert_eq!(resp.bytes().await.unwrap().as_ref(), b"value");
+
+        let resp = http.delete(&url).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        let resp = http.get(&url).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let url = format!("http://{addr}/v1/db/test_db/collection/not_exists/key/a");
+        let resp = http.get(&url).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    });
+}
+
+#[test]
+fn reject_oversized_key_or_value() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__reject_oversized_key_or_value");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection_with_limits("test_co".to_string(), None, 8, 16)
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        co.put(b"12345678".to_vec(), vec![0u8; 16]).await.unwrap();
+        assert!(matches!(
+            co.put(b"123456789".to_vec(), b"value".to_vec()).await,
+            Err(AppError::KeyTooLarge(9, 8))
+        ));
+        assert!(matches!(
+            co.put(b"key".to_vec(), vec![0u8; 17]).await,
+            Err(AppError::ValueTooLarge(17, 16))
+        ));
+        assert!(matches!(
+            co.get(b"123456789".to_vec()).await,
+            Err(AppError::KeyTooLarge(9, 8))
+        ));
+
+        let opts = ClientOptions {
+            max_batch_size: Some(32),
+            ..Default::default()
+        };
+        let client = c.app_client_with_options(opts).await;
+        let db = client.open_database("test_db".to_string()).await.unwrap();
+        let co = db.open_collection("test_co".to_string()).await.unwrap();
+        let writes = (0..3u8)
+            .map(|i| BatchWriteOp::Put(vec![i; 8], vec![i; 8]))
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            co.batch_write(writes, BatchOptions::default()).await,
+            Err(AppError::BatchTooLarge(48, 32))
+        ));
+    });
+}
+
+#[test]
+fn put_with_write_concerns() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__put_with_write_concerns");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), None)
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        for (i, concern) in [
+            WriteConcern::Committed,
+            WriteConcern::Applied,
+            WriteConcern::Flushed,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let key = format!("key-{i}").into_bytes();
+            let resp = co
+                .put_with_concern(key.clone(), b"value".to_vec(), concern)
+                .await
+                .unwrap();
+            assert_ne!(resp.fencing_token, 0);
+            assert_eq!(co.get(key).await.unwrap(), Some(b"value".to_vec()));
+        }
+    });
+}
+
+#[test]
+fn put_with_fencing_condition() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__put_with_fencing_condition");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), None)
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        let first = co
+            .put_with_condition(b"key".to_vec(), b"1".to_vec(), WriteCondition::default())
+            .await
+            .unwrap();
+        assert_ne!(first.epoch, 0);
+        let second = co
+            .put_with_condition(
+                b"key".to_vec(),
+                b"2".to_vec(),
+                WriteCondition {
+                    epoch_at_least: first.epoch,
+                    group_id: first.group_id,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(second.fencing_token > first.fencing_token);
+
+        assert_eq!(second.group_id, first.group_id);
+
+        let condition = WriteCondition {
+            epoch_at_least: second.epoch + 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            co.put_with_condition(b"key".to_vec(), b"3".to_vec(), condition)
+                .await,
+            Err(AppError::ConditionNotMet(_))
+        ));
+
+        // The tokens issued by other groups are not comparable.
+        let condition = WriteCondition {
+            group_id: second.group_id + 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            co.put_with_condition(b"key".to_vec(), b"3".to_vec(), condition)
+                .await,
+            Err(AppError::ConditionNotMet(_))
+        ));
+        assert_eq!(co.get(b"key".to_vec()).await.unwrap(), Some(b"2".to_vec()));
+    });
+}
+
+#[test]
+fn read_after_restarting_servers() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__read_after_restarting_servers");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes.clone()).await;
+        let client = c.app_client().await;
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+        co.put(b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+        for node_id in nodes.keys() {
+            let addr = ctx.restart_server(*node_id).await;
+            assert_eq!(&addr, nodes.get(node_id).unwrap());
+        }
+        assert_eq!(
+            co.get(b"key".to_vec()).await.unwrap(),
+            Some(b"value".to_vec())
+        );
+    });
+}
+
+#[test]
+fn standalone_mode_without_raft_and_root() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("client_test__standalone_mode_without_raft_and_root");
+        ctx.enable_standalone();
+        let addr = ctx.next_listen_address();
+        ctx.spawn_server(0, &addr, false, vec![]);
+        node_client_with_retry(&addr).await;
+
+        let client = EngulaClient::new(ClientOptions::default(), vec![addr.clone()])
+            .await
+            .unwrap();
+        let db = client.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        assert!(matches!(
+            db.create_collection("test_co".to_string(), None).await,
+            Err(AppError::AlreadyExists(_))
+        ));
+
+        co.put(b"k1".to_vec(), b"v1".to_vec()).await.unwrap();
+        co.put(b"k2".to_vec(), b"v2".to_vec()).await.unwrap();
+        co.delete(b"k2".to_vec()).await.unwrap();
+        assert_eq!(co.get(b"k1".to_vec()).await.unwrap(), Some(b"v1".to_vec()));
+        assert_eq!(co.get(b"k2".to_vec()).await.unwrap(), None);
+
+        // The catalog and the data are persisted across restarting.
+        ctx.restart_server(0).await;
+        let client = EngulaClient::new(ClientOptions::default(), vec![addr])
+            .await
+            .unwrap();
+        let db = client.open_database("test_db".to_string()).await.unwrap();
+        let co = db.open_collection("test_co".to_string()).await.unwrap();
+        assert_eq!(co.get(b"k1".to_vec()).await.unwrap(), Some(b"v1".to_vec()));
+
+        db.delete_collection("test_co".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(co.get(b"k1".to_vec()).await.unwrap(), None);
+    });
+}
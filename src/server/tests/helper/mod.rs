@@ -12,8 +12,4 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-pub mod client;
-pub mod context;
-pub mod init;
-pub mod runtime;
-pub mod socket;
+pub use engula_testkit::{client, context, init, runtime, socket};
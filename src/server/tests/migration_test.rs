@@ -13,14 +13,12 @@
 // limitations under the License.
 mod helper;
 
-use std::time::Duration;
-
 use engula_api::{
     server::v1::{group_request_union::Request, group_response_union::Response, *},
     v1::{GetRequest, GetResponse, PutRequest},
 };
 use engula_client::RetryState;
-use tracing::{debug, error, info, warn};
+use tracing::{error, info};
 
 use crate::helper::{client::*, context::*, init::setup_panic_hook, runtime::*};
 
@@ -30,68 +28,6 @@ fn init() {
     tracing_subscriber::fmt::init();
 }
 
-async fn is_not_in_migration(c: &ClusterClient, dest_group_id: u64) -> bool {
-    use collect_migration_state_response::State;
-    if let Some(leader_node_id) = c.get_group_leader_node_id(dest_group_id).await {
-        debug!("group {dest_group_id} node {leader_node_id} collect migration state",);
-        if let Ok(resp) = c
-            .collect_migration_state(dest_group_id, leader_node_id)
-            .await
-        {
-            debug!(
-                "group {dest_group_id} node {leader_node_id} collect migration state: {:?}",
-                resp.state
-            );
-            if resp.state == State::None as i32 {
-                // migration is finished or aborted.
-                return true;
-            }
-        }
-    }
-    false
-}
-
-async fn move_shard(
-    c: &ClusterClient,
-    shard_desc: &ShardDesc,
-    dest_group_id: u64,
-    src_group_id: u64,
-) {
-    'OUTER: for _ in 0..16 {
-        let src_group_epoch = c.must_group_epoch(src_group_id).await;
-
-        // Shard migration is finished.
-        if c.group_contains_shard(dest_group_id, shard_desc.id) {
-            return;
-        }
-
-        let mut g = c.group(dest_group_id);
-        if let Err(e) = g
-            .accept_shard(src_group_id, src_group_epoch, shard_desc)
-            .await
-        {
-            warn!(
-                "accept shard {} from {src_group_id} to {dest_group_id} with src epoch {src_group_epoch}: {e:?}",
-                shard_desc.id
-            );
-            tokio::time::sleep(Duration::from_millis(10)).await;
-            continue;
-        }
-
-        tokio::time::sleep(Duration::from_millis(10)).await;
-        for _ in 0..1000 {
-            if is_not_in_migration(c, dest_group_id).await {
-                continue 'OUTER;
-            }
-            tokio::time::sleep(Duration::from_millis(10)).await;
-        }
-
-        panic!("migration task is timeout");
-    }
-
-    panic!("move shard is failed after 16 retries");
-}
-
 async fn insert(c: &ClusterClient, group_id: u64, shard_id: u64, range: std::ops::Range<u64>) {
     let mut c = c.group(group_id);
     for i in range {
@@ -100,6 +36,7 @@ async fn insert(c: &ClusterClient, group_id: u64, shard_id: u64, range: std::ops
         let put = PutRequest {
             key: key.as_bytes().to_vec(),
             value: value.as_bytes().to_vec(),
+            ..Default::default()
         };
         let req = Request::Put(ShardPutRequest {
             shard_id,
@@ -186,7 +123,7 @@ fn single_replica_empty_shard_migration() {
             shard_id, group_id_2
         );
 
-        move_shard(&c, &shard_desc, group_id_2, group_id_1).await;
+        c.move_shard(&shard_desc, group_id_2, group_id_1).await;
     });
 }
 
@@ -261,7 +198,7 @@ fn single_replica_migration() {
             shard_id, group_id_2
         );
 
-        move_shard(&c, &shard_desc, group_id_2, group_id_1).await;
+        c.move_shard(&shard_desc, group_id_2, group_id_1).await;
     });
 }
 
@@ -338,7 +275,7 @@ fn basic_migration() {
             shard_id, group_id_2
         );
 
-        move_shard(&c, &shard_desc, group_id_2, group_id_1).await;
+        c.move_shard(&shard_desc, group_id_2, group_id_1).await;
     });
 }
 
@@ -407,7 +344,7 @@ fn migration_with_offline_peers() {
         ctx.stop_server(*node_ids.last().unwrap()).await;
         ctx.wait_election_timeout().await;
 
-        move_shard(&c, &shard_desc, group_id_2, group_id_1).await;
+        c.move_shard(&shard_desc, group_id_2, group_id_1).await;
     });
 }
 
@@ -566,7 +503,7 @@ fn receive_forward_request_after_shard_migrated() {
 
         c.assert_group_leader(group_id_1).await;
         c.assert_group_leader(group_id_2).await;
-        move_shard(&c, &shard_desc, group_id_2, group_id_1).await;
+        c.move_shard(&shard_desc, group_id_2, group_id_1).await;
 
         let mut group_client = c.group(group_id_2);
         let req = ForwardRequest {
@@ -583,6 +520,7 @@ fn receive_forward_request_after_shard_migrated() {
                     put: Some(PutRequest {
                         key: b"b".to_vec(),
                         value: b"value".to_vec(),
+                        ..Default::default()
                     }),
                 })),
             }),
@@ -105,6 +105,96 @@ fn cluster_put_many_keys() {
     });
 }
 
+#[test]
+fn cluster_delete_range() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("rw_test__cluster_delete_range");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let app = c.app_client().await;
+
+        let db = app.create_database("test_db".to_string()).await.unwrap();
+        for (name, partition) in [
+            ("range_co", None),
+            ("hash_co", Some(Partition::Hash { slots: 3 })),
+        ] {
+            let co = db
+                .create_collection(name.to_string(), partition)
+                .await
+                .unwrap();
+            c.assert_collection_ready(&co.desc()).await;
+
+            for i in 0..100 {
+                let k = format!("key-{i:03}").as_bytes().to_vec();
+                co.put(k, b"value".to_vec()).await.unwrap();
+            }
+            co.delete_range(b"key-010".to_vec()..b"key-090".to_vec())
+                .await
+                .unwrap();
+            for i in 0..100 {
+                let k = format!("key-{i:03}").as_bytes().to_vec();
+                let deleted = (10..90).contains(&i);
+                assert_eq!(
+                    co.get(k).await.unwrap().is_none(),
+                    deleted,
+                    "collection {name} key {i}"
+                );
+            }
+
+            co.delete_range(b"key-".to_vec()..vec![]).await.unwrap();
+            for i in 0..100 {
+                let k = format!("key-{i:03}").as_bytes().to_vec();
+                assert!(co.get(k).await.unwrap().is_none());
+            }
+        }
+    });
+}
+
+#[test]
+fn cluster_rename() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("rw_test__cluster_rename");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let app = c.app_client().await;
+
+        let db = app.create_database("test_db".to_string()).await.unwrap();
+        let co = db
+            .create_collection("test_co".to_string(), None)
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        co.put(b"a".to_vec(), b"1".to_vec()).await.unwrap();
+        co.put(b"b".to_vec(), b"2".to_vec()).await.unwrap();
+
+        // The target is exists.
+        assert!(!co.rename(b"a".to_vec(), b"b".to_vec(), true).await.unwrap());
+        assert_eq!(co.get(b"a".to_vec()).await.unwrap(), Some(b"1".to_vec()));
+
+        assert!(co.rename(b"a".to_vec(), b"c".to_vec(), true).await.unwrap());
+        assert!(co.get(b"a".to_vec()).await.unwrap().is_none());
+        assert_eq!(co.get(b"c".to_vec()).await.unwrap(), Some(b"1".to_vec()));
+
+        // The target is overwritten.
+        assert!(co
+            .rename(b"c".to_vec(), b"b".to_vec(), false)
+            .await
+            .unwrap());
+        assert!(co.get(b"c".to_vec()).await.unwrap().is_none());
+        assert_eq!(co.get(b"b".to_vec()).await.unwrap(), Some(b"1".to_vec()));
+
+        // The source is not exists.
+        assert!(!co
+            .rename(b"a".to_vec(), b"d".to_vec(), false)
+            .await
+            .unwrap());
+        assert!(co.get(b"d".to_vec()).await.unwrap().is_none());
+    });
+}
+
 #[test]
 fn operation_with_config_change() {
     block_on_current(async {
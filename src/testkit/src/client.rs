@@ -24,6 +24,7 @@ use engula_client::{
     RouterGroupState, StaticServiceDiscovery,
 };
 use engula_server::{runtime, Result};
+use tracing::{debug, warn};
 
 pub async fn node_client_with_retry(addr: &str) -> NodeClient {
     for _ in 0..10000 {
@@ -391,6 +392,65 @@ impl ClusterClient {
         }
     }
 
+    /// Move shard from the source group to the dest group, and wait until the migration is
+    /// finished.
+    pub async fn move_shard(&self, shard_desc: &ShardDesc, dest_group_id: u64, src_group_id: u64) {
+        'OUTER: for _ in 0..16 {
+            let src_group_epoch = self.must_group_epoch(src_group_id).await;
+
+            // Shard migration is finished.
+            if self.group_contains_shard(dest_group_id, shard_desc.id) {
+                return;
+            }
+
+            let mut g = self.group(dest_group_id);
+            if let Err(e) = g
+                .accept_shard(src_group_id, src_group_epoch, shard_desc)
+                .await
+            {
+                warn!(
+                    "accept shard {} from {src_group_id} to {dest_group_id} with src epoch {src_group_epoch}: {e:?}",
+                    shard_desc.id
+                );
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                continue;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            for _ in 0..1000 {
+                if self.is_not_in_migration(dest_group_id).await {
+                    continue 'OUTER;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            panic!("migration task is timeout");
+        }
+
+        panic!("move shard is failed after 16 retries");
+    }
+
+    pub async fn is_not_in_migration(&self, dest_group_id: u64) -> bool {
+        use collect_migration_state_response::State;
+        if let Some(leader_node_id) = self.get_group_leader_node_id(dest_group_id).await {
+            debug!("group {dest_group_id} node {leader_node_id} collect migration state",);
+            if let Ok(resp) = self
+                .collect_migration_state(dest_group_id, leader_node_id)
+                .await
+            {
+                debug!(
+                    "group {dest_group_id} node {leader_node_id} collect migration state: {:?}",
+                    resp.state
+                );
+                if resp.state == State::None as i32 {
+                    // migration is finished or aborted.
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
     /// Some tests may shut down a server, if root happens to be on that server, and there is only
     /// one replica in root group, then the test will not continue because root group is lost.
     pub async fn assert_root_group_has_promoted(&self) {
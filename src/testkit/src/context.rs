@@ -17,15 +17,17 @@ use engula_server::{
     node::replica::{ReplicaConfig, ReplicaTestingKnobs},
     raftgroup::RaftTestingKnobs,
     runtime::{ExecutorConfig, ExecutorOwner, ShutdownNotifier},
-    Config, DbConfig, NodeConfig, RaftConfig, RootConfig,
+    AuditConfig, Config, DbConfig, NodeConfig, RaftConfig, RootConfig,
 };
 use tempdir::TempDir;
 use tracing::info;
 
-use super::{client::node_client_with_retry, socket::next_n_avail_port};
-use crate::helper::socket::next_avail_port;
+use crate::{
+    client::node_client_with_retry,
+    socket::{next_avail_port, next_n_avail_port},
+};
 
-#[allow(dead_code)]
+/// The context of an in-process cluster, all servers are shutdown once it is dropped.
 pub struct TestContext {
     name: String,
     root_dir: TempDir,
@@ -33,14 +35,18 @@ pub struct TestContext {
     replica_knobs: ReplicaTestingKnobs,
     raft_knobs: RaftTestingKnobs,
     disable_group_promoting: bool,
+    enable_proxy_service: bool,
+    enable_pprof: bool,
+    standalone: bool,
 
     tick_interval_ms: u64,
 
+    /// The listen address, init flag and join list of the spawned servers, used by restarting.
+    servers: HashMap<u64, (String, bool, Vec<String>)>,
     notifiers: HashMap<u64, ShutdownNotifier>,
     handles: HashMap<u64, std::thread::JoinHandle<()>>,
 }
 
-#[allow(dead_code)]
 impl TestContext {
     pub fn new(prefix: &str) -> Self {
         let root_dir = TempDir::new(prefix).unwrap();
@@ -48,10 +54,14 @@ impl TestContext {
             name: prefix.to_owned(),
             root_dir,
             disable_group_promoting: false,
+            enable_proxy_service: false,
+            enable_pprof: false,
+            standalone: false,
             replica_knobs: ReplicaTestingKnobs::default(),
             raft_knobs: RaftTestingKnobs::default(),
             root_cfg: RootConfig::default(),
             tick_interval_ms: 500,
+            servers: HashMap::default(),
             notifiers: HashMap::default(),
             handles: HashMap::default(),
         }
@@ -108,18 +118,29 @@ impl TestContext {
         self.disable_group_balance();
     }
 
+    pub fn enable_proxy_service(&mut self) {
+        self.enable_proxy_service = true;
+    }
+
+    pub fn enable_pprof(&mut self) {
+        self.enable_pprof = true;
+    }
+
+    /// The servers spawned later serve in standalone mode, see `Config::standalone`.
+    pub fn enable_standalone(&mut self) {
+        self.standalone = true;
+    }
+
     pub fn disable_all_node_scheduler(&mut self) {
         self.replica_knobs.disable_scheduler_durable_task = true;
         self.replica_knobs
             .disable_scheduler_remove_orphan_replica_task = true;
     }
 
-    #[allow(dead_code)]
     pub fn spawn_server(&mut self, idx: usize, addr: &str, init: bool, join_list: Vec<String>) {
         self.spawn_server_with_cfg(idx, addr, 2, init, join_list, self.root_cfg.clone());
     }
 
-    #[allow(dead_code)]
     pub fn spawn_server_with_cfg(
         &mut self,
         idx: usize,
@@ -130,6 +151,8 @@ impl TestContext {
         root: RootConfig,
     ) {
         let addr = addr.to_owned();
+        self.servers
+            .insert(idx as u64, (addr.clone(), init, join_list.clone()));
         let name = idx.to_string();
         let root_dir = self.root_dir.path().join(name);
         let cfg = Config {
@@ -137,8 +160,13 @@ impl TestContext {
             addr,
             cpu_nums,
             init,
-            enable_proxy_service: false,
+            standalone: self.standalone,
+            enable_proxy_service: self.enable_proxy_service,
+            proxy_auth_tokens: vec![],
+            pgwire_addr: String::new(),
+            enable_pprof: self.enable_pprof,
             join_list,
+            graceful_shutdown_seconds: 0,
             node: NodeConfig {
                 replica: ReplicaConfig {
                     testing_knobs: self.replica_knobs.clone(),
@@ -154,6 +182,7 @@ impl TestContext {
             root,
             executor: ExecutorConfig::default(),
             db: DbConfig::default(),
+            audit: AuditConfig::default(),
         };
         let notifier = ShutdownNotifier::new();
         let shutdown = notifier.subscribe();
@@ -168,7 +197,6 @@ impl TestContext {
     }
 
     /// Create a set of servers and bootstrap all of them.
-    #[allow(dead_code)]
     pub async fn bootstrap_servers(&mut self, num_server: usize) -> HashMap<u64, String> {
         let nodes = self
             .next_n_listen_addrs(num_server)
@@ -218,6 +246,21 @@ impl TestContext {
         }
     }
 
+    /// Restart a stopped (or running) server with the same address and data, the cluster
+    /// membership is preserved.
+    pub async fn restart_server(&mut self, id: u64) -> String {
+        let (addr, init, join_list) = self
+            .servers
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| panic!("server {id} is never spawned"));
+        self.stop_server(id).await;
+        info!("{} restart server {id}", self.name);
+        self.spawn_server(id as usize, &addr, init, join_list);
+        node_client_with_retry(&addr).await;
+        addr
+    }
+
     pub async fn wait_election_timeout(&self) {
         tokio::time::sleep(Duration::from_millis(self.tick_interval_ms * 6)).await;
     }
@@ -0,0 +1,28 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A harness to run in-process Engula clusters for end-to-end tests.
+//!
+//! [`context::TestContext`] spawns, stops and restarts servers listening on random local ports,
+//! and [`client::ClusterClient`] inspects and drives the cluster, eg. waiting for group leaders
+//! or moving shards between groups.
+
+pub mod client;
+pub mod context;
+pub mod init;
+pub mod runtime;
+pub mod socket;
+
+pub use client::ClusterClient;
+pub use context::TestContext;